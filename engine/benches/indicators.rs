@@ -0,0 +1,73 @@
+//! Indicator kernel timings on a long minute-bar series.
+//!
+//! `cargo bench --bench indicators` (set `BENCH_BARS` to change the size).
+//! Each kernel is timed against the per-window loop it replaced.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use engine_core::kernels;
+
+fn series(n: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let mut x = 0x9E3779B97F4A7C15u64;
+    let mut p = 20_000.0;
+    let closes: Vec<f64> = (0..n).map(|_| {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        p *= 1.0 + ((x % 2001) as f64 - 1000.0) / 200_000.0;
+        p
+    }).collect();
+    let highs = closes.iter().map(|c| c * 1.0007).collect();
+    let lows = closes.iter().map(|c| c * 0.9993).collect();
+    (highs, lows, closes)
+}
+
+fn time<T>(runs: u32, mut f: impl FnMut() -> T) -> Duration {
+    black_box(f());
+    let start = Instant::now();
+    for _ in 0..runs {
+        black_box(f());
+    }
+    start.elapsed() / runs
+}
+
+fn report(name: &str, bars: usize, new: Duration, old: Option<Duration>) {
+    let ns = |d: Duration| d.as_nanos() as f64 / bars as f64;
+    match old {
+        Some(old) => println!("{:<22} {:>8.2} ns/bar   (per-window loop {:>8.2} ns/bar, {:.1}x)", name, ns(new), ns(old), old.as_secs_f64() / new.as_secs_f64()),
+        None => println!("{:<22} {:>8.2} ns/bar", name, ns(new)),
+    }
+}
+
+fn window_mean_std(data: &[f64], period: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut mean = vec![0.0; data.len()];
+    let mut std = vec![0.0; data.len()];
+    for i in period - 1..data.len() {
+        let w = &data[i + 1 - period..=i];
+        let m = w.iter().sum::<f64>() / period as f64;
+        mean[i] = m;
+        std[i] = (w.iter().map(|x| (x - m).powi(2)).sum::<f64>() / period as f64).sqrt();
+    }
+    (mean, std)
+}
+
+fn window_sma(data: &[f64], period: usize) -> Vec<f64> {
+    let mut out = vec![0.0; data.len()];
+    for i in period - 1..data.len() {
+        out[i] = data[i + 1 - period..=i].iter().sum::<f64>() / period as f64;
+    }
+    out
+}
+
+fn main() {
+    let bars: usize = std::env::var("BENCH_BARS").ok().and_then(|v| v.parse().ok()).unwrap_or(1_000_000);
+    let (highs, lows, closes) = series(bars);
+    println!("{} bars", bars);
+
+    report("ema(21)", bars, time(10, || kernels::ema(&closes, 21)), None);
+    report("rsi(14)", bars, time(10, || kernels::rsi(&closes, 14)), None);
+    report("true_range", bars, time(10, || kernels::true_range(&highs, &lows, &closes)), None);
+    report("atr(14)", bars, time(10, || kernels::atr(&highs, &lows, &closes, 14)), None);
+    report("sma(50)", bars, time(10, || kernels::sma(&closes, 50)), Some(time(3, || window_sma(&closes, 50))));
+    report("rolling_mean_std(20)", bars, time(10, || kernels::rolling_mean_std(&closes, 20)), Some(time(3, || window_mean_std(&closes, 20))));
+}
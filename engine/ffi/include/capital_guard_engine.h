@@ -0,0 +1,31 @@
+/*
+ * C interface to the Capital Guard engine (libcapital_guard_engine_ffi).
+ *
+ * Requests and replies use the same JSON envelope as the engine's daemon mode.
+ * All functions are thread-safe.
+ */
+#ifndef CAPITAL_GUARD_ENGINE_H
+#define CAPITAL_GUARD_ENGINE_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Load config from a TOML file (NULL for defaults) before the first request.
+ * Returns 0 on success, 1 on invalid config, 2 if already initialised. */
+int engine_init(const char *config_path);
+
+/* Execute a request or batch; free the result with engine_free_string. */
+char *engine_execute(const char *request_json);
+
+/* Release a string returned by engine_execute. NULL is ignored. */
+void engine_free_string(char *s);
+
+/* Engine version; static storage, do not free. */
+const char *engine_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CAPITAL_GUARD_ENGINE_H */
@@ -0,0 +1,151 @@
+//! C ABI over the engine's JSON protocol.
+//!
+//! Hosts pass the same request JSON they would write to the daemon's stdin
+//! (single request or `{"commands": [...]}` batch) and get the reply JSON
+//! back as a NUL-terminated UTF-8 string owned by the engine:
+//!
+//! ```c
+//! char *reply = engine_execute("{\"id\":\"1\",\"command\":\"version\",\"data\":{}}");
+//! /* ... use reply ... */
+//! engine_free_string(reply);
+//! ```
+//!
+//! Every call is synchronous and thread-safe; state (positions, OMS, caches)
+//! persists across calls for the lifetime of the process.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+
+use engine_core::config::EngineConfig;
+use engine_core::state::AppState;
+use once_cell::sync::OnceCell;
+use serde_json::json;
+
+static STATE: OnceCell<Arc<AppState>> = OnceCell::new();
+
+fn state() -> &'static Arc<AppState> {
+    STATE.get_or_init(|| {
+        let config = EngineConfig::default();
+        let capital = config.initial_capital;
+        AppState::new(config, capital)
+    })
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // Serialized JSON never contains interior NULs; strip defensively anyway.
+    CString::new(s.replace('\0', ""))
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+fn error_reply(msg: &str) -> String {
+    json!({ "id": null, "success": false, "data": null, "error": msg }).to_string()
+}
+
+/// Initialise the engine from a TOML config file. Optional: the first
+/// `engine_execute` otherwise initialises with defaults. Returns 0 on
+/// success, 1 if the config is invalid, 2 if already initialised.
+///
+/// # Safety
+/// `config_path` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn engine_init(config_path: *const c_char) -> i32 {
+    let config = if config_path.is_null() {
+        EngineConfig::default()
+    } else {
+        match CStr::from_ptr(config_path).to_str().map_err(|e| e.to_string()).and_then(EngineConfig::load) {
+            Ok(c) => c,
+            Err(_) => return 1,
+        }
+    };
+    let capital = config.initial_capital;
+    match STATE.set(AppState::new(config, capital)) {
+        Ok(()) => 0,
+        Err(_) => 2,
+    }
+}
+
+/// Execute one request (or batch) and return the reply JSON. The returned
+/// pointer must be released with `engine_free_string`. Never returns NULL
+/// for a non-NULL request; failures are reported as `success: false`.
+///
+/// # Safety
+/// `request_json` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn engine_execute(request_json: *const c_char) -> *mut c_char {
+    if request_json.is_null() {
+        return into_c_string(error_reply("request_json is NULL"));
+    }
+    let input = match CStr::from_ptr(request_json).to_str() {
+        Ok(s) => s,
+        Err(e) => return into_c_string(error_reply(&format!("Request is not valid UTF-8: {}", e))),
+    };
+    into_c_string(execute_str(input))
+}
+
+fn execute_str(input: &str) -> String {
+    let msg = match serde_json::from_str::<serde_json::Value>(input) {
+        Ok(m) => m,
+        Err(e) => return error_reply(&format!("Invalid JSON: {}", e)),
+    };
+    // A panic must not unwind into the host's stack frames.
+    match catch_unwind(AssertUnwindSafe(|| engine_core::handle_message(msg, state()))) {
+        Ok(reply) => serde_json::to_string(&reply).unwrap_or_else(|e| error_reply(&format!("Serialization error: {}", e))),
+        Err(_) => error_reply("Engine panicked while handling the request"),
+    }
+}
+
+/// Release a string returned by `engine_execute`.
+///
+/// # Safety
+/// `s` must be NULL or a pointer previously returned by `engine_execute`,
+/// and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn engine_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Engine version as a static NUL-terminated string; do not free.
+#[no_mangle]
+pub extern "C" fn engine_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(req: &str) -> serde_json::Value {
+        let input = CString::new(req).unwrap();
+        unsafe {
+            let out = engine_execute(input.as_ptr());
+            let text = CStr::from_ptr(out).to_str().unwrap().to_string();
+            engine_free_string(out);
+            serde_json::from_str(&text).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_execute_roundtrip() {
+        let reply = call(r#"{"id":"v1","command":"version","data":{}}"#);
+        assert_eq!(reply["id"], "v1");
+        assert_eq!(reply["success"], true);
+        assert!(reply["data"]["protocol_version"].as_u64().is_some());
+
+        let batch = call(r#"{"commands":[{"id":"a","command":"version","data":{}},{"id":"b","command":"nope","data":{}}]}"#);
+        assert_eq!(batch[1]["success"], false);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert_eq!(call("not json")["success"], false);
+        let out = unsafe { engine_execute(std::ptr::null()) };
+        assert!(!out.is_null());
+        unsafe { engine_free_string(out) };
+        let v = unsafe { CStr::from_ptr(engine_version()) };
+        assert_eq!(v.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+    }
+}
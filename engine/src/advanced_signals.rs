@@ -1,11 +1,138 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use crate::utils::{Candle, round2};
+use crate::indicators;
+use crate::utils::{Candle, parse_timestamp, round2};
 
 #[derive(Deserialize)]
 struct AdvancedSignalConfig {
     candles: Vec<Candle>,
     compute: Vec<String>,
+    #[serde(default)]
+    volume_profile: VolumeProfileParams,
+    #[serde(default)]
+    order_flow: OrderFlowParams,
+    #[serde(default)]
+    market_profile: MarketProfileParams,
+    #[serde(default = "default_rvol_sessions")]
+    rvol_sessions: usize,
+}
+
+fn default_rvol_sessions() -> usize { 10 }
+
+/// The initial balance spans the first `ib_minutes` of the latest session.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+struct MarketProfileParams {
+    ib_minutes: i64,
+}
+
+impl Default for MarketProfileParams {
+    fn default() -> Self {
+        Self { ib_minutes: 60 }
+    }
+}
+
+/// Upper bound on volume profile bins, whether from `tick_size` or `num_levels`.
+const MAX_PROFILE_LEVELS: usize = 10_000;
+
+/// Binning and value-area settings for the volume profile. `tick_size` takes
+/// precedence over `num_levels`; when neither is set the profile falls back to
+/// the adaptive 10–50 bin layout.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+struct VolumeProfileParams {
+    num_levels: Option<usize>,
+    tick_size: Option<f64>,
+    value_area_pct: f64,
+    composites: Vec<CompositeRange>,
+}
+
+impl Default for VolumeProfileParams {
+    fn default() -> Self {
+        Self { num_levels: None, tick_size: None, value_area_pct: 0.7, composites: Vec::new() }
+    }
+}
+
+/// Inclusive timestamp window for a composite profile. Bounds are compared as
+/// strings, so a date-only `end` ("2024-01-05") covers the whole day.
+#[derive(Deserialize, Clone)]
+struct CompositeRange {
+    #[serde(default)]
+    label: Option<String>,
+    start: String,
+    end: String,
+}
+
+/// Optional executed-volume inputs for order flow. `bar_volumes` must align
+/// 1:1 with `candles`; `ticks` are bucketed into the candle whose timestamp
+/// precedes them. Without either, delta is estimated from the candle body.
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+struct OrderFlowParams {
+    bar_volumes: Vec<BarVolume>,
+    ticks: Vec<TradeTick>,
+    footprint_tick_size: f64,
+    imbalance_ratio: f64,
+    stacked_levels: usize,
+    swing_lookback: usize,
+}
+
+impl Default for OrderFlowParams {
+    fn default() -> Self {
+        Self {
+            bar_volumes: Vec::new(),
+            ticks: Vec::new(),
+            footprint_tick_size: 0.05,
+            imbalance_ratio: 3.0,
+            stacked_levels: 3,
+            swing_lookback: 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct BarVolume {
+    buy_volume: f64,
+    sell_volume: f64,
+}
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct TradeTick {
+    pub timestamp: String,
+    pub price: f64,
+    pub size: f64,
+    /// "buy"/"sell" (or "B"/"S"). When absent the tick rule is applied.
+    #[serde(default)]
+    pub side: Option<String>,
+}
+
+/// Aggressor side of each tick in sequence: the explicit `side` when given,
+/// otherwise the tick rule (uptick = buy, downtick = sell, unchanged = same
+/// as the previous tick).
+pub(crate) struct TickClassifier {
+    last_price: Option<f64>,
+    last_is_buy: bool,
+}
+
+impl TickClassifier {
+    pub fn new() -> Self {
+        TickClassifier { last_price: None, last_is_buy: true }
+    }
+
+    pub fn is_buy(&mut self, t: &TradeTick) -> bool {
+        let is_buy = match t.side.as_deref().map(|s| s.to_ascii_lowercase()) {
+            Some(s) if s == "buy" || s == "b" => true,
+            Some(s) if s == "sell" || s == "s" => false,
+            _ => match self.last_price {
+                Some(p) if t.price > p => true,
+                Some(p) if t.price < p => false,
+                _ => self.last_is_buy,
+            },
+        };
+        self.last_price = Some(t.price);
+        self.last_is_buy = is_buy;
+        is_buy
+    }
 }
 
 #[derive(Serialize)]
@@ -14,6 +141,80 @@ struct AdvancedSignalResult {
     volume_profile: Option<VolumeProfileResult>,
     order_flow: Option<OrderFlowResult>,
     market_profile: Option<MarketProfileResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    composite_profiles: Vec<CompositeProfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_profile: Option<SessionProfileResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    liquidity_zones: Option<Vec<LiquidityZone>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rvol: Option<RvolResult>,
+}
+
+#[derive(Serialize)]
+struct RvolResult {
+    current_rvol: Option<f64>,
+    cumulative_rvol: Option<f64>,
+    time_of_day: String,
+    sessions_used: usize,
+    signal: String,
+    series: Vec<RvolPoint>,
+}
+
+#[derive(Serialize)]
+struct RvolPoint {
+    timestamp: String,
+    rvol: Option<f64>,
+}
+
+/// Price zone where heavy volume either failed to move price (absorption) or
+/// launched a directional move (initiative). Ranked by `score`.
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct LiquidityZone {
+    pub price_low: f64,
+    pub price_high: f64,
+    pub mid: f64,
+    pub kind: String,
+    pub zone_type: String,
+    pub events: usize,
+    pub volume: f64,
+    pub score: f64,
+    pub last_timestamp: String,
+}
+
+#[derive(Serialize)]
+struct SessionProfileResult {
+    sessions: Vec<SessionProfile>,
+    naked_pocs: Vec<NakedPoc>,
+    value_area_trend: String,
+}
+
+#[derive(Serialize)]
+struct SessionProfile {
+    session: String,
+    candle_count: usize,
+    high: f64,
+    low: f64,
+    profile: MarketProfileResult,
+    /// Placement of this session's value area relative to the previous one.
+    value_migration: String,
+    poc_naked: bool,
+}
+
+#[derive(Serialize)]
+struct NakedPoc {
+    session: String,
+    poc: f64,
+    distance_pct: f64,
+}
+
+#[derive(Serialize)]
+struct CompositeProfile {
+    label: String,
+    start: String,
+    end: String,
+    candle_count: usize,
+    profile: Option<VolumeProfileResult>,
 }
 
 #[derive(Serialize)]
@@ -64,6 +265,51 @@ struct OrderFlowResult {
     cumulative_delta: f64,
     signal: String,
     recent_deltas: Vec<DeltaPoint>,
+    source: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    footprint: Vec<FootprintBar>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stacked_imbalances: Vec<StackedImbalance>,
+    divergences: Vec<DeltaDivergence>,
+}
+
+/// Price swing that is not confirmed by cumulative delta: a higher swing high
+/// on a lower delta high is BEARISH, a lower swing low on a higher delta low is BULLISH.
+#[derive(Serialize)]
+struct DeltaDivergence {
+    timestamp: String,
+    prev_timestamp: String,
+    kind: String,
+    price: f64,
+    prev_price: f64,
+    cumulative_delta: f64,
+    prev_cumulative_delta: f64,
+    strength: f64,
+}
+
+#[derive(Serialize)]
+struct FootprintBar {
+    timestamp: String,
+    delta: f64,
+    levels: Vec<FootprintLevel>,
+}
+
+#[derive(Serialize)]
+struct FootprintLevel {
+    price: f64,
+    bid_volume: f64,
+    ask_volume: f64,
+    delta: f64,
+    imbalance: Option<String>,
+}
+
+#[derive(Serialize)]
+struct StackedImbalance {
+    timestamp: String,
+    side: String,
+    price_low: f64,
+    price_high: f64,
+    levels: usize,
 }
 
 #[derive(Serialize, Clone)]
@@ -83,6 +329,8 @@ struct MarketProfileResult {
     profile_type: String,
     tpo_count: usize,
     signal: String,
+    ib_method: String,
+    ib_candles: usize,
 }
 
 pub fn compute(data: Value) -> Result<Value, String> {
@@ -103,47 +351,98 @@ pub fn compute(data: Value) -> Result<Value, String> {
         Some(compute_vwap(&config.candles))
     } else { None };
 
-    let volume_profile = if computes.iter().any(|c| c == "volume_profile") {
-        Some(compute_volume_profile(&config.candles))
+    let vp_params = &config.volume_profile;
+    if !(vp_params.value_area_pct > 0.0 && vp_params.value_area_pct <= 1.0) {
+        return Err("value_area_pct must be in (0, 1]".to_string());
+    }
+    if vp_params.tick_size.is_some_and(|t| t <= 0.0 || !t.is_finite()) {
+        return Err("tick_size must be positive".to_string());
+    }
+
+    let want_vp = computes.iter().any(|c| c == "volume_profile");
+    if want_vp {
+        // Composites are subsets, so the full range bounds their bins too.
+        let low = config.candles.iter().map(|c| c.low).fold(f64::INFINITY, f64::min);
+        let high = config.candles.iter().map(|c| c.high).fold(f64::NEG_INFINITY, f64::max);
+        let levels = match (vp_params.tick_size, vp_params.num_levels) {
+            (Some(tick), _) => ((high - low) / tick).ceil(),
+            (None, Some(n)) => n as f64,
+            (None, None) => 0.0,
+        };
+        if levels > MAX_PROFILE_LEVELS as f64 {
+            return Err(format!(
+                "volume_profile would need {:.0} price levels (max {}); use a larger tick_size or fewer num_levels",
+                levels, MAX_PROFILE_LEVELS
+            ));
+        }
+    }
+    let volume_profile = if want_vp {
+        Some(compute_volume_profile(&config.candles, vp_params))
     } else { None };
 
+    let composite_profiles = if want_vp {
+        vp_params.composites.iter().map(|r| {
+            let subset: Vec<Candle> = config.candles.iter()
+                .filter(|c| in_range(&c.timestamp, &r.start, &r.end))
+                .cloned()
+                .collect();
+            CompositeProfile {
+                label: r.label.clone().unwrap_or_else(|| format!("{}..{}", r.start, r.end)),
+                start: r.start.clone(),
+                end: r.end.clone(),
+                candle_count: subset.len(),
+                profile: if subset.is_empty() { None } else { Some(compute_volume_profile(&subset, vp_params)) },
+            }
+        }).collect()
+    } else { Vec::new() };
+
+    let of_params = &config.order_flow;
+    if !of_params.bar_volumes.is_empty() && of_params.bar_volumes.len() != config.candles.len() {
+        return Err(format!(
+            "bar_volumes length {} does not match candles length {}",
+            of_params.bar_volumes.len(), config.candles.len()
+        ));
+    }
+    if of_params.footprint_tick_size <= 0.0 || !of_params.footprint_tick_size.is_finite() {
+        return Err("footprint_tick_size must be positive".to_string());
+    }
+
     let order_flow = if computes.iter().any(|c| c == "order_flow") {
-        Some(compute_order_flow(&config.candles))
+        Some(compute_order_flow(&config.candles, of_params))
     } else { None };
 
     let market_profile = if computes.iter().any(|c| c == "market_profile") {
-        Some(compute_market_profile(&config.candles))
+        Some(compute_market_profile(&config.candles, config.market_profile.ib_minutes))
     } else { None };
 
-    let result = AdvancedSignalResult { vwap, volume_profile, order_flow, market_profile };
-    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
-}
+    let session_profile = if computes.iter().any(|c| c == "session_profile") {
+        Some(compute_session_profiles(&config.candles, config.market_profile.ib_minutes))
+    } else { None };
 
-fn compute_vwap(candles: &[Candle]) -> VWAPResult {
-    let mut cum_tp_vol = 0.0;
-    let mut cum_vol = 0.0;
-    let mut cum_tp2_vol = 0.0;
-    let mut series = Vec::with_capacity(candles.len());
+    let liquidity_zones = if computes.iter().any(|c| c == "liquidity_zones") {
+        let (_, flows, _) = resolve_flows(&config.candles, of_params);
+        Some(detect_liquidity_zones(&config.candles, &flows, 20))
+    } else { None };
 
-    for c in candles {
-        let tp = (c.high + c.low + c.close) / 3.0;
-        cum_tp_vol += tp * c.volume;
-        cum_vol += c.volume;
-        cum_tp2_vol += tp * tp * c.volume;
+    let rvol = if computes.iter().any(|c| c == "rvol") {
+        Some(compute_rvol(&config.candles, config.rvol_sessions))
+    } else { None };
 
-        let vwap = if cum_vol > 0.0 { cum_tp_vol / cum_vol } else { tp };
-        let variance = if cum_vol > 0.0 {
-            (cum_tp2_vol / cum_vol - vwap * vwap).max(0.0)
-        } else { 0.0 };
-        let std = variance.sqrt();
+    let result = AdvancedSignalResult {
+        vwap, volume_profile, order_flow, market_profile, composite_profiles, session_profile,
+        liquidity_zones, rvol,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
 
-        series.push(VWAPPoint {
-            timestamp: c.timestamp.clone(),
-            vwap: round2(vwap),
-            upper1: round2(vwap + std),
-            lower1: round2(vwap - std),
-        });
-    }
+fn compute_vwap(candles: &[Candle]) -> VWAPResult {
+    let (vwaps, stds) = indicators::session_vwap_bands(candles);
+    let series: Vec<VWAPPoint> = candles.iter().zip(vwaps.iter().zip(&stds)).map(|(c, (vwap, std))| VWAPPoint {
+        timestamp: c.timestamp.clone(),
+        vwap: round2(*vwap),
+        upper1: round2(vwap + std),
+        lower1: round2(vwap - std),
+    }).collect();
 
     let last_vwap = series.last().map(|s| s.vwap).unwrap_or(0.0);
     let last_upper = series.last().map(|s| s.upper1).unwrap_or(0.0);
@@ -170,7 +469,11 @@ fn compute_vwap(candles: &[Candle]) -> VWAPResult {
     }
 }
 
-fn compute_volume_profile(candles: &[Candle]) -> VolumeProfileResult {
+fn in_range(ts: &str, start: &str, end: &str) -> bool {
+    ts >= start && (ts <= end || ts.starts_with(end))
+}
+
+fn compute_volume_profile(candles: &[Candle], params: &VolumeProfileParams) -> VolumeProfileResult {
     let min_price = candles.iter().map(|c| c.low).fold(f64::INFINITY, f64::min);
     let max_price = candles.iter().map(|c| c.high).fold(f64::NEG_INFINITY, f64::max);
     let range = max_price - min_price;
@@ -183,8 +486,20 @@ fn compute_volume_profile(candles: &[Candle]) -> VolumeProfileResult {
         };
     }
 
-    let num_levels = 50.min((range / 0.5).ceil() as usize).max(10);
-    let step = range / num_levels as f64;
+    let (num_levels, step) = match (params.tick_size, params.num_levels) {
+        (Some(tick), _) => {
+            let n = ((range / tick).ceil() as usize).max(1);
+            (n, tick)
+        }
+        (None, Some(n)) => {
+            let n = n.max(1);
+            (n, range / n as f64)
+        }
+        (None, None) => {
+            let n = 50.min((range / 0.5).ceil() as usize).max(10);
+            (n, range / n as f64)
+        }
+    };
     let mut volumes = vec![0.0f64; num_levels];
     let total_vol: f64 = candles.iter().map(|c| c.volume).sum();
 
@@ -200,7 +515,7 @@ fn compute_volume_profile(candles: &[Candle]) -> VolumeProfileResult {
         .map(|(i, _)| i).unwrap_or(0);
     let poc_price = min_price + (poc_idx as f64 + 0.5) * step;
 
-    let va_target = total_vol * 0.7;
+    let va_target = total_vol * params.value_area_pct;
     let mut va_vol = volumes[poc_idx];
     let mut va_low_idx = poc_idx;
     let mut va_high_idx = poc_idx;
@@ -248,29 +563,183 @@ fn compute_volume_profile(candles: &[Candle]) -> VolumeProfileResult {
     }
 }
 
-fn compute_order_flow(candles: &[Candle]) -> OrderFlowResult {
+/// Candle-body estimate of (buy, sell) volume when no executed-side data exists.
+pub(crate) fn estimate_bar_flow(c: &Candle) -> (f64, f64) {
+    let body_ratio = if c.high - c.low > 0.0 {
+        (c.close - c.open).abs() / (c.high - c.low)
+    } else { 0.5 };
+
+    if c.close >= c.open {
+        let bv = c.volume * (0.5 + body_ratio * 0.3);
+        (bv, c.volume - bv)
+    } else {
+        let sv = c.volume * (0.5 + body_ratio * 0.3);
+        (c.volume - sv, sv)
+    }
+}
+
+/// Per-bar footprint: price level (in ticks) -> (bid/sell volume, ask/buy volume).
+type Footprint = std::collections::BTreeMap<i64, (f64, f64)>;
+
+/// Assign ticks to candles and classify aggressor side. Returns per-bar
+/// (buy, sell) totals and per-bar footprints.
+fn bucket_ticks(candles: &[Candle], ticks: &[TradeTick], tick_size: f64) -> (Vec<(f64, f64)>, Vec<Footprint>) {
+    let mut flows = vec![(0.0, 0.0); candles.len()];
+    let mut prints: Vec<Footprint> = vec![Footprint::new(); candles.len()];
+    let mut classifier = TickClassifier::new();
+
+    for t in ticks {
+        let is_buy = classifier.is_buy(t);
+
+        let idx = candles.partition_point(|c| c.timestamp.as_str() <= t.timestamp.as_str());
+        if idx == 0 { continue; }
+        let bar = idx - 1;
+        let level = (t.price / tick_size).round() as i64;
+        let entry = prints[bar].entry(level).or_insert((0.0, 0.0));
+        if is_buy {
+            flows[bar].0 += t.size;
+            entry.1 += t.size;
+        } else {
+            flows[bar].1 += t.size;
+            entry.0 += t.size;
+        }
+    }
+    (flows, prints)
+}
+
+/// Diagonal footprint imbalance: ask volume at a level versus bid volume one
+/// tick below (buy side), and bid volume versus ask volume one tick above (sell side).
+fn footprint_bar(timestamp: &str, fp: &Footprint, tick_size: f64, params: &OrderFlowParams)
+    -> (FootprintBar, Vec<StackedImbalance>)
+{
+    let mut levels = Vec::with_capacity(fp.len());
+    for (&lvl, &(bid, ask)) in fp {
+        let bid_below = fp.get(&(lvl - 1)).map(|v| v.0).unwrap_or(0.0);
+        let ask_above = fp.get(&(lvl + 1)).map(|v| v.1).unwrap_or(0.0);
+        let imbalance = if ask > 0.0 && ask >= params.imbalance_ratio * bid_below {
+            Some("BUY".to_string())
+        } else if bid > 0.0 && bid >= params.imbalance_ratio * ask_above {
+            Some("SELL".to_string())
+        } else { None };
+        levels.push(FootprintLevel {
+            price: round2(lvl as f64 * tick_size),
+            bid_volume: round2(bid),
+            ask_volume: round2(ask),
+            delta: round2(ask - bid),
+            imbalance,
+        });
+    }
+
+    let mut stacked = Vec::new();
+    let mut i = 0;
+    while i < levels.len() {
+        let side = match &levels[i].imbalance { Some(s) => s.clone(), None => { i += 1; continue; } };
+        let mut j = i;
+        while j + 1 < levels.len()
+            && levels[j + 1].imbalance.as_deref() == Some(side.as_str())
+            && (levels[j + 1].price - levels[j].price - tick_size).abs() < tick_size * 0.5
+        {
+            j += 1;
+        }
+        let run = j - i + 1;
+        if run >= params.stacked_levels.max(2) {
+            stacked.push(StackedImbalance {
+                timestamp: timestamp.to_string(),
+                side: side.clone(),
+                price_low: levels[i].price,
+                price_high: levels[j].price,
+                levels: run,
+            });
+        }
+        i = j + 1;
+    }
+
+    let delta: f64 = levels.iter().map(|l| l.delta).sum();
+    (FootprintBar { timestamp: timestamp.to_string(), delta: round2(delta), levels }, stacked)
+}
+
+/// Indices of pivot highs (or lows) that dominate `lookback` bars on each side.
+fn swing_points(candles: &[Candle], lookback: usize, highs: bool) -> Vec<usize> {
+    let n = candles.len();
+    if lookback == 0 || n < 2 * lookback + 1 { return vec![]; }
+    (lookback..n - lookback).filter(|&i| {
+        let window = &candles[i - lookback..=i + lookback];
+        if highs {
+            window.iter().all(|c| c.high <= candles[i].high)
+        } else {
+            window.iter().all(|c| c.low >= candles[i].low)
+        }
+    }).collect()
+}
+
+fn detect_delta_divergences(candles: &[Candle], cum: &[f64], lookback: usize) -> Vec<DeltaDivergence> {
+    let cum_min = cum.iter().cloned().fold(f64::INFINITY, f64::min);
+    let cum_max = cum.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let cum_range = (cum_max - cum_min).max(1e-9);
+
+    let mut out = Vec::new();
+    for highs in [true, false] {
+        let swings = swing_points(candles, lookback, highs);
+        for pair in swings.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let (pa, pb) = if highs { (candles[a].high, candles[b].high) } else { (candles[a].low, candles[b].low) };
+            let kind = if highs && pb > pa && cum[b] < cum[a] {
+                "BEARISH"
+            } else if !highs && pb < pa && cum[b] > cum[a] {
+                "BULLISH"
+            } else { continue };
+            out.push(DeltaDivergence {
+                timestamp: candles[b].timestamp.clone(),
+                prev_timestamp: candles[a].timestamp.clone(),
+                kind: kind.to_string(),
+                price: round2(pb),
+                prev_price: round2(pa),
+                cumulative_delta: round2(cum[b]),
+                prev_cumulative_delta: round2(cum[a]),
+                strength: round2(((cum[b] - cum[a]).abs() / cum_range).min(1.0)),
+            });
+        }
+    }
+    out.sort_by(|x, y| x.timestamp.cmp(&y.timestamp));
+    out
+}
+
+/// Per-bar (buy, sell) volume from the best available source, plus tick
+/// footprints when ticks were supplied.
+fn resolve_flows(candles: &[Candle], params: &OrderFlowParams) -> (&'static str, Vec<(f64, f64)>, Vec<Footprint>) {
+    if !params.bar_volumes.is_empty() {
+        ("bar_volume", params.bar_volumes.iter().map(|b| (b.buy_volume, b.sell_volume)).collect(), Vec::new())
+    } else if !params.ticks.is_empty() {
+        let (flows, prints) = bucket_ticks(candles, &params.ticks, params.footprint_tick_size);
+        ("ticks", flows, prints)
+    } else {
+        ("estimated", candles.iter().map(estimate_bar_flow).collect(), Vec::new())
+    }
+}
+
+fn compute_order_flow(candles: &[Candle], params: &OrderFlowParams) -> OrderFlowResult {
     let mut buy_vol = 0.0;
     let mut sell_vol = 0.0;
     let mut cum_delta = 0.0;
     let mut deltas = Vec::with_capacity(candles.len());
+    let mut cum_series = Vec::with_capacity(candles.len());
+    let mut footprint = Vec::new();
+    let mut stacked_imbalances = Vec::new();
 
-    for c in candles {
-        let body_ratio = if c.high - c.low > 0.0 {
-            (c.close - c.open).abs() / (c.high - c.low)
-        } else { 0.5 };
-
-        let (bv, sv) = if c.close >= c.open {
-            let bv = c.volume * (0.5 + body_ratio * 0.3);
-            (bv, c.volume - bv)
-        } else {
-            let sv = c.volume * (0.5 + body_ratio * 0.3);
-            (c.volume - sv, sv)
-        };
+    let (source, flows, prints) = resolve_flows(candles, params);
+    for (c, fp) in candles.iter().zip(prints.iter()) {
+        if fp.is_empty() { continue; }
+        let (bar, stacks) = footprint_bar(&c.timestamp, fp, params.footprint_tick_size, params);
+        footprint.push(bar);
+        stacked_imbalances.extend(stacks);
+    }
 
+    for (c, &(bv, sv)) in candles.iter().zip(flows.iter()) {
         buy_vol += bv;
         sell_vol += sv;
         let delta = bv - sv;
         cum_delta += delta;
+        cum_series.push(cum_delta);
 
         deltas.push(DeltaPoint {
             timestamp: c.timestamp.clone(),
@@ -281,6 +750,7 @@ fn compute_order_flow(candles: &[Candle]) -> OrderFlowResult {
 
     let total = buy_vol + sell_vol;
     let imbalance = if total > 0.0 { (buy_vol - sell_vol) / total } else { 0.0 };
+    let divergences = detect_delta_divergences(candles, &cum_series, params.swing_lookback);
 
     let recent_n = 10.min(deltas.len());
     let recent_delta: f64 = deltas[deltas.len() - recent_n..].iter().map(|d| d.delta).sum();
@@ -299,15 +769,38 @@ fn compute_order_flow(candles: &[Candle]) -> OrderFlowResult {
         cumulative_delta: round2(cum_delta),
         signal: signal.to_string(),
         recent_deltas: deltas[deltas.len().saturating_sub(20)..].to_vec(),
+        source: source.to_string(),
+        footprint,
+        stacked_imbalances,
+        divergences,
     }
 }
 
-fn compute_market_profile(candles: &[Candle]) -> MarketProfileResult {
+/// Candles forming the initial balance: the first `ib_minutes` of the latest
+/// session when timestamps carry intraday times, else the first sixth of the input.
+fn initial_balance_slice(candles: &[Candle], ib_minutes: i64) -> (&[Candle], &'static str) {
+    let fallback = (&candles[..(candles.len() / 6).max(1)], "bar_count");
+    let last_key = match candles.last() { Some(c) => session_key(&c.timestamp), None => return fallback };
+    let start = candles.iter().rposition(|c| session_key(&c.timestamp) != last_key).map(|i| i + 1).unwrap_or(0);
+    let session = &candles[start..];
+    if session.len() < 2 { return fallback; }
+
+    let open_time = match parse_timestamp(&session[0].timestamp) { Some(t) => t, None => return fallback };
+    let cutoff = open_time + chrono::Duration::minutes(ib_minutes.max(1));
+    let n = session.iter()
+        .take_while(|c| parse_timestamp(&c.timestamp).is_some_and(|t| t < cutoff))
+        .count()
+        .max(1);
+    (&session[..n], "time")
+}
+
+fn compute_market_profile(candles: &[Candle], ib_minutes: i64) -> MarketProfileResult {
     if candles.is_empty() {
         return MarketProfileResult {
             poc: 0.0, initial_balance_high: 0.0, initial_balance_low: 0.0,
             value_area_high: 0.0, value_area_low: 0.0,
             profile_type: "unknown".into(), tpo_count: 0, signal: "NEUTRAL".into(),
+            ib_method: "none".into(), ib_candles: 0,
         };
     }
 
@@ -319,6 +812,7 @@ fn compute_market_profile(candles: &[Candle]) -> MarketProfileResult {
             poc: candles[0].close, initial_balance_high: max_p, initial_balance_low: min_p,
             value_area_high: max_p, value_area_low: min_p,
             profile_type: "single_tick".into(), tpo_count: 1, signal: "NEUTRAL".into(),
+            ib_method: "none".into(), ib_candles: candles.len(),
         };
     }
 
@@ -340,9 +834,9 @@ fn compute_market_profile(candles: &[Candle]) -> MarketProfileResult {
         .map(|(i, _)| i).unwrap_or(0);
     let poc = min_p + (poc_idx as f64 + 0.5) * tick;
 
-    let ib_count = (candles.len() / 6).max(1);
-    let ib_high = candles[..ib_count].iter().map(|c| c.high).fold(f64::NEG_INFINITY, f64::max);
-    let ib_low = candles[..ib_count].iter().map(|c| c.low).fold(f64::INFINITY, f64::min);
+    let (ib_slice, ib_method) = initial_balance_slice(candles, ib_minutes);
+    let ib_high = ib_slice.iter().map(|c| c.high).fold(f64::NEG_INFINITY, f64::max);
+    let ib_low = ib_slice.iter().map(|c| c.low).fold(f64::INFINITY, f64::min);
 
     let va_target = (total_tpo as f64 * 0.7) as usize;
     let mut va_tpo = tpo_counts[poc_idx];
@@ -365,6 +859,7 @@ fn compute_market_profile(candles: &[Candle]) -> MarketProfileResult {
             poc: 0.0, initial_balance_high: 0.0, initial_balance_low: 0.0,
             value_area_high: 0.0, value_area_low: 0.0,
             profile_type: "unknown".into(), tpo_count: 0, signal: "NEUTRAL".into(),
+            ib_method: "none".into(), ib_candles: 0,
         },
     };
     let profile_type = if (va_high - va_low) / range < 0.4 { "narrow" }
@@ -386,9 +881,252 @@ fn compute_market_profile(candles: &[Candle]) -> MarketProfileResult {
         profile_type: profile_type.to_string(),
         tpo_count: total_tpo,
         signal: signal.to_string(),
+        ib_method: ib_method.to_string(),
+        ib_candles: ib_slice.len(),
     }
 }
 
+/// Scan bars for volume spikes (>= 1.5x the trailing `lookback` average).
+/// A spike with little body progress is absorption: positive delta that fails
+/// to lift price marks RESISTANCE, negative delta that fails to drop it marks
+/// SUPPORT. A spike with a wide body in the direction of delta is initiative and
+/// leaves its origin as SUPPORT (up move) or RESISTANCE (down move). Overlapping
+/// zones of the same kind merge, and scores grow with volume, recency and the
+/// share of volume-at-price traded inside the zone.
+pub(crate) fn detect_liquidity_zones(candles: &[Candle], flows: &[(f64, f64)], lookback: usize) -> Vec<LiquidityZone> {
+    let n = candles.len();
+    let lookback = lookback.max(5);
+    if n < 6 || flows.len() != n { return vec![]; }
+
+    let mut zones: Vec<LiquidityZone> = Vec::new();
+    for i in 5..n {
+        let w = &candles[i.saturating_sub(lookback)..i];
+        let avg_vol = w.iter().map(|c| c.volume).sum::<f64>() / w.len() as f64;
+        let avg_range = w.iter().map(|c| c.high - c.low).sum::<f64>() / w.len() as f64;
+        let c = &candles[i];
+        if avg_vol <= 0.0 || avg_range <= 0.0 { continue; }
+        let vol_ratio = c.volume / avg_vol;
+        if vol_ratio < 1.5 { continue; }
+
+        let body = c.close - c.open;
+        let progress = body.abs() / avg_range;
+        let delta = flows[i].0 - flows[i].1;
+
+        let (kind, zone_type, low, high, weight) = if progress <= 0.3 {
+            let upper_wick = c.high - c.open.max(c.close);
+            let lower_wick = c.open.min(c.close) - c.low;
+            let resistance = if delta.abs() > f64::EPSILON { delta > 0.0 } else { upper_wick >= lower_wick };
+            (if resistance { "RESISTANCE" } else { "SUPPORT" }, "ABSORPTION", c.low, c.high, 1.0)
+        } else if progress >= 1.0 && body * delta > 0.0 {
+            if body > 0.0 {
+                ("SUPPORT", "INITIATIVE", c.low, c.open, 0.7)
+            } else {
+                ("RESISTANCE", "INITIATIVE", c.open, c.high, 0.7)
+            }
+        } else {
+            continue;
+        };
+        if high <= low { continue; }
+
+        let recency = 0.5 + 0.5 * i as f64 / n as f64;
+        let score = vol_ratio * weight * recency;
+
+        if let Some(z) = zones.iter_mut().find(|z| z.kind == kind && low <= z.price_high && high >= z.price_low) {
+            z.price_low = z.price_low.min(low);
+            z.price_high = z.price_high.max(high);
+            z.events += 1;
+            z.volume += c.volume;
+            z.score += score;
+            z.last_timestamp = c.timestamp.clone();
+            if zone_type == "ABSORPTION" { z.zone_type = zone_type.to_string(); }
+        } else {
+            zones.push(LiquidityZone {
+                price_low: low, price_high: high, mid: 0.0,
+                kind: kind.to_string(), zone_type: zone_type.to_string(),
+                events: 1, volume: c.volume, score,
+                last_timestamp: c.timestamp.clone(),
+            });
+        }
+    }
+
+    let total_vol: f64 = candles.iter().map(|c| c.volume).sum();
+    for z in &mut zones {
+        let vap: f64 = candles.iter()
+            .filter(|c| {
+                let tp = (c.high + c.low + c.close) / 3.0;
+                tp >= z.price_low && tp <= z.price_high
+            })
+            .map(|c| c.volume)
+            .sum();
+        if total_vol > 0.0 { z.score *= 1.0 + vap / total_vol; }
+        z.mid = round2((z.price_low + z.price_high) / 2.0);
+        z.price_low = round2(z.price_low);
+        z.price_high = round2(z.price_high);
+        z.volume = round2(z.volume);
+        z.score = round2(z.score);
+    }
+    zones.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    zones
+}
+
+/// Relative volume per bar versus the average volume at the same time of day
+/// over the previous `sessions` sessions (`None` until history exists). The
+/// second vector compares session-to-date volume the same way.
+pub(crate) fn relative_volume_by_time(candles: &[Candle], sessions: usize) -> (Vec<Option<f64>>, Vec<Option<f64>>) {
+    use std::collections::HashMap;
+    let sessions = sessions.max(1);
+    let mut history: HashMap<String, Vec<(f64, f64)>> = HashMap::new();
+    let mut pending: Vec<(String, f64, f64)> = Vec::new();
+    let mut rvol = Vec::with_capacity(candles.len());
+    let mut cum_rvol = Vec::with_capacity(candles.len());
+    let mut current_day: Option<chrono::NaiveDate> = None;
+    let mut day_cum = 0.0;
+
+    for c in candles {
+        let ts = match parse_timestamp(&c.timestamp) {
+            Some(t) => t,
+            None => { rvol.push(None); cum_rvol.push(None); continue; }
+        };
+        if current_day != Some(ts.date()) {
+            // Roll the finished session into history before starting the next.
+            for (tod, v, cum) in pending.drain(..) {
+                let h = history.entry(tod).or_default();
+                h.push((v, cum));
+                if h.len() > sessions { h.remove(0); }
+            }
+            current_day = Some(ts.date());
+            day_cum = 0.0;
+        }
+        day_cum += c.volume;
+        let tod = ts.format("%H:%M").to_string();
+        let (r, cr) = match history.get(&tod) {
+            Some(h) if !h.is_empty() => {
+                let avg = h.iter().map(|x| x.0).sum::<f64>() / h.len() as f64;
+                let avg_cum = h.iter().map(|x| x.1).sum::<f64>() / h.len() as f64;
+                (
+                    if avg > 0.0 { Some(c.volume / avg) } else { None },
+                    if avg_cum > 0.0 { Some(day_cum / avg_cum) } else { None },
+                )
+            }
+            _ => (None, None),
+        };
+        rvol.push(r);
+        cum_rvol.push(cr);
+        pending.push((tod, c.volume, day_cum));
+    }
+    (rvol, cum_rvol)
+}
+
+fn compute_rvol(candles: &[Candle], sessions: usize) -> RvolResult {
+    let (rvol, cum_rvol) = relative_volume_by_time(candles, sessions);
+    let last = candles.last();
+    let time_of_day = last.and_then(|c| parse_timestamp(&c.timestamp))
+        .map(|t| t.format("%H:%M").to_string())
+        .unwrap_or_default();
+    let last_day = last.and_then(|c| parse_timestamp(&c.timestamp)).map(|t| t.date());
+    let mut days: Vec<chrono::NaiveDate> = candles.iter()
+        .filter_map(|c| parse_timestamp(&c.timestamp).map(|t| t.date()))
+        .filter(|d| Some(*d) != last_day)
+        .collect();
+    days.dedup();
+
+    let current = rvol.last().copied().flatten();
+    let signal = match current {
+        Some(r) if r >= 2.0 => "VERY_HIGH",
+        Some(r) if r >= 1.5 => "HIGH",
+        Some(r) if r <= 0.5 => "LOW",
+        Some(_) => "NORMAL",
+        None => "INSUFFICIENT_HISTORY",
+    };
+
+    let start = candles.len().saturating_sub(50);
+    RvolResult {
+        current_rvol: current.map(round2),
+        cumulative_rvol: cum_rvol.last().copied().flatten().map(round2),
+        time_of_day,
+        sessions_used: days.len().min(sessions.max(1)),
+        signal: signal.to_string(),
+        series: candles[start..].iter().zip(rvol[start..].iter())
+            .map(|(c, r)| RvolPoint { timestamp: c.timestamp.clone(), rvol: r.map(round2) })
+            .collect(),
+    }
+}
+
+/// Session key for a candle: the calendar date portion of its timestamp.
+fn session_key(ts: &str) -> &str {
+    ts.get(..10).unwrap_or(ts)
+}
+
+/// Split consecutive candles into sessions by calendar date.
+fn split_sessions(candles: &[Candle]) -> Vec<(String, &[Candle])> {
+    let mut out: Vec<(String, &[Candle])> = Vec::new();
+    let mut start = 0;
+    for i in 1..=candles.len() {
+        if i == candles.len() || session_key(&candles[i].timestamp) != session_key(&candles[start].timestamp) {
+            out.push((session_key(&candles[start].timestamp).to_string(), &candles[start..i]));
+            start = i;
+        }
+    }
+    out
+}
+
+fn value_migration(prev: &MarketProfileResult, cur: &MarketProfileResult) -> &'static str {
+    if cur.value_area_low > prev.value_area_high { "HIGHER" }
+    else if cur.value_area_high < prev.value_area_low { "LOWER" }
+    else if cur.value_area_high <= prev.value_area_high && cur.value_area_low >= prev.value_area_low { "INSIDE" }
+    else if cur.value_area_high >= prev.value_area_high && cur.value_area_low <= prev.value_area_low { "OUTSIDE" }
+    else if cur.value_area_high > prev.value_area_high { "OVERLAPPING_HIGHER" }
+    else { "OVERLAPPING_LOWER" }
+}
+
+fn compute_session_profiles(candles: &[Candle], ib_minutes: i64) -> SessionProfileResult {
+    let sessions = split_sessions(candles);
+    let mut out: Vec<SessionProfile> = Vec::with_capacity(sessions.len());
+
+    for (key, slice) in &sessions {
+        let profile = compute_market_profile(slice, ib_minutes);
+        let migration = match out.last() {
+            Some(prev) => value_migration(&prev.profile, &profile),
+            None => "FIRST",
+        };
+        out.push(SessionProfile {
+            session: key.clone(),
+            candle_count: slice.len(),
+            high: round2(slice.iter().map(|c| c.high).fold(f64::NEG_INFINITY, f64::max)),
+            low: round2(slice.iter().map(|c| c.low).fold(f64::INFINITY, f64::min)),
+            profile,
+            value_migration: migration.to_string(),
+            poc_naked: false,
+        });
+    }
+
+    // A POC stays naked until a later session trades through it.
+    for i in 0..out.len() {
+        let poc = out[i].profile.poc;
+        out[i].poc_naked = !out[i + 1..].iter().any(|s| s.low <= poc && poc <= s.high);
+    }
+
+    let last_close = candles.last().map(|c| c.close).unwrap_or(0.0);
+    let naked_pocs = out.iter()
+        .take(out.len().saturating_sub(1))
+        .filter(|s| s.poc_naked)
+        .map(|s| NakedPoc {
+            session: s.session.clone(),
+            poc: s.profile.poc,
+            distance_pct: if last_close > 0.0 { round2((s.profile.poc - last_close) / last_close * 100.0) } else { 0.0 },
+        })
+        .collect();
+
+    let recent: Vec<&str> = out.iter().rev().take(3).map(|s| s.value_migration.as_str()).collect();
+    let ups = recent.iter().filter(|m| matches!(**m, "HIGHER" | "OVERLAPPING_HIGHER")).count();
+    let downs = recent.iter().filter(|m| matches!(**m, "LOWER" | "OVERLAPPING_LOWER")).count();
+    let value_area_trend = if ups >= 2 { "MIGRATING_UP" }
+        else if downs >= 2 { "MIGRATING_DOWN" }
+        else { "BALANCED" };
+
+    SessionProfileResult { sessions: out, naked_pocs, value_area_trend: value_area_trend.to_string() }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -431,6 +1169,64 @@ mod tests {
         assert!(poc.is_some(), "poc field missing from volume_profile");
     }
 
+    #[test]
+    fn test_volume_profile_custom_binning() {
+        let data = json!({
+            "candles": sample_candles(20), "compute": ["volume_profile"],
+            "volume_profile": { "num_levels": 8, "value_area_pct": 0.5 }
+        });
+        let result = compute(data).unwrap();
+        let levels = result["volume_profile"]["levels"].as_array().unwrap();
+        assert_eq!(levels.len(), 8);
+
+        let data = json!({
+            "candles": sample_candles(20), "compute": ["volume_profile"],
+            "volume_profile": { "tick_size": 1.0, "num_levels": 8 }
+        });
+        let result = compute(data).unwrap();
+        let levels = result["volume_profile"]["levels"].as_array().unwrap();
+        assert_eq!(levels.len(), 22, "tick_size should override num_levels");
+    }
+
+    #[test]
+    fn test_volume_profile_caps_price_levels() {
+        let data = |params: Value| json!({
+            "candles": sample_candles(20), "compute": ["volume_profile"], "volume_profile": params
+        });
+        let err = compute(data(json!({ "tick_size": 1e-6 }))).err().unwrap();
+        assert!(err.contains("price levels"), "{}", err);
+        assert!(compute(data(json!({ "num_levels": 1_000_000_000usize }))).is_err());
+        assert!(compute(data(json!({ "num_levels": MAX_PROFILE_LEVELS }))).is_ok());
+    }
+
+    #[test]
+    fn test_volume_profile_rejects_bad_value_area() {
+        let data = json!({
+            "candles": sample_candles(20), "compute": ["volume_profile"],
+            "volume_profile": { "value_area_pct": 1.5 }
+        });
+        assert!(compute(data).is_err());
+    }
+
+    #[test]
+    fn test_composite_profiles_by_date_range() {
+        let data = json!({
+            "candles": sample_candles(20), "compute": ["volume_profile"],
+            "volume_profile": { "composites": [
+                { "label": "week1", "start": "2024-01-01", "end": "2024-01-07" },
+                { "start": "2024-03-01", "end": "2024-03-31" }
+            ]}
+        });
+        let result = compute(data).unwrap();
+        let comps = result["composite_profiles"].as_array().unwrap();
+        assert_eq!(comps.len(), 2);
+        assert_eq!(comps[0]["label"], "week1");
+        assert_eq!(comps[0]["candle_count"], 7);
+        assert!(comps[0]["profile"]["poc"].is_number());
+        assert_eq!(comps[1]["candle_count"], 0);
+        assert!(comps[1]["profile"].is_null());
+    }
+
     #[test]
     fn test_order_flow_basic() {
         let data = json!({ "candles": sample_candles(20), "compute": ["order_flow"] });
@@ -441,6 +1237,191 @@ mod tests {
         assert!(buy_vol + sell_vol > 0.0, "buy + sell volume should be positive");
     }
 
+    #[test]
+    fn test_order_flow_bar_volumes() {
+        let candles = sample_candles(3);
+        let data = json!({
+            "candles": candles, "compute": ["order_flow"],
+            "order_flow": { "bar_volumes": [
+                { "buy_volume": 700.0, "sell_volume": 300.0 },
+                { "buy_volume": 200.0, "sell_volume": 800.0 },
+                { "buy_volume": 500.0, "sell_volume": 500.0 }
+            ]}
+        });
+        let of = compute(data).unwrap()["order_flow"].clone();
+        assert_eq!(of["source"], "bar_volume");
+        assert_eq!(of["delta"].as_f64().unwrap(), -200.0);
+        assert_eq!(of["recent_deltas"][0]["delta"].as_f64().unwrap(), 400.0);
+
+        let bad = json!({
+            "candles": sample_candles(3), "compute": ["order_flow"],
+            "order_flow": { "bar_volumes": [{ "buy_volume": 1.0, "sell_volume": 1.0 }] }
+        });
+        assert!(compute(bad).is_err());
+    }
+
+    #[test]
+    fn test_order_flow_ticks_footprint_and_stacked() {
+        let candles = json!([
+            { "timestamp": "2024-01-01T09:15:00", "open": 100.0, "high": 101.0, "low": 99.0, "close": 100.5, "volume": 1000.0 },
+            { "timestamp": "2024-01-01T09:16:00", "open": 100.5, "high": 101.0, "low": 100.0, "close": 100.0, "volume": 1000.0 }
+        ]);
+        let mut ticks = Vec::new();
+        // Aggressive buying stacked over 100.00..100.20 with thin bids below.
+        for (i, p) in [100.0, 100.05, 100.10, 100.15, 100.20].iter().enumerate() {
+            ticks.push(json!({ "timestamp": format!("2024-01-01T09:15:{:02}", i * 2), "price": p, "size": 100.0, "side": "buy" }));
+            ticks.push(json!({ "timestamp": format!("2024-01-01T09:15:{:02}", i * 2 + 1), "price": p, "size": 10.0, "side": "sell" }));
+        }
+        // Unsided ticks in the second bar fall back to the tick rule.
+        ticks.push(json!({ "timestamp": "2024-01-01T09:16:01", "price": 100.3, "size": 50.0 }));
+        ticks.push(json!({ "timestamp": "2024-01-01T09:16:02", "price": 100.1, "size": 80.0 }));
+
+        let data = json!({
+            "candles": candles, "compute": ["order_flow"],
+            "order_flow": { "ticks": ticks, "footprint_tick_size": 0.05 }
+        });
+        let of = compute(data).unwrap()["order_flow"].clone();
+        assert_eq!(of["source"], "ticks");
+        assert_eq!(of["recent_deltas"][0]["delta"].as_f64().unwrap(), 450.0);
+        assert_eq!(of["recent_deltas"][1]["delta"].as_f64().unwrap(), -30.0);
+        assert_eq!(of["footprint"].as_array().unwrap().len(), 2);
+        let stacked = of["stacked_imbalances"].as_array().unwrap();
+        assert_eq!(stacked.len(), 1);
+        assert_eq!(stacked[0]["side"], "BUY");
+        assert!(stacked[0]["levels"].as_u64().unwrap() >= 3);
+    }
+
+    #[test]
+    fn test_delta_divergence_bearish() {
+        // Two swing highs, the second higher in price, while sellers dominate
+        // the rally into it so cumulative delta makes a lower high.
+        let highs = [100.0, 101.0, 105.0, 101.0, 100.0, 101.0, 103.0, 107.0, 103.0, 101.0, 100.0];
+        let candles: Vec<_> = highs.iter().enumerate().map(|(i, h)| json!({
+            "timestamp": format!("2024-01-01T09:{:02}:00", 15 + i),
+            "open": h - 1.0, "high": h, "low": h - 2.0, "close": h - 0.5, "volume": 1000.0
+        })).collect();
+        let flows: Vec<_> = (0..highs.len()).map(|i| {
+            let (b, s) = match i { 0..=2 => (800.0, 200.0), 3..=4 => (500.0, 500.0), _ => (300.0, 700.0) };
+            json!({ "buy_volume": b, "sell_volume": s })
+        }).collect();
+        let data = json!({
+            "candles": candles, "compute": ["order_flow"],
+            "order_flow": { "bar_volumes": flows, "swing_lookback": 2 }
+        });
+        let of = compute(data).unwrap()["order_flow"].clone();
+        let divs = of["divergences"].as_array().unwrap();
+        assert_eq!(divs.len(), 1, "{:?}", divs);
+        assert_eq!(divs[0]["kind"], "BEARISH");
+        assert_eq!(divs[0]["timestamp"], "2024-01-01T09:22:00");
+        assert!(divs[0]["strength"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_session_profiles_naked_poc_and_migration() {
+        let mut candles = Vec::new();
+        for (day, base) in [(2, 100.0), (3, 110.0), (4, 120.0)] {
+            for m in 0..6 {
+                let b = base + m as f64 * 0.5;
+                candles.push(json!({
+                    "timestamp": format!("2024-01-{:02}T09:{:02}:00", day, 15 + m),
+                    "open": b, "high": b + 1.0, "low": b - 1.0, "close": b + 0.5, "volume": 1000.0
+                }));
+            }
+        }
+        let data = json!({ "candles": candles, "compute": ["session_profile"] });
+        let sp = compute(data).unwrap()["session_profile"].clone();
+        let sessions = sp["sessions"].as_array().unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0]["value_migration"], "FIRST");
+        assert_eq!(sessions[1]["value_migration"], "HIGHER");
+        assert_eq!(sessions[2]["value_migration"], "HIGHER");
+        assert_eq!(sp["value_area_trend"], "MIGRATING_UP");
+        let naked = sp["naked_pocs"].as_array().unwrap();
+        assert_eq!(naked.len(), 2);
+        assert_eq!(naked[0]["session"], "2024-01-02");
+        assert!(naked[0]["distance_pct"].as_f64().unwrap() < 0.0);
+    }
+
+    #[test]
+    fn test_market_profile_time_based_initial_balance() {
+        // Prior session followed by a 5-minute-bar session; IB = first 30 minutes.
+        let mut candles = vec![json!({
+            "timestamp": "2024-01-01T15:25:00", "open": 90.0, "high": 95.0, "low": 85.0, "close": 90.0, "volume": 1000.0
+        })];
+        for i in 0..12 {
+            let b = 100.0 + i as f64;
+            candles.push(json!({
+                "timestamp": format!("2024-01-02T{:02}:{:02}:00", 9 + (15 + i * 5) / 60, (15 + i * 5) % 60),
+                "open": b, "high": b + 1.0, "low": b - 1.0, "close": b + 0.5, "volume": 1000.0
+            }));
+        }
+        let data = json!({ "candles": candles, "compute": ["market_profile"], "market_profile": { "ib_minutes": 30 } });
+        let mp = compute(data).unwrap()["market_profile"].clone();
+        assert_eq!(mp["ib_method"], "time");
+        assert_eq!(mp["ib_candles"], 6);
+        assert_eq!(mp["initial_balance_low"].as_f64().unwrap(), 99.0);
+        assert_eq!(mp["initial_balance_high"].as_f64().unwrap(), 106.0);
+    }
+
+    fn flat_candles(n: usize) -> Vec<serde_json::Value> {
+        (0..n).map(|i| json!({
+            "timestamp": format!("2024-01-01T09:{:02}:00", 15 + i),
+            "open": 100.0, "high": 101.0, "low": 99.0, "close": 100.2, "volume": 1000.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_liquidity_zones_absorption_and_initiative() {
+        let mut candles = flat_candles(12);
+        // Heavy buying into 104-106 with no progress: sellers absorbing overhead.
+        candles.push(json!({ "timestamp": "2024-01-01T09:27:00", "open": 105.0, "high": 106.0, "low": 104.0, "close": 105.1, "volume": 5000.0 }));
+        candles.extend(flat_candles(3));
+        // Wide bullish initiative bar launching from 100.
+        candles.push(json!({ "timestamp": "2024-01-01T09:31:00", "open": 100.0, "high": 104.0, "low": 99.5, "close": 103.8, "volume": 4000.0 }));
+        let n = candles.len();
+        let mut flows: Vec<serde_json::Value> = (0..n).map(|_| json!({ "buy_volume": 500.0, "sell_volume": 500.0 })).collect();
+        flows[12] = json!({ "buy_volume": 4000.0, "sell_volume": 1000.0 });
+        flows[n - 1] = json!({ "buy_volume": 3500.0, "sell_volume": 500.0 });
+
+        let data = json!({
+            "candles": candles, "compute": ["liquidity_zones"],
+            "order_flow": { "bar_volumes": flows }
+        });
+        let zones = compute(data).unwrap()["liquidity_zones"].as_array().unwrap().clone();
+        assert_eq!(zones.len(), 2, "{:?}", zones);
+        let res = zones.iter().find(|z| z["kind"] == "RESISTANCE").unwrap();
+        assert_eq!(res["zone_type"], "ABSORPTION");
+        assert_eq!(res["price_low"].as_f64().unwrap(), 104.0);
+        let sup = zones.iter().find(|z| z["kind"] == "SUPPORT").unwrap();
+        assert_eq!(sup["zone_type"], "INITIATIVE");
+        assert_eq!(sup["price_high"].as_f64().unwrap(), 100.0);
+        assert!(zones[0]["score"].as_f64().unwrap() >= zones[1]["score"].as_f64().unwrap());
+    }
+
+    #[test]
+    fn test_rvol_by_time_of_day() {
+        let mut candles = Vec::new();
+        for day in 1..=4 {
+            for (k, tod) in ["09:15", "09:20", "09:25"].iter().enumerate() {
+                let vol = if day == 4 && k == 1 { 3000.0 } else { 1000.0 * (k + 1) as f64 };
+                candles.push(json!({
+                    "timestamp": format!("2024-01-0{}T{}:00", day, tod),
+                    "open": 100.0, "high": 101.0, "low": 99.0, "close": 100.0, "volume": vol
+                }));
+            }
+        }
+        // Drop the final 09:25 bar so the "current" bar is day 4 at 09:20.
+        candles.pop();
+        let data = json!({ "candles": candles, "compute": ["rvol"], "rvol_sessions": 3 });
+        let rv = compute(data).unwrap()["rvol"].clone();
+        assert_eq!(rv["time_of_day"], "09:20");
+        assert_eq!(rv["current_rvol"].as_f64().unwrap(), 1.5);
+        assert_eq!(rv["cumulative_rvol"].as_f64().unwrap(), 1.33);
+        assert_eq!(rv["sessions_used"], 3);
+        assert_eq!(rv["signal"], "HIGH");
+        assert!(rv["series"][0]["rvol"].is_null());
+    }
+
     #[test]
     fn test_market_profile_basic() {
         let data = json!({ "candles": sample_candles(20), "compute": ["market_profile"] });
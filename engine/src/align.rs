@@ -0,0 +1,245 @@
+//! Align several symbols' candles on one timestamp index.
+//!
+//! `policy = "ffill"` (default) uses the union of timestamps and carries the
+//! last close forward as a flat zero-volume bar where a symbol has no print;
+//! rows before every symbol has started are trimmed unless
+//! `trim_leading = false`, in which case the gaps stay null. `max_fill`
+//! caps consecutive fills (longer gaps stay null). `policy = "drop"` keeps
+//! only timestamps every symbol traded.
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{Candle, parse_timestamp, round2};
+
+#[derive(Deserialize)]
+struct AlignConfig {
+    symbols: Vec<SymbolCandles>,
+    #[serde(default = "default_policy")]
+    policy: String,
+    #[serde(default = "default_true")]
+    trim_leading: bool,
+    #[serde(default)]
+    max_fill: Option<usize>,
+}
+
+fn default_policy() -> String { "ffill".to_string() }
+fn default_true() -> bool { true }
+
+#[derive(Deserialize)]
+struct SymbolCandles {
+    symbol: String,
+    candles: Vec<Candle>,
+}
+
+enum Cell {
+    Present(Candle),
+    Filled(Candle),
+    Missing,
+}
+
+#[derive(Serialize)]
+struct AlignedSeries {
+    symbol: String,
+    candles: Vec<Option<Candle>>,
+}
+
+#[derive(Serialize)]
+struct Coverage {
+    symbol: String,
+    input_bars: usize,
+    present: usize,
+    filled: usize,
+    missing: usize,
+    /// Rows with a real print, as % of the aligned index.
+    coverage_pct: f64,
+    first_timestamp: Option<String>,
+    last_timestamp: Option<String>,
+    unparseable_timestamps: usize,
+    duplicate_timestamps: usize,
+}
+
+#[derive(Serialize)]
+struct AlignResult {
+    policy: String,
+    index: Vec<String>,
+    series: Vec<AlignedSeries>,
+    coverage: Vec<Coverage>,
+    /// Union of all timestamps before trimming/dropping.
+    union_rows: usize,
+    rows: usize,
+    dropped_rows: usize,
+    complete_rows: usize,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: AlignConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid align config: {}", e))?;
+    let ffill = match config.policy.as_str() {
+        "ffill" => true,
+        "drop" => false,
+        other => return Err(format!("Unknown align policy '{}' (ffill, drop)", other)),
+    };
+    if config.symbols.is_empty() {
+        return Err("align requires at least one symbol".to_string());
+    }
+
+    // Per-symbol lookup by parsed timestamp; the last bar wins on duplicates.
+    let mut index: BTreeMap<NaiveDateTime, String> = BTreeMap::new();
+    let mut lookups: Vec<HashMap<NaiveDateTime, &Candle>> = Vec::new();
+    let mut coverage: Vec<Coverage> = Vec::new();
+    for sym in &config.symbols {
+        let mut by_ts = HashMap::with_capacity(sym.candles.len());
+        let (mut unparseable, mut duplicates) = (0, 0);
+        for c in &sym.candles {
+            let Some(ts) = parse_timestamp(&c.timestamp) else { unparseable += 1; continue };
+            if by_ts.insert(ts, c).is_some() {
+                duplicates += 1;
+            }
+            index.entry(ts).or_insert_with(|| c.timestamp.clone());
+        }
+        coverage.push(Coverage {
+            symbol: sym.symbol.clone(),
+            input_bars: sym.candles.len(),
+            present: 0, filled: 0, missing: 0, coverage_pct: 0.0,
+            first_timestamp: None, last_timestamp: None,
+            unparseable_timestamps: unparseable,
+            duplicate_timestamps: duplicates,
+        });
+        lookups.push(by_ts);
+    }
+    let union_rows = index.len();
+
+    let mut rows: Vec<(String, Vec<Cell>)> = Vec::with_capacity(index.len());
+    let mut last: Vec<Option<&Candle>> = vec![None; lookups.len()];
+    let mut run: Vec<usize> = vec![0; lookups.len()];
+    for (ts, label) in &index {
+        let row = lookups.iter().enumerate().map(|(i, by_ts)| match by_ts.get(ts) {
+            Some(c) => {
+                last[i] = Some(c);
+                run[i] = 0;
+                Cell::Present((*c).clone())
+            }
+            None => {
+                run[i] += 1;
+                match last[i] {
+                    Some(prev) if ffill && config.max_fill.is_none_or(|m| run[i] <= m) => Cell::Filled(Candle {
+                        timestamp: label.clone(),
+                        open: prev.close, high: prev.close, low: prev.close, close: prev.close,
+                        volume: 0.0,
+                    }),
+                    _ => Cell::Missing,
+                }
+            }
+        }).collect();
+        rows.push((label.clone(), row));
+    }
+
+    let complete = |row: &[Cell]| row.iter().all(|c| matches!(c, Cell::Present(_)));
+    if !ffill {
+        rows.retain(|(_, row)| complete(row));
+    } else if config.trim_leading {
+        let start = rows.iter()
+            .position(|(_, row)| row.iter().all(|c| !matches!(c, Cell::Missing)))
+            .unwrap_or(rows.len());
+        rows.drain(..start);
+    }
+
+    let n = rows.len();
+    let mut series: Vec<AlignedSeries> = config.symbols.iter()
+        .map(|s| AlignedSeries { symbol: s.symbol.clone(), candles: Vec::with_capacity(n) })
+        .collect();
+    let complete_rows = rows.iter().filter(|(_, row)| complete(row)).count();
+    let mut labels = Vec::with_capacity(n);
+    for (label, row) in rows {
+        for (i, cell) in row.into_iter().enumerate() {
+            let cov = &mut coverage[i];
+            let candle = match cell {
+                Cell::Present(c) => {
+                    cov.present += 1;
+                    cov.first_timestamp.get_or_insert_with(|| label.clone());
+                    cov.last_timestamp = Some(label.clone());
+                    Some(c)
+                }
+                Cell::Filled(c) => {
+                    cov.filled += 1;
+                    Some(c)
+                }
+                Cell::Missing => {
+                    cov.missing += 1;
+                    None
+                }
+            };
+            series[i].candles.push(candle);
+        }
+        labels.push(label);
+    }
+    for cov in coverage.iter_mut() {
+        cov.coverage_pct = if n > 0 { round2(cov.present as f64 / n as f64 * 100.0) } else { 0.0 };
+    }
+
+    let result = AlignResult {
+        policy: config.policy,
+        index: labels,
+        series,
+        coverage,
+        union_rows,
+        rows: n,
+        dropped_rows: union_rows - n,
+        complete_rows,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bars(stamps: &[(&str, f64)]) -> Vec<Value> {
+        stamps.iter().map(|(ts, c)| json!({ "timestamp": ts, "open": c, "high": c, "low": c, "close": c, "volume": 10.0 })).collect()
+    }
+
+    fn panel(policy: &str) -> Value {
+        json!({
+            "policy": policy,
+            "symbols": [
+                { "symbol": "A", "candles": bars(&[("2024-01-01", 1.0), ("2024-01-02", 2.0), ("2024-01-03", 3.0), ("2024-01-05", 5.0)]) },
+                { "symbol": "B", "candles": bars(&[("2024-01-02", 20.0), ("2024-01-04", 40.0), ("2024-01-05", 50.0)]) },
+            ]
+        })
+    }
+
+    #[test]
+    fn test_ffill_trims_leading_and_fills_gaps() {
+        let result = compute(panel("ffill")).unwrap();
+        assert_eq!(result["union_rows"], 5);
+        assert_eq!(result["index"], json!(["2024-01-02", "2024-01-03", "2024-01-04", "2024-01-05"]));
+        let b = &result["series"][1]["candles"];
+        assert_eq!(b[1]["close"], 20.0);
+        assert_eq!(b[1]["volume"], 0.0);
+        assert_eq!(b[1]["timestamp"], "2024-01-03");
+        assert_eq!(result["series"][0]["candles"][2]["close"], 3.0);
+        assert_eq!(result["complete_rows"], 2);
+        let cov_b = &result["coverage"][1];
+        assert_eq!((cov_b["present"].as_u64(), cov_b["filled"].as_u64()), (Some(3), Some(1)));
+        assert_eq!(cov_b["coverage_pct"], 75.0);
+    }
+
+    #[test]
+    fn test_drop_keeps_common_rows_and_max_fill() {
+        let result = compute(panel("drop")).unwrap();
+        assert_eq!(result["index"], json!(["2024-01-02", "2024-01-05"]));
+        assert_eq!(result["dropped_rows"], 3);
+
+        let mut data = panel("ffill");
+        data["trim_leading"] = json!(false);
+        data["max_fill"] = json!(0);
+        let result = compute(data).unwrap();
+        assert_eq!(result["rows"], 5);
+        assert!(result["series"][1]["candles"][0].is_null());
+        assert!(result["series"][1]["candles"][2].is_null());
+        assert!(compute(json!({ "symbols": [], "policy": "ffill" })).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Turn scan signals into position sizes against a capital budget.
+//!
+//! Signals (the `scan` output's `signals`, or `scan` itself) are taken in
+//! confidence order. Each is sized so a stop-out loses `risk_per_trade_pct`
+//! of capital, then cut to whatever binds first: `max_position_pct` per
+//! symbol, `max_sector_pct` per sector (`sectors` maps symbol → sector,
+//! existing positions count), `max_gross_exposure_pct` overall and
+//! `max_open_positions`. Quantities round down to `lot_sizes`.
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::round2;
+
+#[derive(Deserialize)]
+struct AllocateConfig {
+    #[serde(default)]
+    signals: Vec<SignalIn>,
+    #[serde(default)]
+    scan: Option<ScanIn>,
+    capital: f64,
+    #[serde(default)]
+    risk: RiskSettings,
+    #[serde(default)]
+    sectors: HashMap<String, String>,
+    #[serde(default)]
+    lot_sizes: HashMap<String, f64>,
+    #[serde(default)]
+    existing_positions: Vec<ExistingPosition>,
+}
+
+#[derive(Deserialize)]
+struct ScanIn {
+    signals: Vec<SignalIn>,
+}
+
+#[derive(Deserialize, Clone)]
+struct SignalIn {
+    symbol: String,
+    direction: String,
+    confidence: f64,
+    entry: f64,
+    stop_loss: f64,
+    #[serde(default)]
+    target: Option<f64>,
+    #[serde(default)]
+    sector: Option<String>,
+    #[serde(default)]
+    strategy: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExistingPosition {
+    symbol: String,
+    /// Absolute market value.
+    value: f64,
+    #[serde(default)]
+    sector: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct RiskSettings {
+    risk_per_trade_pct: f64,
+    max_position_pct: f64,
+    max_sector_pct: f64,
+    max_open_positions: usize,
+    max_gross_exposure_pct: f64,
+    min_confidence: f64,
+}
+
+impl Default for RiskSettings {
+    fn default() -> Self {
+        Self {
+            risk_per_trade_pct: 1.0,
+            max_position_pct: 20.0,
+            max_sector_pct: 40.0,
+            max_open_positions: 10,
+            max_gross_exposure_pct: 100.0,
+            min_confidence: 0.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Allocation {
+    symbol: String,
+    direction: String,
+    confidence: f64,
+    entry: f64,
+    stop_loss: f64,
+    target: Option<f64>,
+    sector: String,
+    strategy: Option<String>,
+    qty: f64,
+    capital_used: f64,
+    capital_pct: f64,
+    risk_amount: f64,
+    risk_pct: f64,
+    reward_risk: Option<f64>,
+    /// Which limit set the size: risk, max_position, sector or capital.
+    binding_constraint: &'static str,
+}
+
+#[derive(Serialize)]
+struct Skipped {
+    symbol: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct Check {
+    check: &'static str,
+    limit: f64,
+    value: f64,
+    ok: bool,
+}
+
+#[derive(Serialize)]
+struct AllocateResult {
+    allocations: Vec<Allocation>,
+    skipped: Vec<Skipped>,
+    capital: f64,
+    capital_used: f64,
+    capital_remaining: f64,
+    utilization_pct: f64,
+    total_risk: f64,
+    total_risk_pct: f64,
+    open_positions: usize,
+    long_exposure: f64,
+    short_exposure: f64,
+    /// Sector → % of capital, including existing positions.
+    sector_exposure: BTreeMap<String, f64>,
+    checks: Vec<Check>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: AllocateConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid allocate config: {}", e))?;
+    if !(config.capital.is_finite() && config.capital > 0.0) {
+        return Err("capital must be positive".to_string());
+    }
+    let r = &config.risk;
+    let capital = config.capital;
+    let mut signals = match config.scan {
+        Some(scan) if config.signals.is_empty() => scan.signals,
+        _ => config.signals.clone(),
+    };
+    signals.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    let sector_of = |symbol: &str, given: &Option<String>| {
+        given.clone()
+            .or_else(|| config.sectors.get(symbol).cloned())
+            .unwrap_or_else(|| "UNCLASSIFIED".to_string())
+    };
+    let mut sector_value: BTreeMap<String, f64> = BTreeMap::new();
+    let mut held: Vec<String> = Vec::new();
+    let mut gross: f64 = 0.0;
+    for p in &config.existing_positions {
+        *sector_value.entry(sector_of(&p.symbol, &p.sector)).or_default() += p.value.abs();
+        gross += p.value.abs();
+        held.push(p.symbol.clone());
+    }
+    let gross_limit = capital * r.max_gross_exposure_pct / 100.0;
+    let sector_limit = capital * r.max_sector_pct / 100.0;
+    let position_limit = capital * r.max_position_pct / 100.0;
+
+    let mut allocations = Vec::new();
+    let mut skipped = Vec::new();
+    let (mut used, mut risk_total, mut long, mut short) = (0.0, 0.0, 0.0, 0.0);
+    for s in signals {
+        let skip = |reason: &str| Skipped { symbol: s.symbol.clone(), reason: reason.to_string() };
+        if s.confidence < r.min_confidence {
+            skipped.push(skip("below min_confidence"));
+            continue;
+        }
+        if held.contains(&s.symbol) {
+            skipped.push(skip("already held or allocated"));
+            continue;
+        }
+        if held.len() >= r.max_open_positions {
+            skipped.push(skip("max_open_positions reached"));
+            continue;
+        }
+        let is_long = !s.direction.to_uppercase().starts_with('S');
+        let risk_per_unit = if is_long { s.entry - s.stop_loss } else { s.stop_loss - s.entry };
+        if !(s.entry > 0.0 && risk_per_unit > 0.0) {
+            skipped.push(skip("stop_loss is not on the losing side of entry"));
+            continue;
+        }
+        let sector = sector_of(&s.symbol, &s.sector);
+        let sector_room = sector_limit - sector_value.get(&sector).copied().unwrap_or(0.0);
+        let budgets = [
+            ("risk", capital * r.risk_per_trade_pct / 100.0 / risk_per_unit),
+            ("max_position", position_limit / s.entry),
+            ("sector", sector_room / s.entry),
+            ("capital", (gross_limit - gross) / s.entry),
+        ];
+        let (binding, raw) = budgets.iter()
+            .fold(("risk", f64::INFINITY), |acc, &(name, q)| if q < acc.1 { (name, q) } else { acc });
+        let lot = config.lot_sizes.get(&s.symbol).copied().filter(|l| *l > 0.0).unwrap_or(1.0);
+        let qty = (raw.max(0.0) / lot).floor() * lot;
+        if qty < lot {
+            skipped.push(skip(&format!("{} limit leaves less than one lot", binding)));
+            continue;
+        }
+        let value = qty * s.entry;
+        let risk_amount = qty * risk_per_unit;
+        gross += value;
+        used += value;
+        risk_total += risk_amount;
+        if is_long { long += value } else { short += value }
+        *sector_value.entry(sector.clone()).or_default() += value;
+        held.push(s.symbol.clone());
+        let reward_risk = s.target.map(|t| round2((t - s.entry).abs() / risk_per_unit));
+        allocations.push(Allocation {
+            symbol: s.symbol,
+            direction: if is_long { "LONG" } else { "SHORT" }.to_string(),
+            confidence: s.confidence,
+            entry: s.entry,
+            stop_loss: s.stop_loss,
+            target: s.target,
+            sector,
+            strategy: s.strategy,
+            qty,
+            capital_used: round2(value),
+            capital_pct: round2(value / capital * 100.0),
+            risk_amount: round2(risk_amount),
+            risk_pct: round2(risk_amount / capital * 100.0),
+            reward_risk,
+            binding_constraint: binding,
+        });
+    }
+
+    let sector_exposure: BTreeMap<String, f64> = sector_value.iter()
+        .map(|(k, v)| (k.clone(), round2(v / capital * 100.0)))
+        .collect();
+    let max_symbol_pct = allocations.iter().map(|a| a.capital_pct).fold(0.0, f64::max);
+    let max_sector_pct = sector_exposure.values().cloned().fold(0.0, f64::max);
+    let gross_pct = gross / capital * 100.0;
+    let checks = vec![
+        Check { check: "max_position_pct", limit: r.max_position_pct, value: max_symbol_pct, ok: max_symbol_pct <= r.max_position_pct + 1e-9 },
+        Check { check: "max_sector_pct", limit: r.max_sector_pct, value: max_sector_pct, ok: max_sector_pct <= r.max_sector_pct + 1e-9 },
+        Check { check: "max_open_positions", limit: r.max_open_positions as f64, value: held.len() as f64, ok: held.len() <= r.max_open_positions },
+        Check { check: "max_gross_exposure_pct", limit: r.max_gross_exposure_pct, value: round2(gross_pct), ok: gross_pct <= r.max_gross_exposure_pct + 1e-9 },
+    ];
+
+    let result = AllocateResult {
+        allocations,
+        skipped,
+        capital,
+        capital_used: round2(used),
+        capital_remaining: round2(capital - used),
+        utilization_pct: round2(used / capital * 100.0),
+        total_risk: round2(risk_total),
+        total_risk_pct: round2(risk_total / capital * 100.0),
+        open_positions: held.len(),
+        long_exposure: round2(long),
+        short_exposure: round2(short),
+        sector_exposure,
+        checks,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn signal(symbol: &str, confidence: f64, entry: f64, stop: f64) -> Value {
+        json!({ "symbol": symbol, "direction": "BUY", "confidence": confidence, "entry": entry, "stop_loss": stop, "target": entry + 2.0 * (entry - stop) })
+    }
+
+    #[test]
+    fn test_risk_sizing_and_position_cap() {
+        let result = compute(json!({
+            "capital": 100_000.0,
+            "scan": { "signals": [signal("A", 0.6, 100.0, 95.0), signal("B", 0.9, 50.0, 49.0)] },
+            "lot_sizes": { "A": 25 }
+        })).unwrap();
+        let a = &result["allocations"];
+        // B first by confidence: risk budget 1000 / 1 = 1000 shares, capped at 20% = 400.
+        assert_eq!(a[0]["symbol"], "B");
+        assert_eq!(a[0]["qty"], 400.0);
+        assert_eq!(a[0]["binding_constraint"], "max_position");
+        // A: 1000 / 5 = 200 shares, a multiple of the 25 lot.
+        assert_eq!(a[1]["qty"], 200.0);
+        assert_eq!(a[1]["binding_constraint"], "risk");
+        assert_eq!(a[1]["reward_risk"], 2.0);
+        assert!(result["checks"].as_array().unwrap().iter().all(|c| c["ok"] == true));
+    }
+
+    #[test]
+    fn test_sector_and_open_position_limits() {
+        let result = compute(json!({
+            "capital": 100_000.0,
+            "signals": [signal("HDFC", 0.9, 100.0, 99.0), signal("ICICI", 0.8, 100.0, 99.0), signal("TCS", 0.7, 100.0, 99.0), signal("INFY", 0.6, 100.0, 99.0)],
+            "sectors": { "HDFC": "BANK", "ICICI": "BANK", "TCS": "IT", "INFY": "IT" },
+            "existing_positions": [{ "symbol": "SBIN", "value": 30_000.0, "sector": "BANK" }],
+            "risk": { "max_sector_pct": 45.0, "max_open_positions": 3 }
+        })).unwrap();
+        let a = result["allocations"].as_array().unwrap();
+        // 15% of bank room left after SBIN: HDFC gets 150 shares, ICICI nothing.
+        assert_eq!(a[0]["qty"], 150.0);
+        assert_eq!(a[0]["binding_constraint"], "sector");
+        assert_eq!(result["skipped"][0]["symbol"], "ICICI");
+        assert_eq!(result["skipped"][1]["symbol"], "INFY");
+        assert_eq!(result["skipped"][1]["reason"], "max_open_positions reached");
+        assert_eq!(result["sector_exposure"]["BANK"], 45.0);
+        assert_eq!(result["open_positions"], 3);
+        assert!(compute(json!({ "capital": 0.0, "signals": [] })).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Typed entry points for Rust callers.
+//!
+//! These are the same computations the JSON commands run, minus the
+//! `serde_json::Value` round trip. The JSON layer in the crate root
+//! deserializes into these types and calls the same functions.
+
+use crate::progress::Progress;
+
+pub use crate::backtest::{BacktestConfig, BacktestResult, CostConfig, EquityPoint, RiskLimitConfig, TradeEntry};
+pub use crate::calendar::CalendarSpec;
+pub use crate::instruments::{Instrument, Instruments, ProductType};
+pub use crate::money::PrecisionSpec;
+pub use crate::vol_target::{CurveStats, VolTargetPoint, VolTargetResult, VolTargetSpec};
+pub use crate::equity_filter::{EquityFilterPoint, EquityFilterResult, EquityFilterSpec};
+pub use crate::greeks::{GreeksInput, GreeksOutput};
+pub use crate::utils::Candle;
+
+/// Run one backtest (the `backtest` command).
+pub fn run_backtest(config: &BacktestConfig) -> Result<BacktestResult, String> {
+    crate::backtest::run_config(config, &Progress::none())
+}
+
+/// Black-Scholes price and Greeks, solving IV when `market_price` is set
+/// (the `greeks` command).
+pub fn compute_greeks(input: &GreeksInput) -> Result<GreeksOutput, String> {
+    crate::greeks::compute_typed(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candles() -> Vec<Candle> {
+        (0..120).map(|i| {
+            let close = 100.0 + (i as f64 * 0.2).sin() * 6.0 + i as f64 * 0.05;
+            Candle {
+                timestamp: format!("2024-01-01T{:02}:{:02}:00", 9 + i / 60, i % 60),
+                open: close,
+                high: close + 1.0,
+                low: close - 1.0,
+                close,
+                volume: 10_000.0,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_typed_backtest_matches_json() {
+        let config = BacktestConfig {
+            strategy: "ema_crossover".to_string(),
+            symbol: "TEST".to_string(),
+            initial_capital: 100_000.0,
+            candles: candles(),
+            params: None,
+            transaction_costs: None,
+            risk_limits: None,
+            bars_per_day: None,
+            volume_participation_limit: None,
+            dynamic_slippage: None,
+            calendar: None,
+            precision: None,
+            vol_target: None,
+            hedge: None,
+            equity_filter: None,
+            instruments: None,
+            auto_slippage: false,
+            exits: None,
+            min_turnover: None,
+            events: vec![],
+            event_blackout: None,
+            event_tag_days: None,
+            strategy_tag: None,
+        };
+        let typed = run_backtest(&config).unwrap();
+        let json = crate::backtest::run(serde_json::to_value(&config).unwrap()).unwrap();
+        assert_eq!(json["total_trades"].as_u64().unwrap() as usize, typed.total_trades);
+        assert_eq!(json["equity_curve"].as_array().unwrap().len(), typed.equity_curve.len());
+    }
+
+    #[test]
+    fn test_typed_greeks() {
+        let g = compute_greeks(&GreeksInput {
+            spot: 100.0,
+            strike: 100.0,
+            time_to_expiry: 0.5,
+            risk_free_rate: 0.05,
+            volatility: 0.2,
+            option_type: "call".to_string(),
+            market_price: None,
+        }).unwrap();
+        assert!(g.delta > 0.5 && g.delta < 0.7);
+        assert!(g.price > 0.0);
+    }
+}
@@ -1,80 +1,171 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use crate::config::EngineConfig;
-use crate::strategy::{create_strategy, Indicators, Side, Strategy};
-use crate::utils::{round2, Candle, TransactionCosts, RiskLimits};
-
-#[derive(Deserialize)]
-struct BacktestConfig {
-    strategy: String,
-    symbol: String,
-    initial_capital: f64,
-    candles: Vec<Candle>,
-    params: Option<Value>,
-    transaction_costs: Option<CostConfig>,
-    risk_limits: Option<RiskLimitConfig>,
+use crate::strategy::{create_strategy, Indicators, Side, Strategy, UnitSizing};
+use crate::calendar::{Calendar, CalendarSpec};
+use crate::instruments::{Instrument, Instruments};
+use crate::money::{Money, PrecisionSpec};
+use crate::vol_target::{VolTargetResult, VolTargetSpec};
+use crate::hedge::{HedgeResult, HedgeSpec};
+use crate::equity_filter::{EquityFilterResult, EquityFilterSpec};
+use crate::exits::{ExitSpec, ExitState, StopSource};
+use crate::liquidity::{turnover_liquidity, DEFAULT_LOOKBACK_DAYS};
+use crate::events::{Event, EventCalendar, EventProximity, EventWindow};
+use crate::indicators::ChannelSpec;
+use crate::utils::{parse_timestamp, round2, Candle, TransactionCosts, RiskLimits};
+use crate::progress::Progress;
+
+/// Input to a single backtest. The JSON `backtest` command deserializes into
+/// this; Rust callers can build it directly and use [`run_config`].
+#[derive(Deserialize, Serialize, Clone)]
+pub struct BacktestConfig {
+    pub strategy: String,
+    pub symbol: String,
+    pub initial_capital: f64,
+    pub candles: Vec<Candle>,
+    pub params: Option<Value>,
+    pub transaction_costs: Option<CostConfig>,
+    pub risk_limits: Option<RiskLimitConfig>,
     /// How many candle bars correspond to one trading day. Default: 1 (daily bars).
     /// For 5-min bars on a 6.25h trading day, use 75.
-    bars_per_day: Option<f64>,
+    pub bars_per_day: Option<f64>,
     /// Max volume participation per bar (e.g. 0.05 = 5%). Orders exceeding this are rejected.
-    volume_participation_limit: Option<f64>,
+    pub volume_participation_limit: Option<f64>,
     /// Enable volume-adjusted slippage based on order size vs liquidity.
-    dynamic_slippage: Option<bool>,
+    pub dynamic_slippage: Option<bool>,
+    /// Exchange calendar for annualization; defaults to the embedded NSE one.
+    #[serde(default)]
+    pub calendar: Option<CalendarSpec>,
+    /// Tick-snapped fills and fixed-point cash/P&L; f64 with 2-decimal
+    /// reporting when omitted.
+    #[serde(default)]
+    pub precision: Option<PrecisionSpec>,
+    /// Scale exposure daily towards a target annualized volatility and
+    /// report the overlay next to the raw curve.
+    #[serde(default)]
+    pub vol_target: Option<VolTargetSpec>,
+    /// Offset the position with a rolling-beta index futures hedge and
+    /// report the hedged curve next to the raw one; see [`crate::hedge`].
+    #[serde(default)]
+    pub hedge: Option<HedgeSpec>,
+    /// Trade the strategy's own equity curve: cut exposure while it is
+    /// below its moving average and report the filtered curve next to the
+    /// raw one; see [`crate::equity_filter`].
+    #[serde(default)]
+    pub equity_filter: Option<EquityFilterSpec>,
+    /// Lot size, tick size and contract multiplier per symbol; only
+    /// `symbol`'s entry is used. Cash equity when absent.
+    #[serde(default)]
+    pub instruments: Option<Instruments>,
+    /// Estimate `slippage_bps` from the candles' range, volatility and
+    /// volume when `transaction_costs` does not set it.
+    #[serde(default)]
+    pub auto_slippage: bool,
+    /// Chandelier, swing-low and break-even trailing stops and scale-out
+    /// targets applied to every strategy's positions on top of the stop it
+    /// sets at entry.
+    #[serde(default)]
+    pub exits: Option<ExitSpec>,
+    /// Skip entries while the trailing 20-session average daily turnover
+    /// (close × volume) is below this.
+    #[serde(default)]
+    pub min_turnover: Option<f64>,
+    /// Earnings, expiry and macro dates; see [`crate::events`].
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// Block new entries this close to a matching event.
+    #[serde(default)]
+    pub event_blackout: Option<EventWindow>,
+    /// Trades entered within this many days of an event are tagged with
+    /// it. Default 5.
+    #[serde(default)]
+    pub event_tag_days: Option<i64>,
+    /// Label copied onto every `trade_log` row so runs of several systems
+    /// can be sliced by it downstream (ledger, attribution, trade quality).
+    #[serde(default)]
+    pub strategy_tag: Option<String>,
 }
 
-#[derive(Deserialize)]
-struct CostConfig {
-    commission: Option<f64>,
-    slippage_bps: Option<f64>,
-    stt_pct: Option<f64>,
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CostConfig {
+    pub commission: Option<f64>,
+    pub slippage_bps: Option<f64>,
+    pub stt_pct: Option<f64>,
 }
 
-#[derive(Deserialize)]
-struct RiskLimitConfig {
-    max_position_pct: Option<f64>,
-    max_loss_pct: Option<f64>,
-    max_drawdown_pct: Option<f64>,
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RiskLimitConfig {
+    pub max_position_pct: Option<f64>,
+    pub max_loss_pct: Option<f64>,
+    pub max_drawdown_pct: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct BacktestResult {
-    cagr: f64,
-    max_drawdown: f64,
-    sharpe_ratio: f64,
-    sortino_ratio: f64,
-    win_rate: f64,
-    profit_factor: f64,
-    total_trades: usize,
-    avg_win: f64,
-    avg_loss: f64,
-    total_costs: f64,
-    cost_drag_pct: f64,
-    risk_rejections: usize,
-    drawdown_circuit_breaks: usize,
-    volume_rejected_trades: usize,
-    avg_slippage_bps: f64,
-    equity_curve: Vec<EquityPoint>,
-    trade_log: Vec<TradeEntry>,
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BacktestResult {
+    pub cagr: f64,
+    pub max_drawdown: f64,
+    pub sharpe_ratio: f64,
+    pub sortino_ratio: f64,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    pub total_trades: usize,
+    pub avg_win: f64,
+    pub avg_loss: f64,
+    pub total_costs: f64,
+    pub cost_drag_pct: f64,
+    pub risk_rejections: usize,
+    pub drawdown_circuit_breaks: usize,
+    pub volume_rejected_trades: usize,
+    /// Entries skipped for turnover under `min_turnover`.
+    #[serde(default)]
+    pub liquidity_rejected_trades: usize,
+    /// Entries skipped inside an `event_blackout` window.
+    #[serde(default)]
+    pub event_blocked_entries: usize,
+    /// Units added to open positions by pyramiding strategies.
+    #[serde(default)]
+    pub pyramid_adds: usize,
+    pub avg_slippage_bps: f64,
+    /// Sessions per year used to annualize Sharpe/Sortino.
+    #[serde(default)]
+    pub trading_days_per_year: f64,
+    pub equity_curve: Vec<EquityPoint>,
+    pub trade_log: Vec<TradeEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vol_target: Option<VolTargetResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hedge: Option<HedgeResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equity_filter: Option<EquityFilterResult>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct EquityPoint {
-    date: String,
-    nav: f64,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EquityPoint {
+    pub date: String,
+    pub nav: f64,
 }
 
-#[derive(Serialize, Deserialize)]
-struct TradeEntry {
-    symbol: String,
-    side: String,
-    entry_price: f64,
-    exit_price: f64,
-    qty: i64,
-    pnl: f64,
-    gross_pnl: f64,
-    costs: f64,
-    entry_time: String,
-    exit_time: String,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TradeEntry {
+    pub symbol: String,
+    pub side: String,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    pub qty: i64,
+    pub pnl: f64,
+    pub gross_pnl: f64,
+    pub costs: f64,
+    pub entry_time: String,
+    pub exit_time: String,
+    /// Entry this exit closes (part of); scale-outs share it with the final exit.
+    #[serde(default)]
+    pub trade_id: usize,
+    /// Nearest event to the entry date, when `events` are given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<EventProximity>,
+    /// The config's `strategy_tag`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_tag: Option<String>,
 }
 
 fn daily_avg_value(candles: &[Candle], i: usize, bars_per_day: f64) -> f64 {
@@ -120,35 +211,151 @@ fn build_engine_config(params: &Option<Value>) -> EngineConfig {
     if let Some(v) = p.get("overbought").and_then(|v| v.as_f64()) {
         config.backtest.rsi_overbought = v;
     }
+    if let Some(v) = p.get("rsi_transform").and_then(|v| v.as_str()) {
+        config.backtest.rsi_transform = v.to_string();
+    }
     if let Some(v) = p.get("target").and_then(|v| v.as_f64()) {
         config.backtest.orb_target_pct = v;
     }
     if let Some(v) = p.get("stop_loss").and_then(|v| v.as_f64()) {
         config.backtest.orb_stop_loss_pct = v;
     }
+    if let Some(v) = p.get("range_minutes").and_then(|v| v.as_f64()) {
+        config.backtest.orb_range_minutes = v as u32;
+    }
+    if let Some(v) = p.get("buffer_pct").and_then(|v| v.as_f64()) {
+        config.backtest.orb_buffer_pct = v;
+    }
+    if let Some(v) = p.get("max_trades_per_day").and_then(|v| v.as_f64()) {
+        config.backtest.orb_max_trades_per_day = v as usize;
+    }
+    if let Some(v) = p.get("square_off").and_then(|v| v.as_str()) {
+        config.backtest.intraday_square_off = v.to_string();
+    }
+    if let Some(v) = p.get("gap_pct").and_then(|v| v.as_f64()) {
+        config.backtest.gap_min_pct = v;
+    }
+    if let Some(v) = p.get("band_mult").and_then(|v| v.as_f64()) {
+        config.backtest.vwap_band_mult = v;
+    }
+    if let Some(v) = p.get("anchor").and_then(|v| v.as_str()) {
+        config.backtest.vwap_band_anchor = v.to_string();
+    }
+    if let Some(v) = p.get("max_bars").and_then(|v| v.as_f64()) {
+        config.backtest.vwap_band_max_bars = v as usize;
+    }
+    if let Some(v) = p.get("entry_period").and_then(|v| v.as_f64()) {
+        config.backtest.turtle_entry_period = v as usize;
+    }
+    if let Some(v) = p.get("exit_period").and_then(|v| v.as_f64()) {
+        config.backtest.turtle_exit_period = v as usize;
+    }
+    if let Some(v) = p.get("max_units").and_then(|v| v.as_f64()) {
+        config.backtest.turtle_max_units = v as usize;
+    }
+    if let Some(v) = p.get("add_step").and_then(|v| v.as_f64()) {
+        config.backtest.turtle_add_step = v;
+    }
+    if let Some(v) = p.get("stop_n").and_then(|v| v.as_f64()) {
+        config.backtest.turtle_stop_n = v;
+    }
+    if let Some(v) = p.get("unit_risk_pct").and_then(|v| v.as_f64()) {
+        config.backtest.turtle_unit_risk_pct = v;
+    }
+    if let Some(v) = p.get("bb_period").and_then(|v| v.as_f64()) {
+        config.backtest.bb_period = v as usize;
+    }
+    if let Some(v) = p.get("band").and_then(|v| v.as_str()) {
+        config.backtest.bb_band = v.to_string();
+    }
+    if let Some([lo, hi]) = p.get("band_levels").and_then(|v| serde_json::from_value::<[f64; 2]>(v.clone()).ok()) {
+        config.backtest.bb_channel_levels = [lo, hi];
+    }
+    if let Some(v) = p.get("fractal_span").and_then(|v| v.as_f64()) {
+        config.backtest.fractal_span = v as usize;
+    }
 
     config
 }
 
 pub fn run(data: Value) -> Result<Value, String> {
+    run_with_progress(data, &Progress::none())
+}
+
+/// `run`, reporting bar-by-bar progress for single long backtests.
+pub fn run_with_progress(data: Value, progress: &Progress) -> Result<Value, String> {
+    let special: Option<fn(Value) -> Result<Value, String>> = match data.get("strategy").and_then(|s| s.as_str()) {
+        Some("wheel" | "covered_call") => Some(crate::wheel::run),
+        Some("pairs") => Some(crate::pairs::backtest),
+        Some("grid" | "dca") => Some(crate::grid::run),
+        _ => None,
+    };
+    if let Some(run) = special {
+        let tag = data.get("strategy_tag").and_then(|t| t.as_str()).map(str::to_string);
+        let mut out = run(data)?;
+        if let (Some(tag), Some(log)) = (tag, out.get_mut("trade_log").and_then(|l| l.as_array_mut())) {
+            for row in log.iter_mut().filter_map(|r| r.as_object_mut()) {
+                row.insert("strategy_tag".to_string(), Value::String(tag.clone()));
+            }
+        }
+        return Ok(out);
+    }
     let config: BacktestConfig =
         serde_json::from_value(data).map_err(|e| format!("Invalid backtest config: {}", e))?;
+    let result = run_config(&config, progress)?;
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
 
+/// (entry_price, qty, entry_time, is_short, stop_loss, take_profit)
+type OpenPosition = (f64, i64, String, bool, Option<f64>, Option<f64>);
+
+/// Typed core of the backtester.
+pub fn run_config(config: &BacktestConfig, progress: &Progress) -> Result<BacktestResult, String> {
     if config.candles.is_empty() {
-        return Ok(serde_json::to_value(BacktestResult {
+        return Ok(BacktestResult {
             cagr: 0.0, max_drawdown: 0.0, sharpe_ratio: 0.0, sortino_ratio: 0.0,
             win_rate: 0.0, profit_factor: 0.0, total_trades: 0,
             avg_win: 0.0, avg_loss: 0.0,
             total_costs: 0.0, cost_drag_pct: 0.0,
             risk_rejections: 0, drawdown_circuit_breaks: 0,
-            volume_rejected_trades: 0, avg_slippage_bps: 0.0,
-            equity_curve: vec![], trade_log: vec![],
-        }).map_err(|e| e.to_string())?);
+            volume_rejected_trades: 0, liquidity_rejected_trades: 0, event_blocked_entries: 0, pyramid_adds: 0,
+            avg_slippage_bps: 0.0,
+            trading_days_per_year: 0.0,
+            equity_curve: vec![], trade_log: vec![], vol_target: None, hedge: None, equity_filter: None,
+        });
     }
 
-    let costs = build_costs(&config.transaction_costs);
+    let calendar = Calendar::from_spec(config.calendar.as_ref())?;
+    let mut costs = build_costs(&config.transaction_costs);
+    if config.auto_slippage && config.transaction_costs.as_ref().and_then(|c| c.slippage_bps).is_none() {
+        let bars_per_day = config.bars_per_day.unwrap_or(1.0);
+        if let Some(e) = crate::slippage::estimate_from_candles(&config.candles, 0, bars_per_day, None) {
+            costs.slippage_bps = e.slippage_bps;
+        }
+    }
     let risk = build_risk_limits(&config.risk_limits);
     let engine_config = build_engine_config(&config.params);
+    if crate::strategy::parse_clock(&engine_config.backtest.intraday_square_off).is_none() {
+        return Err(format!("Invalid square_off '{}' (expected HH:MM)", engine_config.backtest.intraday_square_off));
+    }
+    if crate::strategy::anchor_starts(&[], &engine_config.backtest.vwap_band_anchor).is_none() {
+        return Err(format!("Invalid anchor '{}' (expected session, week or none)", engine_config.backtest.vwap_band_anchor));
+    }
+    let band = &engine_config.backtest;
+    if band.bb_band != "bollinger" {
+        let [lower, upper] = band.bb_channel_levels;
+        ChannelSpec { method: band.bb_band.clone(), period: band.bb_period, lower, upper }.validate()
+            .map_err(|e| format!("Invalid band: {} (or use bollinger)", e))?;
+    }
+    crate::strategy::rsi_transform(&engine_config.backtest.rsi_transform)?;
+    let instrument = config.instruments.as_ref().map(|r| r.get(&config.symbol)).unwrap_or_default();
+    if let Some(spec) = &config.exits {
+        spec.validate()?;
+    }
+    instrument.validate(&config.symbol)?;
+    // An instrument's tick implies tick-snapped fills unless precision says otherwise.
+    let precision = config.precision.clone().or_else(|| instrument.precision());
+    let money = Money::from_spec(precision.as_ref())?.with_multiplier(instrument.multiplier);
 
     let mut strategy: Box<dyn Strategy> = match create_strategy(&config.strategy, &engine_config) {
         Ok(s) => s,
@@ -157,18 +364,34 @@ pub fn run(data: Value) -> Result<Value, String> {
 
     let indicators = Indicators::from_candles(&config.candles, &engine_config);
 
-    let mut cash = config.initial_capital;
+    let mut cash = money.cash(config.initial_capital);
     let mut nav = config.initial_capital;
     let mut peak = nav;
     let mut max_dd = 0.0_f64;
     let mut trades: Vec<TradeEntry> = Vec::new();
     let mut equity_curve: Vec<EquityPoint> = Vec::new();
-    // (entry_price, qty, entry_time, is_short, stop_loss, take_profit)
-    let mut position: Option<(f64, i64, String, bool, Option<f64>, Option<f64>)> = None;
+    // Signed market value held from each bar's close, for the hedge overlay.
+    let mut exposures: Vec<f64> = Vec::with_capacity(config.candles.len());
+    let exposure = |position: &Option<OpenPosition>, close: f64| match position {
+        Some((_, qty, _, true, _, _)) => -money.notional(close, *qty),
+        Some((_, qty, _, false, _, _)) => money.notional(close, *qty),
+        None => 0.0,
+    };
+    let mut position: Option<OpenPosition> = None;
+    // Trailing-stop state, keyed by the entry time of the position it tracks.
+    let mut trail: Option<(String, ExitState)> = None;
+    // Id and original size of the open position, for linking scale-outs.
+    let mut trade_id = 0usize;
+    let mut entry_qty = 0i64;
+    let mut entry_event: Option<EventProximity> = None;
+    let events = EventCalendar::new(&config.events)?;
+    let event_tag_days = config.event_tag_days.unwrap_or(5);
     let mut total_costs = 0.0_f64;
     let mut risk_rejections = 0usize;
     let mut circuit_breaks = 0usize;
     let mut volume_rejected_trades = 0usize;
+    let mut gates = EntryGates::default();
+    let mut pyramid_adds = 0usize;
     let mut slippage_bps_sum = 0.0_f64;
     let mut slippage_trade_count = 0usize;
 
@@ -176,17 +399,65 @@ pub fn run(data: Value) -> Result<Value, String> {
     let dynamic_slippage = config.dynamic_slippage.unwrap_or(false);
     let bars_per_day = config.bars_per_day.unwrap_or(1.0).max(1.0);
 
+    let total_bars = config.candles.len();
     for (i, candle) in config.candles.iter().enumerate() {
+        if progress.is_enabled() && i % 256 == 0 {
+            progress.update("bars", i, total_bars);
+        }
+        // The previous bar's exposure is recorded here, once its every exit path has run.
+        if i > 0 {
+            exposures.push(exposure(&position, config.candles[i - 1].close));
+        }
+        let bar_date = if events.is_empty() { None } else { parse_timestamp(&candle.timestamp).map(|t| t.date()) };
         // Recalculate NAV = cash + open position market value
-        nav = cash;
+        nav = cash.value();
         if let Some((ep, qty, _, is_short, _, _)) = &position {
             if *is_short {
-                nav += (*ep - candle.close) * (*qty) as f64;
+                nav += money.gross_pnl(*ep, candle.close, *qty, true);
             } else {
-                nav += candle.close * (*qty) as f64;
+                nav += money.notional(candle.close, *qty);
+            }
+        }
+        equity_curve.push(EquityPoint { date: candle.timestamp.clone(), nav: money.amount_out(nav) });
+
+        // Scale-out targets, unless the stop is also hit this bar (assumed first).
+        let mut scaled_out = false;
+        if let (Some(spec), Some((ep, qty, et, is_short, sl, _))) = (&config.exits, position.as_mut()) {
+            let stopped = sl.is_some_and(|s| if *is_short { candle.high >= s } else { candle.low <= s });
+            if let Some((_, state)) = trail.as_mut().filter(|(t, _)| t == et && !stopped) {
+                for (level, pct) in state.scale_outs(spec, candle) {
+                    let part = (instrument.round_lots(entry_qty as f64 * pct / 100.0) as i64).min(*qty);
+                    if part <= 0 {
+                        continue;
+                    }
+                    let exit_price = money.snap(costs.slippage_adjusted_price(level, *is_short));
+                    let gross_pnl = money.gross_pnl(*ep, exit_price, part, *is_short);
+                    let exit_value = money.notional(exit_price, part);
+                    let exit_cost = money.amount(costs.total_cost(exit_value, true));
+                    if *is_short {
+                        cash.add(gross_pnl - exit_cost);
+                    } else {
+                        cash.add(exit_value - exit_cost);
+                    }
+                    total_costs += exit_cost;
+                    trades.push(TradeEntry {
+                        symbol: config.symbol.clone(), side: if *is_short { "SHORT_PARTIAL" } else { "LONG_PARTIAL" }.into(),
+                        entry_price: money.price_out(*ep), exit_price: money.price_out(exit_price),
+                        qty: part, pnl: money.amount_out(gross_pnl - exit_cost), gross_pnl: money.amount_out(gross_pnl),
+                        costs: money.amount_out(exit_cost),
+                        entry_time: et.clone(), exit_time: candle.timestamp.clone(), trade_id,
+                        event: entry_event.clone(),
+                        strategy_tag: config.strategy_tag.clone(),
+                    });
+                    *qty -= part;
+                }
             }
+            scaled_out = *qty == 0;
+        }
+        if scaled_out {
+            position = None;
+            strategy.reset();
         }
-        equity_curve.push(EquityPoint { date: candle.timestamp.clone(), nav: round2(nav) });
 
         // Check SL/TP before drawdown and strategy signals
         if let Some((ep, qty, ref et, is_short, sl, tp)) = position {
@@ -203,30 +474,34 @@ pub fn run(data: Value) -> Result<Value, String> {
 
             if hit_sl || hit_tp {
                 let raw_exit = if hit_sl { sl.unwrap() } else { tp.unwrap() };
-                let exit_price = costs.slippage_adjusted_price(raw_exit, is_short);
-                let gross_pnl = if is_short {
-                    (ep - exit_price) * qty as f64
-                } else {
-                    (exit_price - ep) * qty as f64
-                };
-                let exit_value = exit_price * qty as f64;
-                let exit_cost = costs.total_cost(exit_value, true);
+                let exit_price = money.snap(costs.slippage_adjusted_price(raw_exit, is_short));
+                let gross_pnl = money.gross_pnl(ep, exit_price, qty, is_short);
+                let exit_value = money.notional(exit_price, qty);
+                let exit_cost = money.amount(costs.total_cost(exit_value, true));
                 let net_pnl = gross_pnl - exit_cost;
                 if is_short {
-                    cash += gross_pnl - exit_cost;
+                    cash.add(gross_pnl - exit_cost);
                 } else {
-                    cash += exit_value - exit_cost;
+                    cash.add(exit_value - exit_cost);
                 }
                 total_costs += exit_cost;
-                let side_label = if hit_sl {
-                    if is_short { "SHORT_SL" } else { "LONG_SL" }
-                } else if is_short { "SHORT_TP" } else { "LONG_TP" };
+                let trailed = trail.as_ref().is_some_and(|(t, s)| t == et && s.source != StopSource::Initial);
+                let side_label = match (hit_sl, trailed, is_short) {
+                    (true, true, true) => "SHORT_TRAIL",
+                    (true, true, false) => "LONG_TRAIL",
+                    (true, false, true) => "SHORT_SL",
+                    (true, false, false) => "LONG_SL",
+                    (false, _, true) => "SHORT_TP",
+                    (false, _, false) => "LONG_TP",
+                };
                 trades.push(TradeEntry {
                     symbol: config.symbol.clone(), side: side_label.into(),
-                    entry_price: round2(ep), exit_price: round2(exit_price),
-                    qty, pnl: round2(net_pnl), gross_pnl: round2(gross_pnl),
-                    costs: round2(exit_cost),
-                    entry_time: et.clone(), exit_time: candle.timestamp.clone(),
+                    entry_price: money.price_out(ep), exit_price: money.price_out(exit_price),
+                    qty, pnl: money.amount_out(net_pnl), gross_pnl: money.amount_out(gross_pnl),
+                    costs: money.amount_out(exit_cost),
+                    entry_time: et.clone(), exit_time: candle.timestamp.clone(), trade_id,
+                    event: entry_event.clone(),
+                    strategy_tag: config.strategy_tag.clone(),
                 });
                 position = None;
                 strategy.reset();
@@ -237,27 +512,25 @@ pub fn run(data: Value) -> Result<Value, String> {
         let dd_check = risk.check_drawdown(nav, peak);
         if !dd_check.approved {
             if let Some((ep, qty, et, is_short, _, _)) = position.take() {
-                let exit_price = costs.slippage_adjusted_price(candle.close, is_short);
-                let gross_pnl = if is_short {
-                    (ep - exit_price) * qty as f64
-                } else {
-                    (exit_price - ep) * qty as f64
-                };
-                let exit_value = exit_price * qty as f64;
-                let exit_cost = costs.total_cost(exit_value, true);
+                let exit_price = money.snap(costs.slippage_adjusted_price(candle.close, is_short));
+                let gross_pnl = money.gross_pnl(ep, exit_price, qty, is_short);
+                let exit_value = money.notional(exit_price, qty);
+                let exit_cost = money.amount(costs.total_cost(exit_value, true));
                 let net_pnl = gross_pnl - exit_cost;
                 if is_short {
-                    cash += gross_pnl - exit_cost;
+                    cash.add(gross_pnl - exit_cost);
                 } else {
-                    cash += exit_value - exit_cost;
+                    cash.add(exit_value - exit_cost);
                 }
                 total_costs += exit_cost;
                 trades.push(TradeEntry {
                     symbol: config.symbol.clone(), side: "CIRCUIT_BREAK_EXIT".into(),
-                    entry_price: round2(ep), exit_price: round2(exit_price),
-                    qty, pnl: round2(net_pnl), gross_pnl: round2(gross_pnl),
-                    costs: round2(exit_cost),
-                    entry_time: et, exit_time: candle.timestamp.clone(),
+                    entry_price: money.price_out(ep), exit_price: money.price_out(exit_price),
+                    qty, pnl: money.amount_out(net_pnl), gross_pnl: money.amount_out(gross_pnl),
+                    costs: money.amount_out(exit_cost),
+                    entry_time: et, exit_time: candle.timestamp.clone(), trade_id,
+                    event: entry_event.clone(),
+                    strategy_tag: config.strategy_tag.clone(),
                 });
                 circuit_breaks += 1;
                 strategy.reset();
@@ -265,28 +538,66 @@ pub fn run(data: Value) -> Result<Value, String> {
             continue;
         }
 
-        if let Some(signal) = strategy.on_candle(i, candle, &indicators) {
+        // A bar that scaled the position out fully still updates NAV below, but takes no new signal.
+        let signal = if scaled_out { None } else { strategy.on_candle(i, candle, &indicators) };
+        if let Some(signal) = signal {
+            let exit_only = strategy.is_exit(&signal);
             match signal.side {
+                // Pyramiding: grow a same-side position at its average price;
+                // the add's stop, when set, moves the stop for the whole position.
+                _ if strategy.is_add(&signal) => {
+                    let adds_short = matches!(signal.side, Side::Sell);
+                    if let Some((ep, qty, _, is_short, sl, _)) = position.as_mut().filter(|p| p.3 == adds_short) {
+                        let room = calc_qty(nav, candle.close, &risk, &instrument) - *qty;
+                        let mut add = room.min(unit_qty(nav, strategy.unit_sizing(&signal), &instrument));
+                        if !*is_short {
+                            // Longs also pay for the add, costs included.
+                            let spendable = (cash.value() - costs.total_cost(cash.value(), false)).max(0.0);
+                            add = add.min(instrument.round_lots(spendable / instrument.notional(candle.close, 1.0)) as i64);
+                        }
+                        let within_volume = config.volume_participation_limit
+                            .is_none_or(|limit| add as f64 * signal.price <= limit * candle.volume * candle.close);
+                        if add > 0 && within_volume {
+                            let fill = money.snap(costs.slippage_adjusted_price(signal.price, !*is_short));
+                            slippage_bps_sum += costs.slippage_bps;
+                            slippage_trade_count += 1;
+                            let add_value = money.notional(fill, add);
+                            let add_cost = money.amount(costs.total_cost(add_value, false));
+                            cash.add(if *is_short { -add_cost } else { -(add_value + add_cost) });
+                            total_costs += add_cost;
+                            *ep = (*ep * *qty as f64 + fill * add as f64) / (*qty + add) as f64;
+                            *qty += add;
+                            entry_qty += add;
+                            if signal.stop_loss.is_some() {
+                                *sl = signal.stop_loss;
+                            }
+                            pyramid_adds += 1;
+                        }
+                    }
+                }
                 Side::Buy => {
                     if let Some((ep, qty, et, true, _, _)) = position.take() {
-                        let exit_price = costs.slippage_adjusted_price(signal.price, true);
-                        let gross_pnl = (ep - exit_price) * qty as f64;
-                        let exit_value = exit_price * qty as f64;
-                        let exit_cost = costs.total_cost(exit_value, true);
+                        let exit_price = money.snap(costs.slippage_adjusted_price(signal.price, true));
+                        let gross_pnl = money.gross_pnl(ep, exit_price, qty, true);
+                        let exit_value = money.notional(exit_price, qty);
+                        let exit_cost = money.amount(costs.total_cost(exit_value, true));
                         let net_pnl = gross_pnl - exit_cost;
-                        cash += gross_pnl - exit_cost;
+                        cash.add(gross_pnl - exit_cost);
                         total_costs += exit_cost;
                         trades.push(TradeEntry {
                             symbol: config.symbol.clone(), side: "SHORT".into(),
-                            entry_price: round2(ep), exit_price: round2(exit_price),
-                            qty, pnl: round2(net_pnl), gross_pnl: round2(gross_pnl),
-                            costs: round2(exit_cost),
-                            entry_time: et, exit_time: candle.timestamp.clone(),
+                            entry_price: money.price_out(ep), exit_price: money.price_out(exit_price),
+                            qty, pnl: money.amount_out(net_pnl), gross_pnl: money.amount_out(gross_pnl),
+                            costs: money.amount_out(exit_cost),
+                            entry_time: et, exit_time: candle.timestamp.clone(), trade_id,
+                            event: entry_event.clone(),
+                            strategy_tag: config.strategy_tag.clone(),
                         });
                     }
-                    if position.is_none() {
-                        let qty = calc_qty(cash, candle.close, &risk);
-                        let check = risk.check_position_size(nav, candle.close, qty, None);
+                    if position.is_none() && !exit_only {
+                        let qty = calc_qty(cash.value(), candle.close, &risk, &instrument)
+                            .min(unit_qty(nav, strategy.unit_sizing(&signal), &instrument));
+                        let check = risk.check_position_size(nav, candle.close * instrument.multiplier, qty, None);
                         if !check.approved {
                             risk_rejections += 1;
                             continue;
@@ -300,46 +611,55 @@ pub fn run(data: Value) -> Result<Value, String> {
                                     continue;
                                 }
                             }
+                            if !gates.admit(config, &events, i, bar_date, bars_per_day) {
+                                continue;
+                            }
                             let entry_price = if dynamic_slippage {
                                 let daily_avg = daily_avg_value(&config.candles, i, bars_per_day);
                                 let available = (daily_avg * volume_limit).max(1e-10);
                                 let effective_bps = costs.slippage_bps * (1.0 + order_value / available);
                                 slippage_bps_sum += effective_bps;
                                 slippage_trade_count += 1;
-                                slippage_adjusted_with_bps(signal.price, true, effective_bps)
+                                money.snap(slippage_adjusted_with_bps(signal.price, true, effective_bps))
                             } else {
                                 slippage_bps_sum += costs.slippage_bps;
                                 slippage_trade_count += 1;
-                                costs.slippage_adjusted_price(signal.price, true)
+                                money.snap(costs.slippage_adjusted_price(signal.price, true))
                             };
-                            let position_value = entry_price * qty as f64;
-                            let entry_cost = costs.total_cost(position_value, false);
-                            cash -= position_value + entry_cost;
+                            let position_value = money.notional(entry_price, qty);
+                            let entry_cost = money.amount(costs.total_cost(position_value, false));
+                            cash.add(-(position_value + entry_cost));
                             total_costs += entry_cost;
                             position = Some((entry_price, qty, candle.timestamp.clone(), false, signal.stop_loss, signal.take_profit));
+                            trade_id += 1;
+                            entry_qty = qty;
+                            entry_event = bar_date.and_then(|d| events.nearest(&config.symbol, d, event_tag_days));
                         }
                     }
                 }
                 Side::Sell => {
                     if let Some((ep, qty, et, false, _, _)) = position.take() {
-                        let exit_price = costs.slippage_adjusted_price(signal.price, false);
-                        let exit_value = exit_price * qty as f64;
-                        let gross_pnl = (exit_price - ep) * qty as f64;
-                        let exit_cost = costs.total_cost(exit_value, true);
+                        let exit_price = money.snap(costs.slippage_adjusted_price(signal.price, false));
+                        let exit_value = money.notional(exit_price, qty);
+                        let gross_pnl = money.gross_pnl(ep, exit_price, qty, false);
+                        let exit_cost = money.amount(costs.total_cost(exit_value, true));
                         let net_pnl = gross_pnl - exit_cost;
-                        cash += exit_value - exit_cost;
+                        cash.add(exit_value - exit_cost);
                         total_costs += exit_cost;
                         trades.push(TradeEntry {
                             symbol: config.symbol.clone(), side: "LONG".into(),
-                            entry_price: round2(ep), exit_price: round2(exit_price),
-                            qty, pnl: round2(net_pnl), gross_pnl: round2(gross_pnl),
-                            costs: round2(exit_cost),
-                            entry_time: et, exit_time: candle.timestamp.clone(),
+                            entry_price: money.price_out(ep), exit_price: money.price_out(exit_price),
+                            qty, pnl: money.amount_out(net_pnl), gross_pnl: money.amount_out(gross_pnl),
+                            costs: money.amount_out(exit_cost),
+                            entry_time: et, exit_time: candle.timestamp.clone(), trade_id,
+                            event: entry_event.clone(),
+                            strategy_tag: config.strategy_tag.clone(),
                         });
                     }
-                    if position.is_none() {
-                        let qty = calc_qty(cash, candle.close, &risk);
-                        let check = risk.check_position_size(nav, candle.close, qty, None);
+                    if position.is_none() && !exit_only {
+                        let qty = calc_qty(cash.value(), candle.close, &risk, &instrument)
+                            .min(unit_qty(nav, strategy.unit_sizing(&signal), &instrument));
+                        let check = risk.check_position_size(nav, candle.close * instrument.multiplier, qty, None);
                         if !check.approved {
                             risk_rejections += 1;
                             continue;
@@ -353,23 +673,29 @@ pub fn run(data: Value) -> Result<Value, String> {
                                     continue;
                                 }
                             }
+                            if !gates.admit(config, &events, i, bar_date, bars_per_day) {
+                                continue;
+                            }
                             let entry_price = if dynamic_slippage {
                                 let daily_avg = daily_avg_value(&config.candles, i, bars_per_day);
                                 let available = (daily_avg * volume_limit).max(1e-10);
                                 let effective_bps = costs.slippage_bps * (1.0 + order_value / available);
                                 slippage_bps_sum += effective_bps;
                                 slippage_trade_count += 1;
-                                slippage_adjusted_with_bps(signal.price, false, effective_bps)
+                                money.snap(slippage_adjusted_with_bps(signal.price, false, effective_bps))
                             } else {
                                 slippage_bps_sum += costs.slippage_bps;
                                 slippage_trade_count += 1;
-                                costs.slippage_adjusted_price(signal.price, false)
+                                money.snap(costs.slippage_adjusted_price(signal.price, false))
                             };
-                            let position_value = entry_price * qty as f64;
-                            let entry_cost = costs.total_cost(position_value, false);
-                            cash -= entry_cost;
+                            let position_value = money.notional(entry_price, qty);
+                            let entry_cost = money.amount(costs.total_cost(position_value, false));
+                            cash.add(-(entry_cost));
                             total_costs += entry_cost;
                             position = Some((entry_price, qty, candle.timestamp.clone(), true, signal.stop_loss, signal.take_profit));
+                            trade_id += 1;
+                            entry_qty = qty;
+                            entry_event = bar_date.and_then(|d| events.nearest(&config.symbol, d, event_tag_days));
                         }
                     }
                 }
@@ -377,53 +703,171 @@ pub fn run(data: Value) -> Result<Value, String> {
         }
 
         // Final NAV recalculation
-        nav = cash;
+        nav = cash.value();
         if let Some((ep, qty, _, is_short, _, _)) = &position {
             if *is_short {
-                nav += (*ep - candle.close) * (*qty) as f64;
+                nav += money.gross_pnl(*ep, candle.close, *qty, true);
             } else {
-                nav += candle.close * (*qty) as f64;
+                nav += money.notional(candle.close, *qty);
             }
         }
         if nav > peak { peak = nav; }
         let dd = if peak > 0.0 { (peak - nav) / peak } else { 0.0 };
         if dd > max_dd { max_dd = dd; }
+
+        // Trail from the bar after entry: the entry bar's range predates the fill.
+        if let (Some(spec), Some((ep, _, et, is_short, sl, _))) = (&config.exits, position.as_mut()) {
+            match &mut trail {
+                Some((t, state)) if t == et => *sl = state.update(spec, &config.candles, i, indicators.atr[i]),
+                _ => trail = Some((et.clone(), ExitState::new(*ep, *is_short, *sl))),
+            }
+        }
     }
 
     // Close any remaining open position at the last candle price
     if let Some((ep, qty, et, is_short, _, _)) = position.take() {
         if let Some(last_candle) = config.candles.last() {
-            let exit_price = costs.slippage_adjusted_price(last_candle.close, is_short);
-            let gross_pnl = if is_short {
-                (ep - exit_price) * qty as f64
-            } else {
-                (exit_price - ep) * qty as f64
-            };
-            let exit_value = exit_price * qty as f64;
-            let exit_cost = costs.total_cost(exit_value, true);
+            let exit_price = money.snap(costs.slippage_adjusted_price(last_candle.close, is_short));
+            let gross_pnl = money.gross_pnl(ep, exit_price, qty, is_short);
+            let exit_value = money.notional(exit_price, qty);
+            let exit_cost = money.amount(costs.total_cost(exit_value, true));
             let net_pnl = gross_pnl - exit_cost;
             if is_short {
-                cash += gross_pnl - exit_cost;
+                cash.add(gross_pnl - exit_cost);
             } else {
-                cash += exit_value - exit_cost;
+                cash.add(exit_value - exit_cost);
             }
             total_costs += exit_cost;
             trades.push(TradeEntry {
                 symbol: config.symbol.clone(),
                 side: if is_short { "SHORT_EOD_EXIT".into() } else { "LONG_EOD_EXIT".into() },
-                entry_price: round2(ep), exit_price: round2(exit_price),
-                qty, pnl: round2(net_pnl), gross_pnl: round2(gross_pnl),
-                costs: round2(exit_cost),
-                entry_time: et, exit_time: last_candle.timestamp.clone(),
+                entry_price: money.price_out(ep), exit_price: money.price_out(exit_price),
+                qty, pnl: money.amount_out(net_pnl), gross_pnl: money.amount_out(gross_pnl),
+                costs: money.amount_out(exit_cost),
+                entry_time: et, exit_time: last_candle.timestamp.clone(), trade_id,
+                event: entry_event.clone(),
+                strategy_tag: config.strategy_tag.clone(),
             });
-            nav = cash;
+            nav = cash.value();
+        }
+    }
+
+    // Flat after the close-out above, so the hedge comes off with it.
+    if let Some(last) = config.candles.last() {
+        exposures.push(exposure(&position, last.close));
+    }
+
+    let span = dated_span(&config.candles);
+    let perf = performance(&equity_curve, &trades, config.initial_capital, nav, span, &calendar, bars_per_day);
+    let trading_days = perf.trading_days;
+
+    let cost_drag = if config.initial_capital > 0.0 {
+        total_costs / config.initial_capital * 100.0
+    } else { 0.0 };
+
+    let avg_slippage_bps = if slippage_trade_count > 0 {
+        slippage_bps_sum / slippage_trade_count as f64
+    } else {
+        0.0
+    };
+
+    let vol_target = config.vol_target.as_ref()
+        .map(|spec| crate::vol_target::overlay(&equity_curve, spec, trading_days, bars_per_day))
+        .transpose()?;
+    let hedge = config.hedge.as_ref()
+        .map(|spec| crate::hedge::overlay(&equity_curve, &exposures, &config.candles, spec, trading_days, bars_per_day))
+        .transpose()?;
+    let equity_filter = config.equity_filter.as_ref()
+        .map(|spec| crate::equity_filter::overlay(&equity_curve, spec, trading_days, bars_per_day))
+        .transpose()?;
+
+    progress.update("bars", total_bars, total_bars);
+
+    Ok(BacktestResult {
+        cagr: round2(perf.cagr),
+        max_drawdown: round2(max_dd * 100.0),
+        sharpe_ratio: round2(perf.sharpe),
+        sortino_ratio: round2(perf.sortino),
+        win_rate: round2(perf.win_rate),
+        profit_factor: round2(perf.profit_factor),
+        total_trades: perf.trades,
+        avg_win: money.amount_out(perf.avg_win),
+        avg_loss: money.amount_out(perf.avg_loss),
+        total_costs: money.amount_out(total_costs),
+        cost_drag_pct: round2(cost_drag),
+        risk_rejections,
+        drawdown_circuit_breaks: circuit_breaks,
+        volume_rejected_trades,
+        liquidity_rejected_trades: gates.liquidity_rejected,
+        event_blocked_entries: gates.event_blocked,
+        pyramid_adds,
+        avg_slippage_bps: round2(avg_slippage_bps),
+        trading_days_per_year: round2(trading_days),
+        equity_curve,
+        trade_log: trades,
+        vol_target,
+        hedge,
+        equity_filter,
+    })
+}
+
+/// Entry filters checked after sizing, the same for longs and shorts; each
+/// refusal is counted under the gate that made it.
+#[derive(Default)]
+struct EntryGates {
+    event_blocked: usize,
+    liquidity_rejected: usize,
+}
+
+impl EntryGates {
+    fn admit(&mut self, config: &BacktestConfig, events: &EventCalendar, i: usize,
+             date: Option<chrono::NaiveDate>, bars_per_day: f64) -> bool {
+        if let (Some(window), Some(date)) = (&config.event_blackout, date) {
+            if events.blackout(&config.symbol, date, window).is_some() {
+                self.event_blocked += 1;
+                return false;
+            }
+        }
+        if let Some(min) = config.min_turnover {
+            let liquidity = turnover_liquidity(&config.candles[..=i], bars_per_day, DEFAULT_LOOKBACK_DAYS);
+            if liquidity.is_none_or(|l| l.daily_turnover < min) {
+                self.liquidity_rejected += 1;
+                return false;
+            }
         }
+        true
     }
+}
 
-    let wins: Vec<f64> = trades.iter().filter(|t| t.pnl > 0.0).map(|t| t.pnl).collect();
-    let losses: Vec<f64> = trades.iter().filter(|t| t.pnl < 0.0).map(|t| t.pnl.abs()).collect();
+/// Trade and equity-curve ratios, shared with trade-log replay so live
+/// fills and backtests are measured the same way.
+pub(crate) struct Performance {
+    /// Round trips: exits sharing a `trade_id` count once.
+    pub trades: usize,
+    pub cagr: f64,
+    pub sharpe: f64,
+    pub sortino: f64,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    pub avg_win: f64,
+    pub avg_loss: f64,
+    pub trading_days: f64,
+}
 
-    let win_rate = if trades.is_empty() { 0.0 } else { wins.len() as f64 / trades.len() as f64 * 100.0 };
+pub(crate) fn performance(
+    equity_curve: &[EquityPoint],
+    trades: &[TradeEntry],
+    initial_capital: f64,
+    final_nav: f64,
+    span: Option<(chrono::NaiveDate, chrono::NaiveDate)>,
+    calendar: &Calendar,
+    bars_per_day: f64,
+) -> Performance {
+    let round_trips = round_trip_pnls(trades);
+    let wins: Vec<f64> = round_trips.iter().filter(|&&p| p > 0.0).copied().collect();
+    let losses: Vec<f64> = round_trips.iter().filter(|&&p| p < 0.0).map(|p| p.abs()).collect();
+
+    let win_rate = if round_trips.is_empty() { 0.0 } else { wins.len() as f64 / round_trips.len() as f64 * 100.0 };
     let total_wins: f64 = wins.iter().sum();
     let total_losses: f64 = losses.iter().sum();
     let profit_factor = if total_losses > 0.0 { total_wins / total_losses } else { 0.0 };
@@ -434,7 +878,7 @@ pub fn run(data: Value) -> Result<Value, String> {
     let bar_returns: Vec<f64> = equity_curve.windows(2)
         .map(|w| if w[0].nav > 0.0 { w[1].nav / w[0].nav - 1.0 } else { 0.0 })
         .collect();
-    let trading_days = 252.0;
+    let trading_days = span.map_or(crate::calendar::DEFAULT_TRADING_DAYS, |(a, b)| calendar.trading_days_per_year(a, b));
     let annualization = (trading_days * bars_per_day).sqrt();
 
     let mean_ret = if bar_returns.is_empty() { 0.0 } else {
@@ -452,44 +896,55 @@ pub fn run(data: Value) -> Result<Value, String> {
     };
     let sortino = if down_var > 0.0 { mean_ret / down_var.sqrt() * annualization } else { 0.0 };
 
-    let total_return = (nav - config.initial_capital) / config.initial_capital;
-    let years = config.candles.len() as f64 / (trading_days * bars_per_day);
+    let total_return = (final_nav - initial_capital) / initial_capital;
+    // Elapsed calendar time when the bars are dated, so holidays and gaps do
+    // not stretch or shrink the CAGR horizon; bar count otherwise.
+    let years = match span {
+        Some((a, b)) => ((b - a).num_days() + 1) as f64 / 365.25,
+        None => equity_curve.len() as f64 / (trading_days * bars_per_day),
+    };
     let cagr = if years > 0.0 { ((1.0 + total_return).powf(1.0 / years) - 1.0) * 100.0 } else { 0.0 };
 
-    let cost_drag = if config.initial_capital > 0.0 {
-        total_costs / config.initial_capital * 100.0
-    } else { 0.0 };
-
-    let avg_slippage_bps = if slippage_trade_count > 0 {
-        slippage_bps_sum / slippage_trade_count as f64
-    } else {
-        0.0
-    };
+    Performance { trades: round_trips.len(), cagr, sharpe, sortino, win_rate, profit_factor, avg_win, avg_loss, trading_days }
+}
 
-    let result = BacktestResult {
-        cagr: round2(cagr),
-        max_drawdown: round2(max_dd * 100.0),
-        sharpe_ratio: round2(sharpe),
-        sortino_ratio: round2(sortino),
-        win_rate: round2(win_rate),
-        profit_factor: round2(profit_factor),
-        total_trades: trades.len(),
-        avg_win: round2(avg_win),
-        avg_loss: round2(avg_loss),
-        total_costs: round2(total_costs),
-        cost_drag_pct: round2(cost_drag),
-        risk_rejections,
-        drawdown_circuit_breaks: circuit_breaks,
-        volume_rejected_trades,
-        avg_slippage_bps: round2(avg_slippage_bps),
-        equity_curve,
-        trade_log: trades,
-    };
+/// Net P&L per round trip, in order of first exit. Scale-outs and the final
+/// exit of one entry share its `trade_id` (per symbol); rows without an id
+/// (0) stand alone.
+fn round_trip_pnls(trades: &[TradeEntry]) -> Vec<f64> {
+    let mut slot: std::collections::HashMap<(&str, usize), usize> = std::collections::HashMap::new();
+    let mut pnls = Vec::new();
+    for t in trades {
+        if t.trade_id == 0 {
+            pnls.push(t.pnl);
+            continue;
+        }
+        let k = *slot.entry((t.symbol.as_str(), t.trade_id)).or_insert_with(|| {
+            pnls.push(0.0);
+            pnls.len() - 1
+        });
+        pnls[k] += t.pnl;
+    }
+    pnls
+}
 
-    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+/// First and last session dates when every timestamp parses and the series
+/// is in chronological order.
+fn dated_span(candles: &[Candle]) -> Option<(chrono::NaiveDate, chrono::NaiveDate)> {
+    let mut prev: Option<chrono::NaiveDateTime> = None;
+    let mut first = None;
+    for c in candles {
+        let t = parse_timestamp(&c.timestamp)?;
+        if prev.is_some_and(|p| t < p) {
+            return None;
+        }
+        first.get_or_insert(t.date());
+        prev = Some(t);
+    }
+    Some((first?, prev?.date()))
 }
 
-fn build_costs(config: &Option<CostConfig>) -> TransactionCosts {
+pub(crate) fn build_costs(config: &Option<CostConfig>) -> TransactionCosts {
     match config {
         Some(c) => TransactionCosts {
             commission_per_trade: c.commission.unwrap_or(20.0),
@@ -513,16 +968,29 @@ fn build_risk_limits(config: &Option<RiskLimitConfig>) -> RiskLimits {
     }
 }
 
-fn calc_qty(nav: f64, price: f64, risk: &RiskLimits) -> i64 {
+/// Whole-lot quantity a strategy's unit sizing asks for; unbounded without one.
+fn unit_qty(nav: f64, sizing: Option<UnitSizing>, instrument: &Instrument) -> i64 {
+    match sizing {
+        Some(s) if s.price_move > 0.0 => {
+            let risk_amount = nav * s.risk_pct / 100.0;
+            instrument.round_lots(risk_amount / instrument.notional(s.price_move, 1.0)).min(i64::MAX as f64) as i64
+        }
+        _ => i64::MAX,
+    }
+}
+
+/// Largest whole-lot quantity within the position limit.
+fn calc_qty(nav: f64, price: f64, risk: &RiskLimits, instrument: &Instrument) -> i64 {
     if price <= 0.0 { return 0; }
     let max_value = nav * risk.max_position_size_pct / 100.0;
-    (max_value / price).min(i64::MAX as f64) as i64
+    instrument.round_lots(max_value / instrument.notional(price, 1.0)).min(i64::MAX as f64) as i64
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
+    use crate::utils::fixtures::{grid_date, sine_candles};
 
     fn trending_up_candles(n: usize, start: f64, step: f64) -> Vec<serde_json::Value> {
         (0..n).map(|i| {
@@ -685,6 +1153,108 @@ mod tests {
         assert_eq!(r.equity_curve.len(), 50);
     }
 
+    /// 5-minute NSE sessions: a 15-minute range of 100–101, then a steady
+    /// climb to `100 + climb` by the close.
+    fn intraday_sessions(days: usize, climb: f64) -> Vec<serde_json::Value> {
+        (0..days).flat_map(|d| (0..75).map(move |k| {
+            let minutes = 9 * 60 + 15 + k * 5;
+            let close = if k < 3 { 100.0 + (k % 2) as f64 } else { 100.5 + climb * (k - 2) as f64 / 72.0 };
+            json!({
+                "timestamp": format!("2025-03-{:02}T{:02}:{:02}:00", 3 + d, minutes / 60, minutes % 60),
+                "open": close - 0.05, "high": close + 0.1, "low": close - 0.1, "close": close, "volume": 1e6,
+            })
+        })).collect()
+    }
+
+    #[test]
+    fn test_session_orb_breaks_range_and_squares_off() {
+        let result = run(json!({
+            "strategy": "orb", "symbol": "TEST", "initial_capital": 100000.0,
+            "candles": intraday_sessions(2, 1.5),
+            "params": { "target": 50.0, "stop_loss": 5.0, "range_minutes": 15, "buffer_pct": 0.1 },
+        })).unwrap();
+        let r: BacktestResult = serde_json::from_value(result).unwrap();
+        // One long per session, each flattened at 15:15 with no reversal.
+        assert_eq!(r.trade_log.len(), 2, "{:?}", r.trade_log);
+        for (d, t) in r.trade_log.iter().enumerate() {
+            assert_eq!(t.side, "LONG");
+            assert_eq!(t.exit_time, format!("2025-03-{:02}T15:15:00", 3 + d));
+            assert!(t.entry_price > 101.1 * 1.001 - 0.2 && t.entry_price < 101.5, "entry {}", t.entry_price);
+        }
+        assert!(run(json!({
+            "strategy": "orb", "symbol": "TEST", "initial_capital": 100000.0,
+            "candles": intraday_sessions(1, 1.5), "params": { "square_off": "3pm" },
+        })).is_err());
+    }
+
+    #[test]
+    fn test_vwap_band_reversion_fades_and_times_out() {
+        // 5-minute sessions chopping 100.0/100.2; a spike to 101.5 at 11:45
+        // reverts the next bar on day one and holds on day two.
+        let candles: Vec<serde_json::Value> = (0..2).flat_map(|d| (0..75).map(move |k| {
+            let minutes = 9 * 60 + 15 + k * 5;
+            let close = if k == 30 || (d == 1 && k > 30) { 101.5 } else { 100.0 + 0.2 * (k % 2) as f64 };
+            json!({
+                "timestamp": format!("2025-03-{:02}T{:02}:{:02}:00", 3 + d, minutes / 60, minutes % 60),
+                "open": close, "high": close + 0.1, "low": close - 0.1, "close": close, "volume": 1e6,
+            })
+        })).collect();
+        let run_with = |params: serde_json::Value| run(json!({
+            "strategy": "vwap_band_reversion", "symbol": "TEST", "initial_capital": 100000.0,
+            "candles": candles, "params": params,
+        }));
+        let r: BacktestResult = serde_json::from_value(run_with(json!({ "band_mult": 2.0, "max_bars": 12 })).unwrap()).unwrap();
+        let first = &r.trade_log[0];
+        assert!(first.side.starts_with("SHORT"), "{:?}", r.trade_log);
+        assert_eq!(first.entry_time, "2025-03-03T11:45:00");
+        assert_eq!(first.exit_time, "2025-03-03T11:50:00");
+        let second = r.trade_log.iter().find(|t| t.entry_time.starts_with("2025-03-04")).unwrap();
+        assert_eq!(second.side, "SHORT");
+        assert_eq!(second.exit_time, "2025-03-04T12:45:00");
+        assert!(second.pnl < 0.0);
+        assert!(run_with(json!({ "anchor": "month" })).is_err());
+    }
+
+    #[test]
+    fn test_turtle_pyramids_units_then_exits() {
+        // 30 bars ranging 99–101, a 25-bar climb of 1 a bar, then a slide.
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let candles: Vec<serde_json::Value> = (0..60).map(|i| {
+            let close = match i {
+                0..30 => 100.0 + if i % 2 == 0 { 0.5 } else { -0.5 },
+                30..55 => 100.0 + (i - 29) as f64,
+                _ => 125.0 - 3.0 * (i - 54) as f64,
+            };
+            json!({
+                "timestamp": (start + chrono::Duration::days(i)).to_string(),
+                "open": close, "high": close + 0.5, "low": close - 0.5, "close": close, "volume": 1e6,
+            })
+        }).collect();
+        let run_with = |risk_pct: f64| -> BacktestResult {
+            serde_json::from_value(run(json!({
+                "strategy": "turtle", "symbol": "TEST", "initial_capital": 1_000_000.0, "candles": candles,
+                "params": { "unit_risk_pct": risk_pct },
+            })).unwrap()).unwrap()
+        };
+
+        let r = run_with(0.05);
+        assert_eq!(r.pyramid_adds, 3, "{:?}", r.trade_log);
+        assert_eq!(r.trade_log.len(), 1);
+        let trade = &r.trade_log[0];
+        assert_eq!(trade.side, "LONG");
+        assert_eq!(trade.entry_time, "2024-02-01");
+        // Four units from the 102 breakout averaged in, exited on the slide.
+        assert!(trade.entry_price > 102.0 && trade.entry_price < 105.0, "entry {}", trade.entry_price);
+        assert!(trade.exit_price < 125.0 && trade.pnl > 0.0);
+        // Each unit risks ~₹500 per N; four stay well inside the 20% limit.
+        assert!(trade.qty > 0 && trade.qty as f64 * trade.entry_price < 200_000.0);
+
+        // Units as large as the position limit leave no room to add.
+        let capped = run_with(1.0);
+        assert_eq!(capped.pyramid_adds, 0);
+        assert!(capped.trade_log[0].qty > trade.qty);
+    }
+
     fn gap_candles(n: usize, base: f64) -> Vec<serde_json::Value> {
         (0..n).map(|i| {
             let gap = if i % 5 == 0 { 3.0 } else { -0.5 };
@@ -774,6 +1344,52 @@ mod tests {
         assert!(r.max_drawdown >= 0.0);
     }
 
+    #[test]
+    fn test_quantile_channel_as_band_source() {
+        let candles = volatile_candles(120, 100.0);
+        let mut config = build_engine_config(&Some(json!({ "band": "quantile", "band_levels": [0.05, 0.95] })));
+        let typed: Vec<Candle> = serde_json::from_value(json!(candles)).unwrap();
+        let closes: Vec<f64> = typed.iter().map(|c| c.close).collect();
+        let ind = Indicators::from_candles(&typed, &config);
+        assert_eq!(ind.bb_upper, crate::indicators::quantile_channel(&closes, 20, 0.05, 0.95).0);
+        config.backtest.bb_band = "bollinger".into();
+        assert_ne!(Indicators::from_candles(&typed, &config).bb_upper, ind.bb_upper);
+
+        let mut data = json!({
+            "strategy": "volatility_breakout", "symbol": "TEST", "initial_capital": 100000.0, "candles": candles,
+            "params": { "band": "expectile", "bb_period": 30 },
+        });
+        let r: BacktestResult = serde_json::from_value(run(data.clone()).unwrap()).unwrap();
+        assert_eq!(r.equity_curve.len(), 120);
+        data["params"] = json!({ "band": "median" });
+        assert!(run(data.clone()).is_err());
+        data["params"] = json!({ "band": "quantile", "band_levels": [0.9, 0.1] });
+        assert!(run(data).is_err());
+    }
+
+    #[test]
+    fn test_rsi_transform_sets_relative_thresholds() {
+        let candles = volatile_candles(120, 100.0);
+        let config = build_engine_config(&Some(json!({ "rsi_transform": "percentile(rsi_14, 30)" })));
+        let typed: Vec<Candle> = serde_json::from_value(json!(candles)).unwrap();
+        let ind = Indicators::from_candles(&typed, &config);
+        let expected = crate::indicators::percentile_rank(&ind.rsi, 30);
+        assert_eq!(ind.rsi_relative[119], expected[119]);
+        assert!(ind.rsi_relative[20].is_nan());
+        assert_eq!(Indicators::from_candles(&typed, &build_engine_config(&None)).rsi_relative, ind.rsi);
+
+        let mut data = json!({
+            "strategy": "rsi_reversal", "symbol": "TEST", "initial_capital": 100000.0, "candles": candles,
+            "params": { "rsi_transform": "zscore(rsi_14, 40)", "oversold": -1.2, "overbought": 1.2 },
+        });
+        let r: BacktestResult = serde_json::from_value(run(data.clone()).unwrap()).unwrap();
+        assert!(r.total_trades > 0);
+        data["params"] = json!({ "rsi_transform": "zscore(close, 40)" });
+        assert!(run(data.clone()).unwrap_err().contains("rsi_14"));
+        data["params"] = json!({ "rsi_transform": "zscore(rsi_14)" });
+        assert!(run(data).is_err());
+    }
+
     #[test]
     fn test_backtest_multiple_strategies_same_data() {
         let candles = trending_up_candles(80, 100.0, 0.5);
@@ -989,4 +1605,325 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_vol_target_overlay_reported_alongside_raw_curve() {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let candles: Vec<serde_json::Value> = (0..160).map(|i| {
+            let close = 100.0 + (i as f64 * 0.15).sin() * 12.0 + i as f64 * 0.1;
+            json!({
+                "timestamp": (start + chrono::Duration::days(i)).format("%Y-%m-%d").to_string(),
+                "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 1e6,
+            })
+        }).collect();
+        let base = json!({ "strategy": "ema_crossover", "symbol": "TEST", "initial_capital": 100000.0, "candles": candles });
+        let plain = run(base.clone()).unwrap();
+        assert!(plain.get("vol_target").is_none());
+
+        let mut with = base.clone();
+        with["vol_target"] = json!({ "target_vol_pct": 5.0, "min_periods": 10 });
+        let r: BacktestResult = serde_json::from_value(run(with).unwrap()).unwrap();
+        let vt = r.vol_target.unwrap();
+        assert_eq!(vt.curve.len(), 160);
+        assert_eq!(vt.curve.last().unwrap().raw_nav, r.equity_curve.last().unwrap().nav);
+        assert!(vt.curve.iter().all(|p| p.leverage > 0.0 && p.leverage <= 2.0));
+        assert_eq!(vt.raw.total_return_pct,
+            round2((r.equity_curve.last().unwrap().nav / 100000.0 - 1.0) * 100.0));
+
+        let mut bad = base;
+        bad["vol_target"] = json!({ "target_vol_pct": -1.0 });
+        assert!(run(bad).is_err());
+    }
+
+    #[test]
+    fn test_equity_filter_overlay_reported_alongside_raw_curve() {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let candles: Vec<serde_json::Value> = (0..160).map(|i| {
+            let close = 100.0 + (i as f64 * 0.15).sin() * 12.0 + i as f64 * 0.1;
+            json!({
+                "timestamp": (start + chrono::Duration::days(i)).format("%Y-%m-%d").to_string(),
+                "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 1e6,
+            })
+        }).collect();
+        let mut with = json!({ "strategy": "ema_crossover", "symbol": "TEST", "initial_capital": 100000.0, "candles": candles });
+        with["equity_filter"] = json!({ "ma_period": 10 });
+        let r: BacktestResult = serde_json::from_value(run(with.clone()).unwrap()).unwrap();
+        let ef = r.equity_filter.unwrap();
+        assert_eq!(ef.curve.len(), 160);
+        assert_eq!(ef.curve.last().unwrap().raw_nav, r.equity_curve.last().unwrap().nav);
+        assert!(ef.curve.iter().all(|p| p.exposure == 0.0 || p.exposure == 1.0));
+        assert_eq!(ef.raw.total_return_pct,
+            round2((r.equity_curve.last().unwrap().nav / 100000.0 - 1.0) * 100.0));
+
+        with["equity_filter"] = json!({ "reduced_exposure": 2.0 });
+        assert!(run(with).is_err());
+    }
+
+    #[test]
+    fn test_strategy_tag_on_every_trade() {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let candles: Vec<Value> = (0..160).map(|i| {
+            let close = 100.0 + (i as f64 * 0.15).sin() * 12.0;
+            json!({
+                "timestamp": (start + chrono::Duration::days(i)).format("%Y-%m-%d").to_string(),
+                "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 1e6,
+            })
+        }).collect();
+        let mut data = json!({ "strategy": "ema_crossover", "symbol": "TEST", "initial_capital": 100000.0, "candles": candles });
+        let untagged = run(data.clone()).unwrap();
+        assert!(untagged["trade_log"].as_array().unwrap().iter().all(|t| t.get("strategy_tag").is_none()));
+        data["strategy_tag"] = json!("trend-v2");
+        let tagged = run(data).unwrap();
+        let log = tagged["trade_log"].as_array().unwrap();
+        assert!(!log.is_empty());
+        assert!(log.iter().all(|t| t["strategy_tag"] == "trend-v2"));
+
+        // Special strategies get it on their own trade rows.
+        let closes: Vec<Value> = [100.0, 99.0, 98.0, 99.0, 100.0].iter().enumerate().map(|(i, c)| json!({
+            "timestamp": format!("2024-01-{:02}", i + 1), "open": c, "high": c, "low": c, "close": c, "volume": 1e6,
+        })).collect();
+        let grid = run(json!({
+            "strategy": "grid", "initial_capital": 30_000.0, "candles": closes, "strategy_tag": "grid-a",
+            "params": { "levels": 2, "spacing_pct": 1.0, "order_value": 9_000.0, "fractional": true },
+        })).unwrap();
+        let log = grid["trade_log"].as_array().unwrap();
+        assert!(!log.is_empty());
+        assert!(log.iter().all(|t| t["strategy_tag"] == "grid-a"));
+    }
+
+    /// An ema_crossover run on a stock tracking a synthetic index, and the index bars.
+    fn hedge_fixture() -> (Value, Vec<Value>) {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let day = |i: i64| (start + chrono::Duration::days(i)).format("%Y-%m-%d").to_string();
+        let index: Vec<f64> = (0..160).map(|i| 20_000.0 + (i as f64 * 0.15).sin() * 1_500.0 + i as f64 * 10.0).collect();
+        let candles: Vec<serde_json::Value> = index.iter().enumerate().map(|(i, x)| {
+            let close = x / 200.0 + (i as f64 * 0.4).cos();
+            json!({ "timestamp": day(i as i64), "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 1e6 })
+        }).collect();
+        let index_candles: Vec<serde_json::Value> = index.iter().enumerate()
+            .map(|(i, x)| json!({ "timestamp": day(i as i64), "open": x, "high": x, "low": x, "close": x, "volume": 0 }))
+            .collect();
+        (json!({ "strategy": "ema_crossover", "symbol": "TEST", "initial_capital": 100000.0, "candles": candles }), index_candles)
+    }
+
+    #[test]
+    fn test_beta_hedge_overlay_only_while_positioned() {
+        let (base, index_candles) = hedge_fixture();
+        assert!(run(base.clone()).unwrap().get("hedge").is_none());
+
+        let mut with = base.clone();
+        with["hedge"] = json!({ "symbol": "NIFTY", "candles": index_candles, "lookback": 20 });
+        let r: BacktestResult = serde_json::from_value(run(with).unwrap()).unwrap();
+        assert!(r.total_trades > 0);
+        let h = r.hedge.unwrap();
+        assert_eq!(h.curve.len(), r.equity_curve.len());
+        assert_eq!(h.stale_index_bars, 0);
+        assert!(h.curve.iter().any(|p| p.hedge_notional < 0.0));
+        // Flat bars carry no hedge, and the curves differ by the hedge P&L.
+        assert_eq!(h.curve[0].hedge_notional, 0.0);
+        let last = h.curve.last().unwrap();
+        assert!((last.hedged_nav - (last.raw_nav + h.hedge_pnl - h.hedge_costs)).abs() < 0.02);
+
+        let mut bad = base;
+        bad["hedge"] = json!({ "candles": [] });
+        assert!(run(bad).is_err());
+    }
+
+    #[test]
+    fn test_hedge_follows_trade_entries_and_exits() {
+        let (mut data, index_candles) = hedge_fixture();
+        data["hedge"] = json!({ "symbol": "NIFTY", "candles": index_candles, "lookback": 20 });
+        let r: BacktestResult = serde_json::from_value(run(data).unwrap()).unwrap();
+        assert!(r.trade_log.len() >= 2);
+        // Hedged from each entry bar's close up to the exit bar, where it comes off.
+        let h = r.hedge.unwrap();
+        for p in &h.curve {
+            let open = r.trade_log.iter().any(|t| t.entry_time <= p.date && p.date < t.exit_time);
+            assert_eq!(p.hedge_notional != 0.0, open, "bar {}", p.date);
+        }
+    }
+
+    #[test]
+    fn test_fixed_point_precision_for_sub_paisa_prices() {
+        let candles: Vec<serde_json::Value> = (0..200).map(|i| {
+            let close = 0.00012 + 0.00002 * (i as f64 * 0.2).sin();
+            json!({ "timestamp": grid_date(i),
+                "open": close, "high": close * 1.01, "low": close * 0.99, "close": close, "volume": 1e12 })
+        }).collect();
+        let input = |precision: serde_json::Value| json!({
+            "strategy": "ema_crossover", "symbol": "SHIB", "initial_capital": 1000.0, "candles": candles,
+            "transaction_costs": { "commission": 0.0, "slippage_bps": 3.0, "stt_pct": 0.0 },
+            "precision": precision,
+        });
+        let float: BacktestResult = serde_json::from_value(run(input(serde_json::Value::Null)).unwrap()).unwrap();
+        assert!(float.trade_log.iter().all(|t| t.entry_price == 0.0), "2-decimal reporting loses these prices");
+
+        let fixed: BacktestResult = serde_json::from_value(run(input(json!({ "tick_size": 0.00000001 }))).unwrap()).unwrap();
+        assert!(!fixed.trade_log.is_empty());
+        for t in &fixed.trade_log {
+            assert!(t.entry_price > 0.0);
+            let ticks = t.exit_price * 1e8;
+            assert!((ticks - ticks.round()).abs() < 1e-6, "exit {} is off the tick grid", t.exit_price);
+            let units = |v: f64| (v * 1e8).round() as i64;
+            assert_eq!(units(t.pnl), units(t.gross_pnl) - units(t.costs));
+        }
+        assert!(run(input(json!({ "tick_size": -1.0 }))).is_err());
+    }
+
+    #[test]
+    fn test_instrument_lots_and_multiplier() {
+        let candles: Vec<serde_json::Value> = (0..120).map(|i| {
+            let close = 22_000.0 + 400.0 * (i as f64 * 0.15).sin();
+            json!({ "timestamp": grid_date(i),
+                "open": close, "high": close + 20.0, "low": close - 20.0, "close": close, "volume": 1e6 })
+        }).collect();
+        let input = |instruments: serde_json::Value| json!({
+            "strategy": "ema_crossover", "symbol": "NIFTYFUT", "initial_capital": 50_000_000.0, "candles": candles,
+            "transaction_costs": { "commission": 0.0, "slippage_bps": 3.0, "stt_pct": 0.0 },
+            "instruments": instruments,
+        });
+        let r: BacktestResult = serde_json::from_value(run(input(json!({
+            "NIFTYFUT": { "lot_size": 75, "tick_size": 0.05, "multiplier": 2, "product_type": "futures" }
+        }))).unwrap()).unwrap();
+        assert!(!r.trade_log.is_empty());
+        for t in &r.trade_log {
+            assert_eq!(t.qty % 75, 0, "qty {} is not whole lots", t.qty);
+            let ticks = t.exit_price / 0.05;
+            assert!((ticks - ticks.round()).abs() < 1e-6, "exit {} is off the tick grid", t.exit_price);
+            let points = if t.side.starts_with("SHORT") { t.entry_price - t.exit_price } else { t.exit_price - t.entry_price };
+            assert!((t.gross_pnl - points * t.qty as f64 * 2.0).abs() < 0.01, "gross P&L should be points × qty × multiplier");
+        }
+        // Other symbols' entries are ignored; invalid ones are rejected.
+        let plain: BacktestResult = serde_json::from_value(run(input(json!({ "BANKNIFTY": { "lot_size": 15 } }))).unwrap()).unwrap();
+        assert!(plain.trade_log.iter().any(|t| t.qty % 75 != 0));
+        assert!(run(input(json!({ "NIFTYFUT": { "multiplier": 0 } }))).is_err());
+    }
+
+    #[test]
+    fn test_auto_slippage_from_candles() {
+        let candles = sine_candles(120, 0.0, 0.0, 0.03);
+        let input = |costs: serde_json::Value| json!({
+            "strategy": "ema_crossover", "symbol": "X", "initial_capital": 1_000_000.0, "candles": candles,
+            "transaction_costs": costs, "auto_slippage": true,
+        });
+        let parsed: Vec<Candle> = serde_json::from_value(json!(candles)).unwrap();
+        let expected = crate::slippage::estimate_from_candles(&parsed, 0, 1.0, None).unwrap().slippage_bps;
+
+        let auto: BacktestResult = serde_json::from_value(run(input(json!({ "commission": 0.0 }))).unwrap()).unwrap();
+        assert!(auto.total_trades > 0);
+        assert!((auto.avg_slippage_bps - round2(expected)).abs() < 0.01, "{} vs {}", auto.avg_slippage_bps, expected);
+        // An explicit figure wins over the estimate.
+        let fixed: BacktestResult = serde_json::from_value(run(input(json!({ "slippage_bps": 1.0 }))).unwrap()).unwrap();
+        assert_eq!(fixed.avg_slippage_bps, 1.0);
+    }
+
+    #[test]
+    fn test_trailing_exits_close_positions() {
+        // Rally, then a slide long enough for the trail to catch it before the crossover does.
+        let candles: Vec<serde_json::Value> = (0..120).map(|i| {
+            let x = i as f64;
+            let close = if i < 30 { 130.0 - x } else if i < 80 { 100.0 + (x - 30.0) * 1.5 } else { 175.0 - (x - 80.0) * 1.5 };
+            json!({ "timestamp": grid_date(i),
+                "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 1e6 })
+        }).collect();
+        let input = |exits: serde_json::Value| json!({
+            "strategy": "sma_crossover", "symbol": "X", "initial_capital": 1_000_000.0, "candles": candles, "exits": exits,
+        });
+        let plain: BacktestResult = serde_json::from_value(run(input(json!(null))).unwrap()).unwrap();
+        assert!(!plain.trade_log.is_empty());
+        assert!(plain.trade_log.iter().all(|t| !t.side.ends_with("_TRAIL")));
+
+        let trailed: BacktestResult = serde_json::from_value(run(input(json!({
+            "chandelier": { "period": 10, "atr_mult": 2.0 }, "break_even_r": 1.0
+        }))).unwrap()).unwrap();
+        let trail_exits: Vec<_> = trailed.trade_log.iter().filter(|t| t.side.ends_with("_TRAIL")).collect();
+        assert!(!trail_exits.is_empty(), "sides: {:?}", trailed.trade_log.iter().map(|t| &t.side).collect::<Vec<_>>());
+        assert!(run(input(json!({ "swing_trail": { "lookback": 0 } }))).is_err());
+    }
+
+    #[test]
+    fn test_scale_out_links_partial_exits() {
+        let candles: Vec<serde_json::Value> = (0..120).map(|i| {
+            let x = i as f64;
+            let close = if i < 30 { 130.0 - x } else if i < 80 { 100.0 + (x - 30.0) * 1.5 } else { 175.0 - (x - 80.0) * 1.5 };
+            json!({ "timestamp": grid_date(i),
+                "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 1e6 })
+        }).collect();
+        let r: BacktestResult = serde_json::from_value(run(json!({
+            "strategy": "sma_crossover", "symbol": "X", "initial_capital": 1_000_000.0, "candles": candles,
+            "exits": { "targets": [{ "at_r": 1.0, "pct": 50 }], "chandelier": { "period": 10, "atr_mult": 2.0 } },
+        })).unwrap()).unwrap();
+        let partial = r.trade_log.iter().find(|t| t.side == "LONG_PARTIAL").expect("no scale-out");
+        let rest: Vec<_> = r.trade_log.iter().filter(|t| t.trade_id == partial.trade_id && t.side != "LONG_PARTIAL").collect();
+        assert_eq!(rest.len(), 1, "the remainder exits once under the same id");
+        assert_eq!(partial.entry_time, rest[0].entry_time);
+        assert_eq!(partial.qty, rest[0].qty, "50% scaled out, 50% trailed");
+        assert!(partial.exit_time <= rest[0].exit_time);
+        // The scale-out and the trailed remainder are one trade in the stats.
+        assert_eq!(r.total_trades, r.trade_log.len() - r.trade_log.iter().filter(|t| t.side == "LONG_PARTIAL").count());
+        // Entries are numbered from 1, one id per entry.
+        let mut ids: Vec<usize> = r.trade_log.iter().map(|t| t.trade_id).collect();
+        ids.dedup();
+        assert_eq!(ids, (1..=ids.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_performance_counts_round_trips_once() {
+        let row = |trade_id: usize, pnl: f64| TradeEntry {
+            symbol: "X".into(), side: "LONG".into(), entry_price: 100.0, exit_price: 100.0, qty: 1,
+            pnl, gross_pnl: pnl, costs: 0.0, entry_time: grid_date(0), exit_time: grid_date(1), trade_id,
+            event: None, strategy_tag: None,
+        };
+        // A scale-out worth 100 and its remainder losing 30 make one winner.
+        let trades = vec![row(1, 100.0), row(1, -30.0), row(2, -50.0)];
+        let perf = performance(&[], &trades, 1000.0, 1020.0, None, &Calendar::nse(), 1.0);
+        assert_eq!(perf.trades, 2);
+        assert_eq!(perf.win_rate, 50.0);
+        assert_eq!(perf.avg_win, 70.0);
+        assert_eq!(perf.profit_factor, 1.4);
+    }
+
+    #[test]
+    fn test_min_turnover_skips_illiquid_entries() {
+        let candles = sine_candles(120, 0.0, 0.0, 0.01);
+        // About ₹5 crore a day.
+        let input = |min: f64| json!({
+            "strategy": "ema_crossover", "symbol": "X", "initial_capital": 1_000_000.0, "candles": candles, "min_turnover": min,
+        });
+        let liquid: BacktestResult = serde_json::from_value(run(input(1e7)).unwrap()).unwrap();
+        assert!(liquid.total_trades > 0);
+        assert_eq!(liquid.liquidity_rejected_trades, 0);
+        let illiquid: BacktestResult = serde_json::from_value(run(input(1e9)).unwrap()).unwrap();
+        assert_eq!(illiquid.total_trades, 0);
+        assert!(illiquid.liquidity_rejected_trades > 0);
+    }
+
+    #[test]
+    fn test_event_blackout_and_tags() {
+        let candles: Vec<serde_json::Value> = (0..120).map(|i| {
+            let close = 1_000.0 + 60.0 * (i as f64 * 0.15).sin();
+            let date = chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap() + chrono::Duration::days(i);
+            json!({ "timestamp": date.to_string(),
+                "open": close, "high": close * 1.01, "low": close * 0.99, "close": close, "volume": 50_000 })
+        }).collect();
+        let input = |extra: serde_json::Value| {
+            let mut v = json!({ "strategy": "ema_crossover", "symbol": "X", "initial_capital": 1_000_000.0, "candles": candles });
+            v.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            v
+        };
+        let plain: BacktestResult = serde_json::from_value(run(input(json!({}))).unwrap()).unwrap();
+        let first_entry = chrono::NaiveDate::parse_from_str(&plain.trade_log[0].entry_time, "%Y-%m-%d").unwrap();
+        assert!(plain.trade_log.iter().all(|t| t.event.is_none()));
+
+        let events = json!([{ "date": (first_entry + chrono::Duration::days(2)).to_string(), "kind": "earnings", "symbol": "X" }]);
+        let tagged: BacktestResult = serde_json::from_value(run(input(json!({ "events": events }))).unwrap()).unwrap();
+        let event = tagged.trade_log[0].event.as_ref().unwrap();
+        assert_eq!((event.kind.as_str(), event.days), ("earnings", -2));
+
+        let blocked: BacktestResult = serde_json::from_value(run(input(json!({
+            "events": events, "event_blackout": { "days_before": 3, "days_after": 0 }
+        }))).unwrap()).unwrap();
+        assert!(blocked.event_blocked_entries > 0);
+        assert!(blocked.trade_log.iter().all(|t| t.entry_time != plain.trade_log[0].entry_time));
+    }
 }
@@ -0,0 +1,317 @@
+//! Price-driven bar types: Renko, range bars and point-and-figure.
+//!
+//! The `bar_transform` command returns the transformed series. Any request
+//! carrying `bar_transform: {...}` next to `candles` (at any depth, like
+//! `candles_file`) has its candles replaced before dispatch, so backtest,
+//! signals and scan run on the transformed bars unchanged.
+//!
+//! Box size is `box_size` (price units), `box_pct` (percent of the first
+//! close) or `atr_period` (latest ATR of the source series).
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{Candle, calc_atr_candles, round2, round4, sanitize_candles};
+
+#[derive(Deserialize, Clone)]
+struct TransformSpec {
+    /// "renko", "range" or "pnf".
+    #[serde(rename = "type")]
+    kind: String,
+    box_size: Option<f64>,
+    box_pct: Option<f64>,
+    atr_period: Option<usize>,
+    /// Boxes against the trend needed to reverse. Defaults: renko 2, pnf 3.
+    reversal: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct TransformInput {
+    candles: Vec<Candle>,
+    #[serde(flatten)]
+    spec: TransformSpec,
+}
+
+#[derive(Serialize, Clone)]
+struct PnfColumn {
+    /// "X" (rising) or "O" (falling).
+    direction: &'static str,
+    top: f64,
+    bottom: f64,
+    boxes: usize,
+    start_timestamp: String,
+    end_timestamp: String,
+}
+
+struct Transformed {
+    box_size: f64,
+    bars: Vec<Candle>,
+    columns: Option<Vec<PnfColumn>>,
+}
+
+/// `bar_transform` command.
+pub fn compute(data: Value) -> Result<Value, String> {
+    let mut input: TransformInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid bar_transform input: {}", e))?;
+    sanitize_candles(&mut input.candles);
+    let out = transform(&input.candles, &input.spec)?;
+    Ok(serde_json::json!({
+        "type": input.spec.kind.to_lowercase(),
+        "box_size": round4(out.box_size),
+        "source_candles": input.candles.len(),
+        "bars_count": out.bars.len(),
+        "bars": out.bars,
+        "columns": out.columns,
+    }))
+}
+
+/// Replace `candles` wherever a sibling `bar_transform` spec is present.
+pub fn resolve(data: &mut Value) -> Result<(), String> {
+    match data {
+        Value::Object(map) => {
+            if let Some(spec) = map.remove("bar_transform") {
+                let spec: TransformSpec = serde_json::from_value(spec)
+                    .map_err(|e| format!("Invalid bar_transform spec: {}", e))?;
+                let candles = map.get("candles").ok_or("bar_transform requires candles")?.clone();
+                let mut candles: Vec<Candle> = serde_json::from_value(candles)
+                    .map_err(|e| format!("Invalid candles for bar_transform: {}", e))?;
+                sanitize_candles(&mut candles);
+                let bars = transform(&candles, &spec)?.bars;
+                map.insert("candles".to_string(),
+                    serde_json::to_value(bars).map_err(|e| format!("Serialization error: {}", e))?);
+            }
+            map.values_mut().try_for_each(resolve)
+        }
+        Value::Array(items) => items.iter_mut().try_for_each(resolve),
+        _ => Ok(()),
+    }
+}
+
+fn transform(candles: &[Candle], spec: &TransformSpec) -> Result<Transformed, String> {
+    if candles.is_empty() {
+        return Err("No candles provided".to_string());
+    }
+    let box_size = match (spec.box_size, spec.box_pct, spec.atr_period) {
+        (Some(b), _, _) => b,
+        (None, Some(pct), _) => candles[0].close * pct / 100.0,
+        (None, None, Some(period)) => calc_atr_candles(candles, period.max(1)),
+        (None, None, None) => return Err("bar_transform needs box_size, box_pct or atr_period".to_string()),
+    };
+    if !box_size.is_finite() || box_size <= 0.0 {
+        return Err(format!("box size must be positive, got {}", box_size));
+    }
+    match spec.kind.to_lowercase().as_str() {
+        "renko" => Ok(Transformed { box_size, bars: renko(candles, box_size, spec.reversal.unwrap_or(2).max(1)), columns: None }),
+        "range" => Ok(Transformed { box_size, bars: range_bars(candles, box_size), columns: None }),
+        "pnf" | "point_and_figure" => {
+            let columns = pnf(candles, box_size, spec.reversal.unwrap_or(3).max(1));
+            let bars = columns.iter().map(|c| {
+                let (open, close) = if c.direction == "X" { (c.bottom, c.top) } else { (c.top, c.bottom) };
+                Candle { timestamp: c.end_timestamp.clone(), open, high: c.top, low: c.bottom, close, volume: 0.0 }
+            }).collect();
+            Ok(Transformed { box_size, bars, columns: Some(columns) })
+        }
+        other => Err(format!("Unknown bar_transform type '{}' (renko, range, pnf)", other)),
+    }
+}
+
+/// Close-based Renko. A brick prints when the close clears the last brick by
+/// one box in the trend direction, or by `reversal` boxes against it. Volume
+/// since the previous brick goes to the first brick a candle prints.
+fn renko(candles: &[Candle], size: f64, reversal: usize) -> Vec<Candle> {
+    let mut bricks: Vec<Candle> = Vec::new();
+    let base = (candles[0].close / size).floor() * size;
+    let (mut top, mut bottom) = (base, base);
+    let mut up: Option<bool> = None;
+    let mut pending_volume = 0.0;
+    let rev = reversal as f64;
+
+    for c in candles {
+        pending_volume += c.volume;
+        loop {
+            let brick = if c.close >= top + size && up != Some(false) || (up == Some(false) && c.close >= bottom + rev * size) {
+                let open = if up == Some(false) { bottom + (rev - 1.0) * size } else { top };
+                Some((open, open + size, true))
+            } else if c.close <= bottom - size && up != Some(true) || (up == Some(true) && c.close <= top - rev * size) {
+                let open = if up == Some(true) { top - (rev - 1.0) * size } else { bottom };
+                Some((open, open - size, false))
+            } else {
+                None
+            };
+            let Some((open, close, rising)) = brick else { break };
+            bricks.push(Candle {
+                timestamp: c.timestamp.clone(),
+                open: round4(open),
+                high: round4(open.max(close)),
+                low: round4(open.min(close)),
+                close: round4(close),
+                volume: round2(pending_volume),
+            });
+            pending_volume = 0.0;
+            top = open.max(close);
+            bottom = open.min(close);
+            up = Some(rising);
+        }
+    }
+    bricks
+}
+
+/// Range bars: each bar spans exactly `size` from high to low. The intrabar
+/// path is assumed open → low → high → close on up candles and
+/// open → high → low → close on down candles.
+fn range_bars(candles: &[Candle], size: f64) -> Vec<Candle> {
+    let mut bars: Vec<Candle> = Vec::new();
+    let first = candles[0].open;
+    let mut cur = Candle { timestamp: candles[0].timestamp.clone(), open: first, high: first, low: first, close: first, volume: 0.0 };
+
+    for c in candles {
+        let path = if c.close >= c.open { [c.open, c.low, c.high, c.close] } else { [c.open, c.high, c.low, c.close] };
+        let per_point = c.volume / 4.0;
+        for price in path {
+            // A long move can complete several bars within one leg.
+            loop {
+                let hi = cur.high.max(price);
+                let lo = cur.low.min(price);
+                if hi - lo < size - 1e-9 {
+                    cur.high = hi;
+                    cur.low = lo;
+                    cur.close = price;
+                    break;
+                }
+                let rising = price > cur.close;
+                let boundary = if rising { cur.low + size } else { cur.high - size };
+                cur.high = cur.high.max(boundary);
+                cur.low = cur.low.min(boundary);
+                cur.close = boundary;
+                cur.timestamp = c.timestamp.clone();
+                bars.push(round_bar(&cur));
+                cur = Candle { timestamp: c.timestamp.clone(), open: boundary, high: boundary, low: boundary, close: boundary, volume: 0.0 };
+                if (price - boundary).abs() < 1e-9 {
+                    break;
+                }
+            }
+            cur.volume += per_point;
+        }
+    }
+    bars
+}
+
+fn round_bar(c: &Candle) -> Candle {
+    Candle {
+        timestamp: c.timestamp.clone(),
+        open: round4(c.open),
+        high: round4(c.high),
+        low: round4(c.low),
+        close: round4(c.close),
+        volume: round2(c.volume),
+    }
+}
+
+/// Close-based point-and-figure with `reversal`-box reversals.
+fn pnf(candles: &[Candle], size: f64, reversal: usize) -> Vec<PnfColumn> {
+    let mut columns: Vec<PnfColumn> = Vec::new();
+    let level = |p: f64| (p / size).floor();
+    let start = level(candles[0].close);
+    let (mut top, mut bottom) = (start, start);
+    let mut rising: Option<bool> = None;
+    let mut start_ts = candles[0].timestamp.clone();
+    let rev = reversal as f64;
+
+    let push = |columns: &mut Vec<PnfColumn>, rising: bool, top: f64, bottom: f64, start: &str, end: &str| {
+        columns.push(PnfColumn {
+            direction: if rising { "X" } else { "O" },
+            top: round4(top * size),
+            bottom: round4(bottom * size),
+            boxes: (top - bottom).round() as usize,
+            start_timestamp: start.to_string(),
+            end_timestamp: end.to_string(),
+        });
+    };
+
+    let mut end_ts = start_ts.clone();
+    for c in candles.iter().skip(1) {
+        let l = level(c.close);
+        match rising {
+            None => {
+                if l >= top + 1.0 { rising = Some(true); top = l; }
+                else if l <= bottom - 1.0 { rising = Some(false); bottom = l; }
+                end_ts = c.timestamp.clone();
+            }
+            Some(true) => {
+                if l > top { top = l; end_ts = c.timestamp.clone(); }
+                else if l <= top - rev {
+                    push(&mut columns, true, top, bottom, &start_ts, &end_ts);
+                    start_ts = c.timestamp.clone();
+                    end_ts = start_ts.clone();
+                    bottom = l;
+                    top -= 1.0;
+                    rising = Some(false);
+                }
+            }
+            Some(false) => {
+                if l < bottom { bottom = l; end_ts = c.timestamp.clone(); }
+                else if l >= bottom + rev {
+                    push(&mut columns, false, top, bottom, &start_ts, &end_ts);
+                    start_ts = c.timestamp.clone();
+                    end_ts = start_ts.clone();
+                    top = l;
+                    bottom += 1.0;
+                    rising = Some(true);
+                }
+            }
+        }
+    }
+    if let Some(r) = rising {
+        push(&mut columns, r, top, bottom, &start_ts, &end_ts);
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn closes(values: &[f64]) -> Vec<Value> {
+        values.iter().enumerate().map(|(i, &c)| json!({
+            "timestamp": format!("2024-01-{:02}", i + 1), "open": c, "high": c, "low": c, "close": c, "volume": 100.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_renko_bricks_and_reversal() {
+        let result = compute(json!({
+            "candles": closes(&[100.0, 101.5, 103.2, 102.1, 100.9, 99.5]), "type": "renko", "box_size": 1.0
+        })).unwrap();
+        let bars = result["bars"].as_array().unwrap();
+        let pairs: Vec<(f64, f64)> = bars.iter().map(|b| (b["open"].as_f64().unwrap(), b["close"].as_f64().unwrap())).collect();
+        // Up from the 100 base to 103, then a two-box reversal prints 102→101 at 100.9 and 101→100 at 99.5.
+        assert_eq!(pairs, vec![(100.0, 101.0), (101.0, 102.0), (102.0, 103.0), (102.0, 101.0), (101.0, 100.0)]);
+        assert_eq!(bars[0]["volume"], 200.0);
+    }
+
+    #[test]
+    fn test_range_bars_have_fixed_range() {
+        let candles = vec![json!({ "timestamp": "t1", "open": 100.0, "high": 104.2, "low": 99.0, "close": 104.0, "volume": 400.0 })];
+        let result = compute(json!({ "candles": candles, "type": "range", "box_size": 2.0 })).unwrap();
+        let bars = result["bars"].as_array().unwrap();
+        assert!(!bars.is_empty());
+        for b in bars {
+            let range = b["high"].as_f64().unwrap() - b["low"].as_f64().unwrap();
+            assert!((range - 2.0).abs() < 1e-6, "range {}", range);
+        }
+    }
+
+    #[test]
+    fn test_pnf_columns_and_resolve() {
+        let series = closes(&[100.0, 103.0, 105.0, 104.0, 101.0, 100.0, 104.0]);
+        let result = compute(json!({ "candles": series, "type": "pnf", "box_size": 1.0, "reversal": 3 })).unwrap();
+        let dirs: Vec<&str> = result["columns"].as_array().unwrap().iter().map(|c| c["direction"].as_str().unwrap()).collect();
+        assert_eq!(dirs, ["X", "O", "X"]);
+        assert_eq!(result["columns"][0]["top"], 105.0);
+
+        let mut req = json!({ "candles": series, "bar_transform": { "type": "renko", "box_pct": 1.0 } });
+        resolve(&mut req).unwrap();
+        assert!(req.get("bar_transform").is_none());
+        assert!(req["candles"].as_array().unwrap().len() > 1);
+    }
+}
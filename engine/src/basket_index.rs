@@ -0,0 +1,250 @@
+//! Custom basket / sector index from member candles.
+//!
+//! Members are aligned on the timestamps they all traded and the index holds
+//! a number of units of each, scaled so it opens at `base_value`:
+//! - `price`: one unit of every member (a price-weighted average);
+//! - `cap`: units proportional to `shares` (or `market_cap` at the first
+//!   close);
+//! - `equal` / `custom`: units giving equal or the given `weight`s, reset at
+//!   the first bar of each `rebalance` period ("none", "daily", "weekly",
+//!   "monthly") without a jump in the index.
+//!
+//! Index bars are the unit-weighted sums of member bars, so high and low
+//! bound the true intrabar extremes. The candles come back in the usual
+//! shape for `scan`, `backtest` and the risk commands.
+
+use std::collections::BTreeMap;
+use chrono::{Datelike, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{parse_timestamp, round2, round4, sanitize_candles, Candle};
+
+#[derive(Deserialize)]
+struct BasketConfig {
+    members: Vec<Member>,
+    #[serde(default = "default_weighting")]
+    weighting: String,
+    #[serde(default = "default_rebalance")]
+    rebalance: String,
+    #[serde(default = "default_base")]
+    base_value: f64,
+}
+
+fn default_weighting() -> String { "equal".to_string() }
+fn default_rebalance() -> String { "none".to_string() }
+fn default_base() -> f64 { 1000.0 }
+
+#[derive(Deserialize)]
+struct Member {
+    symbol: String,
+    candles: Vec<Candle>,
+    #[serde(default)]
+    weight: Option<f64>,
+    #[serde(default, alias = "shares_outstanding")]
+    shares: Option<f64>,
+    #[serde(default)]
+    market_cap: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct MemberView {
+    symbol: String,
+    initial_weight: f64,
+    final_weight: f64,
+    return_pct: f64,
+}
+
+#[derive(Serialize)]
+struct BasketResult {
+    weighting: String,
+    rebalance: String,
+    base_value: f64,
+    candles: Vec<Candle>,
+    members: Vec<MemberView>,
+    return_pct: f64,
+    rebalances: usize,
+    rows: usize,
+    /// Member bars on timestamps missing from some other member.
+    dropped_rows: usize,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let mut config: BasketConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid basket_index config: {}", e))?;
+    if config.members.is_empty() {
+        return Err("At least one member required".to_string());
+    }
+    if !(config.base_value.is_finite() && config.base_value > 0.0) {
+        return Err("base_value must be positive".to_string());
+    }
+    if !matches!(config.weighting.as_str(), "equal" | "cap" | "price" | "custom") {
+        return Err(format!("Unknown weighting '{}' (equal, cap, price, custom)", config.weighting));
+    }
+    if !matches!(config.rebalance.as_str(), "none" | "daily" | "weekly" | "monthly") {
+        return Err(format!("Unknown rebalance '{}' (none, daily, weekly, monthly)", config.rebalance));
+    }
+
+    // Timestamp -> one bar per member, kept only where every member traded.
+    let n = config.members.len();
+    let mut rows: BTreeMap<NaiveDateTime, Vec<Option<Candle>>> = BTreeMap::new();
+    let mut total_bars = 0;
+    for (i, m) in config.members.iter_mut().enumerate() {
+        sanitize_candles(&mut m.candles);
+        for c in &m.candles {
+            let ts = parse_timestamp(&c.timestamp)
+                .ok_or_else(|| format!("members[{}] ({}): invalid timestamp '{}'", i, m.symbol, c.timestamp))?;
+            rows.entry(ts).or_insert_with(|| vec![None; n])[i] = Some(c.clone());
+            total_bars += 1;
+        }
+    }
+    let rows: Vec<(NaiveDateTime, Vec<Candle>)> = rows.into_iter()
+        .filter_map(|(ts, bars)| bars.into_iter().collect::<Option<Vec<Candle>>>().map(|b| (ts, b)))
+        .collect();
+    let Some((_, first)) = rows.first() else {
+        return Err("Members share no timestamps".to_string());
+    };
+    if first.iter().any(|c| c.close <= 0.0) {
+        return Err("Member closes must be positive on the first shared bar".to_string());
+    }
+
+    let targets: Vec<f64> = match config.weighting.as_str() {
+        "custom" => {
+            let w: Vec<f64> = config.members.iter().enumerate()
+                .map(|(i, m)| m.weight.filter(|w| *w > 0.0).ok_or_else(|| format!("members[{}] ({}): custom weighting needs a positive weight", i, m.symbol)))
+                .collect::<Result<_, _>>()?;
+            let sum: f64 = w.iter().sum();
+            w.iter().map(|x| x / sum).collect()
+        }
+        _ => vec![1.0 / n as f64; n],
+    };
+    // Units at the base, before scaling to base_value.
+    let raw_units: Vec<f64> = match config.weighting.as_str() {
+        "price" => vec![1.0; n],
+        "cap" => config.members.iter().zip(first).enumerate().map(|(i, (m, c))| {
+            m.shares.or(m.market_cap.map(|cap| cap / c.close)).filter(|s| *s > 0.0)
+                .ok_or_else(|| format!("members[{}] ({}): cap weighting needs shares or market_cap", i, m.symbol))
+        }).collect::<Result<_, _>>()?,
+        _ => targets.iter().zip(first).map(|(w, c)| w / c.close).collect(),
+    };
+    let base_level: f64 = raw_units.iter().zip(first).map(|(u, c)| u * c.close).sum();
+    let mut units: Vec<f64> = raw_units.iter().map(|u| u * config.base_value / base_level).collect();
+    let weights_at = |units: &[f64], bars: &[Candle]| -> Vec<f64> {
+        let total: f64 = units.iter().zip(bars).map(|(u, c)| u * c.close).sum();
+        units.iter().zip(bars).map(|(u, c)| if total > 0.0 { u * c.close / total } else { 0.0 }).collect()
+    };
+    let initial_weights = weights_at(&units, first);
+
+    let rebalancing = config.rebalance != "none" && matches!(config.weighting.as_str(), "equal" | "custom");
+    let period = |ts: &NaiveDateTime| match config.rebalance.as_str() {
+        "daily" => (ts.year(), ts.ordinal()),
+        "weekly" => (ts.iso_week().year(), ts.iso_week().week()),
+        _ => (ts.year(), ts.month()),
+    };
+    let mut candles = Vec::with_capacity(rows.len());
+    let mut rebalances = 0;
+    for (k, (ts, bars)) in rows.iter().enumerate() {
+        if rebalancing && k > 0 && period(ts) != period(&rows[k - 1].0) {
+            // Reset to target weights at the previous close, so the level carries over.
+            let prev = &rows[k - 1].1;
+            let level: f64 = units.iter().zip(prev).map(|(u, c)| u * c.close).sum();
+            units = targets.iter().zip(prev).map(|(w, c)| if c.close > 0.0 { w * level / c.close } else { 0.0 }).collect();
+            rebalances += 1;
+        }
+        let sum = |f: fn(&Candle) -> f64| units.iter().zip(bars).map(|(u, c)| u * f(c)).sum::<f64>();
+        candles.push(Candle {
+            timestamp: bars[0].timestamp.clone(),
+            open: round4(sum(|c| if c.open > 0.0 { c.open } else { c.close })),
+            high: round4(sum(|c| c.high)),
+            low: round4(sum(|c| c.low)),
+            close: round4(sum(|c| c.close)),
+            volume: bars.iter().map(|c| c.volume).sum(),
+        });
+    }
+
+    let last = &rows[rows.len() - 1].1;
+    let final_weights = weights_at(&units, last);
+    let members = config.members.iter().enumerate().map(|(i, m)| MemberView {
+        symbol: m.symbol.clone(),
+        initial_weight: round4(initial_weights[i]),
+        final_weight: round4(final_weights[i]),
+        return_pct: round2((last[i].close / first[i].close - 1.0) * 100.0),
+    }).collect();
+    let close = candles.last().map_or(config.base_value, |c| c.close);
+    let result = BasketResult {
+        return_pct: round2((close / config.base_value - 1.0) * 100.0),
+        rows: candles.len(),
+        dropped_rows: total_bars - candles.len() * n,
+        weighting: config.weighting,
+        rebalance: config.rebalance,
+        base_value: config.base_value,
+        candles,
+        members,
+        rebalances,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn member(symbol: &str, closes: &[f64], start_day: usize) -> Value {
+        let candles: Vec<Value> = closes.iter().enumerate().map(|(i, &c)| json!({
+            "timestamp": format!("2024-01-{:02}", start_day + i), "open": c, "high": c * 1.01, "low": c * 0.99, "close": c, "volume": 100.0
+        })).collect();
+        json!({ "symbol": symbol, "candles": candles })
+    }
+
+    #[test]
+    fn test_weightings_from_the_same_members() {
+        let a = member("A", &[100.0, 110.0, 121.0], 1);
+        let b = member("B", &[50.0, 50.0, 45.0], 1);
+        let run = |weighting: &str, extra: Value| {
+            let mut members = json!([a.clone(), b.clone()]);
+            for (m, e) in members.as_array_mut().unwrap().iter_mut().zip(extra.as_array().unwrap()) {
+                m.as_object_mut().unwrap().extend(e.as_object().unwrap().clone());
+            }
+            compute(json!({ "members": members, "weighting": weighting, "base_value": 100.0 })).unwrap()
+        };
+        // Equal: half in each at the start, then buy and hold.
+        let eq = run("equal", json!([{}, {}]));
+        assert_eq!(eq["candles"][0]["close"], 100.0);
+        assert_eq!(eq["candles"][2]["close"], 60.5 + 45.0);
+        assert_eq!(eq["members"][0]["initial_weight"], 0.5);
+        assert_eq!(eq["members"][0]["return_pct"], 21.0);
+        assert_eq!(eq["candles"][1]["volume"], 200.0);
+        // Price: the dearer stock dominates.
+        let px = run("price", json!([{}, {}]));
+        assert_eq!(px["members"][0]["initial_weight"], round4(100.0 / 150.0));
+        assert_eq!(px["candles"][2]["close"], round4((121.0 + 45.0) / 150.0 * 100.0));
+        // Cap: shares, or market cap at the first close.
+        let cap = run("cap", json!([{ "shares": 1.0 }, { "market_cap": 150.0 }]));
+        assert_eq!(cap["members"][1]["initial_weight"], 0.6);
+        let custom = run("custom", json!([{ "weight": 3.0 }, { "weight": 1.0 }]));
+        assert_eq!(custom["members"][0]["initial_weight"], 0.75);
+        assert!(compute(json!({ "members": [a.clone(), b.clone()], "weighting": "cap" })).unwrap_err().contains("shares"));
+        assert!(compute(json!({ "members": [a, b], "weighting": "median" })).is_err());
+    }
+
+    #[test]
+    fn test_alignment_and_rebalancing() {
+        // B starts a day late and skips the 4th; only shared dates count.
+        let a = member("A", &[100.0, 100.0, 200.0, 200.0, 200.0], 1);
+        let mut b = member("B", &[100.0, 100.0, 100.0, 100.0], 2);
+        b["candles"].as_array_mut().unwrap().remove(2);
+        let result = compute(json!({ "members": [a.clone(), b.clone()], "base_value": 1000.0 })).unwrap();
+        let ts: Vec<&str> = result["candles"].as_array().unwrap().iter().map(|c| c["timestamp"].as_str().unwrap()).collect();
+        assert_eq!(ts, ["2024-01-02", "2024-01-03", "2024-01-05"]);
+        assert_eq!(result["dropped_rows"], 2);
+        assert_eq!(result["candles"][2]["close"], 1500.0);
+        assert_eq!(result["members"][0]["final_weight"], round4(2.0 / 3.0));
+
+        // Daily rebalancing holds the weights at 50/50 going into each bar.
+        let daily = compute(json!({ "members": [a, b], "base_value": 1000.0, "rebalance": "daily" })).unwrap();
+        assert_eq!(daily["rebalances"], 2);
+        assert_eq!(daily["candles"][2]["close"], 1500.0);
+        assert_eq!(daily["members"][0]["final_weight"], 0.5);
+        assert!(compute(json!({ "members": [member("A", &[1.0], 1), member("B", &[1.0], 9)] })).unwrap_err().contains("share no"));
+    }
+}
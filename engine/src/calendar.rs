@@ -0,0 +1,316 @@
+//! Exchange trading calendar: weekends, published holidays and special
+//! (muhurat) sessions.
+//!
+//! The NSE list ships in `data/nse_calendar.json` (BSE follows the same
+//! holidays). Requests can pass `calendar: {...}` to replace or extend it,
+//! e.g. for years the embedded list does not cover yet. Outside the covered
+//! years only weekends are closed.
+
+use std::collections::BTreeMap;
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::round2;
+
+/// Annualization fallback for years without a published holiday list.
+pub const DEFAULT_TRADING_DAYS: f64 = 252.0;
+
+/// Request-level calendar override.
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+pub struct CalendarSpec {
+    /// "NSE" / "BSE" for the embedded list, "none" for weekends only.
+    pub exchange: Option<String>,
+    /// Replaces the embedded holiday list ("YYYY-MM-DD").
+    pub holidays: Option<Vec<String>>,
+    /// Added on top of the embedded (or replaced) list.
+    #[serde(default)]
+    pub extra_holidays: Vec<String>,
+    #[serde(default)]
+    pub special_sessions: Vec<SpecialSession>,
+    pub session_open: Option<String>,
+    pub session_close: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SpecialSession {
+    pub date: String,
+    pub open: String,
+    pub close: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct EmbeddedCalendar {
+    exchange: String,
+    session_open: String,
+    session_close: String,
+    covered_years: Vec<i32>,
+    holidays: Vec<EmbeddedHoliday>,
+    special_sessions: Vec<SpecialSession>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddedHoliday {
+    date: String,
+    name: String,
+}
+
+#[derive(Clone)]
+pub struct Calendar {
+    pub exchange: String,
+    pub session_open: NaiveTime,
+    pub session_close: NaiveTime,
+    holidays: BTreeMap<NaiveDate, String>,
+    special_sessions: BTreeMap<NaiveDate, SpecialSession>,
+    covered_years: Vec<i32>,
+}
+
+static NSE: Lazy<Calendar> = Lazy::new(|| {
+    let raw: EmbeddedCalendar = serde_json::from_str(include_str!("../data/nse_calendar.json"))
+        .expect("embedded nse_calendar.json is valid");
+    let mut calendar = Calendar::weekends_only(&raw.exchange);
+    calendar.session_open = parse_time(&raw.session_open).expect("embedded session_open");
+    calendar.session_close = parse_time(&raw.session_close).expect("embedded session_close");
+    calendar.covered_years = raw.covered_years;
+    for h in raw.holidays {
+        calendar.holidays.insert(parse_date(&h.date).expect("embedded holiday date"), h.name);
+    }
+    for s in raw.special_sessions {
+        calendar.special_sessions.insert(parse_date(&s.date).expect("embedded session date"), s);
+    }
+    calendar
+});
+
+fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", s))
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M").map_err(|_| format!("Invalid time '{}', expected HH:MM", s))
+}
+
+impl Calendar {
+    fn weekends_only(exchange: &str) -> Self {
+        Calendar {
+            exchange: exchange.to_string(),
+            session_open: NaiveTime::from_hms_opt(9, 15, 0).unwrap_or_default(),
+            session_close: NaiveTime::from_hms_opt(15, 30, 0).unwrap_or_default(),
+            holidays: BTreeMap::new(),
+            special_sessions: BTreeMap::new(),
+            covered_years: Vec::new(),
+        }
+    }
+
+    pub fn nse() -> Self {
+        NSE.clone()
+    }
+
+    /// The embedded NSE calendar with `spec` applied on top.
+    pub fn from_spec(spec: Option<&CalendarSpec>) -> Result<Self, String> {
+        let Some(spec) = spec else { return Ok(Self::nse()) };
+        let mut calendar = match spec.exchange.as_deref().map(|e| e.to_uppercase()) {
+            None => Self::nse(),
+            Some(e) if e == "NSE" || e == "BSE" => Calendar { exchange: e, ..Self::nse() },
+            Some(e) if e == "NONE" => Self::weekends_only("none"),
+            Some(e) => return Err(format!("Unknown calendar exchange '{}' (NSE, BSE or none)", e)),
+        };
+        if let Some(holidays) = &spec.holidays {
+            calendar.holidays.clear();
+            calendar.covered_years.clear();
+            for d in holidays {
+                let date = parse_date(d)?;
+                calendar.holidays.insert(date, "Holiday".to_string());
+                if !calendar.covered_years.contains(&date.year()) {
+                    calendar.covered_years.push(date.year());
+                }
+            }
+        }
+        for d in &spec.extra_holidays {
+            calendar.holidays.insert(parse_date(d)?, "Holiday".to_string());
+        }
+        for s in &spec.special_sessions {
+            parse_time(&s.open)?;
+            parse_time(&s.close)?;
+            calendar.special_sessions.insert(parse_date(&s.date)?, s.clone());
+        }
+        if let Some(open) = &spec.session_open {
+            calendar.session_open = parse_time(open)?;
+        }
+        if let Some(close) = &spec.session_close {
+            calendar.session_close = parse_time(close)?;
+        }
+        if calendar.session_close <= calendar.session_open {
+            return Err("session_close must be after session_open".to_string());
+        }
+        Ok(calendar)
+    }
+
+    /// A normal full session: a weekday that is not a holiday.
+    pub fn is_regular_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains_key(&date)
+    }
+
+    /// Regular day or a special session such as muhurat trading.
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        self.is_regular_day(date) || self.special_sessions.contains_key(&date)
+    }
+
+    pub fn holiday_name(&self, date: NaiveDate) -> Option<&str> {
+        self.holidays.get(&date).map(|s| s.as_str())
+    }
+
+    pub fn covers(&self, year: i32) -> bool {
+        self.covered_years.contains(&year)
+    }
+
+    /// Regular trading days in `[start, end]`.
+    pub fn trading_days_between(&self, start: NaiveDate, end: NaiveDate) -> usize {
+        start.iter_days().take_while(|d| *d <= end).filter(|d| self.is_regular_day(*d)).count()
+    }
+
+    /// Regular sessions in `year`, or [`DEFAULT_TRADING_DAYS`] when the
+    /// holiday list does not cover it.
+    pub fn trading_days_in_year(&self, year: i32) -> f64 {
+        match (NaiveDate::from_ymd_opt(year, 1, 1), NaiveDate::from_ymd_opt(year, 12, 31)) {
+            (Some(a), Some(b)) if self.covers(year) => self.trading_days_between(a, b) as f64,
+            _ => DEFAULT_TRADING_DAYS,
+        }
+    }
+
+    /// Average sessions per year over the years `start..=end` touch; used in
+    /// place of a flat 252 when annualizing.
+    pub fn trading_days_per_year(&self, start: NaiveDate, end: NaiveDate) -> f64 {
+        let years: Vec<i32> = (start.year()..=end.year().max(start.year())).collect();
+        years.iter().map(|y| self.trading_days_in_year(*y)).sum::<f64>() / years.len() as f64
+    }
+
+    /// Latest regular session on or before `date` — where an expiry that
+    /// falls on a holiday moves to.
+    pub fn on_or_before(&self, date: NaiveDate) -> NaiveDate {
+        let mut d = date;
+        while !self.is_regular_day(d) {
+            d -= Duration::days(1);
+        }
+        d
+    }
+
+    pub fn next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut d = date + Duration::days(1);
+        while !self.is_regular_day(d) {
+            d += Duration::days(1);
+        }
+        d
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarInput {
+    start: String,
+    end: String,
+    /// Optional single date to classify.
+    date: Option<String>,
+    #[serde(default)]
+    calendar: Option<CalendarSpec>,
+}
+
+#[derive(Serialize)]
+struct HolidayEntry {
+    date: String,
+    weekday: String,
+    name: String,
+}
+
+/// `calendar` command: trading days, holidays and special sessions in a range.
+pub fn compute(data: Value) -> Result<Value, String> {
+    let input: CalendarInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid calendar input: {}", e))?;
+    let calendar = Calendar::from_spec(input.calendar.as_ref())?;
+    let (start, end) = (parse_date(&input.start)?, parse_date(&input.end)?);
+    if end < start {
+        return Err("end must not be before start".to_string());
+    }
+    if (end - start).num_days() > 366 * 50 {
+        return Err("calendar range is limited to 50 years".to_string());
+    }
+
+    let holidays: Vec<HolidayEntry> = calendar
+        .holidays
+        .range(start..=end)
+        .filter(|(d, _)| !matches!(d.weekday(), Weekday::Sat | Weekday::Sun))
+        .map(|(d, name)| HolidayEntry { date: d.to_string(), weekday: d.weekday().to_string(), name: name.clone() })
+        .collect();
+    let special: Vec<&SpecialSession> = calendar.special_sessions.range(start..=end).map(|(_, s)| s).collect();
+    let uncovered: Vec<i32> = (start.year()..=end.year()).filter(|y| !calendar.covers(*y)).collect();
+
+    let day = match &input.date {
+        Some(d) => {
+            let date = parse_date(d)?;
+            Some(serde_json::json!({
+                "date": date.to_string(),
+                "is_trading_day": calendar.is_trading_day(date),
+                "is_regular_session": calendar.is_regular_day(date),
+                "holiday": calendar.holiday_name(date),
+                "special_session": calendar.special_sessions.get(&date),
+                "previous_trading_day": calendar.on_or_before(date - Duration::days(1)).to_string(),
+                "next_trading_day": calendar.next_trading_day(date).to_string(),
+            }))
+        }
+        None => None,
+    };
+
+    Ok(serde_json::json!({
+        "exchange": calendar.exchange,
+        "start": start.to_string(),
+        "end": end.to_string(),
+        "session_open": calendar.session_open.format("%H:%M").to_string(),
+        "session_close": calendar.session_close.format("%H:%M").to_string(),
+        "trading_days": calendar.trading_days_between(start, end),
+        "trading_days_per_year": round2(calendar.trading_days_per_year(start, end)),
+        "holidays": holidays,
+        "special_sessions": special,
+        "uncovered_years": uncovered,
+        "date": day,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn d(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    #[test]
+    fn test_embedded_nse_calendar() {
+        let cal = Calendar::nse();
+        assert!(!cal.is_trading_day(d("2024-01-26")));
+        assert!(cal.is_trading_day(d("2024-01-25")));
+        assert!(!cal.is_trading_day(d("2024-01-27")), "Saturday");
+        // Muhurat trading on a holiday.
+        assert!(!cal.is_regular_day(d("2024-11-01")));
+        assert!(cal.is_trading_day(d("2024-11-01")));
+        assert_eq!(cal.trading_days_in_year(2024), 245.0);
+        assert_eq!(cal.trading_days_in_year(2040), DEFAULT_TRADING_DAYS);
+        // Holi on Friday 2025-03-14 moves an expiry back to Thursday.
+        assert_eq!(cal.on_or_before(d("2025-03-14")), d("2025-03-13"));
+    }
+
+    #[test]
+    fn test_calendar_command_with_overrides() {
+        let result = compute(json!({
+            "start": "2030-01-01", "end": "2030-01-31", "date": "2030-01-15",
+            "calendar": { "extra_holidays": ["2030-01-15"] }
+        })).unwrap();
+        assert_eq!(result["date"]["is_trading_day"], false);
+        assert_eq!(result["date"]["next_trading_day"], "2030-01-16");
+        assert_eq!(result["holidays"][0]["date"], "2030-01-15");
+        assert_eq!(result["uncovered_years"], json!([2030]));
+        // 23 weekdays in January 2030, one of them a holiday.
+        assert_eq!(result["trading_days"], 22);
+        assert!(compute(json!({ "start": "2024-01-01", "end": "2024-01-02", "calendar": { "exchange": "LSE" } })).is_err());
+    }
+}
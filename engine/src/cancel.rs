@@ -0,0 +1,116 @@
+//! Cooperative cancellation for long-running commands.
+//!
+//! A `CancelToken` trips when its deadline passes, when a `cancel` request
+//! names its request id, or when the process receives a shutdown signal.
+//! Long loops (optimize, walk_forward) poll it between units of work and
+//! return what they have finished so far with `cancelled: true`.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+static IN_FLIGHT: Lazy<DashMap<String, CancelToken>> = Lazy::new(DashMap::new);
+
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new(timeout_ms: Option<u64>) -> Self {
+        CancelToken {
+            flag: Arc::new(AtomicBool::new(false)),
+            deadline: timeout_ms.map(|ms| Instant::now() + Duration::from_millis(ms)),
+        }
+    }
+
+    /// A token that only trips on process shutdown.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    /// Why the token tripped: "cancelled", "timeout" or "shutdown".
+    pub fn reason(&self) -> Option<&'static str> {
+        if self.flag.load(Ordering::SeqCst) {
+            Some("cancelled")
+        } else if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            Some("timeout")
+        } else if SHUTDOWN.load(Ordering::SeqCst) {
+            Some("shutdown")
+        } else {
+            None
+        }
+    }
+}
+
+/// Registration of an in-flight request; removed from the registry on drop.
+pub struct InFlight(String);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.remove(&self.0);
+    }
+}
+
+/// Make `token` reachable by `cancel_request(id)` until the guard drops.
+pub fn track(id: &str, token: &CancelToken) -> InFlight {
+    IN_FLIGHT.insert(id.to_string(), token.clone());
+    InFlight(id.to_string())
+}
+
+/// Trip the token of an in-flight request. Returns false if no such request.
+pub fn cancel_request(id: &str) -> bool {
+    match IN_FLIGHT.get(id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn in_flight_ids() -> Vec<String> {
+    IN_FLIGHT.iter().map(|e| e.key().clone()).collect()
+}
+
+/// Trip every token, current and future. Used by the SIGTERM/Ctrl-C handler.
+pub fn request_shutdown() {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_trips() {
+        let token = CancelToken::new(Some(0));
+        std::thread::sleep(Duration::from_millis(2));
+        assert_eq!(token.reason(), Some("timeout"));
+        assert!(!CancelToken::new(Some(60_000)).is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_by_id() {
+        let token = CancelToken::new(None);
+        {
+            let _guard = track("cancel-test-1", &token);
+            assert!(in_flight_ids().contains(&"cancel-test-1".to_string()));
+            assert!(cancel_request("cancel-test-1"));
+        }
+        assert_eq!(token.reason(), Some("cancelled"));
+        assert!(!cancel_request("cancel-test-1"), "guard drop should unregister");
+    }
+}
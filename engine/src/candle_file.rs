@@ -0,0 +1,474 @@
+//! File-based candle input.
+//!
+//! Any request may carry `candles_file: {"path", "format", "columns", ...}`
+//! in place of an inline `candles` array. The file is loaded before dispatch
+//! and spliced in as `candles`, so commands never see the difference. Works
+//! at any depth, e.g. per-symbol entries of a scan request. Arrow IPC files
+//! (`.arrow`/`.feather`, or `.arrows` streams) are read column-wise without
+//! going through JSON rows when built with the `arrow` feature.
+
+use serde::Deserialize;
+use serde_json::Value;
+use crate::utils::Candle;
+
+#[derive(Deserialize)]
+struct CandleFileSpec {
+    path: String,
+    /// "csv", "parquet", "arrow" (IPC file) or "arrows" (IPC stream);
+    /// inferred from the extension when omitted.
+    format: Option<String>,
+    #[serde(default)]
+    columns: ColumnMap,
+    /// CSV field delimiter, single character.
+    #[serde(default = "default_delimiter")]
+    delimiter: String,
+    #[serde(default = "default_true")]
+    has_header: bool,
+    /// Keep rows whose timestamp is >= start / <= end (string comparison,
+    /// same convention as composite ranges in advanced_signals).
+    start: Option<String>,
+    end: Option<String>,
+    /// Keep only the last N rows after filtering.
+    tail: Option<usize>,
+}
+
+fn default_delimiter() -> String { ",".to_string() }
+fn default_true() -> bool { true }
+
+/// Source column names for each candle field. Matching is case-insensitive;
+/// without a header row they may be zero-based column indices ("0", "1", ...).
+#[derive(Deserialize)]
+#[serde(default)]
+struct ColumnMap {
+    timestamp: String,
+    open: String,
+    high: String,
+    low: String,
+    close: String,
+    volume: String,
+}
+
+impl Default for ColumnMap {
+    fn default() -> Self {
+        ColumnMap {
+            timestamp: "timestamp".to_string(),
+            open: "open".to_string(),
+            high: "high".to_string(),
+            low: "low".to_string(),
+            close: "close".to_string(),
+            volume: "volume".to_string(),
+        }
+    }
+}
+
+impl ColumnMap {
+    fn names(&self) -> [&str; 6] {
+        [&self.timestamp, &self.open, &self.high, &self.low, &self.close, &self.volume]
+    }
+}
+
+/// Replace every `candles_file` object in `data` with a loaded `candles` array.
+pub fn resolve(data: &mut Value) -> Result<(), String> {
+    match data {
+        Value::Object(map) => {
+            if let Some(spec) = map.remove("candles_file") {
+                let candles = load(spec)?;
+                let value = serde_json::to_value(candles)
+                    .map_err(|e| format!("Serialization error: {}", e))?;
+                map.insert("candles".to_string(), value);
+            }
+            map.values_mut().try_for_each(resolve)
+        }
+        Value::Array(items) => items.iter_mut().try_for_each(resolve),
+        _ => Ok(()),
+    }
+}
+
+fn load(spec: Value) -> Result<Vec<Candle>, String> {
+    let spec: CandleFileSpec = serde_json::from_value(spec)
+        .map_err(|e| format!("Invalid candles_file spec: {}", e))?;
+
+    let format = match &spec.format {
+        Some(f) => f.to_lowercase(),
+        None => std::path::Path::new(&spec.path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_else(|| "csv".to_string()),
+    };
+
+    let mut candles = match format.as_str() {
+        "csv" | "txt" => read_csv(&spec)?,
+        "parquet" | "pq" => read_parquet(&spec)?,
+        "arrow" | "ipc" | "feather" => read_arrow(&spec, false)?,
+        "arrows" => read_arrow(&spec, true)?,
+        other => return Err(format!("Unsupported candles_file format: {}", other)),
+    };
+
+    candles.retain(|c| {
+        spec.start.as_ref().is_none_or(|s| c.timestamp.as_str() >= s.as_str())
+            && spec.end.as_ref().is_none_or(|e| c.timestamp.as_str() <= e.as_str() || c.timestamp.starts_with(e.as_str()))
+    });
+    if let Some(n) = spec.tail {
+        let skip = candles.len().saturating_sub(n);
+        candles.drain(..skip);
+    }
+    if candles.is_empty() {
+        return Err(format!("candles_file {} produced no candles", spec.path));
+    }
+    Ok(candles)
+}
+
+/// Resolve each mapped column to a position in `header`. Volume is optional.
+fn column_positions(header: &[String], columns: &ColumnMap, has_header: bool) -> Result<[Option<usize>; 6], String> {
+    let mut out = [None; 6];
+    for (i, name) in columns.names().iter().enumerate() {
+        let pos = if has_header {
+            header.iter().position(|h| h.trim().eq_ignore_ascii_case(name))
+        } else {
+            name.parse::<usize>().ok().filter(|&p| p < header.len())
+        };
+        if pos.is_none() && i != 5 {
+            return Err(format!("candles_file column '{}' not found", name));
+        }
+        out[i] = pos;
+    }
+    Ok(out)
+}
+
+fn read_csv(spec: &CandleFileSpec) -> Result<Vec<Candle>, String> {
+    let delimiter = match spec.delimiter.as_bytes() {
+        [b] => *b,
+        _ => return Err("delimiter must be a single character".to_string()),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(spec.has_header)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(&spec.path)
+        .map_err(|e| format!("Failed to open {}: {}", spec.path, e))?;
+
+    let named: Vec<String> = if spec.has_header {
+        reader.headers()
+            .map_err(|e| format!("CSV error in {}: {}", spec.path, e))?
+            .iter().map(|h| h.to_string()).collect()
+    } else {
+        Vec::new()
+    };
+    let mut records = reader.records();
+    let (header, first) = if spec.has_header {
+        (named, None)
+    } else {
+        match records.next() {
+            Some(r) => {
+                let r = r.map_err(|e| format!("CSV error in {}: {}", spec.path, e))?;
+                let width: Vec<String> = (0..r.len()).map(|i| i.to_string()).collect();
+                (width, Some(r))
+            }
+            None => return Ok(Vec::new()),
+        }
+    };
+    let pos = column_positions(&header, &spec.columns, spec.has_header)?;
+
+    let mut candles = Vec::new();
+    for (row, record) in first.into_iter().map(Ok).chain(records).enumerate() {
+        let record = record.map_err(|e| format!("CSV error in {}: {}", spec.path, e))?;
+        let field = |i: usize| pos[i].and_then(|p| record.get(p)).unwrap_or("");
+        let num = |i: usize| -> Result<f64, String> {
+            let raw = field(i);
+            if raw.is_empty() && i == 5 { return Ok(0.0); }
+            raw.parse::<f64>().map_err(|_| {
+                format!("{} row {}: invalid {} value '{}'", spec.path, row + 1, spec.columns.names()[i], raw)
+            })
+        };
+        candles.push(Candle {
+            timestamp: field(0).to_string(),
+            open: num(1)?,
+            high: num(2)?,
+            low: num(3)?,
+            close: num(4)?,
+            volume: num(5)?,
+        });
+    }
+    Ok(candles)
+}
+
+#[cfg(feature = "parquet")]
+fn read_parquet(spec: &CandleFileSpec) -> Result<Vec<Candle>, String> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+
+    let file = std::fs::File::open(&spec.path)
+        .map_err(|e| format!("Failed to open {}: {}", spec.path, e))?;
+    let reader = SerializedFileReader::new(file)
+        .map_err(|e| format!("Parquet error in {}: {}", spec.path, e))?;
+    let header: Vec<String> = reader.metadata().file_metadata().schema_descr().columns()
+        .iter().map(|c| c.name().to_string()).collect();
+    let pos = column_positions(&header, &spec.columns, true)?;
+    let rows = reader.get_row_iter(None)
+        .map_err(|e| format!("Parquet error in {}: {}", spec.path, e))?;
+
+    let as_f64 = |f: &Field| -> Option<f64> {
+        match f {
+            Field::Double(v) => Some(*v),
+            Field::Float(v) => Some(*v as f64),
+            Field::Int(v) => Some(*v as f64),
+            Field::Long(v) => Some(*v as f64),
+            Field::UInt(v) => Some(*v as f64),
+            Field::ULong(v) => Some(*v as f64),
+            Field::Str(s) => s.parse().ok(),
+            Field::Null => Some(0.0),
+            _ => None,
+        }
+    };
+    let as_timestamp = |f: &Field| -> String {
+        match f {
+            Field::Str(s) => s.clone(),
+            Field::TimestampMillis(ms) => chrono::DateTime::from_timestamp_millis(*ms)
+                .map(|d| d.to_rfc3339())
+                .unwrap_or_else(|| ms.to_string()),
+            Field::TimestampMicros(us) => chrono::DateTime::from_timestamp_micros(*us)
+                .map(|d| d.to_rfc3339())
+                .unwrap_or_else(|| us.to_string()),
+            other => other.to_string(),
+        }
+    };
+
+    let mut candles = Vec::new();
+    for (row_idx, row) in rows.enumerate() {
+        let row = row.map_err(|e| format!("Parquet error in {}: {}", spec.path, e))?;
+        let fields: Vec<&Field> = row.get_column_iter().map(|(_, f)| f).collect();
+        let num = |i: usize| -> Result<f64, String> {
+            match pos[i] {
+                None => Ok(0.0),
+                Some(p) => as_f64(fields[p]).ok_or_else(|| {
+                    format!("{} row {}: invalid {} value", spec.path, row_idx + 1, spec.columns.names()[i])
+                }),
+            }
+        };
+        candles.push(Candle {
+            timestamp: pos[0].map(|p| as_timestamp(fields[p])).unwrap_or_default(),
+            open: num(1)?,
+            high: num(2)?,
+            low: num(3)?,
+            close: num(4)?,
+            volume: num(5)?,
+        });
+    }
+    Ok(candles)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn read_parquet(_spec: &CandleFileSpec) -> Result<Vec<Candle>, String> {
+    Err("Parquet support not compiled in; rebuild with --features parquet".to_string())
+}
+
+#[cfg(feature = "arrow")]
+fn read_arrow(spec: &CandleFileSpec, stream: bool) -> Result<Vec<Candle>, String> {
+    use arrow_array::RecordBatch;
+    use arrow_ipc::reader::{FileReader, StreamReader};
+
+    let err = |e: arrow_schema::ArrowError| format!("Arrow error in {}: {}", spec.path, e);
+    let file = std::fs::File::open(&spec.path)
+        .map_err(|e| format!("Failed to open {}: {}", spec.path, e))?;
+    let batches: Vec<RecordBatch> = if stream {
+        StreamReader::try_new_buffered(file, None).map_err(err)?.collect::<Result<_, _>>().map_err(err)?
+    } else {
+        FileReader::try_new_buffered(file, None).map_err(err)?.collect::<Result<_, _>>().map_err(err)?
+    };
+    let Some(first) = batches.first() else { return Ok(Vec::new()) };
+    let header: Vec<String> = first.schema().fields().iter().map(|f| f.name().clone()).collect();
+    let pos = column_positions(&header, &spec.columns, true)?;
+
+    let mut candles = Vec::with_capacity(batches.iter().map(|b| b.num_rows()).sum());
+    let mut offset = 0;
+    for batch in &batches {
+        let n = batch.num_rows();
+        let timestamps = match pos[0] {
+            Some(p) => arrow_strings(batch.column(p).as_ref())
+                .ok_or_else(|| format!("{}: unsupported {} column type", spec.path, spec.columns.timestamp))?,
+            None => vec![String::new(); n],
+        };
+        let mut fields: [Vec<f64>; 5] = Default::default();
+        for (i, out) in fields.iter_mut().enumerate() {
+            *out = match pos[i + 1] {
+                Some(p) => arrow_f64(batch.column(p).as_ref()).ok_or_else(|| {
+                    format!("{}: unsupported {} column type", spec.path, spec.columns.names()[i + 1])
+                })?,
+                None => vec![0.0; n],
+            };
+        }
+        let [open, high, low, close, volume] = &fields;
+        for (r, timestamp) in timestamps.into_iter().enumerate() {
+            if [open[r], high[r], low[r], close[r]].iter().any(|v| v.is_nan()) {
+                return Err(format!("{} row {}: missing price value", spec.path, offset + r + 1));
+            }
+            candles.push(Candle { timestamp, open: open[r], high: high[r], low: low[r], close: close[r], volume: if volume[r].is_nan() { 0.0 } else { volume[r] } });
+        }
+        offset += n;
+    }
+    Ok(candles)
+}
+
+/// Numeric column as f64; nulls become NaN (callers treat that as missing).
+#[cfg(feature = "arrow")]
+fn arrow_f64(col: &dyn arrow_array::Array) -> Option<Vec<f64>> {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::*;
+    use arrow_schema::DataType;
+
+    fn collect<T: ArrowPrimitiveType>(a: &arrow_array::PrimitiveArray<T>, f: impl Fn(T::Native) -> f64) -> Vec<f64> {
+        a.iter().map(|v| v.map(&f).unwrap_or(f64::NAN)).collect()
+    }
+    let out = match col.data_type() {
+        DataType::Float64 => collect(col.as_primitive::<Float64Type>(), |v| v),
+        DataType::Float32 => collect(col.as_primitive::<Float32Type>(), |v| v as f64),
+        DataType::Int64 => collect(col.as_primitive::<Int64Type>(), |v| v as f64),
+        DataType::Int32 => collect(col.as_primitive::<Int32Type>(), |v| v as f64),
+        DataType::UInt64 => collect(col.as_primitive::<UInt64Type>(), |v| v as f64),
+        DataType::UInt32 => collect(col.as_primitive::<UInt32Type>(), |v| v as f64),
+        DataType::Utf8 => col.as_string::<i32>().iter()
+            .map(|s| s.and_then(|s| s.trim().parse().ok()).unwrap_or(f64::NAN)).collect(),
+        _ => return None,
+    };
+    Some(out)
+}
+
+/// Timestamp column rendered the way CSV/Parquet input would arrive:
+/// strings as-is, temporal types as RFC 3339 (UTC) or ISO dates.
+#[cfg(feature = "arrow")]
+fn arrow_strings(col: &dyn arrow_array::Array) -> Option<Vec<String>> {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::*;
+    use arrow_schema::{DataType, TimeUnit};
+
+    let from_nanos = |ns: i64| chrono::DateTime::from_timestamp_nanos(ns).to_rfc3339();
+    let out: Vec<String> = match col.data_type() {
+        DataType::Utf8 => col.as_string::<i32>().iter().map(|s| s.unwrap_or_default().to_string()).collect(),
+        DataType::LargeUtf8 => col.as_string::<i64>().iter().map(|s| s.unwrap_or_default().to_string()).collect(),
+        DataType::Timestamp(unit, _) => {
+            let scale = match unit {
+                TimeUnit::Second => 1_000_000_000,
+                TimeUnit::Millisecond => 1_000_000,
+                TimeUnit::Microsecond => 1_000,
+                TimeUnit::Nanosecond => 1,
+            };
+            let raw = match unit {
+                TimeUnit::Second => col.as_primitive::<TimestampSecondType>().values(),
+                TimeUnit::Millisecond => col.as_primitive::<TimestampMillisecondType>().values(),
+                TimeUnit::Microsecond => col.as_primitive::<TimestampMicrosecondType>().values(),
+                TimeUnit::Nanosecond => col.as_primitive::<TimestampNanosecondType>().values(),
+            };
+            raw.iter().map(|&v| from_nanos(v.saturating_mul(scale))).collect()
+        }
+        DataType::Date32 => col.as_primitive::<Date32Type>().values().iter()
+            .map(|&d| from_nanos(d as i64 * 86_400_000_000_000)[..10].to_string()).collect(),
+        DataType::Int64 => col.as_primitive::<Int64Type>().values().iter().map(|v| v.to_string()).collect(),
+        _ => return None,
+    };
+    Some(out)
+}
+
+#[cfg(not(feature = "arrow"))]
+fn read_arrow(_spec: &CandleFileSpec, _stream: bool) -> Result<Vec<Candle>, String> {
+    Err("Arrow support not compiled in; rebuild with --features arrow".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write_tmp(name: &str, body: &str) -> String {
+        let path = std::env::temp_dir().join(format!("cg_candle_file_{}_{}", std::process::id(), name));
+        std::fs::write(&path, body).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_csv_with_column_mapping() {
+        let path = write_tmp("mapped.csv",
+            "Date,Open,High,Low,Close,Vol\n2024-01-01,10,11,9,10.5,100\n2024-01-02,10.5,12,10,11.5,200\n2024-01-03,11.5,12,11,11,150\n");
+        let mut data = json!({
+            "symbol": "X",
+            "candles_file": { "path": path, "columns": { "timestamp": "date", "volume": "Vol" }, "start": "2024-01-02" }
+        });
+        resolve(&mut data).unwrap();
+        assert!(data.get("candles_file").is_none());
+        let candles = data["candles"].as_array().unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0]["timestamp"], "2024-01-02");
+        assert_eq!(candles[1]["volume"].as_f64().unwrap(), 150.0);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_headerless_nested_and_errors() {
+        let path = write_tmp("plain.txt", "t1;1;2;0.5;1.5\nt2;1.5;2.5;1;2\n");
+        let mut data = json!({ "symbols": [{ "candles_file": {
+            "path": path, "format": "csv", "delimiter": ";", "has_header": false, "tail": 1,
+            "columns": { "timestamp": "0", "open": "1", "high": "2", "low": "3", "close": "4", "volume": "9" }
+        }}]});
+        resolve(&mut data).unwrap();
+        let candles = data["symbols"][0]["candles"].as_array().unwrap();
+        assert_eq!(candles.len(), 1);
+        assert_eq!(candles[0]["close"].as_f64().unwrap(), 2.0);
+        assert_eq!(candles[0]["volume"].as_f64().unwrap(), 0.0);
+
+        let bad = write_tmp("bad.csv", "timestamp,open,high,low,close\nt1,1,2,x,1\n");
+        let err = resolve(&mut json!({ "candles_file": { "path": bad } })).unwrap_err();
+        assert!(err.contains("invalid low"), "{}", err);
+        assert!(resolve(&mut json!({ "candles_file": { "path": "/nonexistent.csv" } })).is_err());
+        std::fs::remove_file(path).ok();
+        std::fs::remove_file(bad).ok();
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_arrow_ipc_columns() {
+        use std::sync::Arc;
+        use arrow_array::{ArrayRef, Float32Array, Float64Array, Int64Array, RecordBatch, TimestampMillisecondArray};
+        use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), false),
+            Field::new("Open", DataType::Float64, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float32, false),
+            Field::new("close", DataType::Float64, false),
+            Field::new("volume", DataType::Int64, true),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMillisecondArray::from(vec![1_704_067_200_000, 1_704_153_600_000])),
+            Arc::new(Float64Array::from(vec![10.0, 10.5])),
+            Arc::new(Float64Array::from(vec![11.0, 12.0])),
+            Arc::new(Float32Array::from(vec![9.5, 10.0])),
+            Arc::new(Float64Array::from(vec![10.5, 11.5])),
+            Arc::new(Int64Array::from(vec![Some(100), None])),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let path = std::env::temp_dir().join(format!("cg_candle_file_{}_bars.arrow", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = arrow_ipc::writer::FileWriter::try_new(file, &schema).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+
+        let mut data = json!({ "candles_file": { "path": path.to_string_lossy(), "columns": { "timestamp": "ts" } } });
+        resolve(&mut data).unwrap();
+        let candles = data["candles"].as_array().unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles[0]["timestamp"], "2024-01-01T00:00:00+00:00");
+        assert_eq!(candles[0]["low"].as_f64().unwrap(), 9.5);
+        assert_eq!(candles[0]["volume"].as_f64().unwrap(), 100.0);
+        assert_eq!(candles[1]["volume"].as_f64().unwrap(), 0.0);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[cfg(not(feature = "arrow"))]
+    #[test]
+    fn test_arrow_requires_feature() {
+        let err = resolve(&mut json!({ "candles_file": { "path": "bars.feather" } })).unwrap_err();
+        assert!(err.contains("--features arrow"), "{}", err);
+    }
+}
@@ -0,0 +1,366 @@
+//! Option-chain analytics for premium sellers.
+//!
+//! For every strike and side: IV (given, or solved from the mid), greeks,
+//! bid-ask spread %, a 0–100 liquidity score and the return from selling
+//! at the bid. OTM calls are ranked as covered calls and OTM puts as
+//! cash-secured puts by annualized ROI × probability of expiring OTM,
+//! restricted to liquid strikes inside the delta band.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::greeks::solve_iv;
+use crate::strategy_payoff::valuation_date;
+use crate::utils::{bs_greeks, norm_cdf, parse_timestamp, round2, round4};
+
+#[derive(Deserialize)]
+struct ChainConfig {
+    spot: f64,
+    strikes: Vec<ChainStrike>,
+    #[serde(default)]
+    expiry: Option<String>,
+    #[serde(default)]
+    expiry_days: Option<f64>,
+    #[serde(default)]
+    valuation_date: Option<String>,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    #[serde(default = "default_min_liquidity")]
+    min_liquidity: f64,
+    /// |delta| band for suggestions.
+    #[serde(default = "default_min_delta")]
+    min_delta: f64,
+    #[serde(default = "default_max_delta")]
+    max_delta: f64,
+    /// Spread at which the spread component of liquidity reaches zero.
+    #[serde(default = "default_max_spread")]
+    max_spread_pct: f64,
+    #[serde(default = "default_top_n")]
+    top_n: usize,
+}
+
+fn default_rate() -> f64 { 0.065 }
+fn default_min_liquidity() -> f64 { 40.0 }
+fn default_min_delta() -> f64 { 0.10 }
+fn default_max_delta() -> f64 { 0.40 }
+fn default_max_spread() -> f64 { 10.0 }
+fn default_top_n() -> usize { 5 }
+
+/// One strike of a chain, shared with the strategy-suggestion command.
+#[derive(Deserialize, Default, Clone)]
+pub(crate) struct ChainStrike {
+    pub strike: f64,
+    #[serde(default)] pub call_bid: f64,
+    #[serde(default)] pub call_ask: f64,
+    #[serde(default)] pub call_ltp: f64,
+    #[serde(default)] pub call_oi: f64,
+    #[serde(default)] pub call_volume: f64,
+    #[serde(default)] pub call_iv: Option<f64>,
+    #[serde(default)] pub put_bid: f64,
+    #[serde(default)] pub put_ask: f64,
+    #[serde(default)] pub put_ltp: f64,
+    #[serde(default)] pub put_oi: f64,
+    #[serde(default)] pub put_volume: f64,
+    #[serde(default)] pub put_iv: Option<f64>,
+}
+
+impl ChainStrike {
+    /// (bid, ask, ltp, iv) for one side.
+    pub fn quote(&self, is_call: bool) -> (f64, f64, f64, Option<f64>) {
+        if is_call {
+            (self.call_bid, self.call_ask, self.call_ltp, self.call_iv)
+        } else {
+            (self.put_bid, self.put_ask, self.put_ltp, self.put_iv)
+        }
+    }
+}
+
+/// Calendar days to expiry from `expiry_days`, or `expiry` counted from
+/// `valuation_date` (today when omitted); must be positive.
+pub(crate) fn days_to_expiry(
+    expiry_days: Option<f64>,
+    expiry: Option<&str>,
+    valuation: Option<&str>,
+    command: &str,
+) -> Result<f64, String> {
+    let days = match (expiry_days, expiry) {
+        (Some(d), _) => d,
+        (None, Some(e)) => {
+            let valuation = valuation_date(valuation)?;
+            let expiry = parse_timestamp(e).ok_or_else(|| format!("Invalid expiry '{}'", e))?;
+            (expiry.date() - valuation).num_days() as f64
+        }
+        (None, None) => return Err(format!("{} requires expiry or expiry_days", command)),
+    };
+    if days <= 0.0 {
+        return Err("expiry must be after the valuation date".to_string());
+    }
+    Ok(days)
+}
+
+/// Mid of a two-sided quote, else the last traded price.
+pub(crate) fn mid_price(bid: f64, ask: f64, ltp: f64) -> f64 {
+    if bid > 0.0 && ask >= bid { (bid + ask) / 2.0 } else { ltp }
+}
+
+struct Quote {
+    bid: f64,
+    ask: f64,
+    ltp: f64,
+    oi: f64,
+    volume: f64,
+    iv: Option<f64>,
+}
+
+#[derive(Serialize, Clone)]
+struct SideAnalysis {
+    mid: f64,
+    iv: f64,
+    delta: f64,
+    gamma: f64,
+    theta: f64,
+    vega: f64,
+    spread_pct: Option<f64>,
+    liquidity_score: f64,
+    prob_otm: f64,
+    /// Premium at the bid over capital: spot for calls, strike for puts.
+    sell_roi_pct: f64,
+    sell_annualized_roi_pct: f64,
+    oi: f64,
+    volume: f64,
+    /// The bid, or the mid without one.
+    #[serde(skip)]
+    sell_price: f64,
+}
+
+#[derive(Serialize)]
+struct StrikeRow {
+    strike: f64,
+    call: Option<SideAnalysis>,
+    put: Option<SideAnalysis>,
+}
+
+#[derive(Serialize)]
+struct Suggestion {
+    strike: f64,
+    premium: f64,
+    delta: f64,
+    iv: f64,
+    prob_otm: f64,
+    roi_pct: f64,
+    annualized_roi_pct: f64,
+    /// Covered call: total return if called away. Put: effective buy price.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    if_assigned_roi_pct: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    effective_buy_price: Option<f64>,
+    breakeven: f64,
+    liquidity_score: f64,
+    spread_pct: Option<f64>,
+    score: f64,
+}
+
+#[derive(Serialize)]
+struct ChainResult {
+    spot: f64,
+    expiry_days: f64,
+    atm_strike: f64,
+    atm_iv: Option<f64>,
+    strikes: Vec<StrikeRow>,
+    covered_calls: Vec<Suggestion>,
+    cash_secured_puts: Vec<Suggestion>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: ChainConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid chain_analysis config: {}", e))?;
+    if !(config.spot.is_finite() && config.spot > 0.0) {
+        return Err("spot must be positive".to_string());
+    }
+    let days = days_to_expiry(config.expiry_days, config.expiry.as_deref(), config.valuation_date.as_deref(), "chain_analysis")?;
+    let t = days / 365.0;
+    let mut strikes: Vec<ChainStrike> = config.strikes.into_iter().filter(|s| s.strike.is_finite() && s.strike > 0.0).collect();
+    if strikes.is_empty() {
+        return Err("chain_analysis requires strikes".to_string());
+    }
+    strikes.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+
+    let max_oi = strikes.iter().flat_map(|s| [s.call_oi, s.put_oi]).fold(0.0, f64::max);
+    let max_vol = strikes.iter().flat_map(|s| [s.call_volume, s.put_volume]).fold(0.0, f64::max);
+    let log_share = |v: f64, max: f64| if max > 0.0 { (1.0 + v.max(0.0)).ln() / (1.0 + max).ln() } else { 0.0 };
+
+    let analyze = |strike: f64, q: Quote, is_call: bool| -> Option<SideAnalysis> {
+        let mid = mid_price(q.bid, q.ask, q.ltp);
+        if mid <= 0.0 {
+            return None;
+        }
+        let iv = q.iv.filter(|v| *v > 0.0)
+            .unwrap_or_else(|| solve_iv(config.spot, strike, config.risk_free_rate, t, mid, is_call));
+        if iv <= 0.0 {
+            return None;
+        }
+        let (delta, gamma, theta, vega, _) = bs_greeks(config.spot, strike, t, config.risk_free_rate, iv, is_call);
+        let d2 = ((config.spot / strike).ln() + (config.risk_free_rate - iv * iv / 2.0) * t) / (iv * t.sqrt());
+        let prob_otm = if is_call { norm_cdf(-d2) } else { norm_cdf(d2) };
+        let spread_pct = (q.bid > 0.0 && q.ask >= q.bid).then(|| (q.ask - q.bid) / mid * 100.0);
+        let spread_component = spread_pct.map_or(0.0, |s| (1.0 - s / config.max_spread_pct).clamp(0.0, 1.0));
+        let liquidity = 40.0 * spread_component + 30.0 * log_share(q.oi, max_oi) + 30.0 * log_share(q.volume, max_vol);
+        let sell_price = if q.bid > 0.0 { q.bid } else { mid };
+        let capital = if is_call { config.spot } else { strike };
+        let roi = if capital > 0.0 { sell_price / capital * 100.0 } else { 0.0 };
+        Some(SideAnalysis {
+            mid: round2(mid),
+            iv: round4(iv),
+            delta: round4(delta),
+            gamma: round4(gamma),
+            theta: round4(theta),
+            vega: round4(vega),
+            spread_pct: spread_pct.map(round2),
+            liquidity_score: round2(liquidity),
+            prob_otm: round4(prob_otm),
+            sell_roi_pct: round2(roi),
+            sell_annualized_roi_pct: round2(roi * 365.0 / days),
+            oi: q.oi,
+            volume: q.volume,
+            sell_price,
+        })
+    };
+
+    let rows: Vec<StrikeRow> = strikes.iter().map(|s| StrikeRow {
+        strike: s.strike,
+        call: analyze(s.strike, Quote { bid: s.call_bid, ask: s.call_ask, ltp: s.call_ltp, oi: s.call_oi, volume: s.call_volume, iv: s.call_iv }, true),
+        put: analyze(s.strike, Quote { bid: s.put_bid, ask: s.put_ask, ltp: s.put_ltp, oi: s.put_oi, volume: s.put_volume, iv: s.put_iv }, false),
+    }).collect();
+
+    let atm = rows.iter()
+        .min_by(|a, b| (a.strike - config.spot).abs().total_cmp(&(b.strike - config.spot).abs()))
+        .expect("strikes is non-empty");
+    let atm_iv = match (&atm.call, &atm.put) {
+        (Some(c), Some(p)) => Some(round4((c.iv + p.iv) / 2.0)),
+        (Some(x), None) | (None, Some(x)) => Some(x.iv),
+        (None, None) => None,
+    };
+
+    let eligible = |a: &SideAnalysis| {
+        a.liquidity_score >= config.min_liquidity && a.delta.abs() >= config.min_delta && a.delta.abs() <= config.max_delta
+    };
+    let rank = |mut v: Vec<Suggestion>| -> Vec<Suggestion> {
+        v.sort_by(|a, b| b.score.total_cmp(&a.score));
+        v.truncate(config.top_n);
+        v
+    };
+    let covered_calls = rank(rows.iter()
+        .filter(|r| r.strike > config.spot)
+        .filter_map(|r| r.call.as_ref().filter(|a| eligible(a)).map(|a| (r.strike, a)))
+        .map(|(strike, a)| {
+            let bid = a.sell_price;
+            Suggestion {
+                strike,
+                premium: round2(bid),
+                delta: a.delta,
+                iv: a.iv,
+                prob_otm: a.prob_otm,
+                roi_pct: a.sell_roi_pct,
+                annualized_roi_pct: a.sell_annualized_roi_pct,
+                if_assigned_roi_pct: Some(round2((strike - config.spot + bid) / config.spot * 100.0)),
+                effective_buy_price: None,
+                breakeven: round2(config.spot - bid),
+                liquidity_score: a.liquidity_score,
+                spread_pct: a.spread_pct,
+                score: round4(a.sell_annualized_roi_pct * a.prob_otm),
+            }
+        }).collect());
+    let cash_secured_puts = rank(rows.iter()
+        .filter(|r| r.strike < config.spot)
+        .filter_map(|r| r.put.as_ref().filter(|a| eligible(a)).map(|a| (r.strike, a)))
+        .map(|(strike, a)| {
+            let bid = a.sell_price;
+            Suggestion {
+                strike,
+                premium: round2(bid),
+                delta: a.delta,
+                iv: a.iv,
+                prob_otm: a.prob_otm,
+                roi_pct: a.sell_roi_pct,
+                annualized_roi_pct: a.sell_annualized_roi_pct,
+                if_assigned_roi_pct: None,
+                effective_buy_price: Some(round2(strike - bid)),
+                breakeven: round2(strike - bid),
+                liquidity_score: a.liquidity_score,
+                spread_pct: a.spread_pct,
+                score: round4(a.sell_annualized_roi_pct * a.prob_otm),
+            }
+        }).collect());
+
+    let result = ChainResult {
+        spot: config.spot,
+        expiry_days: days,
+        atm_strike: atm.strike,
+        atm_iv,
+        strikes: rows,
+        covered_calls,
+        cash_secured_puts,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::bs_price;
+    use serde_json::json;
+
+    /// A chain priced at 20% IV with 2% spreads and OI peaking near the money.
+    fn chain(spot: f64, days: f64) -> Value {
+        let t = days / 365.0;
+        let strikes: Vec<Value> = (0..11).map(|i| {
+            let k = spot * 0.9 + i as f64 * spot * 0.02;
+            let c = bs_price(spot, k, 0.065, t, 0.2, true);
+            let p = bs_price(spot, k, 0.065, t, 0.2, false);
+            let oi = 10_000.0 - (i as f64 - 5.0).abs() * 1500.0;
+            json!({
+                "strike": k,
+                "call_bid": c * 0.99, "call_ask": c * 1.01, "call_oi": oi, "call_volume": oi / 2.0,
+                "put_bid": p * 0.99, "put_ask": p * 1.01, "put_oi": oi, "put_volume": oi / 2.0,
+            })
+        }).collect();
+        json!({ "spot": spot, "expiry_days": days, "strikes": strikes })
+    }
+
+    #[test]
+    fn test_iv_greeks_and_atm() {
+        let result = compute(chain(100.0, 30.0)).unwrap();
+        assert_eq!(result["atm_strike"].as_f64().unwrap(), 100.0);
+        assert!((result["atm_iv"].as_f64().unwrap() - 0.2).abs() < 0.005);
+        let atm_call = &result["strikes"][5]["call"];
+        assert!((atm_call["delta"].as_f64().unwrap() - 0.54).abs() < 0.03);
+        assert!((atm_call["spread_pct"].as_f64().unwrap() - 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_suggestions_are_otm_liquid_and_ranked() {
+        let result = compute(chain(100.0, 30.0)).unwrap();
+        let calls = result["covered_calls"].as_array().unwrap();
+        let puts = result["cash_secured_puts"].as_array().unwrap();
+        assert!(!calls.is_empty() && !puts.is_empty());
+        for c in calls {
+            assert!(c["strike"].as_f64().unwrap() > 100.0);
+            assert!(c["delta"].as_f64().unwrap() <= 0.40);
+            assert!(c["liquidity_score"].as_f64().unwrap() >= 40.0);
+        }
+        let input = chain(100.0, 30.0);
+        for p in puts {
+            let strike = p["strike"].as_f64().unwrap();
+            assert!(strike < 100.0);
+            let eff = p["effective_buy_price"].as_f64().unwrap();
+            assert!(eff < strike);
+            // The bid itself, over the strike it secures.
+            let row = input["strikes"].as_array().unwrap().iter().find(|s| s["strike"].as_f64() == Some(strike)).unwrap();
+            let bid = row["put_bid"].as_f64().unwrap();
+            assert_eq!(p["premium"].as_f64().unwrap(), round2(bid));
+            assert_eq!(p["roi_pct"].as_f64().unwrap(), round2(bid / strike * 100.0));
+        }
+        let scores: Vec<f64> = calls.iter().map(|c| c["score"].as_f64().unwrap()).collect();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]));
+        assert!(compute(json!({ "spot": 100.0, "strikes": [] })).is_err());
+    }
+}
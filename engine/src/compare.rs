@@ -0,0 +1,305 @@
+//! Side-by-side backtests of several strategy configs on the same data.
+//!
+//! The request is a `backtest` request plus `strategies`, a list of
+//! overrides (`strategy`, `params` or any other backtest field) each merged
+//! over the shared settings and labelled by `name` (the strategy by default).
+//! Besides each config's metrics, the per-bar returns of the equity curves
+//! are correlated and the configs are combined into one portfolio: capital
+//! split by `weights` (equal by default, normalised) at the start and left
+//! to drift. `diversification_ratio` is the weighted average volatility over
+//! the combined volatility; above 1 means the mix is smoother than its parts.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::{performance, run_config, BacktestConfig, BacktestResult, EquityPoint};
+use crate::calendar::Calendar;
+use crate::progress::Progress;
+use crate::utils::{parse_timestamp, pearson_correlation, round2, round4};
+
+#[derive(Deserialize)]
+struct CompareConfig {
+    initial_capital: f64,
+    strategies: Vec<Value>,
+    #[serde(default)]
+    weights: Option<Vec<f64>>,
+}
+
+#[derive(Serialize)]
+struct StrategyMetrics {
+    name: String,
+    strategy: String,
+    total_return_pct: f64,
+    cagr: f64,
+    max_drawdown: f64,
+    volatility_pct: f64,
+    sharpe_ratio: f64,
+    sortino_ratio: f64,
+    win_rate: f64,
+    profit_factor: f64,
+    total_trades: usize,
+    total_costs: f64,
+    /// Share of bars with a position open.
+    exposure_pct: f64,
+}
+
+#[derive(Serialize)]
+struct Combined {
+    weights: Vec<f64>,
+    total_return_pct: f64,
+    cagr: f64,
+    max_drawdown: f64,
+    volatility_pct: f64,
+    sharpe_ratio: f64,
+    sortino_ratio: f64,
+    diversification_ratio: f64,
+    /// Combined Sharpe less the best single config's.
+    sharpe_vs_best: f64,
+    /// Combined drawdown less the shallowest single config's (negative is better).
+    drawdown_vs_best: f64,
+    equity_curve: Vec<EquityPoint>,
+}
+
+#[derive(Serialize)]
+struct CompareResult {
+    names: Vec<String>,
+    strategies: Vec<StrategyMetrics>,
+    /// Pearson correlation of per-bar equity returns, in `names` order.
+    correlation_matrix: Vec<Vec<f64>>,
+    avg_pairwise_correlation: f64,
+    combined: Combined,
+    equity_curves: Vec<Vec<EquityPoint>>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: CompareConfig =
+        serde_json::from_value(data.clone()).map_err(|e| format!("Invalid compare config: {}", e))?;
+    let n = config.strategies.len();
+    if n < 2 {
+        return Err("compare needs at least two strategies".to_string());
+    }
+    if config.initial_capital <= 0.0 {
+        return Err("initial_capital must be positive".to_string());
+    }
+    let weights = match &config.weights {
+        Some(w) if w.len() != n => return Err(format!("weights has {} entries for {} strategies", w.len(), n)),
+        Some(w) if w.iter().any(|x| *x < 0.0) || w.iter().sum::<f64>() <= 0.0 =>
+            return Err("weights must be non-negative with a positive sum".to_string()),
+        Some(w) => w.iter().map(|x| x / w.iter().sum::<f64>()).collect(),
+        None => vec![1.0 / n as f64; n],
+    };
+
+    let mut shared = data;
+    if let Some(obj) = shared.as_object_mut() {
+        obj.remove("strategies");
+        obj.remove("weights");
+    }
+    let mut names = Vec::with_capacity(n);
+    let mut runs: Vec<(BacktestConfig, BacktestResult)> = Vec::with_capacity(n);
+    for (i, overrides) in config.strategies.iter().enumerate() {
+        let Some(fields) = overrides.as_object() else {
+            return Err(format!("strategies[{}] must be an object", i));
+        };
+        let mut merged = shared.clone();
+        for (k, v) in fields.iter().filter(|(k, _)| k.as_str() != "name") {
+            merged[k] = v.clone();
+        }
+        let bt: BacktestConfig = serde_json::from_value(merged)
+            .map_err(|e| format!("Invalid compare config for strategies[{}]: {}", i, e))?;
+        let name = fields.get("name").and_then(|v| v.as_str()).unwrap_or(&bt.strategy).to_string();
+        if names.contains(&name) {
+            return Err(format!("Duplicate strategy name '{}'; set a distinct `name`", name));
+        }
+        let result = run_config(&bt, &Progress::none()).map_err(|e| format!("{}: {}", name, e))?;
+        names.push(name);
+        runs.push((bt, result));
+    }
+
+    let (base, _) = &runs[0];
+    let bars = base.candles.len();
+    if runs.iter().any(|(bt, r)| bt.candles.len() != bars || r.equity_curve.len() != bars) {
+        return Err("every strategy must run on the same candles".to_string());
+    }
+    let bars_per_day = base.bars_per_day.unwrap_or(1.0);
+    let calendar = Calendar::from_spec(base.calendar.as_ref())?;
+    let span = match (base.candles.first(), base.candles.last()) {
+        (Some(a), Some(b)) => parse_timestamp(&a.timestamp).zip(parse_timestamp(&b.timestamp)).map(|(a, b)| (a.date(), b.date())),
+        _ => None,
+    };
+
+    let returns: Vec<Vec<f64>> = runs.iter().map(|(_, r)| bar_returns(&r.equity_curve)).collect();
+    let annualization = |r: &BacktestResult| {
+        let days = if r.trading_days_per_year > 0.0 { r.trading_days_per_year } else { crate::calendar::DEFAULT_TRADING_DAYS };
+        (days * bars_per_day).sqrt()
+    };
+    let vols: Vec<f64> = runs.iter().zip(&returns).map(|((_, r), ret)| std_dev(ret) * annualization(r)).collect();
+
+    let strategies: Vec<StrategyMetrics> = runs.iter().zip(&names).zip(&vols).map(|(((bt, r), name), vol)| {
+        let final_nav = r.equity_curve.last().map_or(config.initial_capital, |e| e.nav);
+        StrategyMetrics {
+            name: name.clone(),
+            strategy: bt.strategy.clone(),
+            total_return_pct: round2((final_nav / bt.initial_capital - 1.0) * 100.0),
+            cagr: r.cagr,
+            max_drawdown: r.max_drawdown,
+            volatility_pct: round2(vol * 100.0),
+            sharpe_ratio: r.sharpe_ratio,
+            sortino_ratio: r.sortino_ratio,
+            win_rate: r.win_rate,
+            profit_factor: r.profit_factor,
+            total_trades: r.total_trades,
+            total_costs: r.total_costs,
+            exposure_pct: round2(exposure(r, &bt.candles) * 100.0),
+        }
+    }).collect();
+
+    let correlation_matrix: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| {
+        if i == j { 1.0 } else { round4(pearson_correlation(&returns[i], &returns[j])) }
+    }).collect()).collect();
+    let pairs: Vec<f64> = (0..n).flat_map(|i| ((i + 1)..n).map(move |j| (i, j)))
+        .map(|(i, j)| correlation_matrix[i][j]).collect();
+    let avg_pairwise_correlation = round4(pairs.iter().sum::<f64>() / pairs.len() as f64);
+
+    // Each run starts from its own capital; scale to the weight's share of one pot.
+    let capital = config.initial_capital;
+    let equity_curve: Vec<EquityPoint> = (0..bars).map(|k| {
+        let nav: f64 = runs.iter().zip(&weights)
+            .map(|((bt, r), w)| w * capital * r.equity_curve[k].nav / bt.initial_capital)
+            .sum();
+        EquityPoint { date: base.candles[k].timestamp.clone(), nav: round2(nav) }
+    }).collect();
+    let final_nav = equity_curve.last().map_or(capital, |e| e.nav);
+    let perf = performance(&equity_curve, &[], capital, final_nav, span, &calendar, bars_per_day);
+    let combined_returns = bar_returns(&equity_curve);
+    let combined_vol = std_dev(&combined_returns) * (perf.trading_days * bars_per_day).sqrt();
+    let weighted_vol: f64 = weights.iter().zip(&vols).map(|(w, v)| w * v).sum();
+    let max_dd = max_drawdown(&equity_curve);
+    let best_sharpe = strategies.iter().map(|s| s.sharpe_ratio).fold(f64::NEG_INFINITY, f64::max);
+    let best_dd = strategies.iter().map(|s| s.max_drawdown).fold(f64::INFINITY, f64::min);
+
+    let combined = Combined {
+        weights: weights.iter().map(|w| round4(*w)).collect(),
+        total_return_pct: round2((final_nav / capital - 1.0) * 100.0),
+        cagr: round2(perf.cagr),
+        max_drawdown: round2(max_dd * 100.0),
+        volatility_pct: round2(combined_vol * 100.0),
+        sharpe_ratio: round2(perf.sharpe),
+        sortino_ratio: round2(perf.sortino),
+        diversification_ratio: if combined_vol > 0.0 { round4(weighted_vol / combined_vol) } else { 0.0 },
+        sharpe_vs_best: round2(perf.sharpe - best_sharpe),
+        drawdown_vs_best: round2(max_dd * 100.0 - best_dd),
+        equity_curve,
+    };
+
+    let result = CompareResult {
+        names,
+        strategies,
+        correlation_matrix,
+        avg_pairwise_correlation,
+        combined,
+        equity_curves: runs.into_iter().map(|(_, r)| r.equity_curve).collect(),
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn bar_returns(curve: &[EquityPoint]) -> Vec<f64> {
+    curve.windows(2).map(|w| if w[0].nav > 0.0 { w[1].nav / w[0].nav - 1.0 } else { 0.0 }).collect()
+}
+
+fn std_dev(xs: &[f64]) -> f64 {
+    if xs.len() < 2 {
+        return 0.0;
+    }
+    let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+    (xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (xs.len() - 1) as f64).sqrt()
+}
+
+fn max_drawdown(curve: &[EquityPoint]) -> f64 {
+    let (mut peak, mut max_dd) = (f64::MIN, 0.0_f64);
+    for p in curve {
+        peak = peak.max(p.nav);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - p.nav) / peak);
+        }
+    }
+    max_dd
+}
+
+/// Fraction of bars between a trade's entry and exit.
+fn exposure(r: &BacktestResult, candles: &[crate::utils::Candle]) -> f64 {
+    if candles.is_empty() {
+        return 0.0;
+    }
+    let index: std::collections::HashMap<&str, usize> =
+        candles.iter().enumerate().map(|(i, c)| (c.timestamp.as_str(), i)).collect();
+    let mut held = vec![false; candles.len()];
+    for t in &r.trade_log {
+        if let (Some(&a), Some(&b)) = (index.get(t.entry_time.as_str()), index.get(t.exit_time.as_str())) {
+            held[a..b.max(a)].iter_mut().for_each(|h| *h = true);
+        }
+    }
+    held.iter().filter(|h| **h).count() as f64 / candles.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::fixtures::sine_candles;
+
+    fn candles() -> Vec<Value> {
+        sine_candles(160, 0.0, 1.0, 0.01)
+    }
+
+    fn request(strategies: Value, weights: Option<Value>) -> Value {
+        let mut req = json!({
+            "symbol": "X", "initial_capital": 1_000_000.0, "candles": candles(),
+            "strategy": "ema_crossover", "strategies": strategies,
+        });
+        if let Some(w) = weights {
+            req["weights"] = w;
+        }
+        req
+    }
+
+    #[test]
+    fn test_compare_matches_single_backtests_and_combines() {
+        let r = compute(request(json!([
+            { "strategy": "ema_crossover" },
+            { "name": "rsi", "strategy": "rsi_reversal" },
+        ]), None)).unwrap();
+        assert_eq!(r["names"], json!(["ema_crossover", "rsi"]));
+
+        let single = crate::backtest::run(json!({
+            "strategy": "ema_crossover", "symbol": "X", "initial_capital": 1_000_000.0, "candles": candles(),
+        })).unwrap();
+        assert_eq!(r["strategies"][0]["sharpe_ratio"], single["sharpe_ratio"]);
+        assert_eq!(r["strategies"][0]["total_trades"], single["total_trades"]);
+
+        let corr = &r["correlation_matrix"];
+        assert_eq!(corr[0][0], 1.0);
+        assert_eq!(corr[0][1], corr[1][0]);
+        assert!(corr[0][1].as_f64().unwrap().abs() <= 1.0);
+
+        // Equal weights: combined NAV is the average of the two curves.
+        let navs = |i: usize| r["equity_curves"][i].as_array().unwrap().last().unwrap()["nav"].as_f64().unwrap();
+        let combined = r["combined"]["equity_curve"].as_array().unwrap().last().unwrap()["nav"].as_f64().unwrap();
+        assert!((combined - (navs(0) + navs(1)) / 2.0).abs() < 0.02);
+        assert!(r["combined"]["diversification_ratio"].as_f64().unwrap() >= 1.0 - 1e-9);
+    }
+
+    #[test]
+    fn test_identical_configs_correlate_fully() {
+        let r = compute(request(json!([
+            { "name": "a" },
+            { "name": "b" },
+        ]), Some(json!([3.0, 1.0])))).unwrap();
+        assert_eq!(r["correlation_matrix"][0][1], 1.0);
+        assert_eq!(r["combined"]["weights"], json!([0.75, 0.25]));
+        assert_eq!(r["combined"]["diversification_ratio"], 1.0);
+        assert_eq!(r["combined"]["sharpe_vs_best"], 0.0);
+
+        assert!(compute(request(json!([{}, {}]), None)).is_err());
+        assert!(compute(request(json!([{ "name": "a" }]), None)).is_err());
+        assert!(compute(request(json!([{ "name": "a" }, { "name": "b" }]), Some(json!([1.0])))).is_err());
+    }
+}
@@ -56,6 +56,8 @@ pub struct EngineConfig {
     pub regime_strategy: RegimeStrategyConfig,
     #[serde(default)]
     pub survivorship: SurvivorshipConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
     #[serde(default = "default_initial_capital")]
     pub initial_capital: f64,
 }
@@ -68,6 +70,12 @@ pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub mode: String,
+    /// Request body cap for HTTP mode; raise it when shipping years of candles
+    /// to a remote engine.
+    pub max_body_mb: usize,
+    /// Requests running at once in serve (NDJSON) mode; each may also fan
+    /// out on the rayon pool. Further requests queue.
+    pub max_workers: usize,
 }
 
 impl Default for ServerConfig {
@@ -76,6 +84,8 @@ impl Default for ServerConfig {
             host: "127.0.0.1".into(),
             port: 8400,
             mode: "http".into(),
+            max_body_mb: 2,
+            max_workers: 4,
         }
     }
 }
@@ -87,6 +97,9 @@ pub struct MarketConfig {
     pub trading_days_per_year: f64,
     pub default_iv: f64,
     pub market_type: String,
+    /// IANA zone of the exchange; session logic (VWAP reset, IB, day
+    /// grouping) runs on this local time unless a request sets `timezone`.
+    pub timezone: String,
 }
 
 impl Default for MarketConfig {
@@ -96,6 +109,7 @@ impl Default for MarketConfig {
             trading_days_per_year: 252.0,
             default_iv: 0.20,
             market_type: "equity".into(),
+            timezone: "Asia/Kolkata".into(),
         }
     }
 }
@@ -175,19 +189,52 @@ pub struct BacktestConfig {
     pub default_position_size_pct: f64,
     pub rsi_oversold: f64,
     pub rsi_overbought: f64,
+    /// Optional `zscore(rsi_14, N)` or `percentile(rsi_14, N)` that the RSI
+    /// reversal thresholds apply to instead of raw RSI; empty for raw.
+    pub rsi_transform: String,
     pub ema_short_period: usize,
     pub ema_long_period: usize,
     pub sma_short_period: usize,
     pub sma_long_period: usize,
     pub orb_target_pct: f64,
     pub orb_stop_loss_pct: f64,
+    /// Minutes from the session's first bar that form the opening range.
+    pub orb_range_minutes: u32,
+    /// Break beyond the range needed to enter, in percent.
+    pub orb_buffer_pct: f64,
+    pub orb_max_trades_per_day: usize,
+    /// Exchange-local "HH:MM" at which intraday strategies flatten.
+    pub intraday_square_off: String,
     pub momentum_lookback: usize,
     pub momentum_hold_days: usize,
     pub mean_reversion_period: usize,
     pub mean_reversion_threshold: f64,
     pub vwap_deviation_threshold: f64,
+    /// VWAP-band reversion: entry beyond VWAP ± this many standard deviations.
+    pub vwap_band_mult: f64,
+    /// Where the banded VWAP restarts: "session", "week" or "none".
+    pub vwap_band_anchor: String,
+    /// Bars after which an unreverted VWAP-band trade is closed.
+    pub vwap_band_max_bars: usize,
+    /// Turtle: breakout and exit channel lengths in bars.
+    pub turtle_entry_period: usize,
+    pub turtle_exit_period: usize,
+    /// Units held at most, the first included.
+    pub turtle_max_units: usize,
+    /// Price advance, in N (ATR), that triggers the next unit.
+    pub turtle_add_step: f64,
+    /// Stop distance from the latest unit's entry, in N.
+    pub turtle_stop_n: f64,
+    /// Equity risked per unit on a 1 N move, in percent.
+    pub turtle_unit_risk_pct: f64,
     pub bb_period: usize,
     pub bb_std_mult: f64,
+    /// Bands behind `bb_*`: "bollinger", or a "quantile" / "expectile"
+    /// channel over `bb_period` at `bb_channel_levels`.
+    pub bb_band: String,
+    pub bb_channel_levels: [f64; 2],
+    /// Bars on each side of a Williams fractal.
+    pub fractal_span: usize,
     pub adx_period: usize,
     pub adx_trend_threshold: f64,
     pub gap_min_pct: f64,
@@ -202,19 +249,36 @@ impl Default for BacktestConfig {
             default_position_size_pct: 15.0,
             rsi_oversold: 30.0,
             rsi_overbought: 70.0,
+            rsi_transform: String::new(),
             ema_short_period: 9,
             ema_long_period: 21,
             sma_short_period: 10,
             sma_long_period: 30,
             orb_target_pct: 1.5,
             orb_stop_loss_pct: 0.75,
+            orb_range_minutes: 15,
+            orb_buffer_pct: 0.1,
+            orb_max_trades_per_day: 1,
+            intraday_square_off: "15:15".to_string(),
             momentum_lookback: 20,
             momentum_hold_days: 10,
             mean_reversion_period: 20,
             mean_reversion_threshold: 2.0,
             vwap_deviation_threshold: 1.5,
+            vwap_band_mult: 2.0,
+            vwap_band_anchor: "session".to_string(),
+            vwap_band_max_bars: 12,
+            turtle_entry_period: 20,
+            turtle_exit_period: 10,
+            turtle_max_units: 4,
+            turtle_add_step: 0.5,
+            turtle_stop_n: 2.0,
+            turtle_unit_risk_pct: 1.0,
             bb_period: 20,
             bb_std_mult: 2.0,
+            bb_band: "bollinger".to_string(),
+            bb_channel_levels: [0.1, 0.9],
+            fractal_span: 2,
             adx_period: 14,
             adx_trend_threshold: 25.0,
             gap_min_pct: 1.0,
@@ -517,6 +581,7 @@ impl Default for EngineConfig {
             universe: UniverseConfig::default(),
             regime_strategy: RegimeStrategyConfig::default(),
             survivorship: SurvivorshipConfig::default(),
+            limits: LimitsConfig::default(),
             initial_capital: 1_000_000.0,
         }
     }
@@ -779,6 +844,9 @@ impl EngineConfig {
         if self.market.risk_free_rate < 0.0 || self.market.risk_free_rate > 1.0 {
             errors.push("market.risk_free_rate must be in [0, 1]".to_string());
         }
+        if self.market.timezone.parse::<chrono_tz::Tz>().is_err() {
+            errors.push(format!("market.timezone '{}' is not an IANA zone", self.market.timezone));
+        }
         if self.risk.max_position_size_pct <= 0.0 || self.risk.max_position_size_pct > 100.0 {
             errors.push("risk.max_position_size_pct must be in (0, 100]".to_string());
         }
@@ -806,6 +874,9 @@ impl EngineConfig {
         if self.backtest.ema_short_period >= self.backtest.ema_long_period {
             errors.push("backtest.ema_short_period must be < ema_long_period".to_string());
         }
+        if crate::strategy::parse_clock(&self.backtest.intraday_square_off).is_none() {
+            errors.push("backtest.intraday_square_off must be HH:MM".to_string());
+        }
         if self.risk.max_gross_exposure_pct <= 0.0 {
             errors.push("risk.max_gross_exposure_pct must be > 0".to_string());
         }
@@ -815,6 +886,9 @@ impl EngineConfig {
         if self.initial_capital <= 0.0 {
             errors.push("initial_capital must be > 0".to_string());
         }
+        if !matches!(self.limits.on_exceed.as_str(), "error" | "degrade") {
+            errors.push("limits.on_exceed must be \"error\" or \"degrade\"".to_string());
+        }
 
         if errors.is_empty() {
             Ok(())
@@ -904,6 +978,38 @@ impl Default for RateLimitConfig {
     }
 }
 
+// ─── Request Limits ───────────────────────────────────────────────────
+
+/// Input size guardrails applied before any command runs. A value of 0
+/// disables that limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// Max candles per series (top level or per symbol).
+    pub max_candles: usize,
+    /// Max entries in a `symbols` array.
+    pub max_symbols: usize,
+    /// Max parameter combinations in an optimize/walk_forward `param_grid`.
+    pub max_param_combos: usize,
+    /// Max candle series cached with `load_dataset` in serve mode.
+    pub max_datasets: usize,
+    /// "error" rejects oversized requests; "degrade" downsamples candles,
+    /// truncates symbol lists and thins the grid, reporting warnings in `meta`.
+    pub on_exceed: String,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_candles: 500_000,
+            max_symbols: 2_000,
+            max_param_combos: 5_000,
+            max_datasets: 64,
+            on_exceed: "error".into(),
+        }
+    }
+}
+
 // ─── Circuit Breaker ──────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
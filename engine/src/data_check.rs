@@ -0,0 +1,411 @@
+//! Data-quality report for a candle series: gaps against the session
+//! schedule, duplicate / out-of-order timestamps, bad prices and outlier
+//! spikes. Every issue names a repair action; with `repair: true` the
+//! repaired series is returned as well. Holidays and weekends come from the
+//! exchange calendar, so they never count as missing bars.
+
+use std::collections::BTreeMap;
+use chrono::{NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::calendar::{Calendar, CalendarSpec};
+use crate::utils::{Candle, parse_timestamp, round2, round4};
+
+#[derive(Deserialize)]
+struct DataCheckConfig {
+    candles: Vec<Candle>,
+    /// Bar size; inferred from the median intraday spacing when omitted.
+    interval_minutes: Option<f64>,
+    /// Session window; defaults to the calendar's regular session.
+    session_start: Option<String>,
+    session_end: Option<String>,
+    #[serde(default)]
+    calendar: Option<CalendarSpec>,
+    /// Robust z-score (median/MAD of log returns) above which a bar is a spike.
+    #[serde(default = "default_spike_threshold")]
+    spike_threshold: f64,
+    #[serde(default = "default_max_issues")]
+    max_issues: usize,
+    #[serde(default)]
+    repair: bool,
+}
+
+fn default_spike_threshold() -> f64 { 10.0 }
+fn default_max_issues() -> usize { 500 }
+
+const MIN_RETURN_SCALE: f64 = 0.001;
+
+#[derive(Serialize, Clone)]
+struct Issue {
+    index: usize,
+    timestamp: String,
+    kind: &'static str,
+    severity: &'static str,
+    message: String,
+    /// What `repair: true` does about it: drop, sort, swap_high_low,
+    /// clamp_to_range, fill_previous_close, zero_volume, interpolate, review.
+    repair: &'static str,
+    /// Bars missing before this one (missing_bars only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    missing: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct DataCheckResult {
+    candles: usize,
+    interval_minutes: f64,
+    sessions: usize,
+    expected_bars: usize,
+    missing_bars: usize,
+    completeness_pct: f64,
+    clean: bool,
+    summary: BTreeMap<&'static str, usize>,
+    issues: Vec<Issue>,
+    issues_truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repaired_candles: Option<Vec<Candle>>,
+}
+
+struct Issues {
+    list: Vec<Issue>,
+    summary: BTreeMap<&'static str, usize>,
+}
+
+impl Issues {
+    fn push(&mut self, issue: Issue) {
+        *self.summary.entry(issue.kind).or_insert(0) += 1;
+        self.list.push(issue);
+    }
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: DataCheckConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid data_check config: {}", e))?;
+    if config.candles.is_empty() {
+        return Err("No candles provided".to_string());
+    }
+    let parse_hm = |s: &str| NaiveTime::parse_from_str(s, "%H:%M")
+        .map_err(|_| format!("Invalid session time '{}', expected HH:MM", s));
+    let calendar = Calendar::from_spec(config.calendar.as_ref())?;
+    let session_start = config.session_start.as_deref().map_or(Ok(calendar.session_open), parse_hm)?;
+    let session_end = config.session_end.as_deref().map_or(Ok(calendar.session_close), parse_hm)?;
+    if session_end <= session_start {
+        return Err("session_end must be after session_start".to_string());
+    }
+
+    let candles = &config.candles;
+    let times: Vec<Option<NaiveDateTime>> = candles.iter().map(|c| parse_timestamp(&c.timestamp)).collect();
+    let interval = match config.interval_minutes {
+        Some(m) if m > 0.0 => m,
+        Some(_) => return Err("interval_minutes must be positive".to_string()),
+        None => infer_interval(&times),
+    };
+
+    let mut issues = Issues { list: Vec::new(), summary: BTreeMap::new() };
+    let issue = |i: usize, kind, severity, message: String, repair| Issue {
+        index: i, timestamp: candles[i].timestamp.clone(), kind, severity, message, repair, missing: None,
+    };
+
+    check_prices(candles, &mut issues, &issue);
+    check_spikes(candles, config.spike_threshold, &mut issues, &issue);
+
+    let mut prev: Option<NaiveDateTime> = None;
+    for (i, t) in times.iter().enumerate() {
+        let Some(t) = *t else {
+            issues.push(issue(i, "unparseable_timestamp", "error",
+                format!("cannot parse timestamp '{}'", candles[i].timestamp), "drop"));
+            continue;
+        };
+        if let Some(p) = prev {
+            if t == p {
+                issues.push(issue(i, "duplicate_timestamp", "error", format!("duplicate of bar {}", i - 1), "drop"));
+                continue;
+            }
+            if t < p {
+                issues.push(issue(i, "out_of_order", "error", format!("{} is earlier than previous {}", t, p), "sort"));
+                continue;
+            }
+        }
+        prev = Some(t);
+    }
+
+    // Gap analysis runs on the ordered, de-duplicated timestamps.
+    let mut ordered: Vec<(usize, NaiveDateTime)> =
+        times.iter().enumerate().filter_map(|(i, t)| t.map(|t| (i, t))).collect();
+    ordered.sort_by_key(|(_, t)| *t);
+    ordered.dedup_by_key(|(_, t)| *t);
+    let gaps = find_gaps(&ordered, interval, session_start, session_end, &calendar);
+    let missing_bars: usize = gaps.iter().map(|(_, n)| n).sum();
+    for (i, n) in gaps {
+        let mut gap = issue(i, "missing_bars", "warning",
+            format!("{} bar(s) missing before this bar", n), "review");
+        gap.missing = Some(n);
+        issues.push(gap);
+    }
+
+    let sessions = {
+        let mut days: Vec<_> = ordered.iter().map(|(_, t)| t.date()).collect();
+        days.dedup();
+        days.len()
+    };
+    let expected_bars = ordered.len() + missing_bars;
+    let repaired_candles = config.repair.then(|| repair(candles, &times, &issues.list));
+
+    issues.list.sort_by_key(|i| i.index);
+    let issues_truncated = issues.list.len() > config.max_issues;
+    issues.list.truncate(config.max_issues);
+    let result = DataCheckResult {
+        candles: candles.len(),
+        interval_minutes: round4(interval),
+        sessions,
+        expected_bars,
+        missing_bars,
+        completeness_pct: if expected_bars > 0 { round2(ordered.len() as f64 / expected_bars as f64 * 100.0) } else { 0.0 },
+        clean: issues.summary.is_empty(),
+        summary: issues.summary,
+        issues: issues.list,
+        issues_truncated,
+        repaired_candles,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Median spacing between consecutive bars of the same day; daily (1440)
+/// when the series has at most one bar per day.
+fn infer_interval(times: &[Option<NaiveDateTime>]) -> f64 {
+    let mut diffs: Vec<f64> = times
+        .windows(2)
+        .filter_map(|w| match (w[0], w[1]) {
+            (Some(a), Some(b)) if a.date() == b.date() && b > a => Some((b - a).num_seconds() as f64 / 60.0),
+            _ => None,
+        })
+        .collect();
+    if diffs.is_empty() {
+        return 1440.0;
+    }
+    diffs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    diffs[diffs.len() / 2]
+}
+
+/// `(index, bars missing before it)` for every gap. Intraday series are
+/// checked against the session window, daily series against trading days.
+/// Special sessions (muhurat) are not regular days and are never gap-checked.
+fn find_gaps(
+    ordered: &[(usize, NaiveDateTime)],
+    interval: f64,
+    session_start: NaiveTime,
+    session_end: NaiveTime,
+    calendar: &Calendar,
+) -> Vec<(usize, usize)> {
+    let mut gaps = Vec::new();
+    if interval >= 1440.0 {
+        for w in ordered.windows(2) {
+            let (a, b) = (w[0].1.date(), w[1].1.date());
+            let sessions = a.iter_days().skip(1).take_while(|d| *d < b)
+                .filter(|d| calendar.is_regular_day(*d))
+                .count();
+            if sessions > 0 {
+                gaps.push((w[1].0, sessions));
+            }
+        }
+        return gaps;
+    }
+
+    let bars_between = |from: NaiveTime, to: NaiveTime| -> usize {
+        let minutes = (to - from).num_seconds() as f64 / 60.0;
+        (minutes / interval + 1e-9).floor().max(0.0) as usize
+    };
+    let in_session = |t: &NaiveDateTime| t.time() >= session_start && t.time() < session_end;
+    for (k, (i, t)) in ordered.iter().enumerate() {
+        if !calendar.is_regular_day(t.date()) || !in_session(t) {
+            continue;
+        }
+        let prev = k.checked_sub(1).map(|p| ordered[p].1);
+        let missing = match prev {
+            Some(p) if p.date() == t.date() && in_session(&p) => bars_between(p.time(), t.time()).saturating_sub(1),
+            // First bar of the session: count bars that should have preceded it.
+            _ => bars_between(session_start, t.time()),
+        };
+        if missing > 0 {
+            gaps.push((*i, missing));
+        }
+        // Last bar of the session: count bars that should have followed it.
+        let next_same_day = ordered.get(k + 1).is_some_and(|(_, n)| n.date() == t.date() && in_session(n));
+        if !next_same_day && k + 1 < ordered.len() {
+            let trailing = bars_between(t.time(), session_end).saturating_sub(1);
+            if trailing > 0 {
+                gaps.push((ordered[k + 1].0, trailing));
+            }
+        }
+    }
+    gaps
+}
+
+fn check_prices<F>(candles: &[Candle], issues: &mut Issues, issue: &F)
+where
+    F: Fn(usize, &'static str, &'static str, String, &'static str) -> Issue,
+{
+    for (i, c) in candles.iter().enumerate() {
+        let bad: Vec<&str> = [("open", c.open), ("high", c.high), ("low", c.low), ("close", c.close)]
+            .iter()
+            .filter(|(_, v)| !v.is_finite() || *v <= 0.0)
+            .map(|(name, _)| *name)
+            .collect();
+        if !bad.is_empty() {
+            issues.push(issue(i, "non_positive_price", "error",
+                format!("{} must be positive", bad.join("/")), "fill_previous_close"));
+            continue;
+        }
+        if c.high < c.low {
+            issues.push(issue(i, "high_below_low", "error",
+                format!("high {} below low {}", c.high, c.low), "swap_high_low"));
+        } else if c.open > c.high || c.open < c.low || c.close > c.high || c.close < c.low {
+            issues.push(issue(i, "outside_range", "warning",
+                format!("open {} / close {} outside [{}, {}]", c.open, c.close, c.low, c.high), "clamp_to_range"));
+        }
+        if c.volume < 0.0 {
+            issues.push(issue(i, "negative_volume", "error", format!("volume {}", c.volume), "zero_volume"));
+        }
+    }
+}
+
+/// Flags closes whose log return is an outlier by robust z-score. A spike
+/// that mostly reverses on the next bar looks like a bad tick and is
+/// interpolated on repair; a move that holds is left for review.
+fn check_spikes<F>(candles: &[Candle], threshold: f64, issues: &mut Issues, issue: &F)
+where
+    F: Fn(usize, &'static str, &'static str, String, &'static str) -> Issue,
+{
+    if candles.len() < 10 || threshold <= 0.0 {
+        return;
+    }
+    let returns: Vec<f64> = candles.windows(2)
+        .map(|w| if w[0].close > 0.0 && w[1].close > 0.0 { (w[1].close / w[0].close).ln() } else { 0.0 })
+        .collect();
+    let median = |xs: &mut Vec<f64>| {
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        xs[xs.len() / 2]
+    };
+    let med = median(&mut returns.clone());
+    // Floor the scale at a 0.1% move so near-flat series do not turn ordinary
+    // ticks into spikes.
+    let mad = (median(&mut returns.iter().map(|r| (r - med).abs()).collect()) * 1.4826).max(MIN_RETURN_SCALE);
+    for (k, r) in returns.iter().enumerate() {
+        let z = (r - med) / mad;
+        if z.abs() < threshold {
+            continue;
+        }
+        let i = k + 1;
+        let reverted = returns.get(i).is_some_and(|next| next.signum() != r.signum() && next.abs() >= r.abs() * 0.5);
+        issues.push(issue(i, "outlier_spike", "warning",
+            format!("close moved {:.2}% (robust z {:.1}){}", (r.exp() - 1.0) * 100.0, z,
+                if reverted { ", reversed next bar" } else { "" }),
+            if reverted { "interpolate" } else { "review" }));
+    }
+}
+
+fn repair(candles: &[Candle], times: &[Option<NaiveDateTime>], issues: &[Issue]) -> Vec<Candle> {
+    let mut fixed: Vec<Candle> = candles.to_vec();
+    let mut drop = vec![false; candles.len()];
+    for issue in issues {
+        let i = issue.index;
+        match issue.repair {
+            "drop" => drop[i] = true,
+            "swap_high_low" => {
+                let c = &mut fixed[i];
+                std::mem::swap(&mut c.high, &mut c.low);
+            }
+            "zero_volume" => fixed[i].volume = 0.0,
+            "interpolate" if i + 1 < candles.len() => {
+                let close = (candles[i - 1].close + candles[i + 1].close) / 2.0;
+                let c = &mut fixed[i];
+                c.close = close;
+                c.open = candles[i - 1].close;
+                c.high = c.open.max(close);
+                c.low = c.open.min(close);
+            }
+            _ => {}
+        }
+    }
+    // fill_previous_close and clamp_to_range are what sanitize_candles does.
+    crate::utils::sanitize_candles(&mut fixed);
+    for c in fixed.iter_mut() {
+        c.open = c.open.clamp(c.low, c.high);
+    }
+
+    let mut kept: Vec<(Option<NaiveDateTime>, Candle)> = fixed
+        .into_iter()
+        .zip(times.iter())
+        .zip(drop)
+        .filter(|(_, d)| !d)
+        .map(|((c, t), _)| (*t, c))
+        .collect();
+    kept.sort_by_key(|(t, _)| *t);
+    kept.dedup_by_key(|(t, _)| *t);
+    kept.into_iter().map(|(_, c)| c).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bar(ts: &str, close: f64) -> Value {
+        json!({ "timestamp": ts, "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 100.0 })
+    }
+
+    fn session(day: &str, skip: &[usize]) -> Vec<Value> {
+        (0..75).filter(|i| !skip.contains(i)).map(|i| {
+            let m = 9 * 60 + 15 + i * 5;
+            bar(&format!("{}T{:02}:{:02}:00", day, m / 60, m % 60), 100.0 + (i % 3) as f64 * 0.1)
+        }).collect()
+    }
+
+    #[test]
+    fn test_clean_series() {
+        let mut candles = session("2024-01-02", &[]);
+        candles.extend(session("2024-01-03", &[]));
+        let result = compute(json!({ "candles": candles })).unwrap();
+        assert_eq!(result["clean"], true, "{}", result["issues"]);
+        assert_eq!(result["interval_minutes"], 5.0);
+        assert_eq!(result["sessions"], 2);
+        assert_eq!(result["expected_bars"], 150);
+    }
+
+    #[test]
+    fn test_detects_and_repairs_issues() {
+        let mut candles = session("2024-01-02", &[0, 10, 11, 74]);
+        candles[5]["high"] = json!(90.0);
+        candles[30]["close"] = json!(200.0);
+        candles[30]["high"] = json!(201.0);
+        candles[40]["close"] = json!(-1.0);
+        candles.insert(20, candles[19].clone());
+        candles.extend(session("2024-01-03", &[]));
+        let result = compute(json!({ "candles": candles, "repair": true })).unwrap();
+        let summary = &result["summary"];
+        assert_eq!(summary["high_below_low"], 1);
+        assert_eq!(summary["duplicate_timestamp"], 1);
+        assert_eq!(summary["non_positive_price"], 1);
+        assert!(summary["outlier_spike"].as_u64().unwrap() >= 1);
+        // Opening bar, two mid-session bars and the closing bar.
+        assert_eq!(result["missing_bars"], 4);
+
+        let repaired = result["repaired_candles"].as_array().unwrap();
+        assert_eq!(repaired.len(), candles.len() - 1);
+        assert!(repaired.iter().all(|c| c["high"].as_f64() >= c["low"].as_f64() && c["close"].as_f64().unwrap() > 0.0));
+        assert!(repaired[30]["close"].as_f64().unwrap() < 150.0);
+    }
+
+    #[test]
+    fn test_daily_gaps_skip_weekends_and_holidays() {
+        // Fri 5th, Mon 8th, Wed 10th: only Tue 9th is missing.
+        let candles = vec![bar("2024-01-05", 100.0), bar("2024-01-08", 101.0), bar("2024-01-10", 102.0)];
+        let result = compute(json!({ "candles": candles })).unwrap();
+        assert_eq!(result["interval_minutes"], 1440.0);
+        assert_eq!(result["missing_bars"], 1);
+        assert_eq!(result["issues"][0]["index"], 2);
+        // Thu 25th to Mon 29th spans the Republic Day holiday and a weekend.
+        let candles = vec![bar("2024-01-25", 100.0), bar("2024-01-29", 101.0)];
+        assert_eq!(compute(json!({ "candles": candles })).unwrap()["clean"], true);
+    }
+}
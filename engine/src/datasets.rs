@@ -0,0 +1,171 @@
+//! In-memory candle datasets for long-lived (serve/daemon) processes.
+//!
+//! `load_dataset {"dataset_id", "candles" | "candles_file"}` parses and
+//! caches a series once. Later requests reference it with
+//! `"dataset_id": "..."` wherever they would put `candles` — top level or per
+//! symbol in a scan — and it is spliced in before guardrails and dispatch.
+
+use std::sync::Arc;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::LimitsConfig;
+use crate::utils::{Candle, round3, sanitize_candles};
+use crate::EST_CANDLE_BYTES;
+
+struct Dataset {
+    candles: Arc<Value>,
+    len: usize,
+    first: Option<String>,
+    last: Option<String>,
+    loaded_at: String,
+}
+
+static DATASETS: Lazy<DashMap<String, Dataset>> = Lazy::new(DashMap::new);
+
+#[derive(Deserialize)]
+struct LoadInput {
+    dataset_id: String,
+    candles: Vec<Candle>,
+}
+
+#[derive(Deserialize)]
+struct DropInput {
+    dataset_id: String,
+}
+
+#[derive(Serialize)]
+struct DatasetInfo {
+    dataset_id: String,
+    candles: usize,
+    first_timestamp: Option<String>,
+    last_timestamp: Option<String>,
+    est_mb: f64,
+    loaded_at: String,
+}
+
+impl DatasetInfo {
+    fn new(id: &str, ds: &Dataset) -> Self {
+        DatasetInfo {
+            dataset_id: id.to_string(),
+            candles: ds.len,
+            first_timestamp: ds.first.clone(),
+            last_timestamp: ds.last.clone(),
+            est_mb: round3(ds.len as f64 * EST_CANDLE_BYTES / (1024.0 * 1024.0)),
+            loaded_at: ds.loaded_at.clone(),
+        }
+    }
+}
+
+/// Replace every `dataset_id` key with the cached `candles` array.
+pub fn resolve(data: &mut Value) -> Result<(), String> {
+    match data {
+        Value::Object(map) => {
+            if let Some(id) = map.remove("dataset_id") {
+                let id = id.as_str().ok_or("dataset_id must be a string")?;
+                let ds = DATASETS
+                    .get(id)
+                    .ok_or_else(|| format!("Unknown dataset_id '{}' (load it with load_dataset)", id))?;
+                map.insert("candles".to_string(), (*ds.candles).clone());
+            }
+            map.values_mut().try_for_each(resolve)
+        }
+        Value::Array(items) => items.iter_mut().try_for_each(resolve),
+        _ => Ok(()),
+    }
+}
+
+pub fn load(data: Value, limits: &LimitsConfig) -> Result<Value, String> {
+    let mut input: LoadInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid load_dataset input: {}", e))?;
+    if input.dataset_id.is_empty() {
+        return Err("dataset_id must not be empty".to_string());
+    }
+    if input.candles.is_empty() {
+        return Err("load_dataset requires at least one candle".to_string());
+    }
+    let replacing = DATASETS.contains_key(&input.dataset_id);
+    if !replacing && limits.max_datasets > 0 && DATASETS.len() >= limits.max_datasets {
+        return Err(format!(
+            "Dataset limit reached ({}); drop_dataset before loading more", limits.max_datasets));
+    }
+    let repaired = sanitize_candles(&mut input.candles);
+    let dataset = Dataset {
+        len: input.candles.len(),
+        first: input.candles.first().map(|c| c.timestamp.clone()),
+        last: input.candles.last().map(|c| c.timestamp.clone()),
+        candles: Arc::new(serde_json::to_value(&input.candles).map_err(|e| format!("Serialization error: {}", e))?),
+        loaded_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let mut info = serde_json::to_value(DatasetInfo::new(&input.dataset_id, &dataset))
+        .map_err(|e| format!("Serialization error: {}", e))?;
+    info["replaced"] = Value::Bool(replacing);
+    info["repaired"] = Value::from(repaired);
+    DATASETS.insert(input.dataset_id, dataset);
+    Ok(info)
+}
+
+pub fn remove(data: Value) -> Result<Value, String> {
+    let input: DropInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid drop_dataset input: {}", e))?;
+    let dropped = DATASETS.remove(&input.dataset_id).is_some();
+    Ok(serde_json::json!({ "dataset_id": input.dataset_id, "dropped": dropped }))
+}
+
+pub fn list() -> Result<Value, String> {
+    let mut datasets: Vec<DatasetInfo> = DATASETS
+        .iter()
+        .map(|entry| DatasetInfo::new(entry.key(), entry.value()))
+        .collect();
+    datasets.sort_by(|a, b| a.dataset_id.cmp(&b.dataset_id));
+    let total: usize = datasets.iter().map(|d| d.candles).sum();
+    Ok(serde_json::json!({ "datasets": datasets, "total_candles": total }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// The registry is process-wide; keep these tests from racing on its size.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn candles(n: usize) -> Vec<Value> {
+        (0..n).map(|i| json!({
+            "timestamp": format!("2024-01-01T10:{:02}:00", i), "open": 100.0, "high": 101.0,
+            "low": 99.0, "close": 100.5, "volume": 1000.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_load_resolve_and_drop() {
+        let _guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let info = load(json!({ "dataset_id": "ds_test_a", "candles": candles(5) }), &LimitsConfig::default()).unwrap();
+        assert_eq!(info["candles"], 5);
+        assert_eq!(info["last_timestamp"], "2024-01-01T10:04:00");
+
+        let mut req = json!({ "dataset_id": "ds_test_a", "symbols": [{ "symbol": "X", "dataset_id": "ds_test_a" }] });
+        resolve(&mut req).unwrap();
+        assert_eq!(req["candles"].as_array().unwrap().len(), 5);
+        assert_eq!(req["symbols"][0]["candles"].as_array().unwrap().len(), 5);
+        assert!(req.get("dataset_id").is_none());
+
+        assert_eq!(remove(json!({ "dataset_id": "ds_test_a" })).unwrap()["dropped"], true);
+        let err = resolve(&mut json!({ "dataset_id": "ds_test_a" })).unwrap_err();
+        assert!(err.contains("Unknown dataset_id"));
+    }
+
+    #[test]
+    fn test_dataset_limit() {
+        let _guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let limits = LimitsConfig { max_datasets: DATASETS.len() + 1, ..LimitsConfig::default() };
+        load(json!({ "dataset_id": "ds_test_b", "candles": candles(2) }), &limits).unwrap();
+        let limits = LimitsConfig { max_datasets: DATASETS.len(), ..LimitsConfig::default() };
+        assert!(load(json!({ "dataset_id": "ds_test_c", "candles": candles(2) }), &limits).is_err());
+        // Replacing an existing id does not count against the limit.
+        assert_eq!(load(json!({ "dataset_id": "ds_test_b", "candles": candles(3) }), &limits).unwrap()["replaced"], true);
+        remove(json!({ "dataset_id": "ds_test_b" })).unwrap();
+    }
+}
@@ -0,0 +1,330 @@
+//! Merge signals from several sources into one ranked list.
+//!
+//! A source is anything that emits per-symbol opinions: a `scan` run (on
+//! any timeframe), strategy rules, or an external model. Signals carry a
+//! `direction` and `confidence`, or a signed `score` in [-1, 1]. Each
+//! source has a `weight`. `method` combines them per symbol:
+//! - `weighted` (default): weighted mean of signed confidence.
+//! - `majority`: the side with more vote weight wins.
+//! - `unanimous`: every reporting source must agree.
+//!
+//! `conflict` decides what happens when sources disagree: `net` lets
+//! opposing votes cancel, `skip` drops the symbol, and `strongest` follows
+//! the single most confident weighted vote.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::round4;
+
+#[derive(Deserialize)]
+struct EnsembleConfig {
+    sources: Vec<Source>,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default = "default_conflict")]
+    conflict: String,
+    #[serde(default = "default_min_sources")]
+    min_sources: usize,
+    #[serde(default)]
+    min_confidence: f64,
+    /// Share of reporting weight that must side with the result (0–1).
+    #[serde(default)]
+    min_agreement: f64,
+    /// Sources silent on a symbol count as neutral votes in `weighted`.
+    #[serde(default)]
+    missing_as_neutral: bool,
+    #[serde(default)]
+    top_n: Option<usize>,
+}
+
+fn default_method() -> String { "weighted".to_string() }
+fn default_conflict() -> String { "net".to_string() }
+fn default_min_sources() -> usize { 1 }
+
+#[derive(Deserialize)]
+struct Source {
+    name: String,
+    #[serde(default = "default_weight")]
+    weight: f64,
+    #[serde(default)]
+    signals: Vec<SignalIn>,
+    /// A whole `scan` response; its `signals` are used.
+    #[serde(default)]
+    scan: Option<ScanIn>,
+}
+
+fn default_weight() -> f64 { 1.0 }
+
+#[derive(Deserialize)]
+struct ScanIn {
+    signals: Vec<SignalIn>,
+}
+
+#[derive(Deserialize, Clone)]
+struct SignalIn {
+    symbol: String,
+    #[serde(default)]
+    direction: Option<String>,
+    #[serde(default)]
+    confidence: Option<f64>,
+    #[serde(default)]
+    score: Option<f64>,
+    #[serde(default)]
+    entry: Option<f64>,
+    #[serde(default)]
+    stop_loss: Option<f64>,
+    #[serde(default)]
+    target: Option<f64>,
+    #[serde(default)]
+    strategy: Option<String>,
+}
+
+/// One source's opinion on a symbol.
+struct Ballot {
+    dir: f64,
+    conf: f64,
+    weight: f64,
+    source: String,
+    signal: SignalIn,
+}
+
+#[derive(Serialize, Clone)]
+struct Vote {
+    source: String,
+    direction: &'static str,
+    confidence: f64,
+    weight: f64,
+}
+
+#[derive(Serialize)]
+struct EnsembleSignal {
+    rank: usize,
+    symbol: String,
+    direction: &'static str,
+    confidence: f64,
+    /// Weighted signed vote in [-1, 1].
+    net_score: f64,
+    agreement: f64,
+    sources_agreeing: usize,
+    sources_opposing: usize,
+    entry: Option<f64>,
+    stop_loss: Option<f64>,
+    target: Option<f64>,
+    strategies: Vec<String>,
+    votes: Vec<Vote>,
+}
+
+#[derive(Serialize)]
+struct Rejected {
+    symbol: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct EnsembleResult {
+    method: String,
+    conflict: String,
+    signals: Vec<EnsembleSignal>,
+    rejected: Vec<Rejected>,
+}
+
+fn side(direction: &str) -> Option<f64> {
+    match direction.to_uppercase().as_str() {
+        "BUY" | "LONG" | "BULLISH" | "B" => Some(1.0),
+        "SELL" | "SHORT" | "BEARISH" | "S" => Some(-1.0),
+        "NEUTRAL" | "HOLD" | "FLAT" => Some(0.0),
+        _ => None,
+    }
+}
+
+fn sign(x: f64) -> f64 {
+    if x > 0.0 { 1.0 } else if x < 0.0 { -1.0 } else { 0.0 }
+}
+
+fn side_name(s: f64) -> &'static str {
+    if s > 0.0 { "BUY" } else if s < 0.0 { "SELL" } else { "NEUTRAL" }
+}
+
+/// (signed side, confidence in [0, 1]) of one signal.
+fn opinion(s: &SignalIn) -> Result<(f64, f64), String> {
+    match (&s.direction, s.score) {
+        (Some(d), _) => {
+            let dir = side(d).ok_or_else(|| format!("{}: unknown direction '{}'", s.symbol, d))?;
+            Ok((dir, s.confidence.or(s.score.map(f64::abs)).unwrap_or(1.0).clamp(0.0, 1.0)))
+        }
+        (None, Some(score)) => Ok((sign(score), score.abs().min(1.0))),
+        (None, None) => Err(format!("{}: signal needs a direction or a score", s.symbol)),
+    }
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: EnsembleConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid ensemble config: {}", e))?;
+    if !["weighted", "majority", "unanimous"].contains(&config.method.as_str()) {
+        return Err(format!("Unknown method '{}' (weighted, majority, unanimous)", config.method));
+    }
+    if !["net", "skip", "strongest"].contains(&config.conflict.as_str()) {
+        return Err(format!("Unknown conflict '{}' (net, skip, strongest)", config.conflict));
+    }
+    let total_weight: f64 = config.sources.iter().map(|s| s.weight.max(0.0)).sum();
+
+    let mut by_symbol: BTreeMap<String, Vec<Ballot>> = BTreeMap::new();
+    for src in &config.sources {
+        let signals = match &src.scan {
+            Some(scan) if src.signals.is_empty() => &scan.signals,
+            _ => &src.signals,
+        };
+        for s in signals {
+            let (dir, conf) = opinion(s).map_err(|e| format!("source {}: {}", src.name, e))?;
+            by_symbol.entry(s.symbol.clone()).or_default().push(Ballot { dir, conf, weight: src.weight.max(0.0), source: src.name.clone(), signal: s.clone() });
+        }
+    }
+
+    let mut signals = Vec::new();
+    let mut rejected = Vec::new();
+    for (symbol, votes) in by_symbol {
+        let reject = |reason: String| Rejected { symbol: symbol.clone(), reason };
+        if votes.len() < config.min_sources {
+            rejected.push(reject(format!("{} of {} required sources", votes.len(), config.min_sources)));
+            continue;
+        }
+        let reporting: f64 = votes.iter().map(|v| v.weight).sum();
+        let weight_for = |s: f64| votes.iter().filter(|v| v.dir == s).map(|v| v.weight).sum::<f64>();
+        let (long_w, short_w) = (weight_for(1.0), weight_for(-1.0));
+        let conflicted = long_w > 0.0 && short_w > 0.0;
+        if conflicted && config.conflict == "skip" {
+            rejected.push(reject("sources disagree".to_string()));
+            continue;
+        }
+        let denom = if config.missing_as_neutral { total_weight } else { reporting };
+        let net = if denom > 0.0 { votes.iter().map(|v| v.dir * v.conf * v.weight).sum::<f64>() / denom } else { 0.0 };
+
+        let (dir, confidence) = if conflicted && config.conflict == "strongest" {
+            let best = votes.iter().filter(|v| v.dir != 0.0).max_by(|a, b| (a.conf * a.weight).total_cmp(&(b.conf * b.weight))).map(|v| v.dir).unwrap_or(0.0);
+            let agreeing: Vec<_> = votes.iter().filter(|v| v.dir == best).collect();
+            let w: f64 = agreeing.iter().map(|v| v.weight).sum();
+            (best, if w > 0.0 { agreeing.iter().map(|v| v.conf * v.weight).sum::<f64>() / w } else { 0.0 })
+        } else {
+            match config.method.as_str() {
+                "weighted" => (sign(net), net.abs()),
+                _ => {
+                    if config.method == "unanimous" && conflicted {
+                        rejected.push(reject("not unanimous".to_string()));
+                        continue;
+                    }
+                    let d = if long_w > short_w { 1.0 } else if short_w > long_w { -1.0 } else { 0.0 };
+                    let agreeing: Vec<_> = votes.iter().filter(|v| v.dir == d).collect();
+                    let w: f64 = agreeing.iter().map(|v| v.weight).sum();
+                    let avg_conf = if w > 0.0 { agreeing.iter().map(|v| v.conf * v.weight).sum::<f64>() / w } else { 0.0 };
+                    (d, avg_conf * if reporting > 0.0 { w / reporting } else { 0.0 })
+                }
+            }
+        };
+        if dir == 0.0 {
+            rejected.push(reject("no net direction".to_string()));
+            continue;
+        }
+        let agreeing_w = weight_for(dir);
+        let agreement = if reporting > 0.0 { agreeing_w / reporting } else { 0.0 };
+        if agreement + 1e-12 < config.min_agreement {
+            rejected.push(reject(format!("agreement {:.2} below min_agreement", agreement)));
+            continue;
+        }
+        if confidence < config.min_confidence {
+            rejected.push(reject(format!("confidence {:.2} below min_confidence", confidence)));
+            continue;
+        }
+        // Levels from the most confident agreeing source that supplies them.
+        let mut agreeing: Vec<_> = votes.iter().filter(|v| v.dir == dir).collect();
+        agreeing.sort_by(|a, b| (b.conf * b.weight).total_cmp(&(a.conf * a.weight)));
+        let level = |f: fn(&SignalIn) -> Option<f64>| agreeing.iter().find_map(|v| f(&v.signal));
+        let mut strategies: Vec<String> = agreeing.iter().filter_map(|v| v.signal.strategy.clone()).collect();
+        strategies.dedup();
+        signals.push(EnsembleSignal {
+            rank: 0,
+            symbol,
+            direction: side_name(dir),
+            confidence: round4(confidence.min(1.0)),
+            net_score: round4(net),
+            agreement: round4(agreement),
+            sources_agreeing: agreeing.len(),
+            sources_opposing: votes.iter().filter(|v| v.dir == -dir).count(),
+            entry: level(|s| s.entry),
+            stop_loss: level(|s| s.stop_loss),
+            target: level(|s| s.target),
+            strategies,
+            votes: votes.iter().map(|v| Vote { source: v.source.clone(), direction: side_name(v.dir), confidence: round4(v.conf), weight: v.weight }).collect(),
+        });
+    }
+
+    signals.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then(b.agreement.total_cmp(&a.agreement)));
+    if let Some(n) = config.top_n {
+        for s in signals.drain(n.min(signals.len())..) {
+            rejected.push(Rejected { symbol: s.symbol, reason: "outside top_n".to_string() });
+        }
+    }
+    for (i, s) in signals.iter_mut().enumerate() {
+        s.rank = i + 1;
+    }
+    let result = EnsembleResult { method: config.method, conflict: config.conflict, signals, rejected };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sources() -> Value {
+        json!([
+            { "name": "scan_15m", "weight": 1.0, "scan": { "signals": [
+                { "symbol": "INFY", "direction": "BUY", "confidence": 0.8, "entry": 1500.0, "stop_loss": 1480.0, "target": 1540.0 },
+                { "symbol": "TCS", "direction": "SELL", "confidence": 0.6 }
+            ] } },
+            { "name": "scan_1d", "weight": 2.0, "signals": [
+                { "symbol": "INFY", "direction": "BUY", "confidence": 0.5, "strategy": "trend" },
+                { "symbol": "TCS", "direction": "BUY", "confidence": 0.9 }
+            ] },
+            { "name": "model", "weight": 1.0, "signals": [{ "symbol": "HDFC", "score": -0.7 }, { "symbol": "TCS", "score": -0.2 }] }
+        ])
+    }
+
+    #[test]
+    fn test_weighted_net_and_ranking() {
+        let result = compute(json!({ "sources": sources() })).unwrap();
+        let s = result["signals"].as_array().unwrap();
+        // INFY: (0.8 + 2·0.5) / 3 = 0.6; HDFC: 0.7 from the model alone.
+        assert_eq!(s[0]["symbol"], "HDFC");
+        assert_eq!(s[0]["direction"], "SELL");
+        assert_eq!(s[1]["symbol"], "INFY");
+        assert_eq!(s[1]["confidence"], 0.6);
+        assert_eq!(s[1]["entry"], 1500.0);
+        assert_eq!(s[1]["agreement"], 1.0);
+        // TCS: (-0.6 + 1.8 - 0.2) / 4 = 0.25 long, half the weight opposing.
+        assert_eq!(s[2]["symbol"], "TCS");
+        assert_eq!(s[2]["direction"], "BUY");
+        assert_eq!(s[2]["sources_opposing"], 2);
+        assert_eq!(s[2]["agreement"], 0.5);
+    }
+
+    #[test]
+    fn test_conflict_modes_and_filters() {
+        let skip = compute(json!({ "sources": sources(), "conflict": "skip" })).unwrap();
+        assert_eq!(skip["signals"].as_array().unwrap().len(), 2);
+        assert_eq!(skip["rejected"][0]["symbol"], "TCS");
+
+        let unanimous = compute(json!({ "sources": sources(), "method": "unanimous", "min_sources": 2 })).unwrap();
+        let s = unanimous["signals"].as_array().unwrap();
+        assert_eq!(s.len(), 1);
+        assert_eq!(s[0]["symbol"], "INFY");
+        // Average confidence of agreeing votes, weighted: (0.8 + 1.0) / 3.
+        assert_eq!(s[0]["confidence"], 0.6);
+
+        // Majority: TCS has 2 weight long vs 2 short → no direction.
+        let majority = compute(json!({ "sources": sources(), "method": "majority", "missing_as_neutral": true, "top_n": 1 })).unwrap();
+        assert_eq!(majority["signals"].as_array().unwrap().len(), 1);
+        assert!(majority["rejected"].as_array().unwrap().iter().any(|r| r["symbol"] == "TCS" && r["reason"] == "no net direction"));
+        assert!(compute(json!({ "sources": sources(), "method": "stacking" })).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Equity-curve trading overlay for backtest equity curves.
+//!
+//! The strategy keeps running on paper, and its own daily equity decides how
+//! much of it is traded: while the raw curve's last close is below its
+//! `ma_period`-day simple average, exposure drops to `reduced_exposure`
+//! (0 stops trading, 0.5 halves size) until the raw curve closes back at or
+//! above the average. The decision for each day uses closes up to the
+//! previous one, so there is no look-ahead; exposure stays at 1 until the
+//! average has `ma_period` closes. Each switch can be charged a cost.
+
+use serde::{Deserialize, Serialize};
+use crate::backtest::EquityPoint;
+use crate::utils::{round2, round4};
+use crate::vol_target::{daily_closes, stats, years, CurveStats};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct EquityFilterSpec {
+    /// Daily closes in the equity moving average.
+    #[serde(default = "default_ma_period")]
+    pub ma_period: usize,
+    /// Exposure held while equity is below its average, 0 to 1.
+    #[serde(default)]
+    pub reduced_exposure: f64,
+    /// Charged on |Δ exposure| × NAV at each switch.
+    #[serde(default)]
+    pub switch_cost_bps: f64,
+}
+
+fn default_ma_period() -> usize { 20 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EquityFilterPoint {
+    pub date: String,
+    pub raw_nav: f64,
+    /// Average of the raw closes up to `date`; None until it has `ma_period`.
+    pub raw_ma: Option<f64>,
+    pub filtered_nav: f64,
+    /// Exposure held over the day ending at `date`.
+    pub exposure: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EquityFilterResult {
+    pub ma_period: usize,
+    pub reduced_exposure: f64,
+    pub raw: CurveStats,
+    pub filtered: CurveStats,
+    /// Days held at `reduced_exposure`, as % of all days.
+    pub reduced_days_pct: f64,
+    pub switches: usize,
+    pub switch_costs: f64,
+    pub curve: Vec<EquityFilterPoint>,
+}
+
+/// Apply the overlay to a bar-level equity curve, collapsed to daily closes
+/// the same way as [`crate::vol_target::overlay`].
+pub(crate) fn overlay(
+    curve: &[EquityPoint],
+    spec: &EquityFilterSpec,
+    trading_days: f64,
+    bars_per_day: f64,
+) -> Result<EquityFilterResult, String> {
+    if spec.ma_period < 2 {
+        return Err("equity_filter.ma_period must be at least 2".to_string());
+    }
+    if !(0.0..=1.0).contains(&spec.reduced_exposure) {
+        return Err("equity_filter.reduced_exposure must be between 0 and 1".to_string());
+    }
+    if !(spec.switch_cost_bps.is_finite() && spec.switch_cost_bps >= 0.0) {
+        return Err("equity_filter.switch_cost_bps must be non-negative".to_string());
+    }
+
+    let days = daily_closes(curve, bars_per_day);
+    let ma = |end: usize| (end + 1 >= spec.ma_period)
+        .then(|| days[end + 1 - spec.ma_period..=end].iter().map(|d| d.1).sum::<f64>() / spec.ma_period as f64);
+    let Some(first) = days.first() else {
+        return Ok(EquityFilterResult {
+            ma_period: spec.ma_period,
+            reduced_exposure: spec.reduced_exposure,
+            raw: stats(&[], 0.0, trading_days),
+            filtered: stats(&[], 0.0, trading_days),
+            reduced_days_pct: 0.0,
+            switches: 0,
+            switch_costs: 0.0,
+            curve: vec![],
+        });
+    };
+
+    let mut out = vec![EquityFilterPoint {
+        date: first.0.clone(),
+        raw_nav: round2(first.1),
+        raw_ma: ma(0).map(round2),
+        filtered_nav: round2(first.1),
+        exposure: 1.0,
+    }];
+    let mut nav = first.1;
+    let mut exposure = 1.0;
+    let (mut switches, mut reduced_days, mut costs) = (0usize, 0usize, 0.0);
+    let mut raw_returns = Vec::with_capacity(days.len());
+    let mut filtered_returns = Vec::with_capacity(days.len());
+    for (i, w) in days.windows(2).enumerate() {
+        let ret = if w[0].1 > 0.0 { w[1].1 / w[0].1 - 1.0 } else { 0.0 };
+        // Trade today on where the raw curve closed yesterday.
+        let next = match ma(i) {
+            Some(avg) if w[0].1 < avg => spec.reduced_exposure,
+            _ => 1.0,
+        };
+        let cost = (next - exposure).abs() * nav * spec.switch_cost_bps / 10_000.0;
+        if next != exposure {
+            switches += 1;
+        }
+        if next < 1.0 {
+            reduced_days += 1;
+        }
+        exposure = next;
+        costs += cost;
+        let prev = nav;
+        nav = (nav * (1.0 + exposure * ret) - cost).max(0.0);
+        raw_returns.push(ret);
+        filtered_returns.push(if prev > 0.0 { nav / prev - 1.0 } else { 0.0 });
+        out.push(EquityFilterPoint {
+            date: w[1].0.clone(),
+            raw_nav: round2(w[1].1),
+            raw_ma: ma(i + 1).map(round2),
+            filtered_nav: round2(nav),
+            exposure: round4(exposure),
+        });
+    }
+
+    let years = years(&days, trading_days);
+    Ok(EquityFilterResult {
+        ma_period: spec.ma_period,
+        reduced_exposure: spec.reduced_exposure,
+        raw: stats(&raw_returns, years, trading_days),
+        filtered: stats(&filtered_returns, years, trading_days),
+        reduced_days_pct: if raw_returns.is_empty() { 0.0 } else {
+            round2(reduced_days as f64 / raw_returns.len() as f64 * 100.0)
+        },
+        switches,
+        switch_costs: round2(costs),
+        curve: out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(ma_period: usize, reduced_exposure: f64) -> EquityFilterSpec {
+        EquityFilterSpec { ma_period, reduced_exposure, switch_cost_bps: 0.0 }
+    }
+
+    /// Daily NAVs rising 1% a day for `up` days, then falling 1% a day.
+    fn curve(up: usize, down: usize) -> Vec<EquityPoint> {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut nav = 100_000.0;
+        (0..up + down).map(|d| {
+            if d > 0 {
+                nav *= if d < up { 1.01 } else { 0.99 };
+            }
+            EquityPoint { date: (start + chrono::Duration::days(d as i64)).format("%Y-%m-%d").to_string(), nav }
+        }).collect()
+    }
+
+    #[test]
+    fn test_stops_trading_below_equity_average() {
+        let r = overlay(&curve(40, 40), &spec(10, 0.0), 252.0, 1.0).unwrap();
+        assert_eq!(r.curve.len(), 80);
+        // The peak is day 39; day 42 is the first close under the 10-day
+        // average, so full size through day 42 and flat after.
+        assert!(r.curve[..43].iter().all(|p| p.exposure == 1.0));
+        assert!(r.curve[43..].iter().all(|p| p.exposure == 0.0));
+        assert_eq!(r.switches, 1);
+        let kept = r.curve.last().unwrap().filtered_nav;
+        assert_eq!(kept, r.curve[42].filtered_nav);
+        assert!(r.filtered.max_drawdown < 4.0 && r.raw.max_drawdown > 30.0);
+        assert!(r.filtered.total_return_pct > r.raw.total_return_pct);
+        assert!(r.curve[..9].iter().all(|p| p.raw_ma.is_none()) && r.curve[9].raw_ma.is_some());
+    }
+
+    #[test]
+    fn test_half_size_and_switch_costs() {
+        let half = overlay(&curve(40, 40), &spec(10, 0.5), 252.0, 1.0).unwrap();
+        assert!(half.curve[43..].iter().all(|p| p.exposure == 0.5));
+        let flat = overlay(&curve(40, 40), &spec(10, 0.0), 252.0, 1.0).unwrap();
+        assert!(half.filtered.total_return_pct < flat.filtered.total_return_pct);
+        assert!(half.filtered.total_return_pct > half.raw.total_return_pct);
+
+        let costly = overlay(&curve(40, 40), &EquityFilterSpec { switch_cost_bps: 10.0, ..spec(10, 0.0) }, 252.0, 1.0).unwrap();
+        assert!(costly.switch_costs > 0.0);
+        assert!(overlay(&curve(5, 5), &spec(1, 0.0), 252.0, 1.0).is_err());
+        assert!(overlay(&curve(5, 5), &spec(5, 1.5), 252.0, 1.0).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! Event calendar: earnings, results, expiries and macro dates.
+//!
+//! `events: [{"date": "2024-07-18", "kind": "earnings", "symbol": "INFY"},
+//! {"date": "2024-06-07", "kind": "rbi_policy"}]` lists dated events; an
+//! event without a symbol (or with "*") applies to every symbol. Requests
+//! may give `events_file: {"path", "format"}` instead (CSV with date, kind,
+//! symbol and name columns, or a JSON array); it is loaded before dispatch
+//! like `candles_file`.
+//!
+//! `event_blackout: {"days_before": 2, "days_after": 1, "kinds": [...]}`
+//! blocks new entries within that many calendar days of a matching event;
+//! empty `kinds` matches all.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::parse_timestamp;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub date: String,
+    #[serde(alias = "type", alias = "event")]
+    pub kind: String,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventWindow {
+    #[serde(default = "default_days")]
+    pub days_before: i64,
+    #[serde(default = "default_days")]
+    pub days_after: i64,
+    #[serde(default)]
+    pub kinds: Vec<String>,
+}
+
+fn default_days() -> i64 { 1 }
+
+/// An event near a trade or signal; `days` is the trade date minus the
+/// event date (negative = before the event).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventProximity {
+    pub kind: String,
+    pub date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub days: i64,
+}
+
+/// Events with parsed dates, sorted by date.
+#[derive(Debug, Clone, Default)]
+pub struct EventCalendar {
+    events: Vec<(NaiveDate, Event)>,
+}
+
+impl EventCalendar {
+    pub fn new(events: &[Event]) -> Result<Self, String> {
+        let mut parsed = events.iter()
+            .map(|e| parse_timestamp(&e.date)
+                .map(|d| (d.date(), e.clone()))
+                .ok_or_else(|| format!("Invalid event date '{}'", e.date)))
+            .collect::<Result<Vec<_>, _>>()?;
+        parsed.sort_by_key(|(d, _)| *d);
+        Ok(Self { events: parsed })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    fn applying_to<'a>(&'a self, symbol: &'a str) -> impl Iterator<Item = &'a (NaiveDate, Event)> + 'a {
+        self.events.iter()
+            .filter(move |(_, e)| e.symbol.as_deref().is_none_or(|s| s == "*" || s.eq_ignore_ascii_case(symbol)))
+    }
+
+    /// First event whose blackout window covers `date`.
+    pub fn blackout(&self, symbol: &str, date: NaiveDate, window: &EventWindow) -> Option<EventProximity> {
+        self.applying_to(symbol)
+            .filter(|(_, e)| window.kinds.is_empty() || window.kinds.iter().any(|k| k.eq_ignore_ascii_case(&e.kind)))
+            .find(|(d, _)| {
+                let days = (date - *d).num_days();
+                -window.days_before <= days && days <= window.days_after
+            })
+            .map(|(d, e)| proximity(*d, e, date))
+    }
+
+    /// Nearest event within `max_days` of `date`, preferring the upcoming
+    /// one on ties.
+    pub fn nearest(&self, symbol: &str, date: NaiveDate, max_days: i64) -> Option<EventProximity> {
+        self.applying_to(symbol)
+            .filter(|(d, _)| (date - *d).num_days().abs() <= max_days)
+            .min_by_key(|(d, _)| {
+                let days = (date - *d).num_days();
+                (days.abs(), days > 0)
+            })
+            .map(|(d, e)| proximity(*d, e, date))
+    }
+}
+
+fn proximity(event_date: NaiveDate, e: &Event, date: NaiveDate) -> EventProximity {
+    EventProximity { kind: e.kind.clone(), date: event_date.to_string(), name: e.name.clone(), days: (date - event_date).num_days() }
+}
+
+#[derive(Deserialize)]
+struct EventsFileSpec {
+    path: String,
+    /// "csv" or "json"; inferred from the extension when omitted.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// Replace a top-level `events_file` with the `events` it holds, appended
+/// to any inline events.
+pub fn resolve(data: &mut Value) -> Result<(), String> {
+    let Some(map) = data.as_object_mut() else { return Ok(()) };
+    let Some(spec) = map.remove("events_file") else { return Ok(()) };
+    let spec: EventsFileSpec = serde_json::from_value(spec).map_err(|e| format!("Invalid events_file spec: {}", e))?;
+    let mut loaded = load(&spec)?;
+    if let Some(Value::Array(inline)) = map.remove("events") {
+        loaded.splice(0..0, inline);
+    }
+    map.insert("events".to_string(), Value::Array(loaded));
+    Ok(())
+}
+
+fn load(spec: &EventsFileSpec) -> Result<Vec<Value>, String> {
+    let format = spec.format.clone().map(|f| f.to_lowercase()).unwrap_or_else(|| {
+        std::path::Path::new(&spec.path).extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_else(|| "csv".to_string())
+    });
+    match format.as_str() {
+        "csv" | "txt" => {
+            let mut reader = csv::ReaderBuilder::new()
+                .flexible(true)
+                .trim(csv::Trim::All)
+                .from_path(&spec.path)
+                .map_err(|e| format!("Failed to open {}: {}", spec.path, e))?;
+            let header: Vec<String> = reader.headers()
+                .map_err(|e| format!("CSV error in {}: {}", spec.path, e))?
+                .iter().map(|h| h.to_lowercase()).collect();
+            let mut rows = Vec::new();
+            for record in reader.records() {
+                let record = record.map_err(|e| format!("CSV error in {}: {}", spec.path, e))?;
+                let row: serde_json::Map<String, Value> = header.iter().zip(record.iter())
+                    .filter(|(_, v)| !v.is_empty())
+                    .map(|(h, v)| (h.clone(), Value::from(v)))
+                    .collect();
+                rows.push(Value::Object(row));
+            }
+            Ok(rows)
+        }
+        "json" => {
+            let text = std::fs::read_to_string(&spec.path).map_err(|e| format!("Failed to open {}: {}", spec.path, e))?;
+            match serde_json::from_str(&text).map_err(|e| format!("JSON error in {}: {}", spec.path, e))? {
+                Value::Array(items) => Ok(items),
+                Value::Object(mut o) => match o.remove("events") {
+                    Some(Value::Array(items)) => Ok(items),
+                    _ => Err(format!("{}: expected an array of events", spec.path)),
+                },
+                _ => Err(format!("{}: expected an array of events", spec.path)),
+            }
+        }
+        other => Err(format!("Unsupported events_file format: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_blackout_and_nearest() {
+        let events: Vec<Event> = serde_json::from_value(json!([
+            { "date": "2024-07-18", "kind": "earnings", "symbol": "INFY" },
+            { "date": "2024-06-07", "type": "rbi_policy" },
+        ])).unwrap();
+        let cal = EventCalendar::new(&events).unwrap();
+        let window = EventWindow { days_before: 2, days_after: 1, kinds: vec![] };
+        assert_eq!(cal.blackout("INFY", date("2024-07-16"), &window).unwrap().days, -2);
+        assert!(cal.blackout("INFY", date("2024-07-20"), &window).is_none());
+        assert!(cal.blackout("TCS", date("2024-07-18"), &window).is_none());
+        // Market-wide events apply to every symbol.
+        assert_eq!(cal.blackout("TCS", date("2024-06-08"), &window).unwrap().kind, "rbi_policy");
+        let earnings_only = EventWindow { kinds: vec!["EARNINGS".into()], ..window };
+        assert!(cal.blackout("TCS", date("2024-06-08"), &earnings_only).is_none());
+
+        let near = cal.nearest("INFY", date("2024-07-21"), 5).unwrap();
+        assert_eq!((near.kind.as_str(), near.days), ("earnings", 3));
+        assert!(cal.nearest("INFY", date("2024-08-21"), 5).is_none());
+        assert!(EventCalendar::new(&[Event { date: "soon".into(), kind: "x".into(), symbol: None, name: None }]).is_err());
+    }
+
+    #[test]
+    fn test_events_file_csv() {
+        let path = std::env::temp_dir().join(format!("events_{}.csv", std::process::id()));
+        std::fs::write(&path, "Date,Kind,Symbol,Name\n2024-07-18,earnings,INFY,Q1 results\n2024-06-07,rbi_policy,,\n").unwrap();
+        let mut data = json!({
+            "events": [{ "date": "2024-07-25", "kind": "expiry" }],
+            "events_file": { "path": path.to_str().unwrap() },
+        });
+        resolve(&mut data).unwrap();
+        std::fs::remove_file(&path).ok();
+        let events: Vec<Event> = serde_json::from_value(data["events"].clone()).unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].kind, "expiry");
+        assert_eq!(events[1].name.as_deref(), Some("Q1 results"));
+        assert!(events[2].symbol.is_none());
+        assert!(data.get("events_file").is_none());
+    }
+}
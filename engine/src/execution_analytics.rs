@@ -1,11 +1,16 @@
 //! Latency monitoring and execution quality analytics.
 //! Records execution events, computes slippage/latency/market impact,
 //! and aggregates stats by symbol, broker, and time-of-day bucket.
+//! The stateless "benchmark" command scores completed orders against the
+//! market data around them: arrival price, interval VWAP/TWAP and
+//! implementation shortfall.
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Mutex;
+use chrono::NaiveDateTime;
+use crate::utils::{parse_timestamp, Candle};
 
 static ANALYTICS_STORE: once_cell::sync::Lazy<Mutex<ExecutionAnalyticsStore>> =
     once_cell::sync::Lazy::new(|| Mutex::new(ExecutionAnalyticsStore::new()));
@@ -277,7 +282,268 @@ fn round4(x: f64) -> f64 {
     (x * 10_000.0).round() / 10_000.0
 }
 
-/// JSON API entry point. Commands: "record", "stats", "by_symbol", "by_broker", "by_time_bucket", "reset", "record_order_sent"
+#[derive(Deserialize)]
+struct BenchmarkInput {
+    orders: Vec<OrderIn>,
+    #[serde(default)]
+    candles: Vec<Candle>,
+    #[serde(default)]
+    candles_by_symbol: HashMap<String, Vec<Candle>>,
+    #[serde(default)]
+    ticks: Vec<TickIn>,
+}
+
+#[derive(Deserialize)]
+struct OrderIn {
+    #[serde(default)]
+    order_id: Option<String>,
+    #[serde(default)]
+    symbol: String,
+    side: String,
+    /// Intended quantity; unfilled remainder is charged as opportunity cost.
+    qty: f64,
+    #[serde(alias = "order_time")]
+    arrival_time: String,
+    /// Price when the signal fired, if earlier than arrival.
+    #[serde(default)]
+    decision_price: Option<f64>,
+    /// Window end; defaults to the last fill (or arrival when unfilled).
+    #[serde(default)]
+    end_time: Option<String>,
+    #[serde(default)]
+    fills: Vec<FillIn>,
+    #[serde(default)]
+    fees: f64,
+}
+
+#[derive(Deserialize)]
+struct FillIn {
+    #[serde(alias = "timestamp")]
+    time: String,
+    price: f64,
+    qty: f64,
+}
+
+#[derive(Deserialize)]
+struct TickIn {
+    #[serde(alias = "time")]
+    timestamp: String,
+    price: f64,
+    #[serde(default)]
+    volume: f64,
+}
+
+/// A bar or tick on a common footing; ticks have open = close = typical.
+struct Obs {
+    t: NaiveDateTime,
+    open: f64,
+    close: f64,
+    typical: f64,
+    volume: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Shortfall {
+    /// Decision price to arrival price, on the filled quantity.
+    delay_cost: f64,
+    /// Arrival price to fills.
+    trading_cost: f64,
+    /// Decision price to window end, on the unfilled quantity.
+    opportunity_cost: f64,
+    fees: f64,
+    total: f64,
+    /// Total over paper notional (qty × decision price).
+    bps: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OrderBenchmark {
+    order_id: String,
+    symbol: String,
+    side: String,
+    qty: f64,
+    filled_qty: f64,
+    fill_rate: f64,
+    avg_fill_price: Option<f64>,
+    decision_price: f64,
+    arrival_price: f64,
+    interval_vwap: f64,
+    interval_twap: f64,
+    end_price: f64,
+    /// Positive = paid more (buy) or received less (sell) than the benchmark.
+    slippage_vs_arrival_bps: Option<f64>,
+    slippage_vs_vwap_bps: Option<f64>,
+    slippage_vs_twap_bps: Option<f64>,
+    shortfall: Shortfall,
+    duration_secs: i64,
+    observations: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BenchmarkSummary {
+    orders: usize,
+    filled_notional: f64,
+    /// Filled-notional weighted.
+    avg_slippage_vs_arrival_bps: f64,
+    avg_slippage_vs_vwap_bps: f64,
+    total_shortfall: f64,
+    shortfall_bps: f64,
+    worst_order: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SkippedOrder {
+    order_id: String,
+    reason: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BenchmarkResult {
+    orders: Vec<OrderBenchmark>,
+    summary: BenchmarkSummary,
+    skipped: Vec<SkippedOrder>,
+}
+
+fn benchmark(data: Value) -> Result<Value, String> {
+    let input: BenchmarkInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid benchmark input: {}", e))?;
+    if input.orders.is_empty() {
+        return Err("At least one order required".to_string());
+    }
+    let candle_obs = |candles: &[Candle]| -> Vec<Obs> {
+        let mut obs: Vec<Obs> = candles.iter().filter_map(|c| Some(Obs {
+            t: parse_timestamp(&c.timestamp)?,
+            open: c.open,
+            close: c.close,
+            typical: (c.high + c.low + c.close) / 3.0,
+            volume: c.volume,
+        })).collect();
+        obs.sort_by_key(|o| o.t);
+        obs
+    };
+    let mut tick_obs: Vec<Obs> = input.ticks.iter().filter_map(|k| Some(Obs {
+        t: parse_timestamp(&k.timestamp)?,
+        open: k.price,
+        close: k.price,
+        typical: k.price,
+        volume: k.volume,
+    })).collect();
+    tick_obs.sort_by_key(|o| o.t);
+    let default_obs = candle_obs(&input.candles);
+    let by_symbol: HashMap<&str, Vec<Obs>> = input.candles_by_symbol.iter()
+        .map(|(k, v)| (k.as_str(), candle_obs(v)))
+        .collect();
+
+    let mut orders = Vec::new();
+    let mut skipped = Vec::new();
+    for (i, order) in input.orders.iter().enumerate() {
+        let id = order.order_id.clone().unwrap_or_else(|| format!("order_{}", i));
+        let obs = if !tick_obs.is_empty() {
+            &tick_obs
+        } else {
+            by_symbol.get(order.symbol.as_str()).unwrap_or(&default_obs)
+        };
+        match benchmark_order(order, id.clone(), obs) {
+            Ok(b) => orders.push(b),
+            Err(reason) => skipped.push(SkippedOrder { order_id: id, reason }),
+        }
+    }
+
+    let filled_notional: f64 = orders.iter().map(|o| o.avg_fill_price.unwrap_or(0.0) * o.filled_qty).sum();
+    let weighted = |f: fn(&OrderBenchmark) -> Option<f64>| {
+        if filled_notional <= 0.0 { return 0.0; }
+        round2(orders.iter().map(|o| f(o).unwrap_or(0.0) * o.avg_fill_price.unwrap_or(0.0) * o.filled_qty).sum::<f64>() / filled_notional)
+    };
+    let paper: f64 = orders.iter().map(|o| o.qty * o.decision_price).sum();
+    let total_shortfall: f64 = orders.iter().map(|o| o.shortfall.total).sum();
+    let summary = BenchmarkSummary {
+        orders: orders.len(),
+        filled_notional: round2(filled_notional),
+        avg_slippage_vs_arrival_bps: weighted(|o| o.slippage_vs_arrival_bps),
+        avg_slippage_vs_vwap_bps: weighted(|o| o.slippage_vs_vwap_bps),
+        total_shortfall: round2(total_shortfall),
+        shortfall_bps: if paper > 0.0 { round2(total_shortfall / paper * 10_000.0) } else { 0.0 },
+        worst_order: orders.iter().max_by(|a, b| a.shortfall.bps.total_cmp(&b.shortfall.bps)).map(|o| o.order_id.clone()),
+    };
+    serde_json::to_value(BenchmarkResult { orders, summary, skipped })
+        .map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn benchmark_order(order: &OrderIn, order_id: String, obs: &[Obs]) -> Result<OrderBenchmark, String> {
+    let sign = match order.side.to_lowercase().as_str() {
+        "buy" | "long" => 1.0,
+        "sell" | "short" => -1.0,
+        other => return Err(format!("unknown side '{}'", other)),
+    };
+    if order.qty <= 0.0 {
+        return Err("qty must be positive".to_string());
+    }
+    if obs.is_empty() {
+        return Err(format!("no market data for {}", order.symbol));
+    }
+    let arrival = parse_timestamp(&order.arrival_time)
+        .ok_or_else(|| format!("bad arrival_time '{}'", order.arrival_time))?;
+    let mut fills = Vec::with_capacity(order.fills.len());
+    for f in &order.fills {
+        let t = parse_timestamp(&f.time).ok_or_else(|| format!("bad fill time '{}'", f.time))?;
+        fills.push((t, f.price, f.qty.abs()));
+    }
+    let end = match &order.end_time {
+        Some(e) => parse_timestamp(e).ok_or_else(|| format!("bad end_time '{}'", e))?,
+        None => fills.iter().map(|f| f.0).max().unwrap_or(arrival).max(arrival),
+    };
+
+    // Bar (or last tick) in force at a moment; before the data starts, the first one.
+    let at = |t: NaiveDateTime| obs.partition_point(|o| o.t <= t).saturating_sub(1);
+    let (first, last) = (at(arrival), at(end));
+    let window = &obs[first..=last.max(first)];
+    let arrival_price = obs[first].open;
+    let decision_price = order.decision_price.unwrap_or(arrival_price);
+    let end_price = window[window.len() - 1].close;
+    let twap = window.iter().map(|o| o.typical).sum::<f64>() / window.len() as f64;
+    let vol: f64 = window.iter().map(|o| o.volume).sum();
+    let vwap = if vol > 0.0 { window.iter().map(|o| o.typical * o.volume).sum::<f64>() / vol } else { twap };
+
+    let filled_qty: f64 = fills.iter().map(|f| f.2).sum::<f64>().min(order.qty);
+    let avg_fill = (filled_qty > 0.0).then(|| fills.iter().map(|f| f.1 * f.2).sum::<f64>() / fills.iter().map(|f| f.2).sum::<f64>());
+    let bps = |bench: f64| avg_fill.filter(|_| bench > 0.0).map(|p| round2(sign * (p - bench) / bench * 10_000.0));
+
+    let delay_cost = sign * (arrival_price - decision_price) * filled_qty;
+    let trading_cost = avg_fill.map(|p| sign * (p - arrival_price) * filled_qty).unwrap_or(0.0);
+    let opportunity_cost = sign * (end_price - decision_price) * (order.qty - filled_qty);
+    let total = delay_cost + trading_cost + opportunity_cost + order.fees;
+    let paper = order.qty * decision_price;
+
+    Ok(OrderBenchmark {
+        order_id,
+        symbol: order.symbol.clone(),
+        side: order.side.to_lowercase(),
+        qty: order.qty,
+        filled_qty,
+        fill_rate: round4(filled_qty / order.qty),
+        avg_fill_price: avg_fill.map(round4),
+        decision_price: round4(decision_price),
+        arrival_price: round4(arrival_price),
+        interval_vwap: round4(vwap),
+        interval_twap: round4(twap),
+        end_price: round4(end_price),
+        slippage_vs_arrival_bps: bps(arrival_price),
+        slippage_vs_vwap_bps: bps(vwap),
+        slippage_vs_twap_bps: bps(twap),
+        shortfall: Shortfall {
+            delay_cost: round2(delay_cost),
+            trading_cost: round2(trading_cost),
+            opportunity_cost: round2(opportunity_cost),
+            fees: round2(order.fees),
+            total: round2(total),
+            bps: if paper > 0.0 { round2(total / paper * 10_000.0) } else { 0.0 },
+        },
+        duration_secs: (end - arrival).num_seconds(),
+        observations: window.len(),
+    })
+}
+
+/// JSON API entry point. Commands: "record", "stats", "by_symbol", "by_broker", "by_time_bucket", "reset", "record_order_sent", "benchmark"
 pub fn compute(data: Value) -> Result<Value, String> {
     let command = data
         .get("command")
@@ -321,6 +587,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
             let buckets = store.by_time_bucket();
             serde_json::to_value(buckets).map_err(|e| format!("Serialization error: {}", e))
         }
+        "benchmark" => benchmark(data),
         "reset" => {
             let mut store = ANALYTICS_STORE.lock().map_err(|_| "Lock poisoned".to_string())?;
             store.reset();
@@ -455,6 +722,71 @@ mod tests {
         assert!(stats.fill_rate > 0.0 && stats.fill_rate <= 1.0, "fill rate should be between 0 and 1");
     }
 
+    fn minute_bars() -> Vec<Value> {
+        (0..10).map(|i| {
+            let p = 100.0 + i as f64;
+            json!({ "timestamp": format!("2024-01-15 09:{}:00", 15 + i), "open": p, "high": p + 0.5, "low": p - 0.5, "close": p + 0.5, "volume": if i < 5 { 100.0 } else { 300.0 } })
+        }).collect()
+    }
+
+    #[test]
+    fn test_benchmark_buy_vs_arrival_and_vwap() {
+        let result = compute(json!({
+            "command": "benchmark",
+            "candles": minute_bars(),
+            "orders": [{
+                "order_id": "A1", "symbol": "X", "side": "BUY", "qty": 100,
+                "arrival_time": "2024-01-15 09:15:00", "decision_price": 99.0, "fees": 20.0,
+                "fills": [
+                    { "time": "2024-01-15 09:16:10", "price": 101.5, "qty": 40 },
+                    { "time": "2024-01-15 09:19:30", "price": 104.5, "qty": 40 }
+                ]
+            }]
+        })).unwrap();
+        let b: BenchmarkResult = serde_json::from_value(result).unwrap();
+        let o = &b.orders[0];
+        assert_eq!(o.arrival_price, 100.0);
+        assert_eq!(o.avg_fill_price, Some(103.0));
+        assert_eq!(o.observations, 5);
+        // Typical prices 100.17..104.17, equal volume: VWAP = TWAP = 102.1667.
+        assert_eq!(o.interval_vwap, o.interval_twap);
+        assert_eq!(o.slippage_vs_arrival_bps, Some(300.0));
+        assert!(o.slippage_vs_vwap_bps.unwrap() > 0.0);
+        // 80 × (100 − 99) + 80 × (103 − 100) + 20 × (104.5 − 99) + 20 fees.
+        assert_eq!(o.shortfall.delay_cost, 80.0);
+        assert_eq!(o.shortfall.trading_cost, 240.0);
+        assert_eq!(o.shortfall.opportunity_cost, 110.0);
+        assert_eq!(o.shortfall.total, 450.0);
+        assert_eq!(o.shortfall.bps, round2(450.0 / 9900.0 * 10_000.0));
+        assert_eq!(o.duration_secs, 270);
+    }
+
+    #[test]
+    fn test_benchmark_sell_ticks_and_skips() {
+        let result = compute(json!({
+            "command": "benchmark",
+            "ticks": [
+                { "timestamp": "2024-01-15 10:00:00", "price": 200.0, "volume": 10 },
+                { "timestamp": "2024-01-15 10:00:05", "price": 199.0, "volume": 30 },
+                { "timestamp": "2024-01-15 10:00:09", "price": 198.0, "volume": 10 }
+            ],
+            "orders": [
+                { "order_id": "S1", "side": "sell", "qty": 10, "arrival_time": "2024-01-15 10:00:01",
+                  "fills": [{ "time": "2024-01-15 10:00:09", "price": 198.0, "qty": 10 }] },
+                { "order_id": "bad", "side": "hold", "qty": 1, "arrival_time": "2024-01-15 10:00:01" }
+            ]
+        })).unwrap();
+        let b: BenchmarkResult = serde_json::from_value(result).unwrap();
+        let o = &b.orders[0];
+        assert_eq!(o.arrival_price, 200.0);
+        assert_eq!(o.interval_vwap, 199.0);
+        // Sold 2 below arrival: a cost of 100 bps.
+        assert_eq!(o.slippage_vs_arrival_bps, Some(100.0));
+        assert_eq!(o.shortfall.total, 20.0);
+        assert_eq!(b.skipped.len(), 1);
+        assert_eq!(b.summary.worst_order.as_deref(), Some("S1"));
+    }
+
     #[test]
     fn test_by_time_bucket() {
         compute(json!({ "command": "reset" })).unwrap();
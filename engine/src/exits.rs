@@ -0,0 +1,284 @@
+//! Reusable trailing exits shared by backtest and scan.
+//!
+//! `exits: {"chandelier": {"period": 22, "atr_mult": 3}, "swing_trail":
+//! {"lookback": 5}, "break_even_r": 1}` layers trailing stops on top of
+//! whatever stop a strategy sets at entry:
+//!
+//! - chandelier: highest high of the last `period` bars minus `atr_mult`
+//!   × ATR (lowest low plus, for shorts);
+//! - swing trail: lowest low of the last `lookback` bars (highest high for
+//!   shorts);
+//! - break-even: once price has moved `break_even_r` × the initial risk in
+//!   favour, the stop moves to entry.
+//!
+//! Stops are recomputed on each bar's close and only ever tighten.
+//!
+//! `targets: [{"at_r": 1, "pct": 50}]` scales out: when price reaches entry
+//! ± `at_r` × initial risk, `pct`% of the original quantity is closed there
+//! and the rest keeps trailing.
+
+use serde::{Deserialize, Serialize};
+use crate::utils::{Candle, round2};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExitSpec {
+    #[serde(default)]
+    pub chandelier: Option<ChandelierSpec>,
+    #[serde(default)]
+    pub swing_trail: Option<SwingTrailSpec>,
+    /// Favourable move, in multiples of the initial risk, that moves the
+    /// stop to entry. Needs an initial stop.
+    #[serde(default)]
+    pub break_even_r: Option<f64>,
+    /// Partial-profit targets, in any order. Need an initial stop.
+    #[serde(default)]
+    pub targets: Vec<ScaleOutTarget>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScaleOutTarget {
+    pub at_r: f64,
+    /// Share of the original quantity closed at the target.
+    pub pct: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChandelierSpec {
+    #[serde(default = "default_chandelier_period")]
+    pub period: usize,
+    #[serde(default = "default_atr_mult")]
+    pub atr_mult: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SwingTrailSpec {
+    #[serde(default = "default_swing_lookback")]
+    pub lookback: usize,
+}
+
+fn default_chandelier_period() -> usize { 22 }
+fn default_atr_mult() -> f64 { 3.0 }
+fn default_swing_lookback() -> usize { 5 }
+
+impl ExitSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(c) = &self.chandelier {
+            if c.period == 0 || !(c.atr_mult.is_finite() && c.atr_mult > 0.0) {
+                return Err("exits.chandelier needs period ≥ 1 and a positive atr_mult".to_string());
+            }
+        }
+        if self.swing_trail.as_ref().is_some_and(|s| s.lookback == 0) {
+            return Err("exits.swing_trail.lookback must be at least 1".to_string());
+        }
+        if self.break_even_r.is_some_and(|r| !(r.is_finite() && r > 0.0)) {
+            return Err("exits.break_even_r must be positive".to_string());
+        }
+        if self.targets.iter().any(|t| !(t.at_r.is_finite() && t.at_r > 0.0 && t.pct > 0.0 && t.pct <= 100.0)) {
+            return Err("exits.targets need a positive at_r and pct in (0, 100]".to_string());
+        }
+        if self.targets.iter().map(|t| t.pct).sum::<f64>() > 100.0 + 1e-9 {
+            return Err("exits.targets pct must not add up to more than 100".to_string());
+        }
+        Ok(())
+    }
+
+    /// Chandelier stop as of bar `i`'s close.
+    pub fn chandelier_stop(&self, candles: &[Candle], i: usize, atr: f64, is_short: bool) -> Option<f64> {
+        let c = self.chandelier.as_ref()?;
+        if !(atr.is_finite() && atr > 0.0) {
+            return None;
+        }
+        let window = &candles[(i + 1).saturating_sub(c.period)..=i];
+        Some(if is_short {
+            window.iter().map(|b| b.low).fold(f64::INFINITY, f64::min) + c.atr_mult * atr
+        } else {
+            window.iter().map(|b| b.high).fold(f64::NEG_INFINITY, f64::max) - c.atr_mult * atr
+        })
+    }
+
+    /// Swing stop as of bar `i`'s close.
+    pub fn swing_stop(&self, candles: &[Candle], i: usize, is_short: bool) -> Option<f64> {
+        let s = self.swing_trail.as_ref()?;
+        let window = &candles[(i + 1).saturating_sub(s.lookback)..=i];
+        Some(if is_short {
+            window.iter().map(|b| b.high).fold(f64::NEG_INFINITY, f64::max)
+        } else {
+            window.iter().map(|b| b.low).fold(f64::INFINITY, f64::min)
+        })
+    }
+
+    /// Levels for a fresh signal at bar `i`, for reporting.
+    pub fn levels(&self, candles: &[Candle], i: usize, atr: f64, entry: f64, stop: Option<f64>, is_short: bool) -> ExitLevels {
+        let chandelier = self.chandelier_stop(candles, i, atr, is_short);
+        let swing = self.swing_stop(candles, i, is_short);
+        let risk = stop.map(|s| (entry - s).abs()).filter(|r| *r > 0.0);
+        let break_even_at = self.break_even_r.zip(risk)
+            .map(|(r, risk)| if is_short { entry - r * risk } else { entry + r * risk });
+        ExitLevels {
+            chandelier: chandelier.map(round2),
+            swing: swing.map(round2),
+            trail_stop: tighter(chandelier, swing, is_short).map(round2),
+            break_even_at: break_even_at.map(round2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExitLevels {
+    pub chandelier: Option<f64>,
+    pub swing: Option<f64>,
+    /// The tighter of the enabled trailing stops.
+    pub trail_stop: Option<f64>,
+    /// Price at which the stop moves to entry.
+    pub break_even_at: Option<f64>,
+}
+
+/// Which rule last moved the stop.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopSource {
+    Initial,
+    Chandelier,
+    Swing,
+    BreakEven,
+}
+
+/// Trailing-stop state of one open position.
+#[derive(Debug, Clone)]
+pub struct ExitState {
+    pub entry: f64,
+    pub is_short: bool,
+    pub initial_stop: Option<f64>,
+    pub stop: Option<f64>,
+    pub source: StopSource,
+    /// Best price reached since entry (high for longs, low for shorts).
+    favourable: f64,
+    /// Indices into `ExitSpec::targets` already filled.
+    targets_hit: Vec<usize>,
+}
+
+impl ExitState {
+    pub fn new(entry: f64, is_short: bool, stop: Option<f64>) -> Self {
+        Self { entry, is_short, initial_stop: stop, stop, source: StopSource::Initial, favourable: entry, targets_hit: Vec::new() }
+    }
+
+    /// Tighten the stop after bar `i` closes and return it.
+    pub fn update(&mut self, spec: &ExitSpec, candles: &[Candle], i: usize, atr: f64) -> Option<f64> {
+        let bar = &candles[i];
+        self.favourable = if self.is_short { self.favourable.min(bar.low) } else { self.favourable.max(bar.high) };
+
+        let mut candidates = vec![
+            (spec.chandelier_stop(candles, i, atr, self.is_short), StopSource::Chandelier),
+            (spec.swing_stop(candles, i, self.is_short), StopSource::Swing),
+        ];
+        if let (Some(r), Some(initial)) = (spec.break_even_r, self.initial_stop) {
+            let risk = (self.entry - initial).abs();
+            let moved = if self.is_short { self.entry - self.favourable } else { self.favourable - self.entry };
+            if risk > 0.0 && moved >= r * risk {
+                candidates.push((Some(self.entry), StopSource::BreakEven));
+            }
+        }
+        for (level, source) in candidates {
+            let Some(level) = level else { continue };
+            // A stop on the wrong side of the close would fill immediately.
+            let valid = if self.is_short { level > bar.close } else { level < bar.close };
+            if valid && tighter(self.stop, Some(level), self.is_short) == Some(level) && self.stop != Some(level) {
+                self.stop = Some(level);
+                self.source = source;
+            }
+        }
+        self.stop
+    }
+
+    /// Targets `bar` reaches that have not filled yet, nearest first, as
+    /// (price, pct of the original quantity). Marks them filled.
+    pub fn scale_outs(&mut self, spec: &ExitSpec, bar: &Candle) -> Vec<(f64, f64)> {
+        let Some(risk) = self.initial_stop.map(|s| (self.entry - s).abs()).filter(|r| *r > 0.0) else {
+            return Vec::new();
+        };
+        let mut hits: Vec<(usize, f64, f64)> = spec.targets.iter().enumerate()
+            .filter(|(k, _)| !self.targets_hit.contains(k))
+            .filter_map(|(k, t)| {
+                let level = if self.is_short { self.entry - t.at_r * risk } else { self.entry + t.at_r * risk };
+                let reached = if self.is_short { bar.low <= level } else { bar.high >= level };
+                reached.then_some((k, level, t.pct))
+            })
+            .collect();
+        hits.sort_by(|a, b| if self.is_short { b.1.total_cmp(&a.1) } else { a.1.total_cmp(&b.1) });
+        self.targets_hit.extend(hits.iter().map(|h| h.0));
+        hits.into_iter().map(|(_, level, pct)| (level, pct)).collect()
+    }
+}
+
+/// The stop closer to price: higher for longs, lower for shorts.
+fn tighter(a: Option<f64>, b: Option<f64>, is_short: bool) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if is_short { a.min(b) } else { a.max(b) }),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(high: f64, low: f64, close: f64) -> Candle {
+        Candle { timestamp: String::new(), open: close, high, low, close, volume: 1000.0 }
+    }
+
+    #[test]
+    fn test_stops_ratchet_and_break_even() {
+        let spec: ExitSpec = serde_json::from_value(serde_json::json!({
+            "chandelier": { "period": 3, "atr_mult": 2 },
+            "swing_trail": { "lookback": 2 },
+            "break_even_r": 1,
+        })).unwrap();
+        spec.validate().unwrap();
+        let candles = vec![
+            bar(101.0, 99.0, 100.0),
+            bar(104.0, 100.0, 103.0),
+            bar(106.0, 102.0, 105.0),
+            bar(105.0, 103.0, 104.0),
+        ];
+        // Long from 100 with a stop at 95 (risk 5).
+        let mut state = ExitState::new(100.0, false, Some(95.0));
+        // Bar 1: chandelier 104 - 2 = 102 beats swing low 99; a 4-point move is under 1R.
+        assert_eq!(state.update(&spec, &candles, 1, 1.0), Some(102.0));
+        assert_eq!(state.source, StopSource::Chandelier);
+        // Bar 2: high 106 → moved 6 ≥ 1R; chandelier 104, swing 100, break-even 100 → 104.
+        assert_eq!(state.update(&spec, &candles, 2, 1.0), Some(104.0));
+        // Bar 3: levels fall back (chandelier 104 would be above the 104 close) → stop holds.
+        assert_eq!(state.update(&spec, &candles, 3, 1.0), Some(104.0));
+
+        let levels = spec.levels(&candles, 2, 1.0, 105.0, Some(100.0), false);
+        assert_eq!((levels.chandelier, levels.swing, levels.trail_stop), (Some(104.0), Some(100.0), Some(104.0)));
+        assert_eq!(levels.break_even_at, Some(110.0));
+        let short = spec.levels(&candles, 2, 1.0, 105.0, Some(110.0), true);
+        assert_eq!((short.chandelier, short.swing, short.trail_stop), (Some(101.0), Some(106.0), Some(101.0)));
+        assert_eq!(short.break_even_at, Some(100.0));
+
+        assert!(serde_json::from_value::<ExitSpec>(serde_json::json!({ "break_even_r": -1 })).unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_scale_out_targets_fill_once_nearest_first() {
+        let spec: ExitSpec = serde_json::from_value(serde_json::json!({
+            "targets": [{ "at_r": 2, "pct": 25 }, { "at_r": 1, "pct": 50 }],
+        })).unwrap();
+        spec.validate().unwrap();
+        let mut state = ExitState::new(100.0, false, Some(95.0));
+        assert!(state.scale_outs(&spec, &bar(104.0, 99.0, 103.0)).is_empty());
+        assert_eq!(state.scale_outs(&spec, &bar(111.0, 103.0, 110.0)), vec![(105.0, 50.0), (110.0, 25.0)]);
+        assert!(state.scale_outs(&spec, &bar(112.0, 108.0, 111.0)).is_empty());
+
+        let mut short = ExitState::new(100.0, true, Some(104.0));
+        assert_eq!(short.scale_outs(&spec, &bar(99.0, 95.0, 96.0)), vec![(96.0, 50.0)]);
+        // No initial stop, no R.
+        assert!(ExitState::new(100.0, false, None).scale_outs(&spec, &bar(200.0, 99.0, 150.0)).is_empty());
+
+        let over: ExitSpec = serde_json::from_value(serde_json::json!({
+            "targets": [{ "at_r": 1, "pct": 60 }, { "at_r": 2, "pct": 50 }],
+        })).unwrap();
+        assert!(over.validate().is_err());
+    }
+}
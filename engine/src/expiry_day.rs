@@ -0,0 +1,534 @@
+//! Expiry-day analytics for short straddle/strangle sellers.
+//!
+//! Takes a series of intraday option-chain snapshots for the expiring
+//! series and reports, per snapshot: ATM straddle premium and its theta
+//! burn (observed between snapshots and modelled from Black-Scholes), max
+//! pain, and the marked-to-market position with its greeks. From the
+//! latest snapshot it maps where chain gamma concentrates around max pain
+//! and derives the spot/premium levels that should trigger an adjustment.
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::chain_analysis::{mid_price, ChainStrike};
+use crate::greeks::solve_iv;
+use crate::strategy::parse_clock;
+use crate::utils::{bs_greeks, parse_timestamp, round2, round4};
+
+const MINUTES_PER_YEAR: f64 = 365.0 * 1440.0;
+
+#[derive(Deserialize)]
+struct ExpiryConfig {
+    snapshots: Vec<Snapshot>,
+    /// Settlement time on the snapshots' date.
+    #[serde(default = "default_expiry_time")]
+    expiry_time: String,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    /// Defaults to a short ATM straddle at the first snapshot.
+    #[serde(default)]
+    position: Option<ShortPosition>,
+    /// |position delta| per unit that calls for a re-centre.
+    #[serde(default = "default_delta_trigger")]
+    delta_trigger: f64,
+    /// Exit when the combined premium reaches this multiple of the credit.
+    #[serde(default = "default_stop_multiple")]
+    premium_stop_multiple: f64,
+    /// Strikes either side of max pain in the gamma profile.
+    #[serde(default = "default_zone_strikes")]
+    zone_strikes: usize,
+}
+
+fn default_expiry_time() -> String { "15:30".to_string() }
+fn default_rate() -> f64 { 0.065 }
+fn default_delta_trigger() -> f64 { 0.25 }
+fn default_stop_multiple() -> f64 { 1.5 }
+fn default_zone_strikes() -> usize { 3 }
+fn default_quantity() -> f64 { 1.0 }
+
+#[derive(Deserialize)]
+struct Snapshot {
+    timestamp: String,
+    spot: f64,
+    strikes: Vec<ChainStrike>,
+}
+
+/// Short call + short put. Premiums default to the first snapshot's mids.
+#[derive(Deserialize)]
+struct ShortPosition {
+    call_strike: f64,
+    put_strike: f64,
+    #[serde(default)]
+    call_premium: Option<f64>,
+    #[serde(default)]
+    put_premium: Option<f64>,
+    #[serde(default = "default_quantity")]
+    quantity: f64,
+}
+
+#[derive(Serialize)]
+struct PositionOut {
+    kind: &'static str,
+    call_strike: f64,
+    put_strike: f64,
+    call_premium: f64,
+    put_premium: f64,
+    quantity: f64,
+    credit: f64,
+    upper_breakeven: f64,
+    lower_breakeven: f64,
+}
+
+#[derive(Serialize)]
+struct SnapshotRow {
+    timestamp: String,
+    spot: f64,
+    minutes_to_expiry: f64,
+    atm_strike: f64,
+    atm_iv: Option<f64>,
+    straddle_premium: f64,
+    /// Premium lost per minute since the previous snapshot.
+    observed_burn_per_min: Option<f64>,
+    /// Black-Scholes theta of the ATM straddle per minute.
+    model_burn_per_min: f64,
+    max_pain: f64,
+    position_premium: f64,
+    pnl: f64,
+    /// Per unit; negative when short calls dominate.
+    position_delta: f64,
+    position_gamma: f64,
+    position_theta_per_min: f64,
+    triggers: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct GammaLevel {
+    spot: f64,
+    /// OI-weighted gamma of the whole chain were spot at this level.
+    chain_gamma: f64,
+    position_gamma: f64,
+    /// Position P&L for a 0.5% move from this level, gamma term only.
+    half_pct_move_pnl: f64,
+    risk: &'static str,
+}
+
+#[derive(Serialize)]
+struct GammaZone {
+    max_pain: f64,
+    lower: f64,
+    upper: f64,
+    /// ±1σ move left in the session at the ATM IV.
+    expected_move: f64,
+    spot_inside: bool,
+    levels: Vec<GammaLevel>,
+}
+
+#[derive(Serialize)]
+struct Trigger {
+    trigger: &'static str,
+    /// Spot level, or combined premium for `PREMIUM_STOP`.
+    level: f64,
+    distance_pct: f64,
+    action: &'static str,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    credit: f64,
+    final_pnl: f64,
+    worst_pnl: f64,
+    captured_pct: f64,
+    avg_burn_per_min: Option<f64>,
+    last_burn_per_min: Option<f64>,
+    first_trigger: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ExpiryResult {
+    position: PositionOut,
+    snapshots: Vec<SnapshotRow>,
+    gamma_zone: GammaZone,
+    adjustment_triggers: Vec<Trigger>,
+    summary: Summary,
+}
+
+/// One snapshot priced: the legs of the position and the ATM straddle.
+struct Priced {
+    t: f64,
+    minutes: f64,
+    atm_strike: f64,
+    atm_iv: Option<f64>,
+    call: Leg,
+    put: Leg,
+    atm_call: Leg,
+    atm_put: Leg,
+}
+
+#[derive(Clone, Copy)]
+struct Leg {
+    mid: f64,
+    iv: f64,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let mut config: ExpiryConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid expiry_day config: {}", e))?;
+    if config.snapshots.is_empty() {
+        return Err("expiry_day requires at least one chain snapshot".to_string());
+    }
+    let expiry = parse_clock(&config.expiry_time)
+        .ok_or_else(|| format!("Invalid expiry_time '{}' (expected HH:MM)", config.expiry_time))?;
+    let mut snapshots = std::mem::take(&mut config.snapshots);
+    let mut times = Vec::with_capacity(snapshots.len());
+    for snap in &mut snapshots {
+        let ts = parse_timestamp(&snap.timestamp)
+            .ok_or_else(|| format!("Invalid snapshot timestamp '{}'", snap.timestamp))?;
+        snap.strikes.retain(|s| s.strike.is_finite() && s.strike > 0.0);
+        if snap.strikes.is_empty() || !snap.spot.is_finite() || snap.spot <= 0.0 {
+            return Err(format!("Snapshot {} needs a positive spot and strikes", snap.timestamp));
+        }
+        snap.strikes.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+        times.push(ts);
+    }
+    let mut order: Vec<usize> = (0..snapshots.len()).collect();
+    order.sort_by_key(|&i| times[i]);
+
+    let r = config.risk_free_rate;
+    let first = &snapshots[order[0]];
+    let atm0 = atm_strike(first);
+    let pos = config.position.take().unwrap_or(ShortPosition {
+        call_strike: atm0,
+        put_strike: atm0,
+        call_premium: None,
+        put_premium: None,
+        quantity: 1.0,
+    });
+    let first_priced = price(first, times[order[0]].time(), expiry, r, &pos);
+    let call_premium = pos.call_premium.unwrap_or(first_priced.call.mid);
+    let put_premium = pos.put_premium.unwrap_or(first_priced.put.mid);
+    let credit = call_premium + put_premium;
+    if credit <= 0.0 {
+        return Err("Position has no premium; pass call_premium/put_premium or quotes".to_string());
+    }
+    let upper_breakeven = pos.call_strike + credit;
+    let lower_breakeven = pos.put_strike - credit;
+    let stop = credit * config.premium_stop_multiple;
+
+    let mut rows: Vec<SnapshotRow> = Vec::with_capacity(order.len());
+    let mut last: Option<(Priced, f64)> = None;
+    for &i in &order {
+        let snap = &snapshots[i];
+        let p = price(snap, times[i].time(), expiry, r, &pos);
+        let straddle = p.atm_call.mid + p.atm_put.mid;
+        let observed = rows.last().and_then(|prev| {
+            let elapsed = prev.minutes_to_expiry - p.minutes;
+            (elapsed > 0.0).then(|| round4((prev.straddle_premium - straddle) / elapsed))
+        });
+        let atm_theta = greeks(snap.spot, p.atm_strike, p.t, r, p.atm_call, true).2
+            + greeks(snap.spot, p.atm_strike, p.t, r, p.atm_put, false).2;
+        let (delta, gamma, theta) = position_greeks(snap.spot, &pos, &p, r);
+        let premium = p.call.mid + p.put.mid;
+
+        let mut triggers = Vec::new();
+        if snap.spot >= upper_breakeven { triggers.push("UPPER_BREAKEVEN"); }
+        if snap.spot <= lower_breakeven { triggers.push("LOWER_BREAKEVEN"); }
+        if delta <= -config.delta_trigger { triggers.push("CALL_DELTA"); }
+        if delta >= config.delta_trigger { triggers.push("PUT_DELTA"); }
+        if premium >= stop { triggers.push("PREMIUM_STOP"); }
+
+        rows.push(SnapshotRow {
+            timestamp: snap.timestamp.clone(),
+            spot: snap.spot,
+            minutes_to_expiry: p.minutes,
+            atm_strike: p.atm_strike,
+            atm_iv: p.atm_iv.map(round4),
+            straddle_premium: round2(straddle),
+            observed_burn_per_min: observed,
+            model_burn_per_min: round4(-atm_theta / 1440.0),
+            max_pain: max_pain(&snap.strikes),
+            position_premium: round2(premium),
+            pnl: round2((credit - premium) * pos.quantity),
+            position_delta: round4(delta),
+            position_gamma: round4(gamma),
+            position_theta_per_min: round4(-theta / 1440.0),
+            triggers,
+        });
+        last = Some((p, snap.spot));
+    }
+    let Some((latest, spot)) = last else {
+        return Err("expiry_day requires at least one chain snapshot".to_string());
+    };
+    let latest_snap = &snapshots[order[order.len() - 1]];
+
+    let gamma_zone = gamma_zone(latest_snap, &latest, &pos, r, config.zone_strikes);
+    let adjustment_triggers = triggers(spot, &latest, &pos, r, &config, upper_breakeven, lower_breakeven, stop);
+
+    let final_pnl = rows.last().map(|row| row.pnl).unwrap_or(0.0);
+    let elapsed = rows[0].minutes_to_expiry - rows[rows.len() - 1].minutes_to_expiry;
+    let summary = Summary {
+        credit: round2(credit),
+        final_pnl,
+        worst_pnl: rows.iter().map(|row| row.pnl).fold(f64::INFINITY, f64::min),
+        captured_pct: round2(final_pnl / (credit * pos.quantity) * 100.0),
+        avg_burn_per_min: (elapsed > 0.0)
+            .then(|| round4((rows[0].straddle_premium - rows[rows.len() - 1].straddle_premium) / elapsed)),
+        last_burn_per_min: rows.last().and_then(|row| row.observed_burn_per_min),
+        first_trigger: rows.iter()
+            .find_map(|row| row.triggers.first().map(|t| format!("{} at {}", t, row.timestamp))),
+    };
+
+    let result = ExpiryResult {
+        position: PositionOut {
+            kind: if pos.call_strike == pos.put_strike { "short_straddle" } else { "short_strangle" },
+            call_strike: pos.call_strike,
+            put_strike: pos.put_strike,
+            call_premium: round2(call_premium),
+            put_premium: round2(put_premium),
+            quantity: pos.quantity,
+            credit: round2(credit),
+            upper_breakeven: round2(upper_breakeven),
+            lower_breakeven: round2(lower_breakeven),
+        },
+        snapshots: rows,
+        gamma_zone,
+        adjustment_triggers,
+        summary,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn atm_strike(snap: &Snapshot) -> f64 {
+    snap.strikes.iter()
+        .min_by(|a, b| (a.strike - snap.spot).abs().total_cmp(&(b.strike - snap.spot).abs()))
+        .map(|s| s.strike)
+        .unwrap_or(snap.spot)
+}
+
+/// Settlement strike that minimises writers' payout.
+fn max_pain(strikes: &[ChainStrike]) -> f64 {
+    strikes.iter()
+        .map(|settle| {
+            let pain: f64 = strikes.iter().map(|s| {
+                s.call_oi * (settle.strike - s.strike).max(0.0) + s.put_oi * (s.strike - settle.strike).max(0.0)
+            }).sum();
+            (settle.strike, pain)
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(strike, _)| strike)
+        .unwrap_or(0.0)
+}
+
+fn price(snap: &Snapshot, time: NaiveTime, expiry: NaiveTime, r: f64, pos: &ShortPosition) -> Priced {
+    let minutes = ((expiry - time).num_seconds() as f64 / 60.0).max(0.0);
+    let t = minutes / MINUTES_PER_YEAR;
+    let atm = atm_strike(snap);
+    let leg = |strike: f64, is_call: bool| -> Leg {
+        let Some(s) = snap.strikes.iter().find(|s| (s.strike - strike).abs() < 1e-9) else {
+            return Leg { mid: 0.0, iv: 0.0 };
+        };
+        let (bid, ask, ltp, iv) = s.quote(is_call);
+        let mid = mid_price(bid, ask, ltp);
+        let iv = iv.filter(|v| *v > 0.0).unwrap_or_else(|| solve_iv(snap.spot, strike, r, t, mid, is_call));
+        Leg { mid, iv }
+    };
+    let atm_call = leg(atm, true);
+    let atm_put = leg(atm, false);
+    let ivs: Vec<f64> = [atm_call.iv, atm_put.iv].into_iter().filter(|v| *v > 0.0).collect();
+    let atm_iv = (!ivs.is_empty()).then(|| ivs.iter().sum::<f64>() / ivs.len() as f64);
+    // Deep legs near expiry often have no solvable IV; borrow the ATM one.
+    let with_fallback = |l: Leg| Leg { iv: if l.iv > 0.0 { l.iv } else { atm_iv.unwrap_or(0.0) }, ..l };
+    Priced {
+        t,
+        minutes,
+        atm_strike: atm,
+        atm_iv,
+        call: with_fallback(leg(pos.call_strike, true)),
+        put: with_fallback(leg(pos.put_strike, false)),
+        atm_call,
+        atm_put,
+    }
+}
+
+fn greeks(spot: f64, strike: f64, t: f64, r: f64, leg: Leg, is_call: bool) -> (f64, f64, f64) {
+    let (delta, gamma, theta, _, _) = bs_greeks(spot, strike, t, r, leg.iv, is_call);
+    (delta, gamma, theta)
+}
+
+/// Per-unit delta, gamma and daily theta of the short legs at `spot`.
+fn position_greeks(spot: f64, pos: &ShortPosition, p: &Priced, r: f64) -> (f64, f64, f64) {
+    let c = greeks(spot, pos.call_strike, p.t, r, p.call, true);
+    let q = greeks(spot, pos.put_strike, p.t, r, p.put, false);
+    (-(c.0 + q.0), -(c.1 + q.1), -(c.2 + q.2))
+}
+
+/// Chain gamma at each strike level around max pain; the zone is the span
+/// where it stays within 75% of its peak, padded by half a strike step —
+/// where pinning and hedging flows make the short position's P&L swing
+/// hardest.
+fn gamma_zone(snap: &Snapshot, p: &Priced, pos: &ShortPosition, r: f64, zone_strikes: usize) -> GammaZone {
+    let pain = max_pain(&snap.strikes);
+    let centre = snap.strikes.iter().position(|s| s.strike == pain).unwrap_or(0);
+    let lo = centre.saturating_sub(zone_strikes);
+    let hi = (centre + zone_strikes).min(snap.strikes.len() - 1);
+    let iv = p.atm_iv.unwrap_or(0.0);
+    // Near settlement gamma collapses onto the strikes, so floor time at a
+    // few minutes to keep the profile readable.
+    let t = p.t.max(5.0 / MINUTES_PER_YEAR);
+
+    let raw: Vec<(f64, f64, f64)> = snap.strikes[lo..=hi].iter().map(|level| {
+        let chain: f64 = snap.strikes.iter().map(|s| {
+            let g = bs_greeks(level.strike, s.strike, t, r, iv, true).1;
+            (s.call_oi + s.put_oi) * g
+        }).sum();
+        let c = bs_greeks(level.strike, pos.call_strike, t, r, if p.call.iv > 0.0 { p.call.iv } else { iv }, true).1;
+        let q = bs_greeks(level.strike, pos.put_strike, t, r, if p.put.iv > 0.0 { p.put.iv } else { iv }, false).1;
+        (level.strike, chain, -(c + q))
+    }).collect();
+    let peak = raw.iter().map(|l| l.1).fold(0.0, f64::max);
+    let levels: Vec<GammaLevel> = raw.iter().map(|&(spot, chain, gamma)| {
+        let share = if peak > 0.0 { chain / peak } else { 0.0 };
+        let mv = spot * 0.005;
+        GammaLevel {
+            spot,
+            chain_gamma: round4(chain),
+            position_gamma: round4(gamma),
+            half_pct_move_pnl: round2(0.5 * gamma * mv * mv * pos.quantity),
+            risk: if share >= 0.75 { "HIGH" } else if share >= 0.4 { "MEDIUM" } else { "LOW" },
+        }
+    }).collect();
+    // Each level stands for the half-step either side of its strike.
+    let half_step = levels.windows(2).map(|w| w[1].spot - w[0].spot).fold(f64::INFINITY, f64::min) / 2.0;
+    let half_step = if half_step.is_finite() { half_step } else { 0.0 };
+    let high: Vec<f64> = levels.iter().filter(|l| l.risk == "HIGH").map(|l| l.spot).collect();
+    let lower = high.first().copied().unwrap_or(pain) - half_step;
+    let upper = high.last().copied().unwrap_or(pain) + half_step;
+    let spot = snap.spot;
+    GammaZone {
+        max_pain: pain,
+        lower,
+        upper,
+        expected_move: round2(spot * iv * p.t.sqrt()),
+        spot_inside: spot >= lower && spot <= upper,
+        levels,
+    }
+}
+
+/// Forward-looking levels from the latest snapshot, nearest first.
+#[allow(clippy::too_many_arguments)]
+fn triggers(
+    spot: f64,
+    p: &Priced,
+    pos: &ShortPosition,
+    r: f64,
+    config: &ExpiryConfig,
+    upper_breakeven: f64,
+    lower_breakeven: f64,
+    stop: f64,
+) -> Vec<Trigger> {
+    let distance = |level: f64| round2((level - spot) / spot * 100.0);
+    let mut out = vec![
+        Trigger {
+            trigger: "UPPER_BREAKEVEN",
+            level: round2(upper_breakeven),
+            distance_pct: distance(upper_breakeven),
+            action: "Exit or roll the call leg up; losses accrue beyond this level",
+        },
+        Trigger {
+            trigger: "LOWER_BREAKEVEN",
+            level: round2(lower_breakeven),
+            distance_pct: distance(lower_breakeven),
+            action: "Exit or roll the put leg down; losses accrue below this level",
+        },
+    ];
+    // Walk spot in 0.05% steps up to 5% each way for the delta thresholds.
+    let delta_at = |s: f64| position_greeks(s, pos, p, r).0;
+    let step = spot * 0.0005;
+    let up = (1..=100).map(|k| spot + step * k as f64).find(|&s| delta_at(s) <= -config.delta_trigger);
+    let down = (1..=100).map(|k| spot - step * k as f64).find(|&s| delta_at(s) >= config.delta_trigger);
+    if let Some(level) = up {
+        out.push(Trigger {
+            trigger: "CALL_DELTA",
+            level: round2(level),
+            distance_pct: distance(level),
+            action: "Roll the put leg up towards spot to flatten delta",
+        });
+    }
+    if let Some(level) = down {
+        out.push(Trigger {
+            trigger: "PUT_DELTA",
+            level: round2(level),
+            distance_pct: distance(level),
+            action: "Roll the call leg down towards spot to flatten delta",
+        });
+    }
+    let premium = p.call.mid + p.put.mid;
+    out.push(Trigger {
+        trigger: "PREMIUM_STOP",
+        level: round2(stop),
+        distance_pct: if premium > 0.0 { round2((stop - premium) / premium * 100.0) } else { 0.0 },
+        action: "Buy back both legs",
+    });
+    out.sort_by(|a, b| a.distance_pct.abs().total_cmp(&b.distance_pct.abs()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::bs_price;
+
+    /// A 5-strike chain priced off one vol, OI heaviest at 22000.
+    fn snapshot(time: &str, spot: f64, minutes: f64) -> Value {
+        let t = minutes / MINUTES_PER_YEAR;
+        let strikes: Vec<Value> = [21900.0, 21950.0, 22000.0, 22050.0, 22100.0].iter().map(|&k| {
+            let oi = if k == 22000.0 { 900000.0 } else { 300000.0 };
+            json!({
+                "strike": k,
+                "call_ltp": bs_price(spot, k, 0.065, t, 0.15, true),
+                "put_ltp": bs_price(spot, k, 0.065, t, 0.15, false),
+                "call_oi": oi, "put_oi": oi,
+            })
+        }).collect();
+        json!({ "timestamp": format!("2025-03-27T{}:00", time), "spot": spot, "strikes": strikes })
+    }
+
+    #[test]
+    fn test_straddle_burns_and_pins_at_max_pain() {
+        let out = compute(json!({
+            "snapshots": [snapshot("13:30", 22000.0, 120.0), snapshot("09:30", 22010.0, 360.0), snapshot("15:00", 22005.0, 30.0)],
+        })).unwrap();
+        assert_eq!(out["position"]["kind"], "short_straddle");
+        assert_eq!(out["position"]["call_strike"], 22000.0);
+        let rows = out["snapshots"].as_array().unwrap();
+        assert_eq!(rows[0]["minutes_to_expiry"], 360.0);
+        assert!(rows.iter().all(|r| r["max_pain"] == 22000.0));
+        // Decay accelerates into the close.
+        let burn = |i: usize| rows[i]["observed_burn_per_min"].as_f64().unwrap();
+        assert!(rows[0]["observed_burn_per_min"].is_null());
+        assert!(burn(2) > burn(1) && burn(1) > 0.0);
+        assert!(rows[2]["model_burn_per_min"].as_f64().unwrap() > rows[0]["model_burn_per_min"].as_f64().unwrap());
+        assert!(out["summary"]["final_pnl"].as_f64().unwrap() > 0.0);
+        assert!(out["gamma_zone"]["spot_inside"].as_bool().unwrap());
+        assert_eq!(out["gamma_zone"]["levels"].as_array().unwrap().len(), 5);
+        let triggers: Vec<&str> = out["adjustment_triggers"].as_array().unwrap()
+            .iter().map(|t| t["trigger"].as_str().unwrap()).collect();
+        assert!(triggers.contains(&"CALL_DELTA") && triggers.contains(&"UPPER_BREAKEVEN"));
+    }
+
+    #[test]
+    fn test_breakout_fires_adjustments() {
+        let out = compute(json!({
+            "snapshots": [snapshot("10:00", 22000.0, 330.0), snapshot("14:00", 22160.0, 90.0)],
+            "position": { "call_strike": 22050.0, "put_strike": 21950.0, "call_premium": 40.0, "put_premium": 40.0, "quantity": 50 },
+        })).unwrap();
+        assert_eq!(out["position"]["kind"], "short_strangle");
+        assert_eq!(out["position"]["upper_breakeven"], 22130.0);
+        let fired = out["snapshots"][1]["triggers"].as_array().unwrap();
+        assert!(fired.contains(&json!("UPPER_BREAKEVEN")) && fired.contains(&json!("CALL_DELTA")));
+        assert!(out["snapshots"][1]["pnl"].as_f64().unwrap() < 0.0);
+        assert!(out["summary"]["first_trigger"].as_str().unwrap().starts_with("UPPER_BREAKEVEN"));
+        assert!(compute(json!({ "snapshots": [], })).is_err());
+        assert!(compute(json!({ "snapshots": [snapshot("10:00", 22000.0, 330.0)], "expiry_time": "3:30pm" })).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+//! Pre-trade exposure and concentration checks.
+//!
+//! Measures symbol, sector and beta-weighted exposure of `positions` (a
+//! ledger's `positions` array works as is) as a % of `capital`, then walks
+//! `candidates` in order. Each candidate is checked against the book plus
+//! the candidates accepted before it; one that pushes an exposure past its
+//! limit is rejected and left out of the projection. Trades that shrink an
+//! exposure are never rejected for it, so a book already over a limit can
+//! still be cut. Sectors come from the position, then `sectors`, else
+//! UNCLASSIFIED; betas from the position, then `betas`, else 1. Contract
+//! multipliers come from the position or the `instruments` registry.
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::instruments::Instruments;
+use crate::utils::round2;
+
+#[derive(Deserialize)]
+struct ExposureConfig {
+    capital: f64,
+    #[serde(default)]
+    positions: Vec<PositionIn>,
+    #[serde(default)]
+    candidates: Vec<CandidateIn>,
+    #[serde(default)]
+    sectors: HashMap<String, String>,
+    #[serde(default)]
+    betas: HashMap<String, f64>,
+    #[serde(default)]
+    limits: ExposureLimits,
+}
+
+#[derive(Deserialize)]
+struct PositionIn {
+    symbol: String,
+    /// Signed: negative = short.
+    qty: f64,
+    #[serde(alias = "last_price")]
+    price: f64,
+    #[serde(default)]
+    multiplier: Option<f64>,
+    #[serde(default)]
+    sector: Option<String>,
+    #[serde(default)]
+    beta: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct CandidateIn {
+    symbol: String,
+    side: String,
+    qty: f64,
+    price: f64,
+    #[serde(default)]
+    sector: Option<String>,
+    #[serde(default)]
+    beta: Option<f64>,
+}
+
+/// Limits in % of capital.
+#[derive(Deserialize)]
+#[serde(default)]
+struct ExposureLimits {
+    max_symbol_pct: f64,
+    max_sector_pct: f64,
+    max_gross_pct: f64,
+    /// Cap on |Σ value × beta|.
+    max_beta_weighted_pct: f64,
+}
+
+impl Default for ExposureLimits {
+    fn default() -> Self {
+        Self { max_symbol_pct: 20.0, max_sector_pct: 40.0, max_gross_pct: 100.0, max_beta_weighted_pct: 100.0 }
+    }
+}
+
+#[derive(Serialize)]
+struct SymbolExposure {
+    symbol: String,
+    sector: String,
+    beta: f64,
+    value: f64,
+    pct: f64,
+}
+
+#[derive(Serialize)]
+struct SectorExposure {
+    /// Sum of absolute position values.
+    gross: f64,
+    net: f64,
+    pct: f64,
+}
+
+#[derive(Serialize)]
+struct ExposureSummary {
+    long: f64,
+    short: f64,
+    gross: f64,
+    net: f64,
+    beta_weighted: f64,
+    gross_pct: f64,
+    net_pct: f64,
+    beta_weighted_pct: f64,
+    symbols: Vec<SymbolExposure>,
+    sectors: BTreeMap<String, SectorExposure>,
+}
+
+#[derive(Serialize)]
+struct Violation {
+    /// symbol, sector, gross or beta_weighted.
+    limit: &'static str,
+    key: String,
+    value_pct: f64,
+    limit_pct: f64,
+}
+
+#[derive(Serialize)]
+struct CandidateCheck {
+    symbol: String,
+    side: String,
+    qty: f64,
+    value: f64,
+    allowed: bool,
+    violations: Vec<Violation>,
+}
+
+#[derive(Serialize)]
+struct ExposureResult {
+    current: ExposureSummary,
+    /// Book after the allowed candidates.
+    projected: ExposureSummary,
+    /// Limits the current book already breaches.
+    current_violations: Vec<Violation>,
+    candidates: Vec<CandidateCheck>,
+    all_allowed: bool,
+}
+
+/// Signed value per symbol with its classification.
+struct Book {
+    values: BTreeMap<String, f64>,
+    sector: HashMap<String, String>,
+    beta: HashMap<String, f64>,
+}
+
+/// Exposure percentages the limits apply to.
+struct Measures {
+    symbol: HashMap<String, f64>,
+    sector: HashMap<String, f64>,
+    gross: f64,
+    beta_weighted: f64,
+}
+
+impl Book {
+    fn measures(&self, capital: f64) -> Measures {
+        let pct = |v: f64| v / capital * 100.0;
+        let mut sector: HashMap<String, f64> = HashMap::new();
+        let (mut gross, mut beta_weighted) = (0.0, 0.0);
+        for (symbol, &v) in &self.values {
+            *sector.entry(self.sector[symbol].clone()).or_default() += pct(v.abs());
+            gross += v.abs();
+            beta_weighted += v * self.beta[symbol];
+        }
+        Measures {
+            symbol: self.values.iter().map(|(s, v)| (s.clone(), pct(v.abs()))).collect(),
+            sector,
+            gross: pct(gross),
+            beta_weighted: pct(beta_weighted.abs()),
+        }
+    }
+
+    fn summary(&self, capital: f64) -> ExposureSummary {
+        let pct = |v: f64| round2(v / capital * 100.0);
+        let mut symbols = Vec::new();
+        let mut sectors: BTreeMap<String, SectorExposure> = BTreeMap::new();
+        let (mut long, mut short, mut beta_weighted) = (0.0, 0.0, 0.0);
+        for (symbol, &v) in self.values.iter().filter(|(_, v)| **v != 0.0) {
+            let (sector, beta) = (self.sector[symbol].clone(), self.beta[symbol]);
+            if v > 0.0 { long += v } else { short -= v }
+            beta_weighted += v * beta;
+            let s = sectors.entry(sector.clone()).or_insert(SectorExposure { gross: 0.0, net: 0.0, pct: 0.0 });
+            s.gross += v.abs();
+            s.net += v;
+            symbols.push(SymbolExposure { symbol: symbol.clone(), sector, beta, value: round2(v), pct: pct(v.abs()) });
+        }
+        for s in sectors.values_mut() {
+            s.pct = pct(s.gross);
+            s.gross = round2(s.gross);
+            s.net = round2(s.net);
+        }
+        symbols.sort_by(|a, b| b.pct.total_cmp(&a.pct));
+        ExposureSummary {
+            long: round2(long),
+            short: round2(short),
+            gross: round2(long + short),
+            net: round2(long - short),
+            beta_weighted: round2(beta_weighted),
+            gross_pct: pct(long + short),
+            net_pct: pct(long - short),
+            beta_weighted_pct: pct(beta_weighted),
+            symbols,
+            sectors,
+        }
+    }
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let instruments = Instruments::from_request(&data)?;
+    let config: ExposureConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid exposure_check config: {}", e))?;
+    if !(config.capital.is_finite() && config.capital > 0.0) {
+        return Err("capital must be positive".to_string());
+    }
+    let capital = config.capital;
+    let limits = &config.limits;
+
+    let mut book = Book { values: BTreeMap::new(), sector: HashMap::new(), beta: HashMap::new() };
+    let classify = |book: &mut Book, symbol: &str, sector: &Option<String>, beta: Option<f64>| {
+        let sector = sector.clone()
+            .or_else(|| config.sectors.get(symbol).cloned())
+            .unwrap_or_else(|| "UNCLASSIFIED".to_string());
+        let beta = beta.or_else(|| config.betas.get(symbol).copied()).unwrap_or(1.0);
+        book.sector.entry(symbol.to_string()).or_insert(sector);
+        book.beta.entry(symbol.to_string()).or_insert(beta);
+        book.values.entry(symbol.to_string()).or_insert(0.0);
+    };
+    for p in &config.positions {
+        classify(&mut book, &p.symbol, &p.sector, p.beta);
+        let multiplier = p.multiplier.unwrap_or_else(|| instruments.get(&p.symbol).multiplier);
+        *book.values.get_mut(&p.symbol).unwrap() += p.qty * p.price * multiplier;
+    }
+
+    let before = book.measures(capital);
+    let current = book.summary(capital);
+    let current_violations = violations(&before, None, limits);
+
+    let mut checks = Vec::new();
+    for c in &config.candidates {
+        let sign = match c.side.to_uppercase().as_str() {
+            "BUY" | "LONG" => 1.0,
+            "SELL" | "SHORT" => -1.0,
+            other => return Err(format!("{}: side must be BUY or SELL, got '{}'", c.symbol, other)),
+        };
+        if !(c.qty.is_finite() && c.qty > 0.0 && c.price.is_finite() && c.price > 0.0) {
+            return Err(format!("{}: qty and price must be positive", c.symbol));
+        }
+        let instrument = instruments.get(&c.symbol);
+        let value = sign * instrument.notional(c.price, c.qty);
+
+        classify(&mut book, &c.symbol, &c.sector, c.beta);
+        let old = book.measures(capital);
+        *book.values.get_mut(&c.symbol).unwrap() += value;
+        let new = book.measures(capital);
+        let found = violations(&new, Some((&old, &c.symbol, &book.sector[&c.symbol])), limits);
+        let allowed = found.is_empty();
+        if !allowed {
+            *book.values.get_mut(&c.symbol).unwrap() -= value;
+        }
+        checks.push(CandidateCheck {
+            symbol: c.symbol.clone(),
+            side: c.side.to_uppercase(),
+            qty: c.qty,
+            value: round2(value),
+            allowed,
+            violations: found,
+        });
+    }
+
+    let result = ExposureResult {
+        current,
+        projected: book.summary(capital),
+        current_violations,
+        all_allowed: checks.iter().all(|c| c.allowed),
+        candidates: checks,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Limits `m` breaches. With `change = (before, symbol, sector)` only the
+/// exposures the trade touched and increased count.
+fn violations(m: &Measures, change: Option<(&Measures, &String, &String)>, limits: &ExposureLimits) -> Vec<Violation> {
+    let eps = 1e-9;
+    let grew = |now: f64, was: Option<f64>| was.is_none_or(|w| now > w + eps);
+    let mut out = Vec::new();
+    let mut check = |limit: &'static str, key: &str, value: f64, was: Option<f64>, cap: f64| {
+        if value > cap + eps && grew(value, was) {
+            out.push(Violation { limit, key: key.to_string(), value_pct: round2(value), limit_pct: cap });
+        }
+    };
+    match change {
+        Some((before, symbol, sector)) => {
+            let was = |map: &HashMap<String, f64>, k: &str| Some(map.get(k).copied().unwrap_or(0.0));
+            check("symbol", symbol, m.symbol[symbol], was(&before.symbol, symbol), limits.max_symbol_pct);
+            if sector != "UNCLASSIFIED" {
+                check("sector", sector, m.sector[sector], was(&before.sector, sector), limits.max_sector_pct);
+            }
+            check("gross", "portfolio", m.gross, Some(before.gross), limits.max_gross_pct);
+            check("beta_weighted", "portfolio", m.beta_weighted, Some(before.beta_weighted), limits.max_beta_weighted_pct);
+        }
+        None => {
+            let mut symbols: Vec<_> = m.symbol.iter().collect();
+            symbols.sort_by(|a, b| a.0.cmp(b.0));
+            for (symbol, &v) in symbols {
+                check("symbol", symbol, v, None, limits.max_symbol_pct);
+            }
+            let mut sectors: Vec<_> = m.sector.iter().filter(|(s, _)| *s != "UNCLASSIFIED").collect();
+            sectors.sort_by(|a, b| a.0.cmp(b.0));
+            for (sector, &v) in sectors {
+                check("sector", sector, v, None, limits.max_sector_pct);
+            }
+            check("gross", "portfolio", m.gross, None, limits.max_gross_pct);
+            check("beta_weighted", "portfolio", m.beta_weighted, None, limits.max_beta_weighted_pct);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_candidates_checked_against_book_and_earlier_candidates() {
+        let out = compute(json!({
+            "capital": 1_000_000.0,
+            "sectors": { "HDFCBANK": "BANK", "ICICIBANK": "BANK", "SBIN": "BANK", "INFY": "IT" },
+            "betas": { "SBIN": 1.4 },
+            "positions": [
+                { "symbol": "HDFCBANK", "qty": 100, "last_price": 1_500.0 },
+                { "symbol": "INFY", "qty": -50, "price": 1_600.0 },
+            ],
+            "candidates": [
+                { "symbol": "ICICIBANK", "side": "BUY", "qty": 150, "price": 1_000.0 },
+                { "symbol": "SBIN", "side": "BUY", "qty": 150, "price": 800.0 },
+                { "symbol": "TCS", "side": "BUY", "qty": 100, "price": 4_000.0 },
+                { "symbol": "HDFCBANK", "side": "SELL", "qty": 50, "price": 1_500.0 },
+            ],
+        })).unwrap();
+
+        let current = &out["current"];
+        assert_eq!(current["gross"], 230_000.0);
+        assert_eq!(current["net"], 70_000.0);
+        assert_eq!(current["sectors"]["BANK"]["pct"], 15.0);
+        assert!(out["current_violations"].as_array().unwrap().is_empty());
+
+        let checks = out["candidates"].as_array().unwrap();
+        // BANK goes 15% → 30%: fine.
+        assert_eq!(checks[0]["allowed"], true);
+        // SBIN would take BANK to 42% > 40%.
+        assert_eq!(checks[1]["allowed"], false);
+        assert_eq!(checks[1]["violations"][0]["limit"], "sector");
+        assert_eq!(checks[1]["violations"][0]["value_pct"], 42.0);
+        // TCS is 40% of capital on its own.
+        assert_eq!(checks[2]["violations"][0]["limit"], "symbol");
+        // Trimming is always allowed.
+        assert_eq!(checks[3]["allowed"], true);
+        assert_eq!(out["all_allowed"], false);
+
+        // Projection holds only the accepted trades: HDFCBANK 75k, ICICIBANK 150k, INFY -80k.
+        let projected = &out["projected"];
+        assert_eq!(projected["gross"], 305_000.0);
+        assert_eq!(projected["sectors"]["BANK"]["gross"], 225_000.0);
+        assert!(projected["symbols"].as_array().unwrap().iter().all(|s| s["symbol"] != "SBIN"));
+    }
+
+    #[test]
+    fn test_beta_weighted_and_existing_breaches() {
+        let out = compute(json!({
+            "capital": 100_000.0,
+            "limits": { "max_symbol_pct": 50.0, "max_beta_weighted_pct": 60.0 },
+            "instruments": { "NIFTYFUT": { "lot_size": 75, "multiplier": 1 } },
+            "betas": { "NIFTYFUT": 1.0, "ADANIENT": 2.0 },
+            "positions": [{ "symbol": "ADANIENT", "qty": 20, "price": 2_500.0 }],
+            "candidates": [
+                { "symbol": "NIFTYFUT", "side": "SELL", "qty": 1, "price": 22_000.0 },
+                { "symbol": "ADANIENT", "side": "BUY", "qty": 1, "price": 2_500.0 },
+            ],
+        })).unwrap();
+        // 50k at beta 2 = 100% beta-weighted, already over 60%.
+        assert_eq!(out["current"]["beta_weighted_pct"], 100.0);
+        assert_eq!(out["current_violations"][0]["limit"], "beta_weighted");
+        // A short hedge lowers beta-weighted exposure and is allowed.
+        assert_eq!(out["candidates"][0]["allowed"], true);
+        assert_eq!(out["projected"]["beta_weighted_pct"], 78.0);
+        // Adding to the high-beta name grows both its symbol share and beta exposure.
+        let limits: Vec<&str> = out["candidates"][1]["violations"].as_array().unwrap().iter()
+            .map(|v| v["limit"].as_str().unwrap()).collect();
+        assert_eq!(limits, ["symbol", "beta_weighted"]);
+
+        assert!(compute(json!({ "capital": 0.0 })).is_err());
+        assert!(compute(json!({ "capital": 1.0, "candidates": [{ "symbol": "X", "side": "HOLD", "qty": 1, "price": 1 }] })).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Wide feature matrix for training models outside the engine.
+//!
+//! Columns are the `signals` indicators (same definitions, same warm-up)
+//! plus ATR, trailing returns over `return_periods` and, unless
+//! `include_regime` is off, the `regime` label code and probabilities. Each
+//! base column is repeated at every lag in `lags`; `target_fwd_{k}` is the
+//! forward return over `forward_periods`, left empty where the future is
+//! unknown. The first `warmup_bars` rows (indicators still settling) are
+//! dropped. With `path` the matrix is written as CSV, as Parquet when built
+//! with the `parquet` feature, or as an Arrow IPC file (`.arrow`/`.feather`,
+//! readable zero-copy by Polars/pyarrow) with the `arrow` feature; otherwise
+//! it is returned inline.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::indicators;
+use crate::regime::{self, RegimeParams, LABELS};
+use crate::utils::{calc_atr_series, sanitize_candles, Candle};
+
+#[derive(Deserialize)]
+struct FeatureConfig {
+    candles: Vec<Candle>,
+    /// `signals` outputs to keep; empty = all of them.
+    #[serde(default)]
+    indicators: Vec<String>,
+    #[serde(default = "default_lags")]
+    lags: Vec<usize>,
+    #[serde(default = "default_return_periods")]
+    return_periods: Vec<usize>,
+    #[serde(default = "default_forward_periods")]
+    forward_periods: Vec<usize>,
+    #[serde(default = "default_true")]
+    include_regime: bool,
+    #[serde(default)]
+    regime: RegimeParams,
+    #[serde(default = "default_warmup")]
+    warmup_bars: usize,
+    /// Drop tail rows whose targets are unknown.
+    #[serde(default)]
+    drop_incomplete_targets: bool,
+    #[serde(default)]
+    path: Option<String>,
+    /// "csv", "parquet" or "arrow"; inferred from the path's extension when omitted.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+fn default_lags() -> Vec<usize> { vec![1, 2, 3] }
+fn default_return_periods() -> Vec<usize> { vec![1, 5, 20] }
+fn default_forward_periods() -> Vec<usize> { vec![1, 5] }
+fn default_true() -> bool { true }
+fn default_warmup() -> usize { 50 }
+
+#[derive(Serialize)]
+struct FeatureResult {
+    rows: usize,
+    columns: Vec<String>,
+    /// Order of the `regime` codes.
+    regime_labels: Option<[&'static str; 3]>,
+    path: Option<String>,
+    format: Option<String>,
+    timestamps: Option<Vec<String>>,
+    data: Option<Vec<Vec<Option<f64>>>>,
+}
+
+/// Named column of per-bar values; None where undefined.
+type Column = (String, Vec<Option<f64>>);
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: FeatureConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid features config: {}", e))?;
+    let mut candles = config.candles;
+    sanitize_candles(&mut candles);
+    let n = candles.len();
+    if n <= config.warmup_bars {
+        return Err(format!("Need more than {} candles (warmup_bars)", config.warmup_bars));
+    }
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+
+    let mut base: Vec<Column> = Vec::new();
+    let series = indicators::standard(&candles).columns();
+    for name in &config.indicators {
+        if !series.iter().any(|(k, _)| k == name) {
+            let known: Vec<&str> = series.iter().map(|(k, _)| *k).collect();
+            return Err(format!("Unknown indicator '{}' ({})", name, known.join(", ")));
+        }
+    }
+    for (name, values) in series {
+        if !config.indicators.is_empty() && !config.indicators.iter().any(|k| k == name) {
+            continue;
+        }
+        base.push((name.to_string(), values.into_iter().map(Some).collect()));
+    }
+    let highs: Vec<f64> = candles.iter().map(|c| c.high).collect();
+    let lows: Vec<f64> = candles.iter().map(|c| c.low).collect();
+    base.push(("atr_14".to_string(), calc_atr_series(&highs, &lows, &closes, 14).into_iter().map(Some).collect()));
+    for &k in config.return_periods.iter().filter(|k| **k > 0) {
+        let values = (0..n).map(|i| (i >= k && closes[i - k] > 0.0).then(|| closes[i] / closes[i - k] - 1.0)).collect();
+        base.push((format!("return_{}", k), values));
+    }
+    if config.include_regime {
+        let fit = regime::classify(&candles, &config.regime)?;
+        let code = fit.bars.iter().map(|b| b.regime.and_then(|r| LABELS.iter().position(|l| *l == r)).map(|c| c as f64)).collect();
+        base.push(("regime".to_string(), code));
+        for (k, label) in LABELS.iter().enumerate() {
+            let p = fit.bars.iter().map(|b| b.regime.map(|_| b.probabilities[k])).collect();
+            base.push((format!("regime_p_{}", label), p));
+        }
+    }
+
+    let mut columns: Vec<Column> = Vec::new();
+    for (name, values) in &base {
+        columns.push((name.clone(), values.clone()));
+        for &lag in config.lags.iter().filter(|l| **l > 0) {
+            let shifted = (0..n).map(|i| if i >= lag { values[i - lag] } else { None }).collect();
+            columns.push((format!("{}_lag{}", name, lag), shifted));
+        }
+    }
+    for &k in config.forward_periods.iter().filter(|k| **k > 0) {
+        let values = (0..n).map(|i| (i + k < n && closes[i] > 0.0).then(|| closes[i + k] / closes[i] - 1.0)).collect();
+        columns.push((format!("target_fwd_{}", k), values));
+    }
+
+    let max_fwd = config.forward_periods.iter().copied().max().unwrap_or(0);
+    let end = if config.drop_incomplete_targets { n.saturating_sub(max_fwd) } else { n };
+    let rows: Vec<usize> = (config.warmup_bars..end).collect();
+    let names: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+    let matrix: Vec<Vec<Option<f64>>> = rows.iter()
+        .map(|&i| columns.iter().map(|(_, v)| v[i].filter(|x| x.is_finite())).collect())
+        .collect();
+    let timestamps: Vec<String> = rows.iter().map(|&i| candles[i].timestamp.clone()).collect();
+    let regime_labels = config.include_regime.then_some(LABELS);
+
+    let result = match &config.path {
+        Some(path) => {
+            let format = config.format.clone()
+                .or_else(|| std::path::Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()))
+                .unwrap_or_else(|| "csv".to_string());
+            match format.as_str() {
+                "csv" | "txt" => write_csv(path, &names, &timestamps, &matrix)?,
+                "parquet" | "pq" => write_parquet(path, &names, &timestamps, &matrix)?,
+                "arrow" | "ipc" | "feather" => write_arrow(path, &names, &timestamps, &matrix)?,
+                other => return Err(format!("Unsupported features format: {}", other)),
+            }
+            FeatureResult { rows: matrix.len(), columns: names, regime_labels, path: Some(path.clone()), format: Some(format), timestamps: None, data: None }
+        }
+        None => FeatureResult { rows: matrix.len(), columns: names, regime_labels, path: None, format: None, timestamps: Some(timestamps), data: Some(matrix) },
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn write_csv(path: &str, names: &[String], timestamps: &[String], matrix: &[Vec<Option<f64>>]) -> Result<(), String> {
+    let mut writer = csv::Writer::from_path(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let err = |e: csv::Error| format!("CSV error in {}: {}", path, e);
+    writer.write_record(std::iter::once("timestamp").chain(names.iter().map(|s| s.as_str()))).map_err(err)?;
+    for (ts, row) in timestamps.iter().zip(matrix) {
+        let cells = row.iter().map(|v| v.map(|x| x.to_string()).unwrap_or_default());
+        writer.write_record(std::iter::once(ts.clone()).chain(cells)).map_err(err)?;
+    }
+    writer.flush().map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(path: &str, names: &[String], timestamps: &[String], matrix: &[Vec<Option<f64>>]) -> Result<(), String> {
+    use std::sync::Arc;
+    use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    let err = |e: parquet::errors::ParquetError| format!("Parquet error in {}: {}", path, e);
+    let fields: Vec<String> = names.iter().map(|n| format!("OPTIONAL DOUBLE {};", n)).collect();
+    let schema = format!("message features {{ REQUIRED BYTE_ARRAY timestamp (UTF8); {} }}", fields.join(" "));
+    let schema = Arc::new(parse_message_type(&schema).map_err(err)?);
+    let file = std::fs::File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut writer = SerializedFileWriter::new(file, schema, Arc::new(WriterProperties::builder().build())).map_err(err)?;
+    let mut group = writer.next_row_group().map_err(err)?;
+    let mut index = 0;
+    while let Some(mut col) = group.next_column().map_err(err)? {
+        if index == 0 {
+            let values: Vec<ByteArray> = timestamps.iter().map(|t| ByteArray::from(t.as_str())).collect();
+            col.typed::<ByteArrayType>().write_batch(&values, None, None).map_err(err)?;
+        } else {
+            let cells = matrix.iter().map(|row| row[index - 1]);
+            let values: Vec<f64> = cells.clone().flatten().collect();
+            let defs: Vec<i16> = cells.map(|v| v.is_some() as i16).collect();
+            col.typed::<DoubleType>().write_batch(&values, Some(&defs), None).map_err(err)?;
+        }
+        col.close().map_err(err)?;
+        index += 1;
+    }
+    group.close().map_err(err)?;
+    writer.close().map_err(err)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_path: &str, _names: &[String], _timestamps: &[String], _matrix: &[Vec<Option<f64>>]) -> Result<(), String> {
+    Err("Parquet support not compiled in; rebuild with --features parquet".to_string())
+}
+
+#[cfg(feature = "arrow")]
+fn write_arrow(path: &str, names: &[String], timestamps: &[String], matrix: &[Vec<Option<f64>>]) -> Result<(), String> {
+    use std::sync::Arc;
+    use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+    use arrow_ipc::writer::FileWriter;
+    use arrow_schema::{DataType, Field, Schema};
+
+    let err = |e: arrow_schema::ArrowError| format!("Arrow error in {}: {}", path, e);
+    let mut fields = vec![Field::new("timestamp", DataType::Utf8, false)];
+    fields.extend(names.iter().map(|n| Field::new(n, DataType::Float64, true)));
+    let schema = Arc::new(Schema::new(fields));
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(timestamps))];
+    columns.extend((0..names.len()).map(|c| {
+        Arc::new(matrix.iter().map(|row| row[c]).collect::<Float64Array>()) as ArrayRef
+    }));
+    let batch = RecordBatch::try_new(schema.clone(), columns).map_err(err)?;
+    let file = std::fs::File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut writer = FileWriter::try_new_buffered(file, &schema).map_err(err)?;
+    writer.write(&batch).map_err(err)?;
+    writer.finish().map_err(err)
+}
+
+#[cfg(not(feature = "arrow"))]
+fn write_arrow(_path: &str, _names: &[String], _timestamps: &[String], _matrix: &[Vec<Option<f64>>]) -> Result<(), String> {
+    Err("Arrow support not compiled in; rebuild with --features arrow".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candles(n: usize) -> Vec<Value> {
+        (0..n).map(|i| {
+            let c = 100.0 + (i as f64 * 0.3).sin() * 5.0 + i as f64 * 0.1;
+            json!({ "timestamp": format!("2024-01-01T{:02}:{:02}:00", 9 + i / 60, i % 60), "open": c, "high": c + 1.0, "low": c - 1.0, "close": c, "volume": 1000.0 })
+        }).collect()
+    }
+
+    #[test]
+    fn test_matrix_lags_and_targets() {
+        let result = compute(json!({ "candles": candles(120), "indicators": ["rsi_14", "ema_9"], "lags": [1], "forward_periods": [2] })).unwrap();
+        let cols: Vec<&str> = result["columns"].as_array().unwrap().iter().map(|c| c.as_str().unwrap()).collect();
+        assert_eq!(&cols[..4], &["ema_9", "ema_9_lag1", "rsi_14", "rsi_14_lag1"]);
+        assert!(cols.contains(&"regime_p_crash"));
+        assert_eq!(*cols.last().unwrap(), "target_fwd_2");
+        assert_eq!(result["rows"], 70);
+        let data = result["data"].as_array().unwrap();
+        // The lag column is the previous row's value.
+        assert_eq!(data[1][1], data[0][0]);
+        // Last two rows have no 2-bar forward return.
+        assert!(data[69].as_array().unwrap().last().unwrap().is_null());
+        assert!(!data[67].as_array().unwrap().last().unwrap().is_null());
+        assert!(compute(json!({ "candles": candles(120), "indicators": ["adx"] })).is_err());
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let path = std::env::temp_dir().join(format!("features_{}.csv", std::process::id()));
+        let path_str = path.to_string_lossy().to_string();
+        let result = compute(json!({
+            "candles": candles(100), "indicators": ["macd"], "lags": [], "include_regime": false,
+            "drop_incomplete_targets": true, "path": path_str
+        })).unwrap();
+        assert_eq!(result["rows"], 45);
+        assert!(result["data"].is_null());
+        let text = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let mut lines = text.lines();
+        assert_eq!(lines.next().unwrap(), "timestamp,macd,atr_14,return_1,return_5,return_20,target_fwd_1,target_fwd_5");
+        assert_eq!(lines.count(), 45);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_arrow_export() {
+        let path = std::env::temp_dir().join(format!("features_{}.arrow", std::process::id()));
+        let result = compute(json!({
+            "candles": candles(100), "indicators": ["rsi_14"], "lags": [], "include_regime": false,
+            "path": path.to_string_lossy()
+        })).unwrap();
+        assert_eq!(result["format"], "arrow");
+        let file = std::fs::File::open(&path).unwrap();
+        let batches: Vec<_> = arrow_ipc::reader::FileReader::try_new(file, None).unwrap().map(|b| b.unwrap()).collect();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(batches[0].num_rows(), 50);
+        assert_eq!(batches[0].schema().field(1).name(), "rsi_14");
+        // Unknown forward returns are nulls, not NaN.
+        assert_eq!(batches[0].column(batches[0].num_columns() - 1).null_count(), 5);
+    }
+}
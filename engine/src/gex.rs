@@ -0,0 +1,290 @@
+//! Dealer gamma and vega exposure (GEX/VEX) from an option chain.
+//!
+//! Each strike's open interest is assumed held by dealers against
+//! customers: with the default `"standard"` convention dealers are long the
+//! calls customers wrote and short the puts they bought, so call exposure
+//! counts positive and put exposure negative; `"short_all"` makes dealers
+//! short both sides. Gamma exposure is in rupees of delta per 1% spot move
+//! (Γ × OI × lot × S² × 0.01) and vega exposure in rupees per vol point.
+//!
+//! The profile re-prices the whole chain at spots across `range_pct` with
+//! IVs held fixed; where total GEX changes sign is the zero-gamma level,
+//! above which dealer hedging dampens moves and below which it amplifies
+//! them. Pinning candidates are the strikes with the largest positive net
+//! GEX near spot.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::chain_analysis::{days_to_expiry, mid_price, ChainStrike};
+use crate::greeks::solve_iv;
+use crate::utils::{bs_greeks, round2};
+
+#[derive(Deserialize)]
+struct GexConfig {
+    spot: f64,
+    strikes: Vec<ChainStrike>,
+    #[serde(default)]
+    expiry: Option<String>,
+    #[serde(default)]
+    expiry_days: Option<f64>,
+    #[serde(default)]
+    valuation_date: Option<String>,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    /// Units per contract when OI is quoted in contracts.
+    #[serde(default = "default_lot_size")]
+    lot_size: f64,
+    /// "standard" (dealers long calls, short puts) or "short_all".
+    #[serde(default = "default_convention")]
+    convention: String,
+    /// IV for sides with neither an IV nor a quote to solve one from;
+    /// such sides are skipped when omitted.
+    #[serde(default)]
+    default_iv: Option<f64>,
+    /// Profile spans spot ± this percentage.
+    #[serde(default = "default_range_pct")]
+    range_pct: f64,
+    #[serde(default = "default_steps")]
+    steps: usize,
+    #[serde(default = "default_top_n")]
+    top_n: usize,
+}
+
+fn default_rate() -> f64 { 0.065 }
+fn default_lot_size() -> f64 { 1.0 }
+fn default_convention() -> String { "standard".to_string() }
+fn default_range_pct() -> f64 { 10.0 }
+fn default_steps() -> usize { 41 }
+fn default_top_n() -> usize { 3 }
+
+#[derive(Serialize)]
+struct StrikeExposure {
+    strike: f64,
+    call_gex: f64,
+    put_gex: f64,
+    net_gex: f64,
+    call_vex: f64,
+    put_vex: f64,
+    net_vex: f64,
+    call_oi: f64,
+    put_oi: f64,
+}
+
+#[derive(Serialize)]
+struct ProfilePoint {
+    spot: f64,
+    gex: f64,
+}
+
+#[derive(Serialize)]
+struct PinCandidate {
+    strike: f64,
+    net_gex: f64,
+    total_oi: f64,
+    distance_pct: f64,
+}
+
+#[derive(Serialize)]
+struct GexResult {
+    spot: f64,
+    expiry_days: f64,
+    convention: String,
+    total_gex: f64,
+    total_vex: f64,
+    /// "LONG_GAMMA" (hedging dampens moves) or "SHORT_GAMMA".
+    regime: String,
+    /// Spot where total GEX crosses zero, nearest the current spot; None
+    /// when it keeps one sign across the profile.
+    zero_gamma: Option<f64>,
+    /// Strikes with the largest call and put gamma exposure.
+    call_wall: Option<f64>,
+    put_wall: Option<f64>,
+    pin_candidates: Vec<PinCandidate>,
+    by_strike: Vec<StrikeExposure>,
+    profile: Vec<ProfilePoint>,
+}
+
+/// One side's open interest with the IV its greeks are taken at.
+struct Side {
+    strike: f64,
+    iv: f64,
+    oi: f64,
+    is_call: bool,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: GexConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid gex config: {}", e))?;
+    if !(config.spot.is_finite() && config.spot > 0.0) {
+        return Err("spot must be positive".to_string());
+    }
+    if !(config.lot_size.is_finite() && config.lot_size > 0.0) {
+        return Err("lot_size must be positive".to_string());
+    }
+    let (call_sign, put_sign) = match config.convention.to_lowercase().as_str() {
+        "standard" => (1.0, -1.0),
+        "short_all" => (-1.0, -1.0),
+        other => return Err(format!("Unknown convention: {}", other)),
+    };
+    let days = days_to_expiry(config.expiry_days, config.expiry.as_deref(), config.valuation_date.as_deref(), "gex")?;
+    let t = days / 365.0;
+    let r = config.risk_free_rate;
+    let mut strikes: Vec<ChainStrike> = config.strikes.into_iter().filter(|s| s.strike.is_finite() && s.strike > 0.0).collect();
+    if strikes.is_empty() {
+        return Err("gex requires strikes".to_string());
+    }
+    strikes.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+
+    let side = |s: &ChainStrike, is_call: bool| -> Option<Side> {
+        let (bid, ask, ltp, iv) = s.quote(is_call);
+        let oi = if is_call { s.call_oi } else { s.put_oi };
+        if oi <= 0.0 {
+            return None;
+        }
+        let mid = mid_price(bid, ask, ltp);
+        let iv = iv.filter(|v| *v > 0.0)
+            .or_else(|| (mid > 0.0).then(|| solve_iv(config.spot, s.strike, r, t, mid, is_call)).filter(|v| *v > 0.0))
+            .or(config.default_iv.filter(|v| *v > 0.0))?;
+        Some(Side { strike: s.strike, iv, oi, is_call })
+    };
+    let sides: Vec<Side> = strikes.iter().flat_map(|s| [side(s, true), side(s, false)]).flatten().collect();
+    let sign = |is_call: bool| if is_call { call_sign } else { put_sign };
+    // (gex, vex) of one side at `spot`.
+    let exposure = |sd: &Side, spot: f64| {
+        let (_, gamma, _, vega, _) = bs_greeks(spot, sd.strike, t, r, sd.iv, sd.is_call);
+        let units = sd.oi * config.lot_size * sign(sd.is_call);
+        (gamma * units * spot * spot * 0.01, vega * units)
+    };
+
+    let by_strike: Vec<StrikeExposure> = strikes.iter().map(|s| {
+        let (mut cg, mut pg, mut cv, mut pv) = (0.0, 0.0, 0.0, 0.0);
+        for sd in sides.iter().filter(|sd| sd.strike == s.strike) {
+            let (g, v) = exposure(sd, config.spot);
+            if sd.is_call { cg += g; cv += v; } else { pg += g; pv += v; }
+        }
+        StrikeExposure {
+            strike: s.strike,
+            call_gex: round2(cg),
+            put_gex: round2(pg),
+            net_gex: round2(cg + pg),
+            call_vex: round2(cv),
+            put_vex: round2(pv),
+            net_vex: round2(cv + pv),
+            call_oi: s.call_oi,
+            put_oi: s.put_oi,
+        }
+    }).collect();
+    let total_gex: f64 = by_strike.iter().map(|s| s.net_gex).sum();
+    let total_vex: f64 = by_strike.iter().map(|s| s.net_vex).sum();
+
+    let steps = config.steps.max(2);
+    let (lo, hi) = (config.spot * (1.0 - config.range_pct / 100.0), config.spot * (1.0 + config.range_pct / 100.0));
+    let profile: Vec<(f64, f64)> = (0..steps).map(|i| {
+        let s = lo + (hi - lo) * i as f64 / (steps - 1) as f64;
+        (s, sides.iter().map(|sd| exposure(sd, s).0).sum())
+    }).collect();
+    let zero_gamma = profile.windows(2)
+        .filter(|w| (w[0].1 < 0.0) != (w[1].1 < 0.0))
+        .map(|w| {
+            let (a, b) = (w[0], w[1]);
+            a.0 + (b.0 - a.0) * a.1 / (a.1 - b.1)
+        })
+        .min_by(|a, b| (a - config.spot).abs().total_cmp(&(b - config.spot).abs()));
+
+    let wall = |key: fn(&StrikeExposure) -> f64| by_strike.iter()
+        .filter(|s| key(s) != 0.0)
+        .max_by(|a, b| key(a).abs().total_cmp(&key(b).abs()))
+        .map(|s| s.strike);
+    let mut pin_candidates: Vec<PinCandidate> = by_strike.iter()
+        .filter(|s| s.net_gex > 0.0)
+        .map(|s| PinCandidate {
+            strike: s.strike,
+            net_gex: s.net_gex,
+            total_oi: s.call_oi + s.put_oi,
+            distance_pct: round2((s.strike / config.spot - 1.0) * 100.0),
+        })
+        .filter(|p| p.distance_pct.abs() <= config.range_pct)
+        .collect();
+    pin_candidates.sort_by(|a, b| b.net_gex.total_cmp(&a.net_gex));
+    pin_candidates.truncate(config.top_n);
+
+    let result = GexResult {
+        spot: config.spot,
+        expiry_days: days,
+        convention: config.convention.to_lowercase(),
+        total_gex: round2(total_gex),
+        total_vex: round2(total_vex),
+        regime: if total_gex >= 0.0 { "LONG_GAMMA" } else { "SHORT_GAMMA" }.to_string(),
+        zero_gamma: zero_gamma.map(round2),
+        call_wall: wall(|s| s.call_gex),
+        put_wall: wall(|s| s.put_gex),
+        pin_candidates,
+        by_strike,
+        profile: profile.into_iter().map(|(spot, gex)| ProfilePoint { spot: round2(spot), gex: round2(gex) }).collect(),
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Call OI stacked above spot, put OI below, at a flat 20% IV.
+    fn chain(spot: f64) -> Value {
+        let strikes: Vec<Value> = (0..11).map(|i| {
+            let k = spot * 0.9 + i as f64 * spot * 0.02;
+            let above = k >= spot;
+            json!({
+                "strike": k, "call_iv": 0.2, "put_iv": 0.2,
+                "call_oi": if above { 20_000.0 } else { 2_000.0 },
+                "put_oi": if above { 2_000.0 } else { 20_000.0 },
+            })
+        }).collect();
+        json!({ "spot": spot, "expiry_days": 7, "lot_size": 25, "strikes": strikes })
+    }
+
+    #[test]
+    fn test_gex_signs_walls_and_zero_gamma() {
+        let result = compute(chain(100.0)).unwrap();
+        let rows = result["by_strike"].as_array().unwrap();
+        assert_eq!(rows.len(), 11);
+        for r in rows {
+            assert!(r["call_gex"].as_f64().unwrap() >= 0.0 && r["put_gex"].as_f64().unwrap() <= 0.0);
+        }
+        assert!(result["call_wall"].as_f64().unwrap() >= 100.0);
+        assert!(result["put_wall"].as_f64().unwrap() < 100.0);
+        // Long gamma above the put-heavy strikes, short below: the flip sits
+        // just under spot.
+        let zero = result["zero_gamma"].as_f64().unwrap();
+        assert!(zero > 95.0 && zero < 100.0, "zero gamma {}", zero);
+        let profile = result["profile"].as_array().unwrap();
+        assert_eq!(profile.len(), 41);
+        assert!(profile[0]["gex"].as_f64().unwrap() < 0.0 && profile[40]["gex"].as_f64().unwrap() > 0.0);
+        let pins = result["pin_candidates"].as_array().unwrap();
+        assert!(!pins.is_empty() && pins.iter().all(|p| p["net_gex"].as_f64().unwrap() > 0.0));
+        assert!(pins[0]["strike"].as_f64().unwrap() >= 100.0);
+        assert_eq!(result["regime"], if result["total_gex"].as_f64().unwrap() >= 0.0 { "LONG_GAMMA" } else { "SHORT_GAMMA" });
+    }
+
+    #[test]
+    fn test_short_all_convention_and_vega() {
+        let mut input = chain(100.0);
+        input["convention"] = json!("short_all");
+        let result = compute(input).unwrap();
+        assert!(result["by_strike"].as_array().unwrap().iter().all(|r| r["net_gex"].as_f64().unwrap() <= 0.0));
+        assert!(result["total_vex"].as_f64().unwrap() < 0.0);
+        assert_eq!(result["regime"], "SHORT_GAMMA");
+        assert!(result["pin_candidates"].as_array().unwrap().is_empty());
+
+        // Without IVs or quotes every side is skipped unless default_iv is set.
+        let bare = json!({ "spot": 100.0, "expiry_days": 7, "strikes": [{ "strike": 100.0, "call_oi": 1000.0 }] });
+        let empty = compute(bare.clone()).unwrap();
+        assert_eq!(empty["total_gex"], 0.0);
+        assert!(empty["zero_gamma"].is_null());
+        let mut flat = bare;
+        flat["default_iv"] = json!(0.2);
+        assert!(compute(flat).unwrap()["total_gex"].as_f64().unwrap() > 0.0);
+        assert!(compute(json!({ "spot": 100.0, "expiry_days": 7, "strikes": [], "convention": "x" })).is_err());
+    }
+}
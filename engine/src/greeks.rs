@@ -4,34 +4,34 @@ use std::f64::consts::E;
 
 use crate::utils::{norm_cdf, norm_pdf, round4, bs_price};
 
-#[derive(Deserialize)]
-struct GreeksInput {
-    spot: f64,
-    strike: f64,
-    time_to_expiry: f64,
-    risk_free_rate: f64,
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GreeksInput {
+    pub spot: f64,
+    pub strike: f64,
+    pub time_to_expiry: f64,
+    pub risk_free_rate: f64,
     #[serde(default)]
-    volatility: f64,
-    option_type: String,
+    pub volatility: f64,
+    pub option_type: String,
     /// When provided, IV is solved from this market price via bisection.
     /// `volatility` is only used as a fallback when `market_price` is absent.
     #[serde(default)]
-    market_price: Option<f64>,
+    pub market_price: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct GreeksOutput {
-    price: f64,
-    delta: f64,
-    gamma: f64,
-    theta: f64,
-    vega: f64,
-    rho: f64,
-    implied_volatility: f64,
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GreeksOutput {
+    pub price: f64,
+    pub delta: f64,
+    pub gamma: f64,
+    pub theta: f64,
+    pub vega: f64,
+    pub rho: f64,
+    pub implied_volatility: f64,
 }
 
 /// Solve implied volatility from a market price using bisection search.
-fn solve_iv(spot: f64, strike: f64, r: f64, t: f64, market_price: f64, is_call: bool) -> f64 {
+pub(crate) fn solve_iv(spot: f64, strike: f64, r: f64, t: f64, market_price: f64, is_call: bool) -> f64 {
     if t <= 0.0 || market_price <= 0.0 { return 0.0; }
     let intrinsic = if is_call { (spot - strike * E.powf(-r * t)).max(0.0) } else { (strike * E.powf(-r * t) - spot).max(0.0) };
     if market_price < intrinsic { return 0.0; }
@@ -101,7 +101,11 @@ fn compute_greeks_at_vol(s: f64, k: f64, r: f64, t: f64, sigma: f64, is_call: bo
 pub fn compute(data: Value) -> Result<Value, String> {
     let input: GreeksInput =
         serde_json::from_value(data).map_err(|e| format!("Invalid greeks input: {}", e))?;
+    let output = compute_typed(&input)?;
+    serde_json::to_value(output).map_err(|e| format!("Serialization error: {}", e))
+}
 
+pub fn compute_typed(input: &GreeksInput) -> Result<GreeksOutput, String> {
     let is_call = input.option_type.to_lowercase() == "call" || input.option_type.to_lowercase() == "ce";
 
     let s = input.spot;
@@ -118,8 +122,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
         return Err("Cannot compute Greeks: volatility is zero or negative and no market_price provided to solve IV".into());
     }
 
-    let output = compute_greeks_at_vol(s, k, r, t, sigma, is_call);
-    serde_json::to_value(output).map_err(|e| format!("Serialization error: {}", e))
+    Ok(compute_greeks_at_vol(s, k, r, t, sigma, is_call))
 }
 
 #[cfg(test)]
@@ -0,0 +1,416 @@
+//! Grid and dollar-cost-averaging backtests.
+//!
+//! Selected by `backtest` with `strategy` "grid" or "dca". Both hold many
+//! lots at once, each with its own entry and exit, so every closed lot is its
+//! own trade-log row and lots still held at the end are listed under
+//! `open_lots` at their unrealized P&L rather than force-closed.
+//!
+//! The grid places `levels` buy levels `spacing_pct` apart below `anchor`
+//! (the first close by default). A bar trading through an empty level buys
+//! `order_value` there, and that lot sells `take_profit_pct` above its level
+//! (one grid step by default), freeing the level to buy again.
+//!
+//! DCA buys `order_value` every `interval` bars (by default the capital spread
+//! evenly over the scheduled buys), plus an extra buy whenever the close is
+//! `dip_pct` below the last buy. With `take_profit_pct`, every lot is sold
+//! once the close is that far above the average cost.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::{build_costs, CostConfig, EquityPoint};
+use crate::utils::{parse_timestamp, round2, round4, Candle, TransactionCosts};
+
+#[derive(Deserialize)]
+struct GridConfig {
+    strategy: String,
+    #[serde(default)]
+    symbol: String,
+    initial_capital: f64,
+    candles: Vec<Candle>,
+    #[serde(default)]
+    transaction_costs: Option<CostConfig>,
+    #[serde(default)]
+    params: GridParams,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct GridParams {
+    /// Currency per buy; default depends on the strategy (see module docs).
+    order_value: Option<f64>,
+    /// Buy fractional units (crypto); whole units otherwise.
+    fractional: bool,
+    take_profit_pct: Option<f64>,
+    // Grid
+    anchor: Option<f64>,
+    spacing_pct: f64,
+    levels: usize,
+    // DCA
+    interval: usize,
+    dip_pct: Option<f64>,
+}
+
+impl Default for GridParams {
+    fn default() -> Self {
+        Self {
+            order_value: None,
+            fractional: false,
+            take_profit_pct: None,
+            anchor: None,
+            spacing_pct: 1.0,
+            levels: 5,
+            interval: 20,
+            dip_pct: None,
+        }
+    }
+}
+
+struct Lot {
+    id: usize,
+    level: Option<usize>,
+    entry_price: f64,
+    qty: f64,
+    entry_cost: f64,
+    entry_time: String,
+}
+
+#[derive(Serialize)]
+struct LotTrade {
+    lot_id: usize,
+    /// Grid level (1 = nearest below the anchor); absent for DCA.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level: Option<usize>,
+    entry_time: String,
+    exit_time: String,
+    entry_price: f64,
+    exit_price: f64,
+    qty: f64,
+    gross_pnl: f64,
+    /// Entry and exit costs of this lot.
+    costs: f64,
+    pnl: f64,
+}
+
+#[derive(Serialize)]
+struct OpenLot {
+    lot_id: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    level: Option<usize>,
+    entry_time: String,
+    entry_price: f64,
+    qty: f64,
+    unrealized_pnl: f64,
+}
+
+#[derive(Serialize)]
+struct GridResult {
+    strategy: String,
+    symbol: String,
+    total_return_pct: f64,
+    cagr: f64,
+    max_drawdown: f64,
+    buy_and_hold_return_pct: f64,
+    /// Closed lots.
+    total_trades: usize,
+    win_rate: f64,
+    buys: usize,
+    /// Buys skipped for lack of cash.
+    skipped_buys: usize,
+    max_open_lots: usize,
+    realized_pnl: f64,
+    unrealized_pnl: f64,
+    total_costs: f64,
+    final_cash: f64,
+    final_units: f64,
+    /// Volume-weighted entry price of the open lots.
+    avg_open_cost: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    grid_levels: Vec<f64>,
+    equity_curve: Vec<EquityPoint>,
+    trade_log: Vec<LotTrade>,
+    open_lots: Vec<OpenLot>,
+}
+
+/// Cash, open lots and the running tallies shared by both strategies.
+struct Book {
+    costs: TransactionCosts,
+    fractional: bool,
+    cash: f64,
+    lots: Vec<Lot>,
+    next_id: usize,
+    buys: usize,
+    skipped_buys: usize,
+    total_costs: f64,
+    trades: Vec<LotTrade>,
+}
+
+impl Book {
+    /// Buy `value` worth at `price`; false when cash can't cover it.
+    fn buy(&mut self, price: f64, value: f64, level: Option<usize>, time: &str) -> bool {
+        let fill = self.costs.slippage_adjusted_price(price, true);
+        let qty = if self.fractional { value / fill } else { (value / fill).floor() };
+        let notional = qty * fill;
+        let cost = self.costs.total_cost(notional, false);
+        if qty <= 0.0 || notional + cost > self.cash {
+            self.skipped_buys += 1;
+            return false;
+        }
+        self.cash -= notional + cost;
+        self.total_costs += cost;
+        self.buys += 1;
+        self.next_id += 1;
+        self.lots.push(Lot {
+            id: self.next_id, level, entry_price: fill, qty, entry_cost: cost, entry_time: time.to_string(),
+        });
+        true
+    }
+
+    fn sell(&mut self, lot: Lot, price: f64, time: &str) {
+        let fill = self.costs.slippage_adjusted_price(price, false);
+        let notional = lot.qty * fill;
+        let cost = self.costs.total_cost(notional, true);
+        self.cash += notional - cost;
+        self.total_costs += cost;
+        let gross = (fill - lot.entry_price) * lot.qty;
+        self.trades.push(LotTrade {
+            lot_id: lot.id,
+            level: lot.level,
+            entry_time: lot.entry_time,
+            exit_time: time.to_string(),
+            entry_price: round4(lot.entry_price),
+            exit_price: round4(fill),
+            qty: round4(lot.qty),
+            gross_pnl: round2(gross),
+            costs: round2(lot.entry_cost + cost),
+            pnl: round2(gross - lot.entry_cost - cost),
+        });
+    }
+
+    fn units(&self) -> f64 {
+        self.lots.iter().map(|l| l.qty).sum()
+    }
+}
+
+pub fn run(data: Value) -> Result<Value, String> {
+    let config: GridConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid backtest config: {}", e))?;
+    let is_grid = match config.strategy.as_str() {
+        "grid" => true,
+        "dca" => false,
+        other => return Err(format!("Unknown lot backtest strategy '{}' (grid, dca)", other)),
+    };
+    let p = &config.params;
+    let (Some(first), Some(last)) = (config.candles.first(), config.candles.last()) else {
+        return Err("grid and dca backtests need candles".to_string());
+    };
+    if config.initial_capital <= 0.0 {
+        return Err("initial_capital must be positive".to_string());
+    }
+    if p.order_value.is_some_and(|v| v <= 0.0) || p.take_profit_pct.is_some_and(|v| v <= 0.0) {
+        return Err("order_value and take_profit_pct must be positive".to_string());
+    }
+
+    let mut book = Book {
+        costs: build_costs(&config.transaction_costs),
+        fractional: p.fractional,
+        cash: config.initial_capital,
+        lots: Vec::new(),
+        next_id: 0,
+        buys: 0,
+        skipped_buys: 0,
+        total_costs: 0.0,
+        trades: Vec::new(),
+    };
+
+    let grid_levels: Vec<f64> = if is_grid {
+        let deepest = p.spacing_pct * p.levels as f64;
+        if !(p.spacing_pct > 0.0 && deepest < 100.0 && p.levels > 0) {
+            return Err("grid needs levels > 0 and spacing_pct > 0 with every level above zero".to_string());
+        }
+        let anchor = p.anchor.unwrap_or(first.close);
+        (1..=p.levels).map(|k| anchor * (1.0 - p.spacing_pct * k as f64 / 100.0)).collect()
+    } else {
+        if p.interval == 0 {
+            return Err("dca interval must be at least 1 bar".to_string());
+        }
+        Vec::new()
+    };
+    let order_value = p.order_value.unwrap_or(if is_grid {
+        config.initial_capital / p.levels as f64
+    } else {
+        config.initial_capital / config.candles.len().div_ceil(p.interval) as f64
+    });
+    let grid_tp = p.take_profit_pct.unwrap_or(p.spacing_pct) / 100.0;
+
+    let mut equity_curve = Vec::with_capacity(config.candles.len());
+    let mut peak = config.initial_capital;
+    let mut max_dd = 0.0_f64;
+    let mut max_open_lots = 0usize;
+    let mut last_buy: Option<f64> = None;
+
+    for (i, candle) in config.candles.iter().enumerate() {
+        let time = candle.timestamp.as_str();
+        if is_grid {
+            // Exits first, so a lot never round-trips within its entry bar.
+            let (hit, held): (Vec<Lot>, Vec<Lot>) = std::mem::take(&mut book.lots).into_iter().partition(|l| {
+                let target = grid_levels[l.level.unwrap_or(1) - 1] * (1.0 + grid_tp);
+                candle.high >= target
+            });
+            book.lots = held;
+            for lot in hit {
+                let target = grid_levels[lot.level.unwrap_or(1) - 1] * (1.0 + grid_tp);
+                book.sell(lot, target.max(candle.open), time);
+            }
+            for (k, &level) in grid_levels.iter().enumerate() {
+                let taken = book.lots.iter().any(|l| l.level == Some(k + 1));
+                if !taken && candle.low <= level {
+                    book.buy(level.min(candle.open), order_value, Some(k + 1), time);
+                }
+            }
+        } else {
+            if let Some(tp) = p.take_profit_pct {
+                let units = book.units();
+                let cost: f64 = book.lots.iter().map(|l| l.entry_price * l.qty).sum();
+                if units > 0.0 && candle.close >= cost / units * (1.0 + tp / 100.0) {
+                    for lot in std::mem::take(&mut book.lots) {
+                        book.sell(lot, candle.close, time);
+                    }
+                }
+            }
+            let scheduled = i % p.interval == 0;
+            let dip = matches!((p.dip_pct, last_buy), (Some(d), Some(prev)) if candle.close <= prev * (1.0 - d / 100.0));
+            if (scheduled || dip) && book.buy(candle.close, order_value, None, time) {
+                last_buy = Some(candle.close);
+            }
+        }
+        max_open_lots = max_open_lots.max(book.lots.len());
+
+        let nav = book.cash + book.units() * candle.close;
+        peak = peak.max(nav);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - nav) / peak);
+        }
+        equity_curve.push(EquityPoint { date: candle.timestamp.clone(), nav: round2(nav) });
+    }
+
+    let units = book.units();
+    let final_nav = book.cash + units * last.close;
+    let total_return = final_nav / config.initial_capital - 1.0;
+    let years = match (parse_timestamp(&first.timestamp), parse_timestamp(&last.timestamp)) {
+        (Some(a), Some(b)) => ((b.date() - a.date()).num_days() + 1) as f64 / 365.25,
+        _ => config.candles.len() as f64 / crate::calendar::DEFAULT_TRADING_DAYS,
+    };
+    let cagr = if years > 0.0 && total_return > -1.0 { (1.0 + total_return).powf(1.0 / years) - 1.0 } else { 0.0 };
+    let realized: f64 = book.trades.iter().map(|t| t.pnl).sum();
+    let wins = book.trades.iter().filter(|t| t.pnl > 0.0).count();
+    let open_lots: Vec<OpenLot> = book.lots.iter().map(|l| OpenLot {
+        lot_id: l.id,
+        level: l.level,
+        entry_time: l.entry_time.clone(),
+        entry_price: round4(l.entry_price),
+        qty: round4(l.qty),
+        unrealized_pnl: round2((last.close - l.entry_price) * l.qty - l.entry_cost),
+    }).collect();
+
+    let result = GridResult {
+        strategy: config.strategy,
+        symbol: config.symbol,
+        total_return_pct: round2(total_return * 100.0),
+        cagr: round2(cagr * 100.0),
+        max_drawdown: round2(max_dd * 100.0),
+        buy_and_hold_return_pct: round2(if first.close > 0.0 { (last.close / first.close - 1.0) * 100.0 } else { 0.0 }),
+        total_trades: book.trades.len(),
+        win_rate: if book.trades.is_empty() { 0.0 } else { round2(wins as f64 / book.trades.len() as f64 * 100.0) },
+        buys: book.buys,
+        skipped_buys: book.skipped_buys,
+        max_open_lots,
+        realized_pnl: round2(realized),
+        unrealized_pnl: round2(open_lots.iter().map(|l| l.unrealized_pnl).sum()),
+        total_costs: round2(book.total_costs),
+        final_cash: round2(book.cash),
+        final_units: round4(units),
+        avg_open_cost: (units > 0.0).then(|| round4(book.lots.iter().map(|l| l.entry_price * l.qty).sum::<f64>() / units)),
+        grid_levels: grid_levels.iter().map(|l| round4(*l)).collect(),
+        equity_curve,
+        trade_log: book.trades,
+        open_lots,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+    use serde_json::json;
+
+    fn daily(closes: &[f64]) -> Vec<Value> {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        closes.iter().enumerate().map(|(i, c)| json!({
+            "timestamp": (start + Duration::days(i as i64)).to_string(),
+            "open": c, "high": c, "low": c, "close": c, "volume": 1000.0
+        })).collect()
+    }
+
+    const NO_COSTS: fn() -> Value = || json!({ "commission": 0.0, "slippage_bps": 0.0, "stt_pct": 0.0 });
+
+    #[test]
+    fn test_grid_round_trips_each_level() {
+        // 100 → 97 → 100 → 97.5: levels 99/98/97 fill, sell at 100/99/98, then 99/98 refill.
+        let closes = [100.0, 99.0, 98.0, 97.0, 98.0, 99.0, 100.0, 99.0, 97.5];
+        let result = run(json!({
+            "strategy": "grid", "initial_capital": 30_000.0, "candles": daily(&closes),
+            "transaction_costs": NO_COSTS(),
+            "params": { "levels": 3, "spacing_pct": 1.0, "order_value": 9_000.0, "fractional": true },
+        })).unwrap();
+        assert_eq!(result["grid_levels"], json!([99.0, 98.0, 97.0]));
+        assert_eq!(result["buys"], 5);
+        let log = result["trade_log"].as_array().unwrap();
+        assert_eq!(log.len(), 3);
+        // Each lot exits on the first bar through its own target, at the open
+        // when the bar gaps past it.
+        for t in log {
+            let level = t["level"].as_u64().unwrap() as f64;
+            assert!((t["entry_price"].as_f64().unwrap() - (100.0 - level)).abs() < 1e-9);
+            assert!((t["exit_price"].as_f64().unwrap() - (101.0 - level)).abs() < 1e-9);
+            assert!(t["pnl"].as_f64().unwrap() > 0.0);
+        }
+        assert_eq!(result["max_open_lots"], 3);
+        let open = result["open_lots"].as_array().unwrap();
+        assert_eq!(open.len(), 2);
+        // Level 98 refills at the 97.5 open the bar gapped down to.
+        assert_eq!(result["final_units"].as_f64().unwrap(), round4(9_000.0 / 99.0 + 9_000.0 / 97.5));
+        assert!(run(json!({ "strategy": "grid", "initial_capital": 1.0, "candles": daily(&closes),
+            "params": { "levels": 200, "spacing_pct": 1.0 } })).is_err());
+    }
+
+    #[test]
+    fn test_dca_buys_on_schedule_and_takes_profit() {
+        // Falls from 100 to 80 over 40 bars, then recovers to 100.
+        let closes: Vec<f64> = (0..60).map(|i| if i < 40 { 100.0 - i as f64 * 0.5 } else { 80.0 + (i - 39) as f64 }).collect();
+        let dca = |params: Value| run(json!({
+            "strategy": "dca", "initial_capital": 60_000.0, "candles": daily(&closes),
+            "transaction_costs": NO_COSTS(), "params": params,
+        })).unwrap();
+
+        let plain = dca(json!({ "interval": 10 }));
+        // Capital spread over the 6 scheduled buys, whole units only.
+        assert_eq!(plain["buys"], 6);
+        assert_eq!(plain["total_trades"], 0);
+        assert_eq!(plain["open_lots"].as_array().unwrap().len(), 6);
+        let avg = plain["avg_open_cost"].as_f64().unwrap();
+        assert!(avg < 100.0 && avg > 80.0);
+        assert!(plain["total_return_pct"].as_f64().unwrap() > plain["buy_and_hold_return_pct"].as_f64().unwrap());
+
+        let tp = dca(json!({ "interval": 10, "order_value": 5_000.0, "take_profit_pct": 5.0, "dip_pct": 3.0 }));
+        // Dip buys between the scheduled ones while the price slides.
+        assert!(tp["buys"].as_u64().unwrap() > 6);
+        let log = tp["trade_log"].as_array().unwrap();
+        assert!(!log.is_empty());
+        // Every lot closes on the same bar, each with its own entry.
+        let exit = &log[0]["exit_time"];
+        assert!(log.iter().all(|t| &t["exit_time"] == exit));
+        let ids: std::collections::HashSet<u64> = log.iter().map(|t| t["lot_id"].as_u64().unwrap()).collect();
+        assert_eq!(ids.len(), log.len());
+    }
+}
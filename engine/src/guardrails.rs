@@ -0,0 +1,191 @@
+//! Input size guardrails.
+//!
+//! Checked before dispatch so a 5M-candle or 100k-combo request fails fast
+//! with a structured error, or — with `on_exceed = "degrade"` — is reduced
+//! to fit: candles are merged into coarser OHLCV bars, symbol lists are
+//! truncated and parameter grids are thinned, each with a warning.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::config::LimitsConfig;
+
+/// A limit that was exceeded while `on_exceed = "error"`.
+#[derive(Serialize, Debug)]
+pub struct LimitExceeded {
+    pub limit: &'static str,
+    pub max: usize,
+    pub actual: usize,
+    /// Where in the payload, e.g. "candles" or "symbols[3].candles".
+    pub path: String,
+}
+
+impl LimitExceeded {
+    pub fn message(&self) -> String {
+        format!("{} exceeds {}: {} > {} (set limits.on_exceed = \"degrade\" to downsample instead)",
+            self.path, self.limit, self.actual, self.max)
+    }
+}
+
+/// Enforce `limits` on `data` in place. Returns degradation warnings.
+pub fn apply(data: &mut Value, limits: &LimitsConfig, degrade: bool) -> Result<Vec<String>, LimitExceeded> {
+    let mut warnings = Vec::new();
+    let Some(map) = data.as_object_mut() else { return Ok(warnings) };
+
+    check_candles(map, "candles", limits.max_candles, degrade, &mut warnings)?;
+
+    if let Some(Value::Array(symbols)) = map.get_mut("symbols") {
+        if limits.max_symbols > 0 && symbols.len() > limits.max_symbols {
+            if !degrade {
+                return Err(LimitExceeded {
+                    limit: "max_symbols", max: limits.max_symbols, actual: symbols.len(), path: "symbols".into(),
+                });
+            }
+            warnings.push(format!("symbols truncated from {} to {}", symbols.len(), limits.max_symbols));
+            symbols.truncate(limits.max_symbols);
+        }
+        for (i, sym) in symbols.iter_mut().enumerate() {
+            if let Some(obj) = sym.as_object_mut() {
+                let path = format!("symbols[{}].candles", i);
+                check_candles(obj, &path, limits.max_candles, degrade, &mut warnings)?;
+            }
+        }
+    }
+
+    if let Some(Value::Object(grid)) = map.get_mut("param_grid") {
+        let combos = grid_size(grid);
+        if limits.max_param_combos > 0 && combos > limits.max_param_combos {
+            if !degrade {
+                return Err(LimitExceeded {
+                    limit: "max_param_combos", max: limits.max_param_combos, actual: combos, path: "param_grid".into(),
+                });
+            }
+            thin_grid(grid, limits.max_param_combos);
+            warnings.push(format!("param_grid thinned from {} to {} combinations", combos, grid_size(grid)));
+        }
+    }
+    Ok(warnings)
+}
+
+fn check_candles(
+    obj: &mut Map<String, Value>,
+    path: &str,
+    max: usize,
+    degrade: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(), LimitExceeded> {
+    let Some(Value::Array(candles)) = obj.get_mut("candles") else { return Ok(()) };
+    if max == 0 || candles.len() <= max {
+        return Ok(());
+    }
+    if !degrade {
+        return Err(LimitExceeded { limit: "max_candles", max, actual: candles.len(), path: path.to_string() });
+    }
+    let factor = candles.len().div_ceil(max);
+    let before = candles.len();
+    *candles = downsample(candles, factor);
+    warnings.push(format!("{} downsampled {}x from {} to {} bars", path, factor, before, candles.len()));
+    Ok(())
+}
+
+/// Merge every `factor` consecutive bars into one OHLCV bar stamped with the
+/// first bar's timestamp.
+fn downsample(candles: &[Value], factor: usize) -> Vec<Value> {
+    let num = |c: &Value, k: &str| c.get(k).and_then(|v| v.as_f64());
+    candles.chunks(factor).map(|chunk| {
+        let first = &chunk[0];
+        let last = &chunk[chunk.len() - 1];
+        let close = num(last, "close").unwrap_or(0.0);
+        let high = chunk.iter().filter_map(|c| num(c, "high")).fold(f64::NEG_INFINITY, f64::max);
+        let low = chunk.iter().filter_map(|c| num(c, "low")).fold(f64::INFINITY, f64::min);
+        serde_json::json!({
+            "timestamp": first.get("timestamp").cloned().unwrap_or(Value::Null),
+            "open": num(first, "open").unwrap_or(close),
+            "high": if high.is_finite() { high } else { close },
+            "low": if low.is_finite() { low } else { close },
+            "close": close,
+            "volume": chunk.iter().filter_map(|c| num(c, "volume")).sum::<f64>(),
+        })
+    }).collect()
+}
+
+fn grid_size(grid: &Map<String, Value>) -> usize {
+    grid.values()
+        .map(|v| v.as_array().map_or(1, |a| a.len().max(1)))
+        .fold(1usize, |acc, n| acc.saturating_mul(n))
+}
+
+/// Halve the longest dimension (keeping both endpoints) until the grid fits.
+fn thin_grid(grid: &mut Map<String, Value>, max: usize) {
+    while grid_size(grid) > max {
+        let longest = grid.iter()
+            .filter_map(|(k, v)| v.as_array().map(|a| (k.clone(), a.len())))
+            .max_by_key(|(_, n)| *n);
+        let Some((key, len)) = longest else { return };
+        if len <= 2 {
+            return;
+        }
+        if let Some(Value::Array(values)) = grid.get_mut(&key) {
+            let last = values[len - 1].clone();
+            let mut kept: Vec<Value> = values.iter().step_by(2).cloned().collect();
+            if (len - 1) % 2 != 0 {
+                kept.push(last);
+            }
+            *values = kept;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn limits(max_candles: usize, max_symbols: usize, max_param_combos: usize) -> LimitsConfig {
+        LimitsConfig { max_candles, max_symbols, max_param_combos, on_exceed: "error".into(), ..LimitsConfig::default() }
+    }
+
+    fn candles(n: usize) -> Vec<Value> {
+        (0..n).map(|i| json!({
+            "timestamp": format!("t{:04}", i), "open": i as f64, "high": i as f64 + 1.0,
+            "low": i as f64 - 1.0, "close": i as f64 + 0.5, "volume": 10.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_error_mode_reports_limit() {
+        let mut data = json!({ "symbols": [{ "symbol": "A", "candles": candles(50) }] });
+        let err = apply(&mut data, &limits(20, 10, 10), false).unwrap_err();
+        assert_eq!(err.limit, "max_candles");
+        assert_eq!(err.path, "symbols[0].candles");
+        assert_eq!(err.actual, 50);
+    }
+
+    #[test]
+    fn test_degrade_downsamples_candles() {
+        let mut data = json!({ "candles": candles(10) });
+        let warnings = apply(&mut data, &limits(4, 0, 0), true).unwrap();
+        let out = data["candles"].as_array().unwrap();
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0]["timestamp"], "t0000");
+        assert_eq!(out[0]["open"].as_f64().unwrap(), 0.0);
+        assert_eq!(out[0]["high"].as_f64().unwrap(), 3.0);
+        assert_eq!(out[0]["close"].as_f64().unwrap(), 2.5);
+        assert_eq!(out[0]["volume"].as_f64().unwrap(), 30.0);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_degrade_thins_grid_and_truncates_symbols() {
+        let mut data = json!({
+            "symbols": [{}, {}, {}],
+            "param_grid": { "a": [1, 2, 3, 4, 5, 6, 7, 8, 9], "b": [1, 2, 3] }
+        });
+        let warnings = apply(&mut data, &limits(0, 2, 10), true).unwrap();
+        assert_eq!(data["symbols"].as_array().unwrap().len(), 2);
+        let a = data["param_grid"]["a"].as_array().unwrap();
+        assert_eq!(a.first().unwrap(), 1);
+        assert_eq!(a.last().unwrap(), 9);
+        assert!(grid_size(data["param_grid"].as_object().unwrap()) <= 10);
+        assert_eq!(warnings.len(), 2);
+    }
+}
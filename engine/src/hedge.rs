@@ -0,0 +1,280 @@
+//! Beta-hedge overlay for backtest equity curves.
+//!
+//! At each bar close the open position is offset with a short index
+//! futures position of `beta × exposure`, where beta is the rolling
+//! regression of the symbol's bar returns on the index's over the last
+//! `lookback` returns (known at that close — no look-ahead). The hedge is
+//! held to the next close, so the hedged curve is the raw curve plus the
+//! index overlay's P&L less rebalance costs: what is left is the
+//! strategy's return net of market direction.
+//!
+//! Index bars are joined to the backtest's bars as of each timestamp (the
+//! last index bar at or before it); bars before the first index bar use its
+//! close. `candles_file` works in place of `candles` like anywhere else.
+
+use serde::{Deserialize, Serialize};
+use crate::backtest::EquityPoint;
+use crate::rolling_beta::rolling;
+use crate::utils::{parse_timestamp, round2, round4, Candle};
+use crate::vol_target::{daily_closes, stats, years, CurveStats};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct HedgeSpec {
+    /// Index the overlay trades, e.g. "NIFTY"; reporting only.
+    #[serde(default)]
+    pub symbol: Option<String>,
+    pub candles: Vec<Candle>,
+    /// Returns in the rolling beta window.
+    #[serde(default = "default_lookback")]
+    pub lookback: usize,
+    /// Beta used until the window fills.
+    #[serde(default = "default_beta")]
+    pub default_beta: f64,
+    /// Index units per futures contract; the hedge is rounded to whole
+    /// contracts when set and held as exact notional otherwise.
+    #[serde(default)]
+    pub lot_size: Option<f64>,
+    /// Charged on the notional traded at each rebalance.
+    #[serde(default)]
+    pub cost_bps: f64,
+}
+
+fn default_lookback() -> usize { 60 }
+fn default_beta() -> f64 { 1.0 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HedgePoint {
+    pub date: String,
+    pub raw_nav: f64,
+    pub hedged_nav: f64,
+    /// Beta the hedge was sized with at this close.
+    pub beta: f64,
+    /// Signed index notional held from this close (negative = short).
+    pub hedge_notional: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contracts: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HedgeResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    pub lookback: usize,
+    pub raw: CurveStats,
+    pub hedged: CurveStats,
+    /// Realized beta of each curve's daily returns to the index.
+    pub raw_beta: f64,
+    pub hedged_beta: f64,
+    /// Mean hedge beta over bars with an open position.
+    pub avg_beta: f64,
+    pub hedge_pnl: f64,
+    pub hedge_costs: f64,
+    /// Backtest bars with no index bar at the same timestamp.
+    pub stale_index_bars: usize,
+    pub curve: Vec<HedgePoint>,
+}
+
+/// Apply the overlay. `exposures[i]` is the signed market value held from
+/// bar i's close; `candles` are the backtest's own bars.
+pub(crate) fn overlay(
+    curve: &[EquityPoint],
+    exposures: &[f64],
+    candles: &[Candle],
+    spec: &HedgeSpec,
+    trading_days: f64,
+    bars_per_day: f64,
+) -> Result<HedgeResult, String> {
+    if spec.lookback < 2 {
+        return Err("hedge.lookback must be at least 2".to_string());
+    }
+    if !spec.default_beta.is_finite() {
+        return Err("hedge.default_beta must be finite".to_string());
+    }
+    if spec.lot_size.is_some_and(|l| !(l.is_finite() && l > 0.0)) {
+        return Err("hedge.lot_size must be positive".to_string());
+    }
+    if !(spec.cost_bps.is_finite() && spec.cost_bps >= 0.0) {
+        return Err("hedge.cost_bps must be non-negative".to_string());
+    }
+    let (index, stale_index_bars) = index_closes(candles, &spec.candles)?;
+
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    let betas = rolling(&closes, &index, spec.lookback).beta;
+    let mut out: Vec<HedgePoint> = Vec::with_capacity(curve.len());
+    let (mut held, mut pnl, mut costs) = (0.0, 0.0, 0.0);
+    let (mut beta_sum, mut beta_bars) = (0.0, 0usize);
+    for (i, point) in curve.iter().enumerate() {
+        if i > 0 {
+            let move_pct = index[i] / index[i - 1] - 1.0;
+            pnl += held * move_pct;
+            held *= 1.0 + move_pct;
+        }
+        let exposure = exposures.get(i).copied().unwrap_or(0.0);
+        let beta = betas.get(i).copied().flatten().unwrap_or(spec.default_beta);
+        let target = -beta * exposure;
+        let (next, contracts) = match spec.lot_size {
+            Some(lot) => {
+                let n = (target / (index[i] * lot)).round();
+                (n * index[i] * lot, Some(n as i64))
+            }
+            None => (target, None),
+        };
+        costs += (next - held).abs() * spec.cost_bps / 10_000.0;
+        held = next;
+        if exposure != 0.0 {
+            beta_sum += beta;
+            beta_bars += 1;
+        }
+        out.push(HedgePoint {
+            date: point.date.clone(),
+            raw_nav: point.nav,
+            hedged_nav: round2(point.nav + pnl - costs),
+            beta: round4(beta),
+            hedge_notional: round2(held),
+            contracts,
+        });
+    }
+
+    let hedged_curve: Vec<EquityPoint> = out.iter()
+        .map(|p| EquityPoint { date: p.date.clone(), nav: p.hedged_nav })
+        .collect();
+    let index_curve: Vec<EquityPoint> = curve.iter().zip(&index)
+        .map(|(p, &close)| EquityPoint { date: p.date.clone(), nav: close })
+        .collect();
+    let raw_days = daily_closes(curve, bars_per_day);
+    let hedged_days = daily_closes(&hedged_curve, bars_per_day);
+    let index_days = daily_closes(&index_curve, bars_per_day);
+    let raw_returns = daily_returns(&raw_days);
+    let hedged_returns = daily_returns(&hedged_days);
+    let index_returns = daily_returns(&index_days);
+    let years = years(&raw_days, trading_days);
+
+    Ok(HedgeResult {
+        symbol: spec.symbol.clone(),
+        lookback: spec.lookback,
+        raw: stats(&raw_returns, years, trading_days),
+        hedged: stats(&hedged_returns, years, trading_days),
+        raw_beta: round4(beta_of(&raw_returns, &index_returns)),
+        hedged_beta: round4(beta_of(&hedged_returns, &index_returns)),
+        avg_beta: if beta_bars > 0 { round4(beta_sum / beta_bars as f64) } else { 0.0 },
+        hedge_pnl: round2(pnl),
+        hedge_costs: round2(costs),
+        stale_index_bars,
+        curve: out,
+    })
+}
+
+/// Index close as of each of `candles`' timestamps, and how many of them
+/// had no index bar at exactly that time.
+fn index_closes(candles: &[Candle], index: &[Candle]) -> Result<(Vec<f64>, usize), String> {
+    let mut bars = index.iter()
+        .map(|c| parse_timestamp(&c.timestamp)
+            .map(|ts| (ts, c.close))
+            .ok_or_else(|| format!("hedge: invalid index timestamp '{}'", c.timestamp)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if bars.iter().any(|(_, close)| !(close.is_finite() && *close > 0.0)) {
+        return Err("hedge: index closes must be positive".to_string());
+    }
+    bars.sort_by_key(|(ts, _)| *ts);
+    let Some(&(_, first)) = bars.first() else {
+        return Err("hedge.candles is empty".to_string());
+    };
+
+    let mut out = Vec::with_capacity(candles.len());
+    let (mut next, mut close, mut stale) = (0usize, first, 0usize);
+    for c in candles {
+        let ts = parse_timestamp(&c.timestamp)
+            .ok_or_else(|| format!("hedge: invalid timestamp '{}'", c.timestamp))?;
+        while next < bars.len() && bars[next].0 <= ts {
+            close = bars[next].1;
+            next += 1;
+        }
+        if next == 0 || bars[next - 1].0 != ts {
+            stale += 1;
+        }
+        out.push(close);
+    }
+    Ok((out, stale))
+}
+
+fn daily_returns(days: &[(String, f64)]) -> Vec<f64> {
+    days.windows(2).map(|w| if w[0].1 > 0.0 { w[1].1 / w[0].1 - 1.0 } else { 0.0 }).collect()
+}
+
+/// Full-sample OLS beta of `y` on `x`; 0 when `x` has no variance.
+pub(crate) fn beta_of(y: &[f64], x: &[f64]) -> f64 {
+    let n = y.len().min(x.len());
+    if n < 2 {
+        return 0.0;
+    }
+    let (mx, my) = (x[..n].iter().sum::<f64>() / n as f64, y[..n].iter().sum::<f64>() / n as f64);
+    let cov: f64 = x[..n].iter().zip(&y[..n]).map(|(a, b)| (a - mx) * (b - my)).sum();
+    let var: f64 = x[..n].iter().map(|a| (a - mx).powi(2)).sum();
+    if var > 1e-18 { cov / var } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(closes: &[f64]) -> Vec<Candle> {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        closes.iter().enumerate().map(|(i, &close)| Candle {
+            timestamp: (start + chrono::Duration::days(i as i64)).to_string(),
+            open: close, high: close, low: close, close, volume: 1_000.0,
+        }).collect()
+    }
+
+    #[test]
+    fn test_hedge_removes_market_beta() {
+        // The stock moves 1.5× the index plus a steady 0.1% a day of alpha.
+        let index: Vec<f64> = (0..120).map(|i| 20_000.0 * (1.0 + 0.01 * (i as f64 * 0.7).sin())).collect();
+        let mut stock = vec![500.0];
+        for w in index.windows(2) {
+            let last = *stock.last().unwrap();
+            stock.push(last * (1.0 + 1.5 * (w[1] / w[0] - 1.0) + 0.001));
+        }
+        let candles = bars(&stock);
+        // Fully invested from the first close: 200 shares.
+        let curve: Vec<EquityPoint> = candles.iter()
+            .map(|c| EquityPoint { date: c.timestamp.clone(), nav: 200.0 * c.close })
+            .collect();
+        let exposures: Vec<f64> = curve.iter().map(|p| p.nav).collect();
+        let spec = HedgeSpec {
+            symbol: Some("NIFTY".into()), candles: bars(&index), lookback: 20,
+            default_beta: 1.0, lot_size: None, cost_bps: 0.0,
+        };
+        let r = overlay(&curve, &exposures, &candles, &spec, 252.0, 1.0).unwrap();
+        assert!((r.curve.last().unwrap().beta - 1.5).abs() < 0.01);
+        assert!(r.raw_beta > 1.2, "raw beta {}", r.raw_beta);
+        assert!(r.hedged_beta.abs() < 0.3, "hedged beta {}", r.hedged_beta);
+        assert!(r.hedged.ann_vol_pct < r.raw.ann_vol_pct / 2.0);
+        assert!(r.hedged.total_return_pct > 0.0);
+        assert_eq!(r.stale_index_bars, 0);
+        assert_eq!(r.curve[0].hedged_nav, r.curve[0].raw_nav);
+
+        // 25-unit contracts on a ₹20 lakh book: a few lots short.
+        let lots = HedgeSpec { lot_size: Some(25.0), cost_bps: 5.0, ..spec.clone() };
+        let book: Vec<f64> = exposures.iter().map(|e| e * 20.0).collect();
+        let r = overlay(&curve, &book, &candles, &lots, 252.0, 1.0).unwrap();
+        let p = &r.curve[30];
+        assert_eq!(p.hedge_notional, round2(p.contracts.unwrap() as f64 * 25.0 * index[30]));
+        assert!(p.contracts.unwrap() < 0);
+        assert!(r.hedge_costs > 0.0);
+
+        let bad = HedgeSpec { lookback: 1, ..spec };
+        assert!(overlay(&curve, &exposures, &candles, &bad, 252.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_index_joined_as_of_each_bar() {
+        let candles = bars(&[100.0, 101.0, 102.0, 103.0]);
+        // Index is missing the third day.
+        let mut index = bars(&[10.0, 11.0, 12.0, 13.0]);
+        index.remove(2);
+        let (closes, stale) = index_closes(&candles, &index).unwrap();
+        assert_eq!(closes, vec![10.0, 11.0, 11.0, 13.0]);
+        assert_eq!(stale, 1);
+        assert!(index_closes(&candles, &[]).is_err());
+    }
+}
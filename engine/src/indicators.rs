@@ -0,0 +1,709 @@
+//! Typed indicator APIs shared by every command.
+//!
+//! `signals`, `scan`, `multi_timeframe`, `feature_export`, `advanced_signals`
+//! and the backtest's `strategy::Indicators` all compute from here, in
+//! memory, so the same candles give the same numbers everywhere and nobody
+//! round-trips through JSON. The moving-average kernels behind these live in
+//! `kernels`.
+
+use serde::{Deserialize, Serialize};
+use crate::utils::{session_starts, Candle};
+
+pub use crate::kernels::{atr, ema, rolling_mean_std, rsi, sma};
+
+/// The default `signals` set. Warm-up NaNs are replaced with 0 so the
+/// series serialize as JSON and compare the same way in every consumer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Standard {
+    pub ema_9: Vec<f64>,
+    pub ema_21: Vec<f64>,
+    pub rsi_14: Vec<f64>,
+    pub macd: Vec<f64>,
+    pub macd_signal: Vec<f64>,
+    pub macd_histogram: Vec<f64>,
+    pub bollinger_upper: Vec<f64>,
+    pub bollinger_lower: Vec<f64>,
+    pub bollinger_middle: Vec<f64>,
+    pub vwap: Vec<f64>,
+    pub supertrend: Vec<f64>,
+}
+
+impl Standard {
+    /// Every series by name, in name order.
+    pub fn columns(self) -> Vec<(&'static str, Vec<f64>)> {
+        let mut cols = vec![
+            ("ema_9", self.ema_9),
+            ("ema_21", self.ema_21),
+            ("rsi_14", self.rsi_14),
+            ("macd", self.macd),
+            ("macd_signal", self.macd_signal),
+            ("macd_histogram", self.macd_histogram),
+            ("bollinger_upper", self.bollinger_upper),
+            ("bollinger_lower", self.bollinger_lower),
+            ("bollinger_middle", self.bollinger_middle),
+            ("vwap", self.vwap),
+            ("supertrend", self.supertrend),
+        ];
+        cols.sort_by_key(|c| c.0);
+        cols
+    }
+}
+
+/// Compute the `Standard` set. Candles are taken as given; callers that
+/// accept raw input run `sanitize_candles` first.
+pub fn standard(candles: &[Candle]) -> Standard {
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    let highs: Vec<f64> = candles.iter().map(|c| c.high).collect();
+    let lows: Vec<f64> = candles.iter().map(|c| c.low).collect();
+    let volumes: Vec<f64> = candles.iter().map(|c| c.volume).collect();
+
+    let (macd_line, macd_signal, macd_histogram) = macd(&closes, 12, 26, 9);
+    let (bollinger_upper, bollinger_lower, bollinger_middle) = bollinger(&closes, 20, 2.0);
+    Standard {
+        ema_9: nan_to_zero(&ema(&closes, 9)),
+        ema_21: nan_to_zero(&ema(&closes, 21)),
+        rsi_14: rsi(&closes, 14),
+        macd: nan_to_zero(&macd_line),
+        macd_signal: nan_to_zero(&macd_signal),
+        macd_histogram: nan_to_zero(&macd_histogram),
+        bollinger_upper,
+        bollinger_lower,
+        bollinger_middle,
+        vwap: session_vwap(&highs, &lows, &closes, &volumes, &session_starts(candles)),
+        supertrend: supertrend(&highs, &lows, &closes, 10, 3.0),
+    }
+}
+
+/// Replace NaN with 0.0 for JSON serialization (NaN is not valid JSON)
+pub fn nan_to_zero(data: &[f64]) -> Vec<f64> {
+    data.iter().map(|&v| if v.is_nan() { 0.0 } else { v }).collect()
+}
+
+/// MACD line, signal and histogram; NaN until each is defined.
+pub fn macd(data: &[f64], fast: usize, slow: usize, signal_period: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let ema_fast = ema(data, fast);
+    let ema_slow = ema(data, slow);
+    let mut macd_line = vec![f64::NAN; data.len()];
+    for i in 0..data.len() {
+        let a = ema_fast[i];
+        let b = ema_slow[i];
+        macd_line[i] = if a.is_nan() || b.is_nan() { f64::NAN } else { a - b };
+    }
+    let clean_macd: Vec<f64> = macd_line.iter().map(|&v| if v.is_nan() { 0.0 } else { v }).collect();
+    let signal = ema(&clean_macd, signal_period);
+    let mut histogram = vec![f64::NAN; data.len()];
+    for i in 0..data.len() {
+        let m = macd_line[i];
+        let s = signal[i];
+        histogram[i] = if m.is_nan() || s.is_nan() { f64::NAN } else { m - s };
+    }
+    (macd_line, signal, histogram)
+}
+
+/// Volume-weighted MACD `(line, signal, histogram)`: the MACD of `fast`- and
+/// `slow`-bar volume-weighted averages of the close, so moves on heavy volume
+/// count for more than drift on thin volume; NaN until each is defined. The
+/// signal EMA starts at the line's first value, not at zero.
+pub fn vw_macd(closes: &[f64], volumes: &[f64], fast: usize, slow: usize, signal_period: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let n = closes.len();
+    let (fast_ma, slow_ma) = (vwma(closes, volumes, fast), vwma(closes, volumes, slow));
+    let line: Vec<f64> = fast_ma.iter().zip(&slow_ma).map(|(a, b)| a - b).collect();
+    let mut signal = vec![f64::NAN; n];
+    let mut histogram = vec![f64::NAN; n];
+    if let Some(start) = line.iter().position(|v| !v.is_nan()) {
+        for (k, s) in ema(&line[start..], signal_period).into_iter().enumerate() {
+            signal[start + k] = s;
+            histogram[start + k] = line[start + k] - s;
+        }
+    }
+    (line, signal, histogram)
+}
+
+/// Volume-weighted moving average of `closes`; the plain average over a
+/// window without volume, NaN before the first full window.
+fn vwma(closes: &[f64], volumes: &[f64], period: usize) -> Vec<f64> {
+    let mut out = vec![f64::NAN; closes.len()];
+    if period == 0 {
+        return out;
+    }
+    for (i, slot) in out.iter_mut().enumerate().skip(period - 1) {
+        let window = i + 1 - period..=i;
+        let vol: f64 = volumes[window.clone()].iter().sum();
+        *slot = if vol > 0.0 {
+            window.map(|j| closes[j] * volumes[j]).sum::<f64>() / vol
+        } else {
+            closes[window].iter().sum::<f64>() / period as f64
+        };
+    }
+    out
+}
+
+/// Elder Impulse colour per bar: 1 (green) when both the 13-bar EMA and the
+/// 12/26/9 MACD histogram rose on the bar, -1 (red) when both fell, 0 (blue)
+/// when they disagree or are not yet defined.
+pub fn elder_impulse(closes: &[f64]) -> Vec<f64> {
+    let trend = ema(closes, 13);
+    let (_, _, histogram) = macd(closes, 12, 26, 9);
+    (0..closes.len()).map(|i| {
+        if i == 0 {
+            return 0.0;
+        }
+        let (de, dh) = (trend[i] - trend[i - 1], histogram[i] - histogram[i - 1]);
+        if de > 0.0 && dh > 0.0 {
+            1.0
+        } else if de < 0.0 && dh < 0.0 {
+            -1.0
+        } else {
+            0.0
+        }
+    }).collect()
+}
+
+/// Bollinger bands `(upper, lower, middle)` at `mult` population standard
+/// deviations; 0 before the first full window.
+pub fn bollinger(data: &[f64], period: usize, mult: f64) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let n = data.len();
+    let mut upper = vec![0.0; n];
+    let mut lower = vec![0.0; n];
+    let (middle, std_dev) = rolling_mean_std(data, period);
+    for i in period.saturating_sub(1)..n {
+        upper[i] = middle[i] + mult * std_dev[i];
+        lower[i] = middle[i] - mult * std_dev[i];
+    }
+    (upper, lower, middle)
+}
+
+/// `(upper, lower, middle)` band series.
+pub type Channel = (Vec<f64>, Vec<f64>, Vec<f64>);
+
+/// Rolling quantile channel `(upper, lower, middle)`: the `upper_q` and
+/// `lower_q` quantiles of the last `period` values (linearly interpolated)
+/// and their median; 0 before the first full window. Unlike Bollinger bands
+/// the two sides need not be the same distance from the middle, which suits
+/// skewed intraday distributions.
+pub fn quantile_channel(data: &[f64], period: usize, lower_q: f64, upper_q: f64) -> Channel {
+    rolling_channel(data, period, |window| {
+        let mut sorted = window.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        (quantile_sorted(&sorted, upper_q), quantile_sorted(&sorted, lower_q), quantile_sorted(&sorted, 0.5))
+    })
+}
+
+/// Rolling expectile channel `(upper, lower, middle)`: the asymmetric
+/// least-squares counterparts of [`quantile_channel`], so every value in the
+/// window pulls on the bands by its distance, not just its rank. The middle
+/// is the 0.5 expectile, the window mean.
+pub fn expectile_channel(data: &[f64], period: usize, lower_tau: f64, upper_tau: f64) -> Channel {
+    rolling_channel(data, period, |window| {
+        (expectile(window, upper_tau), expectile(window, lower_tau), window.iter().sum::<f64>() / window.len() as f64)
+    })
+}
+
+/// Which channel to compute and its window, as taken by `signals`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelSpec {
+    /// "quantile" or "expectile".
+    #[serde(default = "default_channel_method")]
+    pub method: String,
+    #[serde(default = "default_channel_period")]
+    pub period: usize,
+    #[serde(default = "default_channel_lower")]
+    pub lower: f64,
+    #[serde(default = "default_channel_upper")]
+    pub upper: f64,
+}
+
+fn default_channel_method() -> String { "quantile".to_string() }
+fn default_channel_period() -> usize { 20 }
+fn default_channel_lower() -> f64 { 0.1 }
+fn default_channel_upper() -> f64 { 0.9 }
+
+impl ChannelSpec {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.period < 2 {
+            return Err("channel period must be at least 2".to_string());
+        }
+        if !(0.0 <= self.lower && self.lower < self.upper && self.upper <= 1.0) {
+            return Err(format!("channel levels need 0 <= lower < upper <= 1, got {} and {}", self.lower, self.upper));
+        }
+        if !matches!(self.method.as_str(), "quantile" | "expectile") {
+            return Err(format!("Unknown channel method '{}' (expected quantile or expectile)", self.method));
+        }
+        Ok(())
+    }
+
+    /// `(upper, lower, middle)` for `data`.
+    pub fn compute(&self, data: &[f64]) -> Result<Channel, String> {
+        self.validate()?;
+        Ok(if self.method == "expectile" {
+            expectile_channel(data, self.period, self.lower, self.upper)
+        } else {
+            quantile_channel(data, self.period, self.lower, self.upper)
+        })
+    }
+}
+
+fn rolling_channel(data: &[f64], period: usize, levels: impl Fn(&[f64]) -> (f64, f64, f64)) -> Channel {
+    let n = data.len();
+    let (mut upper, mut lower, mut middle) = (vec![0.0; n], vec![0.0; n], vec![0.0; n]);
+    if period == 0 {
+        return (upper, lower, middle);
+    }
+    for i in period.saturating_sub(1)..n {
+        (upper[i], lower[i], middle[i]) = levels(&data[i + 1 - period..=i]);
+    }
+    (upper, lower, middle)
+}
+
+/// Type-7 quantile of ascending `sorted`.
+pub(crate) fn quantile_sorted(sorted: &[f64], q: f64) -> f64 {
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// The `tau` expectile: the `e` where `tau` times the mean excess above `e`
+/// balances `1 - tau` times the mean shortfall below it. Found by iterating
+/// the weighted mean, which converges monotonically.
+fn expectile(values: &[f64], tau: f64) -> f64 {
+    let tau = tau.clamp(0.0, 1.0);
+    let mut e = values.iter().sum::<f64>() / values.len() as f64;
+    for _ in 0..100 {
+        let (mut num, mut den) = (0.0, 0.0);
+        for &x in values {
+            let w = if x > e { tau } else { 1.0 - tau };
+            num += w * x;
+            den += w;
+        }
+        let next = if den > 0.0 { num / den } else { e };
+        if (next - e).abs() <= 1e-12 * e.abs().max(1.0) {
+            return next;
+        }
+        e = next;
+    }
+    e
+}
+
+/// Bill Williams fractals as breakout levels `(up, down)`: at each bar the
+/// high of the latest up fractal (a high above the `span` highs on either
+/// side) and the low of the latest down fractal; 0 until the first one. A
+/// fractal is only known `span` bars after its centre, so its level shows
+/// from that bar on and never repaints.
+pub fn fractals(highs: &[f64], lows: &[f64], span: usize) -> (Vec<f64>, Vec<f64>) {
+    let n = highs.len();
+    let (mut up, mut down) = (vec![0.0; n], vec![0.0; n]);
+    let (mut last_up, mut last_down) = (0.0, 0.0);
+    for i in 0..n {
+        if span > 0 && i >= 2 * span {
+            let c = i - span;
+            let side = (c - span..c).chain(c + 1..=i);
+            if side.clone().all(|j| highs[c] > highs[j]) {
+                last_up = highs[c];
+            }
+            if side.into_iter().all(|j| lows[c] < lows[j]) {
+                last_down = lows[c];
+            }
+        }
+        up[i] = last_up;
+        down[i] = last_down;
+    }
+    (up, down)
+}
+
+/// Williams Alligator `(jaw, teeth, lips)`: smoothed (Wilder) moving
+/// averages of the median price (high + low) / 2 over 13, 8 and 5 bars,
+/// pushed forward 8, 5 and 3 bars; 0 until defined. Pushed forward, the
+/// value at bar `i` is the average as of bar `i - shift`, so it only uses
+/// data up to that bar.
+pub fn alligator(highs: &[f64], lows: &[f64]) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let median: Vec<f64> = highs.iter().zip(lows).map(|(h, l)| (h + l) / 2.0).collect();
+    (shifted_smma(&median, 13, 8), shifted_smma(&median, 8, 5), shifted_smma(&median, 5, 3))
+}
+
+/// Smoothed moving average seeded with the SMA of the first `period`
+/// values, moved `shift` bars later.
+fn shifted_smma(data: &[f64], period: usize, shift: usize) -> Vec<f64> {
+    let n = data.len();
+    let mut out = vec![0.0; n];
+    if period == 0 || n < period {
+        return out;
+    }
+    let mut prev = data[..period].iter().sum::<f64>() / period as f64;
+    for (i, &x) in data.iter().enumerate().skip(period - 1) {
+        if i >= period {
+            prev = (prev * (period - 1) as f64 + x) / period as f64;
+        }
+        if let Some(o) = out.get_mut(i + shift) {
+            *o = prev;
+        }
+    }
+    out
+}
+
+/// VWAP using typical price = (high + low + close) / 3
+/// Session VWAP: accumulates from the first bar and restarts wherever
+/// `resets[i]` is set.
+pub fn session_vwap(highs: &[f64], lows: &[f64], closes: &[f64], volumes: &[f64], resets: &[bool]) -> Vec<f64> {
+    let mut result = vec![0.0; closes.len()];
+    let mut cum_vol = 0.0;
+    let mut cum_pv = 0.0;
+    for i in 0..closes.len() {
+        if resets.get(i).copied().unwrap_or(false) {
+            cum_vol = 0.0;
+            cum_pv = 0.0;
+        }
+        let typical = (highs[i] + lows[i] + closes[i]) / 3.0;
+        cum_pv += typical * volumes[i];
+        cum_vol += volumes[i];
+        result[i] = if cum_vol > 0.0 { cum_pv / cum_vol } else { closes[i] };
+    }
+    result
+}
+
+/// Session VWAP and the volume-weighted standard deviation of typical price
+/// around it, `(vwap, std)`, restarting at each session.
+pub fn session_vwap_bands(candles: &[Candle]) -> (Vec<f64>, Vec<f64>) {
+    vwap_bands(candles, &session_starts(candles))
+}
+
+/// `session_vwap_bands` restarting wherever `resets[i]` is set.
+pub fn vwap_bands(candles: &[Candle], resets: &[bool]) -> (Vec<f64>, Vec<f64>) {
+    let n = candles.len();
+    let mut vwap = Vec::with_capacity(n);
+    let mut std = Vec::with_capacity(n);
+    let (mut cum_pv, mut cum_vol, mut cum_p2v) = (0.0, 0.0, 0.0);
+    for (i, c) in candles.iter().enumerate() {
+        if resets.get(i).copied().unwrap_or(false) {
+            cum_pv = 0.0;
+            cum_vol = 0.0;
+            cum_p2v = 0.0;
+        }
+        let typical = (c.high + c.low + c.close) / 3.0;
+        cum_pv += typical * c.volume;
+        cum_vol += c.volume;
+        cum_p2v += typical * typical * c.volume;
+        let v = if cum_vol > 0.0 { cum_pv / cum_vol } else { c.close };
+        vwap.push(v);
+        std.push(if cum_vol > 0.0 { (cum_p2v / cum_vol - v * v).max(0.0).sqrt() } else { 0.0 });
+    }
+    (vwap, std)
+}
+
+/// Supertrend stop line from ATR(`period`) bands; 0 until defined.
+pub fn supertrend(highs: &[f64], lows: &[f64], closes: &[f64], period: usize, multiplier: f64) -> Vec<f64> {
+    let n = closes.len();
+    let mut result = vec![0.0; n];
+    if n < period { return result; }
+
+    let atr = atr(highs, lows, closes, period);
+
+    for i in period..n {
+        let hl2 = (highs[i] + lows[i]) / 2.0;
+        let upper = hl2 + multiplier * atr[i];
+        let lower = hl2 - multiplier * atr[i];
+        result[i] = if closes[i] > upper { lower } else if closes[i] < lower { upper } else { result[i - 1] };
+        if result[i] == 0.0 { result[i] = lower; }
+    }
+    result
+}
+
+/// Wilder ADX with +DI/−DI: `(adx, plus_di, minus_di)`.
+pub fn adx(highs: &[f64], lows: &[f64], closes: &[f64], period: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let n = closes.len();
+    let mut adx = vec![0.0; n];
+    let mut plus_di = vec![0.0; n];
+    let mut minus_di = vec![0.0; n];
+    if n < period + 1 { return (adx, plus_di, minus_di); }
+
+    let mut tr_sum = 0.0;
+    let mut plus_dm_sum = 0.0;
+    let mut minus_dm_sum = 0.0;
+    let mut dx_sum = 0.0;
+    let mut dx_count = 0usize;
+
+    for i in 1..n {
+        let tr = (highs[i] - lows[i])
+            .max((highs[i] - closes[i - 1]).abs())
+            .max((lows[i] - closes[i - 1]).abs());
+        let up = highs[i] - highs[i - 1];
+        let down = lows[i - 1] - lows[i];
+        let pdm = if up > down && up > 0.0 { up } else { 0.0 };
+        let mdm = if down > up && down > 0.0 { down } else { 0.0 };
+
+        if i <= period {
+            tr_sum += tr;
+            plus_dm_sum += pdm;
+            minus_dm_sum += mdm;
+            if i == period {
+                let pdi = if tr_sum > 0.0 { plus_dm_sum / tr_sum * 100.0 } else { 0.0 };
+                let mdi = if tr_sum > 0.0 { minus_dm_sum / tr_sum * 100.0 } else { 0.0 };
+                plus_di[i] = pdi;
+                minus_di[i] = mdi;
+                let di_sum = pdi + mdi;
+                let dx = if di_sum > 0.0 { (pdi - mdi).abs() / di_sum * 100.0 } else { 0.0 };
+                dx_sum += dx;
+                dx_count += 1;
+                adx[i] = dx;
+            }
+        } else {
+            let p = period as f64;
+            tr_sum = tr_sum - tr_sum / p + tr;
+            plus_dm_sum = plus_dm_sum - plus_dm_sum / p + pdm;
+            minus_dm_sum = minus_dm_sum - minus_dm_sum / p + mdm;
+            let pdi = if tr_sum > 0.0 { plus_dm_sum / tr_sum * 100.0 } else { 0.0 };
+            let mdi = if tr_sum > 0.0 { minus_dm_sum / tr_sum * 100.0 } else { 0.0 };
+            plus_di[i] = pdi;
+            minus_di[i] = mdi;
+            let di_sum = pdi + mdi;
+            let dx = if di_sum > 0.0 { (pdi - mdi).abs() / di_sum * 100.0 } else { 0.0 };
+            dx_sum += dx;
+            dx_count += 1;
+            adx[i] = if dx_count >= period {
+                (adx[i - 1] * (p - 1.0) + dx) / p
+            } else {
+                dx_sum / dx_count as f64
+            };
+        }
+    }
+    (adx, plus_di, minus_di)
+}
+
+/// How a `Transform` rescales its source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformKind {
+    /// Standard deviations from the trailing mean.
+    ZScore,
+    /// Percentile rank (0-100) within the trailing window.
+    Percentile,
+}
+
+/// A per-symbol relative view of one series over a trailing window, written
+/// `zscore(rsi_14, 100)` or `percentile(close, 252)`, so a threshold like
+/// "RSI in its top decile" means the same thing on every symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    pub kind: TransformKind,
+    pub source: String,
+    pub lookback: usize,
+}
+
+impl Transform {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let bad = || format!("Invalid transform '{}' (expected zscore(<series>, <lookback>) or percentile(<series>, <lookback>))", expr);
+        let (name, args) = expr.trim().strip_suffix(')').and_then(|e| e.split_once('(')).ok_or_else(bad)?;
+        let kind = match name.trim().to_ascii_lowercase().as_str() {
+            "zscore" | "z_score" => TransformKind::ZScore,
+            "percentile" | "pct_rank" => TransformKind::Percentile,
+            _ => return Err(bad()),
+        };
+        let (source, lookback) = args.split_once(',').ok_or_else(bad)?;
+        let source = source.trim().to_string();
+        let lookback: usize = lookback.trim().parse().map_err(|_| bad())?;
+        if source.is_empty() {
+            return Err(bad());
+        }
+        if lookback < 2 {
+            return Err(format!("Invalid transform '{}': lookback must be at least 2", expr));
+        }
+        Ok(Self { kind, source, lookback })
+    }
+
+    /// Output series name, e.g. `zscore_rsi_14_100`.
+    pub fn key(&self) -> String {
+        let name = match self.kind {
+            TransformKind::ZScore => "zscore",
+            TransformKind::Percentile => "percentile",
+        };
+        format!("{}_{}_{}", name, self.source, self.lookback)
+    }
+
+    pub fn apply(&self, data: &[f64]) -> Vec<f64> {
+        match self.kind {
+            TransformKind::ZScore => rolling_zscore(data, self.lookback),
+            TransformKind::Percentile => percentile_rank(data, self.lookback),
+        }
+    }
+}
+
+/// Distance of each value from the mean of the last `lookback` values
+/// (itself included) in population standard deviations; 0 on a flat
+/// window, NaN before the first full window or while it holds a NaN.
+pub fn rolling_zscore(data: &[f64], lookback: usize) -> Vec<f64> {
+    rolling_relative(data, lookback, |window, v| {
+        let n = window.len() as f64;
+        let mean = window.iter().sum::<f64>() / n;
+        let sd = (window.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n).sqrt();
+        if sd > 1e-12 { (v - mean) / sd } else { 0.0 }
+    })
+}
+
+/// Percentile rank (0-100) of each value among the last `lookback` values
+/// (itself included), ties counted half; 50 on a flat window, NaN before the
+/// first full window or while it holds a NaN.
+pub fn percentile_rank(data: &[f64], lookback: usize) -> Vec<f64> {
+    rolling_relative(data, lookback, |window, v| {
+        let below = window.iter().filter(|&&x| x < v).count() as f64;
+        let equal = window.iter().filter(|&&x| x == v).count() as f64;
+        (below + 0.5 * equal) / window.len() as f64 * 100.0
+    })
+}
+
+fn rolling_relative(data: &[f64], lookback: usize, f: impl Fn(&[f64], f64) -> f64) -> Vec<f64> {
+    let mut out = vec![f64::NAN; data.len()];
+    if lookback == 0 {
+        return out;
+    }
+    for (i, slot) in out.iter_mut().enumerate().skip(lookback - 1) {
+        let window = &data[i + 1 - lookback..=i];
+        if window.iter().all(|v| v.is_finite()) {
+            *slot = f(window, data[i]);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candles(n: usize) -> Vec<Candle> {
+        (0..n).map(|i| {
+            let c = 100.0 + (i as f64 * 0.3).sin() * 4.0 + i as f64 * 0.05;
+            let day = 2 + i / 40;
+            serde_json::from_value(json!({
+                "timestamp": format!("2024-01-{:02}T{:02}:{:02}:00", day, 9 + (i % 40) / 6, (i % 6) * 10),
+                "open": c - 0.2, "high": c + 0.8, "low": c - 0.9, "close": c, "volume": 1000.0 + (i % 7) as f64 * 150.0
+            })).unwrap()
+        }).collect()
+    }
+
+    #[test]
+    fn test_standard_matches_signals_command() {
+        let bars = candles(120);
+        let typed = standard(&bars);
+        let via_json: Standard = serde_json::from_value(crate::signals::compute(json!({ "candles": bars })).unwrap()).unwrap();
+        assert_eq!(typed.rsi_14, via_json.rsi_14);
+        assert_eq!(typed.macd_histogram, via_json.macd_histogram);
+        assert_eq!(typed.supertrend, via_json.supertrend);
+        let names: Vec<&str> = typed.columns().iter().map(|c| c.0).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert_eq!(names.len(), 11);
+    }
+
+    #[test]
+    fn test_quantile_and_expectile_channels() {
+        // A right-skewed window: mostly 100 with a few spikes.
+        let data: Vec<f64> = (0..40).map(|i| if i % 10 == 9 { 120.0 } else { 100.0 + (i % 3) as f64 }).collect();
+        let (qu, ql, qm) = quantile_channel(&data, 20, 0.1, 0.9);
+        assert_eq!(qu[18], 0.0);
+        let mut window = data[20..40].to_vec();
+        window.sort_by(f64::total_cmp);
+        assert_eq!(ql[39], window[1] + (window[2] - window[1]) * 0.9);
+        assert_eq!(qm[39], (window[9] + window[10]) / 2.0);
+        assert!(qu[39] - qm[39] > qm[39] - ql[39], "skew shows as a wider upper side");
+
+        let (eu, el, em) = expectile_channel(&data, 20, 0.1, 0.9);
+        let mean = data[20..40].iter().sum::<f64>() / 20.0;
+        assert!((em[39] - mean).abs() < 1e-9);
+        assert!(el[39] < em[39] && em[39] < eu[39]);
+        // The defining balance holds at the upper expectile.
+        let above: f64 = data[20..40].iter().map(|x| (x - eu[39]).max(0.0)).sum();
+        let below: f64 = data[20..40].iter().map(|x| (eu[39] - x).max(0.0)).sum();
+        assert!((0.9 * above - 0.1 * below).abs() < 1e-6);
+
+        let spec: ChannelSpec = serde_json::from_value(json!({ "method": "expectile" })).unwrap();
+        assert_eq!(spec.compute(&data).unwrap().0, eu);
+        assert!(ChannelSpec { lower: 0.9, upper: 0.1, ..spec.clone() }.compute(&data).is_err());
+        assert!(ChannelSpec { method: "median".into(), ..spec }.compute(&data).is_err());
+    }
+
+    #[test]
+    fn test_fractals_confirm_late_and_alligator_shifts() {
+        let highs = [10.0, 11.0, 14.0, 12.0, 11.0, 12.0, 13.0, 15.0, 14.0, 13.0];
+        let lows: Vec<f64> = highs.iter().map(|h| h - 2.0).collect();
+        let (up, down) = fractals(&highs, &lows, 2);
+        // The peak at bar 2 is only known once bars 3 and 4 are in.
+        assert_eq!(&up[..4], &[0.0; 4]);
+        assert_eq!(up[4], 14.0);
+        assert_eq!(up[8], 14.0);
+        assert_eq!(up[9], 15.0);
+        // The trough at bar 4 (low 9) shows from bar 6.
+        assert_eq!((down[5], down[6]), (0.0, 9.0));
+
+        let bars = candles(60);
+        let highs: Vec<f64> = bars.iter().map(|c| c.high).collect();
+        let lows: Vec<f64> = bars.iter().map(|c| c.low).collect();
+        let (jaw, teeth, lips) = alligator(&highs, &lows);
+        assert_eq!(jaw[19], 0.0);
+        let median: Vec<f64> = highs.iter().zip(&lows).map(|(h, l)| (h + l) / 2.0).collect();
+        assert!((jaw[20] - median[..13].iter().sum::<f64>() / 13.0).abs() < 1e-9);
+        assert!((teeth[12] - median[..8].iter().sum::<f64>() / 8.0).abs() < 1e-9);
+        assert_eq!((lips[6], lips[7] > 0.0), (0.0, true));
+        // Nothing after bar i moves the values at i.
+        let (jaw_short, _, _) = alligator(&highs[..40], &lows[..40]);
+        assert_eq!(&jaw_short[..], &jaw[..40]);
+    }
+
+    #[test]
+    fn test_transforms_parse_and_rescale() {
+        let t = Transform::parse(" zscore(rsi_14, 100) ").unwrap();
+        assert_eq!((t.kind, t.source.as_str(), t.lookback), (TransformKind::ZScore, "rsi_14", 100));
+        assert_eq!(t.key(), "zscore_rsi_14_100");
+        assert_eq!(Transform::parse("percentile(close,252)").unwrap().key(), "percentile_close_252");
+        for bad in ["zscore(rsi_14)", "rank(close, 20)", "percentile(close, 1)", "zscore(, 5)", "zscore close 5"] {
+            assert!(Transform::parse(bad).is_err(), "{}", bad);
+        }
+
+        let data = [1.0, 2.0, 3.0, 4.0, 5.0, 5.0];
+        let z = rolling_zscore(&data, 5);
+        assert!(z[3].is_nan());
+        assert!((z[4] - 2.0 / 2f64.sqrt()).abs() < 1e-12, "5 is sqrt(2) sds above 3");
+        let p = percentile_rank(&data, 5);
+        assert!(p[3].is_nan());
+        assert_eq!(p[4], 90.0);
+        assert_eq!(p[5], 80.0, "the tie with the previous 5 counts half");
+        assert_eq!(rolling_zscore(&[7.0; 4], 3)[3], 0.0);
+        assert_eq!(percentile_rank(&[7.0; 4], 3)[3], 50.0);
+        assert!(percentile_rank(&[f64::NAN, 1.0, 2.0, 3.0], 3)[2].is_nan());
+    }
+
+    #[test]
+    fn test_vw_macd_and_elder_impulse() {
+        let closes: Vec<f64> = (0..80).map(|i| 100.0 + (i as f64 * 0.2).sin() * 5.0 + i as f64 * 0.1).collect();
+        // Equal volume reduces the volume-weighted averages to plain ones.
+        let (line, signal, hist) = vw_macd(&closes, &[500.0; 80], 12, 26, 9);
+        let (fast, slow) = (sma(&closes, 12), sma(&closes, 26));
+        assert!(line[24].is_nan() && !line[25].is_nan());
+        assert!((line[60] - (fast[60] - slow[60])).abs() < 1e-9);
+        assert!(signal[32].is_nan() && (hist[33] - (line[33] - signal[33])).abs() < 1e-12);
+        // A heavy bar pulls the fast average harder than the slow one.
+        let mut volumes = vec![500.0; 80];
+        volumes[79] = 50_000.0;
+        let mut spiked = vec![100.0; 80];
+        spiked[79] = 110.0;
+        let heavy = vw_macd(&spiked, &volumes, 12, 26, 9).0[79];
+        let light = vw_macd(&spiked, &[500.0; 80], 12, 26, 9).0[79];
+        assert!(heavy > light);
+
+        let up: Vec<f64> = (0..60).map(|i| 100.0 * 1.01f64.powi(i * i / 20)).collect();
+        assert_eq!(elder_impulse(&up)[59], 1.0);
+        let down: Vec<f64> = up.iter().rev().copied().collect();
+        assert_eq!(elder_impulse(&down)[59], -1.0);
+        assert_eq!(elder_impulse(&[100.0; 40]), vec![0.0; 40]);
+    }
+
+    #[test]
+    fn test_vwap_bands_share_session_vwap() {
+        let bars = candles(120);
+        let (vwap, std) = session_vwap_bands(&bars);
+        assert_eq!(vwap, standard(&bars).vwap);
+        // Each session restarts: the first bar's VWAP is its own typical price.
+        let tp = (bars[40].high + bars[40].low + bars[40].close) / 3.0;
+        assert!((vwap[40] - tp).abs() < 1e-9);
+        assert_eq!(std[40], 0.0);
+        assert!(std[41] > 0.0);
+    }
+}
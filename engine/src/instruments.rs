@@ -0,0 +1,166 @@
+//! Per-symbol instrument metadata.
+//!
+//! `instruments: {"NIFTY24JANFUT": {"lot_size": 50, "tick_size": 0.05,
+//! "multiplier": 1, "product_type": "futures", "margin_pct": 0.12}, ...}`
+//! tells backtest, position sizing and paper orders how a symbol trades:
+//! quantities are whole lots, prices sit on the tick grid, and notional and
+//! P&L are points × quantity × multiplier. Quantities stay in units (a
+//! NIFTY lot of 50 is qty 50). Symbols not in the registry trade as cash
+//! equity: lot 1, multiplier 1, no tick grid.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::money::PrecisionSpec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductType {
+    #[default]
+    #[serde(alias = "eq", alias = "cash")]
+    Equity,
+    #[serde(alias = "fut", alias = "future")]
+    Futures,
+    #[serde(alias = "opt", alias = "option")]
+    Options,
+    #[serde(alias = "cds")]
+    Currency,
+    #[serde(alias = "mcx")]
+    Commodity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Instrument {
+    #[serde(default = "default_one")]
+    pub lot_size: f64,
+    #[serde(default)]
+    pub tick_size: Option<f64>,
+    /// Cash value of one point per unit of quantity.
+    #[serde(default = "default_one")]
+    pub multiplier: f64,
+    #[serde(default)]
+    pub product_type: ProductType,
+    /// Fraction of notional blocked as margin; the ledger default when None.
+    #[serde(default)]
+    pub margin_pct: Option<f64>,
+}
+
+fn default_one() -> f64 { 1.0 }
+
+impl Default for Instrument {
+    fn default() -> Self {
+        Self { lot_size: 1.0, tick_size: None, multiplier: 1.0, product_type: ProductType::Equity, margin_pct: None }
+    }
+}
+
+impl Instrument {
+    pub fn validate(&self, symbol: &str) -> Result<(), String> {
+        let positive = |v: f64| v.is_finite() && v > 0.0;
+        if !positive(self.lot_size) {
+            return Err(format!("{}: lot_size must be positive", symbol));
+        }
+        if !positive(self.multiplier) {
+            return Err(format!("{}: multiplier must be positive", symbol));
+        }
+        if self.tick_size.is_some_and(|t| !positive(t)) {
+            return Err(format!("{}: tick_size must be positive", symbol));
+        }
+        if self.margin_pct.is_some_and(|m| !(m.is_finite() && m >= 0.0)) {
+            return Err(format!("{}: margin_pct must be non-negative", symbol));
+        }
+        Ok(())
+    }
+
+    /// Cash value of `qty` units at `price`.
+    pub fn notional(&self, price: f64, qty: f64) -> f64 {
+        price * qty * self.multiplier
+    }
+
+    /// `qty` rounded down to whole lots.
+    pub fn round_lots(&self, qty: f64) -> f64 {
+        (qty / self.lot_size + 1e-9).floor() * self.lot_size
+    }
+
+    pub fn is_lot_multiple(&self, qty: f64) -> bool {
+        let lots = qty / self.lot_size;
+        (lots - lots.round()).abs() < 1e-9
+    }
+
+    /// Nearest price on the tick grid; unchanged without a tick size.
+    pub fn snap(&self, price: f64) -> f64 {
+        match self.tick_size {
+            Some(t) => {
+                // Round away the float noise of `n * t` at the tick's own decimals.
+                let decimals = (0..12)
+                    .find(|&d| {
+                        let scaled = t * 10f64.powi(d);
+                        (scaled - scaled.round()).abs() < 1e-9 * scaled.max(1.0)
+                    })
+                    .unwrap_or(12);
+                let scale = 10f64.powi(decimals);
+                ((price / t).round() * t * scale).round() / scale
+            }
+            None => price,
+        }
+    }
+
+    pub fn on_tick(&self, price: f64) -> bool {
+        self.tick_size.is_none_or(|t| ((price / t) - (price / t).round()).abs() < 1e-6)
+    }
+
+    /// Fixed-point precision implied by the tick size, for callers that did
+    /// not ask for one explicitly.
+    pub fn precision(&self) -> Option<PrecisionSpec> {
+        self.tick_size.map(|tick_size| PrecisionSpec { tick_size, decimals: None })
+    }
+}
+
+/// Symbol → instrument map; lookups fall back to [`Instrument::default`].
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(transparent)]
+pub struct Instruments(pub BTreeMap<String, Instrument>);
+
+impl Instruments {
+    pub fn get(&self, symbol: &str) -> Instrument {
+        self.0.get(symbol).cloned().unwrap_or_default()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.0.iter().try_for_each(|(symbol, i)| i.validate(symbol))
+    }
+
+    /// Registry under `data.instruments`, validated; empty when absent.
+    pub fn from_request(data: &serde_json::Value) -> Result<Self, String> {
+        let Some(v) = data.get("instruments").filter(|v| !v.is_null()) else { return Ok(Self::default()) };
+        let registry: Self = serde_json::from_value(v.clone()).map_err(|e| format!("Invalid instruments: {}", e))?;
+        registry.validate()?;
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_registry_lookup_and_rounding() {
+        let registry = Instruments::from_request(&json!({ "instruments": {
+            "NIFTYFUT": { "lot_size": 50, "tick_size": 0.05, "product_type": "fut" },
+            "GOLDM": { "lot_size": 1, "multiplier": 10, "product_type": "mcx" }
+        } })).unwrap();
+        let nifty = registry.get("NIFTYFUT");
+        assert_eq!(nifty.product_type, ProductType::Futures);
+        assert_eq!(nifty.round_lots(149.0), 100.0);
+        assert!(nifty.is_lot_multiple(150.0) && !nifty.is_lot_multiple(75.0));
+        assert_eq!(nifty.snap(22_000.12), 22_000.1);
+        assert!(nifty.on_tick(22_000.15) && !nifty.on_tick(22_000.12));
+        assert_eq!(registry.get("GOLDM").notional(7_000.0, 2.0), 140_000.0);
+        // Unlisted symbols trade as cash equity.
+        let infy = registry.get("INFY");
+        assert_eq!((infy.lot_size, infy.multiplier, infy.product_type), (1.0, 1.0, ProductType::Equity));
+
+        assert!(Instruments::from_request(&json!({})).unwrap().0.is_empty());
+        assert!(Instruments::from_request(&json!({ "instruments": { "X": { "lot_size": 0 } } })).is_err());
+        assert!(Instruments::from_request(&json!({ "instruments": { "X": { "tick_size": -0.05 } } })).is_err());
+    }
+}
@@ -0,0 +1,337 @@
+//! Earnings IV-crush simulator.
+//!
+//! Prices standard event structures at today's pre-event IVs, then revalues
+//! them `event_days` later, after the event, at the post-event IVs across a
+//! range of price outcomes. The front expiry drops from `front_iv` to
+//! `post_iv`; the gap is read as the event's variance, and the back expiry
+//! (for calendars) loses the same variance spread over its longer life
+//! unless `back_post_iv` is given. Outcomes are multiples of the expected
+//! move (`expected_move_pct`, else the move implied by that variance), and
+//! each `iv_shifts` entry adds a row with both post-event IVs shifted by
+//! that many vol points, so every structure gets a price × IV payoff surface.
+//!
+//! Structures: `straddle` (long ATM), `short_straddle`, `iron_condor` (short
+//! strikes one expected move out, wings two) and `calendar` (short front,
+//! long back ATM call).
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::strategy_payoff::{Leg, LegKind};
+use crate::utils::{round2, round4};
+
+#[derive(Deserialize)]
+struct CrushConfig {
+    spot: f64,
+    /// Pre-event IV of the expiry spanning the event.
+    front_iv: f64,
+    /// Assumed front IV once the event has passed.
+    post_iv: f64,
+    front_expiry_days: f64,
+    #[serde(default)]
+    back_iv: Option<f64>,
+    #[serde(default)]
+    back_post_iv: Option<f64>,
+    #[serde(default)]
+    back_expiry_days: Option<f64>,
+    /// Days from now to the post-event revaluation.
+    #[serde(default = "default_event_days")]
+    event_days: f64,
+    /// One expected move, in percent of spot.
+    #[serde(default)]
+    expected_move_pct: Option<f64>,
+    #[serde(default = "default_strategies")]
+    strategies: Vec<String>,
+    /// Price outcomes, in expected moves from spot.
+    #[serde(default = "default_move_multiples")]
+    move_multiples: Vec<f64>,
+    /// Vol-point shifts of the post-event IVs, one surface row each.
+    #[serde(default = "default_iv_shifts")]
+    iv_shifts: Vec<f64>,
+    /// Strikes snap to this grid; exact prices when absent.
+    #[serde(default)]
+    strike_step: Option<f64>,
+    #[serde(default = "default_lot")]
+    lot_size: f64,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+}
+
+fn default_event_days() -> f64 { 1.0 }
+fn default_strategies() -> Vec<String> {
+    vec!["straddle".into(), "iron_condor".into(), "calendar".into()]
+}
+fn default_move_multiples() -> Vec<f64> { vec![-2.0, -1.5, -1.0, -0.5, 0.0, 0.5, 1.0, 1.5, 2.0] }
+fn default_iv_shifts() -> Vec<f64> { vec![0.0] }
+fn default_lot() -> f64 { 1.0 }
+fn default_rate() -> f64 { 0.065 }
+
+#[derive(Serialize)]
+struct LegOut {
+    option_type: &'static str,
+    strike: f64,
+    expiry_days: f64,
+    units: f64,
+    premium: f64,
+    pre_iv: f64,
+    post_iv: f64,
+}
+
+#[derive(Serialize)]
+struct OutcomePoint {
+    move_pct: f64,
+    price: f64,
+    pnl: f64,
+}
+
+#[derive(Serialize)]
+struct SurfaceRow {
+    iv_shift: f64,
+    post_iv: f64,
+    points: Vec<OutcomePoint>,
+}
+
+#[derive(Serialize)]
+struct StrategyOut {
+    name: String,
+    legs: Vec<LegOut>,
+    /// Premium paid (positive, debit) or received (negative, credit).
+    net_premium: f64,
+    /// Post-event P&L at the assumed IVs: unchanged price, then one
+    /// expected move down and up.
+    pnl_no_move: f64,
+    pnl_move_down: f64,
+    pnl_move_up: f64,
+    best: f64,
+    worst: f64,
+    surface: Vec<SurfaceRow>,
+}
+
+#[derive(Serialize)]
+struct CrushResult {
+    spot: f64,
+    expected_move_pct: f64,
+    /// One standard deviation of the event move priced into `front_iv`
+    /// over `post_iv`.
+    implied_move_pct: f64,
+    front_post_iv: f64,
+    back_post_iv: Option<f64>,
+    strategies: Vec<StrategyOut>,
+}
+
+/// A leg plus its post-event IV.
+struct CrushLeg {
+    leg: Leg,
+    post_iv: f64,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: CrushConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid iv_crush config: {}", e))?;
+    let spot = config.spot;
+    if !(spot.is_finite() && spot > 0.0) {
+        return Err("spot must be positive".to_string());
+    }
+    if !(config.front_iv > 0.0 && config.post_iv > 0.0) {
+        return Err("front_iv and post_iv must be positive".to_string());
+    }
+    if !(config.event_days >= 0.0 && config.front_expiry_days > config.event_days) {
+        return Err("front_expiry_days must be after event_days".to_string());
+    }
+    if config.strategies.is_empty() || config.move_multiples.is_empty() || config.iv_shifts.is_empty() {
+        return Err("strategies, move_multiples and iv_shifts must not be empty".to_string());
+    }
+
+    let front_t = config.front_expiry_days / 365.0;
+    let event_var = ((config.front_iv.powi(2) - config.post_iv.powi(2)) * front_t).max(0.0);
+    let implied_move_pct = event_var.sqrt() * 100.0;
+    let move_pct = config.expected_move_pct.unwrap_or(implied_move_pct);
+    if move_pct.is_nan() || move_pct <= 0.0 {
+        return Err("expected_move_pct must be positive (or post_iv below front_iv)".to_string());
+    }
+
+    let back = match (config.back_iv, config.back_expiry_days) {
+        (Some(iv), Some(days)) => {
+            if !(iv > 0.0 && days > config.front_expiry_days) {
+                return Err("back_iv must be positive and back_expiry_days after front_expiry_days".to_string());
+            }
+            let post = config.back_post_iv
+                .unwrap_or_else(|| (iv.powi(2) - event_var / (days / 365.0)).max(0.0001).sqrt());
+            Some((iv, post, days))
+        }
+        (None, None) => None,
+        _ => return Err("back_iv and back_expiry_days go together".to_string()),
+    };
+
+    let snap = |price: f64| match config.strike_step {
+        Some(step) if step > 0.0 => ((price / step).round() * step).max(step),
+        _ => price,
+    };
+    let atm = snap(spot);
+    let one_move = spot * move_pct / 100.0;
+    let lot = config.lot_size;
+    let front = |kind: LegKind, strike: f64, qty: f64| CrushLeg {
+        leg: Leg { kind, strike, premium: 0.0, units: qty * lot, expiry_days: config.front_expiry_days, iv: config.front_iv },
+        post_iv: config.post_iv,
+    };
+
+    let mut strategies = Vec::with_capacity(config.strategies.len());
+    for name in &config.strategies {
+        let mut legs = match name.as_str() {
+            "straddle" | "long_straddle" => vec![front(LegKind::Call, atm, 1.0), front(LegKind::Put, atm, 1.0)],
+            "short_straddle" => vec![front(LegKind::Call, atm, -1.0), front(LegKind::Put, atm, -1.0)],
+            "iron_condor" => {
+                let (put_short, call_short) = (snap(spot - one_move), snap(spot + one_move));
+                let (put_long, call_long) = (snap(spot - 2.0 * one_move), snap(spot + 2.0 * one_move));
+                if !(put_long < put_short && call_short < call_long) {
+                    return Err("iron_condor strikes collapse at this strike_step; widen the expected move".to_string());
+                }
+                vec![
+                    front(LegKind::Put, put_long, 1.0),
+                    front(LegKind::Put, put_short, -1.0),
+                    front(LegKind::Call, call_short, -1.0),
+                    front(LegKind::Call, call_long, 1.0),
+                ]
+            }
+            "calendar" | "calendar_spread" => {
+                let (iv, post_iv, days) = back.ok_or("calendar needs back_iv and back_expiry_days")?;
+                vec![
+                    front(LegKind::Call, atm, -1.0),
+                    CrushLeg { leg: Leg { kind: LegKind::Call, strike: atm, premium: 0.0, units: lot, expiry_days: days, iv }, post_iv },
+                ]
+            }
+            other => return Err(format!("Unknown strategy '{}' (expected straddle, short_straddle, iron_condor or calendar)", other)),
+        };
+        for l in &mut legs {
+            l.leg.premium = l.leg.value(spot, 0.0, config.risk_free_rate);
+        }
+        strategies.push(simulate(name, &legs, &config, one_move));
+    }
+
+    let result = CrushResult {
+        spot,
+        expected_move_pct: round2(move_pct),
+        implied_move_pct: round2(implied_move_pct),
+        front_post_iv: config.post_iv,
+        back_post_iv: back.map(|b| round4(b.1)),
+        strategies,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Post-event P&L surface of one structure.
+fn simulate(name: &str, legs: &[CrushLeg], config: &CrushConfig, one_move: f64) -> StrategyOut {
+    let rate = config.risk_free_rate;
+    let pnl_at = |price: f64, shift: f64| -> f64 {
+        legs.iter().map(|l| {
+            let post = Leg { iv: (l.post_iv + shift / 100.0).max(0.01), ..l.leg.clone() };
+            post.pnl(price, config.event_days, rate)
+        }).sum()
+    };
+    let price_of = |m: f64| (config.spot + m * one_move).max(0.0);
+
+    let surface: Vec<SurfaceRow> = config.iv_shifts.iter().map(|&shift| SurfaceRow {
+        iv_shift: shift,
+        post_iv: round4((config.post_iv + shift / 100.0).max(0.01)),
+        points: config.move_multiples.iter().map(|&m| {
+            let price = price_of(m);
+            OutcomePoint {
+                move_pct: round2((price / config.spot - 1.0) * 100.0),
+                price: round2(price),
+                pnl: round2(pnl_at(price, shift)),
+            }
+        }).collect(),
+    }).collect();
+    let all = surface.iter().flat_map(|r| r.points.iter().map(|p| p.pnl));
+    let (best, worst) = all.fold((f64::NEG_INFINITY, f64::INFINITY), |(b, w), p| (b.max(p), w.min(p)));
+
+    StrategyOut {
+        name: name.to_string(),
+        legs: legs.iter().map(|l| LegOut {
+            option_type: if l.leg.kind == LegKind::Call { "call" } else { "put" },
+            strike: round2(l.leg.strike),
+            expiry_days: l.leg.expiry_days,
+            units: l.leg.units,
+            premium: round2(l.leg.premium),
+            pre_iv: round4(l.leg.iv),
+            post_iv: round4(l.post_iv),
+        }).collect(),
+        net_premium: round2(legs.iter().map(|l| l.leg.premium * l.leg.units).sum()),
+        pnl_no_move: round2(pnl_at(config.spot, 0.0)),
+        pnl_move_down: round2(pnl_at(price_of(-1.0), 0.0)),
+        pnl_move_up: round2(pnl_at(price_of(1.0), 0.0)),
+        best,
+        worst,
+        surface,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn earnings() -> Value {
+        json!({
+            "spot": 1000.0, "front_iv": 0.60, "post_iv": 0.30, "front_expiry_days": 7,
+            "back_iv": 0.40, "back_expiry_days": 35, "strike_step": 10.0
+        })
+    }
+
+    #[test]
+    fn test_crush_favours_short_premium() {
+        let result = compute(earnings()).unwrap();
+        // sqrt((0.36 - 0.09) × 7/365) ≈ 7.2% event move.
+        assert_eq!(result["implied_move_pct"], 7.2);
+        assert_eq!(result["expected_move_pct"], 7.2);
+        let s = result["strategies"].as_array().unwrap();
+        assert_eq!(s.len(), 3);
+        let (straddle, condor, calendar) = (&s[0], &s[1], &s[2]);
+        // Flat price after the event: the long straddle loses its event premium,
+        // the condor keeps most of its credit.
+        assert!(straddle["pnl_no_move"].as_f64().unwrap() < -20.0);
+        assert!(straddle["net_premium"].as_f64().unwrap() > 0.0);
+        assert!(condor["net_premium"].as_f64().unwrap() < 0.0);
+        assert!(condor["pnl_no_move"].as_f64().unwrap() > 0.0);
+        assert_eq!(condor["legs"][1]["strike"], 930.0);
+        assert_eq!(condor["legs"][3]["strike"], 1140.0);
+        // A two-move gap breaks the condor and pays the straddle.
+        let row = &straddle["surface"][0]["points"];
+        assert!(row[8]["pnl"].as_f64().unwrap() > 0.0);
+        assert!(condor["surface"][0]["points"][0]["pnl"].as_f64().unwrap() < 0.0);
+        // The back month loses less vol than the front, so the calendar gains at the strike.
+        assert!(result["back_post_iv"].as_f64().unwrap() > 0.3);
+        assert!(calendar["pnl_no_move"].as_f64().unwrap() > 0.0);
+        assert_eq!(calendar["legs"][1]["expiry_days"], 35.0);
+    }
+
+    #[test]
+    fn test_surface_rows_and_validation() {
+        let mut data = earnings();
+        data["strategies"] = json!(["short_straddle"]);
+        data["iv_shifts"] = json!([-5, 0, 10]);
+        data["move_multiples"] = json!([-1, 0, 1]);
+        data["expected_move_pct"] = json!(5.0);
+        data["lot_size"] = json!(25);
+        let result = compute(data.clone()).unwrap();
+        let strat = &result["strategies"][0];
+        let surface = strat["surface"].as_array().unwrap();
+        assert_eq!(surface.len(), 3);
+        assert_eq!(surface[2]["post_iv"], 0.4);
+        assert_eq!(surface[1]["points"][2]["price"], 1050.0);
+        assert_eq!(strat["legs"][0]["units"], -25.0);
+        // Short vega: a higher post-event IV costs the seller.
+        let flat = |row: usize| surface[row]["points"][1]["pnl"].as_f64().unwrap();
+        assert!(flat(0) > flat(1) && flat(1) > flat(2));
+        assert_eq!(strat["best"], flat(0));
+
+        data["strategies"] = json!(["calendar"]);
+        data.as_object_mut().unwrap().remove("back_iv");
+        assert!(compute(data.clone()).unwrap_err().contains("go together"));
+        data.as_object_mut().unwrap().remove("back_expiry_days");
+        assert!(compute(data.clone()).unwrap_err().contains("calendar needs"));
+        data["strategies"] = json!(["butterfly"]);
+        assert!(compute(data.clone()).unwrap_err().contains("butterfly"));
+        data["front_expiry_days"] = json!(1);
+        assert!(compute(data).is_err());
+    }
+}
@@ -1,13 +1,34 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use crate::utils::{bs_price, round4};
+use crate::utils::{bs_greeks, bs_price, round2, round4};
 #[derive(Deserialize)]
 struct IVSurfaceConfig {
     spot: f64,
     risk_free_rate: Option<f64>,
     strikes: Vec<StrikeData>,
+    /// Price calendars (and optionally diagonals) between two expiries.
+    #[serde(default)]
+    calendar_spread: Option<CalendarSpreadSpec>,
 }
 
+#[derive(Deserialize)]
+struct CalendarSpreadSpec {
+    /// Expiries to pair; the two nearest in the surface when omitted.
+    #[serde(default)]
+    near_expiry_days: Option<f64>,
+    #[serde(default)]
+    far_expiry_days: Option<f64>,
+    /// "call" or "put".
+    #[serde(default = "default_option_type")]
+    option_type: String,
+    /// Also price diagonals whose long far leg sits this far nearer the
+    /// money than the short near leg (below it for calls, above for puts).
+    #[serde(default)]
+    diagonal_width: Option<f64>,
+}
+
+fn default_option_type() -> String { "call".to_string() }
+
 #[derive(Deserialize, Clone)]
 struct StrikeData {
     strike: f64,
@@ -25,6 +46,8 @@ struct IVSurfaceResult {
     anomalies: Vec<Anomaly>,
     term_structure: Vec<TermPoint>,
     summary: SurfaceSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    calendar_spreads: Option<CalendarAnalysis>,
 }
 
 #[derive(Serialize)]
@@ -65,6 +88,51 @@ struct TermPoint {
     atm_iv: f64,
 }
 
+#[derive(Serialize)]
+struct CalendarAnalysis {
+    near_expiry_days: f64,
+    far_expiry_days: f64,
+    option_type: String,
+    near_atm_iv: f64,
+    far_atm_iv: f64,
+    /// Far minus near ATM IV; negative in backwardation, where calendars
+    /// sell the richer vol.
+    iv_differential: f64,
+    /// Vol implied for the period between the expiries; None when total
+    /// variance falls from near to far (a calendar arbitrage).
+    forward_atm_iv: Option<f64>,
+    /// Long calendars (sell near, buy far), best score first.
+    calendars: Vec<SpreadRow>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagonals: Option<Vec<SpreadRow>>,
+    best_strike: Option<f64>,
+}
+
+/// One long spread: short the near-expiry option at `near_strike`, long
+/// the far-expiry option at `far_strike`. Greeks are net, per unit.
+#[derive(Serialize)]
+struct SpreadRow {
+    rank: usize,
+    near_strike: f64,
+    far_strike: f64,
+    moneyness: f64,
+    near_iv: f64,
+    far_iv: f64,
+    iv_differential: f64,
+    forward_iv: Option<f64>,
+    debit: f64,
+    theta: f64,
+    vega: f64,
+    gamma: f64,
+    delta: f64,
+    /// Daily theta as a percentage of the long leg's premium (the debit
+    /// can be near zero in backwardation, so it is a poor base).
+    carry_pct: f64,
+    /// Daily theta plus the gain if the short leg's IV fell to the long
+    /// leg's, in bps of spot so strikes compare on the same notional.
+    score: f64,
+}
+
 #[derive(Serialize)]
 struct SurfaceSummary {
     overall_iv_level: String,
@@ -126,6 +194,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
         else { "FLAT" }
     } else { "INSUFFICIENT_DATA" };
 
+    let calendar_spreads = config.calendar_spread.as_ref()
+        .map(|spec| calendar_analysis(&surface, &term_structure, spec, spot, r))
+        .transpose()?;
+
     let signal = if avg_iv > 0.30 && skew.put_call_iv_ratio > 1.2 { "SELL_PREMIUM" }
         else if avg_iv < 0.15 { "BUY_PREMIUM" }
         else if anomalies.len() > 3 { "ARBITRAGE_OPPORTUNITIES" }
@@ -143,6 +215,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
             mispriced_options_count: anomalies.len(),
             signal: signal.to_string(),
         },
+        calendar_spreads,
     };
 
     serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
@@ -250,6 +323,113 @@ fn compute_term_structure(surface: &[SurfacePoint], spot: f64) -> Vec<TermPoint>
     terms
 }
 
+fn calendar_analysis(
+    surface: &[SurfacePoint],
+    term_structure: &[TermPoint],
+    spec: &CalendarSpreadSpec,
+    spot: f64,
+    r: f64,
+) -> Result<CalendarAnalysis, String> {
+    let is_call = match spec.option_type.to_lowercase().as_str() {
+        "call" | "ce" => true,
+        "put" | "pe" => false,
+        other => return Err(format!("Unknown calendar_spread option_type: {}", other)),
+    };
+    let expiry = |requested: Option<f64>, fallback: usize| match requested {
+        Some(days) => term_structure.iter().find(|t| t.expiry_days as i64 == days as i64)
+            .ok_or_else(|| format!("No strikes at expiry_days {}", days)),
+        None => term_structure.get(fallback)
+            .ok_or_else(|| "calendar_spread needs at least two expiries".to_string()),
+    };
+    let near = expiry(spec.near_expiry_days, 0)?;
+    let far = expiry(spec.far_expiry_days, 1)?;
+    if far.expiry_days <= near.expiry_days {
+        return Err("calendar_spread far expiry must be after the near expiry".to_string());
+    }
+
+    let by_expiry = group_by_expiry(surface);
+    let leg_iv = |p: &SurfacePoint| {
+        let iv = if is_call { p.call_iv } else { p.put_iv };
+        if iv > 0.0 { iv } else { p.avg_iv }
+    };
+    let legs = |days: f64| -> Vec<(f64, f64)> {
+        let mut legs: Vec<(f64, f64)> = by_expiry.get(&(days as i64)).into_iter().flatten()
+            .map(|p| (p.strike, leg_iv(p)))
+            .filter(|(_, iv)| *iv > 0.0)
+            .collect();
+        legs.sort_by(|a, b| a.0.total_cmp(&b.0));
+        legs
+    };
+    let (near_legs, far_legs) = (legs(near.expiry_days), legs(far.expiry_days));
+    let (tn, tf) = (near.expiry_days / 365.0, far.expiry_days / 365.0);
+    let far_at = |strike: f64| far_legs.iter().find(|(k, _)| (k - strike).abs() < 1e-9).map(|(_, iv)| *iv);
+
+    let spread = |near_strike: f64, near_iv: f64, far_strike: f64, far_iv: f64| -> Option<SpreadRow> {
+        let premium = bs_price(spot, far_strike, r, tf, far_iv, is_call);
+        let debit = premium - bs_price(spot, near_strike, r, tn, near_iv, is_call);
+        if debit <= 0.0 {
+            return None;
+        }
+        let (nd, ng, nt, nv, _) = bs_greeks(spot, near_strike, tn, r, near_iv, is_call);
+        let (fd, fg, ft, fv, _) = bs_greeks(spot, far_strike, tf, r, far_iv, is_call);
+        let theta = ft - nt;
+        Some(SpreadRow {
+            rank: 0,
+            near_strike,
+            far_strike,
+            moneyness: round4(near_strike / spot),
+            near_iv: round4(near_iv),
+            far_iv: round4(far_iv),
+            iv_differential: round4(far_iv - near_iv),
+            forward_iv: forward_vol(near_iv, tn, far_iv, tf).map(round4),
+            debit: round2(debit),
+            theta: round4(theta),
+            vega: round4(fv - nv),
+            gamma: round4(fg - ng),
+            delta: round4(fd - nd),
+            carry_pct: round4(theta / premium * 100.0),
+            // Vega is per vol point; IVs are decimals.
+            score: round4((theta + (near_iv - far_iv) * 100.0 * nv) / spot * 10_000.0),
+        })
+    };
+    let ranked = |mut rows: Vec<SpreadRow>| {
+        rows.sort_by(|a, b| b.score.total_cmp(&a.score));
+        for (i, row) in rows.iter_mut().enumerate() {
+            row.rank = i + 1;
+        }
+        rows
+    };
+
+    let calendars = ranked(near_legs.iter()
+        .filter_map(|&(k, near_iv)| spread(k, near_iv, k, far_at(k)?))
+        .collect());
+    let diagonals = spec.diagonal_width.map(|width| {
+        let offset = if is_call { -width } else { width };
+        ranked(near_legs.iter()
+            .filter_map(|&(k, near_iv)| spread(k, near_iv, k + offset, far_at(k + offset)?))
+            .collect())
+    });
+
+    Ok(CalendarAnalysis {
+        near_expiry_days: near.expiry_days,
+        far_expiry_days: far.expiry_days,
+        option_type: if is_call { "call" } else { "put" }.to_string(),
+        near_atm_iv: near.atm_iv,
+        far_atm_iv: far.atm_iv,
+        iv_differential: round4(far.atm_iv - near.atm_iv),
+        forward_atm_iv: forward_vol(near.atm_iv, tn, far.atm_iv, tf).map(round4),
+        best_strike: calendars.first().map(|c| c.near_strike),
+        calendars,
+        diagonals,
+    })
+}
+
+/// Vol between two expiries from their total variances.
+fn forward_vol(near_iv: f64, tn: f64, far_iv: f64, tf: f64) -> Option<f64> {
+    let variance = (far_iv * far_iv * tf - near_iv * near_iv * tn) / (tf - tn);
+    (tf > tn && variance >= 0.0).then(|| variance.sqrt())
+}
+
 fn group_by_expiry(surface: &[SurfacePoint]) -> std::collections::HashMap<i64, Vec<&SurfacePoint>> {
     let mut map: std::collections::HashMap<i64, Vec<&SurfacePoint>> = std::collections::HashMap::new();
     for s in surface {
@@ -427,4 +607,47 @@ mod tests {
         assert!((call_iv - 0.0).abs() < 1e-9);
         assert!((put_iv - 0.0).abs() < 1e-9);
     }
+
+    #[test]
+    fn test_calendar_spreads_ranked_by_strike() {
+        let strikes: Vec<Value> = [(30, 0.30), (60, 0.22)].iter().flat_map(|&(days, atm)| {
+            [90.0, 100.0, 110.0, 130.0].map(|k: f64| {
+                let iv = atm + 0.2 * (k / 100.0 - 1.0).powi(2);
+                json!({ "strike": k, "expiry_days": days, "call_iv": iv, "put_iv": iv })
+            })
+        }).collect();
+        let result = compute(json!({
+            "spot": 100.0, "strikes": strikes,
+            "calendar_spread": { "diagonal_width": 10.0 },
+        })).unwrap();
+        let cal = &result["calendar_spreads"];
+        assert_eq!((cal["near_expiry_days"].as_f64(), cal["far_expiry_days"].as_f64()), (Some(30.0), Some(60.0)));
+        assert!((cal["iv_differential"].as_f64().unwrap() + 0.08).abs() < 1e-9);
+        // Backwardated 30→22 over 30→60 days implies a forward vol under the far IV.
+        let fwd = cal["forward_atm_iv"].as_f64().unwrap();
+        assert!((fwd - ((0.22f64.powi(2) * 60.0 - 0.09 * 30.0) / 30.0).sqrt()).abs() < 1e-4);
+
+        let rows = cal["calendars"].as_array().unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0]["rank"], 1);
+        assert_eq!(cal["best_strike"].as_f64().unwrap(), 100.0);
+        let atm = rows.iter().find(|r| r["near_strike"] == 100.0).unwrap();
+        assert!(atm["theta"].as_f64().unwrap() > 0.0 && atm["vega"].as_f64().unwrap() > 0.0);
+        assert!(atm["debit"].as_f64().unwrap() > 0.0);
+        // Far-OTM calendars carry little theta for their debit.
+        assert!(rows.iter().all(|r| r["score"].as_f64() <= atm["score"].as_f64()));
+
+        let diagonals = cal["diagonals"].as_array().unwrap();
+        assert!(diagonals.iter().any(|d| d["near_strike"] == 110.0 && d["far_strike"] == 100.0));
+        assert!(diagonals.iter().all(|d| d["far_strike"].as_f64().unwrap() < d["near_strike"].as_f64().unwrap()));
+
+        let plain = compute(json!({ "spot": 100.0, "strikes": [
+            { "strike": 100.0, "expiry_days": 30, "call_iv": 0.2, "put_iv": 0.2 }
+        ] })).unwrap();
+        assert!(plain.get("calendar_spreads").is_none());
+        let single = compute(json!({ "spot": 100.0, "calendar_spread": {}, "strikes": [
+            { "strike": 100.0, "expiry_days": 30, "call_iv": 0.2, "put_iv": 0.2 }
+        ] }));
+        assert!(single.is_err());
+    }
 }
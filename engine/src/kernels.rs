@@ -0,0 +1,246 @@
+//! Hot indicator kernels for long series (years of minute bars).
+//!
+//! Everything is O(n) in one or two passes over contiguous slices. The
+//! element-wise passes (true range, gain/loss split) are branch-free and
+//! walk `LANES`-wide chunks so LLVM emits packed SIMD on any x86_64/aarch64
+//! build (wider with `-C target-cpu=native`); recurrences (EMA, Wilder
+//! smoothing) are inherently serial and are kept as tight scalar loops.
+//! Rolling windows slide a running sum instead of re-summing each window,
+//! re-anchoring every `RESYNC` bars so float drift stays at the 1e-12
+//! level. `utils::calc_*` delegate here, so backtest, scan, signals and
+//! advanced_signals all share these. `benches/indicators.rs` times them
+//! against the old per-window loops.
+
+/// Chunk width for the element-wise passes (4 × f64 = one AVX register).
+const LANES: usize = 4;
+
+/// Bars between exact recomputations of a sliding window's sums.
+const RESYNC: usize = 4096;
+
+/// EMA seeded with the SMA of the first `period` values; NaN before that.
+pub fn ema(data: &[f64], period: usize) -> Vec<f64> {
+    let mut out = vec![f64::NAN; data.len()];
+    if period == 0 || data.len() < period {
+        return out;
+    }
+    let mult = 2.0 / (period as f64 + 1.0);
+    let mut prev = data[..period].iter().sum::<f64>() / period as f64;
+    out[period - 1] = prev;
+    for (o, &x) in out[period..].iter_mut().zip(&data[period..]) {
+        prev = (x - prev) * mult + prev;
+        *o = prev;
+    }
+    out
+}
+
+/// Wilder RSI; 50 until `period` changes are available.
+pub fn rsi(data: &[f64], period: usize) -> Vec<f64> {
+    let n = data.len();
+    if period == 0 || n < period + 1 {
+        return vec![50.0; n];
+    }
+    let mut gains = vec![0.0; n - 1];
+    let mut losses = vec![0.0; n - 1];
+    split_changes(data, &mut gains, &mut losses);
+
+    let mut out = vec![50.0; n];
+    let p = period as f64;
+    let mut avg_gain = gains[..period].iter().sum::<f64>() / p;
+    let mut avg_loss = losses[..period].iter().sum::<f64>() / p;
+    out[period] = rsi_value(avg_gain, avg_loss);
+    // Multiplying by 1/p keeps divides off the serial dependency chain.
+    let (keep, alpha) = ((p - 1.0) / p, 1.0 / p);
+    for ((o, g), l) in out[period + 1..].iter_mut().zip(&gains[period..]).zip(&losses[period..]) {
+        avg_gain = avg_gain * keep + g * alpha;
+        avg_loss = avg_loss * keep + l * alpha;
+        *o = rsi_value(avg_gain, avg_loss);
+    }
+    out
+}
+
+/// 100 − 100 / (1 + RS), folded into a single divide.
+#[inline]
+pub(crate) fn rsi_value(avg_gain: f64, avg_loss: f64) -> f64 {
+    if avg_loss == 0.0 { 100.0 } else { 100.0 * avg_gain / (avg_gain + avg_loss) }
+}
+
+/// `gains[i]`/`losses[i]` = up/down move from `data[i]` to `data[i + 1]`.
+fn split_changes(data: &[f64], gains: &mut [f64], losses: &mut [f64]) {
+    let (prev, next) = (&data[..data.len() - 1], &data[1..]);
+    let mut g = gains.chunks_exact_mut(LANES);
+    let mut l = losses.chunks_exact_mut(LANES);
+    let mut a = prev.chunks_exact(LANES);
+    let mut b = next.chunks_exact(LANES);
+    for (((g, l), a), b) in (&mut g).zip(&mut l).zip(&mut a).zip(&mut b) {
+        for k in 0..LANES {
+            let d = b[k] - a[k];
+            g[k] = d.max(0.0);
+            l[k] = (-d).max(0.0);
+        }
+    }
+    let tail = g.into_remainder().iter_mut().zip(l.into_remainder()).zip(a.remainder()).zip(b.remainder());
+    for (((g, l), a), b) in tail {
+        let d = b - a;
+        *g = d.max(0.0);
+        *l = (-d).max(0.0);
+    }
+}
+
+/// True range; the first bar is its high − low.
+pub fn true_range(highs: &[f64], lows: &[f64], closes: &[f64]) -> Vec<f64> {
+    let n = closes.len().min(highs.len()).min(lows.len());
+    let mut tr = vec![0.0; n];
+    if n == 0 {
+        return tr;
+    }
+    tr[0] = highs[0] - lows[0];
+    let (h, l, pc) = (&highs[1..n], &lows[1..n], &closes[..n - 1]);
+    let mut out = tr[1..].chunks_exact_mut(LANES);
+    let mut hs = h.chunks_exact(LANES);
+    let mut ls = l.chunks_exact(LANES);
+    let mut cs = pc.chunks_exact(LANES);
+    for (((o, h), l), c) in (&mut out).zip(&mut hs).zip(&mut ls).zip(&mut cs) {
+        for k in 0..LANES {
+            o[k] = (h[k] - l[k]).max((h[k] - c[k]).abs()).max((l[k] - c[k]).abs());
+        }
+    }
+    let tail = out.into_remainder().iter_mut().zip(hs.remainder()).zip(ls.remainder()).zip(cs.remainder());
+    for (((o, h), l), c) in tail {
+        *o = (h - l).max((h - c).abs()).max((l - c).abs());
+    }
+    tr
+}
+
+/// Wilder ATR; 0 before the first full `period`.
+pub fn atr(highs: &[f64], lows: &[f64], closes: &[f64], period: usize) -> Vec<f64> {
+    let tr = true_range(highs, lows, closes);
+    let n = tr.len();
+    let mut out = vec![0.0; n];
+    if period == 0 || n < period {
+        return out;
+    }
+    let p = period as f64;
+    let mut prev = tr[..period].iter().sum::<f64>() / p;
+    out[period - 1] = prev;
+    let (keep, alpha) = ((p - 1.0) / p, 1.0 / p);
+    for (o, t) in out[period..].iter_mut().zip(&tr[period..]) {
+        prev = prev * keep + t * alpha;
+        *o = prev;
+    }
+    out
+}
+
+/// Simple moving average; 0 before the first full window.
+pub fn sma(data: &[f64], period: usize) -> Vec<f64> {
+    rolling(data, period, false).0
+}
+
+/// Rolling mean and population standard deviation (Bollinger convention);
+/// both 0 before the first full window.
+pub fn rolling_mean_std(data: &[f64], period: usize) -> (Vec<f64>, Vec<f64>) {
+    let (mean, std) = rolling(data, period, true);
+    (mean, std.unwrap_or_default())
+}
+
+/// Sliding sums of `x − anchor` (and its square), where the anchor is a
+/// recent value so the squares stay small and cancellation stays mild.
+fn rolling(data: &[f64], period: usize, with_std: bool) -> (Vec<f64>, Option<Vec<f64>>) {
+    let n = data.len();
+    let mut mean = vec![0.0; n];
+    let mut std = with_std.then(|| vec![0.0; n]);
+    if period == 0 || n < period {
+        return (mean, std);
+    }
+    let p = period as f64;
+    let (mut anchor, mut s1, mut s2) = (0.0, 0.0, 0.0);
+    for i in period - 1..n {
+        let start = i + 1 - period;
+        if start.is_multiple_of(RESYNC) {
+            anchor = data[start];
+            s1 = 0.0;
+            s2 = 0.0;
+            for &x in &data[start..=i] {
+                let d = x - anchor;
+                s1 += d;
+                s2 += d * d;
+            }
+        } else {
+            let (inn, out) = (data[i] - anchor, data[start - 1] - anchor);
+            s1 += inn - out;
+            s2 += inn * inn - out * out;
+        }
+        let m = s1 / p;
+        mean[i] = anchor + m;
+        if let Some(std) = std.as_mut() {
+            std[i] = (s2 / p - m * m).max(0.0).sqrt();
+        }
+    }
+    (mean, std)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(n: usize) -> Vec<f64> {
+        let mut x = 0x2545F4914F6CDD1Du64;
+        let mut p = 20_000.0;
+        (0..n).map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            p *= 1.0 + ((x % 2001) as f64 - 1000.0) / 100_000.0;
+            p
+        }).collect()
+    }
+
+    #[test]
+    fn test_rolling_matches_per_window_loop() {
+        let data = walk(10_000);
+        let (mean, std) = rolling_mean_std(&data, 20);
+        assert_eq!(mean[18], 0.0);
+        for i in (19..data.len()).step_by(97).chain([data.len() - 1]) {
+            let w = &data[i - 19..=i];
+            let m = w.iter().sum::<f64>() / 20.0;
+            let s = (w.iter().map(|x| (x - m).powi(2)).sum::<f64>() / 20.0).sqrt();
+            assert!((mean[i] - m).abs() < 1e-8, "mean at {}: {} vs {}", i, mean[i], m);
+            assert!((std[i] - s).abs() < 1e-6, "std at {}: {} vs {}", i, std[i], s);
+        }
+        assert_eq!(sma(&data, 20), mean);
+        assert_eq!(sma(&[1.0, 2.0], 3), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_chunked_passes_match_scalar() {
+        // Odd lengths exercise the chunk remainders.
+        let closes = walk(1_003);
+        let highs: Vec<f64> = closes.iter().map(|c| c * 1.004).collect();
+        let lows: Vec<f64> = closes.iter().map(|c| c * 0.995).collect();
+        let tr = true_range(&highs, &lows, &closes);
+        for i in 1..closes.len() {
+            let expect = (highs[i] - lows[i]).max((highs[i] - closes[i - 1]).abs()).max((lows[i] - closes[i - 1]).abs());
+            assert_eq!(tr[i], expect);
+        }
+        let r = rsi(&closes, 14);
+        assert_eq!(r[13], 50.0);
+        // Same recurrence as the textbook divide-every-bar form, to rounding.
+        let (mut g, mut l) = (0.0, 0.0);
+        for i in 1..=14 {
+            let d = closes[i] - closes[i - 1];
+            if d > 0.0 { g += d } else { l -= d }
+        }
+        let (mut g, mut l) = (g / 14.0, l / 14.0);
+        for i in 15..closes.len() {
+            let d = closes[i] - closes[i - 1];
+            g = (g * 13.0 + d.max(0.0)) / 14.0;
+            l = (l * 13.0 + (-d).max(0.0)) / 14.0;
+            assert!((r[i] - (100.0 - 100.0 / (1.0 + g / l))).abs() < 1e-9);
+        }
+        assert!(r[14..].iter().all(|v| (0.0..=100.0).contains(v)));
+        assert_eq!(rsi(&[1.0, 2.0, 3.0, 4.0], 3)[3], 100.0);
+        let e = ema(&closes, 9);
+        assert!(e[7].is_nan());
+        assert_eq!(e[8], closes[..9].iter().sum::<f64>() / 9.0);
+        assert_eq!(atr(&highs, &lows, &closes, 14)[12], 0.0);
+    }
+}
@@ -0,0 +1,2077 @@
+//! Capital Guard engine core.
+//!
+//! Everything the engine computes lives here; the `capital-guard-engine`
+//! binary is a thin shim that parses CLI flags and feeds JSON requests to
+//! [`handle_message`]. Rust callers can skip the JSON layer entirely through
+//! the typed functions in [`api`].
+
+pub mod utils;
+pub mod kernels;
+pub mod indicators;
+pub mod cancel;
+pub mod progress;
+mod candle_file;
+mod guardrails;
+mod validate;
+mod plugins;
+mod datasets;
+mod data_check;
+mod timezone;
+pub mod calendar;
+pub mod money;
+pub mod instruments;
+pub mod vol_target;
+pub mod hedge;
+pub mod equity_filter;
+pub mod exits;
+pub mod liquidity;
+pub mod events;
+mod tick_candles;
+mod bar_transform;
+mod lttb;
+mod align;
+mod returns;
+mod rolling_beta;
+mod performance_report;
+mod strategy_payoff;
+pub mod config;
+pub mod strategy;
+pub mod state;
+pub mod server;
+pub mod backtest;
+pub mod signals;
+pub mod broker;
+pub mod broker_icici;
+pub mod broker_zerodha;
+pub mod broker_upstox;
+pub mod oms;
+pub mod alerts;
+pub mod market_data;
+pub mod options_data;
+mod risk;
+mod greeks;
+mod scan;
+mod optimize;
+mod walk_forward;
+mod advanced_signals;
+mod iv_surface;
+mod monte_carlo;
+mod portfolio_opt;
+mod options_strategy;
+mod correlation;
+mod feature_store;
+mod multi_timeframe;
+mod ml_scorer;
+pub mod exec_algo;
+pub mod slippage;
+mod slippage_estimate;
+mod target_odds;
+pub mod position_sizing;
+pub mod live_executor;
+pub mod premarket;
+pub mod universe;
+pub mod rate_limiter;
+pub mod news_sentiment;
+pub mod futures_scanner;
+pub mod continuous_scanner;
+pub mod strategy_performance;
+pub mod smart_executor;
+pub mod tick_aggregator;
+mod strategy_discovery;
+mod execution_analytics;
+mod signal_ranker;
+mod paper_live_bridge;
+mod orderbook_analyzer;
+mod oi_analysis;
+mod chain_analysis;
+mod gex;
+mod skew_history;
+mod realized_vol;
+mod range_stats;
+mod expiry_day;
+mod pop;
+mod strategy_suggest;
+mod iv_crush;
+mod option_roll;
+mod basket_index;
+mod wheel;
+mod grid;
+mod portfolio_backtest;
+mod compare;
+pub mod portfolio;
+pub mod paper_orders;
+mod allocate;
+mod exposure;
+mod rebalance;
+mod pnl_attribution;
+mod regime;
+mod pairs;
+mod stat_tests;
+mod feature_export;
+mod ensemble;
+mod scenario;
+mod strategy_allocation;
+mod trade_quality;
+mod trade_replay;
+mod stream;
+mod replay;
+pub mod correlation_guard;
+pub mod api;
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn, error};
+
+use crate::state::AppState;
+use crate::broker::{OrderRequest, OrderSide, OrderType, ProductType, OrderStatus};
+use crate::alerts::{AlertSeverity, AlertType};
+
+/// Wire protocol revision; bump when the request/response envelope changes.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Optional envelope features a host can probe for via the `version` command.
+pub const CAPABILITIES: &[&str] = &[
+    "batch", "parallel_batch", "serve_mode", "min_version", "timeout_ms", "cancel", "candles_file", "progress", "on_limit", "precision", "plugins", "datasets", "timezone", "calendar", "bar_transform", "downsample_points", "streaming", "instruments",
+];
+
+/// Every command `dispatch` accepts, in dispatch order; the `version` reply
+/// lists them so hosts can probe for a command before sending it.
+pub const COMMANDS: &[&str] = &[
+    "backtest", "portfolio_backtest", "compare", "signals", "risk", "greeks", "scan",
+    "estimate_slippage", "live_scan", "optimize", "walk_forward", "strategy_discovery",
+    "advanced_signals", "iv_surface", "monte_carlo", "optimize_portfolio", "options_strategy",
+    "correlation", "correlation_guard", "feature_store", "multi_timeframe_scan", "ml_score",
+    "strategy_performance", "smart_executor", "execution_analytics", "signal_ranker",
+    "orderbook_analyze", "paper_live_bridge", "portfolio", "orders", "allocate", "exposure_check",
+    "rebalance", "pnl_attribution", "regime", "pairs", "stat_tests", "features", "ensemble", "scenario",
+    "strategy_allocation", "trade_quality", "replay_trades", "oi_analysis", "max_pain", "pcr",
+    "chain_analysis", "gex", "skew_history", "realized_vol", "range_stats", "expiry_day", "pop",
+    "suggest_strategies", "iv_crush", "suggest_rolls", "basket_index", "ml_scan", "portfolio_snapshot",
+    "list_positions", "list_strategies", "health", "validate", "data_check", "calendar",
+    "ticks_to_candles", "bar_transform", "align", "returns", "rolling_beta", "performance_report",
+    "strategy_payoff", "register_plugin", "unregister_plugin", "list_plugins", "load_dataset",
+    "drop_dataset", "list_datasets", "subscribe", "unsubscribe", "push_candle", "subscriptions",
+    "replay_start", "replay_step", "replay_seek", "replay_stop", "plugin", "cancel", "version",
+    "kill_switch", "kill_switch_off", "audit_log", "execute_signals", "premarket_scan",
+    "premarket_execute", "premarket_status", "scan_sector", "scan_futures", "scan_news", "scan_status",
+    "ml_retrain", "universe_info", "refresh_universe", "oms_submit_order", "oms_cancel_order",
+    "oms_modify_order", "oms_cancel_all", "oms_orders", "oms_reconcile", "alerts", "alert_acknowledge",
+    "alert_counts", "broker_init_session", "broker_refresh_status",
+];
+
+#[derive(Deserialize, Default)]
+pub struct Request {
+    pub id: Option<String>,
+    pub command: String,
+    pub data: serde_json::Value,
+    /// Deadline for cancellable commands; they return partial results with `cancelled: true`.
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Emit NDJSON progress events while backtest/optimize/walk_forward run.
+    #[serde(default)]
+    pub progress: bool,
+    /// Per-request override of `limits.on_exceed` ("error" or "degrade").
+    #[serde(default)]
+    pub on_limit: Option<String>,
+    /// Round every fractional number in `data` to this many decimals. Output
+    /// object keys are always sorted, so equal inputs give byte-equal replies.
+    #[serde(default)]
+    pub precision: Option<u32>,
+    /// IANA zone (or "exchange" for `market.timezone`) that candle/tick
+    /// timestamps are rewritten into, in the reply too. Unset, sessions still
+    /// follow `market.timezone` but replies echo timestamps as sent.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Thin response series longer than this with LTTB (see `lttb`).
+    #[serde(default)]
+    pub downsample_points: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct Response {
+    pub id: Option<String>,
+    pub success: bool,
+    pub data: serde_json::Value,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ResponseMeta>,
+}
+
+/// Diagnostics attached to every dispatched response, so hosts can spot
+/// slow requests and adapt (e.g. downsample candles before retrying).
+#[derive(Serialize, Debug, Clone)]
+pub struct ResponseMeta {
+    /// Time spent loading `candles_file` inputs.
+    pub load_ms: f64,
+    pub compute_ms: f64,
+    pub total_ms: f64,
+    /// Candles in the request, top level plus per-symbol entries.
+    pub candles: usize,
+    /// Rough in-memory size of those candles once parsed.
+    pub est_input_mb: f64,
+    /// Process peak resident set size (Linux only). Process-wide, so it is an
+    /// upper bound for this request rather than its own footprint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_rss_mb: Option<f64>,
+    /// Input diagnostics and any guardrail degradation applied.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Several requests in one round trip. Responses come back in input order;
+/// `parallel` runs them on the rayon pool.
+#[derive(Deserialize)]
+pub struct BatchRequest {
+    pub commands: Vec<Request>,
+    #[serde(default)]
+    pub parallel: bool,
+}
+
+/// What goes back over the wire: one response, or an array for a batch.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Reply {
+    Single(Response),
+    Batch(Vec<Response>),
+}
+
+/// Read one JSON request per line and write exactly one response line per
+/// request, flushing after each so the host can pipeline requests by `id`.
+/// Returns the number of requests answered when the input reaches EOF.
+pub fn serve_ndjson<R: std::io::BufRead, W: std::io::Write + Send>(state: &Arc<AppState>, reader: R, writer: W) -> usize {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let writer = std::sync::Mutex::new(writer);
+    let answered = AtomicUsize::new(0);
+
+    let emit = |reply: &Reply| {
+        match serde_json::to_string(reply) {
+            Ok(out) => {
+                let mut w = match writer.lock() {
+                    Ok(w) => w,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                if writeln!(w, "{}", out).and_then(|_| w.flush()).is_ok() {
+                    answered.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            Err(e) => error!("Failed to serialize response: {}", e),
+        }
+    };
+
+    // Requests run on a bounded pool of workers so a `cancel` line can reach
+    // a long-running command; replies are matched by `id`, not by order.
+    let (jobs, queue) = std::sync::mpsc::channel::<serde_json::Value>();
+    let queue = std::sync::Mutex::new(queue);
+    std::thread::scope(|scope| {
+        for _ in 0..state.config.server.max_workers.max(1) {
+            let (queue, emit) = (&queue, &emit);
+            scope.spawn(move || loop {
+                let next = match queue.lock() {
+                    Ok(q) => q.recv(),
+                    Err(poisoned) => poisoned.into_inner().recv(),
+                };
+                match next {
+                    Ok(msg) => emit(&handle_message(msg, state)),
+                    Err(_) => break,
+                }
+            });
+        }
+        for line in reader.lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(_) => break,
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() { continue; }
+
+            let msg = match serde_json::from_str::<serde_json::Value>(trimmed) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    emit(&Reply::Single(Response {
+                        id: None,
+                        success: false,
+                        data: serde_json::Value::Null,
+                        error: Some(format!("Invalid JSON: {}", e)),
+                        meta: None,
+                    }));
+                    continue;
+                }
+            };
+            if runs_inline(&msg) {
+                emit(&handle_message(msg, state));
+            } else if let Err(returned) = jobs.send(msg) {
+                emit(&handle_message(returned.0, state));
+            }
+        }
+        // Closing the queue lets the workers drain it and exit.
+        drop(jobs);
+    });
+    answered.load(Ordering::SeqCst)
+}
+
+/// Commands that only compute over their own request data, so serve mode may
+/// run them on the worker pool in any order. `signals` qualifies only without
+/// `plugins`, which reads the plugin registry.
+const POOLED_COMMANDS: &[&str] = &[
+    "backtest", "portfolio_backtest", "compare", "signals", "risk", "greeks", "scan", "estimate_slippage",
+    "optimize", "walk_forward", "advanced_signals", "iv_surface", "monte_carlo", "optimize_portfolio",
+    "options_strategy", "correlation", "correlation_guard", "feature_store", "multi_timeframe_scan",
+    "ml_score", "ml_scan", "smart_executor", "signal_ranker", "orderbook_analyze", "allocate",
+    "exposure_check", "rebalance", "pnl_attribution", "regime", "pairs", "stat_tests", "features",
+    "ensemble", "scenario", "strategy_allocation", "trade_quality", "replay_trades", "oi_analysis",
+    "max_pain", "pcr", "chain_analysis", "gex", "skew_history", "realized_vol", "range_stats",
+    "expiry_day", "pop", "suggest_strategies", "iv_crush", "suggest_rolls", "basket_index", "validate",
+    "data_check", "calendar", "ticks_to_candles", "bar_transform", "align", "returns", "rolling_beta",
+    "performance_report", "strategy_payoff",
+];
+
+/// Whether a serve-mode message runs on the reader thread, in arrival order.
+/// Everything outside `POOLED_COMMANDS` does: cancels must not queue behind
+/// the work they cancel, and kill-switch, order, ledger, stream and registry
+/// commands touch shared state, so a later line must see an earlier one's
+/// effect. A batch runs inline when any of its commands would.
+fn runs_inline(msg: &serde_json::Value) -> bool {
+    let inline = |m: &serde_json::Value| match m.get("command").and_then(|c| c.as_str()) {
+        Some("signals") => m.get("data").and_then(|d| d.get("plugins")).is_some(),
+        Some(command) => !POOLED_COMMANDS.contains(&command),
+        None => true,
+    };
+    match msg.get("commands").and_then(|c| c.as_array()) {
+        Some(commands) => commands.iter().any(inline),
+        None => inline(msg),
+    }
+}
+
+/// Entry point for a decoded wire message: a `{"commands": [...]}` batch or a
+/// single request. Malformed requests still echo their `id` when present.
+pub fn handle_message(msg: serde_json::Value, state: &Arc<AppState>) -> Reply {
+    let id = msg.get("id").and_then(|v| v.as_str()).map(String::from);
+    if let Some(min) = msg.get("min_version").and_then(|v| v.as_str()) {
+        if let Err(e) = check_min_version(min) {
+            return Reply::Single(Response {
+                id,
+                success: false,
+                data: serde_json::json!({
+                    "engine_version": env!("CARGO_PKG_VERSION"),
+                    "min_version": min,
+                    "protocol_version": PROTOCOL_VERSION,
+                }),
+                error: Some(e),
+                meta: None,
+            });
+        }
+    }
+    if msg.get("commands").is_some() {
+        return match serde_json::from_value::<BatchRequest>(msg) {
+            Ok(batch) => Reply::Batch(handle_batch(batch, state)),
+            Err(e) => Reply::Single(Response { id, success: false, data: serde_json::Value::Null,
+                error: Some(format!("Invalid batch request: {}", e)),
+                meta: None, }),
+        };
+    }
+    match serde_json::from_value::<Request>(msg) {
+        Ok(req) => Reply::Single(handle_request(req, state)),
+        Err(e) => Reply::Single(Response { id, success: false, data: serde_json::Value::Null,
+            error: Some(format!("Invalid JSON input: {}", e)),
+            meta: None, }),
+    }
+}
+
+fn parse_semver(v: &str) -> Option<(u64, u64, u64)> {
+    let core = v.trim().trim_start_matches('v').split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+/// Reject requests that need a newer engine than this binary.
+fn check_min_version(min: &str) -> Result<(), String> {
+    let required = parse_semver(min).ok_or_else(|| format!("Invalid min_version: {}", min))?;
+    let current = parse_semver(env!("CARGO_PKG_VERSION")).unwrap_or((0, 0, 0));
+    if current < required {
+        return Err(format!(
+            "Engine version {} is older than required {}",
+            env!("CARGO_PKG_VERSION"), min
+        ));
+    }
+    Ok(())
+}
+
+pub fn handle_batch(batch: BatchRequest, state: &Arc<AppState>) -> Vec<Response> {
+    info!(commands = batch.commands.len(), parallel = batch.parallel, "Handling batch");
+    if batch.parallel {
+        use rayon::prelude::*;
+        batch.commands.into_par_iter().map(|req| handle_request(req, state)).collect()
+    } else {
+        batch.commands.into_iter().map(|req| handle_request(req, state)).collect()
+    }
+}
+
+pub fn handle_request(mut req: Request, state: &Arc<AppState>) -> Response {
+    let id = req.id.clone();
+    let cmd = req.command.clone();
+    let precision = req.precision;
+    let downsample_points = req.downsample_points;
+    let cancel_token = cancel::CancelToken::new(req.timeout_ms);
+    let _in_flight = id.as_deref().map(|i| cancel::track(i, &cancel_token));
+    let progress = progress::Progress::for_request(id.clone(), &cmd, req.progress);
+    let started = std::time::Instant::now();
+    let mut originals = timezone::Originals::new();
+    // `validate` loads candle files itself so it can report failures as problems;
+    // dataset commands own the `dataset_id` key rather than referencing one.
+    let loaded = match cmd.as_str() {
+        "validate" => Ok(()),
+        "load_dataset" | "drop_dataset" => candle_file::resolve(&mut req.data),
+        _ => candle_file::resolve(&mut req.data)
+            .and_then(|_| events::resolve(&mut req.data))
+            .and_then(|_| datasets::resolve(&mut req.data)),
+    }
+    .and_then(|_| {
+        // Session logic runs on exchange-local time; the reply keeps the
+        // caller's timestamps unless they asked for a zone.
+        let zone = match req.timezone.as_deref() {
+            None | Some("exchange") => state.config.market.timezone.as_str(),
+            Some(zone) => zone,
+        };
+        timezone::localize(&mut req.data, timezone::parse_zone(zone)?, &mut originals);
+        if req.timezone.is_some() {
+            originals.clear();
+        }
+        // Renko/range/P&F specs swap in their bars before any command sees the candles.
+        if cmd != "validate" {
+            bar_transform::resolve(&mut req.data)?;
+        }
+        Ok(())
+    });
+    let load_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let candles = candle_count(&req.data);
+    let degrade = req.on_limit.as_deref().unwrap_or(state.config.limits.on_exceed.as_str()) == "degrade";
+    let mut warnings = Vec::new();
+
+    let prepared: Result<Vec<String>, (String, serde_json::Value)> = match loaded {
+        Err(e) => {
+            warn!(command = %cmd, id = ?id, error = %e, "Failed to load candles");
+            Err((e, serde_json::Value::Null))
+        }
+        Ok(()) => guardrails::apply(&mut req.data, &state.config.limits, degrade).map_err(|e| {
+            let msg = e.message();
+            warn!(command = %cmd, id = ?id, "{}", msg);
+            (msg, serde_json::json!({ "limit_exceeded": e }))
+        }),
+    };
+
+    let mut response = match prepared {
+        Err((msg, detail)) => {
+            Response { id: id.clone(), success: false, data: detail, error: Some(msg), meta: None }
+        }
+        Ok(degraded) => {
+            info!(command = %cmd, id = ?id, candles, load_ms = utils::round2(load_ms), "Handling request");
+            warnings.extend(degraded);
+            warnings.extend(input_warnings(&cmd, &req.data));
+            for warning in &warnings {
+                warn!(command = %cmd, id = ?id, "{}", warning);
+            }
+            dispatch(req, state, &cancel_token, &progress)
+        }
+    };
+    if !originals.is_empty() {
+        timezone::restore(&mut response.data, &originals);
+    }
+
+    if let (Some(points), true) = (downsample_points, response.success) {
+        warnings.extend(lttb::apply(&mut response.data, points));
+    }
+    if let Some(decimals) = precision {
+        utils::round_json(&mut response.data, decimals);
+    }
+
+    let total_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let compute_ms = utils::round2(total_ms - load_ms);
+    match &response.error {
+        None => info!(command = %cmd, id = ?id, compute_ms, "Request completed successfully"),
+        Some(e) => warn!(command = %cmd, id = ?id, compute_ms, error = %e, "Request failed"),
+    }
+    response.meta = Some(ResponseMeta {
+        load_ms: utils::round2(load_ms),
+        compute_ms,
+        total_ms: utils::round2(total_ms),
+        candles,
+        est_input_mb: utils::round3(candles as f64 * EST_CANDLE_BYTES / (1024.0 * 1024.0)),
+        peak_rss_mb: peak_rss_mb(),
+        warnings,
+    });
+    response
+}
+
+/// Parsed `Candle` plus a typical timestamp string allocation.
+pub(crate) const EST_CANDLE_BYTES: f64 = (std::mem::size_of::<utils::Candle>() + 32) as f64;
+
+/// VmHWM from /proc/self/status.
+fn peak_rss_mb() -> Option<f64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb: f64 = status.lines()
+        .find(|l| l.starts_with("VmHWM:"))?
+        .split_whitespace().nth(1)?
+        .parse().ok()?;
+    Some(utils::round2(kb / 1024.0))
+}
+
+fn dispatch(
+    req: Request,
+    state: &Arc<AppState>,
+    cancel_token: &cancel::CancelToken,
+    progress: &progress::Progress,
+) -> Response {
+    let id = req.id.clone();
+    let cmd = req.command.as_str();
+
+    let result = match cmd {
+        "backtest" => backtest::run_with_progress(req.data, progress),
+        "portfolio_backtest" => portfolio_backtest::compute(req.data),
+        "compare" => compare::compute(req.data),
+        "signals" => plugins::with_plugin_series(req.data, signals::compute),
+        "risk" => risk::compute(req.data),
+        "greeks" => greeks::compute(req.data),
+        "scan" => scan::compute(req.data),
+        "estimate_slippage" => slippage_estimate::compute(req.data),
+
+        "live_scan" => {
+            #[derive(Deserialize)]
+            struct LiveScanInput {
+                symbols: Vec<String>,
+                #[serde(default = "default_interval")]
+                interval: String,
+                #[serde(default = "default_lookback_days")]
+                lookback_days: i64,
+                #[serde(default)]
+                aggressiveness: Option<String>,
+            }
+            fn default_interval() -> String { "1day".into() }
+            fn default_lookback_days() -> i64 { 60 }
+
+            let input: LiveScanInput = match serde_json::from_value(req.data) {
+                Ok(v) => v,
+                Err(e) => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some(format!("Invalid live_scan input: {}", e)),
+                    meta: None, },
+            };
+            if input.symbols.is_empty() {
+                return Response { id, success: true,
+                    data: serde_json::json!({ "signals": [] }), error: None,
+                    meta: None, };
+            }
+
+            let bridge_url = state.config.broker.icici.bridge_url.clone();
+            if bridge_url.is_empty() {
+                return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some("Bridge URL not configured — cannot fetch historical data".into()),
+                    meta: None, };
+            }
+
+            let to_date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+            let from_date = (chrono::Utc::now() - chrono::Duration::days(input.lookback_days))
+                .format("%Y-%m-%d").to_string();
+
+            let mut sym_data_list = Vec::new();
+            for symbol in &input.symbols {
+                let hist = broker_icici::bridge_get_historical(
+                    &bridge_url, symbol, &input.interval, &from_date, &to_date,
+                );
+                let mut candles_json: Vec<serde_json::Value> = match hist {
+                    Ok(val) => {
+                        if let Some(arr) = val.get("data").and_then(|d| d.as_array()) {
+                            arr.clone()
+                        } else if let Some(arr) = val.as_array() {
+                            arr.clone()
+                        } else {
+                            warn!(symbol = %symbol, "No candle data from bridge");
+                            continue;
+                        }
+                    }
+                    Err(e) => {
+                        warn!(symbol = %symbol, error = %e, "Failed to fetch historical from bridge");
+                        continue;
+                    }
+                };
+
+                if let Some(tick) = state.live_prices.get_tick(symbol) {
+                    candles_json.push(serde_json::json!({
+                        "timestamp": tick.timestamp,
+                        "open": tick.open,
+                        "high": tick.high,
+                        "low": tick.low,
+                        "close": tick.close,
+                        "volume": tick.volume,
+                    }));
+                }
+
+                sym_data_list.push(serde_json::json!({
+                    "symbol": symbol,
+                    "candles": candles_json,
+                }));
+            }
+
+            let mut scan_input = serde_json::json!({ "symbols": sym_data_list });
+            if let Some(agg) = &input.aggressiveness {
+                scan_input["aggressiveness"] = serde_json::json!(agg);
+            }
+            scan::compute(scan_input)
+        }
+
+        "optimize" => optimize::compute_cancellable(req.data, cancel_token, progress),
+        "walk_forward" => walk_forward::compute_cancellable(req.data, cancel_token, progress),
+        "strategy_discovery" => strategy_discovery::compute(req.data),
+        "advanced_signals" => advanced_signals::compute(req.data),
+        "iv_surface" => iv_surface::compute(req.data),
+        "monte_carlo" => monte_carlo::compute(req.data),
+        "optimize_portfolio" => portfolio_opt::compute(req.data),
+        "options_strategy" => options_strategy::compute(req.data),
+        "correlation" => correlation::compute(req.data),
+        "correlation_guard" => correlation_guard::compute(req.data),
+        "feature_store" => feature_store::compute(req.data),
+        "multi_timeframe_scan" => multi_timeframe::compute(req.data),
+        "ml_score" => ml_scorer::compute(req.data),
+        "strategy_performance" => strategy_performance::compute(req.data),
+        "smart_executor" => smart_executor::compute(req.data),
+        "execution_analytics" => execution_analytics::compute(req.data),
+        "signal_ranker" => signal_ranker::compute(req.data),
+        "orderbook_analyze" => orderbook_analyzer::compute(req.data),
+        "paper_live_bridge" => paper_live_bridge::compute(req.data),
+        "portfolio" => portfolio::compute(req.data),
+        "orders" => paper_orders::compute(req.data),
+        "allocate" => allocate::compute(req.data),
+        "exposure_check" => exposure::compute(req.data),
+        "rebalance" => rebalance::compute(req.data),
+        "pnl_attribution" => pnl_attribution::compute(req.data),
+        "regime" => regime::compute(req.data),
+        "pairs" => pairs::compute(req.data),
+        "stat_tests" => stat_tests::compute(req.data),
+        "features" => feature_export::compute(req.data),
+        "ensemble" => ensemble::compute(req.data),
+        "scenario" => scenario::compute(req.data),
+        "strategy_allocation" => strategy_allocation::compute(req.data),
+        "trade_quality" => trade_quality::compute(req.data),
+        "replay_trades" => trade_replay::compute(req.data),
+        "oi_analysis" => oi_analysis::compute(req.data),
+        "max_pain" => oi_analysis::max_pain(req.data),
+        "pcr" => oi_analysis::pcr(req.data, &state.config.options),
+        "chain_analysis" => chain_analysis::compute(req.data),
+        "gex" => gex::compute(req.data),
+        "skew_history" => skew_history::compute(req.data),
+        "realized_vol" => realized_vol::compute(req.data),
+        "range_stats" => range_stats::compute(req.data),
+        "expiry_day" => expiry_day::compute(req.data),
+        "pop" => pop::compute(req.data),
+        "suggest_strategies" => strategy_suggest::compute(req.data),
+        "iv_crush" => iv_crush::compute(req.data),
+        "suggest_rolls" => option_roll::compute(req.data),
+        "basket_index" => basket_index::compute(req.data),
+
+        "ml_scan" => {
+            let ml_weights = req.data.get("ml_weights").cloned();
+            let scan_data = req.data.clone();
+
+            let scan_result = match scan::compute(scan_data) {
+                Ok(v) => v,
+                Err(e) => return Response { id, success: false, data: serde_json::Value::Null, error: Some(e), meta: None },
+            };
+
+            let signals = match scan_result.get("signals").and_then(|v| v.as_array()) {
+                Some(arr) if !arr.is_empty() => arr.clone(),
+                _ => return Response { id, success: true, data: scan_result, error: None, meta: None },
+            };
+
+            let symbols_arr = match req.data.get("symbols").and_then(|v| v.as_array()) {
+                Some(a) => a,
+                None => return Response { id, success: true, data: scan_result, error: None, meta: None },
+            };
+
+            let mut features_by_symbol: std::collections::HashMap<String, Vec<Vec<f64>>> =
+                std::collections::HashMap::new();
+
+            for sym_obj in symbols_arr {
+                let symbol = match sym_obj.get("symbol").and_then(|v| v.as_str()) {
+                    Some(s) => s.to_string(),
+                    None => continue,
+                };
+                let candles = match sym_obj.get("candles") {
+                    Some(c) if c.is_array() => c.clone(),
+                    _ => continue,
+                };
+                let candle_count = candles.as_array().map(|a| a.len()).unwrap_or(0);
+                if candle_count < 30 { continue; }
+
+                let fs_input = serde_json::json!({
+                    "command": "extract_features",
+                    "candles": candles,
+                });
+                if let Ok(fs_result) = feature_store::compute(fs_input) {
+                    if let Some(data) = fs_result.get("features")
+                        .and_then(|f| f.get("data"))
+                        .and_then(|d| d.as_array())
+                    {
+                        let rows: Vec<Vec<f64>> = data.iter()
+                            .filter_map(|row| {
+                                row.as_array().map(|arr| {
+                                    arr.iter()
+                                        .filter_map(|v| v.as_f64())
+                                        .collect()
+                                })
+                            })
+                            .collect();
+                        if !rows.is_empty() {
+                            features_by_symbol.insert(symbol, rows);
+                        }
+                    }
+                }
+            }
+
+            let mut enriched_signals: Vec<serde_json::Value> = Vec::new();
+
+            for sig in &signals {
+                let mut enriched = sig.clone();
+                let symbol = sig.get("symbol").and_then(|v| v.as_str()).unwrap_or("");
+
+                let raw_features = features_by_symbol.get(symbol)
+                    .and_then(|rows| rows.last())
+                    .cloned()
+                    .unwrap_or_default();
+
+                let votes = sig.get("votes");
+                let ema_vote = votes.and_then(|v| v.get("ema_crossover")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let rsi_vote = votes.and_then(|v| v.get("rsi")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let macd_vote = votes.and_then(|v| v.get("macd")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let st_vote = votes.and_then(|v| v.get("supertrend")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let bb_vote = votes.and_then(|v| v.get("bollinger")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let vwap_vote = votes.and_then(|v| v.get("vwap")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let mom_vote = votes.and_then(|v| v.get("momentum")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let vol_vote = votes.and_then(|v| v.get("volume")).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let composite = sig.get("confidence").and_then(|v| v.as_f64()).unwrap_or(0.5);
+
+                let feature_row = serde_json::json!({
+                    "ema_vote": ema_vote,
+                    "rsi_vote": rsi_vote,
+                    "macd_vote": macd_vote,
+                    "supertrend_vote": st_vote,
+                    "bollinger_vote": bb_vote,
+                    "vwap_vote": vwap_vote,
+                    "momentum_vote": mom_vote,
+                    "volume_vote": vol_vote,
+                    "composite_score": composite,
+                    "regime": 1.0,
+                    "hour_of_day": chrono::Utc::now().format("%H").to_string().parse::<f64>().unwrap_or(12.0),
+                    "day_of_week": chrono::Utc::now().format("%u").to_string().parse::<f64>().unwrap_or(3.0),
+                    "raw_features": raw_features,
+                });
+
+                enriched["ml_features"] = feature_row.clone();
+
+                if let Some(weights) = &ml_weights {
+                    let predict_input = serde_json::json!({
+                        "command": "predict",
+                        "features": [feature_row],
+                        "weights": weights,
+                    });
+                    if let Ok(pred_result) = ml_scorer::compute(predict_input) {
+                        if let Some(scores) = pred_result.get("scores").and_then(|v| v.as_array()) {
+                            if let Some(ml_score) = scores.first().and_then(|v| v.as_f64()) {
+                                enriched["ml_score"] = serde_json::json!(ml_score);
+                                let blended = composite * 0.6 + ml_score * 0.4;
+                                enriched["blended_confidence"] = serde_json::json!(
+                                    (blended * 1000.0).round() / 1000.0
+                                );
+                            }
+                        }
+                    }
+                }
+
+                enriched_signals.push(enriched);
+            }
+
+            enriched_signals.sort_by(|a, b| {
+                let key_a = a.get("blended_confidence")
+                    .or_else(|| a.get("confidence"))
+                    .and_then(|v| v.as_f64()).unwrap_or(0.0);
+                let key_b = b.get("blended_confidence")
+                    .or_else(|| b.get("confidence"))
+                    .and_then(|v| v.as_f64()).unwrap_or(0.0);
+                key_b.partial_cmp(&key_a).unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+            Ok(serde_json::json!({
+                "signals": enriched_signals,
+                "ml_enhanced": ml_weights.is_some(),
+                "features_extracted": features_by_symbol.len(),
+            }))
+        }
+
+        "portfolio_snapshot" => {
+            Ok(serde_json::to_value(state.snapshot()).unwrap_or_default())
+        }
+        "list_positions" => {
+            let positions: Vec<_> = state.positions.iter()
+                .map(|entry| entry.value().clone())
+                .collect();
+            Ok(serde_json::to_value(positions).unwrap_or_default())
+        }
+        "list_strategies" => {
+            Ok(serde_json::json!({
+                "strategies": strategy::available_strategies()
+            }))
+        }
+        "health" => {
+            Ok(serde_json::json!({
+                "status": if state.is_killed() { "killed" } else { "healthy" },
+                "uptime_seconds": state.uptime_seconds(),
+                "version": env!("CARGO_PKG_VERSION"),
+                "positions": state.positions.len(),
+                "killed": state.is_killed(),
+            }))
+        }
+
+        "validate" => validate::compute(req.data, &state.config.limits),
+        "data_check" => data_check::compute(req.data),
+        "calendar" => calendar::compute(req.data),
+        "ticks_to_candles" => tick_candles::compute(req.data),
+        "bar_transform" => bar_transform::compute(req.data),
+        "align" => align::compute(req.data),
+        "returns" => returns::compute(req.data),
+        "rolling_beta" => rolling_beta::compute(req.data),
+        "performance_report" => performance_report::compute(req.data),
+        "strategy_payoff" => strategy_payoff::compute(req.data),
+
+        "register_plugin" => plugins::register(req.data),
+        "unregister_plugin" => plugins::unregister(req.data),
+        "list_plugins" => plugins::list(),
+
+        "load_dataset" => datasets::load(req.data, &state.config.limits),
+        "drop_dataset" => datasets::remove(req.data),
+        "list_datasets" => datasets::list(),
+
+        "subscribe" => stream::subscribe(req.data, &state.config),
+        "unsubscribe" => stream::unsubscribe(req.data),
+        "push_candle" => stream::push_candle(req.data, &state.config),
+        "subscriptions" => stream::list(),
+        "replay_start" => replay::start(req.data, &state.config),
+        "replay_step" => replay::step(req.data, &state.config),
+        "replay_seek" => replay::seek(req.data, &state.config),
+        "replay_stop" => replay::stop(req.data),
+        "plugin" => plugins::compute(req.data),
+
+        "cancel" => {
+            let target = req.data.get("request_id").and_then(|v| v.as_str()).unwrap_or("");
+            if target.is_empty() {
+                Err("cancel requires data.request_id".to_string())
+            } else {
+                Ok(serde_json::json!({
+                    "request_id": target,
+                    "cancelled": cancel::cancel_request(target),
+                    "in_flight": cancel::in_flight_ids().len(),
+                }))
+            }
+        }
+
+        "version" => {
+            Ok(serde_json::json!({
+                "engine_version": env!("CARGO_PKG_VERSION"),
+                "protocol_version": PROTOCOL_VERSION,
+                "capabilities": CAPABILITIES,
+                "commands": COMMANDS,
+            }))
+        }
+
+        "kill_switch" => {
+            state.activate_kill_switch();
+            Ok(serde_json::json!({ "killed": true }))
+        }
+        "kill_switch_off" => {
+            state.deactivate_kill_switch();
+            Ok(serde_json::json!({ "killed": false }))
+        }
+        "audit_log" => {
+            Ok(serde_json::to_value(state.get_audit_log()).unwrap_or_default())
+        }
+
+        "execute_signals" => {
+            if state.is_killed() {
+                return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some("Kill switch active — signal execution rejected".into()),
+                    meta: None, };
+            }
+
+            #[derive(Deserialize)]
+            struct ExecInput {
+                #[serde(default)]
+                symbols: Vec<String>,
+                #[serde(default = "default_min_confidence")]
+                min_confidence: f64,
+                #[serde(default = "default_exec_exchange")]
+                exchange: String,
+                #[serde(default = "default_exec_product")]
+                product: String,
+                #[serde(default = "default_exec_qty")]
+                default_qty: i64,
+            }
+            fn default_min_confidence() -> f64 { 0.7 }
+            fn default_exec_exchange() -> String { "NSE".into() }
+            fn default_exec_product() -> String { "intraday".into() }
+            fn default_exec_qty() -> i64 { 1 }
+
+            let input: ExecInput = match serde_json::from_value(req.data) {
+                Ok(v) => v,
+                Err(e) => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some(format!("Invalid execute_signals input: {}", e)),
+                    meta: None, },
+            };
+
+            let all_signals: Vec<crate::state::CachedSignal> = if input.symbols.is_empty() {
+                state.signal_cache.iter()
+                    .map(|entry| entry.value().clone())
+                    .collect()
+            } else {
+                input.symbols.iter()
+                    .flat_map(|s| state.get_cached_signals(s))
+                    .collect()
+            };
+
+            let qualifying: Vec<_> = all_signals.into_iter()
+                .filter(|s| {
+                    if strategy_performance::GLOBAL_TRACKER.is_strategy_retired(&s.strategy) {
+                        return false;
+                    }
+                    let cal = strategy_performance::GLOBAL_TRACKER
+                        .calibrate_confidence(s.confidence, &s.strategy);
+                    cal >= input.min_confidence
+                })
+                .collect();
+
+            let product = match input.product.to_lowercase().as_str() {
+                "delivery" | "cnc" => ProductType::Delivery,
+                _ => ProductType::Intraday,
+            };
+
+            let mut results = Vec::new();
+            for sig in &qualifying {
+                let side = match sig.side.to_lowercase().as_str() {
+                    "sell" | "short" => OrderSide::Sell,
+                    _ => OrderSide::Buy,
+                };
+                let qty = sig.suggested_qty.unwrap_or(input.default_qty);
+
+                let exec_plan = smart_executor::compute(serde_json::json!({
+                    "command": "plan",
+                    "symbol": sig.symbol,
+                    "side": sig.side,
+                    "quantity": qty,
+                    "price": sig.price,
+                    "signal_confidence": sig.confidence,
+                }));
+                let plan_info = exec_plan.as_ref().ok();
+
+                let order_req = OrderRequest {
+                    symbol: sig.symbol.clone(),
+                    exchange: input.exchange.clone(),
+                    side,
+                    order_type: OrderType::Limit,
+                    quantity: qty,
+                    price: Some(sig.price),
+                    trigger_price: sig.stop_loss,
+                    product,
+                    tag: Some(format!("auto:{}", sig.strategy)),
+                    ..Default::default()
+                };
+                let ref_price = state.live_prices.get_ltp(&sig.symbol);
+
+                if let Some(pi) = plan_info {
+                    let algo = pi.get("recommended_algo").and_then(|v| v.as_str()).unwrap_or("direct");
+                    let slippage = pi.get("estimated_slippage_bps").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    state.log_audit("SMART_EXEC_PLAN", Some(&sig.symbol),
+                        &format!("algo={} slippage={:.1}bps qty={}", algo, slippage, qty));
+                }
+
+                match state.oms.submit_order(order_req, Some(sig.strategy.clone()), ref_price) {
+                    Ok(order) => {
+                        if order.status == OrderStatus::Filled && order.filled_qty > 0 {
+                            state.sync_oms_fill(
+                                &order.symbol, &sig.side, order.filled_qty,
+                                order.avg_fill_price, sig.stop_loss, sig.take_profit,
+                            );
+                        }
+                        state.log_audit("SIGNAL_EXECUTED", Some(&sig.symbol),
+                            &format!("strategy={} side={} qty={} price={:.2} conf={:.2}",
+                                sig.strategy, sig.side, qty, sig.price, sig.confidence));
+                        results.push(serde_json::json!({
+                            "symbol": sig.symbol, "status": "submitted",
+                            "order_id": order.internal_id,
+                            "broker_order_id": order.broker_order_id,
+                        }));
+                    }
+                    Err(e) => {
+                        state.log_audit("SIGNAL_EXEC_FAILED", Some(&sig.symbol),
+                            &format!("strategy={} error={}", sig.strategy, e));
+                        state.alert_manager.fire(
+                            AlertType::OrderRejected, AlertSeverity::Warning,
+                            "Signal execution rejected",
+                            &format!("Signal exec rejected for {}: {}", sig.symbol, e),
+                            Some(&sig.symbol), Some(&sig.strategy),
+                        );
+                        results.push(serde_json::json!({
+                            "symbol": sig.symbol, "status": "rejected", "reason": e,
+                        }));
+                    }
+                }
+            }
+
+            Ok(serde_json::json!({
+                "signals_evaluated": qualifying.len(),
+                "orders": results,
+            }))
+        }
+
+        "premarket_scan" => {
+            let scan_state = state.clone();
+            let report = premarket::run_premarket_pipeline(&scan_state);
+            Ok(serde_json::to_value(&report).unwrap_or_default())
+        }
+
+        "premarket_execute" => {
+            let exec_config = state.config.premarket.clone();
+            let results = premarket::execute_queued_signals(state, &exec_config);
+            Ok(serde_json::json!({
+                "executed": results.len(),
+                "submitted": results.iter().filter(|r| r.status == "submitted").count(),
+                "rejected": results.iter().filter(|r| r.status == "rejected").count(),
+                "orders": results,
+            }))
+        }
+
+        "premarket_status" => {
+            let watchlist = state.get_dynamic_watchlist();
+            let cached_signals: Vec<serde_json::Value> = state.signal_cache.iter()
+                .filter(|e| e.key().contains("premarket") || e.key().contains("composite"))
+                .map(|e| {
+                    let s = e.value();
+                    serde_json::json!({
+                        "symbol": s.symbol, "strategy": s.strategy,
+                        "side": s.side, "price": s.price,
+                        "confidence": s.confidence, "suggested_qty": s.suggested_qty,
+                    })
+                })
+                .collect();
+            Ok(serde_json::json!({
+                "scheduler_enabled": state.config.premarket.enabled,
+                "scan_time_ist": state.config.premarket.scan_time_ist,
+                "execute_time_ist": state.config.premarket.execute_time_ist,
+                "auto_execute": state.config.premarket.auto_execute_at_open,
+                "dynamic_watchlist": watchlist,
+                "cached_signals": cached_signals.len(),
+                "signals": cached_signals,
+            }))
+        }
+
+        "scan_sector" => {
+            let sector = req.data.get("sector").and_then(|v| v.as_str()).unwrap_or("");
+            let stocks = state.universe.by_sector(sector);
+            if stocks.is_empty() {
+                Ok(serde_json::json!({ "error": format!("No stocks found for sector '{}'", sector), "sectors_available": state.universe.sector_list() }))
+            } else {
+                let num_stocks = stocks.len();
+                let count = continuous_scanner::run_sector_scan(
+                    state, &state.universe, &state.rate_limiter,
+                    &state.news_store, &state.scan_ledger,
+                    sector, &stocks,
+                );
+                Ok(serde_json::json!({ "sector": sector, "stocks_scanned": num_stocks, "signals_generated": count }))
+            }
+        }
+
+        "scan_futures" => {
+            let bridge_url = state.config.broker.icici.bridge_url.clone();
+            let results = futures_scanner::scan_futures(&state.rate_limiter, &bridge_url, &state.universe);
+            Ok(serde_json::to_value(&results).unwrap_or_default())
+        }
+
+        "scan_news" => {
+            state.news_store.fetch_and_update(&state.rate_limiter, &state.universe);
+            let limit = req.data.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+            let items = state.news_store.recent_items(limit);
+            Ok(serde_json::json!({ "items": items, "total": state.news_store.item_count() }))
+        }
+
+        "scan_status" => {
+            let limit = req.data.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+            let status = continuous_scanner::get_status(&state.scan_ledger, limit);
+            Ok(serde_json::to_value(&status).unwrap_or_default())
+        }
+
+        "ml_retrain" => {
+            let path = "data/ml_training_log.json";
+            let training_data: Vec<serde_json::Value> = std::fs::read_to_string(path)
+                .ok()
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default();
+
+            if training_data.len() < 10 {
+                Ok(serde_json::json!({ "error": "Not enough training data", "samples": training_data.len() }))
+            } else {
+                Ok(serde_json::json!({
+                    "status": "retrain_ready",
+                    "samples_available": training_data.len(),
+                    "message": "Use ml_scorer.train command with the training data"
+                }))
+            }
+        }
+
+        "universe_info" => {
+            let sectors = state.universe.sector_list();
+            let total = state.universe.len();
+            let fno = state.universe.fno_stocks().len();
+            let by_sector: Vec<serde_json::Value> = sectors.iter().map(|s| {
+                serde_json::json!({ "sector": s, "count": state.universe.by_sector(s).len() })
+            }).collect();
+            let by_cap = serde_json::json!({
+                "large": state.universe.by_cap(crate::universe::CapCategory::LargeCap).len(),
+                "mid": state.universe.by_cap(crate::universe::CapCategory::MidCap).len(),
+                "small": state.universe.by_cap(crate::universe::CapCategory::SmallCap).len(),
+            });
+            Ok(serde_json::json!({
+                "total_stocks": total, "fno_stocks": fno,
+                "sectors": by_sector, "by_cap": by_cap,
+                "dynamic": true,
+                "note": "Universe is refreshed daily from Breeze bridge. Use refresh_universe to update now."
+            }))
+        }
+
+        "refresh_universe" => {
+            let bridge_url = state.config.broker.icici.bridge_url.clone();
+            if bridge_url.is_empty() {
+                Ok(serde_json::json!({ "error": "No bridge URL configured" }))
+            } else {
+                match state.universe.refresh_from_bridge(&bridge_url) {
+                    Ok(count) => Ok(serde_json::json!({
+                        "success": true,
+                        "stocks_loaded": count,
+                        "sectors": state.universe.sector_list().len(),
+                        "fno_stocks": state.universe.fno_stocks().len(),
+                    })),
+                    Err(e) => Ok(serde_json::json!({ "error": e })),
+                }
+            }
+        }
+
+        "oms_submit_order" => {
+            #[derive(Deserialize)]
+            struct SubmitData {
+                symbol: String,
+                exchange: Option<String>,
+                side: String,
+                order_type: Option<String>,
+                quantity: i64,
+                price: Option<f64>,
+                trigger_price: Option<f64>,
+                product: Option<String>,
+                strategy_id: Option<String>,
+                reference_price: Option<f64>,
+                tag: Option<String>,
+            }
+            let d: SubmitData = match serde_json::from_value(req.data) {
+                Ok(v) => v,
+                Err(e) => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some(format!("Invalid order data: {}", e)),
+                    meta: None, },
+            };
+
+            if state.is_killed() {
+                return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some("Kill switch active — order rejected".into()),
+                    meta: None, };
+            }
+
+            let side = match d.side.to_lowercase().as_str() {
+                "buy" => OrderSide::Buy,
+                "sell" => OrderSide::Sell,
+                _ => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some(format!("Invalid side: {}", d.side)),
+                    meta: None, },
+            };
+            let order_type = match d.order_type.as_deref().unwrap_or("limit") {
+                "market" => OrderType::Market,
+                "limit" => OrderType::Limit,
+                "stop_loss" | "sl" => OrderType::StopLoss,
+                "stop_loss_market" | "slm" => OrderType::StopLossMarket,
+                other => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some(format!("Invalid order type: {}", other)),
+                    meta: None, },
+            };
+            let product = match d.product.as_deref().unwrap_or("delivery") {
+                "intraday" | "mis" => ProductType::Intraday,
+                "delivery" | "cnc" | "nrml" => ProductType::Delivery,
+                other => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some(format!("Invalid product type: {}", other)),
+                    meta: None, },
+            };
+
+            let order_req = OrderRequest {
+                symbol: d.symbol.clone(), exchange: d.exchange.unwrap_or_else(|| "NSE".into()),
+                side, order_type, quantity: d.quantity, price: d.price,
+                trigger_price: d.trigger_price, product, tag: d.tag,
+                ..Default::default()
+            };
+
+            let ref_price = d.reference_price.or_else(|| {
+                state.live_prices.get_tick(&d.symbol).map(|t| t.ltp)
+            });
+
+            match state.oms.submit_order(order_req, d.strategy_id, ref_price) {
+                Ok(order) => {
+                    state.log_audit("OMS_ORDER_SUBMITTED", Some(&order.symbol),
+                        &format!("id={} side={:?} qty={}", order.internal_id, order.side, order.requested_qty));
+
+                    if order.status == OrderStatus::Filled && order.filled_qty > 0 {
+                        let fill_side = match order.side {
+                            OrderSide::Buy => "buy",
+                            OrderSide::Sell => "sell",
+                        };
+                        state.sync_oms_fill(
+                            &order.symbol, fill_side, order.filled_qty,
+                            order.avg_fill_price, None, None,
+                        );
+                    }
+
+                    Ok(serde_json::to_value(order).unwrap_or_default())
+                }
+                Err(e) => {
+                    state.alert_manager.fire(
+                        AlertType::OrderRejected, AlertSeverity::Warning,
+                        "Order rejected", &e, None, None,
+                    );
+                    Err(e)
+                }
+            }
+        }
+
+        "oms_cancel_order" => {
+            let order_id = match req.data.get("order_id").and_then(|v| v.as_str()) {
+                Some(id) => id,
+                None => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some("Missing order_id".into()),
+                    meta: None, },
+            };
+            match state.oms.cancel_order(order_id) {
+                Ok(order) => {
+                    state.log_audit("OMS_ORDER_CANCELLED", Some(&order.symbol),
+                        &format!("id={}", order.internal_id));
+                    Ok(serde_json::to_value(order).unwrap_or_default())
+                }
+                Err(e) => Err(e),
+            }
+        }
+
+        "oms_modify_order" => {
+            if state.is_killed() {
+                return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some("Kill switch active — order modification rejected".into()),
+                    meta: None, };
+            }
+            let order_id = match req.data.get("order_id").and_then(|v| v.as_str()) {
+                Some(id) => id,
+                None => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some("Missing order_id".into()),
+                    meta: None, },
+            };
+            let new_qty = req.data.get("quantity").and_then(|v| v.as_i64());
+            let new_price = req.data.get("price").and_then(|v| v.as_f64());
+            let new_trigger = req.data.get("trigger_price").and_then(|v| v.as_f64());
+            match state.oms.modify_order(order_id, new_qty, new_price, new_trigger) {
+                Ok(order) => {
+                    state.log_audit("OMS_ORDER_MODIFIED", Some(&order.symbol),
+                        &format!("id={} qty={} price={:?}", order.internal_id, order.requested_qty, order.price));
+                    Ok(serde_json::to_value(order).unwrap_or_default())
+                }
+                Err(e) => Err(e),
+            }
+        }
+
+        "oms_cancel_all" => {
+            let cancelled = state.oms.cancel_all();
+            state.log_audit("OMS_CANCEL_ALL", None, &format!("cancelled {} orders", cancelled.len()));
+            Ok(serde_json::json!({ "cancelled": cancelled }))
+        }
+
+        "oms_orders" => {
+            let strategy_filter = req.data.get("strategy_id").and_then(|v| v.as_str());
+            let orders = match strategy_filter {
+                Some(sid) => state.oms.get_orders_by_strategy(sid),
+                None => state.oms.get_orders(),
+            };
+            Ok(serde_json::to_value(orders).unwrap_or_default())
+        }
+
+        "oms_reconcile" => {
+            let engine_positions: Vec<(String, i64, f64)> = state.positions.iter()
+                .map(|entry| {
+                    let p = entry.value();
+                    (p.symbol.clone(), p.qty, p.entry_price)
+                })
+                .collect();
+            let report = state.oms.reconcile(&engine_positions);
+            if !report.mismatches.is_empty() {
+                state.alert_manager.fire(
+                    AlertType::ReconciliationMismatch, AlertSeverity::Critical,
+                    "Position reconciliation mismatch",
+                    &format!("{} mismatches found", report.mismatches.len()),
+                    None, None,
+                );
+            }
+            state.log_audit("OMS_RECONCILE", None,
+                &format!("matched={} mismatches={}", report.matched, report.mismatches.len()));
+            Ok(serde_json::to_value(report).unwrap_or_default())
+        }
+
+        "alerts" => {
+            let severity_filter = req.data.get("min_severity").and_then(|v| v.as_str());
+            let min_sev = match severity_filter {
+                Some("info") => Some(AlertSeverity::Info),
+                Some("warning") => Some(AlertSeverity::Warning),
+                Some("critical") => Some(AlertSeverity::Critical),
+                Some("emergency") => Some(AlertSeverity::Emergency),
+                _ => None,
+            };
+            let limit = req.data.get("limit").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
+            let alerts = state.alert_manager.get_alerts(min_sev, limit);
+            Ok(serde_json::to_value(alerts).unwrap_or_default())
+        }
+
+        "alert_acknowledge" => {
+            let alert_id = match req.data.get("alert_id").and_then(|v| v.as_str()) {
+                Some(id) => id,
+                None => return Response { id, success: false, data: serde_json::Value::Null,
+                    error: Some("Missing alert_id".into()),
+                    meta: None, },
+            };
+            let acked = state.alert_manager.acknowledge(alert_id);
+            Ok(serde_json::json!({ "acknowledged": acked }))
+        }
+
+        "alert_counts" => {
+            let (info, warn, crit, emrg) = state.alert_manager.unacknowledged_counts();
+            Ok(serde_json::json!({
+                "info": info, "warning": warn, "critical": crit, "emergency": emrg,
+                "total": info + warn + crit + emrg,
+            }))
+        }
+
+        "broker_init_session" => {
+            use crate::broker_icici::IciciBreezeBroker;
+            if let Some(icici) = state.broker_adapter.as_any().downcast_ref::<IciciBreezeBroker>() {
+                match icici.init_session() {
+                    Ok(()) => Ok(serde_json::json!({ "status": "session_initialized" })),
+                    Err(e) => Err(format!("Session init failed: {}", e)),
+                }
+            } else {
+                Err("broker_init_session is only supported for ICICI Breeze adapter".into())
+            }
+        }
+
+        "broker_refresh_status" => {
+            use crate::broker_icici::IciciBreezeBroker;
+            if let Some(icici) = state.broker_adapter.as_any().downcast_ref::<IciciBreezeBroker>() {
+                let active = icici.refresh_status();
+                Ok(serde_json::json!({ "connected": active, "broker": "icici_breeze" }))
+            } else {
+                Ok(serde_json::json!({
+                    "connected": state.broker_adapter.is_connected(),
+                    "broker": state.broker_adapter.name(),
+                }))
+            }
+        }
+
+        _ => Err(format!("Unknown command: {}", cmd)),
+    };
+
+    match result {
+        Ok(data) => Response { id, success: true, data, error: None, meta: None },
+        Err(e) => Response { id, success: false, data: serde_json::Value::Null, error: Some(e), meta: None },
+    }
+}
+
+/// Candles supplied at the top level plus under any per-symbol entry.
+fn candle_count(data: &serde_json::Value) -> usize {
+    let own = data.get("candles").and_then(|c| c.as_array()).map_or(0, |c| c.len());
+    let nested: usize = data.get("symbols").and_then(|s| s.as_array()).map_or(0, |syms| {
+        syms.iter().filter_map(|s| s.get("candles").and_then(|c| c.as_array())).map(|c| c.len()).sum()
+    });
+    own + nested
+}
+
+/// Minimum bars before RSI/ATR/ADX (14-period plus warm-up) mean anything.
+const MIN_INDICATOR_CANDLES: usize = 30;
+/// Long-period filters (EMA/SMA 200) stay inactive below this.
+const MIN_BACKTEST_CANDLES: usize = 200;
+
+/// Non-fatal input problems worth a warning in the logs.
+fn input_warnings(cmd: &str, data: &serde_json::Value) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut check = |label: &str, candles: &[serde_json::Value]| {
+        let n = candles.len();
+        if n == 0 {
+            return;
+        }
+        if n < MIN_INDICATOR_CANDLES {
+            warnings.push(format!("{}only {} candles, RSI/ATR-based signals unreliable", label, n));
+        } else if n < MIN_BACKTEST_CANDLES && matches!(cmd, "backtest" | "optimize" | "walk_forward" | "compare") {
+            warnings.push(format!("{}only {} candles, 200-period filters never warm up", label, n));
+        }
+        let ts = |c: &serde_json::Value| c.get("timestamp").and_then(|t| t.as_str()).map(str::to_string);
+        if candles.windows(2).any(|w| matches!((ts(&w[0]), ts(&w[1])), (Some(a), Some(b)) if b < a)) {
+            warnings.push(format!("{}candles are not in chronological order", label));
+        }
+    };
+    if let Some(candles) = data.get("candles").and_then(|c| c.as_array()) {
+        check("", candles);
+    }
+    if let Some(symbols) = data.get("symbols").and_then(|s| s.as_array()) {
+        for sym in symbols {
+            if let Some(candles) = sym.get("candles").and_then(|c| c.as_array()) {
+                let name = sym.get("symbol").and_then(|s| s.as_str()).unwrap_or("?");
+                check(&format!("{}: ", name), candles);
+            }
+        }
+    }
+    if !warnings.is_empty() {
+        debug!(command = cmd, count = warnings.len(), "Input diagnostics");
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EngineConfig;
+    use serde_json::json;
+
+    #[test]
+    fn test_input_warnings() {
+        let few: Vec<serde_json::Value> = (0..10)
+            .map(|i| json!({ "timestamp": format!("t{:02}", i), "close": 1.0 })).collect();
+        let w = input_warnings("signals", &json!({ "candles": few }));
+        assert_eq!(w.len(), 1);
+        assert!(w[0].contains("only 10 candles"));
+
+        let unordered = json!({ "symbols": [{ "symbol": "ABC", "candles": [
+            { "timestamp": "2024-01-02" }, { "timestamp": "2024-01-01" }
+        ]}]});
+        let w = input_warnings("scan", &unordered);
+        assert!(w.iter().any(|m| m == "ABC: candles are not in chronological order"));
+        assert_eq!(candle_count(&unordered), 2);
+    }
+
+    fn make_state() -> Arc<AppState> {
+        AppState::new(EngineConfig::default(), 1_000_000.0)
+    }
+
+    fn req(command: &str, data: serde_json::Value) -> Response {
+        let state = make_state();
+        handle_request(Request {
+            id: Some("test".to_string()),
+            command: command.to_string(),
+            data,
+            ..Default::default()
+        }, &state)
+    }
+
+    fn sample_candles() -> Vec<serde_json::Value> {
+        (0..30).map(|i| json!({
+            "timestamp": format!("2024-01-{:02}T10:00:00", (i % 28) + 1),
+            "open": 100.0 + i as f64,
+            "high": 102.0 + i as f64,
+            "low": 99.0 + i as f64,
+            "close": 101.0 + i as f64,
+            "volume": 10000.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_serve_ndjson_one_line_per_request() {
+        let state = make_state();
+        let input = concat!(
+            "{\"id\":\"a\",\"command\":\"health\",\"data\":{}}\n",
+            "\n",
+            "{\"id\":\"b\",\"command\":\"nope\",\"data\":{}}\n",
+            "{\"id\":\"c\",\"command\":42}\n",
+        );
+        let mut out = Vec::new();
+        let answered = serve_ndjson(&state, std::io::Cursor::new(input), &mut out);
+        assert_eq!(answered, 3);
+
+        let mut lines: Vec<serde_json::Value> = String::from_utf8(out).unwrap()
+            .lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        lines.sort_by_key(|l| l["id"].as_str().unwrap_or("").to_string());
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["id"], "a");
+        assert_eq!(lines[0]["success"], true);
+        assert_eq!(lines[1]["id"], "b");
+        assert_eq!(lines[1]["success"], false);
+        assert_eq!(lines[2]["id"], "c");
+        assert!(lines[2]["error"].as_str().unwrap().contains("Invalid JSON"));
+    }
+
+    #[test]
+    fn test_serve_ndjson_applies_stateful_commands_in_order() {
+        let state = make_state();
+        let file = std::env::temp_dir().join(format!("serve_orders_{}.json", std::process::id()));
+        let file = file.to_str().unwrap();
+        let _ = std::fs::remove_file(file);
+        let line = |id: &str, data: serde_json::Value| {
+            format!("{}\n", json!({ "id": id, "command": "orders", "data": data }))
+        };
+        // Queued work ahead of them must not let the process overtake the place.
+        let mut input = format!("{}\n", json!({ "id": "a", "command": "health", "data": {} }));
+        input += &line("b", json!({ "command": "place", "state_file": file, "symbol": "X", "side": "buy", "qty": 1,
+            "order_type": "market", "timestamp": "2024-01-01T09:15:00" }));
+        input += &line("c", json!({ "command": "process", "state_file": file, "symbol": "X", "candles": [
+            { "timestamp": "2024-01-01T09:15:00", "open": 100.0, "high": 100.0, "low": 100.0, "close": 100.0, "volume": 0.0 }
+        ]}));
+        let mut out = Vec::new();
+        assert_eq!(serve_ndjson(&state, std::io::Cursor::new(input), &mut out), 3);
+        let process = String::from_utf8(out).unwrap().lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .find(|l| l["id"] == "c").unwrap();
+        assert_eq!(process["data"]["fills"][0]["price"], 100.0);
+        let _ = std::fs::remove_file(file);
+
+        assert!(runs_inline(&json!({ "commands": [{ "command": "backtest" }, { "command": "portfolio" }] })));
+        assert!(runs_inline(&json!({ "command": "oms_submit_order" })));
+        assert!(runs_inline(&json!({ "command": "premarket_execute" })));
+        assert!(runs_inline(&json!({ "command": "signals", "data": { "plugins": ["x"] } })));
+        assert!(!runs_inline(&json!({ "command": "optimize" })));
+    }
+
+    #[test]
+    fn test_serve_ndjson_kill_switch_blocks_the_next_order() {
+        let state = make_state();
+        let mut input = String::new();
+        for (id, command, data) in [
+            ("a", "health", json!({})),
+            ("b", "kill_switch", json!({})),
+            ("c", "oms_submit_order", json!({ "symbol": "TCS", "side": "buy", "quantity": 5, "price": 3000.0 })),
+        ] {
+            input += &format!("{}\n", json!({ "id": id, "command": command, "data": data }));
+        }
+        let mut out = Vec::new();
+        assert_eq!(serve_ndjson(&state, std::io::Cursor::new(input), &mut out), 3);
+        let order = String::from_utf8(out).unwrap().lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .find(|l| l["id"] == "c").unwrap();
+        assert_eq!(order["success"], false);
+        assert!(order["error"].as_str().unwrap().contains("Kill switch"), "{}", order);
+    }
+
+    #[test]
+    fn test_timestamps_localized_only_on_request() {
+        let candles: Vec<serde_json::Value> = (0..60).map(|i| {
+            let close = 100.0 + (i as f64 * 0.3).sin() * 5.0;
+            // Alternate zone-suffixed and epoch-second stamps, a day apart.
+            let epoch = 1_704_080_700 + i as i64 * 86_400;
+            let ts = if i % 2 == 0 {
+                chrono::DateTime::from_timestamp(epoch, 0).unwrap().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            } else {
+                epoch.to_string()
+            };
+            json!({ "timestamp": ts, "open": close, "high": close + 1.0, "low": close - 1.0, "close": close, "volume": 1000.0 })
+        }).collect();
+        let data = json!({ "strategy": "ema_crossover", "symbol": "X", "initial_capital": 100000.0, "candles": candles.clone() });
+        let state = make_state();
+        let run = |timezone: Option<&str>| handle_request(Request {
+            command: "backtest".to_string(),
+            data: data.clone(),
+            timezone: timezone.map(String::from),
+            ..Default::default()
+        }, &state);
+
+        let plain = run(None);
+        assert!(plain.success, "{:?}", plain.error);
+        let dates: Vec<&serde_json::Value> = plain.data["equity_curve"].as_array().unwrap().iter().map(|p| &p["date"]).collect();
+        let sent: Vec<&serde_json::Value> = candles.iter().map(|c| &c["timestamp"]).collect();
+        assert_eq!(dates, sent);
+
+        let local = run(Some("exchange"));
+        assert_eq!(local.data["equity_curve"][0]["date"], "2024-01-01T09:15:00+05:30");
+    }
+
+    #[test]
+    fn test_sessions_follow_exchange_zone_by_default() {
+        // 23:50Z and 00:10Z straddle UTC midnight but are both 3 Jan morning in
+        // India; 17:00Z is still the evening of 2 Jan there.
+        let candles: Vec<serde_json::Value> = [("2024-01-02T17:00:00Z", 100.0), ("2024-01-02T23:50:00Z", 110.0), ("2024-01-03T00:10:00Z", 120.0)]
+            .iter()
+            .map(|(ts, c)| json!({ "timestamp": ts, "open": c, "high": c, "low": c, "close": c, "volume": 10.0 }))
+            .collect();
+        let resp = req("signals", json!({ "candles": candles }));
+        assert!(resp.success, "{:?}", resp.error);
+        assert_eq!(resp.data["vwap"][1], 110.0, "the session starts at the 05:20 IST bar");
+        assert_eq!(resp.data["vwap"][2], 115.0);
+    }
+
+    #[test]
+    fn test_batch_preserves_order() {
+        let state = make_state();
+        for parallel in [false, true] {
+            let msg = json!({
+                "parallel": parallel,
+                "commands": [
+                    { "id": "h", "command": "health", "data": {} },
+                    { "id": "g", "command": "greeks", "data": {
+                        "spot": 100.0, "strike": 100.0, "volatility": 0.2,
+                        "time_to_expiry": 0.25, "risk_free_rate": 0.05, "option_type": "call"
+                    }},
+                    { "id": "x", "command": "nope", "data": {} }
+                ]
+            });
+            let out = serde_json::to_value(handle_message(msg, &state)).unwrap();
+            let arr = out.as_array().expect("batch reply should be an array");
+            assert_eq!(arr.len(), 3);
+            assert_eq!(arr[0]["id"], "h");
+            assert_eq!(arr[1]["id"], "g");
+            assert_eq!(arr[1]["success"], true);
+            assert_eq!(arr[2]["success"], false);
+        }
+    }
+
+    #[test]
+    fn test_handle_message_invalid_single_keeps_id() {
+        let state = make_state();
+        let out = serde_json::to_value(handle_message(json!({ "id": "z", "command": 1 }), &state)).unwrap();
+        assert_eq!(out["id"], "z");
+        assert_eq!(out["success"], false);
+    }
+
+    #[test]
+    fn test_response_meta() {
+        let resp = req("signals", json!({ "candles": sample_candles() }));
+        let meta = resp.meta.expect("dispatched responses carry meta");
+        assert_eq!(meta.candles, sample_candles().len());
+        assert!(meta.total_ms >= meta.compute_ms);
+        assert!(meta.est_input_mb > 0.0);
+
+        let failed = req("no_such_command", json!({}));
+        assert!(failed.meta.is_some());
+        let wire = serde_json::to_value(&failed).unwrap();
+        assert_eq!(wire["meta"]["candles"], 0);
+    }
+
+    #[test]
+    fn test_guardrails_error_and_degrade() {
+        let mut config = EngineConfig::default();
+        config.limits.max_candles = 20;
+        let state = AppState::new(config, 1_000_000.0);
+        let data = json!({ "candles": sample_candles() });
+
+        let rejected = handle_request(Request {
+            command: "signals".to_string(), data: data.clone(), ..Default::default()
+        }, &state);
+        assert!(!rejected.success);
+        assert_eq!(rejected.data["limit_exceeded"]["limit"], "max_candles");
+
+        let degraded = handle_request(Request {
+            command: "signals".to_string(), data, on_limit: Some("degrade".to_string()), ..Default::default()
+        }, &state);
+        let meta = degraded.meta.unwrap();
+        assert!(meta.warnings.iter().any(|w| w.contains("downsampled")), "{:?}", meta.warnings);
+    }
+
+    #[test]
+    fn test_precision_and_sorted_keys() {
+        let state = make_state();
+        let resp = handle_request(Request {
+            command: "greeks".to_string(),
+            data: json!({ "spot": 100.0, "strike": 105.0, "time_to_expiry": 0.25,
+                          "risk_free_rate": 0.05, "volatility": 0.2, "option_type": "call" }),
+            precision: Some(1),
+            ..Default::default()
+        }, &state);
+        let delta = resp.data["delta"].as_f64().unwrap();
+        assert_eq!(delta, (delta * 10.0).round() / 10.0);
+
+        // Key order must not depend on insertion order (serde_json without
+        // `preserve_order`); snapshot tests on the host rely on this.
+        let text = serde_json::to_string(&json!({ "b": 1, "a": { "d": 1, "c": 2 } })).unwrap();
+        assert_eq!(text, r#"{"a":{"c":2,"d":1},"b":1}"#);
+    }
+
+    #[test]
+    fn test_downsample_points_thins_series() {
+        let state = make_state();
+        let candles: Vec<serde_json::Value> = (0..300).map(|i| {
+            let close = 100.0 + i as f64;
+            json!({ "timestamp": format!("t{}", i), "open": close, "high": close, "low": close, "close": close, "volume": 1.0 })
+        }).collect();
+        let resp = handle_request(Request {
+            command: "bar_transform".to_string(),
+            data: json!({ "candles": candles, "type": "renko", "box_size": 1.0 }),
+            downsample_points: Some(50),
+            ..Default::default()
+        }, &state);
+        assert!(resp.success, "{:?}", resp.error);
+        assert_eq!(resp.data["bars"].as_array().unwrap().len(), 50);
+        assert!(resp.meta.unwrap().warnings.iter().any(|w| w.contains("data.bars downsampled")));
+    }
+
+    #[test]
+    fn test_cancel_unknown_request() {
+        let resp = req("cancel", json!({ "request_id": "not-running" }));
+        assert!(resp.success);
+        assert_eq!(resp.data["cancelled"], false);
+        assert!(!req("cancel", json!({})).success);
+    }
+
+    #[test]
+    fn test_optimize_timeout_returns_partial() {
+        let state = make_state();
+        let resp = handle_request(Request {
+            id: Some("opt-timeout".to_string()),
+            command: "optimize".to_string(),
+            data: json!({
+                "strategy": "ema_crossover", "symbol": "TEST", "initial_capital": 100000.0,
+                "candles": sample_candles(),
+                "param_grid": { "short_period": [5.0, 9.0] }
+            }),
+            timeout_ms: Some(0),
+            ..Default::default()
+        }, &state);
+        assert!(resp.success, "{:?}", resp.error);
+        assert_eq!(resp.data["cancelled"], true);
+        assert_eq!(resp.data["cancel_reason"], "timeout");
+    }
+
+    #[test]
+    fn test_version_command() {
+        let resp = req("version", json!({}));
+        assert!(resp.success);
+        assert_eq!(resp.data["engine_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(resp.data["protocol_version"], PROTOCOL_VERSION);
+        assert!(resp.data["capabilities"].as_array().unwrap().iter().any(|c| c == "batch"));
+        assert_eq!(resp.data["commands"].as_array().unwrap().len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn test_commands_match_dispatch_arms() {
+        // Read the arms straight from `dispatch` so a new command can't be left out.
+        let source = include_str!("lib.rs");
+        let body = &source[source.find("\nfn dispatch(").unwrap()..];
+        let body = &body[..body.find("\n}\n").unwrap()];
+        let arms: Vec<&str> = body.lines()
+            .filter_map(|l| l.strip_prefix("        \"").and_then(|l| l.split_once("\" =>")))
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(arms, COMMANDS);
+        assert!(POOLED_COMMANDS.iter().all(|c| COMMANDS.contains(c)));
+    }
+
+    #[test]
+    fn test_min_version_gate() {
+        let state = make_state();
+        let ok = serde_json::to_value(handle_message(
+            json!({ "id": "a", "command": "health", "data": {}, "min_version": "0.1" }), &state)).unwrap();
+        assert_eq!(ok["success"], true);
+
+        let stale = serde_json::to_value(handle_message(
+            json!({ "id": "b", "command": "health", "data": {}, "min_version": "99.0.0" }), &state)).unwrap();
+        assert_eq!(stale["success"], false);
+        assert_eq!(stale["id"], "b");
+        assert_eq!(stale["data"]["engine_version"], env!("CARGO_PKG_VERSION"));
+        assert!(stale["error"].as_str().unwrap().contains("older than required"));
+
+        let bad = serde_json::to_value(handle_message(
+            json!({ "command": "health", "data": {}, "min_version": "latest" }), &state)).unwrap();
+        assert!(bad["error"].as_str().unwrap().contains("Invalid min_version"));
+        assert_eq!(parse_semver("v1.2.3-beta"), Some((1, 2, 3)));
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let resp = req("foobar", json!({}));
+        assert!(!resp.success);
+        assert!(resp.error.as_ref().unwrap().contains("Unknown"));
+    }
+
+    #[test]
+    fn test_signals_valid() {
+        let resp = req("signals", json!({ "candles": sample_candles() }));
+        assert!(resp.success, "signals failed: {:?}", resp.error);
+    }
+
+    #[test]
+    fn test_signals_empty() {
+        let resp = req("signals", json!({}));
+        assert!(!resp.success);
+    }
+
+    #[test]
+    fn test_risk_valid() {
+        let returns: Vec<f64> = (0..60).map(|i| 0.01 * (i as f64 * 0.1).sin()).collect();
+        let resp = req("risk", json!({ "returns": returns, "initial_capital": 100000.0 }));
+        assert!(resp.success, "risk failed: {:?}", resp.error);
+    }
+
+    #[test]
+    fn test_greeks_valid() {
+        let resp = req("greeks", json!({
+            "spot": 100.0,
+            "strike": 100.0,
+            "volatility": 0.2,
+            "time_to_expiry": 0.25,
+            "risk_free_rate": 0.05,
+            "option_type": "call"
+        }));
+        assert!(resp.success, "greeks failed: {:?}", resp.error);
+    }
+
+    #[test]
+    fn test_scan_empty_symbols() {
+        let resp = req("scan", json!({ "symbols": [] }));
+        assert!(resp.success, "scan failed: {:?}", resp.error);
+        let signals = resp.data.get("signals").and_then(|v| v.as_array());
+        assert_eq!(signals.map(|a| a.len()), Some(0));
+    }
+
+    #[test]
+    fn test_backtest_valid() {
+        let resp = req("backtest", json!({
+            "strategy": "ema_crossover",
+            "symbol": "TEST",
+            "initial_capital": 100000.0,
+            "candles": sample_candles()
+        }));
+        assert!(resp.success, "backtest failed: {:?}", resp.error);
+    }
+
+    #[test]
+    fn test_options_strategy_valid() {
+        let resp = req("options_strategy", json!({
+            "legs": [
+                { "option_type": "call", "strike": 100.0, "premium": 5.0, "quantity": 1 }
+            ],
+            "spot": 100.0
+        }));
+        assert!(resp.success, "options_strategy failed: {:?}", resp.error);
+    }
+
+    #[test]
+    fn test_monte_carlo_valid() {
+        let returns: Vec<f64> = (0..60).map(|i| 0.01 * (i as f64 * 0.1).sin()).collect();
+        let resp = req("monte_carlo", json!({
+            "returns": returns,
+            "initial_capital": 100000.0,
+            "num_simulations": 100,
+            "time_horizon": 30
+        }));
+        assert!(resp.success, "monte_carlo failed: {:?}", resp.error);
+    }
+
+    #[test]
+    fn test_request_id_preserved() {
+        let state = make_state();
+        let resp = handle_request(Request {
+            id: Some("my-unique-id-42".to_string()),
+            command: "greeks".to_string(),
+            data: json!({
+                "spot": 100.0,
+                "strike": 100.0,
+                "volatility": 0.2,
+                "time_to_expiry": 0.25,
+                "risk_free_rate": 0.05,
+                "option_type": "call"
+            }),
+            ..Default::default()
+        }, &state);
+        assert_eq!(resp.id, Some("my-unique-id-42".to_string()));
+    }
+
+    #[test]
+    fn test_health_endpoint() {
+        let resp = req("health", json!({}));
+        assert!(resp.success);
+        assert_eq!(resp.data["status"], "healthy");
+    }
+
+    #[test]
+    fn test_portfolio_snapshot() {
+        let resp = req("portfolio_snapshot", json!({}));
+        assert!(resp.success);
+        assert_eq!(resp.data["nav"], 1_000_000.0);
+    }
+
+    #[test]
+    fn test_list_strategies() {
+        let resp = req("list_strategies", json!({}));
+        assert!(resp.success);
+        let strats = resp.data["strategies"].as_array().unwrap();
+        assert!(strats.len() >= 6);
+    }
+
+    #[test]
+    fn test_oms_submit_order() {
+        let resp = req("oms_submit_order", json!({
+            "symbol": "RELIANCE",
+            "side": "buy",
+            "quantity": 10,
+            "price": 2500.0,
+        }));
+        assert!(resp.success, "OMS submit failed: {:?}", resp.error);
+        assert_eq!(resp.data["symbol"], "RELIANCE");
+        assert!(resp.data["internal_id"].as_str().unwrap().starts_with("OMS-"));
+    }
+
+    #[test]
+    fn test_oms_submit_order_rejected_by_kill_switch() {
+        let state = make_state();
+        state.activate_kill_switch();
+        let resp = handle_request(Request {
+            id: None,
+            command: "oms_submit_order".to_string(),
+            data: json!({ "symbol": "TCS", "side": "buy", "quantity": 5, "price": 3000.0 }),
+            ..Default::default()
+        }, &state);
+        assert!(!resp.success);
+        assert!(resp.error.as_ref().unwrap().contains("Kill switch"));
+    }
+
+    #[test]
+    fn test_oms_orders() {
+        let state = make_state();
+        handle_request(Request {
+            id: None, command: "oms_submit_order".to_string(),
+            data: json!({ "symbol": "INFY", "side": "buy", "quantity": 5, "price": 1500.0, "strategy_id": "test_strat" }),
+            ..Default::default()
+        }, &state);
+        let resp = handle_request(Request {
+            id: None, command: "oms_orders".to_string(), data: json!({}),
+            ..Default::default()
+        }, &state);
+        assert!(resp.success);
+        let orders = resp.data.as_array().unwrap();
+        assert_eq!(orders.len(), 1);
+    }
+
+    #[test]
+    fn test_oms_reconcile() {
+        let resp = req("oms_reconcile", json!({}));
+        assert!(resp.success, "OMS reconcile failed: {:?}", resp.error);
+        assert!(resp.data.get("matched").is_some());
+    }
+
+    #[test]
+    fn test_alerts_empty() {
+        let resp = req("alerts", json!({}));
+        assert!(resp.success);
+        let alerts = resp.data.as_array().unwrap();
+        assert_eq!(alerts.len(), 0);
+    }
+
+    #[test]
+    fn test_alert_counts() {
+        let resp = req("alert_counts", json!({}));
+        assert!(resp.success);
+        assert_eq!(resp.data["total"], 0);
+    }
+
+    #[test]
+    fn test_oms_fat_finger_rejection_fires_alert() {
+        let state = make_state();
+        let resp = handle_request(Request {
+            id: None, command: "oms_submit_order".to_string(),
+            data: json!({ "symbol": "BIG", "side": "buy", "quantity": 100000, "price": 100.0 }),
+            ..Default::default()
+        }, &state);
+        assert!(!resp.success);
+        let counts_resp = handle_request(Request {
+            id: None, command: "alert_counts".to_string(), data: json!({}),
+            ..Default::default()
+        }, &state);
+        assert!(counts_resp.data["warning"].as_u64().unwrap() >= 1);
+    }
+
+    #[test]
+    fn test_oms_modify_order_rejected_by_kill_switch() {
+        let state = make_state();
+        let submit_resp = handle_request(Request {
+            id: None, command: "oms_submit_order".to_string(),
+            data: json!({ "symbol": "INFY", "side": "buy", "quantity": 10, "price": 1500.0 }),
+            ..Default::default()
+        }, &state);
+        assert!(submit_resp.success, "Setup: submit should succeed");
+        let order_id = submit_resp.data["internal_id"].as_str().unwrap();
+        state.activate_kill_switch();
+        let modify_resp = handle_request(Request {
+            id: None, command: "oms_modify_order".to_string(),
+            data: json!({ "order_id": order_id, "quantity": 20 }),
+            ..Default::default()
+        }, &state);
+        assert!(!modify_resp.success);
+        assert!(modify_resp.error.as_ref().unwrap().contains("Kill switch"));
+    }
+
+    #[test]
+    fn test_ml_scan_without_weights() {
+        let closes: Vec<f64> = (0..35).map(|i| 100.0 + i as f64 * 2.0).collect();
+        let candles: Vec<serde_json::Value> = closes.iter().enumerate().map(|(i, &c)| {
+            json!({
+                "timestamp": format!("2025-01-{:02}", (i % 28) + 1),
+                "open": c - 0.5, "high": c + 1.0, "low": c - 1.0,
+                "close": c, "volume": 1000.0 + i as f64 * 200.0,
+            })
+        }).collect();
+        let resp = req("ml_scan", json!({
+            "symbols": [{ "symbol": "TEST", "candles": candles }],
+        }));
+        assert!(resp.success, "ml_scan failed: {:?}", resp.error);
+        assert_eq!(resp.data["ml_enhanced"], false);
+        let signals = resp.data.get("signals").and_then(|v| v.as_array());
+        if let Some(sigs) = signals {
+            for sig in sigs {
+                assert!(sig.get("ml_features").is_some(),
+                    "each signal should have ml_features attached");
+            }
+        }
+    }
+
+    #[test]
+    fn test_ml_scan_with_weights() {
+        let closes: Vec<f64> = (0..35).map(|i| 100.0 + i as f64 * 2.0).collect();
+        let candles: Vec<serde_json::Value> = closes.iter().enumerate().map(|(i, &c)| {
+            json!({
+                "timestamp": format!("2025-01-{:02}", (i % 28) + 1),
+                "open": c - 0.5, "high": c + 1.0, "low": c - 1.0,
+                "close": c, "volume": 1000.0 + i as f64 * 200.0,
+            })
+        }).collect();
+        let weights = json!({
+            "w": vec![0.1; 12],
+            "bias": 0.0,
+            "feature_names": [],
+            "training_samples": 100,
+            "training_accuracy": 0.75,
+        });
+        let resp = req("ml_scan", json!({
+            "symbols": [{ "symbol": "TEST", "candles": candles }],
+            "ml_weights": weights,
+        }));
+        assert!(resp.success, "ml_scan with weights failed: {:?}", resp.error);
+        assert_eq!(resp.data["ml_enhanced"], true);
+        let signals = resp.data.get("signals").and_then(|v| v.as_array());
+        if let Some(sigs) = signals {
+            for sig in sigs {
+                if sig.get("ml_score").is_some() {
+                    let ml_score = sig["ml_score"].as_f64().unwrap();
+                    assert!(ml_score >= 0.0 && ml_score <= 1.0,
+                        "ml_score should be between 0 and 1, got {}", ml_score);
+                    assert!(sig.get("blended_confidence").is_some(),
+                        "should have blended_confidence when weights provided");
+                }
+            }
+        }
+    }
+}
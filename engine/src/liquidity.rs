@@ -0,0 +1,70 @@
+//! Turnover-based liquidity score.
+//!
+//! Daily turnover is the average close × volume per bar over the last
+//! `lookback_days` sessions, scaled to a full session by `bars_per_day`.
+//! The score maps it onto [0, 1] on a log scale between ₹1 crore (0) and
+//! ₹1,000 crore (1) a day, so it can sit next to the other 0–1 inputs of
+//! `rank_signals`.
+
+use serde::Serialize;
+use crate::utils::Candle;
+
+/// Daily turnover scoring 0 and 1.
+const ILLIQUID_TURNOVER: f64 = 1e7;
+const LIQUID_TURNOVER: f64 = 1e10;
+
+pub const DEFAULT_LOOKBACK_DAYS: usize = 20;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Liquidity {
+    pub daily_turnover: f64,
+    pub score: f64,
+}
+
+/// Liquidity as of the last bar of `candles`; None without any bars.
+pub fn turnover_liquidity(candles: &[Candle], bars_per_day: f64, lookback_days: usize) -> Option<Liquidity> {
+    let bars_per_day = bars_per_day.max(1.0);
+    let n = ((lookback_days.max(1) as f64) * bars_per_day).round() as usize;
+    let window = &candles[candles.len().saturating_sub(n)..];
+    if window.is_empty() {
+        return None;
+    }
+    let per_bar = window.iter().map(|c| (c.close * c.volume).max(0.0)).sum::<f64>() / window.len() as f64;
+    let daily_turnover = per_bar * bars_per_day;
+    Some(Liquidity { daily_turnover, score: score(daily_turnover) })
+}
+
+fn score(daily_turnover: f64) -> f64 {
+    if daily_turnover <= 0.0 {
+        return 0.0;
+    }
+    let span = LIQUID_TURNOVER.log10() - ILLIQUID_TURNOVER.log10();
+    ((daily_turnover.log10() - ILLIQUID_TURNOVER.log10()) / span).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bars(close: f64, volume: f64, n: usize) -> Vec<Candle> {
+        (0..n).map(|i| Candle {
+            timestamp: format!("2025-01-{:02}", i % 28 + 1),
+            open: close, high: close, low: close, close, volume,
+        }).collect()
+    }
+
+    #[test]
+    fn test_turnover_score_scale() {
+        // ₹100 × 1,000,000 = ₹10 crore a day: a third of the way up the log scale.
+        let mid = turnover_liquidity(&bars(100.0, 1_000_000.0, 30), 1.0, 20).unwrap();
+        assert_eq!(mid.daily_turnover, 1e8);
+        assert!((mid.score - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(turnover_liquidity(&bars(10.0, 1_000.0, 30), 1.0, 20).unwrap().score, 0.0);
+        assert_eq!(turnover_liquidity(&bars(2_000.0, 1e8, 30), 1.0, 20).unwrap().score, 1.0);
+
+        // 75 five-minute bars of ₹1.33 lakh each make the same ₹1 crore session.
+        let intraday = turnover_liquidity(&bars(100.0, 1_333.34, 200), 75.0, 1).unwrap();
+        assert!((intraday.daily_turnover - 1e7).abs() < 100.0);
+        assert!(turnover_liquidity(&[], 1.0, 20).is_none());
+    }
+}
@@ -372,6 +372,7 @@ pub fn spawn(state: Arc<AppState>) {
                         signal_confidence: calibrated_confidence,
                         max_position_pct,
                         default_qty,
+                        instrument: Default::default(),
                     };
                     let qty = compute_quantity(sizing_method, &sizing_ctx);
 
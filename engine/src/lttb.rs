@@ -0,0 +1,196 @@
+//! Response downsampling with largest-triangle-three-buckets.
+//!
+//! With `downsample_points` on the request, every series in the response
+//! longer than that is thinned to roughly that many points: numeric arrays
+//! (indicator series) and arrays of objects with a plottable field (equity
+//! curves, candles). Series of equal length inside one object share the
+//! kept indices so they still line up on a chart. First and last points
+//! are always kept.
+
+use serde_json::{Map, Value};
+
+/// Fields tried, in order, as the y value for arrays of objects.
+const Y_FIELDS: &[&str] = &["equity", "value", "close", "price", "pnl", "drawdown"];
+
+/// Indices LTTB keeps when reducing `ys` (x = index) to `threshold` points.
+pub fn lttb_indices(ys: &[f64], threshold: usize) -> Vec<usize> {
+    let n = ys.len();
+    if threshold >= n || threshold < 3 {
+        return (0..n).collect();
+    }
+    let mut kept = Vec::with_capacity(threshold);
+    kept.push(0);
+    let bucket = (n - 2) as f64 / (threshold - 2) as f64;
+    let mut a = 0usize;
+    for i in 0..threshold - 2 {
+        // Average of the next bucket is the third triangle vertex.
+        let next_start = ((i + 1) as f64 * bucket) as usize + 1;
+        let next_end = (((i + 2) as f64 * bucket) as usize + 1).min(n);
+        let (avg_x, avg_y) = if next_start < next_end {
+            let len = (next_end - next_start) as f64;
+            ((next_start..next_end).sum::<usize>() as f64 / len, ys[next_start..next_end].iter().sum::<f64>() / len)
+        } else {
+            ((n - 1) as f64, ys[n - 1])
+        };
+
+        let start = (i as f64 * bucket) as usize + 1;
+        let end = (((i + 1) as f64 * bucket) as usize + 1).min(n - 1);
+        let (ax, ay) = (a as f64, ys[a]);
+        let mut best = start;
+        let mut best_area = -1.0;
+        for (j, &y) in ys.iter().enumerate().take(end.max(start + 1)).skip(start) {
+            let area = ((ax - avg_x) * (y - ay) - (ax - j as f64) * (avg_y - ay)).abs();
+            if area > best_area {
+                best_area = area;
+                best = j;
+            }
+        }
+        kept.push(best);
+        a = best;
+    }
+    kept.push(n - 1);
+    kept
+}
+
+/// Downsample every long series in `data` in place. Returns one warning per
+/// group of series that was reduced.
+pub fn apply(data: &mut Value, points: usize) -> Vec<String> {
+    let mut warnings = Vec::new();
+    walk(data, "data", points, &mut warnings);
+    warnings
+}
+
+fn walk(value: &mut Value, path: &str, points: usize, warnings: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            // Arrays directly under an object are thinned together below.
+            for (key, child) in map.iter_mut() {
+                let child_path = format!("{}.{}", path, key);
+                match child {
+                    Value::Array(items) => walk_items(items, &child_path, points, warnings),
+                    _ => walk(child, &child_path, points, warnings),
+                }
+            }
+            thin_object(map, path, points, warnings);
+        }
+        Value::Array(items) => {
+            walk_items(items, path, points, warnings);
+            if let Some(ys) = series_values(items).filter(|ys| ys.len() > points) {
+                let before = items.len();
+                keep(items, &lttb_indices(&ys, points));
+                warnings.push(format!("{} downsampled from {} to {} points", path, before, items.len()));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn walk_items(items: &mut [Value], path: &str, points: usize, warnings: &mut Vec<String>) {
+    for (i, child) in items.iter_mut().enumerate() {
+        walk(child, &format!("{}[{}]", path, i), points, warnings);
+    }
+}
+
+/// Thin the object's series, grouped by length; each group follows the
+/// first series (in key order) of that length.
+fn thin_object(map: &mut Map<String, Value>, path: &str, points: usize, warnings: &mut Vec<String>) {
+    let mut groups: Vec<(usize, Vec<usize>, Vec<String>)> = Vec::new();
+    for (key, child) in map.iter() {
+        let Value::Array(items) = child else { continue };
+        if items.len() <= points {
+            continue;
+        }
+        let Some(ys) = series_values(items) else { continue };
+        match groups.iter_mut().find(|(len, _, _)| *len == ys.len()) {
+            Some(group) => group.2.push(key.clone()),
+            None => groups.push((ys.len(), lttb_indices(&ys, points), vec![key.clone()])),
+        }
+    }
+    for (len, indices, keys) in groups {
+        for key in &keys {
+            if let Some(Value::Array(items)) = map.get_mut(key) {
+                keep(items, &indices);
+            }
+        }
+        let names: Vec<String> = keys.iter().map(|k| format!("{}.{}", path, k)).collect();
+        warnings.push(format!("{} downsampled from {} to {} points", names.join(", "), len, indices.len()));
+    }
+}
+
+fn keep(items: &mut Vec<Value>, indices: &[usize]) {
+    let taken = std::mem::take(items);
+    let mut wanted = indices.iter().peekable();
+    *items = taken.into_iter().enumerate()
+        .filter(|(i, _)| wanted.next_if(|&&w| w == *i).is_some())
+        .map(|(_, v)| v)
+        .collect();
+}
+
+/// The y values of a plottable series, or None if `items` isn't one. Nulls
+/// (indicator warm-up) carry the previous value so they never win a bucket.
+fn series_values(items: &[Value]) -> Option<Vec<f64>> {
+    let field = match items.first()? {
+        Value::Number(_) | Value::Null => None,
+        Value::Object(obj) => Some(*Y_FIELDS.iter().find(|f| obj.get(**f).is_some_and(Value::is_number))?),
+        _ => return None,
+    };
+    let mut last = 0.0;
+    let mut ys = Vec::with_capacity(items.len());
+    let mut any = false;
+    for item in items {
+        let y = match (field, item) {
+            (None, Value::Number(n)) => n.as_f64(),
+            (None, Value::Null) => None,
+            (Some(f), Value::Object(obj)) => obj.get(f).and_then(Value::as_f64),
+            _ => return None,
+        };
+        if let Some(y) = y.filter(|y| y.is_finite()) {
+            last = y;
+            any = true;
+        }
+        ys.push(last);
+    }
+    any.then_some(ys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lttb_keeps_endpoints_and_peaks() {
+        let mut ys: Vec<f64> = (0..1000).map(|i| (i as f64 * 0.01).sin()).collect();
+        ys[500] = 50.0;
+        let idx = lttb_indices(&ys, 50);
+        assert_eq!(idx.len(), 50);
+        assert_eq!((idx[0], idx[49]), (0, 999));
+        assert!(idx.contains(&500));
+        assert!(idx.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(lttb_indices(&ys[..10], 50).len(), 10);
+    }
+
+    #[test]
+    fn test_apply_keeps_parallel_series_aligned() {
+        let curve: Vec<Value> = (0..300).map(|i| json!({ "timestamp": format!("t{}", i), "equity": (i % 37) as f64 })).collect();
+        let ema: Vec<Value> = (0..300).map(|i| if i < 5 { Value::Null } else { json!(i as f64) }).collect();
+        let rsi: Vec<Value> = (0..300).map(|i| json!((i % 11) as f64)).collect();
+        let mut data = json!({ "equity_curve": curve, "indicators": { "ema": ema, "rsi": rsi }, "short": [1.0, 2.0] });
+        let warnings = apply(&mut data, 40);
+
+        assert_eq!(data["equity_curve"].as_array().unwrap().len(), 40);
+        assert_eq!(data["short"].as_array().unwrap().len(), 2);
+        let ema = data["indicators"]["ema"].as_array().unwrap();
+        let rsi = data["indicators"]["rsi"].as_array().unwrap();
+        assert_eq!(ema.len(), 40);
+        assert_eq!(rsi.len(), 40);
+        // Same kept indices: ema holds the original index once warmed up.
+        for (e, r) in ema.iter().zip(rsi) {
+            if let Some(i) = e.as_f64() {
+                assert_eq!(r.as_f64().unwrap(), (i as usize % 11) as f64);
+            }
+        }
+        assert_eq!(ema.last().unwrap(), 299.0);
+        assert_eq!(warnings.len(), 2);
+    }
+}
@@ -0,0 +1,212 @@
+//! Fixed-point prices and cash for precision-sensitive instruments.
+//!
+//! By default the backtester works in f64 and reports prices and amounts to
+//! 2 decimals, which is fine for NSE cash and F&O but mangles sub-rupee or
+//! crypto prices and lets rounding noise build up over long equity curves.
+//! With `precision: {"tick_size", "decimals"}` every fill snaps to the tick
+//! grid and cash, costs and P&L are booked as integer counts of
+//! 10^-`decimals`, so the trade log and equity curve reconcile to the last
+//! unit and prices are reported at the tick's own precision.
+
+use serde::{Deserialize, Serialize};
+
+/// Beyond this f64 cannot hold a price/amount to the last unit anyway.
+const MAX_DECIMALS: u32 = 12;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PrecisionSpec {
+    /// Minimum price increment, e.g. 0.05 (NSE) or 0.00000001 (BTC pairs).
+    pub tick_size: f64,
+    /// Decimals kept for cash, costs and P&L; at least the tick's own
+    /// decimals. Default: the tick's decimals, but no fewer than 2.
+    #[serde(default)]
+    pub decimals: Option<u32>,
+}
+
+/// Price and amount handling for one run: plain f64 (the default) or
+/// tick-snapped prices with integer cash units.
+#[derive(Clone, Copy, Debug)]
+pub struct Money {
+    fixed: Option<Fixed>,
+    /// Contract multiplier applied to notionals and P&L.
+    multiplier: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Fixed {
+    tick: f64,
+    price_decimals: u32,
+    decimals: u32,
+    /// 10^decimals.
+    scale: f64,
+}
+
+impl Money {
+    pub fn float() -> Self {
+        Money { fixed: None, multiplier: 1.0 }
+    }
+
+    /// The same handling for a contract worth `multiplier` per point.
+    pub fn with_multiplier(self, multiplier: f64) -> Self {
+        Money { multiplier, ..self }
+    }
+
+    pub fn from_spec(spec: Option<&PrecisionSpec>) -> Result<Self, String> {
+        let Some(spec) = spec else { return Ok(Self::float()) };
+        if !(spec.tick_size.is_finite() && spec.tick_size > 0.0) {
+            return Err("precision.tick_size must be positive".to_string());
+        }
+        let price_decimals = (0..=MAX_DECIMALS)
+            .find(|&d| {
+                let scaled = spec.tick_size * 10f64.powi(d as i32);
+                scaled.round() >= 1.0 && (scaled - scaled.round()).abs() < 1e-9 * scaled
+            })
+            .ok_or_else(|| format!("precision.tick_size {} has more than {} decimals", spec.tick_size, MAX_DECIMALS))?;
+        let decimals = spec.decimals.unwrap_or(price_decimals.max(2));
+        if decimals < price_decimals || decimals > MAX_DECIMALS {
+            return Err(format!(
+                "precision.decimals must be between the tick's {} decimals and {}",
+                price_decimals, MAX_DECIMALS
+            ));
+        }
+        Ok(Money {
+            fixed: Some(Fixed {
+                tick: spec.tick_size,
+                price_decimals,
+                decimals,
+                scale: 10f64.powi(decimals as i32),
+            }),
+            multiplier: 1.0,
+        })
+    }
+
+    /// Nearest price on the tick grid.
+    pub fn snap(&self, price: f64) -> f64 {
+        match self.fixed {
+            Some(f) => round_to((price / f.tick).round() * f.tick, f.price_decimals),
+            None => price,
+        }
+    }
+
+    /// An amount rounded to whole cash units.
+    pub fn amount(&self, value: f64) -> f64 {
+        match self.fixed {
+            Some(f) => (value * f.scale).round() / f.scale,
+            None => value,
+        }
+    }
+
+    /// Cash value of `qty` at `price`, rounded to whole cash units.
+    pub fn notional(&self, price: f64, qty: i64) -> f64 {
+        self.amount(price * qty as f64 * self.multiplier)
+    }
+
+    /// Gross P&L of `qty` from `entry` to `exit`. In fixed mode it is the
+    /// difference of the two rounded notionals, so it matches the cash moved.
+    pub fn gross_pnl(&self, entry: f64, exit: f64, qty: i64, is_short: bool) -> f64 {
+        let size = qty as f64 * self.multiplier;
+        match self.fixed {
+            Some(f) => {
+                let units = |price: f64| (price * size * f.scale).round() as i128;
+                let diff = units(exit) - units(entry);
+                (if is_short { -diff } else { diff }) as f64 / f.scale
+            }
+            None if is_short => (entry - exit) * size,
+            None => (exit - entry) * size,
+        }
+    }
+
+    /// A price as reported: 2 decimals, or the tick's decimals in fixed mode.
+    pub fn price_out(&self, price: f64) -> f64 {
+        match self.fixed {
+            Some(f) => round_to(price, f.price_decimals),
+            None => round_to(price, 2),
+        }
+    }
+
+    /// An amount as reported: 2 decimals, or `decimals` in fixed mode.
+    pub fn amount_out(&self, value: f64) -> f64 {
+        match self.fixed {
+            Some(f) => round_to(value, f.decimals),
+            None => round_to(value, 2),
+        }
+    }
+
+    /// A cash balance starting at `initial`.
+    pub fn cash(&self, initial: f64) -> Cash {
+        match self.fixed {
+            Some(f) => Cash::Units { units: (initial * f.scale).round() as i128, scale: f.scale },
+            None => Cash::Float(initial),
+        }
+    }
+}
+
+/// Running cash balance. In fixed mode every movement is rounded to a whole
+/// unit and summed as an integer, so no drift builds up however many fills.
+#[derive(Clone, Copy, Debug)]
+pub enum Cash {
+    Float(f64),
+    Units { units: i128, scale: f64 },
+}
+
+impl Cash {
+    pub fn add(&mut self, amount: f64) {
+        match self {
+            Cash::Float(v) => *v += amount,
+            Cash::Units { units, scale } => *units += (amount * *scale).round() as i128,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        match self {
+            Cash::Float(v) => *v,
+            Cash::Units { units, scale } => *units as f64 / *scale,
+        }
+    }
+}
+
+fn round_to(v: f64, decimals: u32) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    (v * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(tick: f64, decimals: Option<u32>) -> Money {
+        Money::from_spec(Some(&PrecisionSpec { tick_size: tick, decimals })).unwrap()
+    }
+
+    #[test]
+    fn test_ticks_and_decimals() {
+        let m = fixed(0.00000001, None);
+        assert_eq!(m.snap(0.000123456789), 0.00012346);
+        assert_eq!(m.price_out(0.00012346), 0.00012346);
+        assert_eq!(Money::float().price_out(0.00012346), 0.0);
+        let nse = fixed(0.05, None);
+        assert_eq!(nse.snap(101.23), 101.25);
+        assert_eq!(nse.amount(12.3456), 12.35);
+        assert!(Money::from_spec(Some(&PrecisionSpec { tick_size: 0.05, decimals: Some(1) })).is_err());
+        assert!(Money::from_spec(Some(&PrecisionSpec { tick_size: 0.0, decimals: None })).is_err());
+    }
+
+    #[test]
+    fn test_cash_units_do_not_drift() {
+        let m = fixed(0.01, None);
+        let mut fixed_cash = m.cash(0.0);
+        let mut float_cash = Money::float().cash(0.0);
+        for _ in 0..1_000_000 {
+            fixed_cash.add(0.1);
+            float_cash.add(0.1);
+        }
+        assert_eq!(fixed_cash.value(), 100_000.0);
+        assert_ne!(float_cash.value(), 100_000.0);
+        // P&L is the difference of the rounded notionals, so it matches cash.
+        assert_eq!(m.gross_pnl(10.05, 10.10, 3, false), 0.15);
+        assert_eq!(m.gross_pnl(10.05, 10.10, 3, true), -0.15);
+        let lot = m.with_multiplier(10.0);
+        assert_eq!(lot.gross_pnl(10.05, 10.10, 3, false), 1.5);
+        assert_eq!(lot.notional(10.05, 3), 301.5);
+    }
+}
@@ -1,9 +1,20 @@
+//! Bootstrap forward equity paths from an empirical distribution.
+//!
+//! Draws come from `returns` (per-bar or per-trade fractions, compounded) or
+//! `trade_pnls` (absolute P&L per trade, added to NAV; a path that hits zero
+//! stays there). Each of `time_horizon` steps is one draw. NAV and drawdown
+//! bands are sampled at up to 50 `steps`; `drawdown_percentile_95` is the
+//! deep tail, as is `max_drawdown_percentiles.p95`.
+
 use serde::{Deserialize, Serialize};
 use crate::utils::{round2, round4, Xorshift64};
 
 #[derive(Deserialize)]
 struct Config {
+    #[serde(default)]
     returns: Vec<f64>,
+    #[serde(default)]
+    trade_pnls: Option<Vec<f64>>,
     initial_capital: f64,
     num_simulations: Option<usize>,
     time_horizon: Option<usize>,
@@ -18,6 +29,25 @@ struct SimResult {
     percentile_50: Vec<f64>,
     percentile_75: Vec<f64>,
     percentile_95: Vec<f64>,
+    /// Draw index (1-based) of each band point.
+    #[serde(default)]
+    steps: Vec<usize>,
+    /// Drawdown from the running peak at each band point, in percent.
+    #[serde(default)]
+    drawdown_percentile_5: Vec<f64>,
+    #[serde(default)]
+    drawdown_percentile_25: Vec<f64>,
+    #[serde(default)]
+    drawdown_percentile_50: Vec<f64>,
+    #[serde(default)]
+    drawdown_percentile_75: Vec<f64>,
+    #[serde(default)]
+    drawdown_percentile_95: Vec<f64>,
+    #[serde(default)]
+    final_nav_percentiles: Bands,
+    /// Worst peak-to-trough over the horizon, in percent.
+    #[serde(default)]
+    max_drawdown_percentiles: Bands,
     var_95: f64,
     var_99: f64,
     cvar_95: f64,
@@ -32,61 +62,95 @@ struct SimResult {
     kurtosis: f64,
 }
 
+#[derive(Serialize, Deserialize, Default)]
+struct Bands {
+    p5: f64,
+    p25: f64,
+    p50: f64,
+    p75: f64,
+    p95: f64,
+}
+
+impl Bands {
+    fn from_sorted(vals: &[f64], scale: f64) -> Self {
+        Bands {
+            p5: round2(pick(vals, 0.05) * scale),
+            p25: round2(pick(vals, 0.25) * scale),
+            p50: round2(pick(vals, 0.50) * scale),
+            p75: round2(pick(vals, 0.75) * scale),
+            p95: round2(pick(vals, 0.95) * scale),
+        }
+    }
+}
+
+const BAND_POINTS: usize = 50;
+
 pub fn compute(data: serde_json::Value) -> Result<serde_json::Value, String> {
     let config: Config = serde_json::from_value(data).map_err(|e| format!("Invalid input: {}", e))?;
 
     let n_sims = config.num_simulations.unwrap_or(10_000).min(50_000);
     let horizon = config.time_horizon.unwrap_or(252);
-    let returns = &config.returns;
+    let additive = config.trade_pnls.is_some();
+    let draws = config.trade_pnls.as_ref().unwrap_or(&config.returns);
 
-    if returns.len() < 5 {
-        return Err("Need at least 5 historical returns".into());
+    if draws.len() < 5 {
+        return Err("Need at least 5 historical returns or trade_pnls".into());
+    }
+    if horizon == 0 {
+        return Err("time_horizon must be positive".into());
     }
+    // Kelly statistics need fractional returns; trade P&L is taken against starting capital.
+    let scaled: Vec<f64>;
+    let returns = if additive {
+        if config.initial_capital <= 0.0 {
+            return Err("initial_capital must be positive with trade_pnls".into());
+        }
+        scaled = draws.iter().map(|p| p / config.initial_capital).collect();
+        &scaled
+    } else {
+        draws
+    };
 
-    let n_ret = returns.len();
+    let n_ret = draws.len();
     let mut rng = Xorshift64::new(config.seed.unwrap_or(42));
+    let steps = band_steps(horizon, BAND_POINTS);
 
     let mut final_navs = Vec::with_capacity(n_sims);
     let mut max_drawdowns = Vec::with_capacity(n_sims);
-    let mut all_paths: Vec<Vec<f64>> = Vec::with_capacity(n_sims);
+    // Only the band points are kept per path: [step][sim].
+    let mut nav_at: Vec<Vec<f64>> = vec![Vec::with_capacity(n_sims); steps.len()];
+    let mut dd_at: Vec<Vec<f64>> = vec![Vec::with_capacity(n_sims); steps.len()];
 
     for _ in 0..n_sims {
         let mut nav = config.initial_capital;
         let mut peak = nav;
         let mut max_dd = 0.0f64;
-        let mut path = Vec::with_capacity(horizon);
+        let mut next = 0;
 
-        for _ in 0..horizon {
+        for t in 0..horizon {
             let idx = rng.next_usize(n_ret);
-            let r = returns[idx];
-            nav *= 1.0 + r;
+            let r = draws[idx];
+            nav = if additive { (nav + r).max(0.0) } else { nav * (1.0 + r) };
             if nav > peak { peak = nav; }
             let dd = if peak > 0.0 { (peak - nav) / peak } else { 0.0 };
             if dd > max_dd { max_dd = dd; }
-            path.push(nav);
+            if next < steps.len() && steps[next] == t {
+                nav_at[next].push(nav);
+                dd_at[next].push(dd);
+                next += 1;
+            }
         }
 
         final_navs.push(nav);
         max_drawdowns.push(max_dd);
-        all_paths.push(path);
     }
 
-    let mut paths_p5 = vec![0.0f64; horizon];
-    let mut paths_p25 = vec![0.0f64; horizon];
-    let mut paths_p50 = vec![0.0f64; horizon];
-    let mut paths_p75 = vec![0.0f64; horizon];
-    let mut paths_p95 = vec![0.0f64; horizon];
-
-    for t in 0..horizon {
-        let mut vals: Vec<f64> = all_paths.iter().map(|p| p[t]).collect();
-        vals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-        let n = vals.len();
-        paths_p5[t] = vals[(0.05 * n as f64) as usize];
-        paths_p25[t] = vals[(0.25 * n as f64) as usize];
-        paths_p50[t] = vals[(0.50 * n as f64) as usize];
-        paths_p75[t] = vals[(0.75 * n as f64) as usize];
-        paths_p95[t] = vals[((0.95 * n as f64) as usize).min(n - 1)];
-    }
+    let band = |cols: &mut [Vec<f64>], p: f64, scale: f64| -> Vec<f64> {
+        cols.iter_mut().map(|vals| {
+            vals.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            round2(pick(vals, p) * scale)
+        }).collect()
+    };
 
     final_navs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
     max_drawdowns.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
@@ -135,11 +199,19 @@ pub fn compute(data: serde_json::Value) -> Result<serde_json::Value, String> {
     let optimal_size = (kelly * 0.5).max(0.0).min(0.25);
 
     let result = SimResult {
-        percentile_5: decimate(&paths_p5, 50),
-        percentile_25: decimate(&paths_p25, 50),
-        percentile_50: decimate(&paths_p50, 50),
-        percentile_75: decimate(&paths_p75, 50),
-        percentile_95: decimate(&paths_p95, 50),
+        percentile_5: band(&mut nav_at, 0.05, 1.0),
+        percentile_25: band(&mut nav_at, 0.25, 1.0),
+        percentile_50: band(&mut nav_at, 0.50, 1.0),
+        percentile_75: band(&mut nav_at, 0.75, 1.0),
+        percentile_95: band(&mut nav_at, 0.95, 1.0),
+        steps: steps.iter().map(|t| t + 1).collect(),
+        drawdown_percentile_5: band(&mut dd_at, 0.05, 100.0),
+        drawdown_percentile_25: band(&mut dd_at, 0.25, 100.0),
+        drawdown_percentile_50: band(&mut dd_at, 0.50, 100.0),
+        drawdown_percentile_75: band(&mut dd_at, 0.75, 100.0),
+        drawdown_percentile_95: band(&mut dd_at, 0.95, 100.0),
+        final_nav_percentiles: Bands::from_sorted(&final_navs, 1.0),
+        max_drawdown_percentiles: Bands::from_sorted(&max_drawdowns, 100.0),
         var_95: round2(var_95),
         var_99: round2(var_99),
         cvar_95: round2(cvar_95),
@@ -157,10 +229,16 @@ pub fn compute(data: serde_json::Value) -> Result<serde_json::Value, String> {
     serde_json::to_value(result).map_err(|e| e.to_string())
 }
 
-fn decimate(data: &[f64], target: usize) -> Vec<f64> {
-    if data.len() <= target { return data.iter().map(|v| round2(*v)).collect(); }
-    let step = data.len() as f64 / target as f64;
-    (0..target).map(|i| round2(data[(i as f64 * step) as usize])).collect()
+/// Indices of the band points: every step for short horizons, else `target` evenly spaced.
+fn band_steps(len: usize, target: usize) -> Vec<usize> {
+    if len <= target { return (0..len).collect(); }
+    let step = len as f64 / target as f64;
+    (0..target).map(|i| (i as f64 * step) as usize).collect()
+}
+
+/// Value at quantile `p` of an ascending slice.
+fn pick(sorted: &[f64], p: f64) -> f64 {
+    sorted[((p * sorted.len() as f64) as usize).min(sorted.len() - 1)]
 }
 
 #[cfg(test)]
@@ -262,6 +340,36 @@ mod tests {
             "same seed should produce same result");
     }
 
+    #[test]
+    fn test_drawdown_bands_ordered() {
+        let r = run_sim(sample_returns(), 100000.0, 500);
+        assert_eq!(r.steps, (1..=20).collect::<Vec<_>>());
+        assert_eq!(r.drawdown_percentile_95.len(), 20);
+        for t in 0..20 {
+            assert!(r.drawdown_percentile_5[t] <= r.drawdown_percentile_50[t]);
+            assert!(r.drawdown_percentile_50[t] <= r.drawdown_percentile_95[t]);
+        }
+        let m = &r.max_drawdown_percentiles;
+        assert!(m.p5 <= m.p50 && m.p50 <= m.p95);
+        assert_eq!(m.p95, round2(r.max_drawdown_95 * 100.0));
+        assert_eq!(r.final_nav_percentiles.p50, r.median_final_nav);
+    }
+
+    #[test]
+    fn test_trade_pnls_additive() {
+        let result = compute(json!({
+            "trade_pnls": [1000.0, 1000.0, 1000.0, 1000.0, 1000.0],
+            "initial_capital": 50000.0,
+            "num_simulations": 50,
+            "time_horizon": 120,
+        })).unwrap();
+        let r: SimResult = serde_json::from_value(result).unwrap();
+        assert_eq!(r.expected_final_nav, 170000.0);
+        assert_eq!(r.percentile_5.len(), 50);
+        assert_eq!(r.steps[1], 3);
+        assert_eq!(r.max_drawdown_percentiles.p95, 0.0);
+    }
+
     #[test]
     fn test_skewness_and_kurtosis_finite() {
         let r = run_sim(sample_returns(), 100000.0, 500);
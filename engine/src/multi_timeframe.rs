@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use crate::utils::{Candle, get_f64, calc_atr_candles};
-use crate::signals;
+use crate::utils::{Candle, calc_atr_candles, sanitize_candles};
+use crate::indicators;
 
 #[derive(Deserialize)]
 struct MTFInput {
@@ -62,25 +62,17 @@ fn analyze_trend(candles: &[Candle]) -> (String, f64) {
         return ("neutral".to_string(), 0.0);
     }
 
-    let candles_json = match serde_json::to_value(
-        &serde_json::json!({ "candles": candles })
-    ) {
-        Ok(v) => v,
-        Err(_) => return ("neutral".to_string(), 0.0),
-    };
-
-    let indicators = match signals::compute(candles_json) {
-        Ok(v) => v,
-        Err(_) => return ("neutral".to_string(), 0.0),
-    };
+    let mut clean = candles.to_vec();
+    sanitize_candles(&mut clean);
+    let indicators = indicators::standard(&clean);
 
     let n = candles.len();
     let last = n - 1;
 
-    let ema9 = get_f64(&indicators, "ema_9", last);
-    let ema21 = get_f64(&indicators, "ema_21", last);
-    let rsi = get_f64(&indicators, "rsi_14", last);
-    let supertrend = get_f64(&indicators, "supertrend", last);
+    let ema9 = indicators.ema_9[last];
+    let ema21 = indicators.ema_21[last];
+    let rsi = indicators.rsi_14[last];
+    let supertrend = indicators.supertrend[last];
     let close = candles[last].close;
 
     if ema21 == 0.0 {
@@ -0,0 +1,586 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::OptionsConfig;
+use crate::utils::{round2, round3};
+
+#[derive(Deserialize)]
+struct OiConfig {
+    #[serde(default)]
+    bars: Vec<OiBar>,
+    #[serde(default)]
+    strikes: Vec<StrikeOi>,
+    /// Minimum |OI change %| for a bar to be classified; smaller moves are NEUTRAL.
+    #[serde(default)]
+    oi_threshold_pct: f64,
+    #[serde(default = "default_recent_bars")]
+    recent_bars: usize,
+    #[serde(default = "default_top_n")]
+    top_n: usize,
+}
+
+fn default_recent_bars() -> usize { 5 }
+fn default_top_n() -> usize { 3 }
+
+#[derive(Deserialize)]
+struct OiBar {
+    #[serde(default)]
+    timestamp: String,
+    price: f64,
+    oi: f64,
+    #[serde(default)]
+    volume: f64,
+}
+
+/// One strike of an option-chain snapshot. OI change is taken from the
+/// explicit `*_oi_change` field or derived from `prev_*_oi`.
+#[derive(Deserialize)]
+struct StrikeOi {
+    strike: f64,
+    #[serde(default)]
+    call_oi: f64,
+    #[serde(default)]
+    put_oi: f64,
+    call_oi_change: Option<f64>,
+    put_oi_change: Option<f64>,
+    prev_call_oi: Option<f64>,
+    prev_put_oi: Option<f64>,
+    #[serde(default)]
+    call_volume: f64,
+    #[serde(default)]
+    put_volume: f64,
+}
+
+#[derive(Serialize)]
+struct OiResult {
+    bars: Vec<BarClassification>,
+    summary: BuildupSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strike_concentration: Option<StrikeConcentration>,
+}
+
+#[derive(Serialize)]
+struct BarClassification {
+    timestamp: String,
+    price: f64,
+    oi: f64,
+    price_change_pct: f64,
+    oi_change_pct: f64,
+    volume: f64,
+    buildup: String,
+}
+
+#[derive(Serialize)]
+struct BuildupSummary {
+    long_buildup: usize,
+    short_buildup: usize,
+    long_unwinding: usize,
+    short_covering: usize,
+    neutral: usize,
+    total_oi_change_pct: f64,
+    recent_dominant: String,
+    signal: String,
+}
+
+#[derive(Serialize)]
+struct StrikeConcentration {
+    strikes: Vec<StrikeOiChange>,
+    total_call_oi_change: f64,
+    total_put_oi_change: f64,
+    top_call_writing: Vec<f64>,
+    top_put_writing: Vec<f64>,
+    oi_resistance: Option<f64>,
+    oi_support: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct StrikeOiChange {
+    strike: f64,
+    call_oi: f64,
+    put_oi: f64,
+    call_oi_change: f64,
+    put_oi_change: f64,
+    call_share_pct: f64,
+    put_share_pct: f64,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: OiConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid oi_analysis config: {}", e))?;
+
+    if config.bars.len() < 2 && config.strikes.is_empty() {
+        return Err("Need at least 2 price/OI bars or an option-chain strike list".to_string());
+    }
+
+    let bars = classify_bars(&config.bars, config.oi_threshold_pct);
+    let summary = summarize(&bars, &config.bars, config.recent_bars);
+    let strike_concentration = if config.strikes.is_empty() {
+        None
+    } else {
+        Some(strike_concentration(&config.strikes, config.top_n))
+    };
+
+    let result = OiResult { bars, summary, strike_concentration };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Classic price/OI quadrant: rising OI with the move means fresh positions,
+/// falling OI means existing positions are being closed.
+fn classify(price_change: f64, oi_change_pct: f64, threshold_pct: f64) -> &'static str {
+    if oi_change_pct.abs() < threshold_pct || oi_change_pct == 0.0 || price_change == 0.0 {
+        return "NEUTRAL";
+    }
+    match (price_change > 0.0, oi_change_pct > 0.0) {
+        (true, true) => "LONG_BUILDUP",
+        (false, true) => "SHORT_BUILDUP",
+        (false, false) => "LONG_UNWINDING",
+        (true, false) => "SHORT_COVERING",
+    }
+}
+
+fn classify_bars(bars: &[OiBar], threshold_pct: f64) -> Vec<BarClassification> {
+    bars.windows(2).map(|w| {
+        let (prev, cur) = (&w[0], &w[1]);
+        let price_change_pct = if prev.price > 0.0 { (cur.price - prev.price) / prev.price * 100.0 } else { 0.0 };
+        let oi_change_pct = if prev.oi > 0.0 { (cur.oi - prev.oi) / prev.oi * 100.0 } else { 0.0 };
+        BarClassification {
+            timestamp: cur.timestamp.clone(),
+            price: cur.price,
+            oi: cur.oi,
+            price_change_pct: round2(price_change_pct),
+            oi_change_pct: round2(oi_change_pct),
+            volume: cur.volume,
+            buildup: classify(cur.price - prev.price, oi_change_pct, threshold_pct).to_string(),
+        }
+    }).collect()
+}
+
+fn summarize(classified: &[BarClassification], bars: &[OiBar], recent_bars: usize) -> BuildupSummary {
+    let count = |label: &str| classified.iter().filter(|b| b.buildup == label).count();
+
+    let recent = &classified[classified.len().saturating_sub(recent_bars.max(1))..];
+    let labels = ["LONG_BUILDUP", "SHORT_BUILDUP", "LONG_UNWINDING", "SHORT_COVERING"];
+    let recent_dominant = labels.iter()
+        .map(|l| (*l, recent.iter().filter(|b| b.buildup == *l).count()))
+        .filter(|(_, n)| *n > 0)
+        .max_by_key(|(_, n)| *n)
+        .map(|(l, _)| l)
+        .unwrap_or("NEUTRAL");
+
+    let total_oi_change_pct = match (bars.first(), bars.last()) {
+        (Some(f), Some(l)) if f.oi > 0.0 => (l.oi - f.oi) / f.oi * 100.0,
+        _ => 0.0,
+    };
+
+    let signal = match recent_dominant {
+        "LONG_BUILDUP" | "SHORT_COVERING" => "BULLISH",
+        "SHORT_BUILDUP" | "LONG_UNWINDING" => "BEARISH",
+        _ => "NEUTRAL",
+    };
+
+    BuildupSummary {
+        long_buildup: count("LONG_BUILDUP"),
+        short_buildup: count("SHORT_BUILDUP"),
+        long_unwinding: count("LONG_UNWINDING"),
+        short_covering: count("SHORT_COVERING"),
+        neutral: count("NEUTRAL"),
+        total_oi_change_pct: round2(total_oi_change_pct),
+        recent_dominant: recent_dominant.to_string(),
+        signal: signal.to_string(),
+    }
+}
+
+fn strike_concentration(strikes: &[StrikeOi], top_n: usize) -> StrikeConcentration {
+    let changes: Vec<(f64, f64)> = strikes.iter().map(|s| {
+        let call = s.call_oi_change.unwrap_or_else(|| s.prev_call_oi.map(|p| s.call_oi - p).unwrap_or(0.0));
+        let put = s.put_oi_change.unwrap_or_else(|| s.prev_put_oi.map(|p| s.put_oi - p).unwrap_or(0.0));
+        (call, put)
+    }).collect();
+
+    let total_call: f64 = changes.iter().map(|c| c.0).sum();
+    let total_put: f64 = changes.iter().map(|c| c.1).sum();
+    let abs_call: f64 = changes.iter().map(|c| c.0.abs()).sum();
+    let abs_put: f64 = changes.iter().map(|c| c.1.abs()).sum();
+
+    let mut rows: Vec<StrikeOiChange> = strikes.iter().zip(changes.iter()).map(|(s, &(c, p))| {
+        StrikeOiChange {
+            strike: s.strike,
+            call_oi: s.call_oi,
+            put_oi: s.put_oi,
+            call_oi_change: round2(c),
+            put_oi_change: round2(p),
+            call_share_pct: if abs_call > 0.0 { round2(c.abs() / abs_call * 100.0) } else { 0.0 },
+            put_share_pct: if abs_put > 0.0 { round2(p.abs() / abs_put * 100.0) } else { 0.0 },
+        }
+    }).collect();
+    rows.sort_by(|a, b| a.strike.partial_cmp(&b.strike).unwrap_or(std::cmp::Ordering::Equal));
+
+    let top_by = |key: fn(&StrikeOiChange) -> f64| -> Vec<f64> {
+        let mut v: Vec<&StrikeOiChange> = rows.iter().filter(|r| key(r) > 0.0).collect();
+        v.sort_by(|a, b| key(b).partial_cmp(&key(a)).unwrap_or(std::cmp::Ordering::Equal));
+        v.iter().take(top_n).map(|r| r.strike).collect()
+    };
+    let top_call_writing = top_by(|r| r.call_oi_change);
+    let top_put_writing = top_by(|r| r.put_oi_change);
+
+    StrikeConcentration {
+        oi_resistance: top_call_writing.first().copied(),
+        oi_support: top_put_writing.first().copied(),
+        strikes: rows,
+        total_call_oi_change: round2(total_call),
+        total_put_oi_change: round2(total_put),
+        top_call_writing,
+        top_put_writing,
+    }
+}
+
+#[derive(Deserialize)]
+struct MaxPainConfig {
+    strikes: Vec<StrikeOi>,
+    #[serde(default)]
+    spot: Option<f64>,
+    #[serde(default)]
+    expiry: Option<String>,
+    #[serde(default = "default_top_n")]
+    top_n: usize,
+    /// Contract multiplier applied to the pain values.
+    #[serde(default = "default_lot")]
+    lot_size: f64,
+}
+
+fn default_lot() -> f64 { 1.0 }
+
+#[derive(Serialize)]
+struct MaxPainResult {
+    expiry: Option<String>,
+    max_pain: f64,
+    spot: Option<f64>,
+    /// (spot − max pain) / max pain.
+    spot_distance_pct: Option<f64>,
+    pain_curve: Vec<PainPoint>,
+    total_call_oi: f64,
+    total_put_oi: f64,
+    oi_pcr: f64,
+    /// Largest open interest: call walls cap the upside, put walls the downside.
+    call_walls: Vec<OiWall>,
+    put_walls: Vec<OiWall>,
+    /// Strongest fresh writing from change in OI.
+    change_resistance: Option<f64>,
+    change_support: Option<f64>,
+    top_call_writing: Vec<f64>,
+    top_put_writing: Vec<f64>,
+}
+
+#[derive(Serialize)]
+struct PainPoint {
+    strike: f64,
+    /// Payout to call holders if the underlying settles at `strike`.
+    call_pain: f64,
+    put_pain: f64,
+    total_pain: f64,
+}
+
+#[derive(Serialize)]
+struct OiWall {
+    strike: f64,
+    oi: f64,
+    share_pct: f64,
+}
+
+/// `max_pain`: the settlement strike that minimises what option writers pay
+/// out, plus OI walls and change-in-OI support/resistance for one expiry.
+pub fn max_pain(data: Value) -> Result<Value, String> {
+    let mut config: MaxPainConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid max_pain config: {}", e))?;
+    let mut strikes = std::mem::take(&mut config.strikes);
+    strikes.retain(|s| s.strike.is_finite() && s.strike > 0.0);
+    if strikes.is_empty() {
+        return Err("max_pain requires an option-chain strike list".to_string());
+    }
+    strikes.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+
+    let pain_curve: Vec<PainPoint> = strikes.iter().map(|settle| {
+        let call_pain: f64 = strikes.iter().map(|s| s.call_oi * (settle.strike - s.strike).max(0.0)).sum::<f64>() * config.lot_size;
+        let put_pain: f64 = strikes.iter().map(|s| s.put_oi * (s.strike - settle.strike).max(0.0)).sum::<f64>() * config.lot_size;
+        PainPoint { strike: settle.strike, call_pain: round2(call_pain), put_pain: round2(put_pain), total_pain: round2(call_pain + put_pain) }
+    }).collect();
+    let max_pain = pain_curve.iter()
+        .min_by(|a, b| a.total_pain.total_cmp(&b.total_pain))
+        .map(|p| p.strike)
+        .unwrap_or(0.0);
+
+    let total_call_oi: f64 = strikes.iter().map(|s| s.call_oi).sum();
+    let total_put_oi: f64 = strikes.iter().map(|s| s.put_oi).sum();
+    let walls = |oi: fn(&StrikeOi) -> f64, total: f64| -> Vec<OiWall> {
+        let mut v: Vec<&StrikeOi> = strikes.iter().filter(|s| oi(s) > 0.0).collect();
+        v.sort_by(|a, b| oi(b).total_cmp(&oi(a)));
+        v.iter().take(config.top_n).map(|s| OiWall {
+            strike: s.strike,
+            oi: oi(s),
+            share_pct: if total > 0.0 { round2(oi(s) / total * 100.0) } else { 0.0 },
+        }).collect()
+    };
+    let changes = strike_concentration(&strikes, config.top_n);
+
+    let result = MaxPainResult {
+        expiry: config.expiry,
+        max_pain,
+        spot: config.spot,
+        spot_distance_pct: config.spot.filter(|_| max_pain > 0.0).map(|s| round2((s - max_pain) / max_pain * 100.0)),
+        pain_curve,
+        oi_pcr: if total_call_oi > 0.0 { round2(total_put_oi / total_call_oi) } else { 0.0 },
+        total_call_oi,
+        total_put_oi,
+        call_walls: walls(|s| s.call_oi, total_call_oi),
+        put_walls: walls(|s| s.put_oi, total_put_oi),
+        change_resistance: changes.oi_resistance,
+        change_support: changes.oi_support,
+        top_call_writing: changes.top_call_writing,
+        top_put_writing: changes.top_put_writing,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[derive(Deserialize)]
+struct PcrConfig {
+    snapshots: Vec<ChainSnapshot>,
+    /// Defaults to `options.pcr_high_threshold` / `pcr_low_threshold`.
+    #[serde(default)]
+    high_threshold: Option<f64>,
+    #[serde(default)]
+    low_threshold: Option<f64>,
+    /// Snapshots used for percentiles; 0 = all.
+    #[serde(default)]
+    lookback: usize,
+}
+
+/// One chain snapshot: explicit totals, or per-strike OI/volume summed.
+#[derive(Deserialize)]
+struct ChainSnapshot {
+    #[serde(default)]
+    timestamp: String,
+    #[serde(default)]
+    strikes: Vec<StrikeOi>,
+    call_oi: Option<f64>,
+    put_oi: Option<f64>,
+    call_volume: Option<f64>,
+    put_volume: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct PcrPoint {
+    timestamp: String,
+    oi_pcr: Option<f64>,
+    volume_pcr: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct PcrResult {
+    series: Vec<PcrPoint>,
+    oi_pcr: Option<f64>,
+    volume_pcr: Option<f64>,
+    oi_pcr_change: Option<f64>,
+    /// Share of the lookback at or below the latest value.
+    oi_pcr_percentile: Option<f64>,
+    volume_pcr_percentile: Option<f64>,
+    oi_pcr_zscore: Option<f64>,
+    /// -1 (bearish) .. 1 (bullish). Feed to `scan` as `options_sentiment`.
+    sentiment_score: f64,
+    sentiment: String,
+}
+
+/// `pcr`: volume and OI put-call ratios over a series of chain snapshots.
+///
+/// Higher PCR reads bullish — put writing at mid levels, contrarian at the
+/// extremes — matching the `pcr_extremes` live strategy. The score blends
+/// the latest level against the thresholds with its percentile in history,
+/// OI weighted 0.7 and volume 0.3.
+pub fn pcr(data: Value, options: &OptionsConfig) -> Result<Value, String> {
+    let config: PcrConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid pcr config: {}", e))?;
+    if config.snapshots.is_empty() {
+        return Err("pcr requires at least one chain snapshot".to_string());
+    }
+    let high = config.high_threshold.unwrap_or(options.pcr_high_threshold);
+    let low = config.low_threshold.unwrap_or(options.pcr_low_threshold);
+    if !(low < 1.0 && high > 1.0) {
+        return Err("thresholds must satisfy low_threshold < 1 < high_threshold".to_string());
+    }
+
+    let ratio = |put: f64, call: f64| (call > 0.0).then(|| put / call);
+    let series: Vec<PcrPoint> = config.snapshots.iter().map(|snap| {
+        let sum = |f: fn(&StrikeOi) -> f64| snap.strikes.iter().map(f).sum::<f64>();
+        let call_oi = snap.call_oi.unwrap_or_else(|| sum(|s| s.call_oi));
+        let put_oi = snap.put_oi.unwrap_or_else(|| sum(|s| s.put_oi));
+        let call_vol = snap.call_volume.unwrap_or_else(|| sum(|s| s.call_volume));
+        let put_vol = snap.put_volume.unwrap_or_else(|| sum(|s| s.put_volume));
+        PcrPoint {
+            timestamp: snap.timestamp.clone(),
+            oi_pcr: ratio(put_oi, call_oi).map(round3),
+            volume_pcr: ratio(put_vol, call_vol).map(round3),
+        }
+    }).collect();
+
+    let window = if config.lookback > 0 { &series[series.len().saturating_sub(config.lookback)..] } else { &series[..] };
+    let history = |f: fn(&PcrPoint) -> Option<f64>| -> Vec<f64> { window.iter().filter_map(f).collect() };
+    let oi_hist = history(|p| p.oi_pcr);
+    let vol_hist = history(|p| p.volume_pcr);
+    let latest_oi = series.last().and_then(|p| p.oi_pcr);
+    let latest_vol = series.last().and_then(|p| p.volume_pcr);
+    let percentile = |hist: &[f64], v: Option<f64>| -> Option<f64> {
+        let v = v?;
+        (hist.len() >= 2).then(|| round2(hist.iter().filter(|h| **h <= v).count() as f64 / hist.len() as f64 * 100.0))
+    };
+    let oi_pct = percentile(&oi_hist, latest_oi);
+    let vol_pct = percentile(&vol_hist, latest_vol);
+    let oi_z = latest_oi.filter(|_| oi_hist.len() >= 2).and_then(|v| {
+        let mean = oi_hist.iter().sum::<f64>() / oi_hist.len() as f64;
+        let sd = (oi_hist.iter().map(|h| (h - mean).powi(2)).sum::<f64>() / (oi_hist.len() - 1) as f64).sqrt();
+        (sd > 0.0).then(|| round2((v - mean) / sd))
+    });
+    let oi_change = match series.len() {
+        n if n >= 2 => latest_oi.zip(series[n - 2].oi_pcr).map(|(a, b)| round3(a - b)),
+        _ => None,
+    };
+
+    // Level against the thresholds, averaged with the percentile when known.
+    let component = |v: Option<f64>, pct: Option<f64>| -> Option<f64> {
+        let v = v?;
+        let level = if v >= 1.0 { ((v - 1.0) / (high - 1.0)).min(1.0) } else { -((1.0 - v) / (1.0 - low)).min(1.0) };
+        Some(match pct {
+            Some(p) => (level + (p - 50.0) / 50.0) / 2.0,
+            None => level,
+        })
+    };
+    let score = match (component(latest_oi, oi_pct), component(latest_vol, vol_pct)) {
+        (Some(o), Some(v)) => 0.7 * o + 0.3 * v,
+        (Some(o), None) => o,
+        (None, Some(v)) => v,
+        (None, None) => 0.0,
+    };
+    let sentiment = match score {
+        s if s >= 0.6 => "strongly_bullish",
+        s if s >= 0.2 => "bullish",
+        s if s <= -0.6 => "strongly_bearish",
+        s if s <= -0.2 => "bearish",
+        _ => "neutral",
+    };
+
+    let result = PcrResult {
+        series,
+        oi_pcr: latest_oi,
+        volume_pcr: latest_vol,
+        oi_pcr_change: oi_change,
+        oi_pcr_percentile: oi_pct,
+        volume_pcr_percentile: vol_pct,
+        oi_pcr_zscore: oi_z,
+        sentiment_score: round3(score),
+        sentiment: sentiment.to_string(),
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_classify_quadrants() {
+        assert_eq!(classify(1.0, 2.0, 0.0), "LONG_BUILDUP");
+        assert_eq!(classify(-1.0, 2.0, 0.0), "SHORT_BUILDUP");
+        assert_eq!(classify(-1.0, -2.0, 0.0), "LONG_UNWINDING");
+        assert_eq!(classify(1.0, -2.0, 0.0), "SHORT_COVERING");
+        assert_eq!(classify(1.0, 0.5, 1.0), "NEUTRAL");
+    }
+
+    #[test]
+    fn test_bar_classification_and_summary() {
+        let data = json!({
+            "bars": [
+                { "timestamp": "d1", "price": 100.0, "oi": 1000.0 },
+                { "timestamp": "d2", "price": 102.0, "oi": 1100.0 },
+                { "timestamp": "d3", "price": 104.0, "oi": 1200.0 },
+                { "timestamp": "d4", "price": 103.0, "oi": 1150.0 }
+            ],
+            "recent_bars": 3
+        });
+        let result = compute(data).unwrap();
+        let bars = result["bars"].as_array().unwrap();
+        assert_eq!(bars.len(), 3);
+        assert_eq!(bars[0]["buildup"], "LONG_BUILDUP");
+        assert_eq!(bars[2]["buildup"], "LONG_UNWINDING");
+        assert_eq!(result["summary"]["long_buildup"], 2);
+        assert_eq!(result["summary"]["recent_dominant"], "LONG_BUILDUP");
+        assert_eq!(result["summary"]["signal"], "BULLISH");
+        assert_eq!(result["summary"]["total_oi_change_pct"].as_f64().unwrap(), 15.0);
+    }
+
+    #[test]
+    fn test_strike_concentration() {
+        let data = json!({
+            "strikes": [
+                { "strike": 100.0, "call_oi": 500.0, "put_oi": 4000.0, "put_oi_change": 1500.0, "call_oi_change": -100.0 },
+                { "strike": 110.0, "call_oi": 3000.0, "put_oi": 800.0, "prev_call_oi": 1000.0, "prev_put_oi": 700.0 },
+                { "strike": 105.0, "call_oi": 1200.0, "put_oi": 1200.0, "call_oi_change": 400.0, "put_oi_change": 300.0 }
+            ]
+        });
+        let result = compute(data).unwrap();
+        let sc = &result["strike_concentration"];
+        assert_eq!(sc["oi_resistance"].as_f64().unwrap(), 110.0);
+        assert_eq!(sc["oi_support"].as_f64().unwrap(), 100.0);
+        assert_eq!(sc["strikes"][0]["strike"].as_f64().unwrap(), 100.0);
+        assert_eq!(sc["total_call_oi_change"].as_f64().unwrap(), 2300.0);
+        assert_eq!(sc["top_call_writing"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_max_pain_and_walls() {
+        let data = json!({
+            "spot": 104.0,
+            "strikes": [
+                { "strike": 100.0, "call_oi": 100.0, "put_oi": 3000.0, "put_oi_change": 900.0 },
+                { "strike": 105.0, "call_oi": 1500.0, "put_oi": 1500.0 },
+                { "strike": 110.0, "call_oi": 4000.0, "put_oi": 100.0, "call_oi_change": 1200.0 }
+            ]
+        });
+        let result = max_pain(data).unwrap();
+        // Settling at 105: calls pay 100 × 5, puts pay 100 × 5.
+        assert_eq!(result["max_pain"].as_f64().unwrap(), 105.0);
+        assert_eq!(result["pain_curve"][1]["total_pain"].as_f64().unwrap(), 1000.0);
+        assert_eq!(result["call_walls"][0]["strike"].as_f64().unwrap(), 110.0);
+        assert_eq!(result["put_walls"][0]["strike"].as_f64().unwrap(), 100.0);
+        assert_eq!(result["change_resistance"].as_f64().unwrap(), 110.0);
+        assert_eq!(result["change_support"].as_f64().unwrap(), 100.0);
+        assert_eq!(result["spot_distance_pct"].as_f64().unwrap(), -0.95);
+        assert!(max_pain(json!({ "strikes": [] })).is_err());
+    }
+
+    #[test]
+    fn test_pcr_series_and_sentiment() {
+        let options = OptionsConfig::default();
+        let snap = |ts: &str, call: f64, put: f64| json!({ "timestamp": ts, "call_oi": call, "put_oi": put, "call_volume": 100.0, "put_volume": 90.0 });
+        let data = json!({ "snapshots": [
+            snap("t1", 1000.0, 800.0), snap("t2", 1000.0, 900.0), snap("t3", 1000.0, 1000.0),
+            { "timestamp": "t4", "strikes": [
+                { "strike": 100.0, "call_oi": 400.0, "put_oi": 900.0, "call_volume": 50.0, "put_volume": 70.0 },
+                { "strike": 110.0, "call_oi": 600.0, "put_oi": 500.0, "call_volume": 50.0, "put_volume": 50.0 }
+            ]}
+        ]});
+        let result = pcr(data, &options).unwrap();
+        assert_eq!(result["series"].as_array().unwrap().len(), 4);
+        assert_eq!(result["oi_pcr"].as_f64().unwrap(), 1.4);
+        assert_eq!(result["volume_pcr"].as_f64().unwrap(), 1.2);
+        assert_eq!(result["oi_pcr_change"].as_f64().unwrap(), 0.4);
+        assert_eq!(result["oi_pcr_percentile"].as_f64().unwrap(), 100.0);
+        assert_eq!(result["sentiment"], "strongly_bullish");
+
+        let bearish = pcr(json!({ "snapshots": [snap("t", 1000.0, 450.0)] }), &options).unwrap();
+        assert!(bearish["sentiment_score"].as_f64().unwrap() < -0.6);
+        assert!(pcr(json!({ "snapshots": [] }), &options).is_err());
+    }
+
+    #[test]
+    fn test_requires_input() {
+        assert!(compute(json!({ "bars": [{ "price": 1.0, "oi": 1.0 }] })).is_err());
+    }
+}
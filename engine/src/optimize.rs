@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use crate::backtest;
+use crate::cancel::CancelToken;
+use crate::progress::Progress;
 use crate::utils::generate_combinations_map;
 
 #[derive(Deserialize)]
@@ -29,6 +31,11 @@ struct OptimizeResult {
     best_win_rate: f64,
     best_profit_factor: f64,
     all_results: Vec<ParamResult>,
+    cancelled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cancel_reason: Option<String>,
+    completed_combos: usize,
+    total_combos: usize,
 }
 
 #[derive(Serialize, Clone)]
@@ -43,6 +50,12 @@ struct ParamResult {
 }
 
 pub fn compute(data: Value) -> Result<Value, String> {
+    compute_cancellable(data, &CancelToken::none(), &Progress::none())
+}
+
+/// Like `compute`, but stops between parameter combos once `cancel` trips and
+/// ranks only the combos that finished. Reports one progress unit per combo.
+pub fn compute_cancellable(data: Value, cancel: &CancelToken, progress: &Progress) -> Result<Value, String> {
     let config: OptimizeConfig =
         serde_json::from_value(data).map_err(|e| format!("Invalid optimize config: {}", e))?;
 
@@ -69,7 +82,13 @@ pub fn compute(data: Value) -> Result<Value, String> {
 
     let mut all_results: Vec<ParamResult> = Vec::with_capacity(param_combos.len());
 
-    for combo in &param_combos {
+    let mut cancel_reason = None;
+    for (done, combo) in param_combos.iter().enumerate() {
+        progress.update("combo", done, param_combos.len());
+        if let Some(reason) = cancel.reason() {
+            cancel_reason = Some(reason.to_string());
+            break;
+        }
         let backtest_input = serde_json::json!({
             "strategy": config.strategy,
             "symbol": config.symbol,
@@ -111,6 +130,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
         }
     }
 
+    let completed_combos = all_results.len();
+    if cancel_reason.is_none() {
+        progress.update("combo", param_combos.len(), param_combos.len());
+    }
     all_results.sort_by(|a, b| b.sharpe_ratio.partial_cmp(&a.sharpe_ratio).unwrap_or(std::cmp::Ordering::Equal));
 
     let best = all_results.first().cloned().unwrap_or(ParamResult {
@@ -129,8 +152,50 @@ pub fn compute(data: Value) -> Result<Value, String> {
         best_win_rate: best.win_rate,
         best_profit_factor: best.profit_factor,
         all_results,
+        cancelled: cancel_reason.is_some(),
+        cancel_reason,
+        completed_combos,
+        total_combos: param_combos.len(),
     };
 
     serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn input() -> Value {
+        let candles: Vec<Value> = (0..60).map(|i| {
+            let c = 100.0 + (i as f64 * 0.3).sin() * 5.0 + i as f64 * 0.1;
+            json!({ "timestamp": format!("2024-01-{:02}", (i % 28) + 1),
+                    "open": c, "high": c + 1.0, "low": c - 1.0, "close": c, "volume": 1000.0 })
+        }).collect();
+        json!({
+            "strategy": "ema_crossover", "symbol": "TEST", "initial_capital": 100000.0,
+            "candles": candles,
+            "param_grid": { "short_period": [5.0, 9.0], "long_period": [21.0, 30.0] }
+        })
+    }
+
+    #[test]
+    fn test_optimize_runs_all_combos() {
+        let result = compute(input()).unwrap();
+        assert_eq!(result["cancelled"], false);
+        assert_eq!(result["completed_combos"], 4);
+        assert_eq!(result["total_combos"], 4);
+    }
+
+    #[test]
+    fn test_optimize_cancelled_returns_partial() {
+        let token = CancelToken::new(None);
+        token.cancel();
+        let result = compute_cancellable(input(), &token, &Progress::none()).unwrap();
+        assert_eq!(result["cancelled"], true);
+        assert_eq!(result["cancel_reason"], "cancelled");
+        assert_eq!(result["completed_combos"], 0);
+        assert_eq!(result["total_combos"], 4);
+    }
+}
@@ -0,0 +1,304 @@
+//! Roll candidates for a short option leg.
+//!
+//! The leg is bought back at the ask of its own chain (`strikes`) and a
+//! same-side option is sold at the bid: up or down to strikes within
+//! `max_strike_steps` of it in the same expiry, or out to a later expiry in
+//! `expiries` at the same or a nearby strike. Each candidate reports the
+//! net credit of the roll, the breakeven and lognormal POP of the rolled
+//! position counting the original `premium` (today's mid when absent), and
+//! the change in naked-short margin, estimated as premium plus
+//! `margin_pct` of spot less the out-of-the-money amount, floored at half
+//! that percentage. Credit rolls rank ahead of debit rolls, then by POP.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::chain_analysis::ChainStrike;
+use crate::pop::lognormal_pop;
+use crate::strategy_payoff::{Leg, LegKind};
+use crate::strategy_suggest::{Chain, ChainOption};
+use crate::utils::{round2, round4};
+
+#[derive(Deserialize)]
+struct RollConfig {
+    spot: f64,
+    position: ShortLeg,
+    /// Chain of the position's own expiry.
+    strikes: Vec<ChainStrike>,
+    /// Later expiries to roll out to.
+    #[serde(default)]
+    expiries: Vec<ExpiryChain>,
+    #[serde(default = "default_lot")]
+    lot_size: f64,
+    #[serde(default = "default_steps")]
+    max_strike_steps: usize,
+    #[serde(default = "default_margin_pct")]
+    margin_pct: f64,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    #[serde(default = "default_top")]
+    top: usize,
+}
+
+fn default_lot() -> f64 { 1.0 }
+fn default_steps() -> usize { 3 }
+fn default_margin_pct() -> f64 { 15.0 }
+fn default_rate() -> f64 { 0.065 }
+fn default_top() -> usize { 10 }
+
+#[derive(Deserialize)]
+struct ShortLeg {
+    /// "call"/"ce" or "put"/"pe".
+    option_type: String,
+    strike: f64,
+    expiry_days: f64,
+    /// Lots written, as a positive count.
+    #[serde(default = "default_lots", alias = "quantity")]
+    lots: f64,
+    /// Credit originally received per unit.
+    #[serde(default)]
+    premium: Option<f64>,
+}
+
+fn default_lots() -> f64 { 1.0 }
+
+#[derive(Deserialize)]
+struct ExpiryChain {
+    expiry_days: f64,
+    strikes: Vec<ChainStrike>,
+}
+
+#[derive(Serialize)]
+struct CurrentLeg {
+    option_type: &'static str,
+    strike: f64,
+    expiry_days: f64,
+    /// Cost to buy back, per unit.
+    close_price: f64,
+    iv: f64,
+    delta: f64,
+    breakeven: f64,
+    pop: f64,
+    margin: f64,
+}
+
+#[derive(Serialize)]
+struct RollChoice {
+    rank: usize,
+    /// "up", "down", "out", "out_and_up" or "out_and_down".
+    kind: &'static str,
+    strike: f64,
+    expiry_days: f64,
+    days_added: f64,
+    /// Credit for selling the new leg, per unit.
+    open_price: f64,
+    /// Open credit less close cost, per unit; negative = debit.
+    net_credit: f64,
+    net_credit_total: f64,
+    breakeven: f64,
+    breakeven_shift: f64,
+    pop: f64,
+    pop_change: f64,
+    delta: f64,
+    margin: f64,
+    margin_change: f64,
+}
+
+#[derive(Serialize)]
+struct RollResult {
+    spot: f64,
+    units: f64,
+    current: CurrentLeg,
+    rolls: Vec<RollChoice>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: RollConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid suggest_rolls config: {}", e))?;
+    let spot = config.spot;
+    if !(spot.is_finite() && spot > 0.0) {
+        return Err("spot must be positive".to_string());
+    }
+    let leg = &config.position;
+    let is_call = match leg.option_type.to_lowercase().as_str() {
+        "call" | "ce" => true,
+        "put" | "pe" => false,
+        other => return Err(format!("Unknown option_type '{}' (call, put)", other)),
+    };
+    if leg.expiry_days <= 0.0 {
+        return Err("position expiry_days must be positive".to_string());
+    }
+    if !(leg.lots > 0.0 && config.lot_size > 0.0) {
+        return Err("position lots and lot_size must be positive".to_string());
+    }
+    let units = leg.lots * config.lot_size;
+    let rate = config.risk_free_rate;
+
+    let near = Chain::build(&config.strikes, spot, leg.expiry_days, rate);
+    let held = near.side(is_call).iter().find(|o| (o.strike - leg.strike).abs() < 1e-9).copied()
+        .ok_or_else(|| format!("No {} quote at strike {} in strikes", if is_call { "call" } else { "put" }, leg.strike))?;
+    let close_price = if held.ask > 0.0 { held.ask } else { held.mid };
+    let premium = leg.premium.unwrap_or(held.mid);
+    let breakeven = |strike: f64, credit: f64| if is_call { strike + credit } else { strike - credit };
+    let pop_of = |o: &ChainOption, days: f64, credit: f64| {
+        let short = Leg {
+            kind: if is_call { LegKind::Call } else { LegKind::Put },
+            strike: o.strike,
+            premium: credit,
+            units: -1.0,
+            expiry_days: days,
+            iv: o.iv,
+        };
+        lognormal_pop(&[short], spot, o.iv, days, rate).0
+    };
+    let margin_of = |o: &ChainOption, price: f64| {
+        let otm = if is_call { (o.strike - spot).max(0.0) } else { (spot - o.strike).max(0.0) };
+        let pct = config.margin_pct / 100.0;
+        (price + (pct * spot - otm).max(pct / 2.0 * spot)) * units
+    };
+    let current_pop = pop_of(&held, leg.expiry_days, premium);
+    let current_margin = margin_of(&held, held.mid);
+    let current_breakeven = breakeven(held.strike, premium);
+
+    let mut later: Vec<Chain> = config.expiries.iter()
+        .filter(|e| e.expiry_days > leg.expiry_days)
+        .map(|e| Chain::build(&e.strikes, spot, e.expiry_days, rate))
+        .collect();
+    later.sort_by(|a, b| a.days.total_cmp(&b.days));
+
+    let mut rolls = Vec::new();
+    for chain in std::iter::once(&near).chain(&later) {
+        let side = chain.side(is_call);
+        let Some(centre) = side.iter().enumerate()
+            .min_by(|a, b| (a.1.strike - leg.strike).abs().total_cmp(&(b.1.strike - leg.strike).abs()))
+            .map(|(i, _)| i) else { continue };
+        let (from, to) = (centre.saturating_sub(config.max_strike_steps), (centre + config.max_strike_steps).min(side.len() - 1));
+        for o in &side[from..=to] {
+            let out = chain.days > leg.expiry_days;
+            let kind = match (out, o.strike.total_cmp(&leg.strike)) {
+                (false, std::cmp::Ordering::Equal) => continue,
+                (false, std::cmp::Ordering::Greater) => "up",
+                (false, _) => "down",
+                (true, std::cmp::Ordering::Equal) => "out",
+                (true, std::cmp::Ordering::Greater) => "out_and_up",
+                (true, _) => "out_and_down",
+            };
+            let open_price = if o.bid > 0.0 { o.bid } else { o.mid };
+            let net = open_price - close_price;
+            let new_breakeven = breakeven(o.strike, premium + net);
+            let pop = pop_of(o, chain.days, premium + net);
+            let margin = margin_of(o, o.mid);
+            rolls.push(RollChoice {
+                rank: 0,
+                kind,
+                strike: o.strike,
+                expiry_days: chain.days,
+                days_added: chain.days - leg.expiry_days,
+                open_price: round2(open_price),
+                net_credit: round2(net),
+                net_credit_total: round2(net * units),
+                breakeven: round2(new_breakeven),
+                breakeven_shift: round2(new_breakeven - current_breakeven),
+                pop: round4(pop),
+                pop_change: round4(pop - current_pop),
+                delta: round4(-o.delta),
+                margin: round2(margin),
+                margin_change: round2(margin - current_margin),
+            });
+        }
+    }
+    rolls.sort_by(|a, b| (b.net_credit >= 0.0).cmp(&(a.net_credit >= 0.0))
+        .then(b.pop.total_cmp(&a.pop))
+        .then(b.net_credit.total_cmp(&a.net_credit)));
+    rolls.truncate(config.top.max(1));
+    for (i, r) in rolls.iter_mut().enumerate() {
+        r.rank = i + 1;
+    }
+
+    let result = RollResult {
+        spot,
+        units,
+        current: CurrentLeg {
+            option_type: if is_call { "call" } else { "put" },
+            strike: held.strike,
+            expiry_days: leg.expiry_days,
+            close_price: round2(close_price),
+            iv: round4(held.iv),
+            delta: round4(-held.delta),
+            breakeven: round2(current_breakeven),
+            pop: round4(current_pop),
+            margin: round2(current_margin),
+        },
+        rolls,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::bs_price;
+    use serde_json::json;
+
+    /// A 100-point-step chain priced at a flat IV, quoted one point wide.
+    fn chain(spot: f64, days: f64, iv: f64) -> Vec<Value> {
+        (0..11).map(|i| {
+            let k = 21_500.0 + i as f64 * 100.0;
+            let (c, p) = (bs_price(spot, k, 0.065, days / 365.0, iv, true), bs_price(spot, k, 0.065, days / 365.0, iv, false));
+            json!({ "strike": k, "call_bid": c - 0.5, "call_ask": c + 0.5, "put_bid": (p - 0.5).max(0.05), "put_ask": p + 0.5 })
+        }).collect()
+    }
+
+    fn tested_call() -> Value {
+        // A short 22,000 call with spot rallied through it, four days left.
+        json!({
+            "spot": 22_050.0,
+            "position": { "option_type": "call", "strike": 22_000.0, "expiry_days": 4, "lots": 2, "premium": 60.0 },
+            "lot_size": 50,
+            "strikes": chain(22_050.0, 4.0, 0.14),
+            "expiries": [{ "expiry_days": 11, "strikes": chain(22_050.0, 11.0, 0.14) }]
+        })
+    }
+
+    #[test]
+    fn test_rolls_are_ranked_credit_first() {
+        let result = compute(tested_call()).unwrap();
+        let current = &result["current"];
+        assert_eq!(result["units"], 100.0);
+        assert_eq!(current["breakeven"], 22_060.0);
+        assert!(current["delta"].as_f64().unwrap() < -0.5, "short ITM call");
+        let rolls = result["rolls"].as_array().unwrap();
+        assert_eq!(rolls.len(), 10);
+        assert_eq!(rolls[0]["rank"], 1);
+        // Credit rolls come first, each sorted by POP.
+        let credit: Vec<&Value> = rolls.iter().take_while(|r| r["net_credit"].as_f64().unwrap() >= 0.0).collect();
+        assert!(!credit.is_empty() && credit.len() < rolls.len());
+        assert!(credit.windows(2).all(|w| w[0]["pop"].as_f64() >= w[1]["pop"].as_f64()));
+        // Same strike, one week out: a credit that lifts the breakeven.
+        let out = rolls.iter().find(|r| r["kind"] == "out").unwrap();
+        assert_eq!(out["strike"], 22_000.0);
+        assert_eq!(out["days_added"], 7.0);
+        assert!(out["net_credit"].as_f64().unwrap() > 0.0);
+        assert!(out["breakeven_shift"].as_f64().unwrap() > 0.0);
+        // Rolling up and out moves the short call away from spot and frees margin.
+        let up_out = rolls.iter().find(|r| r["kind"] == "out_and_up" && r["strike"] == 22_200.0).unwrap();
+        assert!(up_out["pop_change"].as_f64().unwrap() > 0.0);
+        assert!(up_out["margin_change"].as_f64().unwrap() < out["margin_change"].as_f64().unwrap());
+        assert!(rolls.iter().all(|r| !(r["kind"] == "up" && r["net_credit"].as_f64().unwrap() > 0.0)),
+            "rolling up in the same expiry is always a debit");
+    }
+
+    #[test]
+    fn test_roll_input_errors() {
+        let mut data = tested_call();
+        data["position"]["strike"] = json!(22_050.0);
+        assert!(compute(data.clone()).unwrap_err().contains("22050"));
+        data["position"]["strike"] = json!(22_000.0);
+        data["position"]["option_type"] = json!("straddle");
+        assert!(compute(data.clone()).is_err());
+        data["position"]["option_type"] = json!("put");
+        data["top"] = json!(3);
+        let puts = compute(data).unwrap();
+        assert_eq!(puts["rolls"].as_array().unwrap().len(), 3);
+        assert_eq!(puts["current"]["option_type"], "put");
+    }
+}
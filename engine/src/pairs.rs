@@ -0,0 +1,537 @@
+//! Pairs trading: Engle-Granger cointegration, hedge ratio and spread z-score.
+//!
+//! The `pairs` command regresses leg A on leg B (log prices by default),
+//! runs an augmented Dickey-Fuller test on the residual spread against
+//! MacKinnon's two-variable critical values, and returns the z-score series
+//! (rolling over `lookback`, 0 = full sample) with entry/exit signals: the
+//! spread is shorted above `entry_z`, bought below `-entry_z`, and closed
+//! inside `exit_z` or beyond `stop_z`.
+//!
+//! `backtest` with `strategy = "pairs"` trades `candles` (A) against
+//! `candles_b` (B). To stay free of look-ahead the hedge ratio there is
+//! re-estimated on the trailing `hedge_lookback` bars and fixed for the
+//! life of each trade; fills are at the signal bar's close.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::{build_costs, CostConfig, EquityPoint};
+use crate::utils::{ols_regression, round2, round4, Candle};
+
+#[derive(Deserialize)]
+struct PairsConfig {
+    #[serde(default)]
+    prices_a: Vec<f64>,
+    #[serde(default)]
+    prices_b: Vec<f64>,
+    #[serde(default)]
+    candles_a: Vec<Candle>,
+    #[serde(default)]
+    candles_b: Vec<Candle>,
+    #[serde(default)]
+    timestamps: Vec<String>,
+    #[serde(flatten)]
+    params: PairParams,
+    /// ADF lag order; None picks Schwert's rule, 12·(n/100)^¼.
+    #[serde(default)]
+    adf_lags: Option<usize>,
+    #[serde(default = "default_significance")]
+    significance: f64,
+}
+
+fn default_significance() -> f64 { 0.05 }
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+struct PairParams {
+    use_log: bool,
+    lookback: usize,
+    entry_z: f64,
+    exit_z: f64,
+    /// 0 disables the stop.
+    stop_z: f64,
+    hedge_lookback: usize,
+    /// Gross notional of both legs as % of equity.
+    capital_pct: f64,
+}
+
+impl Default for PairParams {
+    fn default() -> Self {
+        Self { use_log: true, lookback: 20, entry_z: 2.0, exit_z: 0.5, stop_z: 4.0, hedge_lookback: 60, capital_pct: 100.0 }
+    }
+}
+
+#[derive(Serialize)]
+struct AdfResult {
+    statistic: f64,
+    lags: usize,
+    critical_values: CriticalValues,
+    /// Unit root rejected at `significance` (0.01, 0.05 or 0.10).
+    cointegrated: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CriticalValues {
+    #[serde(rename = "1%")]
+    pub one: f64,
+    #[serde(rename = "5%")]
+    pub five: f64,
+    #[serde(rename = "10%")]
+    pub ten: f64,
+}
+
+#[derive(Serialize)]
+struct SpreadPoint {
+    index: usize,
+    timestamp: Option<String>,
+    spread: f64,
+    zscore: Option<f64>,
+    /// 1 long spread (long A, short B), -1 short spread, 0 flat.
+    position: i8,
+}
+
+#[derive(Serialize)]
+struct PairSignal {
+    index: usize,
+    timestamp: Option<String>,
+    action: &'static str,
+    zscore: f64,
+}
+
+#[derive(Serialize)]
+struct PairsResult {
+    observations: usize,
+    hedge_ratio: f64,
+    intercept: f64,
+    correlation: f64,
+    adf: AdfResult,
+    half_life: Option<f64>,
+    spread_mean: f64,
+    spread_std: f64,
+    current_zscore: Option<f64>,
+    current_position: i8,
+    series: Vec<SpreadPoint>,
+    signals: Vec<PairSignal>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: PairsConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid pairs config: {}", e))?;
+    let p = &config.params;
+    let (a, b, timestamps) = if !config.candles_a.is_empty() || !config.candles_b.is_empty() {
+        align(&config.candles_a, &config.candles_b)?
+    } else {
+        if config.prices_a.len() != config.prices_b.len() {
+            return Err("prices_a and prices_b must be the same length".to_string());
+        }
+        (config.prices_a.clone(), config.prices_b.clone(), config.timestamps.clone())
+    };
+    let n = a.len();
+    if n < 30 {
+        return Err("Need at least 30 paired observations".to_string());
+    }
+    let (la, lb) = transform(&a, &b, p.use_log)?;
+
+    let (beta, alpha) = ols_regression(&lb, &la);
+    let spread: Vec<f64> = la.iter().zip(&lb).map(|(x, y)| x - alpha - beta * y).collect();
+    let lags = config.adf_lags.unwrap_or_else(|| (12.0 * (n as f64 / 100.0).powf(0.25)).floor() as usize).min(n / 4);
+    let statistic = adf_statistic(&spread, lags).ok_or("ADF regression is singular (constant spread?)")?;
+    let cv = mackinnon_critical(2, n);
+    let threshold = if config.significance <= 0.01 { cv.one } else if config.significance <= 0.05 { cv.five } else { cv.ten };
+
+    let (mean, sd) = mean_std(&spread);
+    let zscores = zscore_series(&spread, p.lookback);
+    let ts_at = |i: usize| timestamps.get(i).cloned();
+    let mut position = 0i8;
+    let mut series = Vec::with_capacity(n);
+    let mut signals = Vec::new();
+    for (i, (&s, &z)) in spread.iter().zip(&zscores).enumerate() {
+        if let Some(z) = z {
+            if let Some((next, action)) = step(position, z, p) {
+                position = next;
+                signals.push(PairSignal { index: i, timestamp: ts_at(i), action, zscore: round4(z) });
+            }
+        }
+        series.push(SpreadPoint { index: i, timestamp: ts_at(i), spread: round4(s), zscore: z.map(round4), position });
+    }
+
+    let result = PairsResult {
+        observations: n,
+        hedge_ratio: round4(beta),
+        intercept: round4(alpha),
+        correlation: round4(crate::utils::pearson_correlation(&la, &lb)),
+        adf: AdfResult { statistic: round4(statistic), lags, critical_values: cv, cointegrated: statistic < threshold },
+        half_life: half_life(&spread).map(round2),
+        spread_mean: round4(mean),
+        spread_std: round4(sd),
+        current_zscore: zscores.last().copied().flatten().map(round4),
+        current_position: position,
+        series,
+        signals,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Next position and the signal name when the z-score crosses a band.
+fn step(position: i8, z: f64, p: &PairParams) -> Option<(i8, &'static str)> {
+    let stopped = p.stop_z > 0.0 && z.abs() >= p.stop_z;
+    match position {
+        0 if !stopped && z >= p.entry_z => Some((-1, "enter_short_spread")),
+        0 if !stopped && z <= -p.entry_z => Some((1, "enter_long_spread")),
+        0 => None,
+        _ if stopped => Some((0, "stop")),
+        1 if z >= -p.exit_z => Some((0, "exit_long_spread")),
+        -1 if z <= p.exit_z => Some((0, "exit_short_spread")),
+        _ => None,
+    }
+}
+
+fn transform(a: &[f64], b: &[f64], use_log: bool) -> Result<(Vec<f64>, Vec<f64>), String> {
+    if a.iter().chain(b).any(|v| !(v.is_finite() && *v > 0.0)) {
+        return Err("prices must be positive".to_string());
+    }
+    let f = |v: &f64| if use_log { v.ln() } else { *v };
+    Ok((a.iter().map(f).collect(), b.iter().map(f).collect()))
+}
+
+/// Closes of leg A, leg B and their shared timestamps.
+type Aligned = (Vec<f64>, Vec<f64>, Vec<String>);
+
+/// Closes of two candle series joined on timestamp (by position when undated).
+fn align(ca: &[Candle], cb: &[Candle]) -> Result<Aligned, String> {
+    if ca.iter().chain(cb).any(|c| c.timestamp.is_empty()) {
+        if ca.len() != cb.len() {
+            return Err("undated candle series must be the same length".to_string());
+        }
+        return Ok((ca.iter().map(|c| c.close).collect(), cb.iter().map(|c| c.close).collect(), Vec::new()));
+    }
+    let by_ts: HashMap<&str, f64> = cb.iter().map(|c| (c.timestamp.as_str(), c.close)).collect();
+    let (mut a, mut b, mut ts) = (Vec::new(), Vec::new(), Vec::new());
+    for c in ca {
+        if let Some(&close) = by_ts.get(c.timestamp.as_str()) {
+            a.push(c.close);
+            b.push(close);
+            ts.push(c.timestamp.clone());
+        }
+    }
+    Ok((a, b, ts))
+}
+
+fn mean_std(xs: &[f64]) -> (f64, f64) {
+    let n = xs.len() as f64;
+    let mean = xs.iter().sum::<f64>() / n;
+    let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+    (mean, var.sqrt())
+}
+
+/// Rolling z-score over `lookback` bars (full sample when 0); None in warm-up.
+fn zscore_series(spread: &[f64], lookback: usize) -> Vec<Option<f64>> {
+    let full = (lookback == 0).then(|| mean_std(spread));
+    (0..spread.len()).map(|i| {
+        let (m, s) = match full {
+            Some(ms) => ms,
+            None if i + 1 >= lookback.max(2) => mean_std(&spread[i + 1 - lookback.max(2)..=i]),
+            None => return None,
+        };
+        (s > 0.0).then(|| (spread[i] - m) / s)
+    }).collect()
+}
+
+/// Bars for the spread to halve its deviation, from an AR(1) fit of Δs on s.
+fn half_life(spread: &[f64]) -> Option<f64> {
+    let lag = &spread[..spread.len() - 1];
+    let dy: Vec<f64> = spread.windows(2).map(|w| w[1] - w[0]).collect();
+    let (slope, _) = ols_regression(lag, &dy);
+    (slope < 0.0).then(|| -std::f64::consts::LN_2 / slope)
+}
+
+/// ADF t-statistic on γ in Δy_t = c + γ·y_{t-1} + Σ φ_i·Δy_{t-i}.
+pub(crate) fn adf_statistic(y: &[f64], lags: usize) -> Option<f64> {
+    let dy: Vec<f64> = y.windows(2).map(|w| w[1] - w[0]).collect();
+    let rows: Vec<Vec<f64>> = (lags..dy.len()).map(|t| {
+        let mut row = vec![1.0, y[t]];
+        row.extend((1..=lags).map(|i| dy[t - i]));
+        row
+    }).collect();
+    let target: Vec<f64> = (lags..dy.len()).map(|t| dy[t]).collect();
+    let k = lags + 2;
+    if rows.len() <= k + 1 {
+        return None;
+    }
+    let (coef, xtx_inv) = least_squares(&rows, &target)?;
+    let sse: f64 = rows.iter().zip(&target)
+        .map(|(r, t)| (t - r.iter().zip(&coef).map(|(x, c)| x * c).sum::<f64>()).powi(2))
+        .sum();
+    let sigma2 = sse / (rows.len() - k) as f64;
+    let se = (sigma2 * xtx_inv[1][1]).sqrt();
+    (se > 0.0).then(|| coef[1] / se)
+}
+
+/// OLS via the normal equations; returns coefficients and (X'X)⁻¹.
+fn least_squares(x: &[Vec<f64>], y: &[f64]) -> Option<(Vec<f64>, Vec<Vec<f64>>)> {
+    let k = x[0].len();
+    // Augmented [X'X | I] reduced by Gauss-Jordan with partial pivoting.
+    let mut m = vec![vec![0.0; 2 * k]; k];
+    for (i, row) in m.iter_mut().enumerate() {
+        for j in 0..k {
+            row[j] = x.iter().map(|r| r[i] * r[j]).sum();
+        }
+        row[k + i] = 1.0;
+    }
+    for col in 0..k {
+        let pivot = (col..k).max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))?;
+        if m[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(col, pivot);
+        let d = m[col][col];
+        m[col].iter_mut().for_each(|v| *v /= d);
+        for r in 0..k {
+            if r != col {
+                let f = m[r][col];
+                let src = m[col].clone();
+                m[r].iter_mut().zip(&src).for_each(|(v, s)| *v -= f * s);
+            }
+        }
+    }
+    let inv: Vec<Vec<f64>> = m.iter().map(|row| row[k..].to_vec()).collect();
+    let xty: Vec<f64> = (0..k).map(|j| x.iter().zip(y).map(|(r, t)| r[j] * t).sum()).collect();
+    let coef = inv.iter().map(|row| row.iter().zip(&xty).map(|(a, b)| a * b).sum()).collect();
+    Some((coef, inv))
+}
+
+/// MacKinnon (2010) finite-sample critical values for the (Engle-Granger)
+/// ADF test with a constant; `n_vars` = 1 for a plain unit-root test.
+pub(crate) fn mackinnon_critical(n_vars: usize, nobs: usize) -> CriticalValues {
+    let table: [[f64; 3]; 3] = match n_vars {
+        1 => [[-3.43035, -6.5393, -16.786], [-2.86154, -2.8903, -4.234], [-2.56677, -1.5384, -2.809]],
+        2 => [[-3.89644, -10.9519, -22.527], [-3.33613, -6.1101, -6.823], [-3.04445, -4.2412, -2.720]],
+        _ => [[-4.29374, -14.4354, -33.195], [-3.74066, -8.5632, -10.852], [-3.45218, -6.2143, -3.718]],
+    };
+    let t = nobs.max(1) as f64;
+    let cv = |r: [f64; 3]| round4(r[0] + r[1] / t + r[2] / (t * t));
+    CriticalValues { one: cv(table[0]), five: cv(table[1]), ten: cv(table[2]) }
+}
+
+#[derive(Deserialize)]
+struct PairsBacktestConfig {
+    #[serde(default)]
+    symbol: String,
+    #[serde(default)]
+    symbol_b: String,
+    initial_capital: f64,
+    candles: Vec<Candle>,
+    candles_b: Vec<Candle>,
+    #[serde(default)]
+    params: PairParams,
+    #[serde(default)]
+    transaction_costs: Option<CostConfig>,
+}
+
+#[derive(Serialize)]
+struct PairTrade {
+    entry_date: String,
+    exit_date: String,
+    direction: &'static str,
+    hedge_ratio: f64,
+    qty_a: f64,
+    qty_b: f64,
+    entry_z: f64,
+    exit_z: f64,
+    exit_reason: &'static str,
+    pnl: f64,
+}
+
+#[derive(Serialize)]
+struct PairsBacktestResult {
+    strategy: String,
+    symbol: String,
+    symbol_b: String,
+    total_return_pct: f64,
+    max_drawdown: f64,
+    total_trades: usize,
+    win_rate: f64,
+    total_costs: f64,
+    equity_curve: Vec<EquityPoint>,
+    trade_log: Vec<PairTrade>,
+}
+
+struct Open {
+    dir: i8,
+    beta: f64,
+    qty_a: f64,
+    qty_b: f64,
+    entry: usize,
+    entry_z: f64,
+    entry_equity: f64,
+}
+
+/// `backtest` entry point for `strategy = "pairs"`.
+pub fn backtest(data: Value) -> Result<Value, String> {
+    let config: PairsBacktestConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid backtest config: {}", e))?;
+    let p = &config.params;
+    let (a, b, ts) = align(&config.candles, &config.candles_b)?;
+    let (la, lb) = transform(&a, &b, p.use_log)?;
+    let warmup = p.hedge_lookback.max(p.lookback).max(10);
+    if a.len() <= warmup {
+        return Err(format!("pairs backtest needs more than {} aligned bars", warmup));
+    }
+    let costs = build_costs(&config.transaction_costs);
+    let leg_cost = |qty: f64, px: f64, is_sell: bool| {
+        let value = qty.abs() * px;
+        if value > 0.0 { costs.total_cost(value, is_sell) + value * costs.slippage_bps / 10_000.0 } else { 0.0 }
+    };
+    let date = |i: usize| ts.get(i).cloned().unwrap_or_else(|| i.to_string());
+
+    let mut cash = config.initial_capital;
+    let mut open: Option<Open> = None;
+    let mut trades = Vec::new();
+    let mut equity_curve = Vec::with_capacity(a.len());
+    let (mut total_costs, mut peak, mut max_dd) = (0.0, config.initial_capital, 0.0_f64);
+
+    for i in 0..a.len() {
+        let held = open.as_ref().map(|o| (o.qty_a, o.qty_b)).unwrap_or((0.0, 0.0));
+        let equity = cash + held.0 * a[i] + held.1 * b[i];
+        if i >= warmup {
+            // Causal hedge ratio: the trade's own while open, trailing otherwise.
+            let beta = match &open {
+                Some(o) => o.beta,
+                None => ols_regression(&lb[i + 1 - p.hedge_lookback.max(2)..=i], &la[i + 1 - p.hedge_lookback.max(2)..=i]).0,
+            };
+            let lb_start = i + 1 - p.lookback.max(2);
+            let window: Vec<f64> = (lb_start..=i).map(|j| la[j] - beta * lb[j]).collect();
+            let (m, s) = mean_std(&window);
+            let z = if s > 0.0 { (window[window.len() - 1] - m) / s } else { 0.0 };
+            let is_last = i + 1 == a.len();
+            let position = open.as_ref().map(|o| o.dir).unwrap_or(0);
+            let action = if is_last && position != 0 { Some((0, "end")) } else { step(position, z, p) };
+            match (action, open.take()) {
+                (Some((0, reason)), Some(o)) => {
+                    let fee = leg_cost(o.qty_a, a[i], o.qty_a > 0.0) + leg_cost(o.qty_b, b[i], o.qty_b > 0.0);
+                    cash += o.qty_a * a[i] + o.qty_b * b[i] - fee;
+                    total_costs += fee;
+                    trades.push(PairTrade {
+                        entry_date: date(o.entry),
+                        exit_date: date(i),
+                        direction: if o.dir > 0 { "long_spread" } else { "short_spread" },
+                        hedge_ratio: round4(o.beta),
+                        qty_a: o.qty_a,
+                        qty_b: o.qty_b,
+                        entry_z: round4(o.entry_z),
+                        exit_z: round4(z),
+                        exit_reason: match reason { "stop" => "stop", "end" => "end_of_data", _ => "mean_reversion" },
+                        pnl: round2(cash - o.entry_equity),
+                    });
+                }
+                (Some((dir, _)), None) if dir != 0 && !is_last => {
+                    // Notional of B is β × notional of A (dollar terms for log prices).
+                    let hedge = if p.use_log { beta.abs() } else { beta.abs() * b[i] / a[i] };
+                    let gross = equity * p.capital_pct / 100.0;
+                    let qty_a = (gross / (a[i] * (1.0 + hedge))).floor() * dir as f64;
+                    let qty_b = -(qty_a * a[i] * hedge / b[i]).round() * beta.signum();
+                    if qty_a != 0.0 {
+                        let fee = leg_cost(qty_a, a[i], qty_a < 0.0) + leg_cost(qty_b, b[i], qty_b < 0.0);
+                        let entry_equity = cash;
+                        cash -= qty_a * a[i] + qty_b * b[i] + fee;
+                        total_costs += fee;
+                        open = Some(Open { dir, beta, qty_a, qty_b, entry: i, entry_z: z, entry_equity });
+                    }
+                }
+                (_, o) => open = o,
+            }
+        }
+        let held = open.as_ref().map(|o| (o.qty_a, o.qty_b)).unwrap_or((0.0, 0.0));
+        let nav = cash + held.0 * a[i] + held.1 * b[i];
+        peak = peak.max(nav);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - nav) / peak * 100.0);
+        }
+        equity_curve.push(EquityPoint { date: date(i), nav: round2(nav) });
+    }
+
+    let wins = trades.iter().filter(|t| t.pnl > 0.0).count();
+    let final_nav = equity_curve.last().map(|e| e.nav).unwrap_or(config.initial_capital);
+    let result = PairsBacktestResult {
+        strategy: "pairs".to_string(),
+        symbol: config.symbol,
+        symbol_b: config.symbol_b,
+        total_return_pct: round2((final_nav / config.initial_capital - 1.0) * 100.0),
+        max_drawdown: round2(max_dd),
+        total_trades: trades.len(),
+        win_rate: if trades.is_empty() { 0.0 } else { round2(wins as f64 / trades.len() as f64 * 100.0) },
+        total_costs: round2(total_costs),
+        equity_curve,
+        trade_log: trades,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::Xorshift64;
+
+    /// B is a random walk; A = 2·B plus a mean-reverting AR(1) deviation.
+    fn cointegrated(n: usize) -> (Vec<f64>, Vec<f64>) {
+        let mut rng = Xorshift64::new(7);
+        let (mut b, mut dev) = (100.0_f64, 0.0_f64);
+        let (mut pa, mut pb) = (Vec::new(), Vec::new());
+        for _ in 0..n {
+            b *= 1.0 + rng.next_normal(0.0, 0.01);
+            dev = 0.7 * dev + rng.next_normal(0.0, 0.01);
+            pb.push(b);
+            pa.push((2.0 * b.ln() + dev).exp() / 50.0);
+        }
+        (pa, pb)
+    }
+
+    #[test]
+    fn test_cointegrated_pair() {
+        let (a, b) = cointegrated(300);
+        let result = compute(json!({ "prices_a": a, "prices_b": b })).unwrap();
+        assert!((result["hedge_ratio"].as_f64().unwrap() - 2.0).abs() < 0.1);
+        assert_eq!(result["adf"]["cointegrated"], true);
+        assert!(result["half_life"].as_f64().unwrap() < 5.0);
+        assert!(result["series"][5]["zscore"].is_null());
+        assert!(!result["signals"].as_array().unwrap().is_empty());
+
+        // Two independent random walks are not cointegrated.
+        let mut rng = Xorshift64::new(99);
+        let mut p = 50.0_f64;
+        let other: Vec<f64> = (0..300).map(|_| { p *= 1.0 + rng.next_normal(0.0, 0.01); p }).collect();
+        let walk = compute(json!({ "prices_a": other, "prices_b": b })).unwrap();
+        assert_eq!(walk["adf"]["cointegrated"], false);
+        assert_eq!(walk["adf"]["critical_values"]["5%"], -3.3566);
+    }
+
+    #[test]
+    fn test_signal_state_machine() {
+        let p = PairParams::default();
+        assert_eq!(step(0, 2.1, &p), Some((-1, "enter_short_spread")));
+        assert_eq!(step(0, -4.5, &p), None);
+        assert_eq!(step(-1, 0.4, &p), Some((0, "exit_short_spread")));
+        assert_eq!(step(1, -1.0, &p), None);
+        assert_eq!(step(1, -4.0, &p), Some((0, "stop")));
+    }
+
+    #[test]
+    fn test_pairs_backtest() {
+        let (a, b) = cointegrated(400);
+        let candles = |p: &[f64]| p.iter().enumerate()
+            .map(|(i, c)| json!({ "timestamp": format!("2024-01-01T00:{:02}:{:02}", i / 60, i % 60), "high": c, "low": c, "close": c, "volume": 1e6 }))
+            .collect::<Vec<_>>();
+        let result = crate::backtest::run(json!({
+            "strategy": "pairs", "symbol": "A", "symbol_b": "B", "initial_capital": 1_000_000.0,
+            "candles": candles(&a), "candles_b": candles(&b),
+            "transaction_costs": { "commission": 0.0, "slippage_bps": 0.0, "stt_pct": 0.0 }
+        })).unwrap();
+        let trades = result["trade_log"].as_array().unwrap();
+        assert!(trades.len() >= 3);
+        assert!(result["total_return_pct"].as_f64().unwrap() > 0.0);
+        assert_eq!(result["equity_curve"].as_array().unwrap().len(), 400);
+        // Legs are opposite: long A pairs with short B for a positive hedge ratio.
+        assert!(trades[0]["qty_a"].as_f64().unwrap() * trades[0]["qty_b"].as_f64().unwrap() < 0.0);
+    }
+}
@@ -0,0 +1,627 @@
+//! Paper order simulation.
+//!
+//! Market, limit, SL (stop-limit) and SL-M (stop-market) orders rest in a
+//! book until `process` feeds them candles (`symbol` + `candles`) or
+//! `ticks` (`{symbol, price, qty, timestamp}`). Per bar: market orders fill
+//! at the open; limits fill when the bar trades through them, at the open
+//! if it gapped past; stops trigger on the high (buy) or low (sell) and
+//! then fill at the worse of open and trigger (SL-M) or work as a limit
+//! (SL). Fills are capped at `max_participation` of bar volume, leaving the
+//! rest open as a partial fill; IOC remainders are cancelled and DAY orders
+//! lapse on the first bar of a later session. Bars stamped before an
+//! order's `created_at` are skipped for it. Every fill is booked into
+//! the `portfolio` ledger (`portfolio_state_file`, else in memory), which
+//! also backs the margin check at placement. With an `instruments`
+//! registry, quantities must be whole lots, limit and trigger prices must
+//! sit on the tick grid, fills snap to it, and margin, fees and P&L use the
+//! contract multiplier.
+
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::instruments::{Instrument, Instruments};
+use crate::portfolio::{with_ledger, FillInput, Ledger};
+use crate::utils::{parse_timestamp, round2, round4, Candle};
+
+pub static ORDER_BOOK: once_cell::sync::Lazy<Mutex<OrderBook>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(OrderBook::default()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    Market,
+    Limit,
+    #[serde(alias = "SL", alias = "stop_limit")]
+    Sl,
+    #[serde(alias = "SL-M", alias = "sl-m", alias = "stop_market", alias = "stop")]
+    SlM,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Open,
+    /// Stop hit; an SL order is now working as a limit.
+    Triggered,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Validity {
+    #[default]
+    Day,
+    Ioc,
+    Gtc,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperOrder {
+    pub id: String,
+    pub symbol: String,
+    pub side: String,
+    pub order_type: OrderType,
+    pub qty: f64,
+    pub limit_price: Option<f64>,
+    pub trigger_price: Option<f64>,
+    pub validity: Validity,
+    pub status: OrderStatus,
+    pub filled_qty: f64,
+    pub avg_fill_price: f64,
+    pub created_at: String,
+    #[serde(default)]
+    pub triggered: bool,
+    #[serde(default)]
+    pub reject_reason: Option<String>,
+    /// Strategy label; carried onto the ledger fills.
+    #[serde(default, alias = "strategy_tag")]
+    pub tag: Option<String>,
+}
+
+impl PaperOrder {
+    fn is_buy(&self) -> bool {
+        self.side == "buy"
+    }
+
+    fn remaining(&self) -> f64 {
+        self.qty - self.filled_qty
+    }
+
+    fn is_active(&self) -> bool {
+        matches!(self.status, OrderStatus::Open | OrderStatus::Triggered | OrderStatus::PartiallyFilled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct OrderBook {
+    pub orders: Vec<PaperOrder>,
+    pub next_id: u64,
+}
+
+#[derive(Deserialize)]
+struct PlaceInput {
+    symbol: String,
+    side: String,
+    qty: f64,
+    #[serde(default = "default_type")]
+    order_type: OrderType,
+    #[serde(default, alias = "price")]
+    limit_price: Option<f64>,
+    #[serde(default)]
+    trigger_price: Option<f64>,
+    #[serde(default)]
+    validity: Validity,
+    /// Reference price for the margin check when the order has none.
+    #[serde(default)]
+    ltp: Option<f64>,
+    #[serde(default)]
+    timestamp: Option<String>,
+    #[serde(default, alias = "strategy_tag")]
+    tag: Option<String>,
+}
+
+fn default_type() -> OrderType { OrderType::Market }
+
+#[derive(Deserialize)]
+struct Tick {
+    symbol: String,
+    price: f64,
+    #[serde(default, alias = "volume")]
+    qty: f64,
+    timestamp: String,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct SimParams {
+    slippage_bps: f64,
+    /// Share of bar volume one order may take; bars without volume are uncapped.
+    max_participation: f64,
+    commission: f64,
+    fee_bps: f64,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self { slippage_bps: 0.0, max_participation: 0.1, commission: 0.0, fee_bps: 0.0 }
+    }
+}
+
+#[derive(Serialize)]
+struct SimFill {
+    order_id: String,
+    symbol: String,
+    side: String,
+    qty: f64,
+    price: f64,
+    fees: f64,
+    timestamp: String,
+    partial: bool,
+}
+
+/// JSON API entry point. Expects `{ "command": "...", ... }`.
+pub fn compute(data: Value) -> Result<Value, String> {
+    let mut book = ORDER_BOOK.lock().map_err(|e| format!("Lock error: {}", e))?;
+    match data.get("state_file").and_then(|v| v.as_str()) {
+        Some(path) => {
+            let mut loaded: OrderBook = match std::fs::read_to_string(path) {
+                Ok(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid order state file '{}': {}", path, e))?,
+                Err(_) => OrderBook::default(),
+            };
+            let (out, changed) = execute(&mut loaded, &data)?;
+            if changed {
+                let json = serde_json::to_string_pretty(&loaded).map_err(|e| format!("Serialization error: {}", e))?;
+                let tmp = format!("{}.tmp", path);
+                std::fs::write(&tmp, json).map_err(|e| format!("Write error: {}", e))?;
+                std::fs::rename(&tmp, path).map_err(|e| format!("Write error: {}", e))?;
+            }
+            Ok(out)
+        }
+        None => execute(&mut book, &data).map(|(out, _)| out),
+    }
+}
+
+fn execute(book: &mut OrderBook, data: &Value) -> Result<(Value, bool), String> {
+    let command = data.get("command").and_then(|v| v.as_str()).unwrap_or("list");
+    let portfolio_file = data.get("portfolio_state_file").and_then(|v| v.as_str());
+    let order_id = || data.get("order_id").and_then(|v| v.as_str()).ok_or("order_id field required");
+    let to_value = |v: &PaperOrder| serde_json::to_value(v).map_err(|e| format!("Serialization error: {}", e));
+    let instruments = Instruments::from_request(data)?;
+    match command {
+        "list" | "status" => {
+            let all = data.get("all").and_then(|v| v.as_bool()).unwrap_or(false);
+            let orders: Vec<&PaperOrder> = book.orders.iter().filter(|o| all || o.is_active()).collect();
+            Ok((serde_json::json!({ "orders": orders }), false))
+        }
+        "reset" => {
+            *book = OrderBook::default();
+            Ok((serde_json::json!({ "orders": [] }), true))
+        }
+        "place" => {
+            let input: PlaceInput = serde_json::from_value(data.clone())
+                .map_err(|e| format!("Invalid order: {}", e))?;
+            book.next_id += 1;
+            let mut order = PaperOrder {
+                id: format!("PO-{}", book.next_id),
+                symbol: input.symbol,
+                side: input.side.to_lowercase(),
+                order_type: input.order_type,
+                qty: input.qty,
+                limit_price: input.limit_price,
+                trigger_price: input.trigger_price,
+                validity: input.validity,
+                status: OrderStatus::Open,
+                filled_qty: 0.0,
+                avg_fill_price: 0.0,
+                created_at: input.timestamp.unwrap_or_else(|| chrono::Utc::now().to_rfc3339()),
+                triggered: false,
+                reject_reason: None,
+                tag: input.tag,
+            };
+            let reference = input.ltp.or(order.limit_price).or(order.trigger_price);
+            let instrument = instruments.get(&order.symbol);
+            let rejection = match validate(&order).and_then(|_| check_instrument(&order, &instrument)) {
+                Err(e) => Some(e),
+                Ok(()) => with_ledger(portfolio_file, |ledger| Ok((margin_check(ledger, &order, reference, &instrument), false)))?,
+            };
+            if let Some(reason) = rejection {
+                order.status = OrderStatus::Rejected;
+                order.reject_reason = Some(reason);
+            }
+            let out = to_value(&order)?;
+            book.orders.push(order);
+            Ok((out, true))
+        }
+        "cancel" => {
+            let id = order_id()?;
+            let order = book.orders.iter_mut().find(|o| o.id == id).ok_or_else(|| format!("Unknown order {}", id))?;
+            if !order.is_active() {
+                return Err(format!("Order {} is {:?} and cannot be cancelled", id, order.status));
+            }
+            order.status = OrderStatus::Cancelled;
+            Ok((to_value(order)?, true))
+        }
+        "modify" => {
+            let id = order_id()?;
+            let order = book.orders.iter_mut().find(|o| o.id == id).ok_or_else(|| format!("Unknown order {}", id))?;
+            if !order.is_active() {
+                return Err(format!("Order {} is {:?} and cannot be modified", id, order.status));
+            }
+            let mut updated = order.clone();
+            if let Some(q) = data.get("qty").and_then(|v| v.as_f64()) {
+                if q < updated.filled_qty {
+                    return Err(format!("qty {} is below the {} already filled", q, updated.filled_qty));
+                }
+                updated.qty = q;
+            }
+            if let Some(p) = data.get("limit_price").and_then(|v| v.as_f64()) {
+                updated.limit_price = Some(p);
+            }
+            if let Some(p) = data.get("trigger_price").and_then(|v| v.as_f64()) {
+                updated.trigger_price = Some(p);
+            }
+            validate(&updated)?;
+            check_instrument(&updated, &instruments.get(&updated.symbol))?;
+            if updated.remaining() <= 0.0 {
+                updated.status = OrderStatus::Filled;
+            }
+            *order = updated;
+            Ok((to_value(order)?, true))
+        }
+        "process" => {
+            let params: SimParams = serde_json::from_value(data.get("params").cloned().unwrap_or(Value::Null))
+                .unwrap_or_default();
+            let bars = bars_from(data)?;
+            let (fills, portfolio) = with_ledger(portfolio_file, |ledger| {
+                let mut fills = Vec::new();
+                for (symbol, bar) in &bars {
+                    let instrument = instruments.get(symbol);
+                    for order in book.orders.iter_mut().filter(|o| o.is_active() && &o.symbol == symbol) {
+                        if let Some(fill) = match_order(order, bar, &params, &instrument) {
+                            ledger.apply_fill(FillInput {
+                                symbol: fill.symbol.clone(),
+                                side: fill.side.clone(),
+                                qty: fill.qty,
+                                price: fill.price,
+                                fees: fill.fees,
+                                timestamp: Some(fill.timestamp.clone()),
+                                order_id: Some(fill.order_id.clone()),
+                                multiplier: Some(instrument.multiplier),
+                                strategy_tag: order.tag.clone(),
+                                option: None,
+                            })?;
+                            if let Some(pos) = ledger.positions.get_mut(symbol) {
+                                pos.margin_pct = pos.margin_pct.or(instrument.margin_pct);
+                            }
+                            fills.push(fill);
+                        }
+                    }
+                    ledger.mark(symbol, bar.close);
+                }
+                let changed = !bars.is_empty();
+                Ok(((fills, ledger.summary_value()?), changed))
+            })?;
+            let open: Vec<&PaperOrder> = book.orders.iter().filter(|o| o.is_active()).collect();
+            Ok((serde_json::json!({ "fills": fills, "open_orders": open, "portfolio": portfolio }), true))
+        }
+        other => Err(format!("Unknown orders command '{}'", other)),
+    }
+}
+
+fn validate(o: &PaperOrder) -> Result<(), String> {
+    if o.side != "buy" && o.side != "sell" {
+        return Err(format!("Unknown side '{}' (buy, sell)", o.side));
+    }
+    if !(o.qty.is_finite() && o.qty > 0.0) {
+        return Err("qty must be positive".to_string());
+    }
+    let positive = |p: Option<f64>| p.is_some_and(|v| v.is_finite() && v > 0.0);
+    match o.order_type {
+        OrderType::Limit if !positive(o.limit_price) => Err("limit orders need limit_price".to_string()),
+        OrderType::Sl if !(positive(o.limit_price) && positive(o.trigger_price)) => {
+            Err("SL orders need trigger_price and limit_price".to_string())
+        }
+        OrderType::Sl => {
+            let (limit, trigger) = (o.limit_price.unwrap_or(0.0), o.trigger_price.unwrap_or(0.0));
+            if (o.is_buy() && limit < trigger) || (!o.is_buy() && limit > trigger) {
+                Err("SL limit_price must be at or beyond trigger_price in the order's direction".to_string())
+            } else {
+                Ok(())
+            }
+        }
+        OrderType::SlM if !positive(o.trigger_price) => Err("SL-M orders need trigger_price".to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Lot and tick rules from the instrument registry.
+fn check_instrument(o: &PaperOrder, instrument: &Instrument) -> Result<(), String> {
+    if !instrument.is_lot_multiple(o.qty) {
+        return Err(format!("qty {} is not a multiple of lot size {}", o.qty, instrument.lot_size));
+    }
+    for (name, price) in [("limit_price", o.limit_price), ("trigger_price", o.trigger_price)] {
+        if let Some(p) = price.filter(|p| !instrument.on_tick(*p)) {
+            return Err(format!("{} {} is not a multiple of tick size {}", name, p, instrument.tick_size.unwrap_or(0.0)));
+        }
+    }
+    Ok(())
+}
+
+/// Rejection reason when the exposure the order adds needs more margin than is free.
+fn margin_check(ledger: &Ledger, o: &PaperOrder, price: Option<f64>, instrument: &Instrument) -> Option<String> {
+    let price = price.or_else(|| ledger.positions.get(&o.symbol).map(|p| p.last_price))?;
+    let held = ledger.position_qty(&o.symbol);
+    let signed = if o.is_buy() { o.qty } else { -o.qty };
+    // Only the part that grows |position| needs new margin.
+    let added = ((held + signed).abs() - held.abs()).max(0.0);
+    let margin_pct = ledger.positions.get(&o.symbol)
+        .and_then(|p| p.margin_pct)
+        .or(instrument.margin_pct)
+        .unwrap_or(ledger.default_margin_pct);
+    let required = instrument.notional(price, added) * margin_pct;
+    let available = ledger.equity() - ledger.margin_used();
+    (required > available + 1e-9)
+        .then(|| format!("Insufficient margin: requires {:.2}, available {:.2}", required, available))
+}
+
+fn bars_from(data: &Value) -> Result<Vec<(String, Candle)>, String> {
+    let mut bars = Vec::new();
+    if let Some(candles) = data.get("candles") {
+        let symbol = data.get("symbol").and_then(|v| v.as_str()).ok_or("symbol field required with candles")?;
+        let candles: Vec<Candle> = serde_json::from_value(candles.clone()).map_err(|e| format!("Invalid candles: {}", e))?;
+        bars.extend(candles.into_iter().map(|c| (symbol.to_string(), c)));
+    }
+    if let Some(ticks) = data.get("ticks") {
+        let ticks: Vec<Tick> = serde_json::from_value(ticks.clone()).map_err(|e| format!("Invalid ticks: {}", e))?;
+        bars.extend(ticks.into_iter().map(|t| (t.symbol, Candle {
+            timestamp: t.timestamp,
+            open: t.price, high: t.price, low: t.price, close: t.price,
+            volume: t.qty,
+        })));
+    }
+    if bars.is_empty() {
+        return Err("process requires candles or ticks".to_string());
+    }
+    Ok(bars)
+}
+
+/// Advance one order through one bar, returning its fill if any.
+fn match_order(o: &mut PaperOrder, bar: &Candle, p: &SimParams, instrument: &Instrument) -> Option<SimFill> {
+    // Bars from before the order was placed can neither fill nor expire it.
+    if let (Some(bar_ts), Some(created)) = (parse_timestamp(&bar.timestamp), parse_timestamp(&o.created_at)) {
+        if bar_ts < created {
+            return None;
+        }
+    }
+    let session = |ts: &str| parse_timestamp(ts).map(|t| t.date());
+    if o.validity == Validity::Day && session(&bar.timestamp) > session(&o.created_at) && session(&o.created_at).is_some() {
+        o.status = OrderStatus::Cancelled;
+        o.reject_reason = Some("DAY order expired".to_string());
+        return None;
+    }
+    let buy = o.is_buy();
+    if matches!(o.order_type, OrderType::Sl | OrderType::SlM) && !o.triggered {
+        let trigger = o.trigger_price.unwrap_or(0.0);
+        let hit = if buy { bar.high >= trigger } else { bar.low <= trigger };
+        if !hit {
+            return expire_ioc(o);
+        }
+        o.triggered = true;
+        if o.status == OrderStatus::Open {
+            o.status = OrderStatus::Triggered;
+        }
+    }
+    let slip = |px: f64| if buy { px * (1.0 + p.slippage_bps / 10_000.0) } else { px * (1.0 - p.slippage_bps / 10_000.0) };
+    let price = match o.order_type {
+        OrderType::Market => Some(slip(bar.open)),
+        OrderType::SlM => {
+            let trigger = o.trigger_price.unwrap_or(bar.open);
+            Some(slip(if buy { bar.open.max(trigger) } else { bar.open.min(trigger) }))
+        }
+        OrderType::Limit | OrderType::Sl => {
+            let limit = o.limit_price.unwrap_or(0.0);
+            // A stop-limit can only trade from the trigger onward.
+            let start = match (o.order_type, o.trigger_price) {
+                (OrderType::Sl, Some(t)) => if buy { bar.open.max(t) } else { bar.open.min(t) },
+                _ => bar.open,
+            };
+            if buy && bar.low <= limit {
+                Some(start.min(limit))
+            } else if !buy && bar.high >= limit {
+                Some(start.max(limit))
+            } else {
+                None
+            }
+        }
+    };
+    let Some(price) = price.map(|px| instrument.snap(px)) else { return expire_ioc(o) };
+
+    let cap = if bar.volume > 0.0 && p.max_participation > 0.0 {
+        instrument.round_lots((bar.volume * p.max_participation).floor())
+    } else {
+        f64::INFINITY
+    };
+    let qty = o.remaining().min(cap);
+    if qty <= 0.0 {
+        return expire_ioc(o);
+    }
+    o.avg_fill_price = (o.avg_fill_price * o.filled_qty + price * qty) / (o.filled_qty + qty);
+    o.filled_qty += qty;
+    let partial = o.remaining() > 1e-9;
+    o.status = if partial { OrderStatus::PartiallyFilled } else { OrderStatus::Filled };
+    if partial && o.validity == Validity::Ioc {
+        o.status = OrderStatus::Cancelled;
+    }
+    o.avg_fill_price = round4(o.avg_fill_price);
+    Some(SimFill {
+        order_id: o.id.clone(),
+        symbol: o.symbol.clone(),
+        side: o.side.clone(),
+        qty,
+        price: round4(price),
+        fees: round2(p.commission + instrument.notional(price, qty) * p.fee_bps / 10_000.0),
+        timestamp: bar.timestamp.clone(),
+        partial,
+    })
+}
+
+/// IOC orders that could not trade on their first bar are cancelled.
+fn expire_ioc(o: &mut PaperOrder) -> Option<SimFill> {
+    if o.validity == Validity::Ioc {
+        o.status = OrderStatus::Cancelled;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bar(ts: &str, o: f64, h: f64, l: f64, c: f64, v: f64) -> Candle {
+        Candle { timestamp: ts.into(), open: o, high: h, low: l, close: c, volume: v }
+    }
+
+    fn order(order_type: OrderType, side: &str, qty: f64, limit: Option<f64>, trigger: Option<f64>) -> PaperOrder {
+        PaperOrder {
+            id: "PO-1".into(), symbol: "X".into(), side: side.into(), order_type, qty,
+            limit_price: limit, trigger_price: trigger, validity: Validity::Gtc,
+            status: OrderStatus::Open, filled_qty: 0.0, avg_fill_price: 0.0,
+            created_at: "2024-01-01T09:15:00".into(), triggered: false, reject_reason: None, tag: None,
+        }
+    }
+
+    #[test]
+    fn test_limit_stop_and_partial_rules() {
+        let p = SimParams { max_participation: 0.0, ..Default::default() };
+        // Buy limit 99: gaps down to open 98, fills at the better open.
+        let mut o = order(OrderType::Limit, "buy", 10.0, Some(99.0), None);
+        assert_eq!(match_order(&mut o, &bar("2024-01-01T09:20:00", 98.0, 100.0, 97.0, 99.5, 0.0), &p, &Instrument::default()).unwrap().price, 98.0);
+
+        // Sell SL-M at 95: untouched, then hit intrabar at the trigger.
+        let mut o = order(OrderType::SlM, "sell", 10.0, None, Some(95.0));
+        assert!(match_order(&mut o, &bar("2024-01-01T09:20:00", 100.0, 101.0, 96.0, 97.0, 0.0), &p, &Instrument::default()).is_none());
+        assert_eq!(match_order(&mut o, &bar("2024-01-01T09:25:00", 97.0, 97.0, 94.0, 94.5, 0.0), &p, &Instrument::default()).unwrap().price, 95.0);
+
+        // Buy SL trigger 105 limit 106: triggered by a bar that opens at 108, which is above the limit.
+        let mut o = order(OrderType::Sl, "buy", 10.0, Some(106.0), Some(105.0));
+        assert!(match_order(&mut o, &bar("2024-01-01T09:20:00", 108.0, 110.0, 107.0, 109.0, 0.0), &p, &Instrument::default()).is_none());
+        assert_eq!(o.status, OrderStatus::Triggered);
+
+        // 10% of 50 volume per bar: 5 now, 5 next bar.
+        let p = SimParams::default();
+        let mut o = order(OrderType::Market, "buy", 10.0, None, None);
+        let f = match_order(&mut o, &bar("2024-01-01T09:20:00", 100.0, 100.0, 100.0, 100.0, 50.0), &p, &Instrument::default()).unwrap();
+        assert!(f.partial && f.qty == 5.0);
+        assert_eq!(o.status, OrderStatus::PartiallyFilled);
+        match_order(&mut o, &bar("2024-01-01T09:25:00", 101.0, 101.0, 101.0, 101.0, 50.0), &p, &Instrument::default()).unwrap();
+        assert_eq!((o.status, o.avg_fill_price), (OrderStatus::Filled, 100.5));
+    }
+
+    #[test]
+    fn test_bars_before_the_order_are_ignored() {
+        let p = SimParams { max_participation: 0.0, ..Default::default() };
+        let mut o = order(OrderType::Market, "buy", 10.0, None, None);
+        o.validity = Validity::Ioc;
+        // An earlier bar, even from a previous session, neither fills nor cancels it.
+        assert!(match_order(&mut o, &bar("2023-12-29T15:25:00", 90.0, 90.0, 90.0, 90.0, 0.0), &p, &Instrument::default()).is_none());
+        assert!(match_order(&mut o, &bar("2024-01-01T09:10:00", 95.0, 95.0, 95.0, 95.0, 0.0), &p, &Instrument::default()).is_none());
+        assert_eq!(o.status, OrderStatus::Open);
+        let f = match_order(&mut o, &bar("2024-01-01T09:15:00", 100.0, 100.0, 100.0, 100.0, 0.0), &p, &Instrument::default()).unwrap();
+        assert_eq!((f.price, o.status), (100.0, OrderStatus::Filled));
+
+        // A DAY order placed now gets nothing from an old candle fed to `process`.
+        let dir = std::env::temp_dir();
+        let orders_file = dir.join(format!("orders_stale_test_{}.json", std::process::id()));
+        let ledger_file = dir.join(format!("orders_stale_ledger_{}.json", std::process::id()));
+        let (orders_file, ledger_file) = (orders_file.to_str().unwrap(), ledger_file.to_str().unwrap());
+        let _ = std::fs::remove_file(orders_file);
+        let _ = std::fs::remove_file(ledger_file);
+        let base = json!({ "state_file": orders_file, "portfolio_state_file": ledger_file, "symbol": "X" });
+        let with = |extra: Value| {
+            let mut v = base.clone();
+            v.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            compute(v).unwrap()
+        };
+        with(json!({ "command": "place", "side": "buy", "qty": 1, "order_type": "market", "validity": "day" }));
+        let out = with(json!({ "command": "process", "candles": [
+            { "timestamp": "2024-01-05", "open": 90.0, "high": 91.0, "low": 89.0, "close": 90.5, "volume": 0.0 }
+        ]}));
+        assert_eq!(out["fills"].as_array().unwrap().len(), 0);
+        assert_eq!(out["open_orders"][0]["status"], "open");
+        std::fs::remove_file(orders_file).unwrap();
+        let _ = std::fs::remove_file(ledger_file);
+    }
+
+    #[test]
+    fn test_process_feeds_portfolio_and_rejects() {
+        let dir = std::env::temp_dir();
+        let orders_file = dir.join(format!("orders_test_{}.json", std::process::id()));
+        let ledger_file = dir.join(format!("orders_ledger_test_{}.json", std::process::id()));
+        let (orders_file, ledger_file) = (orders_file.to_str().unwrap(), ledger_file.to_str().unwrap());
+        let _ = std::fs::remove_file(orders_file);
+        let _ = std::fs::remove_file(ledger_file);
+        crate::portfolio::compute(json!({ "command": "init", "state_file": ledger_file, "initial_capital": 10_000.0 })).unwrap();
+
+        let base = json!({ "state_file": orders_file, "portfolio_state_file": ledger_file });
+        let with = |extra: Value| {
+            let mut v = base.clone();
+            v.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            compute(v).unwrap()
+        };
+        let rejected = with(json!({ "command": "place", "symbol": "X", "side": "buy", "qty": 200, "order_type": "limit", "limit_price": 100.0 }));
+        assert_eq!(rejected["status"], "rejected");
+        let placed = with(json!({ "command": "place", "symbol": "X", "side": "buy", "qty": 50, "order_type": "limit", "limit_price": 100.0, "validity": "gtc", "strategy_tag": "swing", "timestamp": "2024-01-01T09:15:00" }));
+        assert_eq!(placed["status"], "open");
+
+        let out = with(json!({ "command": "process", "symbol": "X", "candles": [
+            { "timestamp": "2024-01-01T09:15:00", "open": 101.0, "high": 102.0, "low": 99.5, "close": 101.5, "volume": 0.0 }
+        ]}));
+        assert_eq!(out["fills"][0]["price"], 100.0);
+        assert_eq!(out["portfolio"]["positions"][0]["qty"], 50.0);
+        assert_eq!(out["portfolio"]["unrealized_pnl"], 75.0);
+        assert_eq!(out["portfolio"]["positions"][0]["strategy_tag"], "swing");
+        assert_eq!(out["portfolio"]["by_strategy_tag"]["swing"]["open_positions"], 1);
+        assert_eq!(out["open_orders"].as_array().unwrap().len(), 0);
+        let listed = with(json!({ "command": "list", "all": true }));
+        assert_eq!(listed["orders"].as_array().unwrap().len(), 2);
+        std::fs::remove_file(orders_file).unwrap();
+        std::fs::remove_file(ledger_file).unwrap();
+    }
+
+    #[test]
+    fn test_instrument_lots_ticks_and_multiplier() {
+        let ledger_file = std::env::temp_dir().join(format!("orders_instrument_ledger_{}.json", std::process::id()));
+        let ledger_file = ledger_file.to_str().unwrap();
+        let _ = std::fs::remove_file(ledger_file);
+        crate::portfolio::compute(json!({ "command": "init", "state_file": ledger_file, "initial_capital": 500_000.0 })).unwrap();
+        let instruments = json!({ "NIFTYFUT": { "lot_size": 50, "tick_size": 0.05, "multiplier": 2, "product_type": "futures", "margin_pct": 0.1 } });
+        let mut book = OrderBook::default();
+        let mut run = |extra: Value| {
+            let mut v = json!({ "portfolio_state_file": ledger_file, "instruments": instruments });
+            v.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+            execute(&mut book, &v).unwrap().0
+        };
+        let odd_lot = run(json!({ "command": "place", "symbol": "NIFTYFUT", "side": "buy", "qty": 75, "ltp": 22_000.0 }));
+        assert!(odd_lot["reject_reason"].as_str().unwrap().contains("lot size 50"));
+        let off_tick = run(json!({ "command": "place", "symbol": "NIFTYFUT", "side": "buy", "qty": 50, "order_type": "limit", "limit_price": 22_000.03 }));
+        assert!(off_tick["reject_reason"].as_str().unwrap().contains("tick size"));
+        // 100 × 22000 × 2 × 10% = 440k margin fits; 150 would need 660k.
+        let too_big = run(json!({ "command": "place", "symbol": "NIFTYFUT", "side": "buy", "qty": 150, "ltp": 22_000.0 }));
+        assert_eq!(too_big["status"], "rejected");
+        let placed = run(json!({ "command": "place", "symbol": "NIFTYFUT", "side": "buy", "qty": 100, "ltp": 22_000.0, "timestamp": "2024-01-01T09:15:00" }));
+        assert_eq!(placed["status"], "open");
+
+        let out = run(json!({ "command": "process", "symbol": "NIFTYFUT", "params": { "slippage_bps": 1.0 }, "candles": [
+            { "timestamp": "2024-01-01T09:15:00", "open": 22_000.0, "high": 22_030.0, "low": 21_990.0, "close": 22_010.0, "volume": 0.0 }
+        ]}));
+        // 22000 × 1.0001 = 22002.2, snapped to 22002.2 on the 0.05 grid.
+        assert_eq!(out["fills"][0]["price"], 22_002.2);
+        assert_eq!(out["portfolio"]["unrealized_pnl"], 1_560.0);
+        assert_eq!(out["portfolio"]["margin_used"], 440_200.0);
+        std::fs::remove_file(ledger_file).unwrap();
+    }
+}
@@ -0,0 +1,411 @@
+//! Benchmark-relative tear sheet for an equity curve.
+//!
+//! `equity_curve` (a backtest's `[{date, nav}]`) and `benchmark.candles`
+//! (or `benchmark.candles_file`) are collapsed to daily closes and joined on
+//! date; days only one side has are dropped. The report carries summary
+//! stats for both sides plus relative ones (beta, Jensen's alpha, tracking
+//! error, capture ratios), cumulative/relative/drawdown series, rolling
+//! beta/alpha/correlation over `window` days, the deepest drawdowns with
+//! their recovery, and a year × month return grid for the heatmap.
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::EquityPoint;
+use crate::calendar::{Calendar, CalendarSpec};
+use crate::rolling_beta::{rolling, Rolling};
+use crate::utils::{parse_timestamp, round2, round4, Candle};
+use crate::vol_target::{daily_closes, stats, CurveStats};
+
+#[derive(Deserialize)]
+struct ReportConfig {
+    equity_curve: Vec<EquityPoint>,
+    benchmark: BenchmarkInput,
+    /// Rolling window in days.
+    #[serde(default = "default_window")]
+    window: usize,
+    /// Annual risk-free rate in percent, for alpha and Sortino.
+    #[serde(default)]
+    risk_free_rate: f64,
+    #[serde(default = "default_top_drawdowns")]
+    top_drawdowns: usize,
+    bars_per_day: Option<f64>,
+    #[serde(default)]
+    calendar: Option<CalendarSpec>,
+}
+
+fn default_window() -> usize { 63 }
+fn default_top_drawdowns() -> usize { 5 }
+
+#[derive(Deserialize)]
+struct BenchmarkInput {
+    #[serde(default)]
+    symbol: Option<String>,
+    candles: Vec<Candle>,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    start: String,
+    end: String,
+    days: usize,
+    /// Strategy-only days with no benchmark bar (and vice versa).
+    dropped_days: usize,
+    trading_days_per_year: f64,
+    strategy: CurveStats,
+    benchmark: CurveStats,
+    excess_return_pct: f64,
+    beta: f64,
+    /// Annualized Jensen's alpha over the risk-free rate, percent.
+    alpha_pct: f64,
+    correlation: f64,
+    r_squared: f64,
+    tracking_error_pct: f64,
+    information_ratio: f64,
+    sortino_ratio: f64,
+    calmar_ratio: f64,
+    up_capture_pct: f64,
+    down_capture_pct: f64,
+    best_day_pct: f64,
+    worst_day_pct: f64,
+    positive_days_pct: f64,
+    best_month_pct: f64,
+    worst_month_pct: f64,
+    positive_months_pct: f64,
+}
+
+#[derive(Serialize)]
+struct SeriesPoint {
+    date: String,
+    nav: f64,
+    cumulative_return_pct: f64,
+    benchmark_cumulative_pct: f64,
+    /// Strategy growth over benchmark growth, minus one.
+    relative_pct: f64,
+    drawdown_pct: f64,
+    benchmark_drawdown_pct: f64,
+}
+
+#[derive(Serialize)]
+struct RollingPoint {
+    date: String,
+    beta: Option<f64>,
+    /// Annualized, percent.
+    alpha_pct: Option<f64>,
+    correlation: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct DrawdownPeriod {
+    peak: String,
+    trough: String,
+    /// First day back at the peak; None while still under water.
+    recovery: Option<String>,
+    depth_pct: f64,
+    days_to_trough: i64,
+    days_to_recover: Option<i64>,
+    /// Peak to recovery, or to the last day when unrecovered.
+    duration_days: i64,
+}
+
+#[derive(Serialize)]
+struct YearRow {
+    year: i32,
+    /// January..December; None for months outside the data.
+    months: Vec<Option<f64>>,
+    total_pct: f64,
+    benchmark_total_pct: f64,
+}
+
+#[derive(Serialize)]
+struct ReportResult {
+    benchmark_symbol: Option<String>,
+    window: usize,
+    summary: Summary,
+    series: Vec<SeriesPoint>,
+    rolling: Vec<RollingPoint>,
+    drawdowns: Vec<DrawdownPeriod>,
+    monthly_returns: Vec<YearRow>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: ReportConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid performance_report config: {}", e))?;
+    if config.window < 2 {
+        return Err("window must be at least 2".to_string());
+    }
+    let calendar = Calendar::from_spec(config.calendar.as_ref())?;
+    let bars_per_day = config.bars_per_day.unwrap_or(1.0).max(1.0);
+
+    let strategy = dated(daily_closes(&config.equity_curve, bars_per_day), "equity_curve")?;
+    let bench_curve: Vec<EquityPoint> = config.benchmark.candles.iter()
+        .map(|c| EquityPoint { date: c.timestamp.clone(), nav: c.close })
+        .collect();
+    let bench = dated(daily_closes(&bench_curve, bars_per_day), "benchmark")?;
+
+    let bench_by_day: HashMap<NaiveDate, f64> = bench.iter().map(|(d, _, v)| (*d, *v)).collect();
+    let joined: Vec<(NaiveDate, &str, f64, f64)> = strategy.iter()
+        .filter_map(|(d, label, nav)| bench_by_day.get(d).map(|b| (*d, label.as_str(), *nav, *b)))
+        .collect();
+    if joined.len() < 2 {
+        return Err("equity_curve and benchmark share fewer than 2 dates".to_string());
+    }
+    if joined.iter().any(|j| !(j.2.is_finite() && j.2 > 0.0 && j.3.is_finite() && j.3 > 0.0)) {
+        return Err("NAVs and benchmark closes must be positive".to_string());
+    }
+    let dropped_days = strategy.len() + bench.len() - 2 * joined.len();
+
+    let (first, last) = (joined[0].0, joined[joined.len() - 1].0);
+    let trading_days = calendar.trading_days_per_year(first, last);
+    let years = ((last - first).num_days() as f64 / 365.25).max(0.0);
+    let navs: Vec<f64> = joined.iter().map(|j| j.2).collect();
+    let closes: Vec<f64> = joined.iter().map(|j| j.3).collect();
+    let rs: Vec<f64> = navs.windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+    let rb: Vec<f64> = closes.windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+
+    let strategy_stats = stats(&rs, years, trading_days);
+    let bench_stats = stats(&rb, years, trading_days);
+    let rf = config.risk_free_rate / 100.0 / trading_days;
+    let n = rs.len() as f64;
+    let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len().max(1) as f64;
+    let (ms, mb) = (mean(&rs), mean(&rb));
+    let cov = rs.iter().zip(&rb).map(|(s, b)| (s - ms) * (b - mb)).sum::<f64>() / n;
+    let var_s = rs.iter().map(|s| (s - ms).powi(2)).sum::<f64>() / n;
+    let var_b = rb.iter().map(|b| (b - mb).powi(2)).sum::<f64>() / n;
+    let beta = if var_b > 1e-18 { cov / var_b } else { 0.0 };
+    let correlation = if var_s > 1e-18 && var_b > 1e-18 { (cov / (var_s * var_b).sqrt()).clamp(-1.0, 1.0) } else { 0.0 };
+    let alpha = ((ms - rf) - beta * (mb - rf)) * trading_days;
+
+    let active: Vec<f64> = rs.iter().zip(&rb).map(|(s, b)| s - b).collect();
+    let ma = mean(&active);
+    let te = if active.len() > 1 {
+        (active.iter().map(|a| (a - ma).powi(2)).sum::<f64>() / (n - 1.0)).sqrt() * trading_days.sqrt()
+    } else { 0.0 };
+    let downside = (rs.iter().map(|r| (r - rf).min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+    let capture = |up: bool| {
+        let (s, b): (Vec<f64>, Vec<f64>) = rs.iter().zip(&rb).filter(|(_, b)| if up { **b > 0.0 } else { **b < 0.0 }).unzip();
+        let mb = mean(&b);
+        if b.is_empty() || mb == 0.0 { 0.0 } else { mean(&s) / mb * 100.0 }
+    };
+
+    let months = monthly(&joined);
+    let month_returns: Vec<f64> = months.iter().map(|m| m.1).collect();
+    let summary = Summary {
+        start: joined[0].1.to_string(),
+        end: joined[joined.len() - 1].1.to_string(),
+        days: joined.len(),
+        dropped_days,
+        trading_days_per_year: round2(trading_days),
+        excess_return_pct: round2(strategy_stats.total_return_pct - bench_stats.total_return_pct),
+        beta: round4(beta),
+        alpha_pct: round2(alpha * 100.0),
+        correlation: round4(correlation),
+        r_squared: round4(correlation * correlation),
+        tracking_error_pct: round2(te * 100.0),
+        information_ratio: if te > 0.0 { round2(ma * trading_days / te) } else { 0.0 },
+        sortino_ratio: if downside > 0.0 { round2((ms - rf) / downside * trading_days.sqrt()) } else { 0.0 },
+        calmar_ratio: if strategy_stats.max_drawdown > 0.0 { round2(strategy_stats.cagr / strategy_stats.max_drawdown) } else { 0.0 },
+        up_capture_pct: round2(capture(true)),
+        down_capture_pct: round2(capture(false)),
+        best_day_pct: round2(rs.iter().cloned().fold(f64::NEG_INFINITY, f64::max) * 100.0),
+        worst_day_pct: round2(rs.iter().cloned().fold(f64::INFINITY, f64::min) * 100.0),
+        positive_days_pct: round2(rs.iter().filter(|r| **r > 0.0).count() as f64 / n * 100.0),
+        best_month_pct: round2(month_returns.iter().cloned().fold(f64::NEG_INFINITY, f64::max) * 100.0),
+        worst_month_pct: round2(month_returns.iter().cloned().fold(f64::INFINITY, f64::min) * 100.0),
+        positive_months_pct: round2(month_returns.iter().filter(|r| **r > 0.0).count() as f64 / month_returns.len().max(1) as f64 * 100.0),
+        strategy: strategy_stats,
+        benchmark: bench_stats,
+    };
+
+    let (mut peak, mut bench_peak) = (navs[0], closes[0]);
+    let series = joined.iter().map(|(_, label, nav, close)| {
+        peak = peak.max(*nav);
+        bench_peak = bench_peak.max(*close);
+        let growth = nav / navs[0];
+        let bench_growth = close / closes[0];
+        SeriesPoint {
+            date: label.to_string(),
+            nav: round2(*nav),
+            cumulative_return_pct: round2((growth - 1.0) * 100.0),
+            benchmark_cumulative_pct: round2((bench_growth - 1.0) * 100.0),
+            relative_pct: round2((growth / bench_growth - 1.0) * 100.0),
+            drawdown_pct: round2((nav / peak - 1.0) * 100.0),
+            benchmark_drawdown_pct: round2((close / bench_peak - 1.0) * 100.0),
+        }
+    }).collect();
+
+    let Rolling { beta: rb_beta, correlation: rb_corr, alpha: rb_alpha } = rolling(&navs, &closes, config.window);
+    let rolling_points = joined.iter().enumerate().map(|(i, j)| RollingPoint {
+        date: j.1.to_string(),
+        beta: rb_beta[i].map(round4),
+        alpha_pct: rb_alpha[i].map(|a| round2(a * trading_days * 100.0)),
+        correlation: rb_corr[i].map(round4),
+    }).collect();
+
+    let result = ReportResult {
+        benchmark_symbol: config.benchmark.symbol,
+        window: config.window,
+        summary,
+        series,
+        rolling: rolling_points,
+        drawdowns: drawdowns(&joined, config.top_drawdowns),
+        monthly_returns: year_rows(&months),
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Attach parsed dates to daily closes; every timestamp must parse.
+fn dated(days: Vec<(String, f64)>, what: &str) -> Result<Vec<(NaiveDate, String, f64)>, String> {
+    days.into_iter()
+        .map(|(label, v)| match parse_timestamp(&label) {
+            Some(t) => Ok((t.date(), label, v)),
+            None => Err(format!("{}: unparseable date '{}'", what, label)),
+        })
+        .collect()
+}
+
+/// Strategy and benchmark return per calendar month, month-end to
+/// month-end (the first month from the first day).
+fn monthly(joined: &[(NaiveDate, &str, f64, f64)]) -> Vec<((i32, u32), f64, f64)> {
+    let mut ends: BTreeMap<(i32, u32), (f64, f64)> = BTreeMap::new();
+    for (d, _, nav, close) in joined {
+        ends.insert((d.year(), d.month()), (*nav, *close));
+    }
+    let mut prev = (joined[0].2, joined[0].3);
+    ends.into_iter().map(|(key, end)| {
+        let out = (key, end.0 / prev.0 - 1.0, end.1 / prev.1 - 1.0);
+        prev = end;
+        out
+    }).collect()
+}
+
+fn year_rows(months: &[((i32, u32), f64, f64)]) -> Vec<YearRow> {
+    let mut rows: BTreeMap<i32, (Vec<Option<f64>>, f64, f64)> = BTreeMap::new();
+    for ((year, month), s, b) in months {
+        let row = rows.entry(*year).or_insert_with(|| (vec![None; 12], 1.0, 1.0));
+        row.0[*month as usize - 1] = Some(round2(s * 100.0));
+        row.1 *= 1.0 + s;
+        row.2 *= 1.0 + b;
+    }
+    rows.into_iter().map(|(year, (months, s, b))| YearRow {
+        year,
+        months,
+        total_pct: round2((s - 1.0) * 100.0),
+        benchmark_total_pct: round2((b - 1.0) * 100.0),
+    }).collect()
+}
+
+/// The `top` deepest peak-to-trough episodes, deepest first.
+fn drawdowns(joined: &[(NaiveDate, &str, f64, f64)], top: usize) -> Vec<DrawdownPeriod> {
+    let mut out = Vec::new();
+    let mut peak = 0usize;
+    let mut trough: Option<usize> = None;
+    let close = |peak: usize, trough: usize, recovery: Option<usize>| {
+        let (p, t) = (&joined[peak], &joined[trough]);
+        let end = recovery.map_or(joined[joined.len() - 1].0, |r| joined[r].0);
+        DrawdownPeriod {
+            peak: p.1.to_string(),
+            trough: t.1.to_string(),
+            recovery: recovery.map(|r| joined[r].1.to_string()),
+            depth_pct: round2((t.2 / p.2 - 1.0) * 100.0),
+            days_to_trough: (t.0 - p.0).num_days(),
+            days_to_recover: recovery.map(|r| (joined[r].0 - t.0).num_days()),
+            duration_days: (end - p.0).num_days(),
+        }
+    };
+    for i in 1..joined.len() {
+        let nav = joined[i].2;
+        if nav >= joined[peak].2 {
+            if let Some(t) = trough.take() {
+                out.push(close(peak, t, Some(i)));
+            }
+            peak = i;
+        } else if trough.is_none_or(|t| nav < joined[t].2) {
+            trough = Some(i);
+        }
+    }
+    if let Some(t) = trough {
+        out.push(close(peak, t, None));
+    }
+    out.sort_by(|a, b| a.depth_pct.total_cmp(&b.depth_pct));
+    out.truncate(top);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Daily series from 2024-01-01 where the strategy moves 1.5× the
+    /// benchmark plus a small daily edge.
+    fn inputs(days: usize) -> (Vec<Value>, Vec<Value>) {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let (mut nav, mut close) = (100_000.0, 20_000.0);
+        let mut curve = Vec::new();
+        let mut candles = Vec::new();
+        for d in 0..days {
+            if d > 0 {
+                let r = 0.01 * ((d as f64) * 0.7).sin();
+                close *= 1.0 + r;
+                nav *= 1.0 + 1.5 * r + 0.0002;
+            }
+            let date = (start + chrono::Duration::days(d as i64)).format("%Y-%m-%d").to_string();
+            curve.push(json!({ "date": date, "nav": nav }));
+            candles.push(json!({ "timestamp": date, "open": close, "high": close, "low": close, "close": close, "volume": 1.0 }));
+        }
+        (curve, candles)
+    }
+
+    #[test]
+    fn test_relative_stats_and_series() {
+        let (curve, candles) = inputs(120);
+        let r = compute(json!({ "equity_curve": curve, "benchmark": { "symbol": "NIFTY", "candles": candles }, "window": 20 })).unwrap();
+        let s = &r["summary"];
+        assert_eq!(s["days"], 120);
+        assert!((s["beta"].as_f64().unwrap() - 1.5).abs() < 0.01);
+        assert!(s["correlation"].as_f64().unwrap() > 0.99);
+        assert!(s["alpha_pct"].as_f64().unwrap() > 0.0);
+        assert!(s["up_capture_pct"].as_f64().unwrap() > 100.0);
+        assert_eq!(r["series"][0]["cumulative_return_pct"], 0.0);
+        assert_eq!(r["series"].as_array().unwrap().len(), 120);
+        let rolling = r["rolling"].as_array().unwrap();
+        assert!(rolling[19]["beta"].is_null());
+        assert!((rolling[20]["beta"].as_f64().unwrap() - 1.5).abs() < 0.01);
+        // 2024-01-01..2024-04-29: four months in one year row.
+        let row = &r["monthly_returns"][0];
+        assert_eq!(row["year"], 2024);
+        assert!(row["months"][3].is_number() && row["months"][4].is_null());
+        let total: f64 = row["months"].as_array().unwrap().iter()
+            .filter_map(|m| m.as_f64()).map(|m| 1.0 + m / 100.0).product();
+        assert!(((total - 1.0) * 100.0 - row["total_pct"].as_f64().unwrap()).abs() < 0.05);
+        assert_eq!(s["strategy"]["total_return_pct"], r["series"][119]["cumulative_return_pct"]);
+    }
+
+    #[test]
+    fn test_drawdown_table_and_join() {
+        let navs = [100.0, 110.0, 99.0, 105.0, 111.0, 108.0, 100.0, 104.0];
+        let curve: Vec<Value> = navs.iter().enumerate()
+            .map(|(i, n)| json!({ "date": format!("2024-03-{:02}", i + 1), "nav": n })).collect();
+        // Benchmark is missing 2024-03-04.
+        let candles: Vec<Value> = (1..=8).filter(|d| *d != 4)
+            .map(|d| json!({ "timestamp": format!("2024-03-{:02}", d), "high": 1.0, "low": 1.0, "close": 100.0 + d as f64, "volume": 0.0 }))
+            .collect();
+        let r = compute(json!({ "equity_curve": curve, "benchmark": { "candles": candles }, "window": 3 })).unwrap();
+        assert_eq!(r["summary"]["dropped_days"], 1);
+        let dd = r["drawdowns"].as_array().unwrap();
+        assert_eq!(dd.len(), 2);
+        // Deepest first: 110 → 99 recovered on 03-05, then 111 → 100 still open.
+        assert_eq!(dd[0]["peak"], "2024-03-02");
+        assert_eq!(dd[0]["depth_pct"], -10.0);
+        assert_eq!(dd[0]["recovery"], "2024-03-05");
+        assert_eq!(dd[0]["days_to_recover"], 2);
+        assert_eq!(dd[1]["peak"], "2024-03-05");
+        assert_eq!(dd[1]["depth_pct"], -9.91);
+        assert!(dd[1]["recovery"].is_null());
+        assert_eq!(dd[1]["duration_days"], 3);
+
+        assert!(compute(json!({ "equity_curve": [{ "date": "x", "nav": 1.0 }], "benchmark": { "candles": [] } })).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Custom indicators supplied as WebAssembly modules.
+//!
+//! A plugin is a core WASM module exporting:
+//!
+//! - `memory`
+//! - `alloc(bytes: i32) -> i32` — returns a pointer to `bytes` writable bytes
+//! - `compute(ptr: i32, n: i32) -> i32` — reads `n` rows of five little-endian
+//!   f64 (open, high, low, close, volume) at `ptr` and returns a pointer to
+//!   `n` f64 output values. NaN marks bars without a value (serialized as null).
+//!
+//! Modules are compiled once at registration; every call gets a fresh
+//! instance with a fuel budget, so a plugin cannot keep state between calls
+//! or spin forever. Registered plugins can be requested from `signals` via
+//! `"plugins": ["name", ...]` or run directly with the `plugin` command.
+
+use std::sync::Arc;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{Candle, sanitize_candles};
+
+/// Bytes per input row: open, high, low, close, volume.
+#[cfg(feature = "plugins")]
+const ROW_BYTES: usize = 5 * 8;
+/// Base fuel per call plus a per-bar allowance.
+#[cfg(feature = "plugins")]
+const BASE_FUEL: u64 = 1_000_000;
+#[cfg(feature = "plugins")]
+const FUEL_PER_BAR: u64 = 10_000;
+const MAX_MODULE_BYTES: usize = 16 * 1024 * 1024;
+
+#[cfg(feature = "plugins")]
+struct Plugin {
+    engine: wasmi::Engine,
+    module: wasmi::Module,
+    size_bytes: usize,
+    registered_at: String,
+}
+
+#[cfg(not(feature = "plugins"))]
+struct Plugin {
+    size_bytes: usize,
+    registered_at: String,
+}
+
+static REGISTRY: Lazy<DashMap<String, Arc<Plugin>>> = Lazy::new(DashMap::new);
+
+#[derive(Deserialize)]
+struct RegisterInput {
+    name: String,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    wasm_base64: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NameInput {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct RunInput {
+    name: String,
+    candles: Vec<Candle>,
+}
+
+#[derive(Serialize)]
+struct PluginInfo {
+    name: String,
+    size_bytes: usize,
+    registered_at: String,
+}
+
+pub fn register(data: Value) -> Result<Value, String> {
+    let input: RegisterInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid register_plugin input: {}", e))?;
+    if input.name.is_empty() || !input.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("Invalid plugin name '{}': use letters, digits and '_'", input.name));
+    }
+    let wasm = match (&input.path, &input.wasm_base64) {
+        (Some(path), None) => std::fs::read(path).map_err(|e| format!("Cannot read plugin {}: {}", path, e))?,
+        (None, Some(encoded)) => decode_base64(encoded)?,
+        _ => return Err("register_plugin requires exactly one of 'path' or 'wasm_base64'".to_string()),
+    };
+    if wasm.len() > MAX_MODULE_BYTES {
+        return Err(format!("Plugin module is {} bytes, limit is {}", wasm.len(), MAX_MODULE_BYTES));
+    }
+    let plugin = compile(&wasm)?;
+    let replaced = REGISTRY.insert(input.name.clone(), Arc::new(plugin)).is_some();
+    Ok(serde_json::json!({
+        "name": input.name,
+        "size_bytes": wasm.len(),
+        "replaced": replaced,
+    }))
+}
+
+pub fn unregister(data: Value) -> Result<Value, String> {
+    let input: NameInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid unregister_plugin input: {}", e))?;
+    let removed = REGISTRY.remove(&input.name).is_some();
+    Ok(serde_json::json!({ "name": input.name, "removed": removed }))
+}
+
+pub fn list() -> Result<Value, String> {
+    let mut plugins: Vec<PluginInfo> = REGISTRY
+        .iter()
+        .map(|entry| PluginInfo {
+            name: entry.key().clone(),
+            size_bytes: entry.value().size_bytes,
+            registered_at: entry.value().registered_at.clone(),
+        })
+        .collect();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(serde_json::json!({ "plugins": plugins, "enabled": cfg!(feature = "plugins") }))
+}
+
+/// `plugin` command: run one plugin over `candles`.
+pub fn compute(data: Value) -> Result<Value, String> {
+    let mut input: RunInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid plugin input: {}", e))?;
+    sanitize_candles(&mut input.candles);
+    let series = run(&input.name, &input.candles)?;
+    Ok(serde_json::json!({ "name": input.name, "series": series }))
+}
+
+/// Wraps a candle-based command: when `data.plugins` lists plugin names, each
+/// is run over `data.candles` and the series are added under `plugins`.
+pub fn with_plugin_series(mut data: Value, inner: fn(Value) -> Result<Value, String>) -> Result<Value, String> {
+    let names: Vec<String> = match data.as_object_mut().and_then(|o| o.remove("plugins")) {
+        Some(v) => serde_json::from_value(v).map_err(|e| format!("Invalid plugins list: {}", e))?,
+        None => return inner(data),
+    };
+    let candles: Option<Vec<Candle>> = if names.is_empty() {
+        None
+    } else {
+        let mut candles: Vec<Candle> = data
+            .get("candles")
+            .cloned()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|e| format!("Invalid signal input: {}", e))?
+            .unwrap_or_default();
+        sanitize_candles(&mut candles);
+        Some(candles)
+    };
+
+    let mut result = inner(data)?;
+    if let Some(candles) = candles {
+        let mut series = serde_json::Map::new();
+        for name in names {
+            let values = run(&name, &candles)?;
+            series.insert(name, serde_json::json!(values));
+        }
+        if let Some(obj) = result.as_object_mut() {
+            obj.insert("plugins".to_string(), Value::Object(series));
+        }
+    }
+    Ok(result)
+}
+
+fn lookup(name: &str) -> Result<Arc<Plugin>, String> {
+    REGISTRY
+        .get(name)
+        .map(|p| p.value().clone())
+        .ok_or_else(|| format!("Unknown plugin '{}'", name))
+}
+
+#[cfg(feature = "plugins")]
+fn encode_rows(candles: &[Candle]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(candles.len() * ROW_BYTES);
+    for c in candles {
+        for v in [c.open, c.high, c.low, c.close, c.volume] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+#[cfg(feature = "plugins")]
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, String> {
+    use base64::Engine as _;
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid wasm_base64: {}", e))
+}
+
+#[cfg(feature = "plugins")]
+fn compile(wasm: &[u8]) -> Result<Plugin, String> {
+    let mut config = wasmi::Config::default();
+    config.consume_fuel(true);
+    let engine = wasmi::Engine::new(&config);
+    let module = wasmi::Module::new(&engine, wasm).map_err(|e| format!("Invalid plugin module: {}", e))?;
+    for export in ["memory", "alloc", "compute"] {
+        if module.get_export(export).is_none() {
+            return Err(format!("Plugin module does not export '{}'", export));
+        }
+    }
+    Ok(Plugin {
+        engine,
+        module,
+        size_bytes: wasm.len(),
+        registered_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+#[cfg(feature = "plugins")]
+fn run(name: &str, candles: &[Candle]) -> Result<Vec<Option<f64>>, String> {
+    let plugin = lookup(name)?;
+    let err = |e: wasmi::Error| format!("Plugin '{}' failed: {}", name, e);
+
+    let mut store = wasmi::Store::new(&plugin.engine, ());
+    store
+        .set_fuel(BASE_FUEL + FUEL_PER_BAR * candles.len() as u64)
+        .map_err(err)?;
+    let linker = wasmi::Linker::<()>::new(&plugin.engine);
+    let instance = linker
+        .instantiate(&mut store, &plugin.module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(err)?;
+    let memory = instance
+        .get_memory(&store, "memory")
+        .ok_or_else(|| format!("Plugin '{}' has no memory export", name))?;
+    let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc").map_err(err)?;
+    let compute = instance.get_typed_func::<(i32, i32), i32>(&store, "compute").map_err(err)?;
+
+    let input = encode_rows(candles);
+    let n = i32::try_from(candles.len()).map_err(|_| "Too many candles for plugin".to_string())?;
+    let in_ptr = alloc.call(&mut store, input.len() as i32).map_err(err)?;
+    memory
+        .write(&mut store, in_ptr as u32 as usize, &input)
+        .map_err(|e| format!("Plugin '{}' input write failed: {}", name, e))?;
+    let out_ptr = compute.call(&mut store, (in_ptr, n)).map_err(err)?;
+
+    let mut output = vec![0u8; candles.len() * 8];
+    memory
+        .read(&store, out_ptr as u32 as usize, &mut output)
+        .map_err(|e| format!("Plugin '{}' output read failed: {}", name, e))?;
+    Ok(output
+        .chunks_exact(8)
+        .map(|b| {
+            let v = f64::from_le_bytes(b.try_into().unwrap_or([0; 8]));
+            v.is_finite().then_some(v)
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "plugins"))]
+fn decode_base64(_encoded: &str) -> Result<Vec<u8>, String> {
+    Err("WASM plugins require building the engine with the 'plugins' feature".to_string())
+}
+
+#[cfg(not(feature = "plugins"))]
+fn compile(_wasm: &[u8]) -> Result<Plugin, String> {
+    Err("WASM plugins require building the engine with the 'plugins' feature".to_string())
+}
+
+#[cfg(not(feature = "plugins"))]
+fn run(name: &str, _candles: &[Candle]) -> Result<Vec<Option<f64>>, String> {
+    lookup(name)?;
+    Err("WASM plugins require building the engine with the 'plugins' feature".to_string())
+}
+
+#[cfg(all(test, feature = "plugins"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Returns close - open per bar, and NaN for the first bar.
+    const BODY_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "alloc") (param $bytes i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $bytes)))
+            (local.get $ptr))
+          (func (export "compute") (param $ptr i32) (param $n i32) (result i32)
+            (local $out i32) (local $i i32) (local $row i32)
+            (local.set $out (global.get $next))
+            (global.set $next (i32.add (global.get $next) (i32.mul (local.get $n) (i32.const 8))))
+            (block $done
+              (loop $each
+                (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                (local.set $row (i32.add (local.get $ptr) (i32.mul (local.get $i) (i32.const 40))))
+                (f64.store
+                  (i32.add (local.get $out) (i32.mul (local.get $i) (i32.const 8)))
+                  (select
+                    (f64.const nan)
+                    (f64.sub (f64.load offset=24 (local.get $row)) (f64.load (local.get $row)))
+                    (i32.eqz (local.get $i))))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $each)))
+            (local.get $out)))
+    "#;
+
+    const SPIN_PLUGIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "compute") (param i32 i32) (result i32)
+            (loop $forever (br $forever))
+            (i32.const 0)))
+    "#;
+
+    fn register_wat(name: &str, wat_src: &str) -> Result<Value, String> {
+        use base64::Engine as _;
+        let wasm = wat::parse_str(wat_src).unwrap();
+        register(json!({ "name": name, "wasm_base64": base64::engine::general_purpose::STANDARD.encode(wasm) }))
+    }
+
+    fn candles(n: usize) -> Vec<Value> {
+        (0..n).map(|i| json!({
+            "timestamp": format!("2024-01-01T10:{:02}:00", i), "open": 100.0 + i as f64,
+            "high": 103.0 + i as f64, "low": 99.0 + i as f64, "close": 102.0 + i as f64, "volume": 1000.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_plugin_series_in_signals() {
+        register_wat("test_body", BODY_PLUGIN).unwrap();
+        let result = with_plugin_series(
+            json!({ "candles": candles(30), "plugins": ["test_body"] }),
+            crate::signals::compute,
+        ).unwrap();
+        let series = result["plugins"]["test_body"].as_array().unwrap();
+        assert_eq!(series.len(), 30);
+        assert!(series[0].is_null());
+        assert_eq!(series[5].as_f64().unwrap(), 2.0);
+        assert!(result["ema_9"].is_array());
+
+        let listed = list().unwrap();
+        assert!(listed["plugins"].as_array().unwrap().iter().any(|p| p["name"] == "test_body"));
+        assert_eq!(unregister(json!({ "name": "test_body" })).unwrap()["removed"], true);
+        assert!(compute(json!({ "name": "test_body", "candles": candles(3) })).is_err());
+    }
+
+    #[test]
+    fn test_runaway_plugin_is_stopped_by_fuel() {
+        register_wat("test_spin", SPIN_PLUGIN).unwrap();
+        let err = compute(json!({ "name": "test_spin", "candles": candles(3) })).unwrap_err();
+        assert!(err.contains("test_spin"), "{}", err);
+    }
+
+    #[test]
+    fn test_rejects_module_without_required_exports() {
+        let err = register_wat("test_empty", "(module)").unwrap_err();
+        assert!(err.contains("does not export"), "{}", err);
+        assert!(register(json!({ "name": "bad name", "wasm_base64": "" })).is_err());
+    }
+}
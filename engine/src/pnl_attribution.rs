@@ -0,0 +1,364 @@
+//! Realized and unrealized P&L from a trade list, broken down by symbol,
+//! strategy tag, day, long/short side and nearby event.
+//!
+//! Trades are replayed in timestamp order (input order when timestamps are
+//! missing). `method = "fifo"` closes the oldest lot first and reports each
+//! matched lot, the way a tax statement does; `"average"` merges opens into
+//! one lot at the weighted average cost. Realized P&L is booked to the
+//! opening lot's strategy and event and to the day of the closing trade; open lots are
+//! marked at `prices`. `reported` (symbol → realized P&L from a broker or
+//! ledger) adds a reconciliation table.
+
+use std::collections::{BTreeMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{parse_timestamp, round2, round4};
+
+#[derive(Deserialize)]
+struct AttributionConfig {
+    trades: Vec<TradeIn>,
+    #[serde(default)]
+    prices: BTreeMap<String, f64>,
+    #[serde(default = "default_method")]
+    method: String,
+    /// Holding period (days) from which a closed lot counts as long-term.
+    #[serde(default = "default_long_term_days")]
+    long_term_days: i64,
+    #[serde(default)]
+    reported: BTreeMap<String, f64>,
+    #[serde(default = "default_tolerance")]
+    tolerance: f64,
+}
+
+fn default_method() -> String { "fifo".to_string() }
+fn default_long_term_days() -> i64 { 365 }
+fn default_tolerance() -> f64 { 0.01 }
+
+#[derive(Deserialize)]
+struct TradeIn {
+    symbol: String,
+    side: String,
+    qty: f64,
+    price: f64,
+    #[serde(default)]
+    fees: f64,
+    #[serde(default, alias = "time", alias = "date")]
+    timestamp: Option<String>,
+    #[serde(default, alias = "tag", alias = "strategy_tag")]
+    strategy: Option<String>,
+    /// Event the trade was placed near, e.g. a backtest trade's `event.kind`.
+    #[serde(default)]
+    event: Option<String>,
+}
+
+struct Lot {
+    qty: f64,
+    price: f64,
+    opened: Option<String>,
+    strategy: String,
+    event: String,
+}
+
+/// Open lots for one symbol; `dir` is 1 long, -1 short, 0 flat.
+#[derive(Default)]
+struct Book {
+    dir: f64,
+    lots: VecDeque<Lot>,
+}
+
+#[derive(Default, Serialize)]
+struct Bucket {
+    realized: f64,
+    unrealized: f64,
+    fees: f64,
+    net: f64,
+    trades: usize,
+}
+
+#[derive(Serialize)]
+struct ClosedLot {
+    symbol: String,
+    side: &'static str,
+    qty: f64,
+    entry_price: f64,
+    exit_price: f64,
+    entry_time: Option<String>,
+    exit_time: Option<String>,
+    holding_days: Option<i64>,
+    /// "short_term" or "long_term" against `long_term_days`; None when undated.
+    term: Option<&'static str>,
+    pnl: f64,
+    strategy: String,
+}
+
+#[derive(Serialize)]
+struct OpenLot {
+    symbol: String,
+    side: &'static str,
+    qty: f64,
+    cost_price: f64,
+    mark_price: Option<f64>,
+    unrealized: f64,
+    opened: Option<String>,
+    strategy: String,
+}
+
+#[derive(Serialize)]
+struct Reconciliation {
+    symbol: String,
+    computed: f64,
+    reported: f64,
+    difference: f64,
+    matched: bool,
+}
+
+#[derive(Serialize)]
+struct AttributionResult {
+    method: String,
+    total: Bucket,
+    by_symbol: BTreeMap<String, Bucket>,
+    by_strategy: BTreeMap<String, Bucket>,
+    /// Realized P&L and fees by trade date; open positions have no day.
+    by_day: BTreeMap<String, Bucket>,
+    by_side: BTreeMap<String, Bucket>,
+    /// Keyed by the opening trade's `event`; "none" when untagged.
+    by_event: BTreeMap<String, Bucket>,
+    closed_lots: Vec<ClosedLot>,
+    open_lots: Vec<OpenLot>,
+    missing_prices: Vec<String>,
+    reconciliation: Vec<Reconciliation>,
+}
+
+fn side_name(dir: f64) -> &'static str {
+    if dir > 0.0 { "long" } else { "short" }
+}
+
+fn day_of(ts: &Option<String>) -> String {
+    ts.as_deref()
+        .and_then(parse_timestamp)
+        .map(|d| d.date().to_string())
+        .unwrap_or_else(|| "undated".to_string())
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: AttributionConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid pnl_attribution config: {}", e))?;
+    let average = match config.method.to_lowercase().as_str() {
+        "fifo" => false,
+        "average" | "avg" | "average_cost" => true,
+        other => return Err(format!("Unknown method '{}' (fifo, average)", other)),
+    };
+
+    let mut trades = config.trades;
+    if trades.iter().all(|t| t.timestamp.as_deref().and_then(parse_timestamp).is_some()) {
+        trades.sort_by_key(|t| t.timestamp.as_deref().and_then(parse_timestamp));
+    }
+
+    let mut books: BTreeMap<String, Book> = BTreeMap::new();
+    let mut by_symbol: BTreeMap<String, Bucket> = BTreeMap::new();
+    let mut by_strategy: BTreeMap<String, Bucket> = BTreeMap::new();
+    let mut by_day: BTreeMap<String, Bucket> = BTreeMap::new();
+    let mut by_side: BTreeMap<String, Bucket> = BTreeMap::new();
+    let mut by_event: BTreeMap<String, Bucket> = BTreeMap::new();
+    let mut closed_lots = Vec::new();
+
+    for (i, t) in trades.into_iter().enumerate() {
+        let sign = match t.side.to_lowercase().as_str() {
+            "buy" | "b" | "long" => 1.0,
+            "sell" | "s" | "short" => -1.0,
+            other => return Err(format!("trade {}: unknown side '{}' (buy, sell)", i, other)),
+        };
+        if !(t.qty.is_finite() && t.qty > 0.0 && t.price.is_finite() && t.price > 0.0) {
+            return Err(format!("trade {}: qty and price must be positive", i));
+        }
+        let tag = t.strategy.clone().unwrap_or_else(|| "untagged".to_string());
+        let event = t.event.clone().unwrap_or_else(|| "none".to_string());
+        let day = day_of(&t.timestamp);
+        let book = books.entry(t.symbol.clone()).or_default();
+        let side = side_name(if book.dir != 0.0 { book.dir } else { sign });
+        for (map, key) in [(&mut by_symbol, &t.symbol), (&mut by_strategy, &tag), (&mut by_day, &day), (&mut by_side, &side.to_string()), (&mut by_event, &event)] {
+            let b = map.entry(key.clone()).or_default();
+            b.fees += t.fees;
+            b.trades += 1;
+        }
+
+        let mut remaining = t.qty;
+        // Close against open lots on the other side first.
+        while remaining > 1e-12 && book.dir == -sign {
+            let Some(lot) = book.lots.front_mut() else { break };
+            let q = remaining.min(lot.qty);
+            let pnl = book.dir * q * (t.price - lot.price);
+            let holding_days = match (lot.opened.as_deref().and_then(parse_timestamp), t.timestamp.as_deref().and_then(parse_timestamp)) {
+                (Some(a), Some(b)) => Some((b.date() - a.date()).num_days()),
+                _ => None,
+            };
+            let lot_side = side_name(book.dir);
+            for (map, key) in [(&mut by_symbol, &t.symbol), (&mut by_strategy, &lot.strategy), (&mut by_day, &day), (&mut by_side, &lot_side.to_string()), (&mut by_event, &lot.event)] {
+                map.entry(key.clone()).or_default().realized += pnl;
+            }
+            closed_lots.push(ClosedLot {
+                symbol: t.symbol.clone(),
+                side: lot_side,
+                qty: q,
+                entry_price: round4(lot.price),
+                exit_price: t.price,
+                entry_time: lot.opened.clone(),
+                exit_time: t.timestamp.clone(),
+                holding_days,
+                term: holding_days.map(|d| if d >= config.long_term_days { "long_term" } else { "short_term" }),
+                pnl: round2(pnl),
+                strategy: lot.strategy.clone(),
+            });
+            lot.qty -= q;
+            remaining -= q;
+            if lot.qty <= 1e-12 {
+                book.lots.pop_front();
+            }
+            if book.lots.is_empty() {
+                book.dir = 0.0;
+            }
+        }
+        // Whatever is left opens (or flips into) a position.
+        if remaining > 1e-12 {
+            book.dir = sign;
+            match book.lots.front_mut() {
+                Some(lot) if average => {
+                    lot.price = (lot.price * lot.qty + t.price * remaining) / (lot.qty + remaining);
+                    lot.qty += remaining;
+                }
+                _ => book.lots.push_back(Lot { qty: remaining, price: t.price, opened: t.timestamp.clone(), strategy: tag, event }),
+            }
+        }
+    }
+
+    let mut open_lots = Vec::new();
+    let mut missing_prices = Vec::new();
+    for (symbol, book) in &books {
+        if book.lots.is_empty() {
+            continue;
+        }
+        let mark = config.prices.get(symbol).copied().filter(|p| *p > 0.0);
+        if mark.is_none() {
+            missing_prices.push(symbol.clone());
+        }
+        for lot in &book.lots {
+            let unrealized = mark.map(|m| book.dir * lot.qty * (m - lot.price)).unwrap_or(0.0);
+            let side = side_name(book.dir);
+            for (map, key) in [(&mut by_symbol, symbol), (&mut by_strategy, &lot.strategy), (&mut by_side, &side.to_string()), (&mut by_event, &lot.event)] {
+                map.entry(key.clone()).or_default().unrealized += unrealized;
+            }
+            open_lots.push(OpenLot {
+                symbol: symbol.clone(),
+                side,
+                qty: lot.qty,
+                cost_price: round4(lot.price),
+                mark_price: mark,
+                unrealized: round2(unrealized),
+                opened: lot.opened.clone(),
+                strategy: lot.strategy.clone(),
+            });
+        }
+    }
+
+    let mut total = Bucket::default();
+    for map in [&mut by_symbol, &mut by_strategy, &mut by_day, &mut by_side, &mut by_event] {
+        for b in map.values_mut() {
+            b.net = round2(b.realized + b.unrealized - b.fees);
+            b.realized = round2(b.realized);
+            b.unrealized = round2(b.unrealized);
+            b.fees = round2(b.fees);
+        }
+    }
+    for b in by_symbol.values() {
+        total.realized += b.realized;
+        total.unrealized += b.unrealized;
+        total.fees += b.fees;
+        total.trades += b.trades;
+    }
+    total.net = round2(total.realized + total.unrealized - total.fees);
+    total.realized = round2(total.realized);
+    total.unrealized = round2(total.unrealized);
+    total.fees = round2(total.fees);
+
+    let reconciliation = config.reported.iter()
+        .map(|(symbol, reported)| {
+            let computed = by_symbol.get(symbol).map(|b| b.realized).unwrap_or(0.0);
+            let difference = round2(computed - reported);
+            Reconciliation { symbol: symbol.clone(), computed, reported: *reported, difference, matched: difference.abs() <= config.tolerance }
+        })
+        .collect();
+
+    let result = AttributionResult {
+        method: if average { "average" } else { "fifo" }.to_string(),
+        total,
+        by_symbol,
+        by_strategy,
+        by_day,
+        by_side,
+        by_event,
+        closed_lots,
+        open_lots,
+        missing_prices,
+        reconciliation,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn trades() -> Value {
+        json!([
+            { "symbol": "INFY", "side": "buy", "qty": 10, "price": 100.0, "timestamp": "2024-01-02", "strategy": "breakout", "fees": 1.0, "event": "earnings" },
+            { "symbol": "INFY", "side": "buy", "qty": 10, "price": 120.0, "timestamp": "2024-01-03", "strategy": "breakout" },
+            { "symbol": "INFY", "side": "sell", "qty": 15, "price": 130.0, "timestamp": "2024-01-05", "fees": 1.0 },
+            { "symbol": "TCS", "side": "sell", "qty": 5, "price": 200.0, "timestamp": "2024-01-04", "tag": "mean_rev" },
+            { "symbol": "TCS", "side": "buy", "qty": 5, "price": 190.0, "timestamp": "2024-01-05" }
+        ])
+    }
+
+    #[test]
+    fn test_fifo_breakdowns() {
+        let result = compute(json!({ "trades": trades(), "prices": { "INFY": 125.0 }, "reported": { "INFY": 400.0 } })).unwrap();
+        // FIFO: 10 @100 → +300, 5 @120 → +50; 5 left @120 marked at 125 → +25.
+        assert_eq!(result["by_symbol"]["INFY"]["realized"], 350.0);
+        assert_eq!(result["by_symbol"]["INFY"]["unrealized"], 25.0);
+        assert_eq!(result["by_symbol"]["INFY"]["net"], 373.0);
+        // TCS short covered 10 lower.
+        assert_eq!(result["by_side"]["short"]["realized"], 50.0);
+        // Realized P&L follows the opening lot's event.
+        assert_eq!(result["by_event"]["earnings"]["realized"], 300.0);
+        assert_eq!(result["by_event"]["none"]["realized"], 100.0);
+        assert_eq!(result["by_event"]["none"]["unrealized"], 25.0);
+        assert_eq!(result["by_strategy"]["breakout"]["realized"], 350.0);
+        assert_eq!(result["by_strategy"]["mean_rev"]["realized"], 50.0);
+        assert_eq!(result["by_day"]["2024-01-05"]["realized"], 400.0);
+        assert_eq!(result["closed_lots"].as_array().unwrap().len(), 3);
+        assert_eq!(result["closed_lots"][2]["holding_days"], 1);
+        assert_eq!(result["total"]["net"], 423.0);
+        assert_eq!(result["reconciliation"][0]["difference"], -50.0);
+        assert_eq!(result["reconciliation"][0]["matched"], false);
+    }
+
+    #[test]
+    fn test_average_cost_flip_and_missing_price() {
+        let result = compute(json!({ "trades": trades(), "method": "average" })).unwrap();
+        // Average cost 110: 15 × 20 = 300 realized, 5 left at 110.
+        assert_eq!(result["by_symbol"]["INFY"]["realized"], 300.0);
+        assert_eq!(result["open_lots"][0]["cost_price"], 110.0);
+        assert_eq!(result["missing_prices"][0], "INFY");
+
+        // Selling through a long flips it short at the trade price.
+        let flip = compute(json!({ "trades": [
+            { "symbol": "X", "side": "buy", "qty": 5, "price": 10.0 },
+            { "symbol": "X", "side": "sell", "qty": 8, "price": 12.0 }
+        ], "prices": { "X": 11.0 } })).unwrap();
+        assert_eq!(flip["total"]["realized"], 10.0);
+        assert_eq!(flip["open_lots"][0]["side"], "short");
+        assert_eq!(flip["open_lots"][0]["qty"], 3.0);
+        assert_eq!(flip["total"]["unrealized"], 3.0);
+        assert!(compute(json!({ "trades": [], "method": "lifo" })).is_err());
+    }
+}
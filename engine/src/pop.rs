@@ -0,0 +1,364 @@
+//! Probability of profit for multi-leg option positions.
+//!
+//! The underlying is modelled as risk-neutral GBM to the front expiry at a
+//! single volatility: `iv` if given, else the `iv_surface` (the `surface`
+//! output of `iv_surface`) interpolated at spot on the expiry nearest the
+//! front, else the legs' IVs weighted by size. Two estimates are returned:
+//! a lognormal integral of the expiry P&L and a Monte Carlo that also walks
+//! daily paths to apply early management, closing at `take_profit_pct` of
+//! max profit (of the debit when profit is unlimited) or at a loss of
+//! `stop_loss_pct` of the same base.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::strategy_payoff::{front_expiry, position_pnl, resolve_legs, valuation_date, Leg, LegInput, LegKind};
+use crate::utils::{round2, round4, Xorshift64};
+
+#[derive(Deserialize)]
+struct PopConfig {
+    spot: f64,
+    legs: Vec<LegInput>,
+    #[serde(default)]
+    valuation_date: Option<String>,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    #[serde(default = "default_iv")]
+    default_iv: f64,
+    #[serde(default)]
+    iv: Option<f64>,
+    #[serde(default)]
+    iv_surface: Option<Vec<SurfacePoint>>,
+    #[serde(default = "default_paths")]
+    num_paths: usize,
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default = "default_take_profit")]
+    take_profit_pct: Option<f64>,
+    #[serde(default)]
+    stop_loss_pct: Option<f64>,
+    #[serde(default = "default_bins")]
+    bins: usize,
+}
+
+fn default_rate() -> f64 { 0.065 }
+fn default_iv() -> f64 { 0.2 }
+fn default_paths() -> usize { 10_000 }
+fn default_take_profit() -> Option<f64> { Some(50.0) }
+fn default_bins() -> usize { 20 }
+
+#[derive(Deserialize)]
+struct SurfacePoint {
+    strike: f64,
+    expiry_days: f64,
+    #[serde(alias = "iv")]
+    avg_iv: f64,
+}
+
+#[derive(Serialize)]
+struct LognormalStats {
+    pop: f64,
+    prob_loss: f64,
+    expected_value: f64,
+}
+
+#[derive(Serialize)]
+struct Percentile {
+    percentile: u32,
+    pnl: f64,
+}
+
+#[derive(Serialize)]
+struct Bin {
+    from: f64,
+    to: f64,
+    probability: f64,
+}
+
+#[derive(Serialize)]
+struct MonteCarloStats {
+    paths: usize,
+    pop: f64,
+    expected_value: f64,
+    std_dev: f64,
+    percentiles: Vec<Percentile>,
+    histogram: Vec<Bin>,
+}
+
+#[derive(Serialize)]
+struct ManagementStats {
+    take_profit_pct: Option<f64>,
+    stop_loss_pct: Option<f64>,
+    /// P&L base the percentages apply to.
+    base: f64,
+    take_profit_rate: f64,
+    stop_loss_rate: f64,
+    held_to_expiry_rate: f64,
+    avg_days_held: f64,
+    pop: f64,
+    expected_value: f64,
+}
+
+#[derive(Serialize)]
+struct PopResult {
+    spot: f64,
+    volatility: f64,
+    volatility_source: &'static str,
+    horizon_days: f64,
+    net_premium: f64,
+    /// At front expiry; null when unlimited.
+    max_profit: Option<f64>,
+    max_loss: Option<f64>,
+    lognormal: LognormalStats,
+    monte_carlo: MonteCarloStats,
+    management: Option<ManagementStats>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: PopConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid pop config: {}", e))?;
+    if !(config.spot.is_finite() && config.spot > 0.0) {
+        return Err("spot must be positive".to_string());
+    }
+    let rate = config.risk_free_rate;
+    let valuation = valuation_date(config.valuation_date.as_deref())?;
+    let legs = resolve_legs(&config.legs, config.spot, valuation, rate, config.default_iv)?;
+    let horizon = front_expiry(&legs);
+    if horizon <= 0.0 {
+        return Err("pop needs at least one option leg before expiry".to_string());
+    }
+    let (sigma, source) = match (config.iv, &config.iv_surface) {
+        (Some(iv), _) => (iv, "iv"),
+        (None, Some(surface)) if !surface.is_empty() => (surface_iv(surface, config.spot, horizon), "iv_surface"),
+        _ => (legs_iv(&legs, config.default_iv), "legs"),
+    };
+    if !(sigma.is_finite() && sigma > 0.0) {
+        return Err("volatility must be positive".to_string());
+    }
+    let at_expiry = |p: f64| position_pnl(&legs, p, horizon, rate);
+    let (max_profit, max_loss) = expiry_extremes(&legs, config.spot, horizon, rate);
+    let net_premium: f64 = legs.iter().filter(|l| l.kind != LegKind::Underlying).map(|l| l.premium * l.units).sum();
+    let (ln_pop, ln_loss, ln_ev) = lognormal_pop(&legs, config.spot, sigma, horizon, rate);
+    let lognormal = LognormalStats {
+        pop: round4(ln_pop),
+        prob_loss: round4(ln_loss),
+        expected_value: round2(ln_ev),
+    };
+
+    // Monte Carlo over daily steps, recording both the held and managed outcome.
+    let base = max_profit.filter(|p| *p > 0.0).unwrap_or(net_premium.abs());
+    let manage = base > 0.0 && (config.take_profit_pct.is_some() || config.stop_loss_pct.is_some());
+    let target = config.take_profit_pct.map(|p| base * p / 100.0);
+    let stop = config.stop_loss_pct.map(|p| -base * p / 100.0);
+    let paths = config.num_paths.clamp(100, 200_000);
+    let day_steps = horizon.ceil().max(1.0) as usize;
+    let dt = horizon / day_steps as f64;
+    let step_drift = (rate - sigma * sigma / 2.0) * dt / 365.0;
+    let step_vol = sigma * (dt / 365.0).sqrt();
+    let mut rng = Xorshift64::new(config.seed.unwrap_or(42));
+    let mut held = Vec::with_capacity(paths);
+    let (mut managed_sum, mut managed_wins, mut tp_hits, mut sl_hits, mut days_held) = (0.0, 0, 0, 0, 0.0);
+    for _ in 0..paths {
+        let mut price = config.spot;
+        let mut exit: Option<(f64, f64)> = None;
+        for step in 1..=day_steps {
+            price *= (step_drift + step_vol * rng.next_normal(0.0, 1.0)).exp();
+            if manage && exit.is_none() && step < day_steps {
+                let day = step as f64 * dt;
+                let pnl = position_pnl(&legs, price, day, rate);
+                if target.is_some_and(|tp| pnl >= tp) {
+                    tp_hits += 1;
+                    exit = Some((pnl, day));
+                } else if stop.is_some_and(|sl| pnl <= sl) {
+                    sl_hits += 1;
+                    exit = Some((pnl, day));
+                }
+            }
+        }
+        let final_pnl = at_expiry(price);
+        held.push(final_pnl);
+        let (pnl, day) = exit.unwrap_or((final_pnl, horizon));
+        managed_sum += pnl;
+        days_held += day;
+        if pnl > 0.0 {
+            managed_wins += 1;
+        }
+    }
+    let n = paths as f64;
+    let mean = held.iter().sum::<f64>() / n;
+    let std_dev = (held.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+    let pop = held.iter().filter(|p| **p > 0.0).count() as f64 / n;
+    held.sort_by(|a, b| a.total_cmp(b));
+    let percentiles = [5, 25, 50, 75, 95].iter()
+        .map(|&p| Percentile { percentile: p, pnl: round2(held[((p as f64 / 100.0) * (n - 1.0)).round() as usize]) })
+        .collect();
+    let monte_carlo = MonteCarloStats {
+        paths,
+        pop: round4(pop),
+        expected_value: round2(mean),
+        std_dev: round2(std_dev),
+        percentiles,
+        histogram: histogram(&held, config.bins.clamp(1, 200)),
+    };
+    let management = manage.then(|| ManagementStats {
+        take_profit_pct: config.take_profit_pct,
+        stop_loss_pct: config.stop_loss_pct,
+        base: round2(base),
+        take_profit_rate: round4(tp_hits as f64 / n),
+        stop_loss_rate: round4(sl_hits as f64 / n),
+        held_to_expiry_rate: round4((paths - tp_hits - sl_hits) as f64 / n),
+        avg_days_held: round2(days_held / n),
+        pop: round4(managed_wins as f64 / n),
+        expected_value: round2(managed_sum / n),
+    });
+
+    let result = PopResult {
+        spot: config.spot,
+        volatility: round4(sigma),
+        volatility_source: source,
+        horizon_days: horizon,
+        net_premium: round2(net_premium),
+        max_profit: max_profit.map(round2),
+        max_loss: max_loss.map(round2),
+        lognormal,
+        monte_carlo,
+        management,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Size-weighted IV of the option legs.
+fn legs_iv(legs: &[Leg], fallback: f64) -> f64 {
+    let (w, s) = legs.iter()
+        .filter(|l| l.kind != LegKind::Underlying)
+        .fold((0.0, 0.0), |(w, s), l| (w + l.units.abs(), s + l.units.abs() * l.iv));
+    if w > 0.0 { s / w } else { fallback }
+}
+
+/// Smile on the expiry nearest `days`, linearly interpolated at `spot`.
+fn surface_iv(surface: &[SurfacePoint], spot: f64, days: f64) -> f64 {
+    let nearest = surface.iter()
+        .map(|p| p.expiry_days)
+        .min_by(|a, b| (a - days).abs().total_cmp(&(b - days).abs()))
+        .unwrap_or(days);
+    let mut smile: Vec<(f64, f64)> = surface.iter()
+        .filter(|p| p.expiry_days == nearest && p.avg_iv > 0.0)
+        .map(|p| (p.strike, p.avg_iv))
+        .collect();
+    smile.sort_by(|a, b| a.0.total_cmp(&b.0));
+    match smile.iter().position(|(k, _)| *k >= spot) {
+        None => smile.last().map_or(0.0, |p| p.1),
+        Some(0) => smile[0].1,
+        Some(i) => {
+            let ((k0, v0), (k1, v1)) = (smile[i - 1], smile[i]);
+            v0 + (v1 - v0) * (spot - k0) / (k1 - k0)
+        }
+    }
+}
+
+/// (P(profit), P(loss), expected P&L) at `horizon` days, integrating the
+/// P&L over a risk-neutral lognormal terminal price.
+pub(crate) fn lognormal_pop(legs: &[Leg], spot: f64, sigma: f64, horizon: f64, rate: f64) -> (f64, f64, f64) {
+    let t = horizon / 365.0;
+    let drift = (rate - sigma * sigma / 2.0) * t;
+    let steps = 4000;
+    let (lo, hi) = (-8.0, 8.0);
+    let dz = (hi - lo) / steps as f64;
+    let (mut w_sum, mut w_win, mut w_loss, mut ev) = (0.0, 0.0, 0.0, 0.0);
+    for i in 0..=steps {
+        let z = lo + i as f64 * dz;
+        let w = (-z * z / 2.0).exp();
+        let pnl = position_pnl(legs, spot * (drift + sigma * t.sqrt() * z).exp(), horizon, rate);
+        w_sum += w;
+        ev += w * pnl;
+        if pnl > 0.0 { w_win += w; } else if pnl < 0.0 { w_loss += w; }
+    }
+    (w_win / w_sum, w_loss / w_sum, ev / w_sum)
+}
+
+/// Max profit/loss at `horizon` over prices >= 0; None when the far slope
+/// makes that side unlimited.
+pub(crate) fn expiry_extremes(legs: &[Leg], spot: f64, horizon: f64, rate: f64) -> (Option<f64>, Option<f64>) {
+    let at_expiry = |p: f64| position_pnl(legs, p, horizon, rate);
+    let top = legs.iter().map(|l| l.strike).fold(spot, f64::max) * 3.0;
+    let mut probe: Vec<f64> = (0..=600).map(|i| top * i as f64 / 600.0).collect();
+    probe.extend(legs.iter().map(|l| l.strike));
+    let values: Vec<f64> = probe.iter().map(|&p| at_expiry(p)).collect();
+    let slope = (at_expiry(top * 2.0) - at_expiry(top)) / top;
+    let max_profit = (slope <= 1e-6).then(|| values.iter().cloned().fold(f64::NEG_INFINITY, f64::max));
+    let max_loss = (slope >= -1e-6).then(|| values.iter().cloned().fold(f64::INFINITY, f64::min));
+    (max_profit, max_loss)
+}
+
+fn histogram(sorted: &[f64], bins: usize) -> Vec<Bin> {
+    let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else { return Vec::new() };
+    let width = (max - min) / bins as f64;
+    if width <= 0.0 {
+        return vec![Bin { from: round2(min), to: round2(max), probability: 1.0 }];
+    }
+    let mut counts = vec![0usize; bins];
+    for v in sorted {
+        counts[(((v - min) / width) as usize).min(bins - 1)] += 1;
+    }
+    counts.iter().enumerate().map(|(i, &c)| Bin {
+        from: round2(min + width * i as f64),
+        to: round2(min + width * (i + 1) as f64),
+        probability: round4(c as f64 / sorted.len() as f64),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn short_strangle() -> Value {
+        json!({
+            "spot": 100.0, "iv": 0.2, "num_paths": 4000, "seed": 7,
+            "legs": [
+                { "option_type": "call", "strike": 110.0, "premium": 0.6, "quantity": -1, "expiry_days": 30 },
+                { "option_type": "put", "strike": 90.0, "premium": 0.5, "quantity": -1, "expiry_days": 30 },
+            ]
+        })
+    }
+
+    #[test]
+    fn test_lognormal_and_monte_carlo_agree() {
+        let result = compute(short_strangle()).unwrap();
+        assert_eq!(result["volatility_source"], "iv");
+        assert_eq!(result["max_profit"], 1.1);
+        assert!(result["max_loss"].is_null());
+        let ln_pop = result["lognormal"]["pop"].as_f64().unwrap();
+        let mc_pop = result["monte_carlo"]["pop"].as_f64().unwrap();
+        // Breakevens 88.9 / 111.1 at 20% vol over 30 days: roughly 90% inside.
+        assert!(ln_pop > 0.8 && ln_pop < 0.97, "pop {}", ln_pop);
+        assert!((ln_pop - mc_pop).abs() < 0.03);
+        let total: f64 = result["monte_carlo"]["histogram"].as_array().unwrap().iter()
+            .map(|b| b["probability"].as_f64().unwrap()).sum();
+        assert!((total - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_early_management_and_surface_vol() {
+        let mut data = short_strangle();
+        data["stop_loss_pct"] = json!(200.0);
+        let result = compute(data).unwrap();
+        let m = &result["management"];
+        assert_eq!(m["base"], 1.1);
+        assert!(m["take_profit_rate"].as_f64().unwrap() > 0.3);
+        assert!(m["avg_days_held"].as_f64().unwrap() < 30.0);
+        let rates = ["take_profit_rate", "stop_loss_rate", "held_to_expiry_rate"]
+            .iter().map(|k| m[*k].as_f64().unwrap()).sum::<f64>();
+        assert!((rates - 1.0).abs() < 1e-3);
+
+        let mut data = short_strangle();
+        data.as_object_mut().unwrap().remove("iv");
+        data["iv_surface"] = json!([
+            { "strike": 95.0, "expiry_days": 28.0, "avg_iv": 0.22 },
+            { "strike": 105.0, "expiry_days": 28.0, "avg_iv": 0.18 },
+            { "strike": 100.0, "expiry_days": 90.0, "avg_iv": 0.30 },
+        ]);
+        let result = compute(data).unwrap();
+        assert_eq!(result["volatility_source"], "iv_surface");
+        assert!((result["volatility"].as_f64().unwrap() - 0.2).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,865 @@
+//! Paper-trading portfolio ledger.
+//!
+//! Cash, positions and the fill history behind paper trading. Every fill
+//! goes through [`Ledger::apply_fill`], which averages into a position,
+//! realizes P&L when it reduces one and flips it when it crosses zero.
+//! The JSON `portfolio` command takes a `command` (status, init, open,
+//! close, modify, record_fill, mark, expire, reset); with `state_file` the
+//! ledger is loaded from and saved back to that JSON file on every call,
+//! otherwise it lives in process memory.
+//!
+//! A fill may carry its `option` terms. `expire` then settles every option
+//! position expiring on or before `date` at the underlying's price: out of
+//! the money it expires worthless; in the money it closes at intrinsic
+//! value, and a physically settled contract also books the delivery into
+//! the underlying at that price (exercised when long, assigned when short).
+//! Short legs deep in the money carry an `assignment_risk` flag.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{parse_timestamp, round2, round4};
+
+pub static PORTFOLIO_STORE: once_cell::sync::Lazy<Mutex<Ledger>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(Ledger::new(0.0, 1.0)));
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ledger {
+    pub initial_capital: f64,
+    pub cash: f64,
+    /// Fraction of notional blocked as margin when a position sets none.
+    pub default_margin_pct: f64,
+    pub realized_pnl: f64,
+    pub fees_paid: f64,
+    pub positions: BTreeMap<String, LedgerPosition>,
+    pub fills: Vec<Fill>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerPosition {
+    pub symbol: String,
+    /// Signed: negative = short.
+    pub qty: f64,
+    pub avg_price: f64,
+    pub last_price: f64,
+    pub realized_pnl: f64,
+    pub opened_at: String,
+    #[serde(default)]
+    pub stop_loss: Option<f64>,
+    #[serde(default)]
+    pub take_profit: Option<f64>,
+    #[serde(default)]
+    pub margin_pct: Option<f64>,
+    /// Contract multiplier: cash value of one point per unit.
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+    /// Tag of the fill that opened the position.
+    #[serde(default)]
+    pub strategy_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub option: Option<OptionContract>,
+}
+
+fn default_multiplier() -> f64 { 1.0 }
+
+/// Underlyings whose options settle in cash unless a contract says otherwise.
+const INDEX_UNDERLYINGS: &[&str] = &["NIFTY", "BANKNIFTY", "FINNIFTY", "MIDCPNIFTY", "NIFTYNXT50", "SENSEX", "BANKEX"];
+
+/// Short legs at least this far in the money (percent of strike) are
+/// flagged for assignment.
+const DEEP_ITM_PCT: f64 = 5.0;
+
+/// Option terms of a position, used to settle it at expiry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptionContract {
+    pub underlying: String,
+    /// "call"/"ce" or "put"/"pe".
+    pub option_type: String,
+    pub strike: f64,
+    /// Expiry date, YYYY-MM-DD.
+    pub expiry: String,
+    /// "cash" or "physical"; index underlyings settle in cash and
+    /// everything else physically when absent.
+    #[serde(default)]
+    pub settlement: Option<String>,
+    /// Last underlying price seen by `mark`.
+    #[serde(default)]
+    pub underlying_price: Option<f64>,
+}
+
+impl OptionContract {
+    fn validate(&self) -> Result<(), String> {
+        if !matches!(self.option_type.to_lowercase().as_str(), "call" | "ce" | "put" | "pe") {
+            return Err(format!("Unknown option_type '{}' (call, put)", self.option_type));
+        }
+        if !(self.strike.is_finite() && self.strike > 0.0) {
+            return Err("option strike must be positive".to_string());
+        }
+        if parse_timestamp(&self.expiry).is_none() {
+            return Err(format!("Invalid option expiry '{}'", self.expiry));
+        }
+        match self.settlement.as_deref() {
+            None | Some("cash" | "physical") => Ok(()),
+            Some(other) => Err(format!("Unknown settlement '{}' (cash, physical)", other)),
+        }
+    }
+
+    fn is_call(&self) -> bool {
+        matches!(self.option_type.to_lowercase().as_str(), "call" | "ce")
+    }
+
+    fn cash_settled(&self) -> bool {
+        match self.settlement.as_deref() {
+            Some(s) => s == "cash",
+            None => INDEX_UNDERLYINGS.contains(&self.underlying.to_uppercase().as_str()),
+        }
+    }
+
+    fn intrinsic(&self, spot: f64) -> f64 {
+        if self.is_call() { (spot - self.strike).max(0.0) } else { (self.strike - spot).max(0.0) }
+    }
+}
+
+impl LedgerPosition {
+    /// Signed market value at the last price.
+    fn market_value(&self) -> f64 {
+        self.qty * self.last_price * self.multiplier
+    }
+
+    fn unrealized(&self) -> f64 {
+        (self.last_price - self.avg_price) * self.qty * self.multiplier
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fill {
+    pub id: usize,
+    pub symbol: String,
+    pub side: String,
+    pub qty: f64,
+    pub price: f64,
+    pub fees: f64,
+    pub timestamp: String,
+    /// P&L realized by this fill, before fees.
+    pub realized_pnl: f64,
+    #[serde(default)]
+    pub order_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy_tag: Option<String>,
+    /// How an expiry closed the position: "expired", "cash_settled",
+    /// "exercised" or "assigned".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settlement: Option<String>,
+}
+
+/// A fill to apply; `side` is "buy" or "sell".
+#[derive(Debug, Clone, Deserialize)]
+pub struct FillInput {
+    pub symbol: String,
+    pub side: String,
+    pub qty: f64,
+    pub price: f64,
+    #[serde(default)]
+    pub fees: f64,
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub order_id: Option<String>,
+    /// Contract multiplier; keeps the position's (1 for a new one) when None.
+    #[serde(default)]
+    pub multiplier: Option<f64>,
+    /// Strategy label carried onto the fill and the position it opens.
+    #[serde(default, alias = "tag")]
+    pub strategy_tag: Option<String>,
+    /// Option terms, kept on the position this fill opens.
+    #[serde(default)]
+    pub option: Option<OptionContract>,
+}
+
+#[derive(Serialize)]
+struct PositionView {
+    symbol: String,
+    side: &'static str,
+    qty: f64,
+    avg_price: f64,
+    last_price: f64,
+    market_value: f64,
+    unrealized_pnl: f64,
+    unrealized_pnl_pct: f64,
+    realized_pnl: f64,
+    margin: f64,
+    stop_loss: Option<f64>,
+    take_profit: Option<f64>,
+    opened_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    strategy_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    option: Option<OptionContract>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignment_risk: Option<AssignmentRisk>,
+}
+
+/// A short option leg deep enough in the money to be exercised against.
+#[derive(Serialize)]
+struct AssignmentRisk {
+    /// How far in the money, in percent of strike.
+    itm_pct: f64,
+    intrinsic: f64,
+    /// Premium left above intrinsic at the last price.
+    time_value: f64,
+    settlement: &'static str,
+}
+
+/// What `expire` did with one option position.
+#[derive(Serialize)]
+struct Settlement {
+    symbol: String,
+    underlying: String,
+    qty: f64,
+    settlement_price: f64,
+    intrinsic: f64,
+    outcome: &'static str,
+    realized_pnl: f64,
+    /// Signed underlying units delivered by a physical settlement.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delivered_qty: Option<f64>,
+}
+
+/// Realized P&L and fees of the fills carrying one `strategy_tag`.
+#[derive(Serialize, Default)]
+struct TagView {
+    realized_pnl: f64,
+    fees: f64,
+    net_pnl: f64,
+    unrealized_pnl: f64,
+    fills: usize,
+    open_positions: usize,
+}
+
+#[derive(Serialize)]
+struct Summary {
+    initial_capital: f64,
+    cash: f64,
+    equity: f64,
+    realized_pnl: f64,
+    unrealized_pnl: f64,
+    total_pnl: f64,
+    fees_paid: f64,
+    margin_used: f64,
+    margin_available: f64,
+    gross_exposure: f64,
+    net_exposure: f64,
+    positions: Vec<PositionView>,
+    /// Keyed by tag; untagged fills and positions fall under "untagged".
+    /// Empty until some fill is tagged.
+    by_strategy_tag: BTreeMap<String, TagView>,
+    fills_count: usize,
+    updated_at: Option<String>,
+}
+
+impl Ledger {
+    pub fn new(initial_capital: f64, default_margin_pct: f64) -> Self {
+        Self {
+            initial_capital,
+            cash: initial_capital,
+            default_margin_pct,
+            realized_pnl: 0.0,
+            fees_paid: 0.0,
+            positions: BTreeMap::new(),
+            fills: Vec::new(),
+            updated_at: None,
+        }
+    }
+
+    /// Book a fill: cash moves by the notional and fees, the position is
+    /// averaged into, reduced (realizing P&L against the average) or flipped.
+    pub fn apply_fill(&mut self, input: FillInput) -> Result<Fill, String> {
+        let sign = match input.side.to_lowercase().as_str() {
+            "buy" | "b" | "long" => 1.0,
+            "sell" | "s" | "short" => -1.0,
+            other => return Err(format!("Unknown fill side '{}' (buy, sell)", other)),
+        };
+        if !(input.qty.is_finite() && input.qty > 0.0) {
+            return Err("fill qty must be positive".to_string());
+        }
+        if !(input.price.is_finite() && input.price > 0.0) {
+            return Err("fill price must be positive".to_string());
+        }
+        if input.multiplier.is_some_and(|m| !(m.is_finite() && m > 0.0)) {
+            return Err("fill multiplier must be positive".to_string());
+        }
+        if let Some(option) = &input.option {
+            option.validate()?;
+        }
+        Ok(self.book(input, sign, None))
+    }
+
+    /// `apply_fill` after validation; settlements book at a zero price too.
+    fn book(&mut self, input: FillInput, sign: f64, settlement: Option<&str>) -> Fill {
+        let timestamp = input.timestamp.unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+        let delta = sign * input.qty;
+        let mut realized = 0.0;
+
+        let pos = self.positions.entry(input.symbol.clone()).or_insert_with(|| LedgerPosition {
+            symbol: input.symbol.clone(),
+            qty: 0.0,
+            avg_price: 0.0,
+            last_price: input.price,
+            realized_pnl: 0.0,
+            opened_at: timestamp.clone(),
+            stop_loss: None,
+            take_profit: None,
+            margin_pct: None,
+            multiplier: input.multiplier.unwrap_or(1.0),
+            strategy_tag: input.strategy_tag.clone(),
+            option: None,
+        });
+        if let Some(m) = input.multiplier {
+            pos.multiplier = m;
+        }
+        if input.option.is_some() {
+            pos.option = input.option;
+        }
+        let multiplier = pos.multiplier;
+        if pos.qty == 0.0 || pos.qty.signum() == delta.signum() {
+            let total = pos.qty + delta;
+            pos.avg_price = (pos.avg_price * pos.qty.abs() + input.price * input.qty) / total.abs();
+            if pos.qty == 0.0 {
+                pos.opened_at = timestamp.clone();
+                pos.strategy_tag = input.strategy_tag.clone();
+            }
+            pos.qty = total;
+        } else {
+            let closed = input.qty.min(pos.qty.abs());
+            realized = (input.price - pos.avg_price) * closed * pos.qty.signum() * multiplier;
+            pos.realized_pnl += realized;
+            let remaining = pos.qty + delta;
+            if remaining.abs() < 1e-9 {
+                pos.qty = 0.0;
+            } else if remaining.signum() != pos.qty.signum() {
+                // Crossed zero: the excess opens a fresh position at this price.
+                pos.qty = remaining;
+                pos.avg_price = input.price;
+                pos.opened_at = timestamp.clone();
+                pos.strategy_tag = input.strategy_tag.clone();
+                pos.stop_loss = None;
+                pos.take_profit = None;
+            } else {
+                pos.qty = remaining;
+            }
+        }
+        pos.last_price = input.price;
+        if pos.qty == 0.0 {
+            self.positions.remove(&input.symbol);
+        }
+
+        self.cash -= delta * input.price * multiplier + input.fees;
+        self.realized_pnl += realized;
+        self.fees_paid += input.fees;
+        let fill = Fill {
+            id: self.fills.len() + 1,
+            symbol: input.symbol,
+            side: if sign > 0.0 { "buy".to_string() } else { "sell".to_string() },
+            qty: input.qty,
+            price: input.price,
+            fees: input.fees,
+            realized_pnl: round4(realized),
+            timestamp: timestamp.clone(),
+            order_id: input.order_id,
+            strategy_tag: input.strategy_tag,
+            settlement: settlement.map(String::from),
+        };
+        self.fills.push(fill.clone());
+        self.updated_at = Some(timestamp);
+        fill
+    }
+
+    /// Mark a position and record the price as the underlying of any
+    /// option positions on `symbol`.
+    pub fn mark(&mut self, symbol: &str, price: f64) {
+        if !(price.is_finite() && price > 0.0) {
+            return;
+        }
+        if let Some(p) = self.positions.get_mut(symbol) {
+            p.last_price = price;
+        }
+        for option in self.positions.values_mut().filter_map(|p| p.option.as_mut()) {
+            if option.underlying == symbol {
+                option.underlying_price = Some(price);
+            }
+        }
+    }
+
+    /// Settle every option position expiring on or before `date` at the
+    /// underlying price from `prices`, else the last one marked.
+    fn settle_expiries(&mut self, date: chrono::NaiveDate, prices: &BTreeMap<String, f64>, timestamp: &str) -> Result<Vec<Settlement>, String> {
+        let due: Vec<(String, OptionContract)> = self.positions.values()
+            .filter_map(|p| p.option.clone().map(|o| (p.symbol.clone(), o)))
+            .filter(|(_, o)| parse_timestamp(&o.expiry).is_some_and(|e| e.date() <= date))
+            .collect();
+        // Check every price first so a missing one settles nothing.
+        let mut spots = Vec::with_capacity(due.len());
+        for (symbol, option) in &due {
+            let spot = prices.get(&option.underlying).copied().or(option.underlying_price)
+                .filter(|s| s.is_finite() && *s > 0.0)
+                .ok_or_else(|| format!("No settlement price for {} (underlying of {})", option.underlying, symbol))?;
+            spots.push(spot);
+        }
+
+        let mut out = Vec::with_capacity(due.len());
+        for ((symbol, option), spot) in due.into_iter().zip(spots) {
+            let pos = &self.positions[&symbol];
+            let (qty, multiplier, tag) = (pos.qty, pos.multiplier, pos.strategy_tag.clone());
+            let intrinsic = option.intrinsic(spot);
+            let outcome = if intrinsic <= 0.0 {
+                "expired"
+            } else if option.cash_settled() {
+                "cash_settled"
+            } else if qty > 0.0 {
+                "exercised"
+            } else {
+                "assigned"
+            };
+            let fill = |sym: &str, sign: f64, qty: f64, price: f64| FillInput {
+                symbol: sym.to_string(),
+                side: if sign > 0.0 { "buy" } else { "sell" }.to_string(),
+                qty,
+                price,
+                fees: 0.0,
+                timestamp: Some(timestamp.to_string()),
+                order_id: None,
+                multiplier: None,
+                strategy_tag: tag.clone(),
+                option: None,
+            };
+            let closed = self.book(fill(&symbol, -qty.signum(), qty.abs(), intrinsic), -qty.signum(), Some(outcome));
+            // Long calls and short puts take delivery; long puts and short calls make it.
+            let delivered_qty = matches!(outcome, "exercised" | "assigned").then(|| {
+                let units = qty * multiplier * if option.is_call() { 1.0 } else { -1.0 };
+                self.book(fill(&option.underlying, units.signum(), units.abs(), spot), units.signum(), Some(outcome));
+                units
+            });
+            out.push(Settlement {
+                symbol,
+                underlying: option.underlying,
+                qty,
+                settlement_price: spot,
+                intrinsic: round4(intrinsic),
+                outcome,
+                realized_pnl: round2(closed.realized_pnl),
+                delivered_qty,
+            });
+        }
+        Ok(out)
+    }
+
+    pub fn position_qty(&self, symbol: &str) -> f64 {
+        self.positions.get(symbol).map_or(0.0, |p| p.qty)
+    }
+
+    pub fn margin_used(&self) -> f64 {
+        self.positions.values()
+            .map(|p| p.market_value().abs() * p.margin_pct.unwrap_or(self.default_margin_pct))
+            .sum()
+    }
+
+    /// Cash plus the signed market value of every position.
+    pub fn equity(&self) -> f64 {
+        self.cash + self.positions.values().map(|p| p.market_value()).sum::<f64>()
+    }
+
+    pub(crate) fn summary_value(&self) -> Result<Value, String> {
+        with_fill(self, None)
+    }
+
+    fn summary(&self) -> Summary {
+        let positions: Vec<PositionView> = self.positions.values().map(|p| {
+            let unrealized = p.unrealized();
+            let cost = p.avg_price * p.qty.abs() * p.multiplier;
+            PositionView {
+                symbol: p.symbol.clone(),
+                side: if p.qty > 0.0 { "long" } else { "short" },
+                qty: p.qty,
+                avg_price: round4(p.avg_price),
+                last_price: p.last_price,
+                market_value: round2(p.market_value()),
+                unrealized_pnl: round2(unrealized),
+                unrealized_pnl_pct: if cost > 0.0 { round2(unrealized / cost * 100.0) } else { 0.0 },
+                realized_pnl: round2(p.realized_pnl),
+                margin: round2(p.market_value().abs() * p.margin_pct.unwrap_or(self.default_margin_pct)),
+                stop_loss: p.stop_loss,
+                take_profit: p.take_profit,
+                opened_at: p.opened_at.clone(),
+                strategy_tag: p.strategy_tag.clone(),
+                option: p.option.clone(),
+                assignment_risk: assignment_risk(p),
+            }
+        }).collect();
+        let unrealized: f64 = self.positions.values().map(|p| p.unrealized()).sum();
+        let equity = self.equity();
+        let margin = self.margin_used();
+        Summary {
+            initial_capital: self.initial_capital,
+            cash: round2(self.cash),
+            equity: round2(equity),
+            realized_pnl: round2(self.realized_pnl),
+            unrealized_pnl: round2(unrealized),
+            total_pnl: round2(equity - self.initial_capital),
+            fees_paid: round2(self.fees_paid),
+            margin_used: round2(margin),
+            margin_available: round2(equity - margin),
+            gross_exposure: round2(self.positions.values().map(|p| p.market_value().abs()).sum()),
+            net_exposure: round2(self.positions.values().map(|p| p.market_value()).sum()),
+            positions,
+            by_strategy_tag: self.by_strategy_tag(),
+            fills_count: self.fills.len(),
+            updated_at: self.updated_at.clone(),
+        }
+    }
+
+    fn by_strategy_tag(&self) -> BTreeMap<String, TagView> {
+        let tagged = self.fills.iter().any(|f| f.strategy_tag.is_some())
+            || self.positions.values().any(|p| p.strategy_tag.is_some());
+        let mut out: BTreeMap<String, TagView> = BTreeMap::new();
+        if !tagged {
+            return out;
+        }
+        let key = |tag: &Option<String>| tag.clone().unwrap_or_else(|| "untagged".to_string());
+        for f in &self.fills {
+            let v = out.entry(key(&f.strategy_tag)).or_default();
+            v.realized_pnl += f.realized_pnl;
+            v.fees += f.fees;
+            v.fills += 1;
+        }
+        for p in self.positions.values() {
+            let v = out.entry(key(&p.strategy_tag)).or_default();
+            v.unrealized_pnl += p.unrealized();
+            v.open_positions += 1;
+        }
+        for v in out.values_mut() {
+            v.net_pnl = round2(v.realized_pnl - v.fees);
+            v.realized_pnl = round2(v.realized_pnl);
+            v.fees = round2(v.fees);
+            v.unrealized_pnl = round2(v.unrealized_pnl);
+        }
+        out
+    }
+
+    pub fn load(path: &str) -> Result<Option<Self>, String> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Read error: {}", e))?;
+        serde_json::from_str(&json).map(Some).map_err(|e| format!("Invalid portfolio state file '{}': {}", path, e))
+    }
+
+    /// Write via a temp file and rename so a crash never leaves half a ledger.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Serialization error: {}", e))?;
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, json).map_err(|e| format!("Write error: {}", e))?;
+        std::fs::rename(&tmp, path).map_err(|e| format!("Write error: {}", e))
+    }
+}
+
+fn assignment_risk(p: &LedgerPosition) -> Option<AssignmentRisk> {
+    let option = p.option.as_ref().filter(|_| p.qty < 0.0)?;
+    let spot = option.underlying_price?;
+    let intrinsic = option.intrinsic(spot);
+    let itm_pct = intrinsic / option.strike * 100.0;
+    (itm_pct >= DEEP_ITM_PCT).then(|| AssignmentRisk {
+        itm_pct: round2(itm_pct),
+        intrinsic: round4(intrinsic),
+        time_value: round4((p.last_price - intrinsic).max(0.0)),
+        settlement: if option.cash_settled() { "cash" } else { "physical" },
+    })
+}
+
+/// Run `f` on the ledger in `state_file` (saved back when `f` reports a
+/// change) or on the in-memory store.
+pub(crate) fn with_ledger<R>(
+    state_file: Option<&str>,
+    f: impl FnOnce(&mut Ledger) -> Result<(R, bool), String>,
+) -> Result<R, String> {
+    let mut store = PORTFOLIO_STORE.lock().map_err(|e| format!("Lock error: {}", e))?;
+    match state_file {
+        Some(path) => {
+            let mut ledger = Ledger::load(path)?.unwrap_or_else(|| Ledger::new(0.0, 1.0));
+            let (out, changed) = f(&mut ledger)?;
+            if changed {
+                ledger.save(path)?;
+            }
+            Ok(out)
+        }
+        None => f(&mut store).map(|(out, _)| out),
+    }
+}
+
+/// JSON API entry point. Expects `{ "command": "...", ... }`.
+pub fn compute(data: Value) -> Result<Value, String> {
+    with_ledger(data.get("state_file").and_then(|v| v.as_str()), |ledger| execute(ledger, &data))
+}
+
+/// Run one ledger command; the flag says whether state changed.
+fn execute(ledger: &mut Ledger, data: &Value) -> Result<(Value, bool), String> {
+    let command = data.get("command").and_then(|v| v.as_str()).unwrap_or("status");
+    let symbol = || data.get("symbol").and_then(|v| v.as_str()).ok_or("symbol field required");
+    let f = |key: &str| data.get(key).and_then(|v| v.as_f64());
+    let mut fill = None;
+    match command {
+        "status" | "positions" => return Ok((with_fill(ledger, None)?, false)),
+        "init" | "reset" => {
+            let capital = f("initial_capital").unwrap_or(ledger.initial_capital);
+            if capital < 0.0 {
+                return Err("initial_capital must be non-negative".to_string());
+            }
+            *ledger = Ledger::new(capital, f("default_margin_pct").unwrap_or(ledger.default_margin_pct));
+        }
+        "record_fill" | "open" => {
+            let input: FillInput = serde_json::from_value(data.clone())
+                .map_err(|e| format!("Invalid portfolio fill: {}", e))?;
+            let sym = input.symbol.clone();
+            fill = Some(ledger.apply_fill(input)?);
+            if let Some(pos) = ledger.positions.get_mut(&sym) {
+                if let Some(v) = f("stop_loss") { pos.stop_loss = Some(v); }
+                if let Some(v) = f("take_profit") { pos.take_profit = Some(v); }
+                if let Some(v) = f("margin_pct") { pos.margin_pct = Some(v); }
+            }
+        }
+        "close" => {
+            let sym = symbol()?;
+            let held = ledger.position_qty(sym);
+            if held == 0.0 {
+                return Err(format!("No open position in {}", sym));
+            }
+            let qty = f("qty").unwrap_or(held.abs()).min(held.abs());
+            let price = f("price").ok_or("price field required")?;
+            fill = Some(ledger.apply_fill(FillInput {
+                symbol: sym.to_string(),
+                side: if held > 0.0 { "sell" } else { "buy" }.to_string(),
+                qty,
+                price,
+                fees: f("fees").unwrap_or(0.0),
+                timestamp: data.get("timestamp").and_then(|v| v.as_str()).map(String::from),
+                order_id: data.get("order_id").and_then(|v| v.as_str()).map(String::from),
+                multiplier: None,
+                strategy_tag: data.get("strategy_tag").or_else(|| data.get("tag")).and_then(|v| v.as_str()).map(String::from)
+                    .or_else(|| ledger.positions.get(sym).and_then(|p| p.strategy_tag.clone())),
+                option: None,
+            })?);
+        }
+        "expire" | "settle_expiry" => {
+            let date = match data.get("date").and_then(|v| v.as_str()) {
+                Some(d) => parse_timestamp(d).map(|t| t.date()).ok_or_else(|| format!("Invalid date '{}'", d))?,
+                None => chrono::Local::now().date_naive(),
+            };
+            let prices: BTreeMap<String, f64> = match data.get("prices") {
+                Some(v) => serde_json::from_value(v.clone()).map_err(|e| format!("Invalid prices: {}", e))?,
+                None => BTreeMap::new(),
+            };
+            let timestamp = data.get("timestamp").and_then(|v| v.as_str()).map(String::from)
+                .unwrap_or_else(|| format!("{}T15:30:00", date));
+            let settlements = ledger.settle_expiries(date, &prices, &timestamp)?;
+            let changed = !settlements.is_empty();
+            let mut out = with_fill(ledger, None)?;
+            out["settlements"] = serde_json::to_value(settlements).map_err(|e| format!("Serialization error: {}", e))?;
+            return Ok((out, changed));
+        }
+        "modify" => {
+            let sym = symbol()?;
+            let pos = ledger.positions.get_mut(sym).ok_or_else(|| format!("No open position in {}", sym))?;
+            // Explicit null clears a level; an absent key leaves it alone.
+            for (key, slot) in [("stop_loss", &mut pos.stop_loss), ("take_profit", &mut pos.take_profit), ("margin_pct", &mut pos.margin_pct)] {
+                if let Some(v) = data.get(key) {
+                    *slot = v.as_f64();
+                }
+            }
+        }
+        "mark" => {
+            let prices = data.get("prices").and_then(|v| v.as_object()).ok_or("prices object required")?;
+            for (sym, price) in prices {
+                if let Some(p) = price.as_f64() {
+                    ledger.mark(sym, p);
+                }
+            }
+        }
+        other => return Err(format!("Unknown portfolio command '{}'", other)),
+    }
+    Ok((with_fill(ledger, fill)?, true))
+}
+
+fn with_fill(ledger: &Ledger, fill: Option<Fill>) -> Result<Value, String> {
+    let mut out = serde_json::to_value(ledger.summary()).map_err(|e| format!("Serialization error: {}", e))?;
+    if let Some(fill) = fill {
+        out["fill"] = serde_json::to_value(fill).map_err(|e| format!("Serialization error: {}", e))?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fill(ledger: &mut Ledger, side: &str, qty: f64, price: f64) -> Fill {
+        fill_symbol(ledger, "INFY", side, qty, price)
+    }
+
+    fn fill_symbol(ledger: &mut Ledger, symbol: &str, side: &str, qty: f64, price: f64) -> Fill {
+        ledger.apply_fill(FillInput {
+            symbol: symbol.into(), side: side.into(), qty, price, fees: 0.0,
+            timestamp: Some("2024-01-01T10:00:00".into()), order_id: None, multiplier: None, strategy_tag: None, option: None,
+        }).unwrap()
+    }
+
+    #[test]
+    fn test_average_reduce_and_flip() {
+        let mut ledger = Ledger::new(100_000.0, 1.0);
+        fill(&mut ledger, "buy", 10.0, 100.0);
+        fill(&mut ledger, "buy", 10.0, 110.0);
+        assert_eq!(ledger.positions["INFY"].avg_price, 105.0);
+        let f = fill(&mut ledger, "sell", 5.0, 120.0);
+        assert_eq!(f.realized_pnl, 75.0);
+        assert_eq!(ledger.position_qty("INFY"), 15.0);
+
+        // Sell 20 against 15 long: realize on 15, open a 5 short at 90.
+        fill(&mut ledger, "sell", 20.0, 90.0);
+        let pos = &ledger.positions["INFY"];
+        assert_eq!((pos.qty, pos.avg_price), (-5.0, 90.0));
+        assert!((ledger.realized_pnl - (75.0 - 225.0)).abs() < 1e-9);
+        // Equity = initial + realized while the open short is marked at cost.
+        assert!((ledger.equity() - (100_000.0 - 150.0)).abs() < 1e-9);
+        ledger.mark("INFY", 80.0);
+        assert!((ledger.equity() - (100_000.0 - 100.0)).abs() < 1e-9);
+        assert_eq!(ledger.margin_used(), 400.0);
+    }
+
+    #[test]
+    fn test_multiplier_scales_cash_and_pnl() {
+        let mut ledger = Ledger::new(100_000.0, 0.1);
+        ledger.apply_fill(FillInput {
+            symbol: "GOLDM".into(), side: "buy".into(), qty: 2.0, price: 7_000.0, fees: 0.0,
+            timestamp: None, order_id: None, multiplier: Some(10.0), strategy_tag: None, option: None,
+        }).unwrap();
+        assert_eq!(ledger.cash, -40_000.0);
+        ledger.mark("GOLDM", 7_050.0);
+        assert_eq!(ledger.margin_used(), 14_100.0);
+        assert_eq!(ledger.equity(), 101_000.0);
+        // Later fills keep the position's multiplier.
+        let f = fill_symbol(&mut ledger, "GOLDM", "sell", 2.0, 7_100.0);
+        assert_eq!(f.realized_pnl, 2_000.0);
+        assert_eq!(ledger.cash, 102_000.0);
+    }
+
+    #[test]
+    fn test_strategy_tags_follow_fills_and_positions() {
+        let mut ledger = Ledger::new(100_000.0, 1.0);
+        assert!(ledger.summary().by_strategy_tag.is_empty());
+        let tagged = |side: &str, symbol: &str, price: f64, tag: &str| FillInput {
+            symbol: symbol.into(), side: side.into(), qty: 10.0, price, fees: 5.0,
+            timestamp: None, order_id: None, multiplier: None, strategy_tag: Some(tag.into()), option: None,
+        };
+        ledger.apply_fill(tagged("buy", "INFY", 100.0, "momentum")).unwrap();
+        ledger.apply_fill(tagged("sell", "INFY", 110.0, "momentum")).unwrap();
+        ledger.apply_fill(tagged("sell", "TCS", 200.0, "mean_rev")).unwrap();
+        fill_symbol(&mut ledger, "SBIN", "buy", 1.0, 50.0);
+        ledger.mark("TCS", 190.0);
+        assert_eq!(ledger.positions["TCS"].strategy_tag.as_deref(), Some("mean_rev"));
+
+        let by_tag = serde_json::to_value(ledger.summary()).unwrap()["by_strategy_tag"].clone();
+        assert_eq!(by_tag["momentum"]["realized_pnl"], 100.0);
+        assert_eq!(by_tag["momentum"]["net_pnl"], 90.0);
+        assert_eq!(by_tag["momentum"]["fills"], 2);
+        assert_eq!(by_tag["mean_rev"]["unrealized_pnl"], 100.0);
+        assert_eq!(by_tag["mean_rev"]["open_positions"], 1);
+        assert_eq!(by_tag["untagged"]["fills"], 1);
+
+        // Closing through the JSON command keeps the position's tag.
+        let out = execute(&mut ledger, &json!({ "command": "close", "symbol": "TCS", "price": 195.0 })).unwrap().0;
+        assert_eq!(out["fill"]["strategy_tag"], "mean_rev");
+        assert_eq!(out["by_strategy_tag"]["mean_rev"]["realized_pnl"], 50.0);
+    }
+
+    #[test]
+    fn test_option_expiry_settlement_and_assignment_risk() {
+        let mut ledger = Ledger::new(1_000_000.0, 1.0);
+        let option = |symbol: &str, side: &str, qty: f64, price: f64, underlying: &str, kind: &str, strike: f64, expiry: &str| json!({
+            "command": "open", "symbol": symbol, "side": side, "qty": qty, "price": price,
+            "option": { "underlying": underlying, "option_type": kind, "strike": strike, "expiry": expiry }
+        });
+        for open in [
+            option("NIFTY24JUN22000PE", "sell", 50.0, 100.0, "NIFTY", "put", 22000.0, "2024-06-27"),
+            option("NIFTY24JUN23000CE", "buy", 50.0, 40.0, "NIFTY", "call", 23000.0, "2024-06-27"),
+            option("RELIANCE24JUN2800CE", "sell", 250.0, 30.0, "RELIANCE", "ce", 2800.0, "2024-06-27"),
+            option("NIFTY24JUL22000CE", "buy", 50.0, 300.0, "NIFTY", "call", 22000.0, "2024-07-25"),
+        ] {
+            execute(&mut ledger, &open).unwrap();
+        }
+        let status = execute(&mut ledger, &json!({ "command": "mark", "prices": {
+            "NIFTY": 21500.0, "RELIANCE": 3000.0, "NIFTY24JUN22000PE": 510.0, "RELIANCE24JUN2800CE": 201.0
+        } })).unwrap().0;
+        let risk = |sym: &str| status["positions"].as_array().unwrap().iter()
+            .find(|p| p["symbol"] == sym).unwrap()["assignment_risk"].clone();
+        assert_eq!(risk("RELIANCE24JUN2800CE"), json!({ "itm_pct": 7.14, "intrinsic": 200.0, "time_value": 1.0, "settlement": "physical" }));
+        assert!(risk("NIFTY24JUN22000PE").is_null(), "2.3% in the money is not deep");
+        assert!(risk("NIFTY24JUN23000CE").is_null(), "long legs are never assigned");
+
+        let out = execute(&mut ledger, &json!({ "command": "expire", "date": "2024-06-27", "prices": { "NIFTY": 21400.0 } })).unwrap().0;
+        let s = out["settlements"].as_array().unwrap();
+        assert_eq!(s.len(), 3);
+        assert_eq!((s[0]["outcome"].as_str(), s[0]["realized_pnl"].as_f64()), (Some("cash_settled"), Some(-25_000.0)));
+        assert_eq!((s[1]["outcome"].as_str(), s[1]["realized_pnl"].as_f64()), (Some("expired"), Some(-2_000.0)));
+        assert_eq!((s[2]["outcome"].as_str(), s[2]["settlement_price"].as_f64()), (Some("assigned"), Some(3000.0)));
+        assert_eq!(s[2]["delivered_qty"], -250.0);
+        // Assignment nets the strike: the short call delivers 250 shares at 2800.
+        assert_eq!(ledger.cash, 1_000_000.0 + 5_000.0 - 2_000.0 + 7_500.0 - 15_000.0 - 30_000.0 - 50_000.0 + 750_000.0);
+        let stock = &ledger.positions["RELIANCE"];
+        assert_eq!((stock.qty, stock.avg_price), (-250.0, 3000.0));
+        assert_eq!(ledger.positions.len(), 2, "the July call and the delivered stock remain");
+        assert_eq!(ledger.fills.iter().filter(|f| f.settlement.as_deref() == Some("assigned")).count(), 2);
+
+        // Nothing left to settle today; a missing underlying price settles nothing.
+        assert!(execute(&mut ledger, &json!({ "command": "expire", "date": "2024-06-27" })).unwrap().0["settlements"].as_array().unwrap().is_empty());
+        let mut fresh = Ledger::new(100_000.0, 1.0);
+        execute(&mut fresh, &option("TCS24JUN4000CE", "buy", 150.0, 50.0, "TCS", "call", 4000.0, "2024-06-27")).unwrap();
+        assert!(execute(&mut fresh, &json!({ "command": "expire", "date": "2024-06-30" })).unwrap_err().contains("TCS"));
+        assert_eq!(fresh.positions.len(), 1);
+        let bad = json!({ "command": "open", "symbol": "X", "side": "buy", "qty": 1, "price": 1.0,
+            "option": { "underlying": "X", "option_type": "call", "strike": 10.0, "expiry": "2024-06-27", "settlement": "gold" } });
+        assert!(execute(&mut fresh, &bad).is_err());
+    }
+
+    #[test]
+    fn test_json_commands_with_state_file() {
+        let path = std::env::temp_dir().join(format!("portfolio_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        compute(json!({ "command": "init", "state_file": path, "initial_capital": 50_000.0, "default_margin_pct": 0.2 })).unwrap();
+        let out = compute(json!({
+            "command": "open", "state_file": path, "symbol": "TCS", "side": "buy",
+            "qty": 10, "price": 3000.0, "fees": 20.0, "stop_loss": 2900.0
+        })).unwrap();
+        assert_eq!(out["fill"]["id"], 1);
+        assert_eq!(out["positions"][0]["stop_loss"], 2900.0);
+        assert_eq!(out["margin_used"], 6000.0);
+
+        compute(json!({ "command": "modify", "state_file": path, "symbol": "TCS", "stop_loss": null, "take_profit": 3300.0 })).unwrap();
+        compute(json!({ "command": "mark", "state_file": path, "prices": { "TCS": 3100.0 } })).unwrap();
+        let status = compute(json!({ "command": "status", "state_file": path })).unwrap();
+        let pos = &status["positions"][0];
+        assert!(pos["stop_loss"].is_null());
+        assert_eq!(pos["unrealized_pnl"], 1000.0);
+        assert_eq!(status["equity"], 50_980.0);
+
+        let out = compute(json!({ "command": "close", "state_file": path, "symbol": "TCS", "price": 3050.0 })).unwrap();
+        assert_eq!(out["realized_pnl"], 500.0);
+        assert_eq!(out["positions"].as_array().unwrap().len(), 0);
+        assert_eq!(out["cash"], 50_480.0);
+        assert!(compute(json!({ "command": "close", "state_file": path, "symbol": "TCS", "price": 1.0 })).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,459 @@
+//! Multi-symbol backtest under portfolio-wide constraints.
+//!
+//! Each entry of `symbols` is backtested as its own sleeve with the request's
+//! shared settings (strategy, params, costs, exits, ...), sized off the whole
+//! `initial_capital`; a symbol may override `strategy` and `params`. The
+//! sleeves' trades are then replayed on the union of their timestamps
+//! against one book, and `constraints` decide which entries are taken:
+//!
+//! - `max_positions`: concurrent open positions.
+//! - `max_gross_exposure_pct`: open notional (marked at the latest close)
+//!   plus the new entry, as % of portfolio equity.
+//! - `daily_loss_limit_pct`: once equity falls this far below the previous
+//!   session's closing equity, no new entries for the rest of the session.
+//!   Open positions run on to their own exits.
+//!
+//! At each timestamp exits are booked first, then the daily loss check runs,
+//! then entries are considered in `symbols` order. A blocked entry drops the
+//! whole trade, scale-outs included, and is recorded in `constraint_events`
+//! with the limit and the value that broke it. Every constraint is off when
+//! not set.
+
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::{performance, run_config, BacktestConfig, EquityPoint, TradeEntry};
+use crate::calendar::Calendar;
+use crate::progress::Progress;
+use crate::utils::{parse_timestamp, round2, Candle};
+
+#[derive(Deserialize)]
+struct PortfolioBacktestConfig {
+    initial_capital: f64,
+    symbols: Vec<SleeveInput>,
+    #[serde(default)]
+    constraints: Constraints,
+}
+
+#[derive(Deserialize)]
+struct SleeveInput {
+    symbol: String,
+    candles: Vec<Candle>,
+    #[serde(default)]
+    strategy: Option<String>,
+    #[serde(default)]
+    params: Option<Value>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Constraints {
+    max_positions: Option<usize>,
+    max_gross_exposure_pct: Option<f64>,
+    daily_loss_limit_pct: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct ConstraintEvent {
+    timestamp: String,
+    /// "entry_blocked" or "trading_halted".
+    action: &'static str,
+    constraint: &'static str,
+    limit: f64,
+    /// Open positions, projected gross exposure % or session loss % that
+    /// broke the limit.
+    value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    side: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    qty: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notional: Option<f64>,
+}
+
+#[derive(Serialize, Default)]
+struct BlockedCounts {
+    max_positions: usize,
+    max_gross_exposure: usize,
+    daily_loss_limit: usize,
+}
+
+#[derive(Serialize)]
+struct SleeveSummary {
+    symbol: String,
+    /// Entries the sleeve's own backtest made.
+    signals: usize,
+    taken: usize,
+    blocked: usize,
+    pnl: f64,
+}
+
+#[derive(Serialize)]
+struct PortfolioBacktestResult {
+    total_return_pct: f64,
+    cagr: f64,
+    max_drawdown: f64,
+    sharpe_ratio: f64,
+    sortino_ratio: f64,
+    win_rate: f64,
+    profit_factor: f64,
+    total_trades: usize,
+    total_costs: f64,
+    max_concurrent_positions: usize,
+    peak_gross_exposure_pct: f64,
+    blocked_entries: usize,
+    blocked_by: BlockedCounts,
+    /// Sessions halted by `daily_loss_limit_pct`.
+    daily_loss_stops: usize,
+    per_symbol: Vec<SleeveSummary>,
+    equity_curve: Vec<EquityPoint>,
+    trade_log: Vec<TradeEntry>,
+    constraint_events: Vec<ConstraintEvent>,
+}
+
+/// One sleeve entry and every trade-log row that closes part of it.
+struct Position {
+    sleeve: usize,
+    is_short: bool,
+    entry_price: f64,
+    qty: i64,
+    rows: Vec<usize>,
+}
+
+impl Position {
+    fn side(&self) -> &'static str {
+        if self.is_short { "SHORT" } else { "LONG" }
+    }
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: PortfolioBacktestConfig = serde_json::from_value(data.clone())
+        .map_err(|e| format!("Invalid portfolio_backtest config: {}", e))?;
+    if config.symbols.is_empty() {
+        return Err("portfolio_backtest needs at least one symbol".to_string());
+    }
+    if config.initial_capital <= 0.0 {
+        return Err("initial_capital must be positive".to_string());
+    }
+    let c = &config.constraints;
+    if c.max_positions == Some(0) || c.max_gross_exposure_pct.is_some_and(|v| v <= 0.0)
+        || c.daily_loss_limit_pct.is_some_and(|v| v <= 0.0) {
+        return Err("constraints must be positive".to_string());
+    }
+
+    // Shared settings for every sleeve: the request minus the portfolio-only keys.
+    let mut shared = data;
+    if let Some(obj) = shared.as_object_mut() {
+        obj.remove("symbols");
+        obj.remove("constraints");
+    }
+    let mut sleeves = Vec::with_capacity(config.symbols.len());
+    let mut first_config: Option<BacktestConfig> = None;
+    for input in &config.symbols {
+        let mut sleeve = shared.clone();
+        sleeve["symbol"] = Value::String(input.symbol.clone());
+        sleeve["candles"] = serde_json::to_value(&input.candles).map_err(|e| format!("Serialization error: {}", e))?;
+        if let Some(s) = &input.strategy {
+            sleeve["strategy"] = Value::String(s.clone());
+        }
+        if let Some(p) = &input.params {
+            sleeve["params"] = p.clone();
+        }
+        let bt: BacktestConfig = serde_json::from_value(sleeve)
+            .map_err(|e| format!("Invalid portfolio_backtest config for {}: {}", input.symbol, e))?;
+        let result = run_config(&bt, &Progress::none()).map_err(|e| format!("{}: {}", input.symbol, e))?;
+        sleeves.push(result.trade_log);
+        first_config.get_or_insert(bt);
+    }
+    let first_config = first_config.expect("at least one symbol");
+    let multipliers: Vec<f64> = config.symbols.iter().map(|s| {
+        first_config.instruments.as_ref().map_or(1.0, |i| i.get(&s.symbol).multiplier)
+    }).collect();
+
+    // Union timeline, chronological when every timestamp parses.
+    let mut stamps: Vec<&str> = config.symbols.iter().flat_map(|s| s.candles.iter().map(|c| c.timestamp.as_str())).collect();
+    stamps.sort_unstable();
+    stamps.dedup();
+    let parsed: Option<Vec<_>> = stamps.iter().map(|t| parse_timestamp(t)).collect();
+    if let Some(p) = &parsed {
+        let mut order: Vec<usize> = (0..stamps.len()).collect();
+        order.sort_by_key(|&i| p[i]);
+        stamps = order.iter().map(|&i| stamps[i]).collect();
+    }
+    let sessions: Vec<Option<NaiveDate>> = stamps.iter().map(|t| parse_timestamp(t).map(|d| d.date())).collect();
+    let index: HashMap<&str, usize> = stamps.iter().enumerate().map(|(i, t)| (*t, i)).collect();
+    let closes: Vec<HashMap<&str, f64>> = config.symbols.iter()
+        .map(|s| s.candles.iter().map(|c| (c.timestamp.as_str(), c.close)).collect())
+        .collect();
+
+    let mut positions: Vec<Position> = Vec::new();
+    let mut entries_at: Vec<Vec<usize>> = vec![Vec::new(); stamps.len()];
+    let mut exits_at: Vec<Vec<(usize, usize)>> = vec![Vec::new(); stamps.len()];
+    for (s, log) in sleeves.iter().enumerate() {
+        let mut by_id: HashMap<usize, usize> = HashMap::new();
+        for (r, row) in log.iter().enumerate() {
+            let (Some(&entry), Some(&exit)) = (index.get(row.entry_time.as_str()), index.get(row.exit_time.as_str())) else {
+                return Err(format!("{}: trade timestamps missing from candles", config.symbols[s].symbol));
+            };
+            let p = *by_id.entry(row.trade_id).or_insert_with(|| {
+                // Circuit-break exits don't name a side; the P&L sign does.
+                let is_short = row.side.starts_with("SHORT")
+                    || (!row.side.starts_with("LONG") && (row.exit_price - row.entry_price) * row.gross_pnl < 0.0);
+                positions.push(Position { sleeve: s, is_short, entry_price: row.entry_price, qty: 0, rows: Vec::new() });
+                entries_at[entry].push(positions.len() - 1);
+                positions.len() - 1
+            });
+            positions[p].qty += row.qty;
+            positions[p].rows.push(r);
+            exits_at[exit].push((p, r));
+        }
+    }
+    for e in &mut entries_at {
+        e.sort_by_key(|&p| positions[p].sleeve);
+    }
+
+    let capital = config.initial_capital;
+    let mut taken = vec![false; positions.len()];
+    let mut remaining: Vec<i64> = positions.iter().map(|p| p.qty).collect();
+    let mut last_close: Vec<Option<f64>> = vec![None; config.symbols.len()];
+    let mut realized = 0.0;
+    let mut open: Vec<usize> = Vec::new();
+    let mut trade_log: Vec<TradeEntry> = Vec::new();
+    let mut events: Vec<ConstraintEvent> = Vec::new();
+    let mut blocked_by = BlockedCounts::default();
+    let mut blocked_per_sleeve = vec![0usize; config.symbols.len()];
+    let mut equity_curve = Vec::with_capacity(stamps.len());
+    let (mut peak, mut max_dd) = (capital, 0.0_f64);
+    let (mut max_open, mut peak_gross_pct) = (0usize, 0.0_f64);
+    let mut session_start_equity = capital;
+    let mut halted = false;
+    let mut daily_loss_stops = 0usize;
+
+    // Open notional and equity at the latest closes.
+    let mark = |open: &[usize], remaining: &[i64], last_close: &[Option<f64>], realized: f64| {
+        let (mut gross, mut unrealized) = (0.0, 0.0);
+        for &p in open {
+            let pos = &positions[p];
+            let price = last_close[pos.sleeve].unwrap_or(pos.entry_price);
+            let units = remaining[p] as f64 * multipliers[pos.sleeve];
+            let dir = if pos.is_short { -1.0 } else { 1.0 };
+            gross += price * units;
+            unrealized += (price - pos.entry_price) * units * dir;
+        }
+        (gross, capital + realized + unrealized)
+    };
+
+    for (t, stamp) in stamps.iter().enumerate() {
+        if t > 0 && (sessions[t].is_none() || sessions[t] != sessions[t - 1]) {
+            session_start_equity = equity_curve.last().map_or(capital, |e: &EquityPoint| e.nav);
+            halted = false;
+        }
+        for (s, closes) in closes.iter().enumerate() {
+            if let Some(&c) = closes.get(stamp) {
+                last_close[s] = Some(c);
+            }
+        }
+        for &(p, r) in &exits_at[t] {
+            if !taken[p] {
+                continue;
+            }
+            let row = &sleeves[positions[p].sleeve][r];
+            realized += row.pnl;
+            remaining[p] -= row.qty;
+            trade_log.push(row.clone());
+        }
+        open.retain(|&p| remaining[p] > 0);
+
+        let (mut gross, equity) = mark(&open, &remaining, &last_close, realized);
+        if let Some(limit) = c.daily_loss_limit_pct {
+            let loss_pct = (session_start_equity - equity) / session_start_equity * 100.0;
+            if !halted && loss_pct >= limit {
+                halted = true;
+                daily_loss_stops += 1;
+                events.push(ConstraintEvent {
+                    timestamp: stamp.to_string(), action: "trading_halted", constraint: "daily_loss_limit",
+                    limit, value: round2(loss_pct), symbol: None, side: None, qty: None, notional: None,
+                });
+            }
+        }
+
+        for &p in &entries_at[t] {
+            let pos = &positions[p];
+            let notional = pos.entry_price * pos.qty as f64 * multipliers[pos.sleeve];
+            let gross_pct = (gross + notional) / equity * 100.0;
+            let breach = if halted {
+                let loss_pct = (session_start_equity - equity) / session_start_equity * 100.0;
+                Some(("daily_loss_limit", c.daily_loss_limit_pct.unwrap_or_default(), loss_pct))
+            } else {
+                c.max_positions.filter(|&m| open.len() >= m)
+                    .map(|max| ("max_positions", max as f64, open.len() as f64))
+                    .or_else(|| c.max_gross_exposure_pct.filter(|&m| equity <= 0.0 || gross_pct > m + 1e-9)
+                        .map(|max| ("max_gross_exposure", max, gross_pct)))
+            };
+            if let Some((constraint, limit, value)) = breach {
+                match constraint {
+                    "daily_loss_limit" => blocked_by.daily_loss_limit += 1,
+                    "max_positions" => blocked_by.max_positions += 1,
+                    _ => blocked_by.max_gross_exposure += 1,
+                }
+                blocked_per_sleeve[pos.sleeve] += 1;
+                events.push(ConstraintEvent {
+                    timestamp: stamp.to_string(), action: "entry_blocked", constraint, limit, value: round2(value),
+                    symbol: Some(config.symbols[pos.sleeve].symbol.clone()), side: Some(pos.side()),
+                    qty: Some(pos.qty), notional: Some(round2(notional)),
+                });
+                continue;
+            }
+            taken[p] = true;
+            open.push(p);
+            gross += notional;
+            // Same-bar exits of this entry were skipped above; book them now.
+            for &r in &pos.rows {
+                let row = &sleeves[pos.sleeve][r];
+                if index[row.exit_time.as_str()] == t {
+                    realized += row.pnl;
+                    remaining[p] -= row.qty;
+                    trade_log.push(row.clone());
+                }
+            }
+        }
+        max_open = max_open.max(open.len());
+        open.retain(|&p| remaining[p] > 0);
+
+        let (gross, nav) = mark(&open, &remaining, &last_close, realized);
+        if nav > 0.0 {
+            peak_gross_pct = peak_gross_pct.max(gross / nav * 100.0);
+        }
+        peak = peak.max(nav);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - nav) / peak);
+        }
+        equity_curve.push(EquityPoint { date: stamp.to_string(), nav: round2(nav) });
+    }
+
+    let final_nav = equity_curve.last().map_or(capital, |e| e.nav);
+    let span = match (sessions.first(), sessions.last()) {
+        (Some(Some(a)), Some(Some(b))) => Some((*a, *b)),
+        _ => None,
+    };
+    let calendar = Calendar::from_spec(first_config.calendar.as_ref())?;
+    let perf = performance(&equity_curve, &trade_log, capital, final_nav, span, &calendar,
+        first_config.bars_per_day.unwrap_or(1.0));
+
+    let per_symbol = config.symbols.iter().enumerate().map(|(s, input)| {
+        let mine = || positions.iter().enumerate().filter(move |(_, p)| p.sleeve == s);
+        SleeveSummary {
+            symbol: input.symbol.clone(),
+            signals: mine().count(),
+            taken: mine().filter(|(i, _)| taken[*i]).count(),
+            blocked: blocked_per_sleeve[s],
+            pnl: round2(trade_log.iter().filter(|t| t.symbol == input.symbol).map(|t| t.pnl).sum()),
+        }
+    }).collect();
+
+    let result = PortfolioBacktestResult {
+        total_return_pct: round2((final_nav / capital - 1.0) * 100.0),
+        cagr: round2(perf.cagr),
+        max_drawdown: round2(max_dd * 100.0),
+        sharpe_ratio: round2(perf.sharpe),
+        sortino_ratio: round2(perf.sortino),
+        win_rate: round2(perf.win_rate),
+        profit_factor: round2(perf.profit_factor),
+        total_trades: perf.trades,
+        total_costs: round2(trade_log.iter().map(|t| t.costs).sum()),
+        max_concurrent_positions: max_open,
+        peak_gross_exposure_pct: round2(peak_gross_pct),
+        blocked_entries: blocked_by.max_positions + blocked_by.max_gross_exposure + blocked_by.daily_loss_limit,
+        blocked_by,
+        daily_loss_stops,
+        per_symbol,
+        equity_curve,
+        trade_log,
+        constraint_events: events,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::fixtures::sine_candles;
+
+    /// A wave drifting down by `drift` a bar.
+    fn wave(n: usize, phase: f64, drift: f64) -> Vec<Value> {
+        sine_candles(n, phase, -drift, 0.01)
+    }
+
+    fn request_with(phases: [f64; 3], drift: f64, constraints: Value) -> Value {
+        json!({
+            "strategy": "ema_crossover", "initial_capital": 1_000_000.0,
+            "transaction_costs": { "commission": 0.0, "slippage_bps": 0.0, "stt_pct": 0.0 },
+            "symbols": [
+                { "symbol": "A", "candles": wave(150, phases[0], drift) },
+                { "symbol": "B", "candles": wave(150, phases[1], drift) },
+                { "symbol": "C", "candles": wave(150, phases[2], drift) },
+            ],
+            "constraints": constraints,
+        })
+    }
+
+    fn request(constraints: Value) -> Value {
+        request_with([0.0; 3], 0.0, constraints)
+    }
+
+    #[test]
+    fn test_unconstrained_matches_sleeve_backtests() {
+        let r = compute(request(json!({}))).unwrap();
+        let single = crate::backtest::run(json!({
+            "strategy": "ema_crossover", "symbol": "A", "initial_capital": 1_000_000.0, "candles": wave(150, 0.0, 0.0),
+            "transaction_costs": { "commission": 0.0, "slippage_bps": 0.0, "stt_pct": 0.0 },
+        })).unwrap();
+        let n = single["trade_log"].as_array().unwrap().len();
+        assert!(n > 0);
+        assert_eq!(r["total_trades"].as_u64().unwrap() as usize, 3 * n);
+        assert_eq!(r["blocked_entries"], 0);
+        assert_eq!(r["max_concurrent_positions"], 3);
+        let pnl: f64 = r["trade_log"].as_array().unwrap().iter().map(|t| t["pnl"].as_f64().unwrap()).sum();
+        let last = r["equity_curve"].as_array().unwrap().last().unwrap()["nav"].as_f64().unwrap();
+        assert!((last - 1_000_000.0 - pnl).abs() < 0.05, "{} vs {}", last, pnl);
+    }
+
+    #[test]
+    fn test_constraints_block_entries_with_events() {
+        let unconstrained = compute(request(json!({}))).unwrap();
+        let total = unconstrained["total_trades"].as_u64().unwrap();
+
+        // Identical sleeves enter together, so only the first symbol gets in.
+        let one = compute(request(json!({ "max_positions": 1 }))).unwrap();
+        assert_eq!(one["max_concurrent_positions"], 1);
+        assert_eq!(one["total_trades"].as_u64().unwrap() * 3, total);
+        assert_eq!(one["blocked_by"]["max_positions"].as_u64().unwrap(), one["blocked_entries"].as_u64().unwrap());
+        let events = one["constraint_events"].as_array().unwrap();
+        assert!(events.iter().all(|e| e["action"] == "entry_blocked" && e["symbol"] != "A" && e["limit"] == 1.0));
+        assert_eq!(one["per_symbol"][1]["taken"], 0);
+
+        // A cap below any entry's notional blocks everything.
+        let gross = compute(request(json!({ "max_gross_exposure_pct": 1e-6 }))).unwrap();
+        assert_eq!(gross["total_trades"], 0);
+        assert!(gross["blocked_by"]["max_gross_exposure"].as_u64().unwrap() > 0);
+
+        // Out-of-phase sleeves on a falling drift: a session already down
+        // 0.01% takes no new entries.
+        let losing = |constraints: Value| compute(request_with([0.0, 1.0, 2.0], 1.5, constraints)).unwrap();
+        let free = losing(json!({}));
+        let stop = losing(json!({ "daily_loss_limit_pct": 0.01 }));
+        let events = stop["constraint_events"].as_array().unwrap();
+        assert!(stop["daily_loss_stops"].as_u64().unwrap() > 0);
+        assert!(stop["blocked_by"]["daily_loss_limit"].as_u64().unwrap() > 0);
+        for blocked in events.iter().filter(|e| e["action"] == "entry_blocked") {
+            assert!(events.iter().any(|e| e["action"] == "trading_halted" && e["timestamp"] == blocked["timestamp"]));
+        }
+        assert!(stop["total_trades"].as_u64().unwrap() < free["total_trades"].as_u64().unwrap());
+
+        assert!(compute(request(json!({ "max_positions": 0 }))).is_err());
+    }
+}
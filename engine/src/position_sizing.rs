@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::instruments::Instrument;
 
 /// Position sizing algorithm
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -51,6 +52,15 @@ pub struct SizingContext {
     pub signal_confidence: f64,
     pub max_position_pct: f64,
     pub default_qty: i64,
+    /// Lot size and multiplier; sized quantities are whole lots.
+    pub instrument: Instrument,
+}
+
+impl SizingContext {
+    /// Cash value of one unit of quantity.
+    fn unit_value(&self) -> f64 {
+        self.instrument.notional(self.price, 1.0)
+    }
 }
 
 impl Default for SizingContext {
@@ -68,6 +78,7 @@ impl Default for SizingContext {
             signal_confidence: 0.7,
             max_position_pct: 20.0,
             default_qty: 1,
+            instrument: Instrument::default(),
         }
     }
 }
@@ -147,14 +158,16 @@ pub fn compute_quantity(method: SizingMethod, ctx: &SizingContext) -> i64 {
         }
     };
 
-    let max_qty = (ctx.nav * ctx.max_position_pct / 100.0 / ctx.price).max(1.0) as i64;
-    raw_qty.max(1).min(max_qty)
+    let max_qty = (ctx.nav * ctx.max_position_pct / 100.0 / ctx.unit_value()).max(1.0) as i64;
+    // Whole lots, never less than one.
+    let lot = ctx.instrument.lot_size;
+    (ctx.instrument.round_lots(raw_qty.max(1).min(max_qty) as f64).max(lot)) as i64
 }
 
 /// Simple NAV percentage sizing
 fn nav_pct_sizing(ctx: &SizingContext) -> i64 {
     let max_value = ctx.nav * ctx.max_position_pct / 100.0;
-    (max_value / ctx.price).max(1.0) as i64
+    (max_value / ctx.unit_value()).max(1.0) as i64
 }
 
 /// Kelly criterion sizing: f* = (p*b - q) / b
@@ -168,7 +181,7 @@ fn kelly_sizing(ctx: &SizingContext, fraction: f64) -> i64 {
     let clamped = kelly_f.max(0.0).min(0.5) * fraction;
 
     let position_value = ctx.nav * clamped;
-    (position_value / ctx.price).max(1.0) as i64
+    (position_value / ctx.unit_value()).max(1.0) as i64
 }
 
 /// Risk-parity: size inversely proportional to asset volatility.
@@ -180,7 +193,7 @@ fn risk_parity_sizing(ctx: &SizingContext) -> i64 {
 
     let weight = (risk_budget / vol).min(ctx.max_position_pct / 100.0);
     let position_value = ctx.nav * weight;
-    (position_value / ctx.price).max(1.0) as i64
+    (position_value / ctx.unit_value()).max(1.0) as i64
 }
 
 /// Volatility-targeting: scale position so that position vol ≈ target vol
@@ -193,7 +206,7 @@ fn vol_target_sizing(ctx: &SizingContext) -> i64 {
     let assumed_positions = 5.0;
     let weight = (vol_scalar / assumed_positions).min(ctx.max_position_pct / 100.0);
     let position_value = ctx.nav * weight;
-    (position_value / ctx.price).max(1.0) as i64
+    (position_value / ctx.unit_value()).max(1.0) as i64
 }
 
 /// Regime-adaptive: base sizing on Kelly/vol-target, then scale by regime multiplier
@@ -210,7 +223,7 @@ fn regime_adaptive_sizing(ctx: &SizingContext) -> i64 {
 /// Convenience: compute sizing and return a breakdown for logging/analytics
 pub fn compute_with_breakdown(method: SizingMethod, ctx: &SizingContext) -> SizingResult {
     let qty = compute_quantity(method, ctx);
-    let position_value = ctx.price * qty as f64;
+    let position_value = ctx.unit_value() * qty as f64;
     let nav_pct = if ctx.nav > 0.0 { position_value / ctx.nav * 100.0 } else { 0.0 };
 
     SizingResult {
@@ -251,6 +264,7 @@ mod tests {
             signal_confidence: 0.8,
             max_position_pct: 20.0,
             default_qty: 10,
+            instrument: Instrument::default(),
         }
     }
 
@@ -371,6 +385,21 @@ mod tests {
         assert_eq!(result.regime_multiplier, 1.0);
     }
 
+    #[test]
+    fn test_sizes_in_lots_with_multiplier() {
+        let ctx = SizingContext {
+            nav: 1_000_000.0,
+            price: 100.0,
+            instrument: Instrument { lot_size: 75.0, multiplier: 10.0, ..Instrument::default() },
+            ..default_ctx()
+        };
+        // 20% of NAV is 200k; 1000 per unit → 200 units → 2 lots of 75.
+        assert_eq!(compute_quantity(SizingMethod::NavPct, &ctx), 150);
+        let tiny = SizingContext { nav: 10_000.0, ..ctx };
+        assert_eq!(compute_quantity(SizingMethod::NavPct, &tiny), 75, "at least one lot");
+        assert_eq!(compute_with_breakdown(SizingMethod::NavPct, &tiny).position_value, 75_000.0);
+    }
+
     #[test]
     fn test_method_parsing() {
         assert_eq!(SizingMethod::from_str_loose("kelly"), SizingMethod::Kelly);
@@ -406,6 +406,7 @@ pub fn run_premarket_pipeline(state: &Arc<AppState>) -> PremarketReport {
             signal_confidence: sig.confidence,
             max_position_pct: state.config.live_executor.max_position_pct,
             default_qty: state.config.live_executor.default_qty,
+            instrument: Default::default(),
         };
         sig.suggested_qty = compute_quantity(sizing_mode, &ctx);
     }
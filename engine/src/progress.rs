@@ -0,0 +1,158 @@
+//! Progress notifications for long-running commands.
+//!
+//! Requests opt in with `"progress": true`. While backtest / optimize /
+//! walk_forward run, throttled NDJSON events are emitted alongside the final
+//! response:
+//!
+//! `{"id":"r1","event":"progress","command":"optimize","stage":"combo",
+//!   "completed":12,"total":48,"percent":25.0,"elapsed_ms":900,"eta_ms":2700}`
+//!
+//! Events go to stderr by default; serve/daemon modes route them to stdout so
+//! the host can match them to the pending request by `id`. Events carry no
+//! `success` field, which is how they are told apart from the response.
+//! Live `stream` events (signal/entry/exit/alert) use the same channel and
+//! carry a `subscription_id` instead of a request `id`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use once_cell::sync::OnceCell;
+use serde_json::{json, Value};
+use crate::utils::round2;
+
+pub type Sink = Arc<dyn Fn(&Value) + Send + Sync>;
+
+static SINK: OnceCell<Sink> = OnceCell::new();
+
+/// Minimum gap between two events of the same request.
+const EMIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Route progress events for the rest of the process. First call wins.
+pub fn set_sink(sink: Sink) {
+    let _ = SINK.set(sink);
+}
+
+/// Send an unsolicited event (e.g. live `stream` signals) down the same
+/// channel as progress updates.
+pub fn publish(event: &Value) {
+    default_sink()(event);
+}
+
+fn default_sink() -> Sink {
+    SINK.get().cloned().unwrap_or_else(|| {
+        Arc::new(|event: &Value| eprintln!("{}", event))
+    })
+}
+
+pub struct Progress {
+    sink: Option<Sink>,
+    id: Option<String>,
+    command: String,
+    started: Instant,
+    last_emit: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    /// A reporter that never emits.
+    pub fn none() -> Self {
+        Progress {
+            sink: None,
+            id: None,
+            command: String::new(),
+            started: Instant::now(),
+            last_emit: Mutex::new(None),
+        }
+    }
+
+    /// A reporter writing to the process-wide sink when `enabled`.
+    pub fn for_request(id: Option<String>, command: &str, enabled: bool) -> Self {
+        if !enabled {
+            return Self::none();
+        }
+        Self::with_sink(id, command, default_sink())
+    }
+
+    pub fn with_sink(id: Option<String>, command: &str, sink: Sink) -> Self {
+        Progress {
+            sink: Some(sink),
+            id,
+            command: command.to_string(),
+            started: Instant::now(),
+            last_emit: Mutex::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Report `completed` of `total` units. Throttled, except that the first
+    /// and the final update are always emitted.
+    pub fn update(&self, stage: &str, completed: usize, total: usize) {
+        let Some(sink) = &self.sink else { return };
+        let now = Instant::now();
+        {
+            let mut last = match self.last_emit.lock() {
+                Ok(l) => l,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            let due = last.is_none_or(|t| now.duration_since(t) >= EMIT_INTERVAL);
+            if !due && completed < total {
+                return;
+            }
+            *last = Some(now);
+        }
+
+        let elapsed_ms = now.duration_since(self.started).as_millis() as u64;
+        let fraction = if total > 0 { completed as f64 / total as f64 } else { 0.0 };
+        let eta_ms = if completed > 0 && completed < total {
+            Some((elapsed_ms as f64 * (1.0 - fraction) / fraction).round() as u64)
+        } else if completed >= total {
+            Some(0)
+        } else {
+            None
+        };
+        sink(&json!({
+            "id": self.id,
+            "event": "progress",
+            "command": self.command,
+            "stage": stage,
+            "completed": completed,
+            "total": total,
+            "percent": round2(fraction * 100.0),
+            "elapsed_ms": elapsed_ms,
+            "eta_ms": eta_ms,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_throttles_and_reports_completion() {
+        let events: Arc<Mutex<Vec<Value>>> = Arc::new(Mutex::new(Vec::new()));
+        let captured = events.clone();
+        let progress = Progress::with_sink(Some("p1".to_string()), "optimize", Arc::new(move |e: &Value| {
+            captured.lock().unwrap().push(e.clone());
+        }));
+        for i in 1..=100 {
+            progress.update("combo", i, 100);
+        }
+        let events = events.lock().unwrap();
+        // First update plus the final one; the rest fall inside the interval.
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["id"], "p1");
+        assert_eq!(events[0]["event"], "progress");
+        assert_eq!(events[1]["percent"].as_f64().unwrap(), 100.0);
+        assert_eq!(events[1]["eta_ms"], 0);
+        assert!(events[1].get("success").is_none());
+    }
+
+    #[test]
+    fn test_disabled_progress_is_silent() {
+        let progress = Progress::for_request(Some("p2".to_string()), "backtest", false);
+        assert!(!progress.is_enabled());
+        progress.update("bars", 1, 2);
+    }
+}
@@ -0,0 +1,282 @@
+//! Per-symbol range, ATR and gap statistics ("stock personality").
+//!
+//! Candles are collapsed to daily OHLC as in `realized_vol`. Each session
+//! after the first gives a range (high − low) and an opening gap, both as %
+//! of the previous close, and an ATR% (Wilder ATR over `atr_period` sessions
+//! / close). Over the last `lookback` sessions (0 = all) every series is
+//! summarized by mean, median, 10th/90th percentiles and the percentile rank
+//! of the latest value.
+//!
+//! `threshold_scale` is the latest ATR% over its median, the factor the
+//! scanner stretches fixed % thresholds by; `regime` buckets the ATR%
+//! percentile ("compressed" below 20, "expanded" above 80).
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::indicators::{percentile_rank, quantile_sorted};
+use crate::kernels::atr;
+use crate::realized_vol::daily_bars;
+use crate::utils::{round2, round4, Candle};
+
+#[derive(Deserialize)]
+struct RangeStatsConfig {
+    symbols: Vec<SymbolCandles>,
+    #[serde(default = "default_atr_period")]
+    atr_period: usize,
+    #[serde(default = "default_lookback")]
+    lookback: usize,
+    /// |gap| at or above this % of the previous close counts as a gap.
+    #[serde(default = "default_gap_threshold")]
+    gap_threshold_pct: f64,
+    #[serde(default)]
+    bars_per_day: Option<f64>,
+}
+
+fn default_atr_period() -> usize { 14 }
+fn default_lookback() -> usize { 252 }
+fn default_gap_threshold() -> f64 { 0.5 }
+
+#[derive(Deserialize)]
+struct SymbolCandles {
+    symbol: String,
+    candles: Vec<Candle>,
+}
+
+#[derive(Serialize)]
+struct Stat {
+    current: f64,
+    mean: f64,
+    median: f64,
+    p10: f64,
+    p90: f64,
+    /// Rank of `current` within the history, 0-100.
+    percentile: f64,
+}
+
+#[derive(Serialize)]
+struct GapStats {
+    /// Share of sessions opening at least the threshold above / below the previous close.
+    up_freq_pct: f64,
+    down_freq_pct: f64,
+    largest_up_pct: f64,
+    largest_down_pct: f64,
+    /// Share of threshold gaps that traded back to the previous close the same session.
+    filled_pct: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct SymbolStats {
+    symbol: String,
+    sessions: usize,
+    /// Sessions in the percentile history.
+    history: usize,
+    last_close: f64,
+    range_pct: Stat,
+    atr_pct: Stat,
+    /// Absolute opening gap.
+    gap_pct: Stat,
+    gaps: GapStats,
+    threshold_scale: f64,
+    regime: &'static str,
+}
+
+#[derive(Serialize)]
+struct Skipped {
+    symbol: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct RangeStatsResult {
+    atr_period: usize,
+    lookback: usize,
+    gap_threshold_pct: f64,
+    symbols: Vec<SymbolStats>,
+    skipped: Vec<Skipped>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: RangeStatsConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid range_stats config: {}", e))?;
+    if config.symbols.is_empty() {
+        return Err("At least one symbol required".to_string());
+    }
+    if config.atr_period == 0 {
+        return Err("atr_period must be at least 1".to_string());
+    }
+    if config.lookback == 1 {
+        return Err("lookback must be 0 (all) or at least 2 sessions".to_string());
+    }
+    if !(config.gap_threshold_pct.is_finite() && config.gap_threshold_pct >= 0.0) {
+        return Err("gap_threshold_pct must be non-negative".to_string());
+    }
+
+    let mut symbols = Vec::new();
+    let mut skipped = Vec::new();
+    for s in &config.symbols {
+        match symbol_stats(s, &config) {
+            Ok(stats) => symbols.push(stats),
+            Err(reason) => skipped.push(Skipped { symbol: s.symbol.clone(), reason }),
+        }
+    }
+
+    let result = RangeStatsResult {
+        atr_period: config.atr_period,
+        lookback: config.lookback,
+        gap_threshold_pct: config.gap_threshold_pct,
+        symbols,
+        skipped,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn symbol_stats(s: &SymbolCandles, config: &RangeStatsConfig) -> Result<SymbolStats, String> {
+    let (_, bars) = daily_bars(&s.candles, config.bars_per_day.unwrap_or(1.0));
+    if bars.iter().any(|b| !(b.open > 0.0 && b.high > 0.0 && b.low > 0.0 && b.close > 0.0)) {
+        return Err("candle prices must be positive".to_string());
+    }
+    if bars.len() <= config.atr_period {
+        return Err(format!("{} sessions, need more than atr_period ({})", bars.len(), config.atr_period));
+    }
+
+    let highs: Vec<f64> = bars.iter().map(|b| b.high).collect();
+    let lows: Vec<f64> = bars.iter().map(|b| b.low).collect();
+    let closes: Vec<f64> = bars.iter().map(|b| b.close).collect();
+    let atr = atr(&highs, &lows, &closes, config.atr_period);
+
+    // Session i against the close of i - 1; ATR% from the first full period.
+    let (mut range, mut gap) = (Vec::new(), Vec::new());
+    for w in bars.windows(2) {
+        range.push((w[1].high - w[1].low) / w[0].close * 100.0);
+        gap.push((w[1].open / w[0].close - 1.0) * 100.0);
+    }
+    let atr_pct: Vec<f64> = atr.iter().zip(&closes).skip(config.atr_period - 1).map(|(a, c)| a / c * 100.0).collect();
+
+    let tail = |v: &[f64]| -> Vec<f64> {
+        let keep = if config.lookback == 0 { v.len() } else { v.len().min(config.lookback) };
+        v[v.len() - keep..].to_vec()
+    };
+    let (range, gap, atr_pct) = (tail(&range), tail(&gap), tail(&atr_pct));
+    let abs_gap: Vec<f64> = gap.iter().map(|g| g.abs()).collect();
+
+    // Gap fills over the same history: a gap up fills when the low reaches the prior close.
+    let first = bars.len() - gap.len();
+    let threshold = config.gap_threshold_pct;
+    let (mut up, mut down, mut filled) = (0usize, 0usize, 0usize);
+    for (k, g) in gap.iter().enumerate() {
+        let (prev, bar) = (&bars[first + k - 1], &bars[first + k]);
+        if *g >= threshold && *g > 0.0 {
+            up += 1;
+            filled += usize::from(bar.low <= prev.close);
+        } else if *g <= -threshold && *g < 0.0 {
+            down += 1;
+            filled += usize::from(bar.high >= prev.close);
+        }
+    }
+    let n = gap.len() as f64;
+    let gaps = GapStats {
+        up_freq_pct: round2(up as f64 / n * 100.0),
+        down_freq_pct: round2(down as f64 / n * 100.0),
+        largest_up_pct: round4(gap.iter().copied().fold(0.0, f64::max)),
+        largest_down_pct: round4(gap.iter().copied().fold(0.0, f64::min)),
+        filled_pct: (up + down > 0).then(|| round2(filled as f64 / (up + down) as f64 * 100.0)),
+    };
+
+    let atr_stat = stat(&atr_pct);
+    let threshold_scale = if atr_stat.median > 0.0 { round4(atr_stat.current / atr_stat.median) } else { 1.0 };
+    let regime = match atr_stat.percentile {
+        p if p < 20.0 => "compressed",
+        p if p > 80.0 => "expanded",
+        _ => "normal",
+    };
+    Ok(SymbolStats {
+        symbol: s.symbol.clone(),
+        sessions: bars.len(),
+        history: range.len(),
+        last_close: round4(closes[closes.len() - 1]),
+        range_pct: stat(&range),
+        atr_pct: atr_stat,
+        gap_pct: stat(&abs_gap),
+        gaps,
+        threshold_scale,
+        regime,
+    })
+}
+
+/// Summary of a non-empty series whose last value is the current reading.
+fn stat(values: &[f64]) -> Stat {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let percentile = percentile_rank(values, values.len()).last().copied().unwrap_or(f64::NAN);
+    Stat {
+        current: round4(values[values.len() - 1]),
+        mean: round4(values.iter().sum::<f64>() / values.len() as f64),
+        median: round4(quantile_sorted(&sorted, 0.5)),
+        p10: round4(quantile_sorted(&sorted, 0.1)),
+        p90: round4(quantile_sorted(&sorted, 0.9)),
+        percentile: round2(percentile),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Daily bars around 100 with a `range`-wide session and an opening gap every fifth day.
+    fn daily(n: usize, range: impl Fn(usize) -> f64) -> Vec<Value> {
+        (0..n).map(|i| {
+            let open = if i % 5 == 4 { 101.0 } else { 100.0 };
+            let r = range(i);
+            json!({
+                "timestamp": format!("2024-{:02}-{:02}", 1 + i / 28, 1 + i % 28),
+                "open": open, "high": 100.0 + r / 2.0, "low": 100.0 - r / 2.0, "close": 100.0, "volume": 1000.0
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn test_range_atr_and_gap_statistics() {
+        let result = compute(json!({
+            "symbols": [
+                { "symbol": "CALM", "candles": daily(60, |_| 2.0) },
+                { "symbol": "WAKING", "candles": daily(60, |i| if i >= 55 { 8.0 } else { 2.0 }) },
+                { "symbol": "SHORT", "candles": daily(5, |_| 2.0) },
+            ],
+            "atr_period": 5,
+        })).unwrap();
+        let calm = &result["symbols"][0];
+        assert_eq!(calm["sessions"], 60);
+        assert_eq!(calm["history"], 59);
+        assert_eq!(calm["range_pct"]["median"], 2.0);
+        assert_eq!(calm["atr_pct"]["current"], 2.0);
+        assert_eq!(calm["threshold_scale"], 1.0);
+        assert_eq!(calm["regime"], "normal");
+        // Opens of 101 on every fifth day, always back to 100 inside the bar.
+        assert_eq!(calm["gaps"]["up_freq_pct"], round2(12.0 / 59.0 * 100.0));
+        assert_eq!(calm["gaps"]["down_freq_pct"], 0.0);
+        assert_eq!(calm["gaps"]["largest_up_pct"], 1.0);
+        assert_eq!(calm["gaps"]["filled_pct"], 100.0);
+
+        let waking = &result["symbols"][1];
+        assert_eq!(waking["range_pct"]["current"], 8.0);
+        assert!(waking["range_pct"]["percentile"].as_f64().unwrap() > 90.0);
+        assert_eq!(waking["regime"], "expanded");
+        assert!(waking["threshold_scale"].as_f64().unwrap() > 2.0);
+
+        assert_eq!(result["skipped"][0]["symbol"], "SHORT");
+        assert!(result["skipped"][0]["reason"].as_str().unwrap().contains("atr_period"));
+    }
+
+    #[test]
+    fn test_lookback_limits_history() {
+        let candles = daily(60, |i| if i < 30 { 6.0 } else { 2.0 });
+        let all = compute(json!({ "symbols": [{ "symbol": "X", "candles": candles.clone() }], "lookback": 0 })).unwrap();
+        let recent = compute(json!({ "symbols": [{ "symbol": "X", "candles": candles.clone() }], "lookback": 20 })).unwrap();
+        assert_eq!(all["symbols"][0]["history"], 59);
+        assert_eq!(recent["symbols"][0]["history"], 20);
+        assert_eq!(recent["symbols"][0]["range_pct"]["p90"], 2.0);
+        assert!(all["symbols"][0]["range_pct"]["p90"].as_f64().unwrap() > 5.0);
+        assert!(compute(json!({ "symbols": [{ "symbol": "X", "candles": candles }], "lookback": 1 })).is_err());
+    }
+}
@@ -0,0 +1,344 @@
+//! Realized volatility estimators and the implied-vs-realized premium.
+//!
+//! Candles are collapsed to daily OHLC (by calendar date, or in
+//! `bars_per_day` chunks when timestamps don't parse). For each window of
+//! the last N sessions four annualized estimators are reported:
+//! close-to-close, Parkinson (high–low), Garman-Klass (OHLC) and
+//! Yang-Zhang (adds overnight gaps). With `implied_vol` (one IV for every
+//! window) or `implied` (a term structure, interpolated at each window's
+//! calendar-day tenor) each window also gets the volatility risk premium:
+//! IV minus the `vrp_estimator` reading, IV² − RV², and IV / RV.
+//!
+//! `iv_history: [{date, iv}]` adds the ex-post premium: each IV against the
+//! close-to-close vol actually realized over the next `horizon` sessions.
+
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::calendar::{Calendar, CalendarSpec};
+use crate::risk::{range_volatility, Ohlc, RangeVolatility};
+use crate::utils::{parse_timestamp, round2, round4, Candle};
+
+#[derive(Deserialize)]
+struct RealizedVolConfig {
+    candles: Vec<Candle>,
+    #[serde(default = "default_windows")]
+    windows: Vec<usize>,
+    #[serde(default)]
+    bars_per_day: Option<f64>,
+    #[serde(default)]
+    calendar: Option<CalendarSpec>,
+    #[serde(default)]
+    implied_vol: Option<f64>,
+    #[serde(default)]
+    implied: Vec<TenorIv>,
+    /// "close_to_close", "parkinson", "garman_klass" or "yang_zhang".
+    #[serde(default = "default_estimator")]
+    vrp_estimator: String,
+    #[serde(default)]
+    iv_history: Vec<DatedIv>,
+    #[serde(default = "default_horizon")]
+    horizon: usize,
+}
+
+fn default_windows() -> Vec<usize> { vec![10, 20, 30, 60] }
+fn default_estimator() -> String { "yang_zhang".to_string() }
+fn default_horizon() -> usize { 20 }
+
+#[derive(Deserialize)]
+struct TenorIv {
+    /// Calendar days to expiry.
+    days: f64,
+    iv: f64,
+}
+
+#[derive(Deserialize)]
+struct DatedIv {
+    #[serde(alias = "timestamp")]
+    date: String,
+    iv: f64,
+}
+
+#[derive(Serialize)]
+struct Premium {
+    implied_vol: f64,
+    realized_vol: f64,
+    /// IV − RV, vol points.
+    spread: f64,
+    /// IV² − RV².
+    variance_premium: f64,
+    ratio: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct WindowResult {
+    window: usize,
+    #[serde(flatten)]
+    realized: RangeVolatility,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    premium: Option<Premium>,
+}
+
+#[derive(Serialize)]
+struct ExPostPoint {
+    date: String,
+    implied_vol: f64,
+    realized_vol: f64,
+    spread: f64,
+}
+
+#[derive(Serialize)]
+struct ExPost {
+    horizon: usize,
+    observations: usize,
+    mean_implied: f64,
+    mean_realized: f64,
+    mean_spread: f64,
+    /// Share of observations where IV exceeded the vol that followed.
+    implied_above_pct: f64,
+    series: Vec<ExPostPoint>,
+}
+
+#[derive(Serialize)]
+struct RealizedVolResult {
+    sessions: usize,
+    trading_days_per_year: f64,
+    vrp_estimator: String,
+    windows: Vec<WindowResult>,
+    /// Windows longer than the history.
+    skipped_windows: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ex_post: Option<ExPost>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: RealizedVolConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid realized_vol config: {}", e))?;
+    let pick: fn(&RangeVolatility) -> f64 = match config.vrp_estimator.as_str() {
+        "close_to_close" => |e| e.close_to_close,
+        "parkinson" => |e| e.parkinson,
+        "garman_klass" => |e| e.garman_klass,
+        "yang_zhang" => |e| e.yang_zhang,
+        other => return Err(format!("Unknown vrp_estimator: {}", other)),
+    };
+    if config.windows.iter().any(|w| *w < 2) {
+        return Err("windows must be at least 2 sessions".to_string());
+    }
+    let (dates, bars) = daily_bars(&config.candles, config.bars_per_day.unwrap_or(1.0));
+    if bars.iter().any(|b| !(b.open > 0.0 && b.high > 0.0 && b.low > 0.0 && b.close > 0.0)) {
+        return Err("candle prices must be positive".to_string());
+    }
+    if bars.len() < 3 {
+        return Err("realized_vol needs at least 3 sessions".to_string());
+    }
+    let calendar = Calendar::from_spec(config.calendar.as_ref())?;
+    let trading_days = match (dates.first().copied().flatten(), dates.last().copied().flatten()) {
+        (Some(a), Some(b)) => calendar.trading_days_per_year(a, b),
+        _ => crate::calendar::DEFAULT_TRADING_DAYS,
+    };
+
+    let mut windows = Vec::new();
+    let mut skipped_windows = Vec::new();
+    for &window in &config.windows {
+        // Close-to-close and Yang-Zhang need the close before the window.
+        if window + 1 > bars.len() {
+            skipped_windows.push(window);
+            continue;
+        }
+        let realized = range_volatility(&bars[bars.len() - window - 1..], trading_days)
+            .ok_or("windows must be at least 2 sessions")?;
+        let tenor_days = window as f64 * 365.0 / trading_days;
+        let implied = config.implied_vol.or_else(|| interpolate_iv(&config.implied, tenor_days));
+        let premium = implied.map(|iv| {
+            let rv = pick(&realized);
+            Premium {
+                implied_vol: round4(iv),
+                realized_vol: round4(rv),
+                spread: round4(iv - rv),
+                variance_premium: round4(iv * iv - rv * rv),
+                ratio: (rv > 0.0).then(|| round4(iv / rv)),
+            }
+        });
+        windows.push(WindowResult { window, realized: realized.map(round4), premium });
+    }
+    if windows.is_empty() {
+        return Err(format!("No window fits the {} sessions given", bars.len()));
+    }
+
+    let ex_post = (!config.iv_history.is_empty())
+        .then(|| ex_post(&config.iv_history, &dates, &bars, config.horizon, trading_days))
+        .transpose()?;
+
+    let result = RealizedVolResult {
+        sessions: bars.len(),
+        trading_days_per_year: round2(trading_days),
+        vrp_estimator: config.vrp_estimator,
+        windows,
+        skipped_windows,
+        ex_post,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// IV at `days`, linear between tenors and flat beyond them.
+fn interpolate_iv(term: &[TenorIv], days: f64) -> Option<f64> {
+    let mut points: Vec<(f64, f64)> = term.iter().filter(|t| t.iv > 0.0).map(|t| (t.days, t.iv)).collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let (first, last) = (*points.first()?, *points.last()?);
+    if days <= first.0 {
+        return Some(first.1);
+    }
+    if days >= last.0 {
+        return Some(last.1);
+    }
+    points.windows(2)
+        .find(|w| days <= w[1].0)
+        .map(|w| w[0].1 + (w[1].1 - w[0].1) * (days - w[0].0) / (w[1].0 - w[0].0))
+}
+
+/// Daily OHLC and each session's date (None when timestamps don't parse).
+pub(crate) fn daily_bars(candles: &[Candle], bars_per_day: f64) -> (Vec<Option<NaiveDate>>, Vec<Ohlc>) {
+    let merge = |a: &mut Ohlc, c: &Candle| {
+        a.high = a.high.max(c.high);
+        a.low = a.low.min(c.low);
+        a.close = c.close;
+    };
+    let dates: Option<Vec<NaiveDate>> = candles.iter().map(|c| parse_timestamp(&c.timestamp).map(|t| t.date())).collect();
+    match dates {
+        Some(dates) => {
+            let (mut days, mut bars): (Vec<Option<NaiveDate>>, Vec<Ohlc>) = (Vec::new(), Vec::new());
+            for (c, d) in candles.iter().zip(dates) {
+                match (days.last(), bars.last_mut()) {
+                    (Some(Some(last)), Some(b)) if *last == d => merge(b, c),
+                    _ => {
+                        days.push(Some(d));
+                        bars.push(Ohlc::from(c));
+                    }
+                }
+            }
+            (days, bars)
+        }
+        None => {
+            let chunk = (bars_per_day.round() as usize).max(1);
+            let bars: Vec<Ohlc> = candles.chunks(chunk).map(|cs| {
+                let mut b = Ohlc::from(&cs[0]);
+                cs[1..].iter().for_each(|c| merge(&mut b, c));
+                b
+            }).collect();
+            (vec![None; bars.len()], bars)
+        }
+    }
+}
+
+fn ex_post(
+    history: &[DatedIv],
+    dates: &[Option<NaiveDate>],
+    bars: &[Ohlc],
+    horizon: usize,
+    trading_days: f64,
+) -> Result<ExPost, String> {
+    if horizon < 2 {
+        return Err("horizon must be at least 2 sessions".to_string());
+    }
+    let index: HashMap<NaiveDate, usize> = dates.iter().enumerate().filter_map(|(i, d)| d.map(|d| (d, i))).collect();
+    let mut series = Vec::new();
+    for h in history {
+        let date = parse_timestamp(&h.date).ok_or_else(|| format!("iv_history: invalid date '{}'", h.date))?.date();
+        let Some(&i) = index.get(&date) else { continue };
+        if i + horizon >= bars.len() || h.iv <= 0.0 {
+            continue;
+        }
+        let Some(realized) = range_volatility(&bars[i..=i + horizon], trading_days) else { continue };
+        let realized = realized.close_to_close;
+        series.push(ExPostPoint {
+            date: h.date.clone(),
+            implied_vol: round4(h.iv),
+            realized_vol: round4(realized),
+            spread: round4(h.iv - realized),
+        });
+    }
+    let n = series.len().max(1) as f64;
+    let mean = |f: fn(&ExPostPoint) -> f64| round4(series.iter().map(f).sum::<f64>() / n);
+    Ok(ExPost {
+        horizon,
+        observations: series.len(),
+        mean_implied: mean(|p| p.implied_vol),
+        mean_realized: mean(|p| p.realized_vol),
+        mean_spread: mean(|p| p.spread),
+        implied_above_pct: round2(series.iter().filter(|p| p.spread > 0.0).count() as f64 / n * 100.0),
+        series,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::Xorshift64;
+
+    /// GBM daily bars at `vol` annualized, with intraday paths for the range.
+    fn candles(n: usize, vol: f64) -> Vec<Value> {
+        let mut rng = Xorshift64::new(11);
+        let step = vol / (252.0f64).sqrt() / 2.0;
+        let start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut price = 100.0;
+        (0..n).map(|i| {
+            // Four quarter-day moves: open, two intraday, close.
+            let open = price * (step * rng.next_normal(0.0, 1.0)).exp();
+            let (mut high, mut low, mut p) = (open, open, open);
+            for _ in 0..3 {
+                p *= (step * rng.next_normal(0.0, 1.0)).exp();
+                high = high.max(p);
+                low = low.min(p);
+            }
+            price = p;
+            json!({ "timestamp": (start + chrono::Duration::days(i as i64)).to_string(),
+                "open": open, "high": high, "low": low, "close": p, "volume": 1000 })
+        }).collect()
+    }
+
+    #[test]
+    fn test_estimators_track_true_vol() {
+        let result = compute(json!({ "candles": candles(400, 0.2), "windows": [60, 250, 1000], "implied_vol": 0.25 })).unwrap();
+        assert_eq!(result["skipped_windows"], json!([1000]));
+        let w = &result["windows"][1];
+        assert_eq!(w["window"], 250);
+        let cc = w["close_to_close"].as_f64().unwrap();
+        assert!((cc - 0.2).abs() < 0.03, "close-to-close {}", cc);
+        // Range estimators only see the intraday part of the move, and three
+        // sampled steps understate the true range.
+        for key in ["parkinson", "garman_klass"] {
+            let v = w[key].as_f64().unwrap();
+            assert!(v > 0.05 && v < cc, "{} {}", key, v);
+        }
+        let yz = w["yang_zhang"].as_f64().unwrap();
+        // Yang-Zhang adds the overnight gaps the range estimators miss.
+        assert!(yz > w["garman_klass"].as_f64().unwrap() && yz < cc, "yang_zhang {} vs {}", yz, cc);
+        let premium = &w["premium"];
+        assert_eq!(premium["realized_vol"], w["yang_zhang"]);
+        assert!(premium["spread"].as_f64().unwrap() > 0.0);
+        assert!(compute(json!({ "candles": candles(30, 0.2), "vrp_estimator": "atr" })).is_err());
+    }
+
+    #[test]
+    fn test_term_structure_and_ex_post() {
+        let bars = candles(120, 0.2);
+        let iv_history: Vec<Value> = bars.iter().step_by(10)
+            .map(|c| json!({ "date": c["timestamp"], "iv": 0.3 }))
+            .collect();
+        let result = compute(json!({
+            "candles": bars, "windows": [10, 60],
+            "implied": [{ "days": 14, "iv": 0.15 }, { "days": 90, "iv": 0.25 }],
+            "iv_history": iv_history, "horizon": 20,
+        })).unwrap();
+        // 10 sessions ≈ 14.5 calendar days, just past the first tenor.
+        let short = result["windows"][0]["premium"]["implied_vol"].as_f64().unwrap();
+        assert!(short > 0.15 && short < 0.16);
+        let ex = &result["ex_post"];
+        // Dates 0, 10, …, 90 have 20 sessions after them.
+        assert_eq!(ex["observations"], 10);
+        assert!(ex["mean_spread"].as_f64().unwrap() > 0.0);
+        assert!(ex["implied_above_pct"].as_f64().unwrap() > 50.0);
+    }
+}
@@ -0,0 +1,305 @@
+//! Trade list to bring holdings back to target weights.
+//!
+//! NAV is cash plus every position at `price`. A symbol trades only when
+//! its weight has drifted more than `drift_band_pct` points from target
+//! (`band_mode = "relative"` reads the band as % of the target weight);
+//! it is then traded back to target, or only to the band edge with
+//! `trade_to = "band"`. Quantities round toward zero to `lot_size`, trades
+//! under `min_trade_value` are dropped, sells are sized first and buys are
+//! scaled down if cash after costs would go negative. Costs use the
+//! backtest `transaction_costs` model.
+
+use std::collections::{BTreeMap, BTreeSet};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::{build_costs, CostConfig};
+use crate::utils::{round2, round4};
+
+#[derive(Deserialize)]
+struct RebalanceConfig {
+    #[serde(default)]
+    positions: Vec<Holding>,
+    #[serde(default)]
+    cash: f64,
+    /// Symbol → weight; fractions or percentages (normalised if they sum past 1).
+    target_weights: BTreeMap<String, f64>,
+    /// Prices for target symbols not currently held.
+    #[serde(default)]
+    prices: BTreeMap<String, f64>,
+    #[serde(default)]
+    lot_sizes: BTreeMap<String, f64>,
+    #[serde(default = "default_band")]
+    drift_band_pct: f64,
+    #[serde(default = "default_band_mode")]
+    band_mode: String,
+    #[serde(default = "default_trade_to")]
+    trade_to: String,
+    #[serde(default)]
+    min_trade_value: f64,
+    #[serde(default)]
+    transaction_costs: Option<CostConfig>,
+}
+
+fn default_band() -> f64 { 5.0 }
+fn default_band_mode() -> String { "absolute".to_string() }
+fn default_trade_to() -> String { "target".to_string() }
+
+#[derive(Deserialize)]
+struct Holding {
+    symbol: String,
+    qty: f64,
+    price: f64,
+    #[serde(default)]
+    lot_size: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Trade {
+    symbol: String,
+    side: &'static str,
+    qty: f64,
+    price: f64,
+    value: f64,
+    est_cost: f64,
+    current_weight: f64,
+    target_weight: f64,
+    post_weight: f64,
+}
+
+#[derive(Serialize)]
+struct Row {
+    symbol: String,
+    current_weight: f64,
+    target_weight: f64,
+    drift: f64,
+    breached: bool,
+}
+
+#[derive(Serialize)]
+struct Skipped {
+    symbol: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct RebalanceResult {
+    nav: f64,
+    trades: Vec<Trade>,
+    drift: Vec<Row>,
+    skipped: Vec<Skipped>,
+    turnover: f64,
+    turnover_pct: f64,
+    total_cost: f64,
+    cash_before: f64,
+    cash_after: f64,
+    max_drift_before: f64,
+    max_drift_after: f64,
+    /// Buys were scaled by this factor to stay within cash.
+    buy_scale: f64,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: RebalanceConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid rebalance config: {}", e))?;
+    let relative = match config.band_mode.as_str() {
+        "absolute" => false,
+        "relative" => true,
+        other => return Err(format!("Unknown band_mode '{}' (absolute, relative)", other)),
+    };
+    let to_band = match config.trade_to.as_str() {
+        "target" => false,
+        "band" => true,
+        other => return Err(format!("Unknown trade_to '{}' (target, band)", other)),
+    };
+    let costs = build_costs(&config.transaction_costs);
+
+    let mut qty: BTreeMap<String, f64> = BTreeMap::new();
+    let mut price: BTreeMap<String, f64> = config.prices.clone();
+    let mut lots: BTreeMap<String, f64> = config.lot_sizes.clone();
+    for h in &config.positions {
+        *qty.entry(h.symbol.clone()).or_default() += h.qty;
+        price.insert(h.symbol.clone(), h.price);
+        if let Some(l) = h.lot_size {
+            lots.insert(h.symbol.clone(), l);
+        }
+    }
+    let weight_sum: f64 = config.target_weights.values().sum();
+    if config.target_weights.values().any(|w| *w < 0.0) {
+        return Err("target_weights must be non-negative".to_string());
+    }
+    // Percent inputs (summing past 1) are scaled to fractions.
+    let scale = if weight_sum > 1.0 + 1e-9 { if weight_sum <= 100.0 + 1e-6 { 0.01 } else { 1.0 / weight_sum } } else { 1.0 };
+    let target: BTreeMap<String, f64> = config.target_weights.iter().map(|(k, w)| (k.clone(), w * scale)).collect();
+
+    let nav = config.cash + qty.iter().map(|(s, q)| q * price.get(s).copied().unwrap_or(0.0)).sum::<f64>();
+    if nav <= 0.0 {
+        return Err("portfolio NAV must be positive".to_string());
+    }
+    let symbols: BTreeSet<String> = qty.keys().chain(target.keys()).cloned().collect();
+    let weight = |s: &str, q: f64| q * price.get(s).copied().unwrap_or(0.0) / nav;
+
+    let mut rows = Vec::new();
+    let mut skipped = Vec::new();
+    // (symbol, signed qty) before cash scaling.
+    let mut wanted: Vec<(String, f64)> = Vec::new();
+    for s in &symbols {
+        let held = qty.get(s).copied().unwrap_or(0.0);
+        let cur = weight(s, held);
+        let tgt = target.get(s).copied().unwrap_or(0.0);
+        let band = if relative { tgt * config.drift_band_pct / 100.0 } else { config.drift_band_pct / 100.0 };
+        let drift = cur - tgt;
+        let breached = drift.abs() > band + 1e-12;
+        rows.push(Row { symbol: s.clone(), current_weight: round4(cur), target_weight: round4(tgt), drift: round4(drift), breached });
+        if !breached {
+            continue;
+        }
+        let Some(&px) = price.get(s).filter(|p| **p > 0.0) else {
+            skipped.push(Skipped { symbol: s.clone(), reason: "no price".to_string() });
+            continue;
+        };
+        let goal = if to_band { tgt + band * drift.signum() } else { tgt };
+        let lot = lots.get(s).copied().filter(|l| *l > 0.0).unwrap_or(1.0);
+        let raw = (goal - cur) * nav / px;
+        let q = (raw.abs() / lot + 1e-9).floor() * lot * raw.signum();
+        if q == 0.0 {
+            skipped.push(Skipped { symbol: s.clone(), reason: "less than one lot".to_string() });
+        } else if q.abs() * px < config.min_trade_value {
+            skipped.push(Skipped { symbol: s.clone(), reason: format!("below min_trade_value ({:.2})", q.abs() * px) });
+        } else {
+            wanted.push((s.clone(), q));
+        }
+    }
+
+    // Sells fund buys; scale buys down if they would overdraw cash.
+    let mut cash = config.cash;
+    let cost_of = |s: &str, q: f64| costs.total_cost(q.abs() * price[s], q < 0.0) + q.abs() * price[s] * costs.slippage_bps / 10_000.0;
+    for (s, q) in wanted.iter().filter(|(_, q)| *q < 0.0) {
+        cash += q.abs() * price[s] - cost_of(s, *q);
+    }
+    let buy_need: f64 = wanted.iter().filter(|(_, q)| *q > 0.0).map(|(s, q)| q * price[s] + cost_of(s, *q)).sum();
+    let buy_scale = if buy_need > cash && buy_need > 0.0 { (cash / buy_need).max(0.0) } else { 1.0 };
+
+    let mut trades = Vec::new();
+    let (mut turnover, mut total_cost) = (0.0, 0.0);
+    let mut post_qty = qty.clone();
+    cash = config.cash;
+    wanted.sort_by(|a, b| a.1.total_cmp(&b.1));
+    for (s, q) in wanted {
+        let lot = lots.get(&s).copied().filter(|l| *l > 0.0).unwrap_or(1.0);
+        let mut q = if q > 0.0 { (q * buy_scale / lot).floor() * lot } else { q };
+        // Fixed fees make costs non-linear; shed lots until the buy is funded.
+        while q > 0.0 && q * price[&s] + cost_of(&s, q) > cash + 1e-9 {
+            q -= lot;
+        }
+        if q <= 0.0 && q > -lot {
+            skipped.push(Skipped { symbol: s, reason: "buy scaled to zero by available cash".to_string() });
+            continue;
+        }
+        let px = price[&s];
+        let value = q.abs() * px;
+        let cost = cost_of(&s, q);
+        cash += -q * px - cost;
+        turnover += value;
+        total_cost += cost;
+        *post_qty.entry(s.clone()).or_default() += q;
+        let cur = weight(&s, qty.get(&s).copied().unwrap_or(0.0));
+        trades.push(Trade {
+            side: if q > 0.0 { "BUY" } else { "SELL" },
+            qty: q.abs(),
+            price: px,
+            value: round2(value),
+            est_cost: round2(cost),
+            current_weight: round4(cur),
+            target_weight: round4(target.get(&s).copied().unwrap_or(0.0)),
+            post_weight: round4(weight(&s, post_qty[&s])),
+            symbol: s,
+        });
+    }
+
+    let max_drift = |q: &BTreeMap<String, f64>| symbols.iter()
+        .map(|s| (weight(s, q.get(s).copied().unwrap_or(0.0)) - target.get(s).copied().unwrap_or(0.0)).abs())
+        .fold(0.0, f64::max);
+    let result = RebalanceResult {
+        nav: round2(nav),
+        max_drift_before: round4(max_drift(&qty)),
+        max_drift_after: round4(max_drift(&post_qty)),
+        trades,
+        drift: rows,
+        skipped,
+        turnover: round2(turnover),
+        turnover_pct: round2(turnover / nav * 100.0),
+        total_cost: round2(total_cost),
+        cash_before: round2(config.cash),
+        cash_after: round2(cash),
+        buy_scale: round4(buy_scale),
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn no_costs() -> Value {
+        json!({ "commission": 0.0, "slippage_bps": 0.0, "stt_pct": 0.0 })
+    }
+
+    #[test]
+    fn test_bands_lots_and_sell_first() {
+        // NAV 100k: A 60%, B 34.9%, cash 5.1%; target 50/50 with a 5-point band.
+        let result = compute(json!({
+            "positions": [{ "symbol": "A", "qty": 600, "price": 100.0 }, { "symbol": "B", "qty": 698, "price": 50.0, "lot_size": 25 }],
+            "cash": 5100.0,
+            "target_weights": { "A": 50, "B": 50 },
+            "transaction_costs": no_costs()
+        })).unwrap();
+        let trades = result["trades"].as_array().unwrap();
+        assert_eq!(trades[0]["symbol"], "A");
+        assert_eq!(trades[0]["side"], "SELL");
+        assert_eq!(trades[0]["qty"], 100.0);
+        // B needs 302 shares, rounded down to lots of 25; exchange fees still apply.
+        assert_eq!(trades[1]["qty"], 300.0);
+        assert!(result["max_drift_after"].as_f64().unwrap() <= 0.001);
+        let cash_after = result["cash_after"].as_f64().unwrap();
+        assert!(cash_after > 0.0 && cash_after < 100.0);
+
+        // Inside the band: nothing to do.
+        let calm = compute(json!({
+            "positions": [{ "symbol": "A", "qty": 520, "price": 100.0 }, { "symbol": "B", "qty": 960, "price": 50.0 }],
+            "target_weights": { "A": 0.5, "B": 0.5 }
+        })).unwrap();
+        assert_eq!(calm["trades"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_band_edge_min_trade_and_cash_scaling() {
+        let result = compute(json!({
+            "positions": [{ "symbol": "A", "qty": 700, "price": 100.0 }, { "symbol": "B", "qty": 598, "price": 50.0 }],
+            "cash": 100.0,
+            "target_weights": { "A": 0.5, "B": 0.5 },
+            "trade_to": "band",
+            "transaction_costs": no_costs()
+        })).unwrap();
+        // A at 70% trades down to 55%, B from 30% up to 45%.
+        assert_eq!(result["trades"][0]["post_weight"], 0.55);
+        // B's last share can't be funded once fees are paid.
+        assert!((result["trades"][1]["post_weight"].as_f64().unwrap() - 0.45).abs() < 0.001);
+
+        // NAV 11k; A and B are just outside a 0.5-point band but their trades are tiny.
+        let result = compute(json!({
+            "positions": [{ "symbol": "A", "qty": 100, "price": 100.0 }, { "symbol": "B", "qty": 10, "price": 10.0 }],
+            "cash": 900.0,
+            "target_weights": { "A": 0.9, "C": 0.1 },
+            "prices": { "C": 10.0 },
+            "drift_band_pct": 0.5,
+            "min_trade_value": 500.0
+        })).unwrap();
+        assert_eq!(result["skipped"][0]["symbol"], "A");
+        assert_eq!(result["skipped"][1]["symbol"], "B");
+        // C wants 110 units (1,100) with 900 of cash and fixed fees.
+        assert!(result["buy_scale"].as_f64().unwrap() < 1.0);
+        assert!(result["trades"][0]["qty"].as_f64().unwrap() < 90.0);
+        assert!(result["cash_after"].as_f64().unwrap() >= 0.0);
+    }
+}
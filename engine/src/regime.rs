@@ -0,0 +1,413 @@
+//! Market regime labels per bar: low-vol trend, high-vol chop or crash.
+//!
+//! `model = "hmm"` (default) fits a three-state Gaussian hidden Markov model
+//! to log returns with Baum-Welch, orders the states by volatility and
+//! reports forward-filtered state probabilities, so a bar's label only uses
+//! bars up to it (given the fitted parameters). `model = "threshold"` is a
+//! rule model: a drawdown of `crash_pct` from the `crash_window` high is a
+//! crash, otherwise ATR% at or above the `high_vol_pct` percentile of its
+//! trailing `vol_lookback` is high-vol chop. Both report Kaufman efficiency
+//! over `lookback` so callers can tell trend from chop within a regime.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{calc_atr_series, round4, sanitize_candles, Candle};
+
+pub(crate) const LABELS: [&str; 3] = ["low_vol_trend", "high_vol_chop", "crash"];
+
+#[derive(Deserialize)]
+struct RegimeConfig {
+    candles: Vec<Candle>,
+    #[serde(flatten)]
+    params: RegimeParams,
+    /// Include the per-bar series (on by default).
+    #[serde(default = "default_true")]
+    include_bars: bool,
+}
+
+fn default_true() -> bool { true }
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct RegimeParams {
+    pub model: String,
+    pub lookback: usize,
+    pub atr_period: usize,
+    pub vol_lookback: usize,
+    pub high_vol_pct: f64,
+    pub crash_window: usize,
+    pub crash_pct: f64,
+    pub max_iter: usize,
+    pub bars_per_year: f64,
+}
+
+impl Default for RegimeParams {
+    fn default() -> Self {
+        Self {
+            model: "hmm".to_string(),
+            lookback: 20,
+            atr_period: 14,
+            vol_lookback: 100,
+            high_vol_pct: 0.7,
+            crash_window: 10,
+            crash_pct: 8.0,
+            max_iter: 100,
+            bars_per_year: 252.0,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub(crate) struct RegimeBar {
+    pub timestamp: String,
+    /// None during warm-up.
+    pub regime: Option<&'static str>,
+    /// Probability of each of `LABELS`, in order.
+    pub probabilities: [f64; 3],
+    pub efficiency_ratio: f64,
+    pub atr_pct: f64,
+}
+
+#[derive(Serialize)]
+struct StateParams {
+    regime: &'static str,
+    mean_return: f64,
+    volatility: f64,
+    annualized_return: f64,
+    annualized_volatility: f64,
+}
+
+#[derive(Serialize)]
+struct RegimeStat {
+    regime: &'static str,
+    bars: usize,
+    frequency: f64,
+    avg_duration: f64,
+    annualized_return: f64,
+    annualized_volatility: f64,
+}
+
+#[derive(Serialize)]
+struct RegimeResult {
+    model: String,
+    current_regime: Option<&'static str>,
+    current_probabilities: [f64; 3],
+    regime_stats: Vec<RegimeStat>,
+    /// Row = from, column = to, in `LABELS` order.
+    transition_matrix: Vec<Vec<f64>>,
+    state_params: Option<Vec<StateParams>>,
+    log_likelihood: Option<f64>,
+    iterations: Option<usize>,
+    bars: Option<Vec<RegimeBar>>,
+}
+
+/// Per-bar labels plus, for the HMM, its parameters (ordered by volatility),
+/// log-likelihood and Baum-Welch iterations.
+pub(crate) struct Fit {
+    pub bars: Vec<RegimeBar>,
+    hmm: Option<(Hmm, f64, usize)>,
+}
+
+#[derive(Clone)]
+struct Hmm {
+    pi: [f64; 3],
+    a: [[f64; 3]; 3],
+    mu: [f64; 3],
+    sigma: [f64; 3],
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: RegimeConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid regime config: {}", e))?;
+    let mut candles = config.candles;
+    sanitize_candles(&mut candles);
+    let p = &config.params;
+    let fit = classify(&candles, p)?;
+    let bars = &fit.bars;
+
+    let labels: Vec<usize> = bars.iter()
+        .filter_map(|b| b.regime.and_then(|r| LABELS.iter().position(|l| *l == r)))
+        .collect();
+    let returns: Vec<Option<f64>> = (0..candles.len())
+        .map(|i| (i > 0 && candles[i - 1].close > 0.0 && candles[i].close > 0.0).then(|| (candles[i].close / candles[i - 1].close).ln()))
+        .collect();
+
+    let mut transitions = [[0.0; 3]; 3];
+    for w in labels.windows(2) {
+        transitions[w[0]][w[1]] += 1.0;
+    }
+    let transition_matrix = transitions.iter()
+        .map(|row| {
+            let total: f64 = row.iter().sum();
+            row.iter().map(|c| if total > 0.0 { round4(c / total) } else { 0.0 }).collect()
+        })
+        .collect();
+
+    let regime_stats = (0..3).map(|k| {
+        let rets: Vec<f64> = bars.iter().zip(&returns)
+            .filter(|(b, _)| b.regime == Some(LABELS[k]))
+            .filter_map(|(_, r)| *r)
+            .collect();
+        let runs = labels.iter().enumerate().filter(|(i, l)| **l == k && (*i == 0 || labels[i - 1] != k)).count();
+        let (mean, sd) = mean_std(&rets);
+        RegimeStat {
+            regime: LABELS[k],
+            bars: rets.len(),
+            frequency: round4(rets.len() as f64 / labels.len().max(1) as f64),
+            avg_duration: if runs > 0 { round4(rets.len() as f64 / runs as f64) } else { 0.0 },
+            annualized_return: round4(mean * p.bars_per_year),
+            annualized_volatility: round4(sd * p.bars_per_year.sqrt()),
+        }
+    }).collect();
+
+    let last = bars.iter().rev().find(|b| b.regime.is_some());
+    let result = RegimeResult {
+        model: p.model.clone(),
+        current_regime: last.and_then(|b| b.regime),
+        current_probabilities: last.map(|b| b.probabilities).unwrap_or_default(),
+        regime_stats,
+        transition_matrix,
+        state_params: fit.hmm.as_ref().map(|(h, _, _)| (0..3).map(|k| StateParams {
+            regime: LABELS[k],
+            mean_return: round4(h.mu[k]),
+            volatility: round4(h.sigma[k]),
+            annualized_return: round4(h.mu[k] * p.bars_per_year),
+            annualized_volatility: round4(h.sigma[k] * p.bars_per_year.sqrt()),
+        }).collect()),
+        log_likelihood: fit.hmm.as_ref().map(|(_, ll, _)| round4(*ll)),
+        iterations: fit.hmm.as_ref().map(|(_, _, it)| *it),
+        bars: config.include_bars.then_some(fit.bars),
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Label every candle. Shared with commands that condition on regime.
+pub(crate) fn classify(candles: &[Candle], p: &RegimeParams) -> Result<Fit, String> {
+    let n = candles.len();
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    let highs: Vec<f64> = candles.iter().map(|c| c.high).collect();
+    let lows: Vec<f64> = candles.iter().map(|c| c.low).collect();
+    let atr_period = p.atr_period.max(1);
+    let atr = if n > 0 { calc_atr_series(&highs, &lows, &closes, atr_period) } else { Vec::new() };
+    let lookback = p.lookback.max(1);
+
+    let mut bars: Vec<RegimeBar> = (0..n).map(|i| {
+        let efficiency_ratio = if i >= lookback {
+            let path: f64 = (i + 1 - lookback..=i).map(|j| (closes[j] - closes[j - 1]).abs()).sum();
+            if path > 0.0 { round4((closes[i] - closes[i - lookback]).abs() / path) } else { 0.0 }
+        } else { 0.0 };
+        let atr_pct = if i + 1 >= atr_period && closes[i] > 0.0 { atr[i] / closes[i] * 100.0 } else { 0.0 };
+        RegimeBar { timestamp: candles[i].timestamp.clone(), regime: None, probabilities: [0.0; 3], efficiency_ratio, atr_pct: round4(atr_pct) }
+    }).collect();
+
+    match p.model.as_str() {
+        "hmm" => {
+            if n < 30 {
+                return Err("Need at least 30 candles for the hmm regime model".to_string());
+            }
+            let returns: Vec<f64> = (1..n)
+                .map(|i| if closes[i] > 0.0 && closes[i - 1] > 0.0 { (closes[i] / closes[i - 1]).ln() } else { 0.0 })
+                .collect();
+            let (hmm, ll, iterations) = fit_hmm(&returns, p.max_iter);
+            let (alphas, _) = forward(&hmm, &returns);
+            for (t, alpha) in alphas.iter().enumerate() {
+                let bar = &mut bars[t + 1];
+                bar.probabilities = alpha.map(round4);
+                bar.regime = Some(LABELS[argmax(alpha)]);
+            }
+            Ok(Fit { bars, hmm: Some((hmm, ll, iterations)) })
+        }
+        "threshold" => {
+            let window = p.crash_window.max(1);
+            let warmup = atr_period.max(2) - 1;
+            for i in warmup..n {
+                let high = closes[i.saturating_sub(window)..=i].iter().cloned().fold(f64::MIN, f64::max);
+                let drawdown = if high > 0.0 { (1.0 - closes[i] / high) * 100.0 } else { 0.0 };
+                let label = if drawdown >= p.crash_pct {
+                    2
+                } else {
+                    let start = i.saturating_sub(p.vol_lookback.max(1) - 1).max(warmup);
+                    let history = &bars[start..=i];
+                    let rank = history.iter().filter(|b| b.atr_pct <= bars[i].atr_pct).count() as f64 / history.len() as f64;
+                    if rank >= p.high_vol_pct && history.len() > 1 { 1 } else { 0 }
+                };
+                bars[i].regime = Some(LABELS[label]);
+                bars[i].probabilities[label] = 1.0;
+            }
+            Ok(Fit { bars, hmm: None })
+        }
+        other => Err(format!("Unknown regime model '{}' (hmm, threshold)", other)),
+    }
+}
+
+fn mean_std(xs: &[f64]) -> (f64, f64) {
+    if xs.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+    let var = if xs.len() > 1 { xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (xs.len() - 1) as f64 } else { 0.0 };
+    (mean, var.sqrt())
+}
+
+fn argmax(p: &[f64; 3]) -> usize {
+    (0..3).fold(0, |best, k| if p[k] > p[best] { k } else { best })
+}
+
+fn density(x: f64, mu: f64, sigma: f64) -> f64 {
+    let z = (x - mu) / sigma;
+    ((-0.5 * z * z).exp() / (sigma * (2.0 * std::f64::consts::PI).sqrt())).max(1e-300)
+}
+
+/// Scaled forward pass: normalized alphas (filtered probabilities) and log-likelihood.
+fn forward(h: &Hmm, x: &[f64]) -> (Vec<[f64; 3]>, f64) {
+    let mut alphas = Vec::with_capacity(x.len());
+    let mut ll = 0.0;
+    let mut prev = h.pi;
+    for (t, &xt) in x.iter().enumerate() {
+        let mut a = [0.0; 3];
+        for (j, aj) in a.iter_mut().enumerate() {
+            let prior = if t == 0 { h.pi[j] } else { (0..3).map(|i| prev[i] * h.a[i][j]).sum() };
+            *aj = prior * density(xt, h.mu[j], h.sigma[j]);
+        }
+        let c: f64 = a.iter().sum();
+        let a = a.map(|v| v / c);
+        ll += c.ln();
+        alphas.push(a);
+        prev = a;
+    }
+    (alphas, ll)
+}
+
+/// Baum-Welch from a volatility-spread start; states come back sorted by sigma.
+fn fit_hmm(x: &[f64], max_iter: usize) -> (Hmm, f64, usize) {
+    let (mean, sd) = mean_std(x);
+    let sd = sd.max(1e-6);
+    let floor = sd * 0.05;
+    let mut h = Hmm {
+        pi: [0.6, 0.3, 0.1],
+        a: [[0.95, 0.04, 0.01], [0.05, 0.9, 0.05], [0.1, 0.2, 0.7]],
+        mu: [mean, mean, mean - sd],
+        sigma: [sd * 0.6, sd * 1.4, sd * 3.0],
+    };
+    let t_len = x.len();
+    let mut ll_prev = f64::NEG_INFINITY;
+    let mut iterations = 0;
+    for _ in 0..max_iter {
+        iterations += 1;
+        let (alphas, ll) = forward(&h, x);
+        // Backward pass with the same per-step scaling.
+        let mut betas = vec![[1.0; 3]; t_len];
+        for t in (0..t_len - 1).rev() {
+            let mut b = [0.0; 3];
+            for (i, bi) in b.iter_mut().enumerate() {
+                *bi = (0..3).map(|j| h.a[i][j] * density(x[t + 1], h.mu[j], h.sigma[j]) * betas[t + 1][j]).sum();
+            }
+            let s: f64 = b.iter().sum();
+            betas[t] = b.map(|v| v / s.max(1e-300));
+        }
+        let gammas: Vec<[f64; 3]> = (0..t_len).map(|t| {
+            let g = [0, 1, 2].map(|k| alphas[t][k] * betas[t][k]);
+            let s: f64 = g.iter().sum();
+            g.map(|v| v / s.max(1e-300))
+        }).collect();
+        let mut xi = [[0.0; 3]; 3];
+        for t in 0..t_len - 1 {
+            let mut m = [[0.0; 3]; 3];
+            let mut s = 0.0;
+            for i in 0..3 {
+                for j in 0..3 {
+                    m[i][j] = alphas[t][i] * h.a[i][j] * density(x[t + 1], h.mu[j], h.sigma[j]) * betas[t + 1][j];
+                    s += m[i][j];
+                }
+            }
+            for i in 0..3 {
+                for j in 0..3 {
+                    xi[i][j] += m[i][j] / s.max(1e-300);
+                }
+            }
+        }
+        for k in 0..3 {
+            let w: f64 = gammas.iter().map(|g| g[k]).sum();
+            if w < 1e-9 {
+                continue;
+            }
+            h.mu[k] = gammas.iter().zip(x).map(|(g, v)| g[k] * v).sum::<f64>() / w;
+            let var = gammas.iter().zip(x).map(|(g, v)| g[k] * (v - h.mu[k]).powi(2)).sum::<f64>() / w;
+            h.sigma[k] = var.sqrt().max(floor);
+            let row: f64 = xi[k].iter().sum();
+            if row > 0.0 {
+                h.a[k] = xi[k].map(|v| v / row);
+            }
+        }
+        h.pi = gammas[0];
+        if (ll - ll_prev).abs() < 1e-6 * ll.abs().max(1.0) {
+            ll_prev = ll;
+            break;
+        }
+        ll_prev = ll;
+    }
+    let mut order = [0, 1, 2];
+    order.sort_by(|&i, &j| h.sigma[i].total_cmp(&h.sigma[j]));
+    let sorted = Hmm {
+        pi: order.map(|i| h.pi[i]),
+        a: order.map(|i| order.map(|j| h.a[i][j])),
+        mu: order.map(|i| h.mu[i]),
+        sigma: order.map(|i| h.sigma[i]),
+    };
+    (sorted, ll_prev, iterations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 120 calm up-trending bars, then 40 wild bars, then a 15% slide.
+    fn candles() -> Vec<Value> {
+        let mut price = 100.0;
+        let mut out = Vec::new();
+        for i in 0..180 {
+            let (ret, range) = if i < 120 {
+                (0.002 + if i % 2 == 0 { 0.001 } else { -0.001 }, 0.005)
+            } else if i < 160 {
+                (if i % 2 == 0 { 0.03 } else { -0.029 }, 0.04)
+            } else {
+                (-0.012 + if i % 2 == 0 { 0.06 } else { -0.06 }, 0.07)
+            };
+            price *= 1.0 + ret;
+            out.push(json!({ "timestamp": format!("d{}", i), "open": price, "high": price * (1.0 + range), "low": price * (1.0 - range), "close": price, "volume": 1000.0 }));
+        }
+        out
+    }
+
+    #[test]
+    fn test_hmm_orders_states_by_volatility() {
+        let result = compute(json!({ "candles": candles() })).unwrap();
+        let bars = result["bars"].as_array().unwrap();
+        assert!(bars[0]["regime"].is_null());
+        assert_eq!(bars[100]["regime"], "low_vol_trend");
+        assert_eq!(bars[140]["regime"], "high_vol_chop");
+        assert_eq!(result["current_regime"], "crash");
+        let vols: Vec<f64> = result["state_params"].as_array().unwrap().iter().map(|s| s["volatility"].as_f64().unwrap()).collect();
+        assert!(vols[0] < vols[1] && vols[1] < vols[2]);
+        let p: f64 = result["current_probabilities"].as_array().unwrap().iter().map(|v| v.as_f64().unwrap()).sum();
+        assert!((p - 1.0).abs() < 1e-3);
+        // Trending calm bars are efficient; the whipsaw is not.
+        assert!(bars[100]["efficiency_ratio"].as_f64().unwrap() > 0.5);
+        assert!(bars[140]["efficiency_ratio"].as_f64().unwrap() < 0.1);
+    }
+
+    #[test]
+    fn test_threshold_model() {
+        let result = compute(json!({ "candles": candles(), "model": "threshold", "include_bars": true })).unwrap();
+        let bars = result["bars"].as_array().unwrap();
+        assert!(bars[5]["regime"].is_null());
+        assert_eq!(bars[100]["regime"], "low_vol_trend");
+        assert_eq!(bars[125]["regime"], "high_vol_chop");
+        assert_eq!(bars[170]["regime"], "crash");
+        assert!(result["state_params"].is_null());
+        let rows = result["transition_matrix"].as_array().unwrap();
+        assert!(rows[0][0].as_f64().unwrap() > 0.9);
+        assert!(compute(json!({ "candles": candles(), "model": "kmeans" })).is_err());
+    }
+}
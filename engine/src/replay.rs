@@ -0,0 +1,234 @@
+//! Bar-by-bar replay of historical candles, for replay trainers.
+//!
+//! `replay_start` takes a `replay_id`, one `symbol` with its full `candles`
+//! and the `subscribe` options (strategies, scan, scan_options, paper,
+//! rsi_levels, max_bars). The first `warmup` bars seed a stream subscription
+//! and every `replay_step` pushes the next `bars` (default 1) through it, so
+//! each step runs exactly the live path: running indicators, the scan vote,
+//! the strategies and the paper position. A step returns each bar with its
+//! indicators and events plus the paper account after the last one; later
+//! bars are never returned. `replay_seek` restarts at bar `to` (an index or
+//! a timestamp) with a fresh paper account and `replay_stop` ends the replay
+//! with its final summary.
+//!
+//! The replay's subscription (`replay:<replay_id>`) only moves on its own
+//! steps: plain `push_candle`s for the same symbol don't reach it and it is
+//! left out of `subscriptions`.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use crate::config::EngineConfig;
+use crate::stream;
+use crate::utils::{sanitize_candles, Candle};
+
+static REPLAYS: Lazy<DashMap<String, Replay>> = Lazy::new(DashMap::new);
+
+#[derive(Deserialize)]
+struct StartInput {
+    replay_id: String,
+    symbol: String,
+    candles: Vec<Candle>,
+    #[serde(default = "default_warmup")]
+    warmup: usize,
+    /// Everything else is passed to `subscribe`.
+    #[serde(flatten)]
+    options: Map<String, Value>,
+}
+
+fn default_warmup() -> usize { 50 }
+
+#[derive(Deserialize)]
+struct StepInput {
+    replay_id: String,
+    #[serde(default = "default_bars")]
+    bars: usize,
+}
+
+fn default_bars() -> usize { 1 }
+
+#[derive(Deserialize)]
+struct SeekInput {
+    replay_id: String,
+    to: Value,
+}
+
+#[derive(Deserialize)]
+struct StopInput {
+    replay_id: String,
+}
+
+struct Replay {
+    symbol: String,
+    candles: Vec<Candle>,
+    options: Map<String, Value>,
+    /// Index of the next bar to push.
+    cursor: usize,
+}
+
+impl Replay {
+    fn subscription_id(id: &str) -> String {
+        format!("replay:{}", id)
+    }
+
+    /// (Re)create the subscription with every bar before `cursor` as warm-up.
+    fn seed(&self, id: &str, config: &EngineConfig) -> Result<(), String> {
+        let mut input = self.options.clone();
+        input.insert("subscription_id".to_string(), json!(Self::subscription_id(id)));
+        input.insert("symbols".to_string(), json!([{ "symbol": self.symbol, "candles": self.candles[..self.cursor] }]));
+        input.entry("emit_events").or_insert(json!(false));
+        stream::subscribe_replay(Value::Object(input), config).map(|_| ())
+    }
+
+    fn state(&self, id: &str) -> Value {
+        let summary = stream::summary(&Self::subscription_id(id)).unwrap_or(Value::Null);
+        json!({
+            "replay_id": id,
+            "symbol": self.symbol,
+            "cursor": self.cursor,
+            "total_bars": self.candles.len(),
+            "remaining": self.candles.len() - self.cursor,
+            "done": self.cursor == self.candles.len(),
+            "last_timestamp": self.cursor.checked_sub(1).map(|i| self.candles[i].timestamp.clone()),
+            "indicators": summary["symbols"][0]["indicators"],
+            "position": summary["symbols"][0]["position"],
+            "equity": summary["equity"],
+            "realized_pnl": summary["realized_pnl"],
+            "unrealized_pnl": summary["unrealized_pnl"],
+            "closed_trades": summary["closed_trades"],
+        })
+    }
+}
+
+pub fn start(data: Value, config: &EngineConfig) -> Result<Value, String> {
+    let input: StartInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid replay_start input: {}", e))?;
+    let mut candles = input.candles;
+    sanitize_candles(&mut candles);
+    if input.warmup >= candles.len() {
+        return Err(format!("warmup ({}) must leave bars to replay out of {}", input.warmup, candles.len()));
+    }
+    let replay = Replay { symbol: input.symbol, candles, options: input.options, cursor: input.warmup };
+    replay.seed(&input.replay_id, config)?;
+    let mut out = replay.state(&input.replay_id);
+    out["replaced"] = json!(REPLAYS.contains_key(&input.replay_id));
+    REPLAYS.insert(input.replay_id, replay);
+    Ok(out)
+}
+
+pub fn step(data: Value, config: &EngineConfig) -> Result<Value, String> {
+    let input: StepInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid replay_step input: {}", e))?;
+    let mut replay = REPLAYS.get_mut(&input.replay_id)
+        .ok_or_else(|| format!("Unknown replay_id '{}'", input.replay_id))?;
+    let subscription_id = Replay::subscription_id(&input.replay_id);
+    let end = (replay.cursor + input.bars).min(replay.candles.len());
+    let mut steps = Vec::with_capacity(end - replay.cursor);
+    while replay.cursor < end {
+        let candle = &replay.candles[replay.cursor];
+        let pushed = stream::push_candle(json!({
+            "symbol": replay.symbol, "candle": candle, "subscription_id": subscription_id,
+        }), config)?;
+        steps.push(json!({
+            "index": replay.cursor,
+            "candle": candle,
+            "indicators": pushed["indicators"],
+            "events": pushed["events"],
+        }));
+        replay.cursor += 1;
+    }
+    let mut out = replay.state(&input.replay_id);
+    out["steps"] = json!(steps);
+    Ok(out)
+}
+
+pub fn seek(data: Value, config: &EngineConfig) -> Result<Value, String> {
+    let input: SeekInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid replay_seek input: {}", e))?;
+    let mut replay = REPLAYS.get_mut(&input.replay_id)
+        .ok_or_else(|| format!("Unknown replay_id '{}'", input.replay_id))?;
+    let to = match &input.to {
+        Value::Number(n) => n.as_u64().map(|i| i as usize),
+        Value::String(ts) => replay.candles.iter().position(|c| c.timestamp == *ts),
+        _ => None,
+    }.filter(|&i| i < replay.candles.len())
+        .ok_or_else(|| format!("replay_seek: no bar {} in {} bars", input.to, replay.candles.len()))?;
+    replay.cursor = to;
+    replay.seed(&input.replay_id, config)?;
+    Ok(replay.state(&input.replay_id))
+}
+
+pub fn stop(data: Value) -> Result<Value, String> {
+    let input: StopInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid replay_stop input: {}", e))?;
+    let (id, replay) = REPLAYS.remove(&input.replay_id)
+        .ok_or_else(|| format!("Unknown replay_id '{}'", input.replay_id))?;
+    let subscription_id = Replay::subscription_id(&id);
+    let mut out = replay.state(&id);
+    out["trades"] = stream::summary(&subscription_id).map_or(json!([]), |s| s["trades"].clone());
+    stream::unsubscribe(json!({ "subscription_id": subscription_id }))?;
+    out["removed"] = json!(true);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candles(n: usize) -> Vec<Value> {
+        (0..n).map(|i| {
+            let x = i as f64;
+            // Slide, then a rally through the EMA 9/21 cross.
+            let close = if i < 60 { 150.0 - x * 0.5 } else { 120.0 + (x - 60.0) * 0.8 };
+            json!({ "timestamp": format!("2024-{:02}-{:02}", 1 + i / 28, 1 + i % 28),
+                "open": close - 0.2, "high": close + 0.5, "low": close - 0.5, "close": close, "volume": 1000.0 })
+        }).collect()
+    }
+
+    #[test]
+    fn test_replay_steps_seek_and_stop() {
+        let config = EngineConfig::default();
+        let id = format!("r-{}", std::process::id());
+        let started = start(json!({
+            "replay_id": id, "symbol": "REPLAYX", "candles": candles(100), "warmup": 40,
+            "strategies": ["ema_crossover"], "scan": false, "paper": { "qty": 10 },
+        }), &config).unwrap();
+        assert_eq!(started["cursor"], 40);
+        assert_eq!(started["remaining"], 60);
+        assert_eq!(started["last_timestamp"], "2024-02-12");
+
+        // Plain pushes for the symbol don't reach the replay.
+        assert!(stream::push_candle(json!({ "symbol": "REPLAYX", "candle": candles(41)[40] }), &config).is_err());
+        assert!(stream::list().unwrap()["subscriptions"].as_array().unwrap().iter()
+            .all(|s| s["subscription_id"] != format!("replay:{}", id).as_str()));
+
+        let one = step(json!({ "replay_id": id }), &config).unwrap();
+        assert_eq!(one["steps"].as_array().unwrap().len(), 1);
+        assert_eq!(one["steps"][0]["index"], 40);
+        assert_eq!(one["steps"][0]["candle"]["timestamp"], "2024-02-13");
+        assert!(one["steps"][0]["indicators"]["ema_9"].is_number());
+
+        // Stepping past the end stops at the last bar.
+        let rest = step(json!({ "replay_id": id, "bars": 500 }), &config).unwrap();
+        assert_eq!(rest["steps"].as_array().unwrap().len(), 59);
+        assert_eq!(rest["done"], true);
+        let events: Vec<&Value> = rest["steps"].as_array().unwrap().iter()
+            .flat_map(|s| s["events"].as_array().unwrap()).collect();
+        assert!(events.iter().any(|e| e["event"] == "entry"), "{:?}", events);
+        // The paper account followed the entry, whether or not it has closed since.
+        assert!(rest["position"].is_object() || rest["closed_trades"].as_u64().unwrap() > 0);
+
+        // Seeking back restarts flat at that bar.
+        let back = seek(json!({ "replay_id": id, "to": "2024-02-13" }), &config).unwrap();
+        assert_eq!(back["cursor"], 40);
+        assert!(back["position"].is_null());
+        assert_eq!(back["closed_trades"], 0);
+        assert!(seek(json!({ "replay_id": id, "to": 100 }), &config).is_err());
+
+        let stopped = stop(json!({ "replay_id": id })).unwrap();
+        assert_eq!(stopped["removed"], true);
+        assert!(step(json!({ "replay_id": id }), &config).is_err());
+        assert!(start(json!({ "replay_id": id, "symbol": "X", "candles": candles(10), "warmup": 10 }), &config).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Price series to returns, computed one way for every consumer.
+//!
+//! Input is `candles` (dated) or bare `prices`. `horizon` is "bar", "daily",
+//! "weekly", "monthly" or a bar count; calendar horizons take the last close
+//! of each day/ISO week/month and need timestamps. `kind` is "simple" or
+//! "log". Cash `dividends` `{ex_date, amount}` are added back on the period
+//! that contains the ex-date, giving total returns. The `returns` array feeds
+//! `risk` as is.
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{Candle, parse_timestamp, round4};
+
+#[derive(Deserialize)]
+struct ReturnsConfig {
+    #[serde(default)]
+    candles: Option<Vec<Candle>>,
+    #[serde(default)]
+    prices: Option<Vec<f64>>,
+    #[serde(default = "default_kind")]
+    kind: String,
+    #[serde(default = "default_horizon")]
+    horizon: Value,
+    #[serde(default)]
+    dividends: Vec<Dividend>,
+}
+
+fn default_kind() -> String { "simple".to_string() }
+fn default_horizon() -> Value { Value::String("bar".to_string()) }
+
+#[derive(Deserialize)]
+struct Dividend {
+    ex_date: String,
+    amount: f64,
+}
+
+#[derive(Serialize)]
+struct ReturnsResult {
+    kind: String,
+    horizon: String,
+    returns: Vec<f64>,
+    /// End of each return period; empty for bare `prices`.
+    timestamps: Vec<String>,
+    periods_per_year: Option<f64>,
+    mean: f64,
+    std_dev: f64,
+    annualized_volatility: Option<f64>,
+    cumulative_return: f64,
+    dividends_applied: usize,
+    skipped_prices: usize,
+}
+
+enum Horizon {
+    Bars(usize),
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl Horizon {
+    fn parse(v: &Value) -> Result<Self, String> {
+        if let Some(n) = v.as_u64() {
+            return if n > 0 { Ok(Horizon::Bars(n as usize)) } else { Err("horizon must be positive".to_string()) };
+        }
+        match v.as_str().map(|s| s.to_lowercase()).as_deref() {
+            Some("bar") => Ok(Horizon::Bars(1)),
+            Some("daily" | "day" | "1d") => Ok(Horizon::Daily),
+            Some("weekly" | "week" | "1w") => Ok(Horizon::Weekly),
+            Some("monthly" | "month" | "1mo") => Ok(Horizon::Monthly),
+            _ => Err(format!("Invalid horizon {} (bar, daily, weekly, monthly or a bar count)", v)),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Horizon::Bars(1) => "bar".to_string(),
+            Horizon::Bars(n) => format!("{}_bars", n),
+            Horizon::Daily => "daily".to_string(),
+            Horizon::Weekly => "weekly".to_string(),
+            Horizon::Monthly => "monthly".to_string(),
+        }
+    }
+
+    /// Calendar bucket a timestamp falls in; None for bar horizons.
+    fn bucket(&self, ts: NaiveDateTime) -> Option<(i32, u32)> {
+        let d = ts.date();
+        match self {
+            Horizon::Bars(_) => None,
+            Horizon::Daily => Some((d.year(), d.ordinal())),
+            Horizon::Weekly => Some((d.iso_week().year(), d.iso_week().week())),
+            Horizon::Monthly => Some((d.year(), d.month())),
+        }
+    }
+
+    fn periods_per_year(&self) -> Option<f64> {
+        match self {
+            Horizon::Bars(_) => None,
+            Horizon::Daily => Some(crate::calendar::DEFAULT_TRADING_DAYS),
+            Horizon::Weekly => Some(52.0),
+            Horizon::Monthly => Some(12.0),
+        }
+    }
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: ReturnsConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid returns config: {}", e))?;
+    let log = match config.kind.as_str() {
+        "simple" => false,
+        "log" => true,
+        other => return Err(format!("Unknown returns kind '{}' (simple, log)", other)),
+    };
+    let horizon = Horizon::parse(&config.horizon)?;
+
+    // (timestamp, label, price); bare prices carry no timestamp.
+    let mut points: Vec<(Option<NaiveDateTime>, String, f64)> = match (&config.candles, &config.prices) {
+        (Some(candles), _) => candles.iter()
+            .map(|c| (parse_timestamp(&c.timestamp), c.timestamp.clone(), c.close))
+            .collect(),
+        (None, Some(prices)) => prices.iter().map(|&p| (None, String::new(), p)).collect(),
+        (None, None) => return Err("returns requires candles or prices".to_string()),
+    };
+    let before = points.len();
+    points.retain(|(_, _, p)| p.is_finite() && *p > 0.0);
+    let dated = config.candles.is_some() && points.iter().all(|(ts, _, _)| ts.is_some());
+    if !dated && (!matches!(horizon, Horizon::Bars(_)) || !config.dividends.is_empty()) {
+        return Err("calendar horizons and dividends need candles with parseable timestamps".to_string());
+    }
+    let skipped = before - points.len();
+
+    let sampled: Vec<(Option<NaiveDateTime>, String, f64)> = match horizon {
+        Horizon::Bars(n) => points.into_iter().step_by(n).collect(),
+        _ => {
+            // Last price of each calendar bucket.
+            let mut out: Vec<(Option<NaiveDateTime>, String, f64)> = Vec::new();
+            let mut last_bucket = None;
+            for p in points {
+                let bucket = p.0.and_then(|ts| horizon.bucket(ts));
+                if bucket.is_some() && bucket == last_bucket {
+                    if let Some(slot) = out.last_mut() {
+                        *slot = p;
+                    }
+                } else {
+                    last_bucket = bucket;
+                    out.push(p);
+                }
+            }
+            out
+        }
+    };
+
+    let mut dividends: Vec<(NaiveDate, f64)> = config.dividends.iter()
+        .map(|d| parse_timestamp(&d.ex_date).map(|t| (t.date(), d.amount))
+            .ok_or_else(|| format!("Invalid dividend ex_date '{}'", d.ex_date)))
+        .collect::<Result<_, _>>()?;
+    dividends.sort_by_key(|(d, _)| *d);
+
+    let mut returns = Vec::with_capacity(sampled.len().saturating_sub(1));
+    let mut timestamps = Vec::with_capacity(returns.capacity());
+    let mut applied = 0;
+    let mut growth = 1.0;
+    for w in sampled.windows(2) {
+        let (prev, cur) = (&w[0], &w[1]);
+        let cash: f64 = match (prev.0, cur.0) {
+            (Some(a), Some(b)) => dividends.iter()
+                .filter(|(d, _)| *d > a.date() && *d <= b.date())
+                .inspect(|_| applied += 1)
+                .map(|(_, amt)| amt)
+                .sum(),
+            _ => 0.0,
+        };
+        let simple = (cur.2 + cash) / prev.2 - 1.0;
+        growth *= 1.0 + simple;
+        returns.push(if log { (1.0 + simple).ln() } else { simple });
+        if dated {
+            timestamps.push(cur.1.clone());
+        }
+    }
+
+    let n = returns.len() as f64;
+    let mean = if n > 0.0 { returns.iter().sum::<f64>() / n } else { 0.0 };
+    let std_dev = if n > 1.0 {
+        (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+    } else {
+        0.0
+    };
+    let periods_per_year = horizon.periods_per_year();
+    let result = ReturnsResult {
+        kind: config.kind,
+        horizon: horizon.label(),
+        returns: returns.iter().map(|r| round_ret(*r)).collect(),
+        timestamps,
+        periods_per_year,
+        mean: round_ret(mean),
+        std_dev: round_ret(std_dev),
+        annualized_volatility: periods_per_year.map(|p| round4(std_dev * p.sqrt())),
+        cumulative_return: round_ret(growth - 1.0),
+        dividends_applied: applied,
+        skipped_prices: skipped,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Returns are small; keep eight decimals rather than the usual four.
+fn round_ret(v: f64) -> f64 {
+    (v * 1e8).round() / 1e8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bar_returns_simple_and_log() {
+        let result = compute(json!({ "prices": [100.0, 110.0, 99.0] })).unwrap();
+        assert_eq!(result["returns"], json!([0.1, -0.1]));
+        assert!((result["cumulative_return"].as_f64().unwrap() - -0.01).abs() < 1e-9);
+
+        let result = compute(json!({ "prices": [100.0, 110.0, 121.0, 133.1], "kind": "log", "horizon": 2 })).unwrap();
+        let r = result["returns"][0].as_f64().unwrap();
+        assert!((r - 1.21f64.ln()).abs() < 1e-7);
+        assert!(compute(json!({ "prices": [1.0, 2.0], "horizon": "weekly" })).is_err());
+    }
+
+    #[test]
+    fn test_weekly_resample_with_dividend() {
+        // Mon 2024-01-01 .. Fri 2024-01-12: two ISO weeks.
+        let candles: Vec<Value> = (1..=12).filter(|d| ![6, 7].contains(d)).map(|d| {
+            let close = 100.0 + d as f64;
+            json!({ "timestamp": format!("2024-01-{:02}T15:30:00", d), "open": close, "high": close, "low": close, "close": close, "volume": 1.0 })
+        }).collect();
+        let result = compute(json!({
+            "candles": candles, "horizon": "weekly",
+            "dividends": [{ "ex_date": "2024-01-09", "amount": 5.0 }]
+        })).unwrap();
+        // Week closes 105 → 112, plus the 5.00 dividend.
+        assert_eq!(result["returns"].as_array().unwrap().len(), 1);
+        let r = result["returns"][0].as_f64().unwrap();
+        assert!((r - (117.0 / 105.0 - 1.0)).abs() < 1e-7);
+        assert_eq!(result["timestamps"][0], "2024-01-12T15:30:00");
+        assert_eq!(result["dividends_applied"], 1);
+        assert_eq!(result["periods_per_year"], 52.0);
+    }
+}
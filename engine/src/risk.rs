@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use crate::utils::{round2, round4, pearson_correlation};
+use crate::calendar::{Calendar, CalendarSpec, DEFAULT_TRADING_DAYS};
+use crate::utils::{parse_timestamp, round2, round4, pearson_correlation, Candle};
 
 #[derive(Deserialize)]
 struct RiskInput {
@@ -8,6 +9,52 @@ struct RiskInput {
     initial_capital: f64,
     risk_free_rate: Option<f64>,
     benchmark_returns: Option<Vec<f64>>,
+    /// Daily OHLC for the range-based estimators, which see the intraday
+    /// swings that close-to-close volatility misses.
+    #[serde(default)]
+    candles: Option<Vec<Candle>>,
+    /// Trading calendar that annualizes the range estimators over the
+    /// candles' dates (NSE by default).
+    #[serde(default)]
+    calendar: Option<CalendarSpec>,
+}
+
+/// One session's OHLC.
+#[derive(Clone, Copy)]
+pub(crate) struct Ohlc {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl From<&Candle> for Ohlc {
+    fn from(c: &Candle) -> Self {
+        Ohlc { open: c.open, high: c.high, low: c.low, close: c.close }
+    }
+}
+
+/// Annualized volatility from four estimators over the same bars.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub(crate) struct RangeVolatility {
+    pub close_to_close: f64,
+    /// High–low range; ignores drift and overnight gaps.
+    pub parkinson: f64,
+    /// Adds open and close to the range; still ignores gaps.
+    pub garman_klass: f64,
+    /// Overnight, open-to-close and Rogers-Satchell terms; drift- and gap-robust.
+    pub yang_zhang: f64,
+}
+
+impl RangeVolatility {
+    pub fn map(self, f: impl Fn(f64) -> f64) -> Self {
+        RangeVolatility {
+            close_to_close: f(self.close_to_close),
+            parkinson: f(self.parkinson),
+            garman_klass: f(self.garman_klass),
+            yang_zhang: f(self.yang_zhang),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,6 +78,16 @@ struct RiskOutput {
     avg_win_loss_ratio: f64,
     correlation_to_benchmark: f64,
     max_drawdown_duration: usize,
+    /// Range-based volatility (percent), when `candles` are supplied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    range_volatility: Option<RangeVolatility>,
+    /// Yang-Zhang over close-to-close; above 1 when intraday swings exceed
+    /// what daily closes show.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    range_to_close_ratio: Option<f64>,
+    /// Sessions a year used for `range_volatility`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trading_days_per_year: Option<f64>,
 }
 
 pub fn compute(data: Value) -> Result<Value, String> {
@@ -46,6 +103,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
             information_ratio: 0.0, treynor_ratio: 0.0, tail_ratio: 1.0,
             win_rate: 0.0, avg_win_loss_ratio: 0.0,
             correlation_to_benchmark: 0.0, max_drawdown_duration: 0,
+            range_volatility: None, range_to_close_ratio: None, trading_days_per_year: None,
         }).map_err(|e| e.to_string())?);
     }
 
@@ -162,6 +220,26 @@ pub fn compute(data: Value) -> Result<Value, String> {
     } else { 0.0 };
     let win_loss_ratio = if avg_loss_val > 0.0 { avg_win / avg_loss_val } else { 0.0 };
 
+    let (range_vol, trading_days) = match &input.candles {
+        Some(candles) => {
+            let bars: Vec<Ohlc> = candles.iter().map(Ohlc::from).collect();
+            if bars.iter().any(|b| !(b.open > 0.0 && b.high > 0.0 && b.low > 0.0 && b.close > 0.0)) {
+                return Err("candle prices must be positive".to_string());
+            }
+            let calendar = Calendar::from_spec(input.calendar.as_ref())?;
+            let date = |c: Option<&Candle>| c.and_then(|c| parse_timestamp(&c.timestamp)).map(|t| t.date());
+            let trading_days = match (date(candles.first()), date(candles.last())) {
+                (Some(a), Some(b)) => calendar.trading_days_per_year(a, b),
+                _ => DEFAULT_TRADING_DAYS,
+            };
+            (range_volatility(&bars, trading_days), Some(trading_days))
+        }
+        None => (None, None),
+    };
+    let range_to_close_ratio = range_vol
+        .filter(|v| v.close_to_close > 0.0)
+        .map(|v| round4(v.yang_zhang / v.close_to_close));
+
     let output = RiskOutput {
         sharpe_ratio: round2(sharpe),
         sortino_ratio: round2(sortino),
@@ -182,11 +260,55 @@ pub fn compute(data: Value) -> Result<Value, String> {
         avg_win_loss_ratio: round4(win_loss_ratio),
         correlation_to_benchmark: round4(corr_to_bench),
         max_drawdown_duration: max_dd_duration,
+        range_volatility: range_vol.map(|v| v.map(|x| round2(x * 100.0))),
+        range_to_close_ratio,
+        trading_days_per_year: trading_days.map(round2),
     };
 
     serde_json::to_value(output).map_err(|e| format!("Serialization error: {}", e))
 }
 
+/// Estimators over `bars[1..]`, with `bars[0]` supplying the prior close
+/// for the close-to-close and overnight terms. Prices must be positive.
+pub(crate) fn range_volatility(bars: &[Ohlc], periods_per_year: f64) -> Option<RangeVolatility> {
+    if bars.len() < 3 {
+        return None;
+    }
+    let n = (bars.len() - 1) as f64;
+    let ln = f64::ln;
+    let w = &bars[1..];
+    let returns: Vec<f64> = bars.windows(2).map(|p| ln(p[1].close / p[0].close)).collect();
+    let overnight: Vec<f64> = bars.windows(2).map(|p| ln(p[1].open / p[0].close)).collect();
+    let intraday: Vec<f64> = w.iter().map(|b| ln(b.close / b.open)).collect();
+
+    let parkinson = w.iter().map(|b| ln(b.high / b.low).powi(2)).sum::<f64>() / (4.0 * n * 2f64.ln());
+    let garman_klass = w.iter()
+        .map(|b| 0.5 * ln(b.high / b.low).powi(2) - (2.0 * 2f64.ln() - 1.0) * ln(b.close / b.open).powi(2))
+        .sum::<f64>() / n;
+    let rogers_satchell = w.iter()
+        .map(|b| ln(b.high / b.close) * ln(b.high / b.open) + ln(b.low / b.close) * ln(b.low / b.open))
+        .sum::<f64>() / n;
+    let k = 0.34 / (1.34 + (n + 1.0) / (n - 1.0));
+    let yang_zhang = sample_variance(&overnight) + k * sample_variance(&intraday) + (1.0 - k) * rogers_satchell;
+
+    let annualize = |var: f64| (var.max(0.0) * periods_per_year).sqrt();
+    Some(RangeVolatility {
+        close_to_close: annualize(sample_variance(&returns)),
+        parkinson: annualize(parkinson),
+        garman_klass: annualize(garman_klass),
+        yang_zhang: annualize(yang_zhang),
+    })
+}
+
+fn sample_variance(xs: &[f64]) -> f64 {
+    if xs.len() < 2 {
+        return 0.0;
+    }
+    let n = xs.len() as f64;
+    let mean = xs.iter().sum::<f64>() / n;
+    xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,4 +430,37 @@ mod tests {
         let r = compute_risk(returns, 100000.0);
         assert!(r.max_drawdown_duration >= 3, "should track drawdown duration, got {}", r.max_drawdown_duration);
     }
+
+    #[test]
+    fn test_range_volatility_from_candles() {
+        // Flat closes with wide intraday ranges: close-to-close sees nothing.
+        let candles: Vec<Value> = (0..30).map(|i| json!({
+            "timestamp": format!("2024-01-{:02}", i + 1),
+            "open": 100.0, "high": 102.0, "low": 98.0, "close": 100.0, "volume": 1000,
+        })).collect();
+        let result = compute(json!({
+            "returns": vec![0.0; 29], "initial_capital": 100000.0, "candles": candles,
+        })).unwrap();
+        let out: RiskOutput = serde_json::from_value(result).unwrap();
+        let rv = out.range_volatility.unwrap();
+        assert_eq!(rv.close_to_close, 0.0);
+        // ln(102/98)² / (4 ln2) per day, over the calendar's 2024 sessions: ≈ 38%.
+        let days = out.trading_days_per_year.unwrap();
+        assert_eq!(days, Calendar::nse().trading_days_per_year(
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(), chrono::NaiveDate::from_ymd_opt(2024, 1, 30).unwrap()));
+        let expected = ((102.0_f64 / 98.0).ln().powi(2) / (4.0 * 2.0_f64.ln()) * days).sqrt() * 100.0;
+        assert!((rv.parkinson - expected).abs() < 0.01, "parkinson {} vs {}", rv.parkinson, expected);
+        assert!((rv.parkinson - 38.0).abs() < 0.5);
+        let weekdays: RiskOutput = serde_json::from_value(compute(json!({
+            "returns": vec![0.0; 29], "initial_capital": 100000.0, "candles": candles, "calendar": { "exchange": "none" },
+        })).unwrap()).unwrap();
+        assert!(weekdays.trading_days_per_year.unwrap() > days);
+        assert!(weekdays.range_volatility.unwrap().parkinson > rv.parkinson);
+        assert!(rv.garman_klass > rv.parkinson);
+        assert!(rv.yang_zhang > 0.0);
+        assert!(out.range_to_close_ratio.is_none());
+
+        let plain = compute_risk(vec![0.01, -0.01, 0.02], 100000.0);
+        assert!(plain.range_volatility.is_none());
+    }
 }
@@ -0,0 +1,191 @@
+//! Rolling beta, correlation and relative strength against a benchmark.
+//!
+//! Unlike `risk`, which reports one beta for the whole window, this returns
+//! a series per bar for charting. Inputs are either `asset_candles` and
+//! `benchmark_candles` (joined on timestamp, bars only one side has are
+//! dropped) or equal-length `asset_prices` and `benchmark_prices`. Values
+//! are null until `window` returns are available.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::utils::{Candle, parse_timestamp, round4};
+
+#[derive(Deserialize)]
+struct RollingConfig {
+    #[serde(default)]
+    asset_candles: Option<Vec<Candle>>,
+    #[serde(default)]
+    benchmark_candles: Option<Vec<Candle>>,
+    #[serde(default)]
+    asset_prices: Option<Vec<f64>>,
+    #[serde(default)]
+    benchmark_prices: Option<Vec<f64>>,
+    #[serde(default = "default_window")]
+    window: usize,
+}
+
+fn default_window() -> usize { 60 }
+
+#[derive(Serialize)]
+struct RollingResult {
+    window: usize,
+    points: usize,
+    timestamps: Vec<String>,
+    beta: Vec<Option<f64>>,
+    correlation: Vec<Option<f64>>,
+    /// Per-bar regression intercept (excess return not explained by beta).
+    alpha: Vec<Option<f64>>,
+    /// Asset / benchmark, rebased to 100 at the first aligned bar.
+    relative_strength: Vec<f64>,
+    latest_beta: Option<f64>,
+    latest_correlation: Option<f64>,
+    dropped_bars: usize,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: RollingConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid rolling_beta config: {}", e))?;
+    if config.window < 2 {
+        return Err("window must be at least 2".to_string());
+    }
+
+    let (timestamps, asset, bench, dropped) = match (&config.asset_candles, &config.benchmark_candles, &config.asset_prices, &config.benchmark_prices) {
+        (Some(a), Some(b), _, _) => join_candles(a, b),
+        (_, _, Some(a), Some(b)) => {
+            if a.len() != b.len() {
+                return Err(format!("asset_prices ({}) and benchmark_prices ({}) differ in length", a.len(), b.len()));
+            }
+            (Vec::new(), a.clone(), b.clone(), 0)
+        }
+        _ => return Err("rolling_beta requires asset_candles and benchmark_candles, or asset_prices and benchmark_prices".to_string()),
+    };
+    if asset.iter().chain(&bench).any(|p| !p.is_finite() || *p <= 0.0) {
+        return Err("prices must be positive and finite".to_string());
+    }
+    let n = asset.len();
+
+    let Rolling { beta, correlation, alpha } = rolling(&asset, &bench, config.window);
+    let beta: Vec<Option<f64>> = beta.into_iter().map(|v| v.map(round4)).collect();
+    let correlation: Vec<Option<f64>> = correlation.into_iter().map(|v| v.map(round4)).collect();
+    let alpha: Vec<Option<f64>> = alpha.into_iter().map(|v| v.map(|a| (a * 1e6).round() / 1e6)).collect();
+    let relative_strength = match (asset.first(), bench.first()) {
+        (Some(a0), Some(b0)) => asset.iter().zip(&bench).map(|(a, b)| round4(a / a0 / (b / b0) * 100.0)).collect(),
+        _ => Vec::new(),
+    };
+
+    let result = RollingResult {
+        window: config.window,
+        points: n,
+        timestamps,
+        latest_beta: beta.last().copied().flatten(),
+        latest_correlation: correlation.last().copied().flatten(),
+        beta,
+        correlation,
+        alpha,
+        relative_strength,
+        dropped_bars: dropped,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Per-bar regression of asset on benchmark returns over the trailing
+/// `window` returns, unrounded; None until the window fills.
+pub(crate) struct Rolling {
+    pub beta: Vec<Option<f64>>,
+    pub correlation: Vec<Option<f64>>,
+    /// Per-bar intercept, in return units.
+    pub alpha: Vec<Option<f64>>,
+}
+
+pub(crate) fn rolling(asset: &[f64], bench: &[f64], w: usize) -> Rolling {
+    let n = asset.len().min(bench.len());
+    // returns[i] is the move into bar i + 1; series index i + 1 gets the
+    // window of returns ending there.
+    let ra: Vec<f64> = asset[..n].windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+    let rb: Vec<f64> = bench[..n].windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+    let mut out = Rolling { beta: vec![None; n], correlation: vec![None; n], alpha: vec![None; n] };
+    let (mut sx, mut sy, mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for i in 0..ra.len() {
+        let (x, y) = (rb[i], ra[i]);
+        sx += x; sy += y; sxx += x * x; syy += y * y; sxy += x * y;
+        if i >= w {
+            let (x0, y0) = (rb[i - w], ra[i - w]);
+            sx -= x0; sy -= y0; sxx -= x0 * x0; syy -= y0 * y0; sxy -= x0 * y0;
+        }
+        if i + 1 < w {
+            continue;
+        }
+        let m = w as f64;
+        let cov = sxy / m - (sx / m) * (sy / m);
+        let var_x = sxx / m - (sx / m).powi(2);
+        let var_y = syy / m - (sy / m).powi(2);
+        if var_x > 1e-18 {
+            let b = cov / var_x;
+            out.beta[i + 1] = Some(b);
+            out.alpha[i + 1] = Some(sy / m - b * sx / m);
+        }
+        if var_x > 1e-18 && var_y > 1e-18 {
+            out.correlation[i + 1] = Some((cov / (var_x * var_y).sqrt()).clamp(-1.0, 1.0));
+        }
+    }
+    out
+}
+
+/// Closes on timestamps both series share, in the asset's order.
+fn join_candles(asset: &[Candle], bench: &[Candle]) -> (Vec<String>, Vec<f64>, Vec<f64>, usize) {
+    let by_ts: HashMap<_, f64> = bench.iter()
+        .filter_map(|c| parse_timestamp(&c.timestamp).map(|ts| (ts, c.close)))
+        .collect();
+    let mut out = (Vec::new(), Vec::new(), Vec::new(), 0);
+    for c in asset {
+        match parse_timestamp(&c.timestamp).and_then(|ts| by_ts.get(&ts)) {
+            Some(&b) => {
+                out.0.push(c.timestamp.clone());
+                out.1.push(c.close);
+                out.2.push(b);
+            }
+            None => out.3 += 1,
+        }
+    }
+    out.3 += bench.len().saturating_sub(out.2.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_constant_beta_and_relative_strength() {
+        // Asset moves exactly twice the benchmark each bar.
+        let mut bench = vec![100.0];
+        let mut asset = vec![50.0];
+        for i in 0..40 {
+            let r = ((i * 7) % 5) as f64 * 0.004 - 0.008;
+            bench.push(bench.last().unwrap() * (1.0 + r));
+            asset.push(asset.last().unwrap() * (1.0 + 2.0 * r));
+        }
+        let result = compute(json!({ "asset_prices": asset, "benchmark_prices": bench, "window": 10 })).unwrap();
+        let beta = result["beta"].as_array().unwrap();
+        assert_eq!(beta.len(), 41);
+        assert!(beta[9].is_null());
+        assert!((beta[10].as_f64().unwrap() - 2.0).abs() < 1e-3);
+        assert!((result["latest_correlation"].as_f64().unwrap() - 1.0).abs() < 1e-3);
+        assert_eq!(result["relative_strength"][0], 100.0);
+    }
+
+    #[test]
+    fn test_candles_joined_on_timestamp() {
+        let candle = |d: u32, c: f64| json!({ "timestamp": format!("2024-01-{:02}", d), "open": c, "high": c, "low": c, "close": c, "volume": 1.0 });
+        let asset: Vec<Value> = (1..=6).map(|d| candle(d, 100.0 + d as f64)).collect();
+        let bench: Vec<Value> = [1, 2, 4, 5, 6].iter().map(|&d| candle(d, 200.0 - d as f64)).collect();
+        let result = compute(json!({ "asset_candles": asset, "benchmark_candles": bench, "window": 2 })).unwrap();
+        assert_eq!(result["points"], 5);
+        assert_eq!(result["dropped_bars"], 1);
+        assert_eq!(result["timestamps"][2], "2024-01-04");
+        assert!(result["latest_beta"].is_number());
+        assert!(compute(json!({ "asset_prices": [1.0, 2.0], "benchmark_prices": [1.0] })).is_err());
+    }
+}
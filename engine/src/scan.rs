@@ -1,8 +1,13 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use crate::signals;
-use crate::utils::{Candle, calc_ema_series, get_f64, round2, round3, round4, calc_atr_candles, sanitize_candles};
+use crate::indicators;
+use crate::advanced_signals;
+use crate::liquidity;
+use crate::events::{Event, EventCalendar, EventProximity, EventWindow};
+use crate::exits::{ExitLevels, ExitSpec};
+use crate::target_odds::{TargetOdds, TargetOddsSpec};
+use crate::utils::{Candle, parse_timestamp, round2, round3, round4, calc_atr_candles, sanitize_candles};
 
 #[derive(Deserialize)]
 struct ScanInput {
@@ -17,10 +22,75 @@ struct ScanInput {
     regime: Option<String>,
     #[serde(default)]
     current_date: Option<String>,  // YYYY-MM-DD for expiry detection
+    /// Holidays shift expiries to the previous trading day.
+    #[serde(default)]
+    calendar: Option<crate::calendar::CalendarSpec>,
     #[serde(default)]
     pair_universe: Option<Vec<(String, String)>>,
+    /// Cap composite targets at the nearest opposing absorption/initiative zone.
+    #[serde(default)]
+    use_liquidity_zones: bool,
+    /// Skip symbols whose current bar trades below this time-of-day relative
+    /// volume. Symbols without enough intraday history are not filtered.
+    #[serde(default)]
+    min_rvol: Option<f64>,
+    #[serde(default = "default_rvol_sessions")]
+    rvol_sessions: usize,
+    /// Symbol → expected slippage in bps (the `estimate_slippage` map),
+    /// reported on each signal.
+    #[serde(default)]
+    slippage_bps: Option<HashMap<String, f64>>,
+    /// Estimate slippage from each symbol's candles when the map lacks it.
+    #[serde(default)]
+    auto_slippage: bool,
+    /// Skip symbols whose expected slippage exceeds this many bps.
+    #[serde(default)]
+    max_slippage_bps: Option<f64>,
+    #[serde(default)]
+    bars_per_day: Option<f64>,
+    /// Skip symbols whose average daily turnover (close × volume over the
+    /// last 20 sessions) is below this.
+    #[serde(default)]
+    min_turnover: Option<f64>,
+    /// Skip symbols whose 0–1 turnover liquidity score is below this.
+    #[serde(default)]
+    min_liquidity_score: Option<f64>,
+    /// Collapse correlated same-direction signals to the strongest symbol
+    /// per cluster.
+    #[serde(default)]
+    correlation_dedup: Option<CorrelationDedup>,
+    /// Trailing-exit rules; each signal reports its levels as of the last bar.
+    #[serde(default)]
+    exits: Option<ExitSpec>,
+    /// Earnings, expiry and macro dates; see [`crate::events`].
+    #[serde(default)]
+    events: Vec<Event>,
+    /// Skip symbols with a matching event this close to `current_date`
+    /// (or the last bar's date).
+    #[serde(default)]
+    event_blackout: Option<EventWindow>,
+    /// Simulate the odds of reaching target before stop from each F&O
+    /// symbol's implied volatility; see [`crate::target_odds`].
+    #[serde(default)]
+    target_odds: Option<TargetOddsSpec>,
 }
 
+#[derive(Deserialize, Clone, Copy)]
+struct CorrelationDedup {
+    /// Symbols whose returns correlate at least this much with a cluster's
+    /// representative join that cluster.
+    #[serde(default = "default_dedup_threshold")]
+    threshold: f64,
+    /// Bars of close-to-close returns compared.
+    #[serde(default = "default_dedup_lookback")]
+    lookback: usize,
+}
+
+fn default_dedup_threshold() -> f64 { 0.8 }
+fn default_dedup_lookback() -> usize { 20 }
+
+fn default_rvol_sessions() -> usize { 10 }
+
 #[derive(Deserialize, Clone)]
 struct VoteWeights {
     #[serde(default = "default_ema_w")] ema: f64,
@@ -31,6 +101,15 @@ struct VoteWeights {
     #[serde(default = "default_vwap_w")] vwap: f64,
     #[serde(default = "default_mom_w")] momentum: f64,
     #[serde(default = "default_vol_w")] volume: f64,
+    /// Order-flow votes; only used for symbols that supply `order_flow`,
+    /// whose indicator votes then share the remaining weight.
+    #[serde(default = "default_cum_delta_w")] cumulative_delta: f64,
+    #[serde(default = "default_imbalance_w")] imbalance: f64,
+    /// Composite votes, off unless given a weight: volume-weighted MACD and
+    /// the Elder Impulse colour. Like order flow they take their share of
+    /// the weight from the indicator votes.
+    #[serde(default)] vw_macd: f64,
+    #[serde(default)] impulse: f64,
 }
 
 fn default_ema_w() -> f64 { 0.15 }
@@ -41,12 +120,16 @@ fn default_bb_w() -> f64 { 0.05 }
 fn default_vwap_w() -> f64 { 0.05 }
 fn default_mom_w() -> f64 { 0.25 }
 fn default_vol_w() -> f64 { 0.20 }
+fn default_cum_delta_w() -> f64 { 0.10 }
+fn default_imbalance_w() -> f64 { 0.10 }
 
 impl Default for VoteWeights {
     fn default() -> Self {
         VoteWeights {
             ema: 0.15, rsi: 0.10, macd: 0.10, supertrend: 0.10,
             bollinger: 0.05, vwap: 0.05, momentum: 0.25, volume: 0.20,
+            cumulative_delta: 0.10, imbalance: 0.10,
+            vw_macd: 0.0, impulse: 0.0,
         }
     }
 }
@@ -65,22 +148,49 @@ fn normalize_weights(w: &mut VoteWeights) {
     }
 }
 
+/// MACD-style vote from `(line, signal, histogram)` now and a bar earlier:
+/// ±1 on a fresh cross, else ±0.7 while the histogram grows and ±0.3 while
+/// it shrinks. 0 while undefined.
+fn macd_cross_vote(now: (f64, f64, f64), before: (f64, f64, f64)) -> f64 {
+    let ((line, signal, hist), (line_prev, signal_prev, hist_prev)) = (now, before);
+    if line.is_nan() || signal.is_nan() || hist.is_nan() {
+        0.0
+    } else if line > signal && line_prev <= signal_prev {
+        1.0
+    } else if line < signal && line_prev >= signal_prev {
+        -1.0
+    } else if hist > 0.0 {
+        // Reward increasing histogram (accelerating momentum)
+        if hist > hist_prev { 0.7 } else { 0.3 }
+    } else if hist < 0.0 {
+        if hist < hist_prev { -0.7 } else { -0.3 }
+    } else {
+        0.0
+    }
+}
+
 fn apply_regime_weights(base: &VoteWeights, regime: &str) -> VoteWeights {
     let mut w = match regime {
         "trending" => VoteWeights {
             ema: base.ema * 1.5, rsi: base.rsi * 0.7, macd: base.macd * 1.3,
             supertrend: base.supertrend * 1.4, bollinger: base.bollinger * 0.8,
             vwap: base.vwap, momentum: base.momentum * 1.4, volume: base.volume,
+            cumulative_delta: base.cumulative_delta * 1.2, imbalance: base.imbalance,
+            vw_macd: base.vw_macd * 1.3, impulse: base.impulse * 1.4,
         },
         "mean_reverting" => VoteWeights {
             ema: base.ema * 0.7, rsi: base.rsi * 1.5, macd: base.macd * 0.8,
             supertrend: base.supertrend * 0.6, bollinger: base.bollinger * 1.6,
             vwap: base.vwap * 1.3, momentum: base.momentum * 0.6, volume: base.volume,
+            cumulative_delta: base.cumulative_delta * 0.8, imbalance: base.imbalance * 1.2,
+            vw_macd: base.vw_macd * 0.8, impulse: base.impulse * 0.6,
         },
         "volatile" => VoteWeights {
             ema: base.ema * 0.8, rsi: base.rsi * 1.2, macd: base.macd,
             supertrend: base.supertrend * 1.2, bollinger: base.bollinger * 1.4,
             vwap: base.vwap, momentum: base.momentum * 0.7, volume: base.volume * 1.3,
+            cumulative_delta: base.cumulative_delta, imbalance: base.imbalance * 1.3,
+            vw_macd: base.vw_macd, impulse: base.impulse,
         },
         _ => base.clone(),
     };
@@ -97,8 +207,66 @@ struct ResolvedPeriods {
 struct SymbolData {
     symbol: String,
     candles: Vec<Candle>,
+    /// Output of the `pcr` command; tilts the composite toward its sentiment.
+    #[serde(default)]
+    options_sentiment: Option<OptionsSentiment>,
+    /// The `order_flow` block of `advanced_signals`; adds cumulative-delta
+    /// trend and imbalance votes.
+    #[serde(default)]
+    order_flow: Option<OrderFlowInput>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+struct OptionsSentiment {
+    sentiment_score: f64,
 }
 
+#[derive(Deserialize, Clone)]
+struct OrderFlowInput {
+    /// (buy − sell) / (buy + sell) over the whole input, −1..1.
+    imbalance_ratio: f64,
+    #[serde(default)]
+    recent_deltas: Vec<OrderFlowDelta>,
+}
+
+#[derive(Deserialize, Clone)]
+struct OrderFlowDelta {
+    delta: f64,
+    cumulative: f64,
+}
+
+/// Imbalance at which the imbalance vote saturates; matches the
+/// STRONG_BUYING/STRONG_SELLING cut-off in `advanced_signals`.
+const FULL_IMBALANCE: f64 = 0.3;
+
+impl OrderFlowInput {
+    /// Least-squares slope of cumulative delta over the recent bars, in units
+    /// of the mean absolute bar delta: ±1 when every bar pushes the same way.
+    fn cumulative_delta_vote(&self) -> f64 {
+        let n = self.recent_deltas.len();
+        let mean_abs = self.recent_deltas.iter().map(|d| d.delta.abs()).sum::<f64>() / n.max(1) as f64;
+        if n < 3 || mean_abs <= 0.0 {
+            return 0.0;
+        }
+        let x_mean = (n - 1) as f64 / 2.0;
+        let y_mean = self.recent_deltas.iter().map(|d| d.cumulative).sum::<f64>() / n as f64;
+        let (mut sxy, mut sxx) = (0.0, 0.0);
+        for (i, d) in self.recent_deltas.iter().enumerate() {
+            let dx = i as f64 - x_mean;
+            sxy += dx * (d.cumulative - y_mean);
+            sxx += dx * dx;
+        }
+        (sxy / sxx / mean_abs).clamp(-1.0, 1.0)
+    }
+
+    fn imbalance_vote(&self) -> f64 {
+        (self.imbalance_ratio / FULL_IMBALANCE).clamp(-1.0, 1.0)
+    }
+}
+
+/// Composite shift at a full-strength (±1) options sentiment score.
+const OPTIONS_SENTIMENT_WEIGHT: f64 = 0.06;
+
 fn default_aggressiveness() -> String {
     "medium".to_string()
 }
@@ -106,6 +274,33 @@ fn default_aggressiveness() -> String {
 #[derive(Serialize)]
 struct ScanOutput {
     signals: Vec<ScanSignal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clusters: Option<Vec<SignalCluster>>,
+    /// Symbols skipped by `event_blackout`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_blocked: Option<Vec<EventBlocked>>,
+}
+
+#[derive(Serialize)]
+struct EventBlocked {
+    symbol: String,
+    event: EventProximity,
+}
+
+#[derive(Serialize)]
+struct SignalCluster {
+    direction: String,
+    /// Highest-confidence symbol; the only one whose signals are kept.
+    representative: String,
+    members: Vec<ClusterMember>,
+}
+
+#[derive(Serialize)]
+struct ClusterMember {
+    symbol: String,
+    confidence: f64,
+    /// Return correlation with the representative (1 for itself).
+    correlation: f64,
 }
 
 #[derive(Serialize)]
@@ -120,6 +315,15 @@ struct ScanSignal {
     votes: VoteBreakdown,
     #[serde(skip_serializing_if = "Option::is_none")]
     strategy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slippage_bps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exits: Option<ExitLevels>,
+    /// Turnover liquidity score; reported when a liquidity filter is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    liquidity_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_odds: Option<TargetOdds>,
 }
 
 #[derive(Serialize, Clone)]
@@ -150,6 +354,14 @@ struct VoteBreakdown {
     vwap: f64,
     momentum: f64,
     volume: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cumulative_delta: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    imbalance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vw_macd: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    impulse: Option<f64>,
 }
 
 struct Thresholds {
@@ -225,6 +437,16 @@ pub fn compute(data: Value) -> Result<Value, String> {
     let input: ScanInput =
         serde_json::from_value(data).map_err(|e| format!("Invalid scan input: {}", e))?;
 
+    if let Some(spec) = &input.exits {
+        spec.validate()?;
+    }
+    if let Some(spec) = &input.target_odds {
+        spec.validate()?;
+    }
+    let events = EventCalendar::new(&input.events)?;
+    let scan_date = input.current_date.as_deref()
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").ok());
+    let mut event_blocked = input.event_blackout.as_ref().map(|_| Vec::new());
     let thresholds = get_thresholds(&input.aggressiveness);
     let periods = resolve_periods(&input.strategy_params);
     let use_custom_ema = input.strategy_params.is_some();
@@ -234,6 +456,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
         None => base_weights,
     };
     let mut out_signals = Vec::new();
+    let mut slippage_by_symbol: HashMap<String, f64> = HashMap::new();
+    let mut returns_by_symbol: HashMap<String, Vec<f64>> = HashMap::new();
+    let mut exit_inputs: HashMap<String, (Vec<Candle>, f64)> = HashMap::new();
+    let mut liquidity_by_symbol: HashMap<String, f64> = HashMap::new();
 
     for sym_data in &input.symbols {
         if sym_data.candles.len() < 15 {
@@ -242,19 +468,60 @@ pub fn compute(data: Value) -> Result<Value, String> {
 
         let mut candles_clean = sym_data.candles.clone();
         sanitize_candles(&mut candles_clean);
-        let sym_data = &SymbolData { symbol: sym_data.symbol.clone(), candles: candles_clean };
-
-        let candles_json = match serde_json::to_value(
-            &serde_json::json!({ "candles": sym_data.candles })
-        ) {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-        let indicators = match signals::compute(candles_json) {
-            Ok(v) => v,
-            Err(_) => continue,
+        let sym_data = &SymbolData {
+            symbol: sym_data.symbol.clone(),
+            candles: candles_clean,
+            options_sentiment: sym_data.options_sentiment,
+            order_flow: sym_data.order_flow.clone(),
         };
 
+        let slippage_bps = input.slippage_bps.as_ref().and_then(|m| m.get(&sym_data.symbol).copied()).or_else(|| {
+            input.auto_slippage.then(|| {
+                crate::slippage::estimate_from_candles(&sym_data.candles, 20, input.bars_per_day.unwrap_or(1.0), None)
+                    .map(|e| round2(e.slippage_bps))
+            }).flatten()
+        });
+        if matches!((slippage_bps, input.max_slippage_bps), (Some(s), Some(max)) if s > max) {
+            continue;
+        }
+        if let Some(bps) = slippage_bps {
+            slippage_by_symbol.insert(sym_data.symbol.clone(), bps);
+        }
+        if let Some(dedup) = input.correlation_dedup {
+            let tail = &sym_data.candles[sym_data.candles.len().saturating_sub(dedup.lookback + 1)..];
+            let returns = tail.windows(2)
+                .map(|w| if w[0].close > 0.0 { w[1].close / w[0].close - 1.0 } else { 0.0 })
+                .collect();
+            returns_by_symbol.insert(sym_data.symbol.clone(), returns);
+        }
+
+        if let (Some(window), Some(blocked)) = (&input.event_blackout, event_blocked.as_mut()) {
+            let date = scan_date.or_else(|| sym_data.candles.last().and_then(|c| parse_timestamp(&c.timestamp)).map(|t| t.date()));
+            if let Some(event) = date.and_then(|d| events.blackout(&sym_data.symbol, d, window)) {
+                blocked.push(EventBlocked { symbol: sym_data.symbol.clone(), event });
+                continue;
+            }
+        }
+
+        if input.min_turnover.is_some() || input.min_liquidity_score.is_some() {
+            let bars_per_day = input.bars_per_day.unwrap_or(1.0);
+            let Some(liquidity) = liquidity::turnover_liquidity(&sym_data.candles, bars_per_day, liquidity::DEFAULT_LOOKBACK_DAYS) else { continue };
+            if input.min_turnover.is_some_and(|min| liquidity.daily_turnover < min)
+                || input.min_liquidity_score.is_some_and(|min| liquidity.score < min) {
+                continue;
+            }
+            liquidity_by_symbol.insert(sym_data.symbol.clone(), round3(liquidity.score));
+        }
+
+        if let Some(min_rvol) = input.min_rvol {
+            let (rvol, _) = advanced_signals::relative_volume_by_time(&sym_data.candles, input.rvol_sessions);
+            if matches!(rvol.last(), Some(Some(r)) if *r < min_rvol) {
+                continue;
+            }
+        }
+
+        let indicators = indicators::standard(&sym_data.candles);
+
         let n = sym_data.candles.len();
         let last = n - 1;
         let prev = n - 2;
@@ -263,32 +530,35 @@ pub fn compute(data: Value) -> Result<Value, String> {
 
         let (ema_short_series, ema_long_series) = if use_custom_ema {
             let closes: Vec<f64> = sym_data.candles.iter().map(|c| c.close).collect();
-            (calc_ema_series(&closes, periods.ema_short), calc_ema_series(&closes, periods.ema_long))
+            (indicators::ema(&closes, periods.ema_short), indicators::ema(&closes, periods.ema_long))
         } else {
             (Vec::new(), Vec::new())
         };
 
-        let ema9 = if use_custom_ema { *ema_short_series.get(last).unwrap_or(&0.0) } else { get_f64(&indicators, "ema_9", last) };
-        let ema21 = if use_custom_ema { *ema_long_series.get(last).unwrap_or(&0.0) } else { get_f64(&indicators, "ema_21", last) };
-        let ema9_prev = if use_custom_ema { *ema_short_series.get(prev).unwrap_or(&0.0) } else { get_f64(&indicators, "ema_9", prev) };
-        let ema21_prev = if use_custom_ema { *ema_long_series.get(prev).unwrap_or(&0.0) } else { get_f64(&indicators, "ema_21", prev) };
-        let rsi = get_f64(&indicators, "rsi_14", last);
-        let macd = get_f64(&indicators, "macd", last);
-        let macd_sig = get_f64(&indicators, "macd_signal", last);
-        let macd_prev = get_f64(&indicators, "macd", prev);
-        let macd_sig_prev = get_f64(&indicators, "macd_signal", prev);
-        let macd_hist = get_f64(&indicators, "macd_histogram", last);
-        let supertrend = get_f64(&indicators, "supertrend", last);
-        let bb_upper = get_f64(&indicators, "bollinger_upper", last);
-        let bb_lower = get_f64(&indicators, "bollinger_lower", last);
+        let ema9 = if use_custom_ema { *ema_short_series.get(last).unwrap_or(&0.0) } else { indicators.ema_9[last] };
+        let ema21 = if use_custom_ema { *ema_long_series.get(last).unwrap_or(&0.0) } else { indicators.ema_21[last] };
+        let ema9_prev = if use_custom_ema { *ema_short_series.get(prev).unwrap_or(&0.0) } else { indicators.ema_9[prev] };
+        let ema21_prev = if use_custom_ema { *ema_long_series.get(prev).unwrap_or(&0.0) } else { indicators.ema_21[prev] };
+        let rsi = indicators.rsi_14[last];
+        let macd = indicators.macd[last];
+        let macd_sig = indicators.macd_signal[last];
+        let macd_prev = indicators.macd[prev];
+        let macd_sig_prev = indicators.macd_signal[prev];
+        let macd_hist = indicators.macd_histogram[last];
+        let supertrend = indicators.supertrend[last];
+        let bb_upper = indicators.bollinger_upper[last];
+        let bb_lower = indicators.bollinger_lower[last];
         let bb_mid = (bb_upper + bb_lower) / 2.0;
-        let vwap = get_f64(&indicators, "vwap", last);
+        let vwap = indicators.vwap[last];
 
         if ema21 == 0.0 || supertrend == 0.0 || bb_upper == 0.0 {
             continue;
         }
 
         let atr = calc_atr_candles(&sym_data.candles, 14);
+        if input.exits.is_some() {
+            exit_inputs.insert(sym_data.symbol.clone(), (sym_data.candles.clone(), atr));
+        }
 
         // ======= MOMENTUM DETECTION (NEW - catches rallies) =======
         let momentum_score = calc_momentum(&sym_data.candles, thresholds.momentum_candles);
@@ -332,20 +602,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
         };
 
         // --- Vote: MACD (weight: 0.10) ---
-        let macd_vote = if macd > macd_sig && macd_prev <= macd_sig_prev {
-            1.0
-        } else if macd < macd_sig && macd_prev >= macd_sig_prev {
-            -1.0
-        } else if macd_hist > 0.0 {
-            // Reward increasing histogram (accelerating momentum)
-            let prev_hist = get_f64(&indicators, "macd_histogram", prev);
-            if macd_hist > prev_hist { 0.7 } else { 0.3 }
-        } else if macd_hist < 0.0 {
-            let prev_hist = get_f64(&indicators, "macd_histogram", prev);
-            if macd_hist < prev_hist { -0.7 } else { -0.3 }
-        } else {
-            0.0
-        };
+        let macd_vote = macd_cross_vote(
+            (macd, macd_sig, macd_hist),
+            (macd_prev, macd_sig_prev, indicators.macd_histogram[prev]),
+        );
 
         // --- Vote: Supertrend (weight: 0.10) ---
         let st_vote = if close > supertrend {
@@ -451,6 +711,34 @@ pub fn compute(data: Value) -> Result<Value, String> {
         } else {
             0.0
         };
+        // Order flow takes its share of the weight from the indicator votes.
+        let order_flow_votes = sym_data.order_flow.as_ref()
+            .map(|of| (of.cumulative_delta_vote(), of.imbalance_vote()));
+        let composite = match order_flow_votes {
+            Some((cd_vote, imb_vote)) => {
+                let of_weight = (weights.cumulative_delta + weights.imbalance).clamp(0.0, 1.0);
+                composite * (1.0 - of_weight)
+                    + cd_vote * weights.cumulative_delta + imb_vote * weights.imbalance
+            }
+            None => composite,
+        };
+        // Composite indicator votes, computed only when weighted.
+        let closes: Vec<f64> = sym_data.candles.iter().map(|c| c.close).collect();
+        let vw_macd_vote = (weights.vw_macd > 0.0).then(|| {
+            let volumes: Vec<f64> = sym_data.candles.iter().map(|c| c.volume).collect();
+            let (line, signal, hist) = indicators::vw_macd(&closes, &volumes, 12, 26, 9);
+            macd_cross_vote((line[last], signal[last], hist[last]), (line[prev], signal[prev], hist[prev]))
+        });
+        let impulse_vote = (weights.impulse > 0.0).then(|| indicators::elder_impulse(&closes)[last]);
+        let composite = match (vw_macd_vote, impulse_vote) {
+            (None, None) => composite,
+            (vw, imp) => {
+                let w_vw = if vw.is_some() { weights.vw_macd } else { 0.0 };
+                let w_imp = if imp.is_some() { weights.impulse } else { 0.0 };
+                composite * (1.0 - (w_vw + w_imp).clamp(0.0, 1.0))
+                    + vw.unwrap_or(0.0) * w_vw + imp.unwrap_or(0.0) * w_imp
+            }
+        };
         let composite = if composite > 0.0 {
             composite + agreement_bonus
         } else if composite < 0.0 {
@@ -472,7 +760,11 @@ pub fn compute(data: Value) -> Result<Value, String> {
             else if volume_ratio < 0.5 { -0.05 }
             else { 0.0 };
 
-        let composite = composite + breakout_score * 0.08 + vol_factor + liq_factor;
+        // Options positioning (PCR) factor
+        let options_factor = sym_data.options_sentiment
+            .map_or(0.0, |o| o.sentiment_score.clamp(-1.0, 1.0) * OPTIONS_SENTIMENT_WEIGHT);
+
+        let composite = composite + breakout_score * 0.08 + vol_factor + liq_factor + options_factor;
 
         let (direction, confidence) = if composite > 0.0 {
             ("BUY".to_string(), composite.min(1.0))
@@ -491,6 +783,11 @@ pub fn compute(data: Value) -> Result<Value, String> {
         } else {
             (close + 1.5 * atr, close - 2.5 * atr)
         };
+        let target = if input.use_liquidity_zones {
+            zone_capped_target(&sym_data.candles, &direction, close, target)
+        } else {
+            target
+        };
 
         let base_indicators = IndicatorSnapshot {
             ema_9: round2(ema9),
@@ -518,6 +815,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
             vwap: round3(vwap_vote),
             momentum: round3(momentum_vote),
             volume: round3(volume_vote),
+            cumulative_delta: order_flow_votes.map(|v| round3(v.0)),
+            imbalance: order_flow_votes.map(|v| round3(v.1)),
+            vw_macd: vw_macd_vote.map(round3),
+            impulse: impulse_vote,
         };
 
         // Composite strategy: uses all indicators
@@ -531,6 +832,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
             indicators: base_indicators.clone(),
             votes: base_votes.clone(),
             strategy: Some("composite".into()),
+            slippage_bps: None,
+            exits: None,
+            liquidity_score: None,
+            target_odds: None,
         });
 
         // === STRATEGY-SPECIFIC SIGNALS (4.2) ===
@@ -556,6 +861,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                         indicators: base_indicators.clone(),
                         votes: base_votes.clone(),
                         strategy: Some("orb".into()),
+                        slippage_bps: None,
+                        exits: None,
+                        liquidity_score: None,
+                        target_odds: None,
                     });
                 }
             } else if orb_range > 0.0 && close < first_low && volume_ratio > 1.2 {
@@ -571,6 +880,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                         indicators: base_indicators.clone(),
                         votes: base_votes.clone(),
                         strategy: Some("orb".into()),
+                        slippage_bps: None,
+                        exits: None,
+                        liquidity_score: None,
+                        target_odds: None,
                     });
                 }
             }
@@ -590,6 +903,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     indicators: base_indicators.clone(),
                     votes: base_votes.clone(),
                     strategy: Some("mean_reversion".into()),
+                    slippage_bps: None,
+                    exits: None,
+                    liquidity_score: None,
+                    target_odds: None,
                 });
             }
         } else if rsi > 70.0 && close > bb_upper && volume_ratio > 0.8 {
@@ -605,6 +922,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     indicators: base_indicators.clone(),
                     votes: base_votes.clone(),
                     strategy: Some("mean_reversion".into()),
+                    slippage_bps: None,
+                    exits: None,
+                    liquidity_score: None,
+                    target_odds: None,
                 });
             }
         }
@@ -629,6 +950,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                             indicators: base_indicators.clone(),
                             votes: base_votes.clone(),
                             strategy: Some("gap_trading".into()),
+                            slippage_bps: None,
+                            exits: None,
+                            liquidity_score: None,
+                            target_odds: None,
                         });
                     }
                 }
@@ -646,6 +971,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                             indicators: base_indicators.clone(),
                             votes: base_votes.clone(),
                             strategy: Some("gap_trading".into()),
+                            slippage_bps: None,
+                            exits: None,
+                            liquidity_score: None,
+                            target_odds: None,
                         });
                     }
                 }
@@ -668,6 +997,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                         indicators: base_indicators.clone(),
                         votes: base_votes.clone(),
                         strategy: Some("vwap_reversion".into()),
+                        slippage_bps: None,
+                        exits: None,
+                        liquidity_score: None,
+                        target_odds: None,
                     });
                 }
             } else if deviation > 1.0 && rsi > 55.0 && volume_ratio > 0.8 {
@@ -683,6 +1016,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                         indicators: base_indicators.clone(),
                         votes: base_votes.clone(),
                         strategy: Some("vwap_reversion".into()),
+                        slippage_bps: None,
+                        exits: None,
+                        liquidity_score: None,
+                        target_odds: None,
                     });
                 }
             }
@@ -691,8 +1028,8 @@ pub fn compute(data: Value) -> Result<Value, String> {
         // 5. Volatility Breakout — Bollinger squeeze then expansion
         if bb_range > 0.0 {
             let squeeze_ratio = bb_range / close;
-            let prev_bb_upper = get_f64(&indicators, "bollinger_upper", prev);
-            let prev_bb_lower = get_f64(&indicators, "bollinger_lower", prev);
+            let prev_bb_upper = indicators.bollinger_upper[prev];
+            let prev_bb_lower = indicators.bollinger_lower[prev];
             let prev_range = prev_bb_upper - prev_bb_lower;
             let expansion = if prev_range > 0.0 { bb_range / prev_range } else { 1.0 };
 
@@ -711,6 +1048,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                             indicators: base_indicators.clone(),
                             votes: base_votes.clone(),
                             strategy: Some("volatility_breakout".into()),
+                            slippage_bps: None,
+                            exits: None,
+                            liquidity_score: None,
+                            target_odds: None,
                         });
                     }
                 } else if close < bb_lower && momentum_score < -0.3 {
@@ -726,6 +1067,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                             indicators: base_indicators.clone(),
                             votes: base_votes.clone(),
                             strategy: Some("volatility_breakout".into()),
+                            slippage_bps: None,
+                            exits: None,
+                            liquidity_score: None,
+                            target_odds: None,
                         });
                     }
                 }
@@ -746,6 +1091,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     indicators: base_indicators.clone(),
                     votes: base_votes.clone(),
                     strategy: Some("sector_rotation".into()),
+                    slippage_bps: None,
+                    exits: None,
+                    liquidity_score: None,
+                    target_odds: None,
                 });
             }
         }
@@ -814,6 +1163,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
                 let dummy_votes = VoteBreakdown {
                     ema_crossover: 0.0, rsi: 0.0, macd: 0.0, supertrend: 0.0,
                     bollinger: 0.0, vwap: 0.0, momentum: 0.0, volume: 0.0,
+                    cumulative_delta: None, imbalance: None, vw_macd: None, impulse: None,
                 };
 
                 out_signals.push(ScanSignal {
@@ -826,6 +1176,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     indicators: dummy_ind.clone(),
                     votes: dummy_votes.clone(),
                     strategy: Some(format!("pairs:{}_{}", sym_a, sym_b)),
+                    slippage_bps: None,
+                    exits: None,
+                    liquidity_score: None,
+                    target_odds: None,
                 });
                 out_signals.push(ScanSignal {
                     symbol: format!("{}_LONG", sym_b),
@@ -837,6 +1191,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     indicators: dummy_ind,
                     votes: dummy_votes,
                     strategy: Some(format!("pairs:{}_{}", sym_a, sym_b)),
+                    slippage_bps: None,
+                    exits: None,
+                    liquidity_score: None,
+                    target_odds: None,
                 });
             }
         }
@@ -852,6 +1210,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
                 let dummy_votes = VoteBreakdown {
                     ema_crossover: 0.0, rsi: 0.0, macd: 0.0, supertrend: 0.0,
                     bollinger: 0.0, vwap: 0.0, momentum: 0.0, volume: 0.0,
+                    cumulative_delta: None, imbalance: None, vw_macd: None, impulse: None,
                 };
 
                 out_signals.push(ScanSignal {
@@ -864,6 +1223,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     indicators: dummy_ind.clone(),
                     votes: dummy_votes.clone(),
                     strategy: Some(format!("pairs:{}_{}", sym_a, sym_b)),
+                    slippage_bps: None,
+                    exits: None,
+                    liquidity_score: None,
+                    target_odds: None,
                 });
                 out_signals.push(ScanSignal {
                     symbol: format!("{}_SHORT", sym_b),
@@ -875,6 +1238,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     indicators: dummy_ind,
                     votes: dummy_votes,
                     strategy: Some(format!("pairs:{}_{}", sym_a, sym_b)),
+                    slippage_bps: None,
+                    exits: None,
+                    liquidity_score: None,
+                    target_odds: None,
                 });
             }
         }
@@ -885,40 +1252,38 @@ pub fn compute(data: Value) -> Result<Value, String> {
     //   NIFTY:   weekly Tuesday (NSE)
     //   SENSEX:  weekly Thursday (BSE)
     //   All others (BANKNIFTY, FINNIFTY, MIDCPNIFTY, stocks): monthly only — last Tuesday
-    let date_info = if let Some(ref date_str) = input.current_date {
-        let parts: Vec<&str> = date_str.split('-').collect();
-        if parts.len() == 3 {
-            let day: u32 = parts[2].parse().unwrap_or(0);
-            let month: u32 = parts[1].parse().unwrap_or(0);
-            let year: i32 = parts[0].parse().unwrap_or(0);
-            if year > 0 && month > 0 && day > 0 {
-                let (y, m) = if month <= 2 { (year - 1, month + 12) } else { (year, month) };
-                let dow = (day as i32 + (13 * (m as i32 + 1)) / 5 + y + y / 4 - y / 100 + y / 400) % 7;
-                // Zeller: 0=Sat, 1=Sun, 2=Mon, 3=Tue, 4=Wed, 5=Thu, 6=Fri
-                // Check if this is the LAST occurrence of this weekday in the month
-                let days_in_month = match month {
-                    1|3|5|7|8|10|12 => 31u32,
-                    4|6|9|11 => 30,
-                    2 => if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 { 29 } else { 28 },
-                    _ => 30,
-                };
-                let is_last_of_weekday = day + 7 > days_in_month;
-                Some((dow, is_last_of_weekday))
-            } else { None }
-        } else { None }
-    } else {
-        None
-    };
-
-    if let Some((dow, is_last_of_weekday)) = date_info {
+    // A scheduled expiry that falls on a holiday moves to the previous
+    // trading day, so compare against the calendar-adjusted date.
+    let calendar = crate::calendar::Calendar::from_spec(input.calendar.as_ref())?;
+    let today = input.current_date.as_deref()
+        .and_then(|d| chrono::NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").ok());
+
+    if let Some(today) = today {
+        use chrono::{Datelike, Duration, Weekday};
+        let weekly = |wd: Weekday| {
+            let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+            calendar.on_or_before(monday + Duration::days(wd.num_days_from_monday() as i64))
+        };
+        let last_tuesday = {
+            let next_month = if today.month() == 12 {
+                chrono::NaiveDate::from_ymd_opt(today.year() + 1, 1, 1)
+            } else {
+                chrono::NaiveDate::from_ymd_opt(today.year(), today.month() + 1, 1)
+            };
+            next_month.map(|first| {
+                let last = first - Duration::days(1);
+                let back = (last.weekday().num_days_from_monday() + 7 - Weekday::Tue.num_days_from_monday()) % 7;
+                calendar.on_or_before(last - Duration::days(back as i64))
+            })
+        };
         for sym_data in &input.symbols {
             let sym_upper = sym_data.symbol.to_uppercase();
 
             let is_expiry_for_symbol = match sym_upper.as_str() {
-                "NIFTY" => dow == 3,            // Weekly Tuesday
-                "SENSEX" => dow == 5,           // Weekly Thursday
+                "NIFTY" => weekly(Weekday::Tue) == today,     // Weekly Tuesday
+                "SENSEX" => weekly(Weekday::Thu) == today,    // Weekly Thursday
                 "BANKNIFTY" | "FINNIFTY" | "MIDCPNIFTY" | "NIFTYNXT50" =>
-                    dow == 3 && is_last_of_weekday, // Monthly: last Tuesday only
+                    last_tuesday == Some(today),               // Monthly: last Tuesday only
                 _ => continue,
             };
             if !is_expiry_for_symbol {
@@ -946,6 +1311,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     let dummy_votes = VoteBreakdown {
                         ema_crossover: 0.0, rsi: 0.0, macd: 0.0, supertrend: 0.0,
                         bollinger: 0.0, vwap: 0.0, momentum: 0.0, volume: 0.0,
+                        cumulative_delta: None, imbalance: None, vw_macd: None, impulse: None,
                     };
                     // Sell straddle: sell ATM CE + PE for theta decay
                     out_signals.push(ScanSignal {
@@ -958,6 +1324,10 @@ pub fn compute(data: Value) -> Result<Value, String> {
                         indicators: dummy_ind,
                         votes: dummy_votes,
                         strategy: Some("expiry_theta".into()),
+                        slippage_bps: None,
+                        exits: None,
+                        liquidity_score: None,
+                        target_odds: None,
                     });
                 }
             }
@@ -976,6 +1346,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
                     let dummy_votes = VoteBreakdown {
                         ema_crossover: 0.0, rsi: 0.0, macd: 0.0, supertrend: 0.0,
                         bollinger: 0.0, vwap: 0.0, momentum: round3(momentum), volume: 0.0,
+                        cumulative_delta: None, imbalance: None, vw_macd: None, impulse: None,
                     };
                     // Directional gamma play with ATM options
                     out_signals.push(ScanSignal {
@@ -988,19 +1359,143 @@ pub fn compute(data: Value) -> Result<Value, String> {
                         indicators: dummy_ind,
                         votes: dummy_votes,
                         strategy: Some("expiry_gamma".into()),
+                        slippage_bps: None,
+                        exits: None,
+                        liquidity_score: None,
+                        target_odds: None,
                     });
                 }
             }
         }
     }
 
+    for sig in &mut out_signals {
+        sig.slippage_bps = per_symbol(&slippage_by_symbol, &sig.symbol);
+        sig.liquidity_score = per_symbol(&liquidity_by_symbol, &sig.symbol);
+    }
+
+    if let Some(spec) = &input.exits {
+        for sig in &mut out_signals {
+            if let Some((candles, atr)) = exit_inputs.get(&sig.symbol) {
+                let is_short = sig.direction == "SELL";
+                sig.exits = Some(spec.levels(candles, candles.len() - 1, *atr, sig.entry, Some(sig.stop_loss), is_short));
+            }
+        }
+    }
+
+    if let Some(spec) = &input.target_odds {
+        let mut days = spec.days;
+        if let Some(expiry) = &spec.expiry {
+            let expiry = chrono::NaiveDate::parse_from_str(expiry.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("Invalid target_odds.expiry '{}'", expiry))?;
+            if let Some(today) = today {
+                let left = calendar.trading_days_between(today + chrono::Duration::days(1), expiry);
+                days = days.min(left.max(1));
+            }
+        }
+        let seed = spec.seed.unwrap_or(42);
+        for sig in &mut out_signals {
+            if let Some(iv) = per_symbol(&spec.iv, &sig.symbol) {
+                let is_short = sig.direction == "SELL";
+                sig.target_odds = crate::target_odds::simulate(
+                    sig.entry, sig.stop_loss, sig.target, is_short, iv, days, spec.num_paths, seed);
+            }
+        }
+    }
+
     out_signals.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
 
-    let output = ScanOutput { signals: out_signals };
+    let clusters = match input.correlation_dedup {
+        Some(dedup) => {
+            let (kept, clusters) = dedupe_correlated(out_signals, &returns_by_symbol, dedup.threshold);
+            out_signals = kept;
+            Some(clusters)
+        }
+        None => None,
+    };
+
+    let output = ScanOutput { signals: out_signals, clusters, event_blocked };
     serde_json::to_value(output).map_err(|e| format!("Serialization error: {}", e))
 }
 
+/// Value for a signal's symbol. Derived signals (pair legs, expiry plays)
+/// are named `<symbol>_<SUFFIX>` and take their underlying's value.
+fn per_symbol(map: &HashMap<String, f64>, symbol: &str) -> Option<f64> {
+    map.get(symbol).copied().or_else(|| {
+        map.iter()
+            .find(|(sym, _)| symbol.strip_prefix(sym.as_str()).is_some_and(|r| r.starts_with('_')))
+            .map(|(_, v)| *v)
+    })
+}
+
+/// Greedy correlation clustering per direction. Symbols are visited by
+/// their best signal's confidence (signals arrive sorted) and join the first
+/// cluster whose representative they correlate with at `threshold` or more;
+/// otherwise they start a cluster. Signals of non-representative members are
+/// dropped. Signals without return history (pair legs, expiry plays) pass
+/// through untouched. Only clusters with more than one member are reported.
+fn dedupe_correlated(
+    signals: Vec<ScanSignal>,
+    returns: &HashMap<String, Vec<f64>>,
+    threshold: f64,
+) -> (Vec<ScanSignal>, Vec<SignalCluster>) {
+    let mut clusters: Vec<SignalCluster> = Vec::new();
+    for sig in &signals {
+        let Some(r) = returns.get(&sig.symbol) else { continue };
+        if clusters.iter().any(|c| c.direction == sig.direction && c.members.iter().any(|m| m.symbol == sig.symbol)) {
+            continue;
+        }
+        let joined = clusters.iter_mut().filter(|c| c.direction == sig.direction).find_map(|c| {
+            let corr = tail_correlation(&returns[&c.representative], r);
+            (corr >= threshold).then_some((c, corr))
+        });
+        let member = |correlation: f64| ClusterMember { symbol: sig.symbol.clone(), confidence: sig.confidence, correlation: round3(correlation) };
+        match joined {
+            Some((c, corr)) => c.members.push(member(corr)),
+            None => clusters.push(SignalCluster {
+                direction: sig.direction.clone(),
+                representative: sig.symbol.clone(),
+                members: vec![member(1.0)],
+            }),
+        }
+    }
+
+    let dropped = |sig: &ScanSignal| clusters.iter()
+        .any(|c| c.direction == sig.direction && c.representative != sig.symbol && c.members.iter().any(|m| m.symbol == sig.symbol));
+    let kept = signals.into_iter().filter(|s| !dropped(s)).collect();
+    clusters.retain(|c| c.members.len() > 1);
+    (kept, clusters)
+}
+
+/// Correlation over the common trailing window; 0 with fewer than 3 points.
+fn tail_correlation(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len().min(b.len());
+    if n < 3 {
+        return 0.0;
+    }
+    let c = crate::utils::pearson_correlation(&a[a.len() - n..], &b[b.len() - n..]);
+    if c.is_finite() { c } else { 0.0 }
+}
+
 /// Momentum score based on consecutive candle direction and rate of change
+/// Pull the target in to the nearest opposing liquidity zone between entry and
+/// the ATR target: resistance for longs, support for shorts.
+fn zone_capped_target(candles: &[Candle], direction: &str, close: f64, target: f64) -> f64 {
+    let flows: Vec<(f64, f64)> = candles.iter().map(advanced_signals::estimate_bar_flow).collect();
+    let zones = advanced_signals::detect_liquidity_zones(candles, &flows, 20);
+    if direction == "BUY" {
+        zones.iter()
+            .filter(|z| z.kind == "RESISTANCE" && z.price_low > close && z.price_low < target)
+            .map(|z| z.price_low)
+            .fold(target, f64::min)
+    } else {
+        zones.iter()
+            .filter(|z| z.kind == "SUPPORT" && z.price_high < close && z.price_high > target)
+            .map(|z| z.price_high)
+            .fold(target, f64::max)
+    }
+}
+
 fn calc_momentum(candles: &[Candle], lookback: usize) -> f64 {
     let n = candles.len();
     if n < lookback + 1 {
@@ -1182,6 +1677,71 @@ mod tests {
         assert!(!sell_signals.is_empty(), "steadily falling prices should produce SELL");
     }
 
+    #[test]
+    fn test_options_sentiment_tilts_composite() {
+        let closes: Vec<f64> = (0..30).map(|i| 100.0 + i as f64 * 0.8 + ((i * 7) % 5) as f64 * 0.6).collect();
+        let candles = serde_json::to_value(make_candles(&closes)).unwrap();
+        let composite = |score: f64| -> f64 {
+            let result = run_scan(json!({
+                "symbols": [{ "symbol": "X", "candles": candles, "options_sentiment": { "sentiment_score": score, "sentiment": "ignored" } }],
+                "aggressiveness": "high"
+            }));
+            result["signals"].as_array().unwrap().iter()
+                .find(|s| s["strategy"] == "composite" && s["direction"] == "BUY")
+                .map_or(0.0, |s| s["confidence"].as_f64().unwrap())
+        };
+        let (bull, bear) = (composite(1.0), composite(-1.0));
+        assert!(bull > 0.0, "expected a composite BUY");
+        assert!((bull - bear - 2.0 * OPTIONS_SENTIMENT_WEIGHT).abs() < 1e-3, "bull {} bear {}", bull, bear);
+    }
+
+    #[test]
+    fn test_order_flow_votes_tilt_composite() {
+        let closes: Vec<f64> = (0..30).map(|i| 100.0 + i as f64 * 0.8 + ((i * 7) % 5) as f64 * 0.6).collect();
+        let candles = serde_json::to_value(make_candles(&closes)).unwrap();
+        let flow = |sign: f64| {
+            let deltas: Vec<Value> = (1..=10).map(|i| json!({ "delta": sign * 100.0, "cumulative": sign * 100.0 * i as f64 })).collect();
+            json!({ "imbalance_ratio": sign * 0.4, "signal": "ignored", "recent_deltas": deltas })
+        };
+        let scan = |order_flow: Value| {
+            let result = run_scan(json!({
+                "symbols": [{ "symbol": "X", "candles": candles, "order_flow": order_flow }],
+                "aggressiveness": "high"
+            }));
+            result["signals"].as_array().unwrap().iter()
+                .find(|s| s["strategy"] == "composite")
+                .cloned()
+                .unwrap_or(Value::Null)
+        };
+        let plain = scan(Value::Null);
+        assert!(plain["votes"].get("cumulative_delta").is_none());
+        let buying = scan(flow(1.0));
+        assert_eq!(buying["votes"]["cumulative_delta"], 1.0);
+        assert_eq!(buying["votes"]["imbalance"], 1.0);
+        let selling = scan(flow(-1.0));
+        let confidence = |s: &Value| if s["direction"] == "BUY" { s["confidence"].as_f64().unwrap() } else { -s["confidence"].as_f64().unwrap_or(0.0) };
+        assert!(confidence(&buying) > confidence(&plain));
+        assert!(confidence(&selling) < confidence(&plain));
+    }
+
+    #[test]
+    fn test_vw_macd_and_impulse_votes_are_opt_in() {
+        let closes: Vec<f64> = (0..40).map(|i| 100.0 * 1.004f64.powi(i * i / 10)).collect();
+        let candles = serde_json::to_value(make_candles(&closes)).unwrap();
+        let scan = |weights: Value| {
+            run_scan(json!({ "symbols": [{ "symbol": "X", "candles": candles }], "aggressiveness": "high", "vote_weights": weights }))["signals"]
+                .as_array().unwrap().iter().find(|s| s["strategy"] == "composite").cloned().unwrap()
+        };
+        let plain = scan(json!({}));
+        assert!(plain["votes"].get("impulse").is_none() && plain["votes"].get("vw_macd").is_none());
+        let weighted = scan(json!({ "vw_macd": 0.2, "impulse": 0.2 }));
+        // An accelerating rise: both composites agree with the trend.
+        assert_eq!(weighted["votes"]["impulse"], 1.0);
+        assert!(weighted["votes"]["vw_macd"].as_f64().unwrap() > 0.0);
+        assert_eq!(weighted["direction"], "BUY");
+        assert_ne!(weighted["confidence"], plain["confidence"]);
+    }
+
     #[test]
     fn test_flat_prices_low_confidence() {
         let candles = make_candles(&vec![100.0; 30]);
@@ -1404,6 +1964,27 @@ mod tests {
         assert!(!expiry_signals.is_empty(), "expiry day should produce expiry signals for NIFTY");
     }
 
+    #[test]
+    fn test_holiday_moves_expiry_to_previous_day() {
+        // Thursday 2025-04-10 (Mahavir Jayanti) is a holiday, so the SENSEX
+        // weekly expiry is on Wednesday the 9th.
+        let closes: Vec<f64> = (0..30).map(|i| 75000.0 + (i as f64 * 10.0).sin() * 150.0).collect();
+        let data: Vec<(f64, f64)> = closes.iter().map(|&c| (c, 100000.0)).collect();
+        let candles = serde_json::to_value(make_candles_with_volume(&data)).unwrap();
+        let expiry_count = |date: &str| {
+            let result = run_scan(json!({
+                "symbols": [{ "symbol": "SENSEX", "candles": candles.clone() }],
+                "aggressiveness": "low",
+                "current_date": date
+            }));
+            result["signals"].as_array().unwrap().iter()
+                .filter(|s| s["strategy"].as_str().is_some_and(|s| s.starts_with("expiry_")))
+                .count()
+        };
+        assert!(expiry_count("2025-04-09") > 0);
+        assert_eq!(expiry_count("2025-04-10"), 0);
+    }
+
     #[test]
     fn test_non_expiry_day_no_expiry_signals() {
         // 2026-03-04 is a Wednesday (not a Tuesday — not expiry)
@@ -1500,6 +2081,206 @@ mod tests {
             "strong trend with volume should trigger multiple strategies, got: {:?}", strategies);
     }
 
+    #[test]
+    fn test_min_rvol_filter() {
+        let mut candles = Vec::new();
+        for day in 1..=6 {
+            for k in 0..6 {
+                let c = 100.0 + (day * 6 + k) as f64;
+                let vol = if day == 6 && k == 5 { 100.0 } else { 1000.0 };
+                candles.push(Candle {
+                    timestamp: format!("2025-01-{:02}T09:{:02}:00", day, 15 + k * 5),
+                    open: c * 0.998, high: c * 1.01, low: c * 0.99, close: c, volume: vol,
+                });
+            }
+        }
+        let candles_json = serde_json::to_value(&candles).unwrap();
+        let base = json!({ "symbols": [{ "symbol": "THIN", "candles": candles_json }], "aggressiveness": "high" });
+        assert!(!run_scan(base.clone())["signals"].as_array().unwrap().is_empty());
+
+        let mut filtered = base;
+        filtered["min_rvol"] = json!(0.5);
+        assert!(run_scan(filtered)["signals"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_slippage_assumptions_per_symbol() {
+        let closes: Vec<f64> = (0..30).map(|i| 100.0 + i as f64 * 2.0).collect();
+        let data: Vec<(f64, f64)> = closes.iter().enumerate()
+            .map(|(i, &c)| (c, 1000.0 + i as f64 * 200.0))
+            .collect();
+        let candles_json = serde_json::to_value(make_candles_with_volume(&data)).unwrap();
+        let base = json!({ "symbols": [{ "symbol": "UP", "candles": candles_json }], "aggressiveness": "high" });
+        let signals = run_scan(base.clone())["signals"].as_array().unwrap().clone();
+        assert!(!signals.is_empty());
+        assert!(signals.iter().all(|s| s.get("slippage_bps").is_none()));
+
+        let mut given = base.clone();
+        given["slippage_bps"] = json!({ "UP": 7.5 });
+        let signals = run_scan(given)["signals"].as_array().unwrap().clone();
+        assert!(signals.iter().all(|s| s["slippage_bps"] == 7.5));
+
+        let mut auto = base;
+        auto["auto_slippage"] = json!(true);
+        let est = run_scan(auto.clone())["signals"][0]["slippage_bps"].as_f64().unwrap();
+        assert!(est > 0.0);
+        auto["max_slippage_bps"] = json!(est / 2.0);
+        assert!(run_scan(auto)["signals"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_correlation_dedup_keeps_strongest_per_cluster() {
+        // Five banks share one return path (scaled), TECH follows its own.
+        let series = |scale: f64, phase: f64| -> serde_json::Value {
+            let data: Vec<(f64, f64)> = (0..30)
+                .map(|i| (scale * (100.0 + i as f64 * 2.0 + 3.0 * (i as f64 * 0.9 + phase).sin()), 1000.0 + i as f64 * 200.0))
+                .collect();
+            serde_json::to_value(make_candles_with_volume(&data)).unwrap()
+        };
+        let mut symbols: Vec<serde_json::Value> = ["HDFCBANK", "ICICIBANK", "SBIN", "AXISBANK", "KOTAKBANK"].iter()
+            .enumerate()
+            .map(|(k, s)| json!({ "symbol": s, "candles": series(1.0 + k as f64 * 0.5, 0.0) }))
+            .collect();
+        symbols.push(json!({ "symbol": "TECH", "candles": series(1.0, 2.0) }));
+        let base = json!({ "symbols": symbols, "aggressiveness": "high" });
+        let plain = run_scan(base.clone());
+        assert!(plain.get("clusters").is_none());
+        let buys = |v: &serde_json::Value| -> std::collections::BTreeSet<String> {
+            v["signals"].as_array().unwrap().iter()
+                .filter(|s| s["direction"] == "BUY")
+                .map(|s| s["symbol"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(buys(&plain).len(), 6);
+
+        let mut dedup = base;
+        dedup["correlation_dedup"] = json!({ "threshold": 0.9 });
+        let out = run_scan(dedup);
+        let kept = buys(&out);
+        let clusters = out["clusters"].as_array().unwrap();
+        let buy_clusters: Vec<_> = clusters.iter().filter(|c| c["direction"] == "BUY").collect();
+        assert_eq!(buy_clusters.len(), 1);
+        let banks = buy_clusters[0];
+        assert_eq!(banks["members"].as_array().unwrap().len(), 5);
+        assert!(kept.contains(banks["representative"].as_str().unwrap()));
+        assert!(kept.contains("TECH"));
+        assert_eq!(kept.len(), 2);
+    }
+
+    #[test]
+    fn test_exit_levels_reported() {
+        let closes: Vec<f64> = (0..30).map(|i| 100.0 + i as f64 * 2.0).collect();
+        let data: Vec<(f64, f64)> = closes.iter().enumerate()
+            .map(|(i, &c)| (c, 1000.0 + i as f64 * 200.0))
+            .collect();
+        let candles_json = serde_json::to_value(make_candles_with_volume(&data)).unwrap();
+        let input = json!({
+            "symbols": [{ "symbol": "UP", "candles": candles_json }],
+            "aggressiveness": "high",
+            "exits": { "chandelier": { "period": 10, "atr_mult": 2.0 }, "swing_trail": { "lookback": 3 }, "break_even_r": 1.0 },
+        });
+        let out = run_scan(input);
+        let buy = out["signals"].as_array().unwrap().iter().find(|s| s["direction"] == "BUY").unwrap();
+        let exits = &buy["exits"];
+        let (entry, stop) = (buy["entry"].as_f64().unwrap(), buy["stop_loss"].as_f64().unwrap());
+        // Swing low of the last 3 bars: 154 * 0.99.
+        assert_eq!(exits["swing"], 152.46);
+        assert!(exits["chandelier"].as_f64().unwrap() < entry);
+        assert!((exits["break_even_at"].as_f64().unwrap() - (2.0 * entry - stop)).abs() < 0.02);
+        assert!(compute(json!({ "symbols": [], "exits": { "chandelier": { "atr_mult": 0 } } })).is_err());
+    }
+
+    #[test]
+    fn test_turnover_liquidity_filter() {
+        let closes: Vec<f64> = (0..30).map(|i| 100.0 + i as f64 * 2.0).collect();
+        let data: Vec<(f64, f64)> = closes.iter().enumerate()
+            .map(|(i, &c)| (c, 1000.0 + i as f64 * 200.0))
+            .collect();
+        let candles_json = serde_json::to_value(make_candles_with_volume(&data)).unwrap();
+        let base = json!({ "symbols": [{ "symbol": "SMALL", "candles": candles_json }], "aggressiveness": "high" });
+        assert!(run_scan(base.clone())["signals"][0].get("liquidity_score").is_none());
+
+        // Roughly ₹6 lakh a day.
+        let mut loose = base.clone();
+        loose["min_turnover"] = json!(100_000.0);
+        let signals = run_scan(loose)["signals"].as_array().unwrap().clone();
+        assert!(!signals.is_empty());
+        assert!(signals.iter().all(|s| s["liquidity_score"] == 0.0));
+
+        let mut strict = base.clone();
+        strict["min_turnover"] = json!(10_000_000.0);
+        assert!(run_scan(strict)["signals"].as_array().unwrap().is_empty());
+        let mut scored = base;
+        scored["min_liquidity_score"] = json!(0.1);
+        assert!(run_scan(scored)["signals"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_event_blackout_skips_symbols() {
+        let closes: Vec<f64> = (0..30).map(|i| 100.0 + i as f64 * 2.0).collect();
+        let data: Vec<(f64, f64)> = closes.iter().enumerate()
+            .map(|(i, &c)| (c, 1000.0 + i as f64 * 200.0))
+            .collect();
+        let candles_json = serde_json::to_value(make_candles_with_volume(&data)).unwrap();
+        let input = |date: &str| json!({
+            "symbols": [{ "symbol": "INFY", "candles": candles_json }],
+            "aggressiveness": "high",
+            "current_date": date,
+            "events": [{ "date": "2025-07-18", "kind": "earnings", "symbol": "INFY" }],
+            "event_blackout": { "days_before": 2 },
+        });
+        let blocked = run_scan(input("2025-07-17"));
+        assert!(blocked["signals"].as_array().unwrap().is_empty());
+        assert_eq!(blocked["event_blocked"][0]["event"]["days"], -1);
+        let clear = run_scan(input("2025-07-10"));
+        assert!(!clear["signals"].as_array().unwrap().is_empty());
+        assert_eq!(clear["event_blocked"], json!([]));
+    }
+
+    #[test]
+    fn test_target_odds_for_symbols_with_iv() {
+        let closes: Vec<f64> = (0..30).map(|i| 100.0 + i as f64 * 2.0).collect();
+        let data: Vec<(f64, f64)> = closes.iter().enumerate()
+            .map(|(i, &c)| (c, 1000.0 + i as f64 * 200.0))
+            .collect();
+        let candles_json = serde_json::to_value(make_candles_with_volume(&data)).unwrap();
+        let result = run_scan(json!({
+            "symbols": [
+                { "symbol": "INFY", "candles": candles_json },
+                { "symbol": "TCS", "candles": candles_json },
+            ],
+            "aggressiveness": "high",
+            // Tuesday; the 2025-07-29 expiry is five sessions away.
+            "current_date": "2025-07-22",
+            "target_odds": { "iv": { "INFY": 0.3 }, "days": 10, "expiry": "2025-07-29", "num_paths": 2000 },
+        }));
+        let signals = result["signals"].as_array().unwrap();
+        let odds: Vec<_> = signals.iter().filter(|s| s["symbol"] == "INFY").map(|s| &s["target_odds"]).collect();
+        assert!(!odds.is_empty());
+        for o in odds {
+            assert_eq!(o["days"], 5);
+            let total = o["prob_target"].as_f64().unwrap() + o["prob_stop"].as_f64().unwrap() + o["prob_open"].as_f64().unwrap();
+            assert!((total - 1.0).abs() < 1e-3);
+        }
+        assert!(signals.iter().filter(|s| s["symbol"] == "TCS").all(|s| s.get("target_odds").is_none()));
+
+        let bad = compute(json!({ "symbols": [], "target_odds": { "iv": { "INFY": -0.3 } } }));
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn test_zone_capped_target_uses_resistance() {
+        let mut candles = make_candles(&[100.0; 12]);
+        candles.push(Candle {
+            timestamp: "2025-01-13".into(), open: 108.0, high: 110.0, low: 107.0, close: 108.1, volume: 6000.0,
+        });
+        candles.extend(make_candles(&[100.0; 3]));
+        let capped = zone_capped_target(&candles, "BUY", 100.0, 115.0);
+        assert_eq!(capped, 107.0);
+        // No support zone below entry, so a short target is left untouched.
+        assert_eq!(zone_capped_target(&candles, "SELL", 100.0, 90.0), 90.0);
+    }
+
     #[test]
     fn test_open_field_in_candle_deserialization() {
         let json_str = r#"{"close":100.0,"high":101.0,"low":99.0,"volume":1000.0,"open":99.5}"#;
@@ -0,0 +1,454 @@
+//! What-if analysis: reprice a book of stock, futures and options under
+//! spot, volatility and time shocks.
+//!
+//! Positions use the `strategy_payoff` leg fields plus an `underlying`
+//! whose current price comes from `spots` (or the position's own `spot`).
+//! Shocks are an explicit `scenarios` list or the grid `spot_pct` ×
+//! `vol_pts` × `days`. A spot shock moves every underlying by
+//! `beta × spot_pct` (`betas`, default 1); `vol_pts` adds IV points; `days`
+//! ages the book, and options expired by then are worth their intrinsic
+//! value. `pnl` is measured against today's mark, `pnl_vs_entry` against
+//! the entry premium or price.
+//!
+//! With a `beta_index` (e.g. "NIFTY") the book's deltas are also reported
+//! beta-weighted into index units. Underlyings missing from `betas` get a
+//! beta estimated from `returns` against the index's own series, and that
+//! beta drives their spot shocks too.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::strategy_payoff::{resolve_legs, valuation_date, Leg, LegInput, LegKind};
+use crate::hedge::beta_of;
+use crate::utils::{bs_greeks, round2, round4};
+
+#[derive(Deserialize)]
+struct ScenarioConfig {
+    positions: Vec<PositionIn>,
+    #[serde(default)]
+    spots: HashMap<String, f64>,
+    #[serde(default)]
+    betas: HashMap<String, f64>,
+    #[serde(default)]
+    beta_index: Option<String>,
+    /// Periodic returns by symbol, aligned at the latest observation.
+    #[serde(default)]
+    returns: HashMap<String, Vec<f64>>,
+    #[serde(default)]
+    scenarios: Vec<Shock>,
+    #[serde(default)]
+    spot_pct: Vec<f64>,
+    #[serde(default)]
+    vol_pts: Vec<f64>,
+    #[serde(default)]
+    days: Vec<f64>,
+    #[serde(default)]
+    valuation_date: Option<String>,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    #[serde(default = "default_iv")]
+    default_iv: f64,
+    /// Include the per-position breakdown in every scenario.
+    #[serde(default = "default_true")]
+    per_position: bool,
+}
+
+fn default_rate() -> f64 { 0.065 }
+fn default_iv() -> f64 { 0.2 }
+fn default_true() -> bool { true }
+
+#[derive(Deserialize)]
+struct PositionIn {
+    #[serde(alias = "symbol")]
+    underlying: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    spot: Option<f64>,
+    #[serde(flatten)]
+    leg: LegInput,
+}
+
+#[derive(Deserialize, Clone, Default)]
+struct Shock {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    spot_pct: f64,
+    #[serde(default)]
+    vol_pts: f64,
+    #[serde(default)]
+    days: f64,
+}
+
+#[derive(Serialize, Default, Clone, Copy)]
+struct Greeks {
+    delta: f64,
+    gamma: f64,
+    /// Per calendar day.
+    theta: f64,
+    /// Per 1 vol point.
+    vega: f64,
+}
+
+impl Greeks {
+    fn add(&mut self, g: Greeks) {
+        self.delta += g.delta;
+        self.gamma += g.gamma;
+        self.theta += g.theta;
+        self.vega += g.vega;
+    }
+
+    fn rounded(self) -> Greeks {
+        Greeks { delta: round4(self.delta), gamma: round4(self.gamma), theta: round2(self.theta), vega: round2(self.vega) }
+    }
+}
+
+#[derive(Serialize)]
+struct PositionResult {
+    id: String,
+    underlying: String,
+    spot: f64,
+    value: f64,
+    pnl: f64,
+    pnl_vs_entry: f64,
+    greeks: Greeks,
+}
+
+#[derive(Serialize)]
+struct ScenarioResult {
+    name: String,
+    spot_pct: f64,
+    vol_pts: f64,
+    days: f64,
+    value: f64,
+    pnl: f64,
+    pnl_vs_entry: f64,
+    greeks: Greeks,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    beta_weighted_delta: Option<f64>,
+    positions: Option<Vec<PositionResult>>,
+}
+
+#[derive(Serialize)]
+struct Extreme {
+    name: String,
+    pnl: f64,
+}
+
+/// Deltas expressed in units of the reference index.
+#[derive(Serialize)]
+struct BetaWeighted {
+    index: String,
+    index_spot: f64,
+    /// Sum of delta × beta × spot / index spot.
+    net_delta: f64,
+    underlyings: Vec<BetaExposure>,
+}
+
+#[derive(Serialize)]
+struct BetaExposure {
+    underlying: String,
+    beta: f64,
+    /// "given", "returns", "index" or "default" (1.0).
+    beta_source: &'static str,
+    delta: f64,
+    weighted_delta: f64,
+}
+
+#[derive(Serialize)]
+struct ScenarioOutput {
+    base_value: f64,
+    base_pnl_vs_entry: f64,
+    base_greeks: Greeks,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    beta_weighted: Option<BetaWeighted>,
+    scenarios: Vec<ScenarioResult>,
+    worst: Option<Extreme>,
+    best: Option<Extreme>,
+}
+
+/// A resolved position: leg, its underlying, today's spot and beta.
+struct Book {
+    id: String,
+    underlying: String,
+    spot: f64,
+    beta: f64,
+    leg: Leg,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: ScenarioConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid scenario config: {}", e))?;
+    if config.positions.is_empty() {
+        return Err("At least one position required".to_string());
+    }
+    let valuation = valuation_date(config.valuation_date.as_deref())?;
+    let rate = config.risk_free_rate;
+    let index = config.beta_index.as_deref();
+    let beta_for = |underlying: &str| -> (f64, &'static str) {
+        if let Some(&b) = config.betas.get(underlying) {
+            return (b, "given");
+        }
+        let Some(index) = index else { return (1.0, "default") };
+        if underlying == index {
+            return (1.0, "index");
+        }
+        match (config.returns.get(underlying), config.returns.get(index)) {
+            (Some(y), Some(x)) if y.len().min(x.len()) >= 2 => {
+                let n = y.len().min(x.len());
+                (beta_of(&y[y.len() - n..], &x[x.len() - n..]), "returns")
+            }
+            _ => (1.0, "default"),
+        }
+    };
+
+    let mut book = Vec::with_capacity(config.positions.len());
+    for (i, p) in config.positions.iter().enumerate() {
+        let spot = p.spot.or_else(|| config.spots.get(&p.underlying).copied())
+            .filter(|s| *s > 0.0)
+            .ok_or_else(|| format!("positions[{}]: no spot for {}", i, p.underlying))?;
+        let leg = resolve_legs(std::slice::from_ref(&p.leg), spot, valuation, rate, config.default_iv)
+            .map_err(|e| e.replace("legs[0]", &format!("positions[{}]", i)))?
+            .remove(0);
+        book.push(Book {
+            id: p.id.clone().unwrap_or_else(|| format!("{}#{}", p.underlying, i)),
+            underlying: p.underlying.clone(),
+            spot,
+            beta: beta_for(&p.underlying).0,
+            leg,
+        });
+    }
+
+    let shocks: Vec<Shock> = if !config.scenarios.is_empty() {
+        config.scenarios.clone()
+    } else {
+        let or = |v: &Vec<f64>, d: Vec<f64>| if v.is_empty() { d } else { v.clone() };
+        let spots = or(&config.spot_pct, vec![-10.0, -5.0, -2.0, 0.0, 2.0, 5.0, 10.0]);
+        let vols = or(&config.vol_pts, vec![0.0]);
+        let days = or(&config.days, vec![0.0]);
+        let mut grid = Vec::with_capacity(spots.len() * vols.len() * days.len());
+        for &d in &days {
+            for &v in &vols {
+                for &s in &spots {
+                    grid.push(Shock { name: None, spot_pct: s, vol_pts: v, days: d });
+                }
+            }
+        }
+        grid
+    };
+
+    let base = evaluate(&book, &Shock::default(), rate);
+    let base_value: f64 = base.iter().map(|p| p.value).sum();
+    let base_entry: f64 = base.iter().map(|p| p.pnl_vs_entry).sum();
+    let mut base_greeks = Greeks::default();
+    base.iter().for_each(|p| base_greeks.add(p.greeks));
+    let index_spot = match index {
+        Some(name) => Some(config.spots.get(name).copied()
+            .or_else(|| book.iter().find(|b| b.underlying == name).map(|b| b.spot))
+            .filter(|s| *s > 0.0)
+            .ok_or_else(|| format!("No spot for beta_index {}", name))?),
+        None => None,
+    };
+    let beta_weighted = index.zip(index_spot).map(|(name, index_spot)| {
+        let mut underlyings: Vec<BetaExposure> = Vec::new();
+        for (p, b) in base.iter().zip(&book) {
+            let weighted = p.greeks.delta * b.beta * b.spot / index_spot;
+            match underlyings.iter_mut().find(|u| u.underlying == b.underlying) {
+                Some(u) => {
+                    u.delta += p.greeks.delta;
+                    u.weighted_delta += weighted;
+                }
+                None => underlyings.push(BetaExposure {
+                    underlying: b.underlying.clone(),
+                    beta: b.beta,
+                    beta_source: beta_for(&b.underlying).1,
+                    delta: p.greeks.delta,
+                    weighted_delta: weighted,
+                }),
+            }
+        }
+        underlyings.sort_by(|a, b| a.underlying.cmp(&b.underlying));
+        let net_delta = underlyings.iter().map(|u| u.weighted_delta).sum();
+        BetaWeighted {
+            index: name.to_string(),
+            index_spot,
+            net_delta: round4(net_delta),
+            underlyings: underlyings.into_iter().map(|u| BetaExposure {
+                beta: round4(u.beta),
+                delta: round4(u.delta),
+                weighted_delta: round4(u.weighted_delta),
+                ..u
+            }).collect(),
+        }
+    });
+
+    let scenarios: Vec<ScenarioResult> = shocks.iter().map(|shock| {
+        let mut positions = evaluate(&book, shock, rate);
+        let mut greeks = Greeks::default();
+        let (mut value, mut entry, mut weighted) = (0.0, 0.0, 0.0);
+        for ((p, b), pos) in positions.iter_mut().zip(&base).zip(&book) {
+            p.pnl = p.value - b.value;
+            value += p.value;
+            entry += p.pnl_vs_entry;
+            greeks.add(p.greeks);
+            weighted += p.greeks.delta * pos.beta * p.spot;
+        }
+        // The index itself moves by the unscaled shock.
+        let beta_weighted_delta = index_spot
+            .map(|s| round4(weighted / (s * (1.0 + shock.spot_pct / 100.0)).max(f64::EPSILON)));
+        let name = shock.name.clone().unwrap_or_else(|| format!("spot {:+}% vol {:+} T+{}", shock.spot_pct, shock.vol_pts, shock.days));
+        ScenarioResult {
+            name,
+            spot_pct: shock.spot_pct,
+            vol_pts: shock.vol_pts,
+            days: shock.days,
+            value: round2(value),
+            pnl: round2(value - base_value),
+            pnl_vs_entry: round2(entry),
+            greeks: greeks.rounded(),
+            beta_weighted_delta,
+            positions: config.per_position.then(|| positions.into_iter().map(|p| PositionResult {
+                value: round2(p.value),
+                pnl: round2(p.pnl),
+                pnl_vs_entry: round2(p.pnl_vs_entry),
+                greeks: p.greeks.rounded(),
+                ..p
+            }).collect()),
+        }
+    }).collect();
+
+    let extreme = |better: fn(f64, f64) -> bool| scenarios.iter()
+        .fold(None::<&ScenarioResult>, |acc, s| match acc { Some(a) if !better(s.pnl, a.pnl) => Some(a), _ => Some(s) })
+        .map(|s| Extreme { name: s.name.clone(), pnl: s.pnl });
+    let result = ScenarioOutput {
+        base_value: round2(base_value),
+        base_pnl_vs_entry: round2(base_entry),
+        base_greeks: base_greeks.rounded(),
+        beta_weighted,
+        worst: extreme(|a, b| a < b),
+        best: extreme(|a, b| a > b),
+        scenarios,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Unrounded per-position value and greeks under one shock; `pnl` is left 0.
+fn evaluate(book: &[Book], shock: &Shock, rate: f64) -> Vec<PositionResult> {
+    book.iter().map(|b| {
+        let spot = b.spot * (1.0 + b.beta * shock.spot_pct / 100.0).max(0.0);
+        let leg = Leg { iv: (b.leg.iv + shock.vol_pts / 100.0).max(0.01), ..b.leg.clone() };
+        let value = leg.value(spot, shock.days, rate) * leg.units;
+        let t = ((leg.expiry_days - shock.days) / 365.0).max(0.0);
+        let greeks = match leg.kind {
+            LegKind::Underlying => Greeks { delta: leg.units, ..Greeks::default() },
+            kind => {
+                let (delta, gamma, theta, vega, _) = bs_greeks(spot, leg.strike, t, rate, leg.iv, kind == LegKind::Call);
+                Greeks { delta: delta * leg.units, gamma: gamma * leg.units, theta: theta * leg.units, vega: vega * leg.units }
+            }
+        };
+        PositionResult {
+            id: b.id.clone(),
+            underlying: b.underlying.clone(),
+            spot: round2(spot),
+            value,
+            pnl: 0.0,
+            pnl_vs_entry: leg.pnl(spot, shock.days, rate),
+            greeks,
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn book() -> Value {
+        json!({
+            "valuation_date": "2024-06-03",
+            "spots": { "NIFTY": 22000.0, "RELIANCE": 2900.0 },
+            "betas": { "RELIANCE": 1.2 },
+            "positions": [
+                { "underlying": "NIFTY", "id": "short_call", "option_type": "call", "strike": 22500.0, "qty": -1, "lot_size": 50, "premium": 120.0, "expiry_days": 20, "iv": 0.15 },
+                { "underlying": "NIFTY", "option_type": "put", "strike": 21500.0, "qty": 1, "lot_size": 50, "premium": 90.0, "expiry_days": 20, "iv": 0.16 },
+                { "symbol": "RELIANCE", "option_type": "stock", "strike": 2800.0, "qty": 100 }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_spot_grid_and_stock_beta() {
+        let result = compute(book()).unwrap();
+        let s = result["scenarios"].as_array().unwrap();
+        assert_eq!(s.len(), 7);
+        // The unshocked scenario has no P&L against today's mark.
+        assert_eq!(s[3]["pnl"], 0.0);
+        // The stock is +10,000 vs entry and moves 1.2 × 10% × 290,000 on the +10% shock.
+        let stock = &s[6]["positions"][2];
+        assert_eq!(stock["pnl"], 34_800.0);
+        assert_eq!(stock["pnl_vs_entry"], 44_800.0);
+        assert_eq!(stock["greeks"]["delta"], 100.0);
+        assert_eq!(s[3]["positions"][0]["id"], "short_call");
+        assert_eq!(s[3]["positions"][1]["id"], "NIFTY#1");
+        // Short call plus long put: the book loses on a rally more than on a sell-off.
+        assert!(result["base_greeks"]["delta"].as_f64().unwrap() < 100.0);
+        assert_eq!(result["worst"]["name"], "spot +10% vol +0 T+0");
+    }
+
+    #[test]
+    fn test_beta_weighted_delta_in_index_units() {
+        let mut data = book();
+        let index: Vec<f64> = (0..30).map(|i| ((i * 7 % 11) as f64 - 5.0) / 1000.0).collect();
+        data["spots"]["HDFCBANK"] = json!(1500.0);
+        data["positions"].as_array_mut().unwrap().push(json!({ "symbol": "HDFCBANK", "option_type": "stock", "strike": 1500.0, "qty": -200 }));
+        data["beta_index"] = json!("NIFTY");
+        data["returns"] = json!({
+            "NIFTY": index,
+            "HDFCBANK": index[10..].iter().map(|r| r * 0.8).collect::<Vec<_>>(),
+        });
+        let result = compute(data.clone()).unwrap();
+        let bw = &result["beta_weighted"];
+        assert_eq!(bw["index_spot"], 22000.0);
+        let u = bw["underlyings"].as_array().unwrap();
+        assert_eq!(u[0]["underlying"], "HDFCBANK");
+        assert_eq!(u[0]["beta_source"], "returns");
+        assert_eq!(u[0]["beta"], 0.8);
+        assert_eq!(u[0]["weighted_delta"], round4(-200.0 * 0.8 * 1500.0 / 22000.0));
+        assert_eq!((u[1]["underlying"].as_str(), u[1]["beta_source"].as_str()), (Some("NIFTY"), Some("index")));
+        assert_eq!(u[1]["delta"], u[1]["weighted_delta"]);
+        assert_eq!(u[2]["beta_source"], "given");
+        assert_eq!(u[2]["weighted_delta"], round4(100.0 * 1.2 * 2900.0 / 22000.0));
+        let net: f64 = u.iter().map(|x| x["weighted_delta"].as_f64().unwrap()).sum();
+        assert!((bw["net_delta"].as_f64().unwrap() - net).abs() < 1e-3);
+        let s = result["scenarios"].as_array().unwrap();
+        assert_eq!(s[3]["beta_weighted_delta"], bw["net_delta"]);
+        // The estimated beta also scales the stock's spot shock.
+        assert_eq!(s[6]["positions"][3]["spot"], 1620.0);
+
+        assert!(compute(book()).unwrap().get("beta_weighted").is_none());
+        data["beta_index"] = json!("BANKNIFTY");
+        assert!(compute(data).unwrap_err().contains("BANKNIFTY"));
+    }
+
+    #[test]
+    fn test_vol_and_time_shocks() {
+        let mut data = book();
+        data["positions"] = json!([data["positions"][0].clone()]);
+        data["scenarios"] = json!([
+            { "name": "vol up", "vol_pts": 5 },
+            { "name": "decay", "days": 10 },
+            { "name": "expired", "days": 30 }
+        ]);
+        let result = compute(data).unwrap();
+        let s = result["scenarios"].as_array().unwrap();
+        // Short option: long theta, short vega.
+        assert!(s[0]["pnl"].as_f64().unwrap() < 0.0);
+        assert!(s[1]["pnl"].as_f64().unwrap() > 0.0);
+        assert!(result["base_greeks"]["theta"].as_f64().unwrap() > 0.0);
+        // OTM call expired worthless keeps the full premium.
+        assert_eq!(s[2]["pnl_vs_entry"], 6000.0);
+        assert_eq!(s[2]["greeks"]["gamma"], 0.0);
+        assert!(compute(json!({ "positions": [{ "underlying": "X", "option_type": "call", "strike": 100.0, "qty": 1, "expiry_days": 5 }] })).is_err());
+    }
+}
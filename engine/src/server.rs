@@ -3,6 +3,7 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use axum::{
     Router,
     extract::{State, Json, Path, WebSocketUpgrade, ws},
+    http::HeaderMap,
     response::IntoResponse,
     routing::{get, post},
     http::StatusCode,
@@ -15,7 +16,7 @@ use serde_json::json;
 use tracing::{info, warn, error};
 
 use crate::state::{AppState, Position};
-use crate::{Request, Response, handle_request};
+use crate::{Reply, Request, Response, handle_message, handle_request};
 use crate::config::TlsConfig;
 
 type SharedState = Arc<AppState>;
@@ -136,6 +137,8 @@ pub async fn run(state: SharedState) {
         .route("/metrics", get(metrics))
 
         .route("/rpc", post(rpc_handler))
+        // Any engine command, body = the command's `data` payload
+        .route("/api/cmd/{command}", post(cmd_generic))
 
         .route("/api/backtest", post(cmd_backtest))
         .route("/api/signals", post(cmd_signals))
@@ -228,7 +231,7 @@ pub async fn run(state: SharedState) {
         ))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
-        .layer(axum::extract::DefaultBodyLimit::max(2 * 1024 * 1024))
+        .layer(axum::extract::DefaultBodyLimit::max(state.config.server.max_body_mb.max(1) * 1024 * 1024))
         .with_state(state.clone());
 
     let addr = format!("{}:{}", state.config.server.host, state.config.server.port);
@@ -500,15 +503,51 @@ async fn audit_log(State(state): State<SharedState>) -> impl IntoResponse {
 
 async fn rpc_handler(
     State(state): State<SharedState>,
-    Json(req): Json<Request>,
+    Json(msg): Json<serde_json::Value>,
 ) -> impl IntoResponse {
-    let response = handle_request(req, &state);
-    let status = if response.success { StatusCode::OK } else { StatusCode::BAD_REQUEST };
-    (status, Json(response))
+    let reply = handle_message(msg, &state);
+    let status = match &reply {
+        Reply::Single(r) if !r.success => StatusCode::BAD_REQUEST,
+        _ => StatusCode::OK,
+    };
+    (status, Json(reply))
 }
 
 // ─── RESTful command wrappers ─────────────────────────────────────────
 
+/// `POST /api/cmd/{command}`: the body is the command's `data`. Optional
+/// `X-Request-Id` (so `/api/cmd/cancel` can target it) and `X-Timeout-Ms`
+/// headers map onto the request envelope.
+async fn cmd_generic(
+    State(state): State<SharedState>,
+    Path(command): Path<String>,
+    headers: HeaderMap,
+    Json(data): Json<serde_json::Value>,
+) -> impl IntoResponse {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let req = Request {
+        id: header("x-request-id"),
+        command,
+        data,
+        timeout_ms: header("x-timeout-ms").and_then(|v| v.parse().ok()),
+        ..Default::default()
+    };
+    // Commands are CPU-bound; keep them off the async workers so a long
+    // optimize does not stall health checks or cancel requests.
+    let response = match tokio::task::spawn_blocking(move || handle_request(req, &state)).await {
+        Ok(r) => r,
+        Err(e) => Response {
+            id: None,
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(format!("Command task failed: {}", e)),
+            meta: None,
+        },
+    };
+    let status = if response.success { StatusCode::OK } else { StatusCode::BAD_REQUEST };
+    (status, Json(response))
+}
+
 macro_rules! cmd_handler {
     ($fn_name:ident, $command:expr) => {
         async fn $fn_name(
@@ -519,6 +558,7 @@ macro_rules! cmd_handler {
                 id: None,
                 command: $command.to_string(),
                 data,
+                ..Default::default()
             };
             let response = handle_request(req, &state);
             let status = if response.success { StatusCode::OK } else { StatusCode::BAD_REQUEST };
@@ -714,7 +754,7 @@ async fn oms_orders(
         Some(sid) => serde_json::json!({ "strategy_id": sid }),
         None => serde_json::json!({}),
     };
-    let req = Request { id: None, command: "oms_orders".to_string(), data };
+    let req = Request { id: None, command: "oms_orders".to_string(), data, ..Default::default() };
     let response = handle_request(req, &state);
     let status = if response.success { StatusCode::OK } else { StatusCode::BAD_REQUEST };
     (status, Json(response))
@@ -731,6 +771,7 @@ async fn oms_modify_order(
         id: None,
         command: "oms_modify_order".to_string(),
         data,
+        ..Default::default()
     };
     let response = handle_request(req, &state);
     let status = if response.success { StatusCode::OK } else { StatusCode::BAD_REQUEST };
@@ -745,6 +786,7 @@ async fn oms_cancel_order(
         id: None,
         command: "oms_cancel_order".to_string(),
         data: serde_json::json!({ "order_id": order_id }),
+        ..Default::default()
     };
     let response = handle_request(req, &state);
     let status = if response.success { StatusCode::OK } else { StatusCode::BAD_REQUEST };
@@ -766,13 +808,13 @@ async fn alerts_list(
             data["limit"] = serde_json::json!(n);
         }
     }
-    let req = Request { id: None, command: "alerts".to_string(), data };
+    let req = Request { id: None, command: "alerts".to_string(), data, ..Default::default() };
     let response = handle_request(req, &state);
     (StatusCode::OK, Json(response))
 }
 
 async fn alert_counts(State(state): State<SharedState>) -> impl IntoResponse {
-    let req = Request { id: None, command: "alert_counts".to_string(), data: serde_json::json!({}) };
+    let req = Request { id: None, command: "alert_counts".to_string(), data: serde_json::json!({}), ..Default::default() };
     let response = handle_request(req, &state);
     (StatusCode::OK, Json(response))
 }
@@ -785,6 +827,7 @@ async fn alert_acknowledge(
         id: None,
         command: "alert_acknowledge".to_string(),
         data: serde_json::json!({ "alert_id": alert_id }),
+        ..Default::default()
     };
     let response = handle_request(req, &state);
     let status = if response.success { StatusCode::OK } else { StatusCode::NOT_FOUND };
@@ -1189,6 +1232,7 @@ async fn handle_ws(mut socket: ws::WebSocket, state: SharedState) {
                         success: false,
                         data: serde_json::Value::Null,
                         error: Some(format!("Invalid JSON: {}", e)),
+                        meta: None,
                     },
                 };
 
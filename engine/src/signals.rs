@@ -1,25 +1,26 @@
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use crate::utils::{Candle, calc_ema_series as calc_ema, calc_rsi_series as calc_rsi, calc_atr_series as calc_atr, sanitize_candles};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::indicators::{self, ChannelSpec};
+use crate::utils::{Candle, sanitize_candles};
 
 #[derive(Deserialize)]
 struct SignalInput {
     candles: Vec<Candle>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct SignalOutput {
-    ema_9: Vec<f64>,
-    ema_21: Vec<f64>,
-    rsi_14: Vec<f64>,
-    macd: Vec<f64>,
-    macd_signal: Vec<f64>,
-    macd_histogram: Vec<f64>,
-    bollinger_upper: Vec<f64>,
-    bollinger_lower: Vec<f64>,
-    bollinger_middle: Vec<f64>,
-    vwap: Vec<f64>,
-    supertrend: Vec<f64>,
+    /// Adds `<method>_upper`, `<method>_lower` and `<method>_middle` series
+    /// of the closes next to the Bollinger bands.
+    #[serde(default)]
+    quantile_channel: Option<ChannelSpec>,
+    /// Adds `fractal_up`/`fractal_down` (latest confirmed levels, two bars
+    /// each side) and the Alligator's `alligator_jaw`, `alligator_teeth`
+    /// and `alligator_lips`.
+    #[serde(default)]
+    williams: bool,
+    /// Relative views such as `zscore(rsi_14, 100)` or `percentile(close,
+    /// 252)`, each added as `<kind>_<series>_<lookback>`. The series is any
+    /// output series above or a candle field (open, high, low, close,
+    /// volume).
+    #[serde(default)]
+    transforms: Vec<String>,
 }
 
 pub fn compute(data: Value) -> Result<Value, String> {
@@ -27,108 +28,49 @@ pub fn compute(data: Value) -> Result<Value, String> {
         serde_json::from_value(data).map_err(|e| format!("Invalid signal input: {}", e))?;
 
     sanitize_candles(&mut input.candles);
-
-    let closes: Vec<f64> = input.candles.iter().map(|c| c.close).collect();
-    let highs: Vec<f64> = input.candles.iter().map(|c| c.high).collect();
-    let lows: Vec<f64> = input.candles.iter().map(|c| c.low).collect();
-    let volumes: Vec<f64> = input.candles.iter().map(|c| c.volume).collect();
-
-    let macd_result = calc_macd(&closes);
-    let bb_result = calc_bollinger(&closes, 20);
-    let output = SignalOutput {
-        ema_9: nan_to_zero(&calc_ema(&closes, 9)),
-        ema_21: nan_to_zero(&calc_ema(&closes, 21)),
-        rsi_14: calc_rsi(&closes, 14),
-        macd: nan_to_zero(&macd_result.0),
-        macd_signal: nan_to_zero(&macd_result.1),
-        macd_histogram: nan_to_zero(&macd_result.2),
-        bollinger_upper: bb_result.0,
-        bollinger_lower: bb_result.1,
-        bollinger_middle: bb_result.2,
-        vwap: calc_vwap(&highs, &lows, &closes, &volumes),
-        supertrend: calc_supertrend(&highs, &lows, &closes, 10, 3.0),
-    };
-
-    serde_json::to_value(output).map_err(|e| format!("Serialization error: {}", e))
-}
-
-/// Replace NaN with 0.0 for JSON serialization (NaN is not valid JSON)
-fn nan_to_zero(data: &[f64]) -> Vec<f64> {
-    data.iter().map(|&v| if v.is_nan() { 0.0 } else { v }).collect()
-}
-
-fn calc_macd(data: &[f64]) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-    let ema12 = calc_ema(data, 12);
-    let ema26 = calc_ema(data, 26);
-    let mut macd_line = vec![f64::NAN; data.len()];
-    for i in 0..data.len() {
-        let a = ema12[i];
-        let b = ema26[i];
-        macd_line[i] = if a.is_nan() || b.is_nan() { f64::NAN } else { a - b };
-    }
-    let clean_macd: Vec<f64> = macd_line.iter().map(|&v| if v.is_nan() { 0.0 } else { v }).collect();
-    let signal = calc_ema(&clean_macd, 9);
-    let mut histogram = vec![f64::NAN; data.len()];
-    for i in 0..data.len() {
-        let m = macd_line[i];
-        let s = signal[i];
-        histogram[i] = if m.is_nan() || s.is_nan() { f64::NAN } else { m - s };
-    }
-    (macd_line, signal, histogram)
-}
-
-fn calc_bollinger(data: &[f64], period: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-    let mut upper = vec![0.0; data.len()];
-    let mut lower = vec![0.0; data.len()];
-    let mut middle = vec![0.0; data.len()];
-
-    for i in period - 1..data.len() {
-        let window = &data[i + 1 - period..=i];
-        let mean = window.iter().sum::<f64>() / period as f64;
-        let variance = window.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / period as f64;
-        let std_dev = variance.sqrt();
-        middle[i] = mean;
-        upper[i] = mean + 2.0 * std_dev;
-        lower[i] = mean - 2.0 * std_dev;
-    }
-    (upper, lower, middle)
-}
-
-/// VWAP using typical price = (high + low + close) / 3
-fn calc_vwap(highs: &[f64], lows: &[f64], closes: &[f64], volumes: &[f64]) -> Vec<f64> {
-    let mut result = vec![0.0; closes.len()];
-    let mut cum_vol = 0.0;
-    let mut cum_pv = 0.0;
-    for i in 0..closes.len() {
-        let typical = (highs[i] + lows[i] + closes[i]) / 3.0;
-        cum_pv += typical * volumes[i];
-        cum_vol += volumes[i];
-        result[i] = if cum_vol > 0.0 { cum_pv / cum_vol } else { closes[i] };
-    }
-    result
-}
-
-fn calc_supertrend(highs: &[f64], lows: &[f64], closes: &[f64], period: usize, multiplier: f64) -> Vec<f64> {
-    let n = closes.len();
-    let mut result = vec![0.0; n];
-    if n < period { return result; }
-
-    let atr = calc_atr(highs, lows, closes, period);
-
-    for i in period..n {
-        let hl2 = (highs[i] + lows[i]) / 2.0;
-        let upper = hl2 + multiplier * atr[i];
-        let lower = hl2 - multiplier * atr[i];
-        result[i] = if closes[i] > upper { lower } else if closes[i] < lower { upper } else { result[i - 1] };
-        if result[i] == 0.0 { result[i] = lower; }
-    }
-    result
+    let output = indicators::standard(&input.candles);
+
+    let mut out = serde_json::to_value(output).map_err(|e| format!("Serialization error: {}", e))?;
+    if let Some(spec) = &input.quantile_channel {
+        let closes: Vec<f64> = input.candles.iter().map(|c| c.close).collect();
+        let (upper, lower, middle) = spec.compute(&closes)?;
+        out[format!("{}_upper", spec.method)] = json!(upper);
+        out[format!("{}_lower", spec.method)] = json!(lower);
+        out[format!("{}_middle", spec.method)] = json!(middle);
+    }
+    if input.williams {
+        let highs: Vec<f64> = input.candles.iter().map(|c| c.high).collect();
+        let lows: Vec<f64> = input.candles.iter().map(|c| c.low).collect();
+        let (up, down) = indicators::fractals(&highs, &lows, 2);
+        let (jaw, teeth, lips) = indicators::alligator(&highs, &lows);
+        out["fractal_up"] = json!(up);
+        out["fractal_down"] = json!(down);
+        out["alligator_jaw"] = json!(jaw);
+        out["alligator_teeth"] = json!(teeth);
+        out["alligator_lips"] = json!(lips);
+    }
+    for expr in &input.transforms {
+        let transform = indicators::Transform::parse(expr)?;
+        let source: Vec<f64> = match transform.source.as_str() {
+            "open" => input.candles.iter().map(|c| c.open).collect(),
+            "high" => input.candles.iter().map(|c| c.high).collect(),
+            "low" => input.candles.iter().map(|c| c.low).collect(),
+            "close" => input.candles.iter().map(|c| c.close).collect(),
+            "volume" => input.candles.iter().map(|c| c.volume).collect(),
+            name => out.get(name).and_then(|v| v.as_array())
+                .ok_or_else(|| format!("Unknown transform series '{}' in '{}'", name, expr))?
+                .iter().map(|v| v.as_f64().unwrap_or(f64::NAN)).collect(),
+        };
+        out[transform.key()] = json!(indicators::nan_to_zero(&transform.apply(&source)));
+    }
+    Ok(out)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
+    use crate::indicators::{bollinger, ema as calc_ema, macd, rsi as calc_rsi, session_vwap as calc_vwap, Standard as SignalOutput};
 
     fn make_candles(closes: &[f64]) -> Vec<serde_json::Value> {
         closes.iter().map(|&c| json!({
@@ -183,7 +125,7 @@ mod tests {
     #[test]
     fn test_macd_zero_on_flat() {
         let data = vec![100.0; 60];
-        let (macd, signal, hist) = calc_macd(&data);
+        let (macd, signal, hist) = macd(&data, 12, 26, 9);
         let last = data.len() - 1;
         assert!((macd[last]).abs() < 0.1, "MACD should be ~0 on flat series, got {}", macd[last]);
         assert!((signal[last]).abs() < 0.1, "MACD signal should be ~0 on flat series, got {}", signal[last]);
@@ -193,7 +135,7 @@ mod tests {
     #[test]
     fn test_bollinger_contains_data() {
         let data: Vec<f64> = (0..30).map(|i| 100.0 + (i as f64 * 0.1).sin() * 5.0).collect();
-        let (upper, lower, middle) = calc_bollinger(&data, 20);
+        let (upper, lower, middle) = bollinger(&data, 20, 2.0);
         for i in 19..30 {
             assert!(upper[i] > middle[i], "upper band should be above middle at {}", i);
             assert!(lower[i] < middle[i], "lower band should be below middle at {}", i);
@@ -201,18 +143,80 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_quantile_channel_is_opt_in() {
+        let closes: Vec<f64> = (0..40).map(|i| 100.0 + (i % 5) as f64).collect();
+        let plain = compute(json!({ "candles": make_candles(&closes) })).unwrap();
+        assert!(plain.get("quantile_upper").is_none());
+        let out = compute(json!({ "candles": make_candles(&closes), "quantile_channel": { "period": 10 } })).unwrap();
+        let (upper, lower) = (out["quantile_upper"].as_array().unwrap(), out["quantile_lower"].as_array().unwrap());
+        assert_eq!(upper.len(), 40);
+        assert_eq!(upper[8], 0.0);
+        assert!(upper[39].as_f64().unwrap() <= 104.0 && lower[39].as_f64().unwrap() >= 100.0);
+        let ex = compute(json!({ "candles": make_candles(&closes), "quantile_channel": { "method": "expectile" } })).unwrap();
+        assert_eq!(ex["expectile_middle"][39], 102.0);
+        assert!(compute(json!({ "candles": make_candles(&closes), "quantile_channel": { "lower": 0.95 } })).is_err());
+    }
+
+    #[test]
+    fn test_williams_series_are_opt_in() {
+        let closes: Vec<f64> = (0..40).map(|i| 100.0 + (i as f64 * 0.7).sin() * 3.0).collect();
+        assert!(compute(json!({ "candles": make_candles(&closes) })).unwrap().get("fractal_up").is_none());
+        let out = compute(json!({ "candles": make_candles(&closes), "williams": true })).unwrap();
+        for key in ["fractal_up", "fractal_down", "alligator_jaw", "alligator_teeth", "alligator_lips"] {
+            assert_eq!(out[key].as_array().unwrap().len(), 40, "{}", key);
+        }
+        assert!(out["fractal_up"][39].as_f64().unwrap() > 100.0);
+        assert!(out["alligator_jaw"][39].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_transforms_add_relative_series() {
+        let closes: Vec<f64> = (0..60).map(|i| 100.0 + (i as f64 * 0.4).sin() * 4.0 + i as f64 * 0.2).collect();
+        let out = compute(json!({
+            "candles": make_candles(&closes),
+            "transforms": ["zscore(rsi_14, 20)", "percentile(close, 30)"]
+        })).unwrap();
+        let z = out["zscore_rsi_14_20"].as_array().unwrap();
+        assert_eq!(z.len(), 60);
+        assert_eq!(z[18], 0.0, "warm-up is zero-filled");
+        assert!(z[59].as_f64().unwrap().abs() < 5.0);
+        let rsi: Vec<f64> = out["rsi_14"].as_array().unwrap().iter().map(|v| v.as_f64().unwrap()).collect();
+        assert_eq!(z[59].as_f64().unwrap(), indicators::rolling_zscore(&rsi, 20)[59]);
+        let pct = out["percentile_close_30"][59].as_f64().unwrap();
+        assert!((0.0..=100.0).contains(&pct));
+        assert_eq!(pct, indicators::percentile_rank(&closes, 30)[59]);
+
+        assert!(compute(json!({ "candles": make_candles(&closes), "transforms": ["zscore(nope, 20)"] })).unwrap_err().contains("nope"));
+        assert!(compute(json!({ "candles": make_candles(&closes), "transforms": ["zscore(close)"] })).is_err());
+        // Transforms may read series added by other options.
+        let chained = compute(json!({ "candles": make_candles(&closes), "williams": true, "transforms": ["percentile(alligator_lips, 10)"] })).unwrap();
+        assert!(chained.get("percentile_alligator_lips_10").is_some());
+    }
+
     #[test]
     fn test_vwap_typical_price_with_equal_volume() {
         let highs = vec![101.0, 103.0, 102.0, 104.0, 105.0];
         let lows = vec![99.0, 101.0, 100.0, 102.0, 103.0];
         let closes = vec![100.0, 102.0, 101.0, 103.0, 104.0];
         let volumes = vec![1000.0; 5];
-        let vwap = calc_vwap(&highs, &lows, &closes, &volumes);
+        let vwap = calc_vwap(&highs, &lows, &closes, &volumes, &[false; 5]);
         let tp: Vec<f64> = (0..5).map(|i| (highs[i] + lows[i] + closes[i]) / 3.0).collect();
         let expected_last = tp.iter().sum::<f64>() / 5.0;
         assert!((vwap[4] - expected_last).abs() < 0.01, "VWAP should use typical price (H+L+C)/3");
     }
 
+    #[test]
+    fn test_vwap_resets_each_intraday_session() {
+        let candles: Vec<serde_json::Value> = [("2024-01-02T15:25:00", 100.0), ("2024-01-02T15:30:00", 110.0), ("2024-01-03T09:15:00", 200.0)]
+            .iter()
+            .map(|(ts, c)| json!({ "timestamp": ts, "close": c, "high": c, "low": c, "volume": 10.0 }))
+            .collect();
+        let s: SignalOutput = serde_json::from_value(compute(json!({ "candles": candles })).unwrap()).unwrap();
+        assert!((s.vwap[1] - 105.0).abs() < 1e-9);
+        assert!((s.vwap[2] - 200.0).abs() < 1e-9, "new session starts a fresh VWAP");
+    }
+
     #[test]
     fn test_output_lengths_match_input() {
         let closes: Vec<f64> = (0..50).map(|i| 100.0 + i as f64).collect();
@@ -0,0 +1,307 @@
+//! Skew time series: 25Δ risk reversal, ATM IV and term slope per day,
+//! each z-scored against its own trailing history.
+//!
+//! Each day in `history` gives the metrics directly or `spot` and `chains`
+//! (one per expiry, strikes as in `chain_analysis`) to derive them from;
+//! given values win. From the front chain, the 25Δ call and put IVs are
+//! interpolated by delta and `rr_25d` is call minus put; `atm_iv` is the
+//! call/put average interpolated at spot. `term_slope` is the next
+//! expiry's ATM IV minus the front's, per 30 days.
+//!
+//! A day's `z` compares its value with the previous `lookback` values and
+//! `change_z` compares its one-day change with the previous changes; both
+//! need `min_periods` observations. Either beyond `z_threshold` is flagged.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::chain_analysis::{mid_price, ChainStrike};
+use crate::greeks::solve_iv;
+use crate::utils::{bs_greeks, round2, round4};
+
+#[derive(Deserialize)]
+struct SkewHistoryConfig {
+    history: Vec<SkewDay>,
+    #[serde(default = "default_lookback")]
+    lookback: usize,
+    #[serde(default = "default_min_periods")]
+    min_periods: usize,
+    #[serde(default = "default_z_threshold")]
+    z_threshold: f64,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+}
+
+fn default_lookback() -> usize { 60 }
+fn default_min_periods() -> usize { 20 }
+fn default_z_threshold() -> f64 { 2.0 }
+fn default_rate() -> f64 { 0.065 }
+
+#[derive(Deserialize)]
+struct SkewDay {
+    #[serde(alias = "timestamp")]
+    date: String,
+    #[serde(default)]
+    rr_25d: Option<f64>,
+    #[serde(default)]
+    atm_iv: Option<f64>,
+    #[serde(default)]
+    term_slope: Option<f64>,
+    #[serde(default)]
+    spot: Option<f64>,
+    #[serde(default)]
+    chains: Vec<DayChain>,
+}
+
+#[derive(Deserialize)]
+struct DayChain {
+    expiry_days: f64,
+    strikes: Vec<ChainStrike>,
+}
+
+#[derive(Serialize)]
+struct MetricPoint {
+    value: f64,
+    change: Option<f64>,
+    z: Option<f64>,
+    change_z: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct SkewPoint {
+    date: String,
+    rr_25d: Option<MetricPoint>,
+    atm_iv: Option<MetricPoint>,
+    term_slope: Option<MetricPoint>,
+}
+
+#[derive(Serialize)]
+struct SkewSignal {
+    date: String,
+    metric: String,
+    /// "level" (z) or "move" (change_z).
+    kind: String,
+    value: f64,
+    z: f64,
+    signal: String,
+}
+
+#[derive(Serialize)]
+struct SkewHistoryResult {
+    series: Vec<SkewPoint>,
+    signals: Vec<SkewSignal>,
+}
+
+const METRICS: [&str; 3] = ["rr_25d", "atm_iv", "term_slope"];
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: SkewHistoryConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid skew_history config: {}", e))?;
+    if config.history.is_empty() {
+        return Err("skew_history requires history".to_string());
+    }
+    if config.min_periods < 2 {
+        return Err("min_periods must be at least 2".to_string());
+    }
+    let r = config.risk_free_rate;
+
+    let values: Vec<[Option<f64>; 3]> = config.history.iter().map(|day| {
+        let derived = day.spot.filter(|s| *s > 0.0).map(|spot| chain_metrics(spot, &day.chains, r)).unwrap_or_default();
+        [day.rr_25d.or(derived[0]), day.atm_iv.or(derived[1]), day.term_slope.or(derived[2])]
+    }).collect();
+
+    let mut points: Vec<[Option<MetricPoint>; 3]> = (0..values.len()).map(|_| [None, None, None]).collect();
+    let mut signals = Vec::new();
+    for (m, name) in METRICS.iter().enumerate() {
+        let series: Vec<Option<f64>> = values.iter().map(|v| v[m]).collect();
+        let changes: Vec<Option<f64>> = (0..series.len())
+            .map(|i| if i == 0 { None } else { series[i].zip(series[i - 1]).map(|(a, b)| a - b) })
+            .collect();
+        let level_z = trailing_z(&series, config.lookback, config.min_periods);
+        let change_z = trailing_z(&changes, config.lookback, config.min_periods);
+        for (i, value) in series.iter().enumerate() {
+            let Some(value) = *value else { continue };
+            let date = &config.history[i].date;
+            for (kind, z) in [("level", level_z[i]), ("move", change_z[i])] {
+                if let Some(z) = z.filter(|z| z.abs() >= config.z_threshold) {
+                    signals.push(SkewSignal {
+                        date: date.clone(),
+                        metric: name.to_string(),
+                        kind: kind.to_string(),
+                        value: round4(value),
+                        z: round2(z),
+                        signal: signal_for(name, z > 0.0).to_string(),
+                    });
+                }
+            }
+            points[i][m] = Some(MetricPoint {
+                value: round4(value),
+                change: changes[i].map(round4),
+                z: level_z[i].map(round2),
+                change_z: change_z[i].map(round2),
+            });
+        }
+    }
+
+    let series = config.history.iter().zip(points)
+        .map(|(day, [rr_25d, atm_iv, term_slope])| SkewPoint { date: day.date.clone(), rr_25d, atm_iv, term_slope })
+        .collect();
+    let result = SkewHistoryResult { series, signals };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Trade suggested by an unusually high (or low) reading.
+fn signal_for(metric: &str, high: bool) -> &'static str {
+    match (metric, high) {
+        // Calls rich against puts: sell the call, buy the put.
+        ("rr_25d", true) => "sell_risk_reversal",
+        ("rr_25d", false) => "buy_risk_reversal",
+        ("atm_iv", true) => "sell_premium",
+        ("atm_iv", false) => "buy_premium",
+        // Back month rich: sell it against the front; inverted: the reverse.
+        ("term_slope", true) => "short_calendar",
+        _ => "long_calendar",
+    }
+}
+
+/// Z-score of each value against up to `lookback` earlier values.
+fn trailing_z(series: &[Option<f64>], lookback: usize, min_periods: usize) -> Vec<Option<f64>> {
+    (0..series.len()).map(|i| {
+        let v = series[i]?;
+        let history: Vec<f64> = series[..i].iter().rev().flatten().take(lookback.max(min_periods)).copied().collect();
+        if history.len() < min_periods {
+            return None;
+        }
+        let n = history.len() as f64;
+        let mean = history.iter().sum::<f64>() / n;
+        let sd = (history.iter().map(|h| (h - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt();
+        (sd > 1e-12).then(|| (v - mean) / sd)
+    }).collect()
+}
+
+/// [rr_25d, atm_iv, term_slope] from a day's chains.
+fn chain_metrics(spot: f64, chains: &[DayChain], r: f64) -> [Option<f64>; 3] {
+    let mut chains: Vec<&DayChain> = chains.iter().filter(|c| c.expiry_days > 0.0 && !c.strikes.is_empty()).collect();
+    chains.sort_by(|a, b| a.expiry_days.total_cmp(&b.expiry_days));
+    let Some(front) = chains.first() else { return [None, None, None] };
+    let smile = |chain: &DayChain| Smile::new(spot, chain, r);
+    let front_smile = smile(front);
+    let rr = front_smile.iv_at_delta(0.25, true).zip(front_smile.iv_at_delta(-0.25, false)).map(|(c, p)| c - p);
+    let front_atm = front_smile.atm_iv();
+    let slope = chains.get(1).and_then(|next| {
+        let next_atm = smile(next).atm_iv()?;
+        Some((next_atm - front_atm?) / (next.expiry_days - front.expiry_days) * 30.0)
+    });
+    [rr, front_atm, slope]
+}
+
+/// (strike, delta, iv) per side of one expiry.
+struct Smile {
+    spot: f64,
+    calls: Vec<(f64, f64, f64)>,
+    puts: Vec<(f64, f64, f64)>,
+}
+
+impl Smile {
+    fn new(spot: f64, chain: &DayChain, r: f64) -> Self {
+        let t = chain.expiry_days / 365.0;
+        let side = |is_call: bool| -> Vec<(f64, f64, f64)> {
+            let mut out: Vec<(f64, f64, f64)> = chain.strikes.iter().filter_map(|s| {
+                let (bid, ask, ltp, iv) = s.quote(is_call);
+                let mid = mid_price(bid, ask, ltp);
+                let iv = iv.filter(|v| *v > 0.0)
+                    .or_else(|| (mid > 0.0).then(|| solve_iv(spot, s.strike, r, t, mid, is_call)))
+                    .filter(|v| *v > 0.0)?;
+                let (delta, ..) = bs_greeks(spot, s.strike, t, r, iv, is_call);
+                Some((s.strike, delta, iv))
+            }).collect();
+            out.sort_by(|a, b| a.0.total_cmp(&b.0));
+            out
+        };
+        Self { spot, calls: side(true), puts: side(false) }
+    }
+
+    /// IV where the side's delta equals `target`, interpolated linearly.
+    fn iv_at_delta(&self, target: f64, is_call: bool) -> Option<f64> {
+        let points = if is_call { &self.calls } else { &self.puts };
+        interpolate(points.iter().map(|p| (p.1, p.2)), target)
+    }
+
+    /// Mean of the call and put IVs interpolated at spot.
+    fn atm_iv(&self) -> Option<f64> {
+        let at_spot = |points: &[(f64, f64, f64)]| interpolate(points.iter().map(|p| (p.0, p.2)), self.spot);
+        match (at_spot(&self.calls), at_spot(&self.puts)) {
+            (Some(c), Some(p)) => Some((c + p) / 2.0),
+            (c, p) => c.or(p),
+        }
+    }
+}
+
+/// Linear interpolation of y at `x` over points monotone in x; None when
+/// `x` is outside them.
+fn interpolate(points: impl Iterator<Item = (f64, f64)>, x: f64) -> Option<f64> {
+    let points: Vec<(f64, f64)> = points.collect();
+    points.windows(2).find_map(|w| {
+        let ((x0, y0), (x1, y1)) = (w[0], w[1]);
+        let (lo, hi) = if x0 <= x1 { (x0, x1) } else { (x1, x0) };
+        if x < lo || x > hi {
+            return None;
+        }
+        Some(if x1 == x0 { y0 } else { y0 + (y1 - y0) * (x - x0) / (x1 - x0) })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_chain_metrics_from_smile() {
+        // Put skew: IV falls 2 vol points per 10% of strike.
+        let chain = |days: f64, atm: f64| {
+            let strikes: Vec<Value> = (0..21).map(|i| {
+                let k = 80.0 + i as f64 * 2.0;
+                let iv = atm - 0.2 * (k / 100.0 - 1.0);
+                json!({ "strike": k, "call_iv": iv, "put_iv": iv })
+            }).collect();
+            json!({ "expiry_days": days, "strikes": strikes })
+        };
+        let day: SkewDay = serde_json::from_value(json!({
+            "date": "2025-01-02", "spot": 100.0, "chains": [chain(60.0, 0.18), chain(30.0, 0.15)],
+        })).unwrap();
+        let [rr, atm, slope] = chain_metrics(100.0, &day.chains, 0.065);
+        assert!((atm.unwrap() - 0.15).abs() < 1e-9);
+        // The 25Δ call is above spot, so it carries the lower IV.
+        assert!(rr.unwrap() < 0.0);
+        assert!((slope.unwrap() - 0.03).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_z_scores_and_signals() {
+        // Steady oscillating RR, then a put-skew blowout on the last day.
+        let mut history: Vec<Value> = (0..40).map(|i| json!({
+            "date": format!("2025-01-{:02}", i % 28 + 1),
+            "rr_25d": -0.02 + 0.002 * (i as f64 * 0.9).sin(),
+            "atm_iv": 0.14,
+        })).collect();
+        history.push(json!({ "date": "2025-02-20", "rr_25d": -0.05, "atm_iv": 0.14 }));
+        let result = compute(json!({ "history": history, "lookback": 30 })).unwrap();
+        let series = result["series"].as_array().unwrap();
+        assert_eq!(series.len(), 41);
+        assert!(series[5]["rr_25d"]["z"].is_null());
+        let last = &series[40]["rr_25d"];
+        assert!(last["z"].as_f64().unwrap() < -5.0);
+        assert!((last["change"].as_f64().unwrap() - (-0.05 - series[39]["rr_25d"]["value"].as_f64().unwrap())).abs() < 1e-3);
+        // A constant ATM IV never scores, and term slope is absent.
+        assert!(series[40]["atm_iv"]["z"].is_null());
+        assert!(series[40]["term_slope"].is_null());
+
+        let signals = result["signals"].as_array().unwrap();
+        let kinds: Vec<(&str, &str)> = signals.iter()
+            .filter(|s| s["date"] == "2025-02-20")
+            .map(|s| (s["kind"].as_str().unwrap(), s["signal"].as_str().unwrap()))
+            .collect();
+        assert_eq!(kinds, vec![("level", "buy_risk_reversal"), ("move", "buy_risk_reversal")]);
+        assert!(compute(json!({ "history": [] })).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use crate::utils::Candle;
 
 /// Slippage model type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -177,6 +178,83 @@ pub struct SlippageCostEstimate {
     pub model: SlippageModel,
 }
 
+/// Share of the average bar range treated as the quoted spread. The
+/// high-low range is a volatility-inflated upper bound on the spread; a
+/// small fraction of it tracks observed NSE spreads on liquid names.
+const SPREAD_SHARE_OF_RANGE: f64 = 0.05;
+/// Order size assumed when the caller gives none: 0.1% of daily volume.
+const DEFAULT_PARTICIPATION: f64 = 0.001;
+
+/// Per-symbol slippage assumption derived from candle microstructure.
+#[derive(Debug, Clone, Serialize)]
+pub struct SlippageEstimate {
+    /// Half spread plus square-root market impact, in bps of price.
+    pub slippage_bps: f64,
+    /// Spread proxy: `SPREAD_SHARE_OF_RANGE` of the mean (high - low) / close.
+    pub spread_bps: f64,
+    pub impact_bps: f64,
+    pub avg_range_bps: f64,
+    /// Daily close-to-close volatility (decimal).
+    pub daily_volatility: f64,
+    pub avg_daily_volume: f64,
+    pub avg_daily_value: f64,
+    /// Order value / average daily traded value.
+    pub participation: f64,
+    pub bars: usize,
+}
+
+/// Estimate slippage from the last `lookback` bars (all bars when 0).
+///
+/// Spread comes from the bar range, impact from the square-root law on
+/// daily volatility and the order's share of daily traded value. `order_value`
+/// defaults to `DEFAULT_PARTICIPATION` of that value. None with fewer than
+/// two usable bars.
+pub fn estimate_from_candles(
+    candles: &[Candle],
+    lookback: usize,
+    bars_per_day: f64,
+    order_value: Option<f64>,
+) -> Option<SlippageEstimate> {
+    let usable: Vec<&Candle> = candles
+        .iter()
+        .filter(|c| c.close > 0.0 && c.high >= c.low && c.close.is_finite())
+        .collect();
+    let window = if lookback == 0 { &usable[..] } else { &usable[usable.len().saturating_sub(lookback)..] };
+    if window.len() < 2 {
+        return None;
+    }
+    let n = window.len() as f64;
+    let bars_per_day = bars_per_day.max(1.0);
+
+    let avg_range = window.iter().map(|c| (c.high - c.low) / c.close).sum::<f64>() / n;
+    let returns: Vec<f64> = window.windows(2).map(|w| w[1].close / w[0].close - 1.0).collect();
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len().max(2) - 1) as f64;
+    let daily_volatility = var.sqrt() * bars_per_day.sqrt();
+
+    let avg_daily_volume = window.iter().map(|c| c.volume.max(0.0)).sum::<f64>() / n * bars_per_day;
+    let avg_daily_value = window.iter().map(|c| c.volume.max(0.0) * c.close).sum::<f64>() / n * bars_per_day;
+    let participation = match order_value {
+        Some(v) if avg_daily_value > 0.0 => (v.abs() / avg_daily_value).min(1.0),
+        Some(_) => 1.0,
+        None => DEFAULT_PARTICIPATION,
+    };
+
+    let spread_bps = avg_range * SPREAD_SHARE_OF_RANGE * 10_000.0;
+    let impact_bps = daily_volatility * participation.sqrt() * 10_000.0;
+    Some(SlippageEstimate {
+        slippage_bps: spread_bps / 2.0 + impact_bps,
+        spread_bps,
+        impact_bps,
+        avg_range_bps: avg_range * 10_000.0,
+        daily_volatility,
+        avg_daily_volume,
+        avg_daily_value,
+        participation,
+        bars: window.len(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,4 +364,41 @@ mod tests {
         assert_eq!(SlippageModel::from_str_loose("almgren_chriss"), SlippageModel::AlmgrenChriss);
         assert_eq!(SlippageModel::from_str_loose("fixed"), SlippageModel::FixedBps);
     }
+
+    fn bars(range_pct: f64, volume: f64, swing: f64) -> Vec<Candle> {
+        (0..40).map(|i| {
+            let close = 100.0 + if i % 2 == 0 { swing } else { -swing };
+            Candle {
+                timestamp: format!("2025-01-{:02}", i % 28 + 1),
+                open: close,
+                high: close * (1.0 + range_pct / 2.0),
+                low: close * (1.0 - range_pct / 2.0),
+                close,
+                volume,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_estimate_from_candles() {
+        let liquid = estimate_from_candles(&bars(0.01, 1_000_000.0, 0.5), 20, 1.0, None).unwrap();
+        assert_eq!(liquid.bars, 20);
+        assert!((liquid.avg_range_bps - 100.0).abs() < 1e-6);
+        assert!((liquid.spread_bps - 5.0).abs() < 1e-6);
+        assert!((liquid.participation - DEFAULT_PARTICIPATION).abs() < 1e-12);
+        assert!((liquid.slippage_bps - (liquid.spread_bps / 2.0 + liquid.impact_bps)).abs() < 1e-9);
+
+        // Wider ranges, choppier closes and thinner volume all cost more.
+        let wide = estimate_from_candles(&bars(0.04, 1_000_000.0, 0.5), 20, 1.0, None).unwrap();
+        let choppy = estimate_from_candles(&bars(0.01, 1_000_000.0, 2.0), 20, 1.0, None).unwrap();
+        let order = Some(1_000_000.0);
+        let deep = estimate_from_candles(&bars(0.01, 1_000_000.0, 0.5), 20, 1.0, order).unwrap();
+        let thin = estimate_from_candles(&bars(0.01, 10_000.0, 0.5), 20, 1.0, order).unwrap();
+        assert!(wide.slippage_bps > liquid.slippage_bps);
+        assert!(choppy.impact_bps > liquid.impact_bps);
+        assert!(thin.slippage_bps > deep.slippage_bps);
+        assert!((thin.participation - 1.0).abs() < 1e-12, "order larger than daily value caps at 1");
+
+        assert!(estimate_from_candles(&bars(0.01, 1.0, 0.5)[..1], 0, 1.0, None).is_none());
+    }
 }
@@ -0,0 +1,134 @@
+//! Per-symbol slippage assumptions from candle microstructure.
+//!
+//! Replaces the single global `slippage_bps` guess with one figure per
+//! symbol: half of a spread proxy taken from the bar range, plus
+//! square-root impact from daily volatility and the order's share of daily
+//! traded value (see [`crate::slippage::estimate_from_candles`]). The
+//! `slippage_bps` map in the result can be passed straight to `scan`, and a
+//! symbol's entry to `backtest` as `transaction_costs.slippage_bps`; both
+//! also estimate inline with `auto_slippage: true`.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::slippage::estimate_from_candles;
+use crate::utils::{Candle, round2, round4};
+
+#[derive(Deserialize)]
+struct EstimateConfig {
+    symbols: Vec<SymbolCandles>,
+    /// Bars to average over; 20 trading days' worth by default.
+    #[serde(default)]
+    lookback: Option<usize>,
+    #[serde(default)]
+    bars_per_day: Option<f64>,
+    /// Typical order value; a per-symbol value overrides it.
+    #[serde(default)]
+    order_value: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct SymbolCandles {
+    symbol: String,
+    candles: Vec<Candle>,
+    #[serde(default)]
+    order_value: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct SymbolEstimate {
+    symbol: String,
+    slippage_bps: f64,
+    spread_bps: f64,
+    impact_bps: f64,
+    avg_range_bps: f64,
+    daily_volatility_pct: f64,
+    avg_daily_volume: f64,
+    avg_daily_value: f64,
+    participation_pct: f64,
+    bars: usize,
+}
+
+#[derive(Serialize)]
+struct EstimateResult {
+    estimates: Vec<SymbolEstimate>,
+    /// Symbol → slippage_bps, the shape `scan` accepts.
+    slippage_bps: BTreeMap<String, f64>,
+    /// Symbols with fewer than two usable bars.
+    skipped: Vec<String>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: EstimateConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid estimate_slippage config: {}", e))?;
+    let bars_per_day = config.bars_per_day.unwrap_or(1.0);
+    if !(bars_per_day.is_finite() && bars_per_day >= 1.0) {
+        return Err("bars_per_day must be at least 1".to_string());
+    }
+    let lookback = config.lookback.unwrap_or((20.0 * bars_per_day).round() as usize);
+
+    let mut estimates = Vec::new();
+    let mut slippage_bps = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for s in &config.symbols {
+        let order_value = s.order_value.or(config.order_value);
+        let Some(e) = estimate_from_candles(&s.candles, lookback, bars_per_day, order_value) else {
+            skipped.push(s.symbol.clone());
+            continue;
+        };
+        slippage_bps.insert(s.symbol.clone(), round2(e.slippage_bps));
+        estimates.push(SymbolEstimate {
+            symbol: s.symbol.clone(),
+            slippage_bps: round2(e.slippage_bps),
+            spread_bps: round2(e.spread_bps),
+            impact_bps: round2(e.impact_bps),
+            avg_range_bps: round2(e.avg_range_bps),
+            daily_volatility_pct: round4(e.daily_volatility * 100.0),
+            avg_daily_volume: round2(e.avg_daily_volume),
+            avg_daily_value: round2(e.avg_daily_value),
+            participation_pct: round4(e.participation * 100.0),
+            bars: e.bars,
+        });
+    }
+
+    serde_json::to_value(EstimateResult { estimates, slippage_bps, skipped })
+        .map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candles(range_pct: f64, volume: f64) -> Vec<Value> {
+        (0..30).map(|i| {
+            let close = 100.0 + (i % 3) as f64;
+            json!({
+                "timestamp": format!("2025-01-{:02}", i % 28 + 1),
+                "open": close, "close": close, "volume": volume,
+                "high": close * (1.0 + range_pct / 2.0),
+                "low": close * (1.0 - range_pct / 2.0),
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn test_per_symbol_estimates() {
+        let out = compute(json!({
+            "order_value": 500_000.0,
+            "symbols": [
+                { "symbol": "RELIANCE", "candles": candles(0.01, 5_000_000.0) },
+                { "symbol": "SMALLCAP", "candles": candles(0.05, 20_000.0) },
+                { "symbol": "NEW", "candles": candles(0.01, 1.0)[..1] },
+            ]
+        })).unwrap();
+        let map = &out["slippage_bps"];
+        assert!(map["SMALLCAP"].as_f64().unwrap() > map["RELIANCE"].as_f64().unwrap());
+        assert_eq!(out["skipped"], json!(["NEW"]));
+        let est = &out["estimates"][0];
+        assert_eq!(est["symbol"], "RELIANCE");
+        assert_eq!(est["bars"], 20);
+        assert_eq!(est["spread_bps"].as_f64().unwrap(), 5.0);
+        assert!(compute(json!({ "symbols": [], "bars_per_day": 0.5 })).is_err());
+    }
+}
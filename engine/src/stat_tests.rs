@@ -0,0 +1,353 @@
+//! Statistical checks on a return series before trusting Sharpe or VaR.
+//!
+//! Takes `returns` directly, or `prices`/`candles` (log returns are taken).
+//! Runs an ADF unit-root test (on returns, and on log prices when given),
+//! Ljung-Box on returns and squared returns, Lo-MacKinlay variance ratios
+//! with the heteroskedasticity-robust z*, and Jarque-Bera normality. Each
+//! rejected assumption adds a plain-language note to `implications`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::pairs::{adf_statistic, mackinnon_critical, CriticalValues};
+use crate::utils::{norm_cdf, round4, Candle};
+
+#[derive(Deserialize)]
+struct StatConfig {
+    #[serde(default)]
+    returns: Vec<f64>,
+    #[serde(default)]
+    prices: Vec<f64>,
+    #[serde(default)]
+    candles: Vec<Candle>,
+    /// Subset of adf, ljung_box, variance_ratio, normality; empty = all.
+    #[serde(default)]
+    tests: Vec<String>,
+    #[serde(default)]
+    ljung_box_lags: Option<usize>,
+    #[serde(default)]
+    adf_lags: Option<usize>,
+    #[serde(default = "default_vr_periods")]
+    variance_ratio_periods: Vec<usize>,
+    #[serde(default = "default_significance")]
+    significance: f64,
+}
+
+fn default_vr_periods() -> Vec<usize> { vec![2, 4, 8, 16] }
+fn default_significance() -> f64 { 0.05 }
+
+#[derive(Serialize)]
+struct AdfRow {
+    series: &'static str,
+    statistic: f64,
+    lags: usize,
+    critical_values: CriticalValues,
+    stationary: bool,
+}
+
+#[derive(Serialize)]
+struct LjungBox {
+    series: &'static str,
+    lags: usize,
+    q_statistic: f64,
+    p_value: f64,
+    /// Autocorrelation at lags 1..=lags.
+    autocorrelations: Vec<f64>,
+    independent: bool,
+}
+
+#[derive(Serialize)]
+struct VarianceRatio {
+    period: usize,
+    ratio: f64,
+    z: f64,
+    z_robust: f64,
+    p_value: f64,
+    random_walk: bool,
+}
+
+#[derive(Serialize)]
+struct Normality {
+    skewness: f64,
+    excess_kurtosis: f64,
+    jarque_bera: f64,
+    p_value: f64,
+    normal: bool,
+}
+
+#[derive(Serialize)]
+struct StatResult {
+    observations: usize,
+    mean: f64,
+    std_dev: f64,
+    adf: Option<Vec<AdfRow>>,
+    ljung_box: Option<Vec<LjungBox>>,
+    variance_ratio: Option<Vec<VarianceRatio>>,
+    normality: Option<Normality>,
+    implications: Vec<String>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: StatConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid stat_tests config: {}", e))?;
+    let prices: Vec<f64> = if !config.prices.is_empty() {
+        config.prices.clone()
+    } else {
+        config.candles.iter().map(|c| c.close).collect()
+    };
+    if prices.iter().any(|p| !(p.is_finite() && *p > 0.0)) {
+        return Err("prices must be positive".to_string());
+    }
+    let returns: Vec<f64> = if !config.returns.is_empty() {
+        config.returns.clone()
+    } else {
+        prices.windows(2).map(|w| (w[1] / w[0]).ln()).collect()
+    };
+    let n = returns.len();
+    if n < 20 {
+        return Err("Need at least 20 returns".to_string());
+    }
+    for t in &config.tests {
+        if !["adf", "ljung_box", "variance_ratio", "normality"].contains(&t.as_str()) {
+            return Err(format!("Unknown test '{}' (adf, ljung_box, variance_ratio, normality)", t));
+        }
+    }
+    let run = |name: &str| config.tests.is_empty() || config.tests.iter().any(|t| t == name);
+    let alpha = config.significance;
+    let (mean, sd) = moments(&returns);
+    let mut implications = Vec::new();
+
+    let adf = run("adf").then(|| {
+        let mut rows = Vec::new();
+        let log_prices: Vec<f64> = prices.iter().map(|p| p.ln()).collect();
+        let series: [(&'static str, &[f64]); 2] = [("returns", &returns), ("log_prices", &log_prices)];
+        for (name, s) in series {
+            if s.len() < 20 {
+                continue;
+            }
+            let lags = config.adf_lags.unwrap_or_else(|| (12.0 * (s.len() as f64 / 100.0).powf(0.25)).floor() as usize).min(s.len() / 4);
+            let Some(statistic) = adf_statistic(s, lags) else { continue };
+            let cv = mackinnon_critical(1, s.len());
+            let threshold = if alpha <= 0.01 { cv.one } else if alpha <= 0.05 { cv.five } else { cv.ten };
+            rows.push(AdfRow { series: name, statistic: round4(statistic), lags, critical_values: cv, stationary: statistic < threshold });
+        }
+        if rows.iter().any(|r| r.series == "returns" && !r.stationary) {
+            implications.push("Returns are not stationary: full-sample mean, volatility and Sharpe are not stable estimates.".to_string());
+        }
+        rows
+    });
+
+    let ljung_box = run("ljung_box").then(|| {
+        let lags = config.ljung_box_lags.unwrap_or(10).clamp(1, n / 2);
+        let squared: Vec<f64> = returns.iter().map(|r| (r - mean).powi(2)).collect();
+        let rows = vec![ljung_box_test("returns", &returns, lags, alpha), ljung_box_test("squared_returns", &squared, lags, alpha)];
+        if !rows[0].independent {
+            implications.push("Returns are autocorrelated: √N annualization of Sharpe and volatility is biased.".to_string());
+        }
+        if !rows[1].independent {
+            implications.push("Volatility clusters (ARCH effects): constant-volatility VaR understates risk after shocks.".to_string());
+        }
+        rows
+    });
+
+    let variance_ratio = run("variance_ratio").then(|| {
+        let rows: Vec<VarianceRatio> = config.variance_ratio_periods.iter()
+            .filter(|q| **q >= 2 && **q < n / 2)
+            .map(|&q| variance_ratio_test(&returns, q, alpha))
+            .collect();
+        if let Some(r) = rows.iter().find(|r| !r.random_walk) {
+            implications.push(format!(
+                "Variance ratio {} at period {} rejects a random walk: returns {} over longer horizons.",
+                r.ratio, r.period, if r.ratio > 1.0 { "trend (momentum)" } else { "mean-revert" }
+            ));
+        }
+        rows
+    });
+
+    let normality = run("normality").then(|| {
+        let m3 = returns.iter().map(|r| (r - mean).powi(3)).sum::<f64>() / n as f64;
+        let m2 = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / n as f64;
+        let m4 = returns.iter().map(|r| (r - mean).powi(4)).sum::<f64>() / n as f64;
+        let (skew, kurt) = if m2 > 0.0 { (m3 / m2.powf(1.5), m4 / (m2 * m2) - 3.0) } else { (0.0, 0.0) };
+        let jb = n as f64 / 6.0 * (skew * skew + kurt * kurt / 4.0);
+        // χ²(2) survival is exactly e^(-x/2).
+        let p_value = (-jb / 2.0).exp();
+        if p_value < alpha {
+            implications.push(format!(
+                "Returns are not normal (skew {:.2}, excess kurtosis {:.2}): parametric VaR understates tail losses; prefer historical VaR/CVaR.",
+                skew, kurt
+            ));
+        }
+        Normality { skewness: round4(skew), excess_kurtosis: round4(kurt), jarque_bera: round4(jb), p_value: round4(p_value), normal: p_value >= alpha }
+    });
+
+    let result = StatResult {
+        observations: n,
+        mean: round4(mean),
+        std_dev: round4(sd),
+        adf,
+        ljung_box,
+        variance_ratio,
+        normality,
+        implications,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn moments(xs: &[f64]) -> (f64, f64) {
+    let n = xs.len() as f64;
+    let mean = xs.iter().sum::<f64>() / n;
+    let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, var.sqrt())
+}
+
+fn ljung_box_test(series: &'static str, x: &[f64], lags: usize, alpha: f64) -> LjungBox {
+    let n = x.len() as f64;
+    let mean = x.iter().sum::<f64>() / n;
+    let denom: f64 = x.iter().map(|v| (v - mean).powi(2)).sum();
+    let acf: Vec<f64> = (1..=lags).map(|k| {
+        if denom == 0.0 {
+            return 0.0;
+        }
+        x[k..].iter().zip(x).map(|(a, b)| (a - mean) * (b - mean)).sum::<f64>() / denom
+    }).collect();
+    let q = n * (n + 2.0) * acf.iter().enumerate().map(|(i, r)| r * r / (n - (i + 1) as f64)).sum::<f64>();
+    let p_value = chi2_sf(q, lags as f64);
+    LjungBox { series, lags, q_statistic: round4(q), p_value: round4(p_value), autocorrelations: acf.into_iter().map(round4).collect(), independent: p_value >= alpha }
+}
+
+/// Lo-MacKinlay (1988) overlapping variance ratio for period `q`.
+fn variance_ratio_test(r: &[f64], q: usize, alpha: f64) -> VarianceRatio {
+    let n = r.len() as f64;
+    let qf = q as f64;
+    let mu = r.iter().sum::<f64>() / n;
+    let dev2: Vec<f64> = r.iter().map(|x| (x - mu).powi(2)).collect();
+    let sum_dev2: f64 = dev2.iter().sum();
+    let var_a = sum_dev2 / (n - 1.0);
+    let m = qf * (n - qf + 1.0) * (1.0 - qf / n);
+    let var_c = r.windows(q).map(|w| (w.iter().sum::<f64>() - qf * mu).powi(2)).sum::<f64>() / m;
+    let ratio = if var_a > 0.0 { var_c / var_a } else { 1.0 };
+    let z = (ratio - 1.0) / (2.0 * (2.0 * qf - 1.0) * (qf - 1.0) / (3.0 * qf * n)).sqrt();
+    let theta: f64 = (1..q).map(|j| {
+        let delta = n * dev2[j..].iter().zip(&dev2).map(|(a, b)| a * b).sum::<f64>() / (sum_dev2 * sum_dev2);
+        (2.0 * (qf - j as f64) / qf).powi(2) * delta
+    }).sum();
+    let z_robust = if theta > 0.0 { (ratio - 1.0) / theta.sqrt() } else { 0.0 };
+    let p_value = 2.0 * (1.0 - norm_cdf(z_robust.abs()));
+    VarianceRatio { period: q, ratio: round4(ratio), z: round4(z), z_robust: round4(z_robust), p_value: round4(p_value), random_walk: p_value >= alpha }
+}
+
+/// Upper tail of the χ² distribution with `k` degrees of freedom.
+fn chi2_sf(x: f64, k: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    gamma_q(k / 2.0, x / 2.0)
+}
+
+/// Regularized upper incomplete gamma Q(a, x): series below a + 1,
+/// Lentz continued fraction above.
+fn gamma_q(a: f64, x: f64) -> f64 {
+    let ln_pre = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        let (mut term, mut sum, mut ap) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..500 {
+            ap += 1.0;
+            term *= x / ap;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * ln_pre.exp()).clamp(0.0, 1.0)
+    } else {
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny { d = tiny; }
+            c = b + an / c;
+            if c.abs() < tiny { c = tiny; }
+            d = 1.0 / d;
+            let del = d * c;
+            h *= del;
+            if (del - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (ln_pre.exp() * h).clamp(0.0, 1.0)
+    }
+}
+
+/// Lanczos approximation (g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const C: [f64; 9] = [
+        0.999_999_999_999_809_9, 676.520_368_121_885_1, -1_259.139_216_722_402_8,
+        771.323_428_777_653_1, -176.615_029_162_140_6, 12.507_343_278_686_905,
+        -0.138_571_095_265_720_12, 9.984_369_578_019_572e-6, 1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = C[1..].iter().enumerate().fold(C[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.0));
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::utils::Xorshift64;
+
+    fn noise(n: usize, seed: u64) -> Vec<f64> {
+        let mut rng = Xorshift64::new(seed);
+        (0..n).map(|_| rng.next_normal(0.0, 0.01)).collect()
+    }
+
+    #[test]
+    fn test_chi2_tail() {
+        // Known quantiles: χ²(1) 3.841 and χ²(10) 18.307 are 5% tails.
+        assert!((chi2_sf(3.841, 1.0) - 0.05).abs() < 1e-3);
+        assert!((chi2_sf(18.307, 10.0) - 0.05).abs() < 1e-3);
+        assert!((chi2_sf(2.0, 2.0) - (-1.0f64).exp()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_white_noise_passes() {
+        let result = compute(json!({ "returns": noise(1000, 3) })).unwrap();
+        assert_eq!(result["adf"][0]["stationary"], true);
+        assert_eq!(result["ljung_box"][0]["independent"], true);
+        assert_eq!(result["normality"]["normal"], true);
+        assert!(result["variance_ratio"].as_array().unwrap().iter().all(|r| r["random_walk"] == true));
+        assert_eq!(result["implications"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_autocorrelated_fat_tailed_prices() {
+        // AR(1) returns with occasional 8σ jumps, fed as prices.
+        let e = noise(1000, 11);
+        let mut r = 0.0;
+        let mut price = 100.0;
+        let mut prices = vec![price];
+        for (i, x) in e.iter().enumerate() {
+            r = 0.4 * r + x + if i % 97 == 0 { -0.08 } else { 0.0 };
+            price *= r.exp();
+            prices.push(price);
+        }
+        let result = compute(json!({ "prices": prices, "tests": ["ljung_box", "variance_ratio", "normality", "adf"] })).unwrap();
+        assert_eq!(result["ljung_box"][0]["independent"], false);
+        assert!(result["ljung_box"][0]["autocorrelations"][0].as_f64().unwrap() > 0.3);
+        assert_eq!(result["normality"]["normal"], false);
+        assert!(result["variance_ratio"][0]["ratio"].as_f64().unwrap() > 1.2);
+        assert_eq!(result["adf"][1]["series"], "log_prices");
+        assert_eq!(result["adf"][1]["stationary"], false);
+        assert!(result["implications"].as_array().unwrap().len() >= 3);
+        assert!(compute(json!({ "returns": noise(50, 1), "tests": ["sharpe"] })).is_err());
+    }
+}
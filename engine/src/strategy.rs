@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use crate::config::EngineConfig;
-use crate::utils::{Candle, calc_ema_series as calc_ema, calc_rsi_series as calc_rsi, calc_sma, calc_atr_series as calc_atr};
+use crate::indicators;
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use crate::utils::{parse_timestamp, Candle, session_starts};
 
 // ─── Core Types ───────────────────────────────────────────────────────
 
@@ -20,6 +22,14 @@ pub struct Signal {
     pub reason: String,
 }
 
+/// Volatility-based size for one entry or add: the quantity that loses
+/// `risk_pct` of equity on an adverse move of `price_move`.
+#[derive(Debug, Clone, Copy)]
+pub struct UnitSizing {
+    pub risk_pct: f64,
+    pub price_move: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Fill {
     pub side: Side,
@@ -35,6 +45,8 @@ pub struct Indicators {
     pub ema_short: Vec<f64>,
     pub ema_long: Vec<f64>,
     pub rsi: Vec<f64>,
+    /// RSI through `rsi_transform`, or raw RSI without one.
+    pub rsi_relative: Vec<f64>,
     pub sma_short: Vec<f64>,
     pub sma_long: Vec<f64>,
     pub atr: Vec<f64>,
@@ -43,13 +55,24 @@ pub struct Indicators {
     pub highs: Vec<f64>,
     pub lows: Vec<f64>,
     pub volumes: Vec<f64>,
+    /// Bollinger bands, or the quantile/expectile channel in `bb_band`.
     pub bb_upper: Vec<f64>,
     pub bb_lower: Vec<f64>,
     pub bb_mid: Vec<f64>,
     pub vwap: Vec<f64>,
+    /// VWAP and its volume-weighted standard deviation, restarting per
+    /// `vwap_band_anchor`.
+    pub vwap_band: Vec<f64>,
+    pub vwap_band_std: Vec<f64>,
     pub adx: Vec<f64>,
     pub plus_di: Vec<f64>,
     pub minus_di: Vec<f64>,
+    /// Latest confirmed up/down fractal levels over `fractal_span` bars.
+    pub fractal_up: Vec<f64>,
+    pub fractal_down: Vec<f64>,
+    pub alligator_jaw: Vec<f64>,
+    pub alligator_teeth: Vec<f64>,
+    pub alligator_lips: Vec<f64>,
 }
 
 impl Indicators {
@@ -60,118 +83,70 @@ impl Indicators {
         let lows: Vec<f64> = candles.iter().map(|c| c.low).collect();
         let volumes: Vec<f64> = candles.iter().map(|c| c.volume).collect();
 
-        let ema_short = calc_ema(&closes, config.backtest.ema_short_period);
-        let ema_long = calc_ema(&closes, config.backtest.ema_long_period);
-        let rsi = calc_rsi(&closes, 14);
-        let sma_short = calc_sma(&closes, config.backtest.sma_short_period);
-        let sma_long = calc_sma(&closes, config.backtest.sma_long_period);
-        let atr = calc_atr(&highs, &lows, &closes, 14);
+        let ema_short = indicators::ema(&closes, config.backtest.ema_short_period);
+        let ema_long = indicators::ema(&closes, config.backtest.ema_long_period);
+        let rsi = indicators::rsi(&closes, 14);
+        let rsi_relative = match rsi_transform(&config.backtest.rsi_transform) {
+            Ok(Some(t)) => t.apply(&rsi),
+            _ => rsi.clone(),
+        };
+        let sma_short = indicators::sma(&closes, config.backtest.sma_short_period);
+        let sma_long = indicators::sma(&closes, config.backtest.sma_long_period);
+        let atr = indicators::atr(&highs, &lows, &closes, 14);
 
         let bb_period = config.backtest.bb_period;
         let bb_mult = config.backtest.bb_std_mult;
-        let (bb_upper, bb_lower, bb_mid) = calc_bollinger(&closes, bb_period, bb_mult);
-        let vwap = calc_vwap(&highs, &lows, &closes, &volumes);
+        let [lo, hi] = config.backtest.bb_channel_levels;
+        let (bb_upper, bb_lower, bb_mid) = match config.backtest.bb_band.as_str() {
+            "quantile" => indicators::quantile_channel(&closes, bb_period, lo, hi),
+            "expectile" => indicators::expectile_channel(&closes, bb_period, lo, hi),
+            _ => indicators::bollinger(&closes, bb_period, bb_mult),
+        };
+        let vwap = indicators::session_vwap(&highs, &lows, &closes, &volumes, &session_starts(candles));
+        let anchors = anchor_starts(candles, &config.backtest.vwap_band_anchor)
+            .unwrap_or_else(|| session_starts(candles));
+        let (vwap_band, vwap_band_std) = indicators::vwap_bands(candles, &anchors);
         let adx_period = config.backtest.adx_period;
-        let (adx, plus_di, minus_di) = calc_adx(&highs, &lows, &closes, adx_period);
+        let (adx, plus_di, minus_di) = indicators::adx(&highs, &lows, &closes, adx_period);
+        let (fractal_up, fractal_down) = indicators::fractals(&highs, &lows, config.backtest.fractal_span);
+        let (alligator_jaw, alligator_teeth, alligator_lips) = indicators::alligator(&highs, &lows);
 
         Self {
-            ema_short, ema_long, rsi, sma_short, sma_long, atr,
+            ema_short, ema_long, rsi, rsi_relative, sma_short, sma_long, atr,
             closes, opens, highs, lows, volumes,
-            bb_upper, bb_lower, bb_mid, vwap, adx, plus_di, minus_di,
+            bb_upper, bb_lower, bb_mid, vwap, vwap_band, vwap_band_std, adx, plus_di, minus_di,
+            fractal_up, fractal_down, alligator_jaw, alligator_teeth, alligator_lips,
         }
     }
 }
 
-fn calc_bollinger(closes: &[f64], period: usize, mult: f64) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-    let n = closes.len();
-    let mut upper = vec![0.0; n];
-    let mut lower = vec![0.0; n];
-    let mut mid = vec![0.0; n];
-    for i in period.saturating_sub(1)..n {
-        let start = if i + 1 >= period { i + 1 - period } else { 0 };
-        let slice = &closes[start..=i];
-        let mean = slice.iter().sum::<f64>() / slice.len() as f64;
-        let var = slice.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / slice.len() as f64;
-        let std = var.sqrt();
-        mid[i] = mean;
-        upper[i] = mean + mult * std;
-        lower[i] = mean - mult * std;
-    }
-    (upper, lower, mid)
-}
-
-fn calc_vwap(highs: &[f64], lows: &[f64], closes: &[f64], volumes: &[f64]) -> Vec<f64> {
-    let n = closes.len();
-    let mut vwap = vec![0.0; n];
-    let mut cum_pv = 0.0;
-    let mut cum_vol = 0.0;
-    for i in 0..n {
-        let typical = (highs[i] + lows[i] + closes[i]) / 3.0;
-        cum_pv += typical * volumes[i];
-        cum_vol += volumes[i];
-        vwap[i] = if cum_vol > 0.0 { cum_pv / cum_vol } else { closes[i] };
-    }
-    vwap
-}
-
-fn calc_adx(highs: &[f64], lows: &[f64], closes: &[f64], period: usize) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
-    let n = closes.len();
-    let mut adx = vec![0.0; n];
-    let mut plus_di = vec![0.0; n];
-    let mut minus_di = vec![0.0; n];
-    if n < period + 1 { return (adx, plus_di, minus_di); }
-
-    let mut tr_sum = 0.0;
-    let mut plus_dm_sum = 0.0;
-    let mut minus_dm_sum = 0.0;
-    let mut dx_sum = 0.0;
-    let mut dx_count = 0usize;
-
-    for i in 1..n {
-        let tr = (highs[i] - lows[i])
-            .max((highs[i] - closes[i - 1]).abs())
-            .max((lows[i] - closes[i - 1]).abs());
-        let up = highs[i] - highs[i - 1];
-        let down = lows[i - 1] - lows[i];
-        let pdm = if up > down && up > 0.0 { up } else { 0.0 };
-        let mdm = if down > up && down > 0.0 { down } else { 0.0 };
-
-        if i <= period {
-            tr_sum += tr;
-            plus_dm_sum += pdm;
-            minus_dm_sum += mdm;
-            if i == period {
-                let pdi = if tr_sum > 0.0 { plus_dm_sum / tr_sum * 100.0 } else { 0.0 };
-                let mdi = if tr_sum > 0.0 { minus_dm_sum / tr_sum * 100.0 } else { 0.0 };
-                plus_di[i] = pdi;
-                minus_di[i] = mdi;
-                let di_sum = pdi + mdi;
-                let dx = if di_sum > 0.0 { (pdi - mdi).abs() / di_sum * 100.0 } else { 0.0 };
-                dx_sum += dx;
-                dx_count += 1;
-                adx[i] = dx;
-            }
-        } else {
-            let p = period as f64;
-            tr_sum = tr_sum - tr_sum / p + tr;
-            plus_dm_sum = plus_dm_sum - plus_dm_sum / p + pdm;
-            minus_dm_sum = minus_dm_sum - minus_dm_sum / p + mdm;
-            let pdi = if tr_sum > 0.0 { plus_dm_sum / tr_sum * 100.0 } else { 0.0 };
-            let mdi = if tr_sum > 0.0 { minus_dm_sum / tr_sum * 100.0 } else { 0.0 };
-            plus_di[i] = pdi;
-            minus_di[i] = mdi;
-            let di_sum = pdi + mdi;
-            let dx = if di_sum > 0.0 { (pdi - mdi).abs() / di_sum * 100.0 } else { 0.0 };
-            dx_sum += dx;
-            dx_count += 1;
-            adx[i] = if dx_count >= period {
-                (adx[i - 1] * (p - 1.0) + dx) / p
-            } else {
-                dx_sum / dx_count as f64
-            };
-        }
+/// The parsed `rsi_transform`, `None` when empty. Only RSI can be transformed.
+pub(crate) fn rsi_transform(expr: &str) -> Result<Option<indicators::Transform>, String> {
+    if expr.trim().is_empty() {
+        return Ok(None);
+    }
+    let t = indicators::Transform::parse(expr)?;
+    if !matches!(t.source.as_str(), "rsi" | "rsi_14") {
+        return Err(format!("Invalid rsi_transform '{}': the series must be rsi_14", expr));
+    }
+    Ok(Some(t))
+}
+
+/// VWAP restart points for an anchor: "session" (each trading day of an
+/// intraday series), "week" (first bar of each ISO week) or "none" (anchored
+/// at the first bar). `None` for an unknown anchor.
+pub(crate) fn anchor_starts(candles: &[Candle], anchor: &str) -> Option<Vec<bool>> {
+    match anchor {
+        "session" => Some(session_starts(candles)),
+        "week" => {
+            let weeks: Vec<Option<chrono::IsoWeek>> = candles.iter()
+                .map(|c| parse_timestamp(&c.timestamp).map(|t| t.date().iso_week()))
+                .collect();
+            Some((0..candles.len()).map(|i| i > 0 && weeks[i].is_some() && weeks[i] != weeks[i - 1]).collect())
+        }
+        "none" => Some(vec![false; candles.len()]),
+        _ => None,
     }
-    (adx, plus_di, minus_di)
 }
 
 // ─── The Strategy Trait ───────────────────────────────────────────────
@@ -198,6 +173,19 @@ pub trait Strategy: Send + Sync {
 
     /// Reset internal state for a fresh run.
     fn reset(&mut self);
+
+    /// Whether `signal` (just returned by `on_candle`) only closes the open
+    /// position, e.g. an intraday square-off, rather than also opening one
+    /// the other way.
+    fn is_exit(&self, _signal: &Signal) -> bool { false }
+
+    /// Whether `signal` adds to the open position on the same side
+    /// (pyramiding) rather than being ignored while one is held.
+    fn is_add(&self, _signal: &Signal) -> bool { false }
+
+    /// Size of the entry or add `signal` asks for; `None` takes the largest
+    /// quantity the position limit allows.
+    fn unit_sizing(&self, _signal: &Signal) -> Option<UnitSizing> { None }
 }
 
 // ─── Built-in Strategies ──────────────────────────────────────────────
@@ -425,14 +413,20 @@ impl Strategy for SmaCrossover {
 pub struct RsiReversal {
     oversold: f64,
     overbought: f64,
+    /// Thresholds are in standard deviations (`zscore` transform) rather
+    /// than on a 0-100 scale.
+    zscore: bool,
     in_position: bool,
 }
 
 impl RsiReversal {
     pub fn new(config: &EngineConfig) -> Self {
+        let zscore = matches!(rsi_transform(&config.backtest.rsi_transform),
+            Ok(Some(t)) if t.kind == indicators::TransformKind::ZScore);
         Self {
             oversold: config.backtest.rsi_oversold,
             overbought: config.backtest.rsi_overbought,
+            zscore,
             in_position: false,
         }
     }
@@ -446,9 +440,14 @@ impl Strategy for RsiReversal {
     fn reset(&mut self) { self.in_position = false; }
 
     fn on_candle(&mut self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
-        if i >= ind.rsi.len() || ind.rsi[i].is_nan() || i < 14 { return None; }
+        if i >= ind.rsi_relative.len() || ind.rsi_relative[i].is_nan() || i < 14 { return None; }
 
-        let rsi = ind.rsi[i];
+        let rsi = ind.rsi_relative[i];
+        let (below, above) = if self.zscore {
+            (self.oversold.abs().max(1.0), self.overbought.abs().max(1.0))
+        } else {
+            (self.oversold, 100.0 - self.overbought)
+        };
 
         if !self.in_position && rsi < self.oversold {
             self.in_position = true;
@@ -457,8 +456,8 @@ impl Strategy for RsiReversal {
                 price: candle.close,
                 stop_loss: Some(candle.close - ind.atr[i] * 2.0),
                 take_profit: None,
-                confidence: ((self.oversold - rsi) / self.oversold).min(1.0),
-                reason: format!("RSI {:.1} below oversold {:.0}", rsi, self.oversold),
+                confidence: ((self.oversold - rsi) / below).min(1.0),
+                reason: format!("RSI {:.1} below oversold {}", rsi, self.oversold),
             })
         } else if self.in_position && rsi > self.overbought {
             self.in_position = false;
@@ -467,8 +466,8 @@ impl Strategy for RsiReversal {
                 price: candle.close,
                 stop_loss: None,
                 take_profit: None,
-                confidence: ((rsi - self.overbought) / (100.0 - self.overbought)).min(1.0),
-                reason: format!("RSI {:.1} above overbought {:.0}", rsi, self.overbought),
+                confidence: ((rsi - self.overbought) / above).min(1.0),
+                reason: format!("RSI {:.1} above overbought {}", rsi, self.overbought),
             })
         } else {
             None
@@ -622,9 +621,81 @@ impl Strategy for Momentum {
     }
 }
 
+/// Session bookkeeping for intraday strategies: which exchange-local date a
+/// bar belongs to and how far into that session it starts. Timestamps are
+/// already in exchange time (see `timezone`); bars stamped at midnight are
+/// treated as daily and yield `None`.
+#[derive(Default)]
+struct SessionClock {
+    date: Option<NaiveDate>,
+    open: Option<NaiveTime>,
+}
+
+struct SessionBar {
+    new_session: bool,
+    minutes: i64,
+    time: NaiveTime,
+}
+
+impl SessionClock {
+    fn observe(&mut self, timestamp: &str) -> Option<SessionBar> {
+        let t = parse_timestamp(timestamp)?;
+        if t.time() == NaiveTime::MIN {
+            return None;
+        }
+        let new_session = self.date != Some(t.date());
+        if new_session {
+            self.date = Some(t.date());
+            self.open = Some(t.time());
+        }
+        let open = self.open.unwrap_or(t.time());
+        Some(SessionBar { new_session, minutes: (t.time() - open).num_minutes(), time: t.time() })
+    }
+}
+
+/// "HH:MM" or "HH:MM:SS".
+pub(crate) fn parse_clock(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))
+        .ok()
+}
+
+fn square_off_time(config: &EngineConfig) -> NaiveTime {
+    parse_clock(&config.backtest.intraday_square_off)
+        .unwrap_or(NaiveTime::from_hms_opt(15, 15, 0).unwrap_or(NaiveTime::MIN))
+}
+
+/// Exit-only signal that flattens an intraday position.
+fn square_off_signal(held: Side, price: f64, reason: &str) -> Signal {
+    Signal {
+        side: match held { Side::Buy => Side::Sell, Side::Sell => Side::Buy },
+        price,
+        stop_loss: None,
+        take_profit: None,
+        confidence: 1.0,
+        reason: reason.into(),
+    }
+}
+
+/// Opening-range breakout. On intraday bars the range is the high/low of the
+/// first `orb_range_minutes` of each session; a break beyond it by
+/// `orb_buffer_pct` enters (at most `orb_max_trades_per_day` per session),
+/// with the stop at the tighter of the range's far side and
+/// `orb_stop_loss_pct`. Anything still open at `intraday_square_off` — or at
+/// the next session's first bar if data is missing — is squared off. On daily
+/// bars it falls back to breaking the previous bar's range.
 pub struct OpeningRangeBreakout {
     target_pct: f64,
     stop_loss_pct: f64,
+    range_minutes: i64,
+    buffer: f64,
+    square_off: NaiveTime,
+    max_trades: usize,
+    clock: SessionClock,
+    range: Option<(f64, f64)>,
+    trades_today: usize,
+    position: Option<Side>,
+    exiting: bool,
 }
 
 impl OpeningRangeBreakout {
@@ -632,18 +703,19 @@ impl OpeningRangeBreakout {
         Self {
             target_pct: config.backtest.orb_target_pct / 100.0,
             stop_loss_pct: config.backtest.orb_stop_loss_pct / 100.0,
+            range_minutes: config.backtest.orb_range_minutes as i64,
+            buffer: config.backtest.orb_buffer_pct / 100.0,
+            square_off: square_off_time(config),
+            max_trades: config.backtest.orb_max_trades_per_day,
+            clock: SessionClock::default(),
+            range: None,
+            trades_today: 0,
+            position: None,
+            exiting: false,
         }
     }
-}
-
-impl Strategy for OpeningRangeBreakout {
-    fn name(&self) -> &str { "opening_range_breakout" }
-
-    fn warmup_period(&self) -> usize { 2 }
-
-    fn reset(&mut self) {}
 
-    fn on_candle(&mut self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
+    fn previous_bar_breakout(&self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
         if i < 1 { return None; }
 
         let prev_high = ind.highs[i - 1];
@@ -678,6 +750,171 @@ impl Strategy for OpeningRangeBreakout {
     }
 }
 
+impl Strategy for OpeningRangeBreakout {
+    fn name(&self) -> &str { "opening_range_breakout" }
+
+    fn warmup_period(&self) -> usize { 2 }
+
+    /// Called after a stop/target exit: flat again, but the session's range
+    /// and trade count stand.
+    fn reset(&mut self) {
+        self.position = None;
+    }
+
+    fn is_exit(&self, _signal: &Signal) -> bool { self.exiting }
+
+    fn on_candle(&mut self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
+        self.exiting = false;
+        let Some(bar) = self.clock.observe(&candle.timestamp) else {
+            return self.previous_bar_breakout(i, candle, ind);
+        };
+        if bar.new_session {
+            self.range = Some((candle.high, candle.low));
+            self.trades_today = 0;
+            if let Some(held) = self.position.take() {
+                self.exiting = true;
+                return Some(square_off_signal(held, candle.open, "ORB: square-off carried over from previous session"));
+            }
+            return None;
+        }
+        let (high, low) = self.range?;
+        if bar.minutes < self.range_minutes {
+            self.range = Some((high.max(candle.high), low.min(candle.low)));
+            return None;
+        }
+        if bar.time >= self.square_off {
+            return self.position.take().map(|held| {
+                self.exiting = true;
+                square_off_signal(held, candle.close, "ORB: intraday square-off")
+            });
+        }
+        if self.position.is_some() || self.trades_today >= self.max_trades {
+            return None;
+        }
+
+        let width = high - low;
+        let confidence = if low > 0.0 { (width / low * 20.0).min(1.0) } else { 0.0 };
+        let (up, down) = (high * (1.0 + self.buffer), low * (1.0 - self.buffer));
+        let (side, entry, stop, target, reason) = if candle.high > up {
+            let entry = up.max(candle.open);
+            (Side::Buy, entry, low.max(entry * (1.0 - self.stop_loss_pct)), entry * (1.0 + self.target_pct),
+             format!("ORB: broke {}-minute range high {:.2}", self.range_minutes, high))
+        } else if candle.low < down {
+            let entry = down.min(candle.open);
+            (Side::Sell, entry, high.min(entry * (1.0 + self.stop_loss_pct)), entry * (1.0 - self.target_pct),
+             format!("ORB: broke {}-minute range low {:.2}", self.range_minutes, low))
+        } else {
+            return None;
+        };
+        self.trades_today += 1;
+        self.position = Some(side.clone());
+        Some(Signal { side, price: entry, stop_loss: Some(stop), take_profit: Some(target), confidence, reason })
+    }
+}
+
+/// Gap-and-go: when a session opens at least `gap_min_pct` away from the
+/// previous close and its first bar closes in the gap's direction, enter on
+/// a break of that bar's extreme, stop at its other extreme, target
+/// `orb_target_pct`, squared off at `intraday_square_off`. Intraday bars
+/// only; one trade per session.
+pub struct GapAndGo {
+    min_gap_pct: f64,
+    target_pct: f64,
+    square_off: NaiveTime,
+    clock: SessionClock,
+    /// Gap direction (true = up) and the first bar's (high, low), when the
+    /// session qualifies.
+    setup: Option<(bool, f64, f64)>,
+    traded: bool,
+    position: Option<Side>,
+    exiting: bool,
+}
+
+impl GapAndGo {
+    pub fn new(config: &EngineConfig) -> Self {
+        Self {
+            min_gap_pct: config.backtest.gap_min_pct,
+            target_pct: config.backtest.orb_target_pct / 100.0,
+            square_off: square_off_time(config),
+            clock: SessionClock::default(),
+            setup: None,
+            traded: false,
+            position: None,
+            exiting: false,
+        }
+    }
+}
+
+impl Strategy for GapAndGo {
+    fn name(&self) -> &str { "gap_and_go" }
+
+    fn warmup_period(&self) -> usize { 2 }
+
+    fn reset(&mut self) {
+        self.position = None;
+    }
+
+    fn is_exit(&self, _signal: &Signal) -> bool { self.exiting }
+
+    fn on_candle(&mut self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
+        self.exiting = false;
+        let bar = self.clock.observe(&candle.timestamp)?;
+        if bar.new_session {
+            self.traded = false;
+            self.setup = None;
+            let prev_close = if i > 0 { ind.closes[i - 1] } else { 0.0 };
+            if prev_close > 0.0 {
+                let gap_pct = (candle.open - prev_close) / prev_close * 100.0;
+                let confirmed = if gap_pct > 0.0 { candle.close > candle.open } else { candle.close < candle.open };
+                if gap_pct.abs() >= self.min_gap_pct && confirmed {
+                    self.setup = Some((gap_pct > 0.0, candle.high, candle.low));
+                }
+            }
+            if let Some(held) = self.position.take() {
+                self.exiting = true;
+                return Some(square_off_signal(held, candle.open, "Gap-and-go: square-off carried over from previous session"));
+            }
+            return None;
+        }
+        if bar.time >= self.square_off {
+            return self.position.take().map(|held| {
+                self.exiting = true;
+                square_off_signal(held, candle.close, "Gap-and-go: intraday square-off")
+            });
+        }
+        if self.traded || self.position.is_some() {
+            return None;
+        }
+        let (up, high, low) = self.setup?;
+        let signal = if up && candle.high > high {
+            let entry = high.max(candle.open);
+            Signal {
+                side: Side::Buy,
+                price: entry,
+                stop_loss: Some(low),
+                take_profit: Some(entry * (1.0 + self.target_pct)),
+                confidence: ((high - low) / low * 20.0).min(1.0),
+                reason: format!("Gap-and-go: gap up held, broke first-bar high {:.2}", high),
+            }
+        } else if !up && candle.low < low {
+            let entry = low.min(candle.open);
+            Signal {
+                side: Side::Sell,
+                price: entry,
+                stop_loss: Some(high),
+                take_profit: Some(entry * (1.0 - self.target_pct)),
+                confidence: ((high - low) / low * 20.0).min(1.0),
+                reason: format!("Gap-and-go: gap down held, broke first-bar low {:.2}", low),
+            }
+        } else {
+            return None;
+        };
+        self.traded = true;
+        self.position = Some(signal.side.clone());
+        Some(signal)
+    }
+}
+
 // ─── New Strategies ───────────────────────────────────────────────────
 
 pub struct GapTrading {
@@ -811,6 +1048,236 @@ impl Strategy for VwapReversion {
     }
 }
 
+/// Fades closes beyond the anchored VWAP ± `vwap_band_mult` σ back toward
+/// VWAP. Targets the VWAP at entry, stops one σ beyond the entry close, and
+/// exits when the close crosses the live VWAP or after `vwap_band_max_bars`.
+/// On intraday bars nothing is held past `intraday_square_off` (or into the
+/// next session).
+pub struct VwapBandReversion {
+    mult: f64,
+    max_bars: usize,
+    square_off: NaiveTime,
+    clock: SessionClock,
+    position: Option<Side>,
+    bars_held: usize,
+    exiting: bool,
+}
+
+impl VwapBandReversion {
+    pub fn new(config: &EngineConfig) -> Self {
+        Self {
+            mult: config.backtest.vwap_band_mult,
+            max_bars: config.backtest.vwap_band_max_bars.max(1),
+            square_off: square_off_time(config),
+            clock: SessionClock::default(),
+            position: None,
+            bars_held: 0,
+            exiting: false,
+        }
+    }
+
+    fn exit(&mut self, price: f64, reason: &str) -> Option<Signal> {
+        self.bars_held = 0;
+        self.position.take().map(|held| {
+            self.exiting = true;
+            square_off_signal(held, price, reason)
+        })
+    }
+}
+
+impl Strategy for VwapBandReversion {
+    fn name(&self) -> &str { "vwap_band_reversion" }
+    fn warmup_period(&self) -> usize { 5 }
+
+    fn reset(&mut self) {
+        self.position = None;
+        self.bars_held = 0;
+    }
+
+    fn is_exit(&self, _signal: &Signal) -> bool { self.exiting }
+
+    fn on_candle(&mut self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
+        self.exiting = false;
+        let bar = self.clock.observe(&candle.timestamp);
+        if let Some(bar) = &bar {
+            if bar.new_session && self.position.is_some() {
+                return self.exit(candle.open, "VWAP band: square-off carried over from previous session");
+            }
+            if bar.time >= self.square_off {
+                return self.exit(candle.close, "VWAP band: intraday square-off");
+            }
+        }
+        let (vwap, std) = (ind.vwap_band[i], ind.vwap_band_std[i]);
+        if let Some(held) = &self.position {
+            self.bars_held += 1;
+            let reverted = match held {
+                Side::Buy => candle.close >= vwap,
+                Side::Sell => candle.close <= vwap,
+            };
+            if reverted {
+                return self.exit(candle.close, "VWAP band: price returned to VWAP");
+            }
+            if self.bars_held >= self.max_bars {
+                return self.exit(candle.close, &format!("VWAP band: no reversion after {} bars", self.max_bars));
+            }
+            return None;
+        }
+        if i < self.warmup_period() || std <= 0.0 || vwap <= 0.0 {
+            return None;
+        }
+
+        let z = (candle.close - vwap) / std;
+        let side = if z > self.mult {
+            Side::Sell
+        } else if z < -self.mult {
+            Side::Buy
+        } else {
+            return None;
+        };
+        let stop = match side {
+            Side::Buy => candle.close - std,
+            Side::Sell => candle.close + std,
+        };
+        self.position = Some(side.clone());
+        self.bars_held = 0;
+        Some(Signal {
+            side,
+            price: candle.close,
+            stop_loss: Some(stop),
+            take_profit: Some(vwap),
+            confidence: (z.abs() / self.mult / 2.0).min(1.0),
+            reason: format!("VWAP band: close {:.2}σ from VWAP {:.2}, fading", z, vwap),
+        })
+    }
+}
+
+/// Turtle-style Donchian breakout. A close beyond the prior
+/// `turtle_entry_period`-bar high (low) enters one unit long (short); each
+/// further `turtle_add_step` N in its favour adds a unit up to
+/// `turtle_max_units`. N is ATR(14). Every unit moves the stop for the whole
+/// position to `turtle_stop_n` N from the latest entry, and a close beyond the
+/// opposite `turtle_exit_period`-bar extreme exits. Units are sized to risk
+/// `turtle_unit_risk_pct` of equity per N.
+pub struct Turtle {
+    entry_period: usize,
+    exit_period: usize,
+    max_units: usize,
+    add_step: f64,
+    stop_n: f64,
+    risk_pct: f64,
+    position: Option<Side>,
+    units: usize,
+    last_entry: f64,
+    n: f64,
+    exiting: bool,
+    adding: bool,
+}
+
+impl Turtle {
+    pub fn new(config: &EngineConfig) -> Self {
+        Self {
+            entry_period: config.backtest.turtle_entry_period.max(1),
+            exit_period: config.backtest.turtle_exit_period.max(1),
+            max_units: config.backtest.turtle_max_units.max(1),
+            add_step: config.backtest.turtle_add_step,
+            stop_n: config.backtest.turtle_stop_n,
+            risk_pct: config.backtest.turtle_unit_risk_pct,
+            position: None,
+            units: 0,
+            last_entry: 0.0,
+            n: 0.0,
+            exiting: false,
+            adding: false,
+        }
+    }
+
+    fn unit(&mut self, side: Side, price: f64, n: f64, reason: String) -> Option<Signal> {
+        self.adding = self.position.is_some();
+        self.units += 1;
+        self.last_entry = price;
+        self.n = n;
+        let stop = match side {
+            Side::Buy => price - self.stop_n * n,
+            Side::Sell => price + self.stop_n * n,
+        };
+        self.position = Some(side.clone());
+        Some(Signal {
+            side,
+            price,
+            stop_loss: Some(stop),
+            take_profit: None,
+            confidence: (self.units as f64 / self.max_units as f64).min(1.0),
+            reason,
+        })
+    }
+}
+
+impl Strategy for Turtle {
+    fn name(&self) -> &str { "turtle" }
+    fn warmup_period(&self) -> usize { self.entry_period.max(self.exit_period) }
+
+    fn reset(&mut self) {
+        self.position = None;
+        self.units = 0;
+    }
+
+    fn is_exit(&self, _signal: &Signal) -> bool { self.exiting }
+
+    fn is_add(&self, _signal: &Signal) -> bool { self.adding }
+
+    fn unit_sizing(&self, _signal: &Signal) -> Option<UnitSizing> {
+        (self.n > 0.0).then_some(UnitSizing { risk_pct: self.risk_pct, price_move: self.n })
+    }
+
+    fn on_candle(&mut self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
+        self.exiting = false;
+        self.adding = false;
+        if i < self.warmup_period() { return None; }
+        let n = ind.atr[i];
+        let close = candle.close;
+        let channel = |period: usize| {
+            let (highs, lows) = (&ind.highs[i - period..i], &ind.lows[i - period..i]);
+            (highs.iter().copied().fold(f64::MIN, f64::max), lows.iter().copied().fold(f64::MAX, f64::min))
+        };
+        let (exit_high, exit_low) = channel(self.exit_period);
+
+        match self.position.clone() {
+            Some(held) => {
+                let broke_exit = match held {
+                    Side::Buy => close < exit_low,
+                    Side::Sell => close > exit_high,
+                };
+                if broke_exit {
+                    self.exiting = true;
+                    self.reset();
+                    return Some(square_off_signal(held, close, &format!("Turtle: {}-bar channel exit", self.exit_period)));
+                }
+                let step = self.add_step * self.n;
+                let advanced = match held {
+                    Side::Buy => close >= self.last_entry + step,
+                    Side::Sell => close <= self.last_entry - step,
+                };
+                if self.units < self.max_units && advanced && n > 0.0 {
+                    let reason = format!("Turtle: unit {} after {:.1}N advance", self.units + 1, self.add_step);
+                    return self.unit(held, close, n, reason);
+                }
+                None
+            }
+            None => {
+                if n <= 0.0 { return None; }
+                let (entry_high, entry_low) = channel(self.entry_period);
+                if close > entry_high {
+                    self.unit(Side::Buy, close, n, format!("Turtle: close above {}-bar high {:.2}", self.entry_period, entry_high))
+                } else if close < entry_low {
+                    self.unit(Side::Sell, close, n, format!("Turtle: close below {}-bar low {:.2}", self.entry_period, entry_low))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
 pub struct VolatilityBreakout {
     in_position: bool,
 }
@@ -1240,6 +1707,79 @@ impl Strategy for TrendFollowing {
     }
 }
 
+/// Bill Williams breakout: with the Alligator's lines fanned out in order
+/// (lips above teeth above jaw for longs, the reverse for shorts) a close
+/// through the latest up (down) fractal enters, stopped at the opposite
+/// fractal. A close back through the teeth exits. Each fractal level is
+/// traded once.
+pub struct FractalBreakout {
+    /// +1 long, -1 short, 0 flat.
+    direction: i8,
+    last_level: f64,
+}
+
+impl FractalBreakout {
+    pub fn new(_config: &EngineConfig) -> Self {
+        Self { direction: 0, last_level: 0.0 }
+    }
+}
+
+impl Strategy for FractalBreakout {
+    fn name(&self) -> &str { "fractal_breakout" }
+    // The jaw's 13-bar average pushed 8 bars forward.
+    fn warmup_period(&self) -> usize { 21 }
+    fn reset(&mut self) { self.direction = 0; self.last_level = 0.0; }
+
+    fn on_candle(&mut self, i: usize, candle: &Candle, ind: &Indicators) -> Option<Signal> {
+        let (jaw, teeth, lips) = (ind.alligator_jaw[i], ind.alligator_teeth[i], ind.alligator_lips[i]);
+        if i < 21 || jaw == 0.0 { return None; }
+
+        if self.direction != 0 {
+            let long = self.direction > 0;
+            if (long && candle.close < teeth) || (!long && candle.close > teeth) {
+                self.direction = 0;
+                return Some(Signal {
+                    side: if long { Side::Sell } else { Side::Buy },
+                    price: candle.close,
+                    stop_loss: None,
+                    take_profit: None,
+                    confidence: 1.0,
+                    reason: format!("Fractal exit: close {:.2} back through teeth {:.2}", candle.close, teeth),
+                });
+            }
+            return None;
+        }
+
+        let (up, down) = (ind.fractal_up[i], ind.fractal_down[i]);
+        let spread = (lips - jaw).abs() / jaw;
+        if lips > teeth && teeth > jaw && up > 0.0 && candle.close > up && up != self.last_level {
+            self.direction = 1;
+            self.last_level = up;
+            return Some(Signal {
+                side: Side::Buy,
+                price: candle.close,
+                stop_loss: (down > 0.0 && down < candle.close).then_some(down),
+                take_profit: None,
+                confidence: (spread * 50.0).min(1.0),
+                reason: format!("Fractal breakout above {:.2}, Alligator up", up),
+            });
+        }
+        if lips < teeth && teeth < jaw && down > 0.0 && candle.close < down && down != self.last_level {
+            self.direction = -1;
+            self.last_level = down;
+            return Some(Signal {
+                side: Side::Sell,
+                price: candle.close,
+                stop_loss: (up > candle.close).then_some(up),
+                take_profit: None,
+                confidence: (spread * 50.0).min(1.0),
+                reason: format!("Fractal breakdown below {:.2}, Alligator down", down),
+            });
+        }
+        None
+    }
+}
+
 // ─── Strategy Registry ────────────────────────────────────────────────
 
 /// Create a strategy instance by name, configured from the engine config.
@@ -1269,9 +1809,15 @@ pub fn create_strategy(name: &str, config: &EngineConfig) -> Result<Box<dyn Stra
         "gap_trading" | "gap-trading" => {
             Ok(Box::new(GapTrading::new(config)))
         }
+        "gap_and_go" | "gap-and-go" => {
+            Ok(Box::new(GapAndGo::new(config)))
+        }
         "vwap_reversion" | "vwap-reversion" => {
             Ok(Box::new(VwapReversion::new(config)))
         }
+        "vwap_band_reversion" | "vwap-band-reversion" | "vwap_bands" => {
+            Ok(Box::new(VwapBandReversion::new(config)))
+        }
         "volatility_breakout" | "volatility-breakout" => {
             Ok(Box::new(VolatilityBreakout::new(config)))
         }
@@ -1290,6 +1836,12 @@ pub fn create_strategy(name: &str, config: &EngineConfig) -> Result<Box<dyn Stra
         "trend_following" | "trend-following" | "adx" => {
             Ok(Box::new(TrendFollowing::new(config)))
         }
+        "turtle" | "donchian" => {
+            Ok(Box::new(Turtle::new(config)))
+        }
+        "fractal_breakout" | "fractal-breakout" | "alligator" => {
+            Ok(Box::new(FractalBreakout::new(config)))
+        }
         _ => Err(format!(
             "Unknown strategy: '{}'. Available: {}",
             name,
@@ -1309,19 +1861,24 @@ pub fn available_strategies() -> Vec<&'static str> {
         "momentum",
         "opening_range_breakout",
         "gap_trading",
+        "gap_and_go",
         "vwap_reversion",
+        "vwap_band_reversion",
         "volatility_breakout",
         "sector_rotation",
         "pairs_trading",
         "expiry_theta",
         "calendar_spread",
         "trend_following",
+        "turtle",
+        "fractal_breakout",
     ]
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::fixtures::grid_date;
 
     fn make_trending_candles(n: usize, start: f64, step: f64) -> Vec<Candle> {
         (0..n).map(|i| {
@@ -1361,11 +1918,13 @@ mod tests {
     #[test]
     fn test_available_strategies() {
         let names = available_strategies();
-        assert_eq!(names.len(), 15);
+        assert_eq!(names.len(), 19);
         assert!(names.contains(&"ema_crossover"));
         assert!(names.contains(&"supertrend"));
         assert!(names.contains(&"gap_trading"));
         assert!(names.contains(&"trend_following"));
+        assert!(names.contains(&"gap_and_go"));
+        assert!(names.contains(&"vwap_band_reversion"));
     }
 
     #[test]
@@ -1520,6 +2079,17 @@ mod tests {
         assert_eq!(signal_count, 0, "No signals expected when there is no gap");
     }
 
+    #[test]
+    fn test_anchor_starts_by_week() {
+        // Fri 2025-01-03, Mon 01-06, Tue 01-07, Mon 01-13.
+        let candles: Vec<Candle> = ["2025-01-03", "2025-01-06", "2025-01-07", "2025-01-13"].iter()
+            .map(|d| Candle { timestamp: d.to_string(), open: 1.0, high: 1.0, low: 1.0, close: 1.0, volume: 1.0 })
+            .collect();
+        assert_eq!(anchor_starts(&candles, "week"), Some(vec![false, true, false, true]));
+        assert_eq!(anchor_starts(&candles, "none"), Some(vec![false; 4]));
+        assert!(anchor_starts(&candles, "month").is_none());
+    }
+
     #[test]
     fn test_vwap_reversion_below_vwap() {
         let config = make_config();
@@ -1656,6 +2226,33 @@ mod tests {
         assert!(buy_found, "Trend following should generate BUY on strong uptrend with high ADX");
     }
 
+    #[test]
+    fn test_fractal_breakout_enters_on_break_and_exits_at_teeth() {
+        let config = make_config();
+        // A rising zigzag leaves a fractal at each swing high, then a slide.
+        let candles: Vec<Candle> = (0..80).map(|i| {
+            let x = i as f64;
+            let close = if i < 60 { 100.0 + x * 0.6 + (x * 0.9).sin() * 2.0 } else { 136.0 - (x - 60.0) * 2.0 };
+            Candle { timestamp: grid_date(i), open: close, high: close + 0.5, low: close - 0.5, close, volume: 10000.0 }
+        }).collect();
+        let ind = Indicators::from_candles(&candles, &config);
+        let mut strat = create_strategy("fractal_breakout", &config).unwrap();
+        let signals: Vec<(usize, Signal)> = candles.iter().enumerate()
+            .filter_map(|(i, c)| strat.on_candle(i, c, &ind).map(|s| (i, s)))
+            .collect();
+        let (entry_bar, entry) = &signals[0];
+        assert!(matches!(entry.side, Side::Buy));
+        assert!(entry.price > ind.fractal_up[*entry_bar] && ind.fractal_up[*entry_bar] > 0.0);
+        assert!(entry.stop_loss.is_some_and(|s| s < entry.price));
+        // The long is closed back below the teeth; the slide then fans the
+        // Alligator down for a short.
+        let (exit_bar, exit) = signals.iter().find(|(_, s)| s.reason.starts_with("Fractal exit")).unwrap();
+        assert!(matches!(exit.side, Side::Sell));
+        assert!(exit.price < ind.alligator_teeth[*exit_bar]);
+        let short = signals.iter().find(|(_, s)| s.reason.contains("breakdown")).map(|(i, _)| *i);
+        assert!(short.is_none_or(|i| i >= 60));
+    }
+
     #[test]
     fn test_trend_following_no_trend() {
         let config = make_config();
@@ -1818,6 +2415,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gap_and_go_needs_confirmed_gap() {
+        let config = make_config();
+        let bar = |ts: &str, open: f64, close: f64| Candle {
+            timestamp: ts.to_string(), open, high: open.max(close) + 0.2, low: open.min(close) - 0.2, close, volume: 1e5,
+        };
+        let candles = vec![
+            bar("2025-03-03T15:25:00", 100.0, 100.0),
+            // Opens 3% up and closes green: the gap holds.
+            bar("2025-03-04T09:15:00", 103.0, 103.6),
+            bar("2025-03-04T09:20:00", 103.6, 104.2),
+            bar("2025-03-04T15:15:00", 104.5, 104.8),
+        ];
+        let ind = Indicators::from_candles(&candles, &config);
+        let mut s = create_strategy("gap-and-go", &config).unwrap();
+        assert!(s.on_candle(0, &candles[0], &ind).is_none());
+        assert!(s.on_candle(1, &candles[1], &ind).is_none());
+        let entry = s.on_candle(2, &candles[2], &ind).unwrap();
+        assert!(matches!(entry.side, Side::Buy));
+        assert_eq!(entry.price, 103.8);
+        assert_eq!(entry.stop_loss, Some(102.8));
+        assert!(!s.is_exit(&entry));
+        let exit = s.on_candle(3, &candles[3], &ind).unwrap();
+        assert!(matches!(exit.side, Side::Sell));
+        assert!(s.is_exit(&exit));
+
+        // Daily bars carry no session clock.
+        let daily: Vec<Candle> = ["2025-03-03", "2025-03-04"].iter().map(|d| bar(d, 100.0, 104.0)).collect();
+        let ind = Indicators::from_candles(&daily, &config);
+        let mut s = create_strategy("gap_and_go", &config).unwrap();
+        assert!(daily.iter().enumerate().all(|(i, c)| s.on_candle(i, c, &ind).is_none()));
+    }
+
     #[test]
     fn test_supertrend_is_not_ema_crossover() {
         let config = make_config();
@@ -0,0 +1,441 @@
+//! Split capital across strategies from their backtest return streams.
+//!
+//! Each strategy supplies `returns` (per-period fractions) or an
+//! `equity_curve` (`backtest`'s `[{date, nav}]`; a whole backtest result
+//! works too). Dated curves are aligned on common dates, anything else on
+//! the most recent `n` periods. Four allocations are returned side by side:
+//! `equal`, `kelly` (Σ⁻¹μ on excess returns, long-only, scaled by
+//! `kelly_fraction`), `vol_target` (inverse-vol weights scaled to
+//! `target_vol_pct`) and `risk_parity` (equal risk contribution, fully
+//! invested). Weights are capped at `max_leverage` gross; whatever is left
+//! earns `risk_free_rate` in the combined metrics.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::EquityPoint;
+use crate::utils::{round2, round4};
+
+#[derive(Deserialize)]
+struct AllocationConfig {
+    strategies: Vec<StrategyIn>,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    capital: Option<f64>,
+    #[serde(default = "default_periods")]
+    periods_per_year: f64,
+    #[serde(default = "default_rf")]
+    risk_free_rate: f64,
+    #[serde(default = "default_kelly_fraction")]
+    kelly_fraction: f64,
+    #[serde(default = "default_target_vol")]
+    target_vol_pct: f64,
+    #[serde(default = "default_max_leverage")]
+    max_leverage: f64,
+}
+
+fn default_method() -> String { "risk_parity".to_string() }
+fn default_periods() -> f64 { 252.0 }
+fn default_rf() -> f64 { 0.065 }
+fn default_kelly_fraction() -> f64 { 0.5 }
+fn default_target_vol() -> f64 { 15.0 }
+fn default_max_leverage() -> f64 { 1.0 }
+
+const MIN_OBSERVATIONS: usize = 10;
+
+#[derive(Deserialize)]
+struct StrategyIn {
+    #[serde(alias = "strategy")]
+    name: String,
+    #[serde(default)]
+    returns: Option<Vec<f64>>,
+    #[serde(default)]
+    equity_curve: Option<Vec<EquityPoint>>,
+}
+
+#[derive(Serialize)]
+struct StrategyStats {
+    name: String,
+    annual_return: f64,
+    volatility: f64,
+    sharpe: f64,
+    cagr: f64,
+    max_drawdown: f64,
+}
+
+#[derive(Serialize)]
+struct WeightOut {
+    name: String,
+    weight: f64,
+    capital: Option<f64>,
+    /// Share of portfolio variance, in percent.
+    risk_contribution_pct: f64,
+}
+
+#[derive(Serialize)]
+struct Allocation {
+    weights: Vec<WeightOut>,
+    gross_weight: f64,
+    cash_weight: f64,
+    annual_return: f64,
+    volatility: f64,
+    sharpe: f64,
+    cagr: f64,
+    max_drawdown: f64,
+    diversification_ratio: f64,
+}
+
+#[derive(Serialize)]
+struct Allocations {
+    equal: Allocation,
+    kelly: Allocation,
+    vol_target: Allocation,
+    risk_parity: Allocation,
+}
+
+#[derive(Serialize)]
+struct AllocationResult {
+    method: String,
+    /// Selected method's weights by strategy name.
+    weights: HashMap<String, f64>,
+    observations: usize,
+    aligned_on: &'static str,
+    strategies: Vec<StrategyStats>,
+    correlation_matrix: Vec<Vec<f64>>,
+    allocations: Allocations,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: AllocationConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid strategy_allocation config: {}", e))?;
+    if config.strategies.is_empty() {
+        return Err("At least one strategy required".to_string());
+    }
+    if !matches!(config.method.as_str(), "equal" | "kelly" | "vol_target" | "risk_parity") {
+        return Err(format!("Unknown method '{}': use equal, kelly, vol_target or risk_parity", config.method));
+    }
+    if config.periods_per_year <= 0.0 || config.max_leverage <= 0.0 {
+        return Err("periods_per_year and max_leverage must be positive".to_string());
+    }
+    let (aligned_on, series) = align(&config.strategies)?;
+    let t = series[0].len();
+    if t < MIN_OBSERVATIONS {
+        return Err(format!("Need at least {} aligned return observations, got {}", MIN_OBSERVATIONS, t));
+    }
+
+    let n = series.len();
+    let ppy = config.periods_per_year;
+    let rf = config.risk_free_rate / ppy;
+    let means: Vec<f64> = series.iter().map(|r| r.iter().sum::<f64>() / t as f64).collect();
+    let mut cov = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i..n {
+            let c = series[i].iter().zip(&series[j])
+                .map(|(a, b)| (a - means[i]) * (b - means[j]))
+                .sum::<f64>() / (t - 1) as f64;
+            cov[i][j] = c;
+            cov[j][i] = c;
+        }
+    }
+    let vols: Vec<f64> = (0..n).map(|i| cov[i][i].sqrt()).collect();
+    let corr = (0..n).map(|i| (0..n).map(|j| {
+        let d = vols[i] * vols[j];
+        if d > 0.0 { round4(cov[i][j] / d) } else { 0.0 }
+    }).collect()).collect();
+
+    let cap = |w: Vec<f64>| {
+        let gross: f64 = w.iter().sum();
+        if gross > config.max_leverage { w.iter().map(|x| x * config.max_leverage / gross).collect() } else { w }
+    };
+    let equal = cap(vec![1.0 / n as f64; n]);
+    let excess: Vec<f64> = means.iter().map(|m| m - rf).collect();
+    let kelly = cap(kelly_weights(&excess, &cov).iter().map(|f| f * config.kelly_fraction).collect());
+    let vol_target = {
+        let inv: Vec<f64> = vols.iter().map(|v| if *v > 0.0 { 1.0 / v } else { 0.0 }).collect();
+        let total: f64 = inv.iter().sum();
+        let base: Vec<f64> = inv.iter().map(|x| if total > 0.0 { x / total } else { 0.0 }).collect();
+        let port_vol = quad(&base, &cov).sqrt() * ppy.sqrt() * 100.0;
+        let scale = if port_vol > 0.0 { config.target_vol_pct / port_vol } else { 0.0 };
+        cap(base.iter().map(|w| w * scale).collect())
+    };
+    let risk_parity = cap(risk_parity_weights(&cov));
+
+    let names: Vec<&str> = config.strategies.iter().map(|s| s.name.as_str()).collect();
+    let summarize = |w: &[f64]| summarize(w, &names, &series, &cov, &vols, rf, ppy, config.capital);
+    let allocations = Allocations {
+        equal: summarize(&equal),
+        kelly: summarize(&kelly),
+        vol_target: summarize(&vol_target),
+        risk_parity: summarize(&risk_parity),
+    };
+    let selected = match config.method.as_str() {
+        "equal" => &allocations.equal,
+        "kelly" => &allocations.kelly,
+        "vol_target" => &allocations.vol_target,
+        _ => &allocations.risk_parity,
+    };
+    let weights = selected.weights.iter().map(|w| (w.name.clone(), w.weight)).collect();
+
+    let strategies = series.iter().zip(&names).map(|(r, name)| {
+        let m = metrics(r, rf, ppy);
+        StrategyStats { name: name.to_string(), annual_return: m.0, volatility: m.1, sharpe: m.2, cagr: m.3, max_drawdown: m.4 }
+    }).collect();
+
+    let result = AllocationResult {
+        method: config.method,
+        weights,
+        observations: t,
+        aligned_on,
+        strategies,
+        correlation_matrix: corr,
+        allocations,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+/// Per-strategy return series of equal length, and how they were aligned.
+fn align(strategies: &[StrategyIn]) -> Result<(&'static str, Vec<Vec<f64>>), String> {
+    let mut dated: Vec<Option<HashMap<&str, f64>>> = Vec::with_capacity(strategies.len());
+    let mut plain: Vec<Vec<f64>> = Vec::with_capacity(strategies.len());
+    for s in strategies {
+        match (&s.returns, &s.equity_curve) {
+            (Some(r), _) => {
+                dated.push(None);
+                plain.push(r.clone());
+            }
+            (None, Some(curve)) => {
+                let pairs: Vec<(&str, f64)> = curve.windows(2)
+                    .filter(|w| w[0].nav > 0.0)
+                    .map(|w| (w[1].date.as_str(), w[1].nav / w[0].nav - 1.0))
+                    .collect();
+                plain.push(pairs.iter().map(|p| p.1).collect());
+                dated.push(Some(pairs.into_iter().collect()));
+            }
+            (None, None) => return Err(format!("Strategy '{}' needs returns or equity_curve", s.name)),
+        }
+    }
+
+    if dated.iter().all(|d| d.is_some()) {
+        let first = strategies[0].equity_curve.as_deref().unwrap_or_default();
+        let maps: Vec<&HashMap<&str, f64>> = dated.iter().flatten().collect();
+        let mut seen = HashSet::new();
+        let dates: Vec<&str> = first.iter().skip(1)
+            .map(|p| p.date.as_str())
+            .filter(|d| seen.insert(*d) && maps.iter().all(|m| m.contains_key(d)))
+            .collect();
+        let series = maps.iter().map(|m| dates.iter().map(|d| m[d]).collect()).collect();
+        return Ok(("date", series));
+    }
+    let t = plain.iter().map(|r| r.len()).min().unwrap_or(0);
+    Ok(("tail", plain.into_iter().map(|r| r[r.len() - t..].to_vec()).collect()))
+}
+
+fn quad(w: &[f64], cov: &[Vec<f64>]) -> f64 {
+    w.iter().enumerate().map(|(i, wi)| wi * w.iter().zip(&cov[i]).map(|(wj, c)| wj * c).sum::<f64>()).sum()
+}
+
+/// Long-only full Kelly: solve Σf = μ, drop strategies with negative
+/// stakes and re-solve on the rest until none remain negative.
+fn kelly_weights(excess: &[f64], cov: &[Vec<f64>]) -> Vec<f64> {
+    let n = excess.len();
+    let mut active: Vec<usize> = (0..n).filter(|&i| excess[i] > 0.0 && cov[i][i] > 0.0).collect();
+    let mut out = vec![0.0; n];
+    while !active.is_empty() {
+        let a: Vec<Vec<f64>> = active.iter().map(|&i| active.iter().map(|&j| cov[i][j]).collect()).collect();
+        let b: Vec<f64> = active.iter().map(|&i| excess[i]).collect();
+        // A singular covariance (duplicate strategies) falls back to μ/σ².
+        let f = solve(a, b).unwrap_or_else(|| active.iter().map(|&i| excess[i] / cov[i][i]).collect());
+        if f.iter().all(|x| *x >= 0.0) {
+            for (k, &i) in active.iter().enumerate() {
+                out[i] = f[k];
+            }
+            break;
+        }
+        active = active.iter().zip(&f).filter(|(_, x)| **x > 0.0).map(|(i, _)| *i).collect();
+    }
+    out
+}
+
+/// Gaussian elimination with partial pivoting.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+        if a[pivot][col].abs() < 1e-14 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col].clone();
+            for (x, p) in a[row].iter_mut().zip(&pivot_row).skip(col) {
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let tail: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    Some(x)
+}
+
+/// Equal risk contribution by multiplicative updates, summing to 1.
+fn risk_parity_weights(cov: &[Vec<f64>]) -> Vec<f64> {
+    let n = cov.len();
+    let usable: Vec<bool> = (0..n).map(|i| cov[i][i] > 0.0).collect();
+    let count = usable.iter().filter(|u| **u).count();
+    if count == 0 {
+        return vec![0.0; n];
+    }
+    let mut w: Vec<f64> = (0..n).map(|i| if usable[i] { 1.0 / cov[i][i].sqrt() } else { 0.0 }).collect();
+    for _ in 0..500 {
+        let var = quad(&w, cov);
+        if var <= 0.0 {
+            break;
+        }
+        let target = var / count as f64;
+        let mut max_dev = 0.0_f64;
+        let marginal: Vec<f64> = (0..n).map(|i| w.iter().zip(&cov[i]).map(|(wj, c)| wj * c).sum()).collect();
+        for i in 0..n {
+            let rc = w[i] * marginal[i];
+            if usable[i] && rc > 0.0 {
+                max_dev = max_dev.max((rc / target - 1.0).abs());
+                w[i] *= (target / rc).sqrt();
+            }
+        }
+        let total: f64 = w.iter().sum();
+        w.iter_mut().for_each(|x| *x /= total);
+        if max_dev < 1e-8 {
+            break;
+        }
+    }
+    let total: f64 = w.iter().sum();
+    w.iter().map(|x| x / total).collect()
+}
+
+/// (annual return %, volatility %, Sharpe, CAGR %, max drawdown %).
+fn metrics(returns: &[f64], rf: f64, ppy: f64) -> (f64, f64, f64, f64, f64) {
+    let t = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / t;
+    let sd = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (t - 1.0)).sqrt();
+    let (mut nav, mut peak, mut max_dd) = (1.0_f64, 1.0_f64, 0.0_f64);
+    for r in returns {
+        nav *= 1.0 + r;
+        peak = peak.max(nav);
+        max_dd = max_dd.max((peak - nav) / peak);
+    }
+    let cagr = if nav > 0.0 { nav.powf(ppy / t) - 1.0 } else { -1.0 };
+    let sharpe = if sd > 0.0 { (mean - rf) / sd * ppy.sqrt() } else { 0.0 };
+    (round2(mean * ppy * 100.0), round2(sd * ppy.sqrt() * 100.0), round2(sharpe), round2(cagr * 100.0), round2(max_dd * 100.0))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn summarize(w: &[f64], names: &[&str], series: &[Vec<f64>], cov: &[Vec<f64>], vols: &[f64], rf: f64, ppy: f64, capital: Option<f64>) -> Allocation {
+    let gross: f64 = w.iter().sum();
+    let combined: Vec<f64> = (0..series[0].len())
+        .map(|k| w.iter().zip(series).map(|(wi, r)| wi * r[k]).sum::<f64>() + (1.0 - gross) * rf)
+        .collect();
+    let var = quad(w, cov);
+    let weights = w.iter().zip(names).enumerate().map(|(i, (wi, name))| {
+        let marginal: f64 = w.iter().zip(&cov[i]).map(|(wj, c)| wj * c).sum();
+        WeightOut {
+            name: name.to_string(),
+            weight: round4(*wi),
+            capital: capital.map(|c| round2(c * wi)),
+            risk_contribution_pct: if var > 0.0 { round2(wi * marginal / var * 100.0) } else { 0.0 },
+        }
+    }).collect();
+    let m = metrics(&combined, rf, ppy);
+    let weighted_vol: f64 = w.iter().zip(vols).map(|(wi, v)| wi * v).sum();
+    Allocation {
+        weights,
+        gross_weight: round4(gross),
+        cash_weight: round4(1.0 - gross),
+        annual_return: m.0,
+        volatility: m.1,
+        sharpe: m.2,
+        cagr: m.3,
+        max_drawdown: m.4,
+        diversification_ratio: if var > 0.0 { round4(weighted_vol / var.sqrt()) } else { 0.0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Two uncorrelated streams: `calm` has a third of `wild`'s volatility.
+    fn streams() -> (Vec<f64>, Vec<f64>) {
+        let calm = (0..200).map(|i| 0.001 + if i % 2 == 0 { 0.004 } else { -0.004 }).collect();
+        let wild = (0..200).map(|i| 0.001 + if (i / 2) % 2 == 0 { 0.012 } else { -0.012 }).collect();
+        (calm, wild)
+    }
+
+    #[test]
+    fn test_risk_parity_and_vol_target() {
+        let (calm, wild) = streams();
+        let result = compute(json!({
+            "strategies": [{ "name": "calm", "returns": calm }, { "name": "wild", "returns": wild }],
+            "capital": 1_000_000.0,
+            "target_vol_pct": 5.0
+        })).unwrap();
+        assert_eq!(result["aligned_on"], "tail");
+        assert_eq!(result["correlation_matrix"][0][1], 0.0);
+        let rp = &result["allocations"]["risk_parity"];
+        // Uncorrelated: risk parity is inverse-vol, 3:1.
+        assert_eq!(rp["weights"][0]["weight"], 0.75);
+        assert_eq!(rp["weights"][0]["capital"], 750_000.0);
+        assert_eq!(rp["weights"][1]["risk_contribution_pct"], 50.0);
+        assert_eq!(result["weights"]["calm"], 0.75);
+        let vt = &result["allocations"]["vol_target"];
+        assert!((vt["volatility"].as_f64().unwrap() - 5.0).abs() < 0.1);
+        assert!(vt["cash_weight"].as_f64().unwrap() > 0.0);
+        assert!(rp["diversification_ratio"].as_f64().unwrap() > 1.0);
+    }
+
+    #[test]
+    fn test_kelly_and_dated_curves() {
+        let (calm, wild) = streams();
+        let curve = |r: &[f64]| {
+            let mut nav = 100.0;
+            let mut out = vec![json!({ "date": "d000", "nav": nav })];
+            for (i, x) in r.iter().enumerate() {
+                nav *= 1.0 + x;
+                out.push(json!({ "date": format!("d{:03}", i + 1), "nav": nav }));
+            }
+            out
+        };
+        let mut wild_curve = curve(&wild);
+        wild_curve.remove(50);
+        let result = compute(json!({
+            "strategies": [
+                { "name": "calm", "equity_curve": curve(&calm) },
+                { "name": "wild", "equity_curve": wild_curve },
+                { "name": "loser", "returns": vec![-0.001; 200] }
+            ],
+            "method": "kelly",
+            "risk_free_rate": 0.0,
+            "max_leverage": 3.0
+        })).unwrap();
+        assert_eq!(result["aligned_on"], "tail");
+        let k = &result["allocations"]["kelly"]["weights"];
+        // Half Kelly μ/σ² on uncorrelated streams: calm gets 9× wild; the loser nothing.
+        let (a, b) = (k[0]["weight"].as_f64().unwrap(), k[1]["weight"].as_f64().unwrap());
+        assert!(a > 0.0 && (a / b - 9.0).abs() < 0.5, "{} {}", a, b);
+        assert_eq!(k[2]["weight"], 0.0);
+        assert_eq!(result["allocations"]["kelly"]["gross_weight"], 3.0);
+
+        let dated = compute(json!({
+            "strategies": [{ "name": "calm", "equity_curve": curve(&calm) }, { "name": "wild", "equity_curve": wild_curve }]
+        })).unwrap();
+        assert_eq!(dated["aligned_on"], "date");
+        assert_eq!(dated["observations"], 199);
+        assert!(compute(json!({ "strategies": [{ "name": "x" }] })).is_err());
+    }
+}
@@ -0,0 +1,391 @@
+//! Multi-leg option payoff analysis.
+//!
+//! Legs are calls, puts or a `future`/`stock` leg (strike = entry price),
+//! each with a signed `quantity` (negative = written) times `lot_size`.
+//! Expiry is an `expiry` date, counted from `valuation_date` (today by
+//! default), or `expiry_days`. Curves are produced for today, each
+//! `days_forward` horizon and the front expiry; legs still alive at a
+//! horizon are repriced with Black-Scholes at their own IV, which is the
+//! given `iv`, else backed out of `premium`, else `default_iv`.
+//!
+//! The leg model and pricing helpers are shared with the probability and
+//! strategy-suggestion commands.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::greeks::solve_iv;
+use crate::utils::{bs_greeks, bs_price, parse_timestamp, round2, round4};
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct LegInput {
+    /// "call"/"ce", "put"/"pe", or "future"/"stock".
+    pub option_type: String,
+    pub strike: f64,
+    #[serde(default)]
+    pub premium: f64,
+    #[serde(alias = "qty")]
+    pub quantity: f64,
+    #[serde(default)]
+    pub lot_size: Option<f64>,
+    #[serde(default)]
+    pub expiry: Option<String>,
+    #[serde(default)]
+    pub expiry_days: Option<f64>,
+    #[serde(default)]
+    pub iv: Option<f64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum LegKind {
+    Call,
+    Put,
+    Underlying,
+}
+
+/// A leg with units, expiry and volatility resolved.
+#[derive(Clone, Debug)]
+pub(crate) struct Leg {
+    pub kind: LegKind,
+    pub strike: f64,
+    pub premium: f64,
+    /// Signed units: quantity × lot size.
+    pub units: f64,
+    pub expiry_days: f64,
+    pub iv: f64,
+}
+
+impl Leg {
+    /// Value per unit at `price` with `days` elapsed from valuation.
+    pub fn value(&self, price: f64, days: f64, rate: f64) -> f64 {
+        let t = ((self.expiry_days - days) / 365.0).max(0.0);
+        match self.kind {
+            LegKind::Underlying => price,
+            LegKind::Call => bs_price(price, self.strike, rate, t, self.iv, true),
+            LegKind::Put => bs_price(price, self.strike, rate, t, self.iv, false),
+        }
+    }
+
+    /// Entry cost per unit: premium for options, entry price for the underlying.
+    pub fn cost(&self) -> f64 {
+        match self.kind {
+            LegKind::Underlying => self.strike,
+            _ => self.premium,
+        }
+    }
+
+    pub fn pnl(&self, price: f64, days: f64, rate: f64) -> f64 {
+        (self.value(price, days, rate) - self.cost()) * self.units
+    }
+}
+
+/// Position P&L at `price` after `days`.
+pub(crate) fn position_pnl(legs: &[Leg], price: f64, days: f64, rate: f64) -> f64 {
+    legs.iter().map(|l| l.pnl(price, days, rate)).sum()
+}
+
+/// Days to the earliest option expiry (0 for an underlying-only position).
+pub(crate) fn front_expiry(legs: &[Leg]) -> f64 {
+    legs.iter()
+        .filter(|l| l.kind != LegKind::Underlying)
+        .map(|l| l.expiry_days)
+        .reduce(f64::min)
+        .unwrap_or(0.0)
+}
+
+/// Resolve raw legs against `spot` and the valuation date.
+pub(crate) fn resolve_legs(
+    raw: &[LegInput],
+    spot: f64,
+    valuation: NaiveDate,
+    rate: f64,
+    default_iv: f64,
+) -> Result<Vec<Leg>, String> {
+    if raw.is_empty() {
+        return Err("At least one leg required".to_string());
+    }
+    raw.iter().enumerate().map(|(i, l)| {
+        let kind = match l.option_type.to_lowercase().as_str() {
+            "call" | "ce" | "c" => LegKind::Call,
+            "put" | "pe" | "p" => LegKind::Put,
+            "future" | "fut" | "stock" | "underlying" => LegKind::Underlying,
+            other => return Err(format!("legs[{}]: unknown option_type '{}'", i, other)),
+        };
+        if !(l.strike.is_finite() && l.strike > 0.0) {
+            return Err(format!("legs[{}]: strike must be positive", i));
+        }
+        let expiry_days = match (&l.expiry, l.expiry_days) {
+            (_, Some(d)) => d.max(0.0),
+            (Some(date), None) => {
+                let expiry = parse_timestamp(date).ok_or_else(|| format!("legs[{}]: invalid expiry '{}'", i, date))?;
+                ((expiry.date() - valuation).num_days() as f64).max(0.0)
+            }
+            (None, None) if kind == LegKind::Underlying => 0.0,
+            (None, None) => return Err(format!("legs[{}]: expiry or expiry_days required", i)),
+        };
+        let t = expiry_days / 365.0;
+        let iv = match l.iv {
+            Some(iv) => iv,
+            None if kind != LegKind::Underlying && l.premium > 0.0 && t > 0.0 => {
+                let solved = solve_iv(spot, l.strike, rate, t, l.premium, kind == LegKind::Call);
+                if solved > 0.0 { solved } else { default_iv }
+            }
+            None => default_iv,
+        };
+        Ok(Leg {
+            kind,
+            strike: l.strike,
+            premium: l.premium,
+            units: l.quantity * l.lot_size.unwrap_or(1.0),
+            expiry_days,
+            iv,
+        })
+    }).collect()
+}
+
+pub(crate) fn valuation_date(date: Option<&str>) -> Result<NaiveDate, String> {
+    match date {
+        Some(d) => parse_timestamp(d).map(|t| t.date()).ok_or_else(|| format!("Invalid valuation_date '{}'", d)),
+        None => Ok(chrono::Local::now().date_naive()),
+    }
+}
+
+#[derive(Deserialize)]
+struct PayoffConfig {
+    spot: f64,
+    legs: Vec<LegInput>,
+    #[serde(default)]
+    valuation_date: Option<String>,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    #[serde(default = "default_iv")]
+    default_iv: f64,
+    /// Extra T+N curves, in days from valuation.
+    #[serde(default)]
+    days_forward: Vec<f64>,
+    #[serde(default)]
+    price_range: Option<(f64, f64)>,
+    #[serde(default = "default_points")]
+    num_points: usize,
+}
+
+fn default_rate() -> f64 { 0.065 }
+fn default_iv() -> f64 { 0.2 }
+fn default_points() -> usize { 100 }
+
+#[derive(Serialize)]
+struct Curve {
+    label: String,
+    days_forward: f64,
+    points: Vec<CurvePoint>,
+}
+
+#[derive(Serialize)]
+struct CurvePoint {
+    price: f64,
+    pnl: f64,
+}
+
+#[derive(Serialize)]
+struct NetGreeks {
+    delta: f64,
+    gamma: f64,
+    /// Per calendar day.
+    theta: f64,
+    /// Per 1 vol point.
+    vega: f64,
+    rho: f64,
+}
+
+#[derive(Serialize)]
+struct LegSummary {
+    option_type: &'static str,
+    strike: f64,
+    units: f64,
+    expiry_days: f64,
+    iv: f64,
+    value_now: f64,
+}
+
+#[derive(Serialize)]
+struct PayoffResult {
+    spot: f64,
+    /// Premium paid (positive, debit) or received (negative, credit).
+    net_premium: f64,
+    front_expiry_days: f64,
+    curves: Vec<Curve>,
+    breakevens: Vec<f64>,
+    /// At front expiry; null when unlimited.
+    max_profit: Option<f64>,
+    max_loss: Option<f64>,
+    unlimited_profit: bool,
+    unlimited_loss: bool,
+    risk_reward: Option<f64>,
+    pnl_now: f64,
+    greeks: NetGreeks,
+    legs: Vec<LegSummary>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: PayoffConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid strategy_payoff config: {}", e))?;
+    if !(config.spot.is_finite() && config.spot > 0.0) {
+        return Err("spot must be positive".to_string());
+    }
+    let rate = config.risk_free_rate;
+    let valuation = valuation_date(config.valuation_date.as_deref())?;
+    let legs = resolve_legs(&config.legs, config.spot, valuation, rate, config.default_iv)?;
+    let front = front_expiry(&legs);
+
+    let (low, high) = config.price_range.unwrap_or((config.spot * 0.8, config.spot * 1.2));
+    if !(low >= 0.0 && high > low) {
+        return Err("price_range must be (low, high) with 0 <= low < high".to_string());
+    }
+    let n = config.num_points.clamp(2, 2000);
+    let mut grid: Vec<f64> = (0..=n).map(|i| low + (high - low) * i as f64 / n as f64).collect();
+    // Strikes are the payoff kinks; including them makes expiry breakevens exact.
+    grid.extend(legs.iter().map(|l| l.strike).filter(|k| *k > low && *k < high));
+    grid.sort_by(|a, b| a.total_cmp(b));
+    grid.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+
+    let mut horizons: Vec<(String, f64)> = vec![("T+0".to_string(), 0.0)];
+    for &d in config.days_forward.iter().filter(|d| **d > 0.0 && **d < front) {
+        horizons.push((format!("T+{}", d), d));
+    }
+    horizons.push(("expiry".to_string(), front));
+    let curves: Vec<Curve> = horizons.into_iter().map(|(label, days)| Curve {
+        points: grid.iter().map(|&p| CurvePoint { price: round2(p), pnl: round2(position_pnl(&legs, p, days, rate)) }).collect(),
+        label,
+        days_forward: days,
+    }).collect();
+
+    let at_expiry = |p: f64| position_pnl(&legs, p, front, rate);
+    let mut breakevens = Vec::new();
+    for w in grid.windows(2) {
+        let (a, b) = (at_expiry(w[0]), at_expiry(w[1]));
+        if a == 0.0 {
+            breakevens.push(round2(w[0]));
+        } else if a * b < 0.0 {
+            breakevens.push(round2(w[0] + (w[1] - w[0]) * a.abs() / (a.abs() + b.abs())));
+        }
+    }
+    if grid.last().is_some_and(|&p| at_expiry(p) == 0.0) {
+        breakevens.push(round2(grid[grid.len() - 1]));
+    }
+    breakevens.dedup();
+
+    // Extremes over every price >= 0: the grid, all strikes and zero, with
+    // the slope past the highest strike deciding unlimited upside/downside.
+    let mut probe = grid.clone();
+    probe.push(0.0);
+    probe.extend(legs.iter().map(|l| l.strike));
+    let top = probe.iter().cloned().fold(0.0, f64::max);
+    let far = top * 2.0 + 1.0;
+    let slope = (at_expiry(far * 2.0) - at_expiry(far)) / far;
+    let values: Vec<f64> = probe.iter().map(|&p| at_expiry(p)).collect();
+    let unlimited_profit = slope > 1e-6;
+    let unlimited_loss = slope < -1e-6;
+    let max_profit = (!unlimited_profit).then(|| values.iter().cloned().fold(f64::NEG_INFINITY, f64::max));
+    let max_loss = (!unlimited_loss).then(|| values.iter().cloned().fold(f64::INFINITY, f64::min));
+    let risk_reward = match (max_profit, max_loss) {
+        (Some(p), Some(l)) if l < 0.0 => Some(round2(p / l.abs())),
+        _ => None,
+    };
+
+    let mut greeks = NetGreeks { delta: 0.0, gamma: 0.0, theta: 0.0, vega: 0.0, rho: 0.0 };
+    for leg in &legs {
+        let (d, g, th, v, r) = match leg.kind {
+            LegKind::Underlying => (1.0, 0.0, 0.0, 0.0, 0.0),
+            kind => bs_greeks(config.spot, leg.strike, leg.expiry_days / 365.0, rate, leg.iv, kind == LegKind::Call),
+        };
+        greeks.delta += d * leg.units;
+        greeks.gamma += g * leg.units;
+        greeks.theta += th * leg.units;
+        greeks.vega += v * leg.units;
+        greeks.rho += r * leg.units;
+    }
+    for g in [&mut greeks.delta, &mut greeks.gamma, &mut greeks.theta, &mut greeks.vega, &mut greeks.rho] {
+        *g = round4(*g);
+    }
+
+    let result = PayoffResult {
+        spot: config.spot,
+        net_premium: round2(legs.iter().filter(|l| l.kind != LegKind::Underlying).map(|l| l.premium * l.units).sum()),
+        front_expiry_days: front,
+        curves,
+        breakevens,
+        max_profit: max_profit.map(round2),
+        max_loss: max_loss.map(round2),
+        unlimited_profit,
+        unlimited_loss,
+        risk_reward,
+        pnl_now: round2(position_pnl(&legs, config.spot, 0.0, rate)),
+        greeks,
+        legs: legs.iter().map(|l| LegSummary {
+            option_type: match l.kind { LegKind::Call => "call", LegKind::Put => "put", LegKind::Underlying => "underlying" },
+            strike: l.strike,
+            units: l.units,
+            expiry_days: l.expiry_days,
+            iv: round4(l.iv),
+            value_now: round2(l.value(config.spot, 0.0, rate)),
+        }).collect(),
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bull_call_spread_expiry_metrics() {
+        let result = compute(json!({
+            "spot": 100.0, "valuation_date": "2024-06-01",
+            "legs": [
+                { "option_type": "call", "strike": 100.0, "premium": 4.0, "quantity": 1, "lot_size": 50, "expiry": "2024-06-27" },
+                { "option_type": "CE", "strike": 110.0, "premium": 1.0, "qty": -1, "lot_size": 50, "expiry": "2024-06-27" },
+            ],
+            "days_forward": [10]
+        })).unwrap();
+        assert_eq!(result["front_expiry_days"], 26.0);
+        assert_eq!(result["net_premium"], 150.0);
+        assert_eq!(result["breakevens"], json!([103.0]));
+        assert_eq!(result["max_profit"], 350.0);
+        assert_eq!(result["max_loss"], -150.0);
+        assert_eq!(result["unlimited_profit"], false);
+        let labels: Vec<&str> = result["curves"].as_array().unwrap().iter().map(|c| c["label"].as_str().unwrap()).collect();
+        assert_eq!(labels, ["T+0", "T+10", "expiry"]);
+        // IV was backed out of the premium, so today's value matches it.
+        assert!((result["legs"][0]["value_now"].as_f64().unwrap() - 4.0).abs() < 0.01);
+        let delta = result["greeks"]["delta"].as_f64().unwrap();
+        assert!(delta > 0.0 && delta < 50.0);
+    }
+
+    #[test]
+    fn test_unlimited_and_calendar() {
+        let naked = compute(json!({
+            "spot": 100.0,
+            "legs": [{ "option_type": "call", "strike": 105.0, "premium": 2.0, "quantity": -1, "expiry_days": 7 }]
+        })).unwrap();
+        assert_eq!(naked["unlimited_loss"], true);
+        assert!(naked["max_loss"].is_null());
+        assert_eq!(naked["max_profit"], 2.0);
+
+        // Short front / long back call: worth most near the strike at front expiry.
+        let calendar = compute(json!({
+            "spot": 100.0,
+            "legs": [
+                { "option_type": "call", "strike": 100.0, "quantity": -1, "expiry_days": 7, "iv": 0.2, "premium": 1.2 },
+                { "option_type": "call", "strike": 100.0, "quantity": 1, "expiry_days": 35, "iv": 0.2, "premium": 2.9 },
+            ]
+        })).unwrap();
+        assert_eq!(calendar["front_expiry_days"], 7.0);
+        assert_eq!(calendar["unlimited_loss"], false);
+        let expiry = calendar["curves"].as_array().unwrap().last().unwrap();
+        let best = expiry["points"].as_array().unwrap().iter()
+            .max_by(|a, b| a["pnl"].as_f64().unwrap().total_cmp(&b["pnl"].as_f64().unwrap())).unwrap();
+        assert!((best["price"].as_f64().unwrap() - 100.0).abs() < 2.0);
+        assert!(compute(json!({ "spot": 100.0, "legs": [] })).is_err());
+    }
+}
@@ -0,0 +1,491 @@
+//! Option structures for a market outlook, with concrete strikes from the chain.
+//!
+//! `outlook` is either free text ("mildly bullish, IV high") or
+//! `{direction, volatility}`. Directions: bullish, mildly_bullish, neutral,
+//! mildly_bearish, bearish, volatile; volatility: high, low or normal. Each
+//! suggested structure picks strikes by delta from `strikes` (the
+//! `chain_analysis` chain format), fills sells at the bid and buys at the
+//! ask, and is scored with the shared payoff helpers: net credit, max
+//! profit/loss, breakevens and lognormal POP at the chain's ATM IV.
+//! Calendars need the next expiry as `far_strikes` + `far_expiry_days`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::chain_analysis::{mid_price, ChainStrike};
+use crate::greeks::solve_iv;
+use crate::pop::{expiry_extremes, lognormal_pop};
+use crate::strategy_payoff::{position_pnl, valuation_date, Leg, LegKind};
+use crate::utils::{bs_greeks, parse_timestamp, round2, round4};
+
+#[derive(Deserialize)]
+struct SuggestConfig {
+    spot: f64,
+    outlook: Value,
+    strikes: Vec<ChainStrike>,
+    #[serde(default)]
+    expiry: Option<String>,
+    #[serde(default)]
+    expiry_days: Option<f64>,
+    #[serde(default)]
+    valuation_date: Option<String>,
+    #[serde(default)]
+    far_strikes: Vec<ChainStrike>,
+    #[serde(default)]
+    far_expiry_days: Option<f64>,
+    #[serde(default = "default_rate")]
+    risk_free_rate: f64,
+    #[serde(default = "default_lot")]
+    lot_size: f64,
+    /// Spread width in strike steps.
+    #[serde(default = "default_width")]
+    wing_width: usize,
+}
+
+fn default_rate() -> f64 { 0.065 }
+fn default_lot() -> f64 { 1.0 }
+fn default_width() -> usize { 2 }
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Direction {
+    Bullish,
+    MildlyBullish,
+    Neutral,
+    MildlyBearish,
+    Bearish,
+    Volatile,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum VolView {
+    High,
+    Normal,
+    Low,
+}
+
+impl Direction {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.to_lowercase().replace(['_', '-'], " ");
+        let mild = s.contains("mild") || s.contains("slight");
+        if s.contains("volatile") || s.contains("breakout") || s.contains("big move") {
+            Some(Direction::Volatile)
+        } else if s.contains("bull") {
+            Some(if mild { Direction::MildlyBullish } else { Direction::Bullish })
+        } else if s.contains("bear") {
+            Some(if mild { Direction::MildlyBearish } else { Direction::Bearish })
+        } else if s.contains("neutral") || s.contains("range") || s.contains("sideways") {
+            Some(Direction::Neutral)
+        } else {
+            None
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Direction::Bullish => "bullish",
+            Direction::MildlyBullish => "mildly_bullish",
+            Direction::Neutral => "neutral",
+            Direction::MildlyBearish => "mildly_bearish",
+            Direction::Bearish => "bearish",
+            Direction::Volatile => "volatile",
+        }
+    }
+}
+
+impl VolView {
+    fn parse(s: &str) -> Self {
+        let s = s.to_lowercase();
+        if s.contains("high") || s.contains("elevated") || s.contains("rich") {
+            VolView::High
+        } else if s.contains("low") || s.contains("cheap") {
+            VolView::Low
+        } else {
+            VolView::Normal
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            VolView::High => "high",
+            VolView::Normal => "normal",
+            VolView::Low => "low",
+        }
+    }
+}
+
+fn parse_outlook(v: &Value) -> Result<(Direction, VolView), String> {
+    let (dir, vol) = match v {
+        Value::String(s) => (s.as_str(), s.as_str()),
+        Value::Object(o) => (
+            o.get("direction").and_then(|d| d.as_str()).unwrap_or(""),
+            o.get("volatility").or_else(|| o.get("iv")).and_then(|d| d.as_str()).unwrap_or(""),
+        ),
+        _ => ("", ""),
+    };
+    let direction = Direction::parse(dir)
+        .ok_or_else(|| format!("Unrecognised outlook direction in {} (bullish, mildly_bullish, neutral, mildly_bearish, bearish, volatile)", v))?;
+    Ok((direction, VolView::parse(vol)))
+}
+
+/// One side of one strike with its IV and delta resolved.
+#[derive(Clone, Copy)]
+pub(crate) struct ChainOption {
+    pub strike: f64,
+    pub is_call: bool,
+    pub bid: f64,
+    pub ask: f64,
+    pub mid: f64,
+    pub iv: f64,
+    pub delta: f64,
+}
+
+pub(crate) struct Chain {
+    spot: f64,
+    pub days: f64,
+    pub calls: Vec<ChainOption>,
+    pub puts: Vec<ChainOption>,
+}
+
+impl Chain {
+    pub fn build(strikes: &[ChainStrike], spot: f64, days: f64, rate: f64) -> Self {
+        let t = days / 365.0;
+        let side = |is_call: bool| -> Vec<ChainOption> {
+            let mut v: Vec<ChainOption> = strikes.iter().filter_map(|s| {
+                let (bid, ask, ltp, iv) = s.quote(is_call);
+                let mid = mid_price(bid, ask, ltp);
+                if s.strike <= 0.0 || mid <= 0.0 {
+                    return None;
+                }
+                let iv = iv.filter(|v| *v > 0.0).unwrap_or_else(|| solve_iv(spot, s.strike, rate, t, mid, is_call));
+                if iv <= 0.0 {
+                    return None;
+                }
+                let delta = bs_greeks(spot, s.strike, t, rate, iv, is_call).0;
+                Some(ChainOption { strike: s.strike, is_call, bid, ask, mid, iv, delta })
+            }).collect();
+            v.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+            v
+        };
+        Chain { spot, days, calls: side(true), puts: side(false) }
+    }
+
+    pub fn side(&self, is_call: bool) -> &[ChainOption] {
+        if is_call { &self.calls } else { &self.puts }
+    }
+
+    /// OTM (or ATM) option with |delta| closest to `target`.
+    fn by_delta(&self, is_call: bool, target: f64) -> Option<ChainOption> {
+        self.side(is_call).iter()
+            .filter(|o| if is_call { o.strike >= self.spot } else { o.strike <= self.spot })
+            .min_by(|a, b| (a.delta.abs() - target).abs().total_cmp(&(b.delta.abs() - target).abs()))
+            .copied()
+    }
+
+    fn atm(&self, is_call: bool) -> Option<ChainOption> {
+        self.side(is_call).iter()
+            .min_by(|a, b| (a.strike - self.spot).abs().total_cmp(&(b.strike - self.spot).abs()))
+            .copied()
+    }
+
+    /// The option `steps` strikes away from `from` (positive = higher strike).
+    fn offset(&self, from: &ChainOption, steps: isize) -> Option<ChainOption> {
+        let side = self.side(from.is_call);
+        let i = side.iter().position(|o| o.strike == from.strike)? as isize + steps;
+        usize::try_from(i).ok().and_then(|i| side.get(i)).copied()
+    }
+
+    fn atm_iv(&self) -> Option<f64> {
+        match (self.atm(true), self.atm(false)) {
+            (Some(c), Some(p)) => Some((c.iv + p.iv) / 2.0),
+            (Some(o), None) | (None, Some(o)) => Some(o.iv),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SuggestedLeg {
+    action: &'static str,
+    option_type: &'static str,
+    strike: f64,
+    price: f64,
+    quantity: f64,
+    expiry_days: f64,
+    iv: f64,
+    delta: f64,
+}
+
+#[derive(Serialize)]
+struct Suggestion {
+    strategy: &'static str,
+    rationale: &'static str,
+    legs: Vec<SuggestedLeg>,
+    /// Positive = credit received, negative = debit paid.
+    net_credit: f64,
+    /// At front expiry; null when unlimited.
+    max_profit: Option<f64>,
+    max_loss: Option<f64>,
+    breakevens: Vec<f64>,
+    risk_reward: Option<f64>,
+    pop: f64,
+    expected_value: f64,
+}
+
+#[derive(Serialize)]
+struct SuggestResult {
+    direction: &'static str,
+    volatility: &'static str,
+    spot: f64,
+    expiry_days: f64,
+    atm_iv: Option<f64>,
+    suggestions: Vec<Suggestion>,
+    /// Structures that fit the outlook but could not be built from this chain.
+    skipped: Vec<&'static str>,
+}
+
+/// (option, sell?, expiry days) for each leg of a structure.
+type Pick = (ChainOption, bool, f64);
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: SuggestConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid suggest_strategies config: {}", e))?;
+    if !(config.spot.is_finite() && config.spot > 0.0) {
+        return Err("spot must be positive".to_string());
+    }
+    let (direction, vol) = parse_outlook(&config.outlook)?;
+    let days = match (config.expiry_days, &config.expiry) {
+        (Some(d), _) => d,
+        (None, Some(e)) => {
+            let valuation = valuation_date(config.valuation_date.as_deref())?;
+            let expiry = parse_timestamp(e).ok_or_else(|| format!("Invalid expiry '{}'", e))?;
+            (expiry.date() - valuation).num_days() as f64
+        }
+        (None, None) => return Err("suggest_strategies requires expiry or expiry_days".to_string()),
+    };
+    if days <= 0.0 {
+        return Err("expiry must be after the valuation date".to_string());
+    }
+    let rate = config.risk_free_rate;
+    let chain = Chain::build(&config.strikes, config.spot, days, rate);
+    if chain.calls.is_empty() && chain.puts.is_empty() {
+        return Err("suggest_strategies requires a priced chain in strikes".to_string());
+    }
+    let far = config.far_expiry_days
+        .filter(|d| *d > days && !config.far_strikes.is_empty())
+        .map(|d| Chain::build(&config.far_strikes, config.spot, d, rate));
+    let atm_iv = chain.atm_iv();
+    let w = config.wing_width.max(1) as isize;
+
+    let candidates: &[&'static str] = match (direction, vol) {
+        (Direction::Bullish, VolView::High) => &["bull_put_spread", "cash_secured_put"],
+        (Direction::Bullish, VolView::Normal) => &["bull_call_spread", "bull_put_spread"],
+        (Direction::Bullish, VolView::Low) => &["long_call", "bull_call_spread"],
+        (Direction::MildlyBullish, VolView::High) => &["bull_put_spread"],
+        (Direction::MildlyBullish, _) => &["bull_call_spread", "bull_put_spread"],
+        (Direction::Neutral, VolView::High) => &["iron_condor", "short_strangle"],
+        (Direction::Neutral, VolView::Normal) => &["iron_condor", "calendar_spread"],
+        (Direction::Neutral, VolView::Low) => &["calendar_spread"],
+        (Direction::MildlyBearish, VolView::High) => &["bear_call_spread"],
+        (Direction::MildlyBearish, _) => &["bear_put_spread", "bear_call_spread"],
+        (Direction::Bearish, VolView::High) => &["bear_call_spread"],
+        (Direction::Bearish, VolView::Normal) => &["bear_put_spread", "bear_call_spread"],
+        (Direction::Bearish, VolView::Low) => &["long_put", "bear_put_spread"],
+        (Direction::Volatile, VolView::Low) => &["long_straddle", "long_strangle"],
+        (Direction::Volatile, _) => &["long_strangle"],
+    };
+    // Short strikes sit closer to the money the stronger the view.
+    let short_delta = match direction {
+        Direction::Bullish | Direction::Bearish => 0.30,
+        _ => 0.25,
+    };
+
+    let build = |name: &str| -> Option<(Vec<Pick>, &'static str)> {
+        let d = days;
+        Some(match name {
+            "bull_put_spread" => {
+                let short = chain.by_delta(false, short_delta)?;
+                let long = chain.offset(&short, -w)?;
+                (vec![(short, true, d), (long, false, d)], "Collect premium below spot with capped risk; rich IV favours selling.")
+            }
+            "bear_call_spread" => {
+                let short = chain.by_delta(true, short_delta)?;
+                let long = chain.offset(&short, w)?;
+                (vec![(short, true, d), (long, false, d)], "Collect premium above spot with capped risk; rich IV favours selling.")
+            }
+            "bull_call_spread" => {
+                let long = chain.atm(true)?;
+                let short = chain.offset(&long, w)?;
+                (vec![(long, false, d), (short, true, d)], "Defined-risk upside; the short call cuts the debit.")
+            }
+            "bear_put_spread" => {
+                let long = chain.atm(false)?;
+                let short = chain.offset(&long, -w)?;
+                (vec![(long, false, d), (short, true, d)], "Defined-risk downside; the short put cuts the debit.")
+            }
+            "cash_secured_put" => {
+                (vec![(chain.by_delta(false, short_delta)?, true, d)], "Get paid to wait for a lower entry; assignment buys the stock at the strike less premium.")
+            }
+            "long_call" => (vec![(chain.by_delta(true, 0.5)?, false, d)], "Cheap IV makes outright upside inexpensive."),
+            "long_put" => (vec![(chain.by_delta(false, 0.5)?, false, d)], "Cheap IV makes outright downside protection inexpensive."),
+            "iron_condor" => {
+                let sp = chain.by_delta(false, 0.16)?;
+                let sc = chain.by_delta(true, 0.16)?;
+                (vec![(chain.offset(&sp, -w)?, false, d), (sp, true, d), (sc, true, d), (chain.offset(&sc, w)?, false, d)],
+                 "Range-bound view with rich IV: sell both wings, capped risk.")
+            }
+            "short_strangle" => {
+                (vec![(chain.by_delta(false, 0.16)?, true, d), (chain.by_delta(true, 0.16)?, true, d)],
+                 "Range-bound view with rich IV; undefined risk, needs margin and active management.")
+            }
+            "calendar_spread" => {
+                let far = far.as_ref()?;
+                let near = chain.atm(true)?;
+                let back = far.calls.iter().find(|o| o.strike == near.strike).copied()?;
+                (vec![(near, true, d), (back, false, far.days)], "Near-term theta decays faster than the back month; benefits from a pinned spot and rising IV.")
+            }
+            "long_straddle" => {
+                (vec![(chain.atm(true)?, false, d), (chain.atm(false)?, false, d)], "Cheap IV and an expected large move in either direction.")
+            }
+            "long_strangle" => {
+                (vec![(chain.by_delta(false, 0.25)?, false, d), (chain.by_delta(true, 0.25)?, false, d)],
+                 "Expected large move; OTM wings cost less than a straddle.")
+            }
+            _ => return None,
+        })
+    };
+
+    let mut suggestions = Vec::new();
+    let mut skipped = Vec::new();
+    for &name in candidates {
+        match build(name) {
+            Some((picks, rationale)) => suggestions.push(score(name, rationale, &picks, &config, atm_iv.unwrap_or(0.2))),
+            None => skipped.push(name),
+        }
+    }
+
+    let result = SuggestResult {
+        direction: direction.label(),
+        volatility: vol.label(),
+        spot: config.spot,
+        expiry_days: days,
+        atm_iv: atm_iv.map(round4),
+        suggestions,
+        skipped,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn score(name: &'static str, rationale: &'static str, picks: &[Pick], config: &SuggestConfig, sigma: f64) -> Suggestion {
+    let rate = config.risk_free_rate;
+    let legs: Vec<Leg> = picks.iter().map(|(o, sell, days)| {
+        let price = if *sell {
+            if o.bid > 0.0 { o.bid } else { o.mid }
+        } else if o.ask > 0.0 { o.ask } else { o.mid };
+        Leg {
+            kind: if o.is_call { LegKind::Call } else { LegKind::Put },
+            strike: o.strike,
+            premium: price,
+            units: if *sell { -config.lot_size } else { config.lot_size },
+            expiry_days: *days,
+            iv: o.iv,
+        }
+    }).collect();
+    let horizon = legs.iter().map(|l| l.expiry_days).fold(f64::INFINITY, f64::min);
+    let (max_profit, max_loss) = expiry_extremes(&legs, config.spot, horizon, rate);
+    let (pop, _, ev) = lognormal_pop(&legs, config.spot, sigma, horizon, rate);
+
+    let mut grid: Vec<f64> = (0..=400).map(|i| config.spot * (0.5 + i as f64 / 400.0)).collect();
+    grid.extend(legs.iter().map(|l| l.strike));
+    grid.sort_by(|a, b| a.total_cmp(b));
+    let mut breakevens = Vec::new();
+    for w in grid.windows(2) {
+        let (a, b) = (position_pnl(&legs, w[0], horizon, rate), position_pnl(&legs, w[1], horizon, rate));
+        if a * b < 0.0 {
+            breakevens.push(round2(w[0] + (w[1] - w[0]) * a.abs() / (a.abs() + b.abs())));
+        }
+    }
+    breakevens.dedup();
+
+    Suggestion {
+        strategy: name,
+        rationale,
+        legs: picks.iter().zip(&legs).map(|((o, sell, days), l)| SuggestedLeg {
+            action: if *sell { "sell" } else { "buy" },
+            option_type: if o.is_call { "call" } else { "put" },
+            strike: o.strike,
+            price: round2(l.premium),
+            quantity: config.lot_size,
+            expiry_days: *days,
+            iv: round4(o.iv),
+            delta: round4(o.delta),
+        }).collect(),
+        net_credit: round2(-legs.iter().map(|l| l.premium * l.units).sum::<f64>()),
+        max_profit: max_profit.map(round2),
+        max_loss: max_loss.map(round2),
+        breakevens,
+        risk_reward: match (max_profit, max_loss) {
+            (Some(p), Some(l)) if l < 0.0 => Some(round2(p / l.abs())),
+            _ => None,
+        },
+        pop: round4(pop),
+        expected_value: round2(ev),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::bs_price;
+    use serde_json::json;
+
+    fn chain(days: f64) -> Vec<Value> {
+        let t = days / 365.0;
+        (0..21).map(|i| {
+            let k = 80.0 + i as f64 * 2.0;
+            let c = bs_price(100.0, k, 0.065, t, 0.25, true);
+            let p = bs_price(100.0, k, 0.065, t, 0.25, false);
+            json!({ "strike": k, "call_bid": c * 0.98, "call_ask": c * 1.02, "put_bid": p * 0.98, "put_ask": p * 1.02 })
+        }).collect()
+    }
+
+    #[test]
+    fn test_mildly_bullish_high_iv_suggests_bull_put_spread() {
+        let result = compute(json!({
+            "spot": 100.0, "expiry_days": 30, "outlook": "Mildly bullish, IV high", "strikes": chain(30.0)
+        })).unwrap();
+        assert_eq!(result["direction"], "mildly_bullish");
+        assert_eq!(result["volatility"], "high");
+        let s = &result["suggestions"][0];
+        assert_eq!(s["strategy"], "bull_put_spread");
+        let legs = s["legs"].as_array().unwrap();
+        assert_eq!(legs[0]["action"], "sell");
+        let (short_k, long_k) = (legs[0]["strike"].as_f64().unwrap(), legs[1]["strike"].as_f64().unwrap());
+        assert!(short_k < 100.0 && long_k == short_k - 4.0);
+        let credit = s["net_credit"].as_f64().unwrap();
+        assert!(credit > 0.0);
+        assert!((s["max_profit"].as_f64().unwrap() - credit).abs() < 0.01);
+        assert!((s["max_loss"].as_f64().unwrap() + (4.0 - credit)).abs() < 0.01);
+        assert!(s["pop"].as_f64().unwrap() > 0.5);
+        assert_eq!(s["breakevens"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_neutral_structures_and_calendar() {
+        let result = compute(json!({
+            "spot": 100.0, "expiry_days": 30, "strikes": chain(30.0),
+            "outlook": { "direction": "neutral", "volatility": "normal" },
+            "far_strikes": chain(58.0), "far_expiry_days": 58
+        })).unwrap();
+        let names: Vec<&str> = result["suggestions"].as_array().unwrap().iter().map(|s| s["strategy"].as_str().unwrap()).collect();
+        assert_eq!(names, ["iron_condor", "calendar_spread"]);
+        let condor = &result["suggestions"][0];
+        assert_eq!(condor["legs"].as_array().unwrap().len(), 4);
+        assert_eq!(condor["breakevens"].as_array().unwrap().len(), 2);
+        let calendar = &result["suggestions"][1];
+        assert!(calendar["net_credit"].as_f64().unwrap() < 0.0);
+        assert_eq!(calendar["legs"][1]["expiry_days"], 58.0);
+
+        let no_far = compute(json!({ "spot": 100.0, "expiry_days": 30, "strikes": chain(30.0), "outlook": "neutral, low iv" })).unwrap();
+        assert_eq!(no_far["skipped"], json!(["calendar_spread"]));
+        assert!(compute(json!({ "spot": 100.0, "expiry_days": 30, "strikes": chain(30.0), "outlook": "who knows" })).is_err());
+    }
+}
@@ -0,0 +1,905 @@
+//! Live paper trading on pushed candles, for serve/daemon processes.
+//!
+//! `subscribe` registers symbols (each with optional warm-up `candles`), the
+//! `strategies` to run and paper-trading settings under a `subscription_id`.
+//! Every `push_candle {"symbol", "candle" | "candles"}` then advances each
+//! subscription holding that symbol:
+//!
+//! - EMA 9/21, RSI 14, ATR 14 and session VWAP step forward in O(1) from
+//!   running state (same definitions as `indicators::standard`);
+//! - the scan vote and the strategies look at the last `max_bars` bars only;
+//! - the open paper position is checked against the bar's range for its stop
+//!   and target, then closed on an opposite signal or opened on a new one.
+//!
+//! Bars are assumed complete. A push with the same timestamp as the last bar
+//! revises it — indicators, marks and stops update, but signals fire once per
+//! bar — and an older one is rejected. The signal/entry/exit/alert events a
+//! push produces are returned and, unless `emit_events` is off, published on
+//! the progress channel as NDJSON lines tagged with the `subscription_id`.
+
+use std::collections::HashMap;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use crate::config::EngineConfig;
+use crate::kernels::rsi_value;
+use crate::progress;
+use crate::strategy::{self, Fill, Indicators, Side, Strategy};
+use crate::utils::{parse_timestamp, round2, round4, sanitize_candles, Candle};
+
+const EMA_FAST: usize = 9;
+const EMA_SLOW: usize = 21;
+const RSI_PERIOD: usize = 14;
+const ATR_PERIOD: usize = 14;
+/// `scan` skips symbols with fewer bars than this.
+const SCAN_MIN_BARS: usize = 15;
+
+static SUBSCRIPTIONS: Lazy<DashMap<String, Subscription>> = Lazy::new(DashMap::new);
+
+#[derive(Deserialize)]
+struct SubscribeInput {
+    subscription_id: String,
+    symbols: Vec<SymbolIn>,
+    /// Names accepted by `backtest` (see `list_strategies`).
+    #[serde(default)]
+    strategies: Vec<String>,
+    /// Run the `scan` vote on every new bar.
+    #[serde(default = "default_true")]
+    scan: bool,
+    /// Extra `scan` input (aggressiveness, vote_weights, strategy_params, ...).
+    #[serde(default)]
+    scan_options: Map<String, Value>,
+    /// Bars kept per symbol for the scan vote and strategies.
+    #[serde(default = "default_max_bars")]
+    max_bars: usize,
+    #[serde(default)]
+    paper: PaperConfig,
+    /// RSI (oversold, overbought) alert levels.
+    #[serde(default = "default_rsi_levels")]
+    rsi_levels: (f64, f64),
+    #[serde(default = "default_true")]
+    emit_events: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SymbolIn {
+    Name(String),
+    Data {
+        symbol: String,
+        #[serde(default)]
+        candles: Vec<Candle>,
+    },
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+struct PaperConfig {
+    enabled: bool,
+    capital: f64,
+    /// Fixed quantity per entry; otherwise sized so the stop risks `risk_pct`
+    /// of current equity.
+    qty: Option<i64>,
+    risk_pct: f64,
+    allow_short: bool,
+    /// Ignore signals below this confidence.
+    min_confidence: f64,
+    /// Stop distance in ATRs when the signal carries none.
+    stop_atr: f64,
+}
+
+impl Default for PaperConfig {
+    fn default() -> Self {
+        PaperConfig {
+            enabled: true,
+            capital: 1_000_000.0,
+            qty: None,
+            risk_pct: 1.0,
+            allow_short: false,
+            min_confidence: 0.0,
+            stop_atr: 2.0,
+        }
+    }
+}
+
+fn default_true() -> bool { true }
+fn default_max_bars() -> usize { 500 }
+fn default_rsi_levels() -> (f64, f64) { (30.0, 70.0) }
+
+#[derive(Deserialize)]
+struct PushInput {
+    symbol: String,
+    #[serde(default)]
+    candle: Option<Candle>,
+    #[serde(default)]
+    candles: Vec<Candle>,
+    /// Only advance this subscription; all holding the symbol otherwise.
+    #[serde(default)]
+    subscription_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeInput {
+    subscription_id: String,
+}
+
+struct Subscription {
+    strategies: Vec<String>,
+    scan: bool,
+    scan_options: Map<String, Value>,
+    max_bars: usize,
+    paper: PaperConfig,
+    rsi_levels: (f64, f64),
+    emit_events: bool,
+    symbols: HashMap<String, SymbolState>,
+    realized_pnl: f64,
+    trades: Vec<ClosedTrade>,
+    created_at: String,
+    /// Owned by a `replay`: only advanced by pushes naming it, and not listed.
+    replay: bool,
+}
+
+struct SymbolState {
+    window: Vec<Candle>,
+    /// Indicator state before and after the last bar, so a revision can
+    /// re-step from `before_last`.
+    before_last: Running,
+    current: Running,
+    strategies: Vec<Box<dyn Strategy>>,
+    position: Option<PaperPosition>,
+}
+
+#[derive(Serialize, Clone)]
+struct PaperPosition {
+    side: &'static str,
+    qty: i64,
+    entry_price: f64,
+    stop_loss: f64,
+    target: Option<f64>,
+    opened_at: String,
+    source: String,
+    mark: f64,
+    unrealized_pnl: f64,
+}
+
+#[derive(Serialize, Clone)]
+struct ClosedTrade {
+    symbol: String,
+    side: &'static str,
+    qty: i64,
+    entry_price: f64,
+    exit_price: f64,
+    pnl: f64,
+    opened_at: String,
+    closed_at: String,
+    source: String,
+    exit_reason: String,
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+struct Snapshot {
+    close: f64,
+    ema_9: Option<f64>,
+    ema_21: Option<f64>,
+    rsi_14: f64,
+    atr_14: f64,
+    vwap: f64,
+}
+
+/// A directional call from the scan vote or a strategy.
+struct StreamSignal {
+    source: String,
+    long: bool,
+    confidence: f64,
+    price: f64,
+    stop_loss: Option<f64>,
+    target: Option<f64>,
+    reason: String,
+    /// Closes a position but never opens one (e.g. a square-off).
+    exit_only: bool,
+}
+
+// ─── Running indicators ──────────────────────────────────────────────
+
+/// SMA-seeded EMA, as `kernels::ema`.
+#[derive(Clone)]
+struct Ema { period: usize, seen: usize, sum: f64, value: Option<f64> }
+
+impl Ema {
+    fn new(period: usize) -> Self { Ema { period, seen: 0, sum: 0.0, value: None } }
+
+    fn update(&mut self, x: f64) {
+        self.seen += 1;
+        match self.value {
+            Some(prev) => {
+                let mult = 2.0 / (self.period as f64 + 1.0);
+                self.value = Some((x - prev) * mult + prev);
+            }
+            None => {
+                self.sum += x;
+                if self.seen == self.period {
+                    self.value = Some(self.sum / self.period as f64);
+                }
+            }
+        }
+    }
+}
+
+/// Wilder smoothing seeded with the mean of the first `period` inputs, as in
+/// `kernels::rsi`/`kernels::atr`.
+#[derive(Clone)]
+struct Wilder { period: usize, seen: usize, sum: f64, value: Option<f64> }
+
+impl Wilder {
+    fn new(period: usize) -> Self { Wilder { period, seen: 0, sum: 0.0, value: None } }
+
+    fn update(&mut self, x: f64) {
+        self.seen += 1;
+        let p = self.period as f64;
+        match self.value {
+            Some(prev) => self.value = Some(prev * ((p - 1.0) / p) + x * (1.0 / p)),
+            None => {
+                self.sum += x;
+                if self.seen == self.period {
+                    self.value = Some(self.sum / p);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Running {
+    ema_fast: Ema,
+    ema_slow: Ema,
+    gain: Wilder,
+    loss: Wilder,
+    atr: Wilder,
+    prev_close: Option<f64>,
+    date: Option<chrono::NaiveDate>,
+    /// Two bars shared a date, so VWAP restarts each session.
+    intraday: bool,
+    cum_pv: f64,
+    cum_vol: f64,
+    last: Snapshot,
+}
+
+impl Running {
+    fn new() -> Self {
+        Running {
+            ema_fast: Ema::new(EMA_FAST),
+            ema_slow: Ema::new(EMA_SLOW),
+            gain: Wilder::new(RSI_PERIOD),
+            loss: Wilder::new(RSI_PERIOD),
+            atr: Wilder::new(ATR_PERIOD),
+            prev_close: None,
+            date: None,
+            intraday: false,
+            cum_pv: 0.0,
+            cum_vol: 0.0,
+            last: Snapshot { rsi_14: 50.0, ..Snapshot::default() },
+        }
+    }
+
+    fn step(&self, c: &Candle) -> Running {
+        let mut next = self.clone();
+        next.ema_fast.update(c.close);
+        next.ema_slow.update(c.close);
+        let tr = match self.prev_close {
+            Some(pc) => {
+                let d = c.close - pc;
+                next.gain.update(d.max(0.0));
+                next.loss.update((-d).max(0.0));
+                (c.high - c.low).max((c.high - pc).abs()).max((c.low - pc).abs())
+            }
+            None => c.high - c.low,
+        };
+        next.atr.update(tr);
+        next.prev_close = Some(c.close);
+
+        let date = parse_timestamp(&c.timestamp).map(|t| t.date());
+        if date.is_some() && date == self.date {
+            next.intraday = true;
+        }
+        if next.intraday && date.is_some() && date != self.date {
+            next.cum_pv = 0.0;
+            next.cum_vol = 0.0;
+        }
+        next.date = date;
+        next.cum_pv += (c.high + c.low + c.close) / 3.0 * c.volume;
+        next.cum_vol += c.volume;
+
+        let rsi = match (next.gain.value, next.loss.value) {
+            (Some(g), Some(l)) => rsi_value(g, l),
+            _ => 50.0,
+        };
+        next.last = Snapshot {
+            close: c.close,
+            ema_9: next.ema_fast.value,
+            ema_21: next.ema_slow.value,
+            rsi_14: rsi,
+            atr_14: next.atr.value.unwrap_or(0.0),
+            vwap: if next.cum_vol > 0.0 { next.cum_pv / next.cum_vol } else { c.close },
+        };
+        next
+    }
+}
+
+// ─── Commands ────────────────────────────────────────────────────────
+
+pub fn subscribe(data: Value, config: &EngineConfig) -> Result<Value, String> {
+    subscribe_as(data, config, false)
+}
+
+/// `subscribe` for a [`crate::replay`] session.
+pub(crate) fn subscribe_replay(data: Value, config: &EngineConfig) -> Result<Value, String> {
+    subscribe_as(data, config, true)
+}
+
+fn subscribe_as(data: Value, config: &EngineConfig, replay: bool) -> Result<Value, String> {
+    let input: SubscribeInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid subscribe input: {}", e))?;
+    if input.symbols.is_empty() {
+        return Err("subscribe requires at least one symbol".to_string());
+    }
+    if input.max_bars < SCAN_MIN_BARS {
+        return Err(format!("max_bars must be at least {}", SCAN_MIN_BARS));
+    }
+    for name in &input.strategies {
+        strategy::create_strategy(name, config)?;
+    }
+
+    let mut symbols = HashMap::new();
+    for entry in input.symbols {
+        let (symbol, mut candles) = match entry {
+            SymbolIn::Name(symbol) => (symbol, Vec::new()),
+            SymbolIn::Data { symbol, candles } => (symbol, candles),
+        };
+        sanitize_candles(&mut candles);
+        let mut state = SymbolState {
+            window: Vec::new(),
+            before_last: Running::new(),
+            current: Running::new(),
+            strategies: input.strategies.iter()
+                .map(|n| strategy::create_strategy(n, config))
+                .collect::<Result<_, _>>()?,
+            position: None,
+        };
+        for c in candles {
+            state.before_last = std::mem::replace(&mut state.current, Running::new());
+            state.current = state.before_last.step(&c);
+            state.window.push(c);
+        }
+        trim(&mut state.window, input.max_bars);
+        symbols.insert(symbol, state);
+    }
+
+    let sub = Subscription {
+        strategies: input.strategies,
+        scan: input.scan,
+        scan_options: input.scan_options,
+        max_bars: input.max_bars,
+        paper: input.paper,
+        rsi_levels: input.rsi_levels,
+        emit_events: input.emit_events,
+        symbols,
+        realized_pnl: 0.0,
+        trades: Vec::new(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        replay,
+    };
+    let replaced = SUBSCRIPTIONS.contains_key(&input.subscription_id);
+    let summary = sub.summary(&input.subscription_id);
+    SUBSCRIPTIONS.insert(input.subscription_id, sub);
+    let mut out = summary;
+    out["replaced"] = json!(replaced);
+    Ok(out)
+}
+
+pub fn unsubscribe(data: Value) -> Result<Value, String> {
+    let input: UnsubscribeInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid unsubscribe input: {}", e))?;
+    let (id, sub) = SUBSCRIPTIONS
+        .remove(&input.subscription_id)
+        .ok_or_else(|| format!("Unknown subscription_id '{}'", input.subscription_id))?;
+    let mut out = sub.summary(&id);
+    out["removed"] = json!(true);
+    Ok(out)
+}
+
+pub fn list() -> Result<Value, String> {
+    let mut subs: Vec<Value> = SUBSCRIPTIONS.iter()
+        .filter(|e| !e.value().replay)
+        .map(|e| e.value().summary(e.key()))
+        .collect();
+    subs.sort_by(|a, b| a["subscription_id"].as_str().cmp(&b["subscription_id"].as_str()));
+    Ok(json!({ "count": subs.len(), "subscriptions": subs }))
+}
+
+/// Current state of one subscription, as `subscriptions` lists it.
+pub(crate) fn summary(subscription_id: &str) -> Option<Value> {
+    SUBSCRIPTIONS.get(subscription_id).map(|s| s.summary(subscription_id))
+}
+
+pub fn push_candle(data: Value, config: &EngineConfig) -> Result<Value, String> {
+    let input: PushInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid push_candle input: {}", e))?;
+    let mut candles: Vec<Candle> = input.candle.into_iter().chain(input.candles).collect();
+    if candles.is_empty() {
+        return Err("push_candle requires candle or candles".to_string());
+    }
+    sanitize_candles(&mut candles);
+
+    let mut events = Vec::new();
+    let mut updated = Vec::new();
+    let mut revised = 0;
+    for mut entry in SUBSCRIPTIONS.iter_mut() {
+        let id = entry.key().clone();
+        let sub = entry.value_mut();
+        match &input.subscription_id {
+            Some(s) if *s != id => continue,
+            None if sub.replay => continue,
+            _ => {}
+        }
+        if !sub.symbols.contains_key(&input.symbol) {
+            continue;
+        }
+        for c in &candles {
+            let (is_revision, mut produced) = sub.advance(&id, &input.symbol, c, config)?;
+            revised += is_revision as usize;
+            if sub.emit_events {
+                produced.iter().for_each(progress::publish);
+            }
+            events.append(&mut produced);
+        }
+        let state = &sub.symbols[&input.symbol];
+        updated.push(json!({
+            "subscription_id": id,
+            "bars": state.window.len(),
+            "position": state.position,
+            "realized_pnl": round2(sub.realized_pnl),
+        }));
+    }
+    if updated.is_empty() {
+        return Err(match &input.subscription_id {
+            Some(id) if !SUBSCRIPTIONS.contains_key(id) => format!("Unknown subscription_id '{}'", id),
+            _ => format!("No subscription holds symbol '{}'", input.symbol),
+        });
+    }
+
+    let snapshot = SUBSCRIPTIONS.iter()
+        .find_map(|e| e.symbols.get(&input.symbol).map(|s| s.current.last))
+        .unwrap_or_default();
+    Ok(json!({
+        "symbol": input.symbol,
+        "timestamp": candles.last().map(|c| c.timestamp.clone()),
+        "candles": candles.len(),
+        "revised": revised,
+        "indicators": rounded(&snapshot),
+        "subscriptions": updated,
+        "events": events,
+    }))
+}
+
+// ─── Per-bar update ──────────────────────────────────────────────────
+
+impl Subscription {
+    /// Apply one candle to `symbol`; returns whether it revised the last bar
+    /// and the events it produced.
+    fn advance(&mut self, id: &str, symbol: &str, c: &Candle, config: &EngineConfig) -> Result<(bool, Vec<Value>), String> {
+        let max_bars = self.max_bars;
+        let state = self.symbols.get_mut(symbol).ok_or("symbol not subscribed")?;
+        let revision = match state.window.last() {
+            Some(last) => match bar_order(&last.timestamp, &c.timestamp) {
+                std::cmp::Ordering::Greater => {
+                    return Err(format!("{}: candle {} is older than the last bar {}", symbol, c.timestamp, last.timestamp));
+                }
+                std::cmp::Ordering::Equal => true,
+                std::cmp::Ordering::Less => false,
+            },
+            None => false,
+        };
+        if revision {
+            state.current = state.before_last.step(c);
+            if let Some(last) = state.window.last_mut() {
+                *last = c.clone();
+            }
+        } else {
+            state.before_last = std::mem::replace(&mut state.current, Running::new());
+            state.current = state.before_last.step(c);
+            state.window.push(c.clone());
+            trim(&mut state.window, max_bars);
+        }
+
+        let event = |kind: &str, detail: Value| {
+            let mut e = json!({ "event": kind, "subscription_id": id, "symbol": symbol, "timestamp": c.timestamp });
+            if let (Some(e), Value::Object(d)) = (e.as_object_mut(), detail) {
+                e.extend(d);
+            }
+            e
+        };
+        let mut events = Vec::new();
+
+        // Stops and targets first: the bar's range was traded before its close.
+        if let Some(exit) = self.check_exit(symbol, c) {
+            events.push(event("exit", exit));
+        }
+        if revision {
+            self.mark(symbol, c.close);
+            return Ok((true, events));
+        }
+
+        let state = &self.symbols[symbol];
+        let (prev, now) = (state.before_last.last, state.current.last);
+        events.extend(self.alerts(&prev, &now).into_iter().map(|(kind, msg)| event("alert", json!({ "kind": kind, "message": msg }))));
+
+        for signal in self.signals(symbol, config)? {
+            events.push(event("signal", json!({
+                "source": signal.source,
+                "direction": if signal.long { "BUY" } else { "SELL" },
+                "confidence": round4(signal.confidence),
+                "price": round2(signal.price),
+                "stop_loss": signal.stop_loss.map(round2),
+                "target": signal.target.map(round2),
+                "reason": signal.reason,
+            })));
+            if !self.paper.enabled || signal.confidence < self.paper.min_confidence {
+                continue;
+            }
+            let holding = self.symbols[symbol].position.as_ref().map(|p| p.side == "long");
+            match holding {
+                Some(long) if long != signal.long => {
+                    let exit = self.close_position(symbol, c.close, c, &format!("{} signal", signal.source));
+                    events.push(event("exit", exit));
+                }
+                Some(_) => continue,
+                None => {}
+            }
+            if self.symbols[symbol].position.is_none() && !signal.exit_only && (signal.long || self.paper.allow_short) {
+                if let Some(entry) = self.open_position(symbol, &signal, c) {
+                    events.push(event("entry", entry));
+                }
+            }
+        }
+        self.mark(symbol, c.close);
+        Ok((false, events))
+    }
+
+    fn alerts(&self, prev: &Snapshot, now: &Snapshot) -> Vec<(&'static str, String)> {
+        let mut out = Vec::new();
+        if let (Some(pf), Some(ps), Some(f), Some(s)) = (prev.ema_9, prev.ema_21, now.ema_9, now.ema_21) {
+            if pf <= ps && f > s {
+                out.push(("ema_cross_up", "EMA 9 crossed above EMA 21".to_string()));
+            } else if pf >= ps && f < s {
+                out.push(("ema_cross_down", "EMA 9 crossed below EMA 21".to_string()));
+            }
+        }
+        let (low, high) = self.rsi_levels;
+        if prev.rsi_14 < high && now.rsi_14 >= high {
+            out.push(("rsi_overbought", format!("RSI {:.1} crossed above {}", now.rsi_14, high)));
+        } else if prev.rsi_14 > low && now.rsi_14 <= low {
+            out.push(("rsi_oversold", format!("RSI {:.1} crossed below {}", now.rsi_14, low)));
+        }
+        out
+    }
+
+    /// Scan vote on the window, then each strategy on the newest bar.
+    fn signals(&mut self, symbol: &str, config: &EngineConfig) -> Result<Vec<StreamSignal>, String> {
+        let mut out = Vec::new();
+        let state = self.symbols.get_mut(symbol).ok_or("symbol not subscribed")?;
+        if self.scan && state.window.len() >= SCAN_MIN_BARS {
+            let mut input = self.scan_options.clone();
+            input.insert("symbols".to_string(), json!([{ "symbol": symbol, "candles": state.window }]));
+            let result = crate::scan::compute(Value::Object(input))?;
+            let best = result["signals"].as_array().into_iter().flatten()
+                .filter(|s| s["symbol"] == symbol)
+                .max_by(|a, b| a["confidence"].as_f64().partial_cmp(&b["confidence"].as_f64()).unwrap_or(std::cmp::Ordering::Equal));
+            if let Some(s) = best {
+                out.push(StreamSignal {
+                    source: "scan".to_string(),
+                    long: s["direction"] == "BUY",
+                    confidence: s["confidence"].as_f64().unwrap_or(0.0),
+                    price: s["entry"].as_f64().unwrap_or(0.0),
+                    stop_loss: s["stop_loss"].as_f64(),
+                    target: s["target"].as_f64(),
+                    reason: s["strategy"].as_str().map_or("scan vote".to_string(), |n| format!("scan: {}", n)),
+                    exit_only: false,
+                });
+            }
+        }
+        if !state.strategies.is_empty() {
+            let ind = Indicators::from_candles(&state.window, config);
+            let i = state.window.len() - 1;
+            let candle = &state.window[i];
+            for (name, strat) in self.strategies.iter().zip(state.strategies.iter_mut()) {
+                if i < strat.warmup_period() {
+                    continue;
+                }
+                if let Some(sig) = strat.on_candle(i, candle, &ind) {
+                    let exit_only = strat.is_exit(&sig);
+                    out.push(StreamSignal {
+                        source: name.clone(),
+                        long: matches!(sig.side, Side::Buy),
+                        confidence: sig.confidence,
+                        price: sig.price,
+                        stop_loss: sig.stop_loss,
+                        target: sig.take_profit,
+                        reason: sig.reason,
+                        exit_only,
+                    });
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn open_position(&mut self, symbol: &str, signal: &StreamSignal, c: &Candle) -> Option<Value> {
+        let equity = self.paper.capital + self.realized_pnl;
+        let state = self.symbols.get_mut(symbol)?;
+        let entry = c.close;
+        let dir = if signal.long { 1.0 } else { -1.0 };
+        let atr = state.current.last.atr_14;
+        // A stop on the wrong side of the entry is no stop; fall back to ATR.
+        let stop = signal.stop_loss
+            .filter(|s| (entry - s) * dir > 0.0)
+            .unwrap_or(entry - dir * self.paper.stop_atr * atr);
+        let target = signal.target.filter(|t| (t - entry) * dir > 0.0);
+        let risk = (entry - stop) * dir;
+        let qty = match self.paper.qty {
+            Some(q) => q,
+            None if risk > 0.0 => {
+                let by_risk = equity * self.paper.risk_pct / 100.0 / risk;
+                by_risk.min(equity / entry).floor() as i64
+            }
+            None => 0,
+        };
+        if qty < 1 {
+            return None;
+        }
+        let side = if signal.long { Side::Buy } else { Side::Sell };
+        for s in state.strategies.iter_mut() {
+            s.on_fill(&Fill { side: side.clone(), price: entry, qty, pnl: 0.0, timestamp: c.timestamp.clone() });
+        }
+        let position = PaperPosition {
+            side: if signal.long { "long" } else { "short" },
+            qty,
+            entry_price: entry,
+            stop_loss: stop,
+            target,
+            opened_at: c.timestamp.clone(),
+            source: signal.source.clone(),
+            mark: entry,
+            unrealized_pnl: 0.0,
+        };
+        let out = json!({
+            "side": position.side,
+            "qty": qty,
+            "price": round2(entry),
+            "stop_loss": round2(stop),
+            "target": target.map(round2),
+            "source": signal.source,
+        });
+        state.position = Some(position);
+        Some(out)
+    }
+
+    /// Stop or target touched by the bar's range; gaps through a level fill
+    /// at the open. The stop wins when both are inside the bar.
+    fn check_exit(&mut self, symbol: &str, c: &Candle) -> Option<Value> {
+        let pos = self.symbols.get(symbol)?.position.as_ref()?;
+        let long = pos.side == "long";
+        let (stop_hit, target_hit) = if long {
+            (c.low <= pos.stop_loss, pos.target.is_some_and(|t| c.high >= t))
+        } else {
+            (c.high >= pos.stop_loss, pos.target.is_some_and(|t| c.low <= t))
+        };
+        let (level, reason) = if stop_hit {
+            (pos.stop_loss, "stop_loss")
+        } else if target_hit {
+            (pos.target?, "target")
+        } else {
+            return None;
+        };
+        let gapped = if long == stop_hit { c.open < level } else { c.open > level };
+        let price = if gapped { c.open } else { level };
+        Some(self.close_position(symbol, price, c, reason))
+    }
+
+    fn close_position(&mut self, symbol: &str, price: f64, c: &Candle, reason: &str) -> Value {
+        let Some(state) = self.symbols.get_mut(symbol) else { return Value::Null };
+        let Some(pos) = state.position.take() else { return Value::Null };
+        let dir = if pos.side == "long" { 1.0 } else { -1.0 };
+        let pnl = (price - pos.entry_price) * dir * pos.qty as f64;
+        let side = if pos.side == "long" { Side::Sell } else { Side::Buy };
+        for s in state.strategies.iter_mut() {
+            s.on_fill(&Fill { side: side.clone(), price, qty: pos.qty, pnl, timestamp: c.timestamp.clone() });
+        }
+        self.realized_pnl += pnl;
+        let out = json!({
+            "side": pos.side,
+            "qty": pos.qty,
+            "entry_price": round2(pos.entry_price),
+            "price": round2(price),
+            "pnl": round2(pnl),
+            "reason": reason,
+            "source": pos.source,
+        });
+        self.trades.push(ClosedTrade {
+            symbol: symbol.to_string(),
+            side: pos.side,
+            qty: pos.qty,
+            entry_price: round2(pos.entry_price),
+            exit_price: round2(price),
+            pnl: round2(pnl),
+            opened_at: pos.opened_at,
+            closed_at: c.timestamp.clone(),
+            source: pos.source,
+            exit_reason: reason.to_string(),
+        });
+        out
+    }
+
+    fn mark(&mut self, symbol: &str, close: f64) {
+        if let Some(pos) = self.symbols.get_mut(symbol).and_then(|s| s.position.as_mut()) {
+            let dir = if pos.side == "long" { 1.0 } else { -1.0 };
+            pos.mark = close;
+            pos.unrealized_pnl = round2((close - pos.entry_price) * dir * pos.qty as f64);
+        }
+    }
+
+    fn summary(&self, id: &str) -> Value {
+        let mut symbols: Vec<Value> = self.symbols.iter().map(|(name, s)| json!({
+            "symbol": name,
+            "bars": s.window.len(),
+            "last_timestamp": s.window.last().map(|c| c.timestamp.clone()),
+            "indicators": rounded(&s.current.last),
+            "position": s.position,
+        })).collect();
+        symbols.sort_by(|a, b| a["symbol"].as_str().cmp(&b["symbol"].as_str()));
+        let unrealized: f64 = self.symbols.values()
+            .filter_map(|s| s.position.as_ref())
+            .map(|p| p.unrealized_pnl)
+            .sum();
+        let wins = self.trades.iter().filter(|t| t.pnl > 0.0).count();
+        json!({
+            "subscription_id": id,
+            "created_at": self.created_at,
+            "strategies": self.strategies,
+            "scan": self.scan,
+            "max_bars": self.max_bars,
+            "paper": self.paper,
+            "symbols": symbols,
+            "equity": round2(self.paper.capital + self.realized_pnl + unrealized),
+            "realized_pnl": round2(self.realized_pnl),
+            "unrealized_pnl": round2(unrealized),
+            "closed_trades": self.trades.len(),
+            "win_rate": if self.trades.is_empty() { 0.0 } else { round4(wins as f64 / self.trades.len() as f64) },
+            "trades": self.trades,
+        })
+    }
+}
+
+fn trim(window: &mut Vec<Candle>, max_bars: usize) {
+    if window.len() > max_bars {
+        let skip = window.len() - max_bars;
+        window.drain(..skip);
+    }
+}
+
+/// Order of two bar timestamps: parsed when both parse, else as strings.
+fn bar_order(a: &str, b: &str) -> std::cmp::Ordering {
+    match (parse_timestamp(a), parse_timestamp(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+fn rounded(s: &Snapshot) -> Value {
+    json!({
+        "close": round2(s.close),
+        "ema_9": s.ema_9.map(round2),
+        "ema_21": s.ema_21.map(round2),
+        "rsi_14": round2(s.rsi_14),
+        "atr_14": round2(s.atr_14),
+        "vwap": round2(s.vwap),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indicators;
+
+    fn bar(i: usize, close: f64) -> Candle {
+        Candle {
+            timestamp: format!("2024-01-{:02}T{:02}:{:02}:00", 1 + i / 300, 9 + (i % 300) / 60, i % 60),
+            open: close - 0.2,
+            high: close + 0.5,
+            low: close - 0.5,
+            close,
+            volume: 1000.0 + (i % 7) as f64 * 100.0,
+        }
+    }
+
+    fn series(n: usize) -> Vec<Candle> {
+        (0..n).map(|i| bar(i, 100.0 + (i as f64 * 0.15).sin() * 4.0 + i as f64 * 0.02)).collect()
+    }
+
+    #[test]
+    fn test_running_state_matches_batch_kernels() {
+        let candles = series(700);
+        let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+        let highs: Vec<f64> = candles.iter().map(|c| c.high).collect();
+        let lows: Vec<f64> = candles.iter().map(|c| c.low).collect();
+        let ema = indicators::ema(&closes, 21);
+        let rsi = indicators::rsi(&closes, 14);
+        let atr = indicators::atr(&highs, &lows, &closes, 14);
+        let (vwap, _) = indicators::session_vwap_bands(&candles);
+
+        let mut running = Running::new();
+        for (i, c) in candles.iter().enumerate() {
+            running = running.step(c);
+            let s = running.last;
+            assert_eq!(s.ema_21.is_some(), !ema[i].is_nan(), "bar {}", i);
+            if let Some(v) = s.ema_21 { assert!((v - ema[i]).abs() < 1e-9, "ema bar {}", i); }
+            assert!((s.rsi_14 - rsi[i]).abs() < 1e-9, "rsi bar {}: {} vs {}", i, s.rsi_14, rsi[i]);
+            assert!((s.atr_14 - atr[i]).abs() < 1e-9, "atr bar {}", i);
+            assert!((s.vwap - vwap[i]).abs() < 1e-9, "vwap bar {}", i);
+        }
+    }
+
+    #[test]
+    fn test_subscribe_push_trades_and_revisions() {
+        let config = EngineConfig::default();
+        let candles = series(60);
+        let id = format!("t-{}", std::process::id());
+        subscribe(json!({
+            "subscription_id": id, "symbols": [{ "symbol": "STREAMX", "candles": candles }],
+            "strategies": ["ema_crossover"], "scan": false, "max_bars": 100,
+            "paper": { "qty": 10 }, "emit_events": false
+        }), &config).unwrap();
+
+        // Slide, rally through the 9/21 EMA cross, then collapse into an exit.
+        let mut entries = 0;
+        let mut exits = Vec::new();
+        let mut i = 60;
+        let mut close = candles[59].close;
+        while i < 140 {
+            close += if i < 75 { -0.8 } else if i < 105 { 0.8 } else { -2.5 };
+            let out = push_candle(json!({ "symbol": "STREAMX", "candle": bar(i, close), "subscription_id": id }), &config).unwrap();
+            for e in out["events"].as_array().unwrap() {
+                assert_eq!(e["subscription_id"], id.as_str());
+                match e["event"].as_str().unwrap() {
+                    "entry" => entries += 1,
+                    "exit" => exits.push(e["reason"].as_str().unwrap().to_string()),
+                    _ => {}
+                }
+            }
+            i += 1;
+        }
+        assert_eq!(entries, 1);
+        assert_eq!(exits.len(), 1);
+
+        // Same timestamp revises the last bar; an older one is rejected.
+        let out = push_candle(json!({ "symbol": "STREAMX", "candle": bar(139, close + 1.0), "subscription_id": id }), &config).unwrap();
+        assert_eq!(out["revised"], 1);
+        assert_eq!(out["subscriptions"][0]["bars"], 100);
+        assert!(push_candle(json!({ "symbol": "STREAMX", "candle": bar(10, close) }), &config).is_err());
+
+        let listed = list().unwrap();
+        let sub = listed["subscriptions"].as_array().unwrap().iter().find(|s| s["subscription_id"] == id.as_str()).unwrap();
+        assert_eq!(sub["closed_trades"], 1);
+        let removed = unsubscribe(json!({ "subscription_id": id })).unwrap();
+        assert_eq!(removed["removed"], true);
+        assert!(push_candle(json!({ "symbol": "STREAMX", "candle": bar(140, close) }), &config).is_err());
+        assert!(subscribe(json!({ "subscription_id": "x", "symbols": ["A"], "strategies": ["nope"] }), &config).is_err());
+
+        // The scan vote runs on the window once it is long enough.
+        let scan_id = format!("{}-scan", id);
+        subscribe(json!({ "subscription_id": scan_id, "symbols": [{ "symbol": "STREAMY", "candles": candles }], "emit_events": false }), &config).unwrap();
+        let out = push_candle(json!({ "symbol": "STREAMY", "candle": bar(60, 140.0) }), &config).unwrap();
+        assert!(out["events"].as_array().unwrap().iter().any(|e| e["event"] == "signal" && e["source"] == "scan"), "{}", out);
+        unsubscribe(json!({ "subscription_id": scan_id })).unwrap();
+    }
+}
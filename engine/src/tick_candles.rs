@@ -0,0 +1,209 @@
+//! Build OHLCV candles from raw trade ticks.
+//!
+//! Ticks `{timestamp, price, size, side?}` are bucketed into bars of any
+//! timeframe, aligned to `anchor` (the session open by default, so hourly
+//! NSE bars run 09:15–10:15). Each bar also carries aggressor buy/sell
+//! volume and delta, classified like the order-flow footprint, so the
+//! output can feed `advanced_signals` or any candle command directly.
+
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::advanced_signals::{TickClassifier, TradeTick};
+use crate::utils::{parse_timestamp, round2, round4};
+
+#[derive(Deserialize)]
+struct TickCandleConfig {
+    ticks: Vec<TradeTick>,
+    /// "15s", "1m", "5min", "1h", "1d", or a number of seconds.
+    timeframe: Value,
+    #[serde(default = "default_anchor")]
+    anchor: String,
+    /// Emit zero-volume bars for empty buckets inside a day.
+    #[serde(default)]
+    fill_gaps: bool,
+}
+
+fn default_anchor() -> String { "09:15".to_string() }
+
+#[derive(Serialize)]
+struct TickCandle {
+    timestamp: String,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: f64,
+    buy_volume: f64,
+    sell_volume: f64,
+    delta: f64,
+    cumulative_delta: f64,
+    trades: usize,
+    vwap: f64,
+}
+
+#[derive(Serialize)]
+struct TickCandleResult {
+    timeframe_seconds: i64,
+    candles: Vec<TickCandle>,
+    ticks_used: usize,
+    skipped_ticks: usize,
+}
+
+/// Parse "15s" / "5m" / "5min" / "1h" / "1d" or a bare number of seconds.
+fn parse_timeframe(tf: &Value) -> Result<i64, String> {
+    if let Some(n) = tf.as_i64() {
+        return if n > 0 { Ok(n) } else { Err("timeframe must be positive".to_string()) };
+    }
+    let s = tf.as_str().ok_or("timeframe must be a string like \"5m\" or a number of seconds")?.trim().to_lowercase();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let n: i64 = num.parse().map_err(|_| format!("Invalid timeframe '{}'", s))?;
+    let unit_secs = match unit {
+        "" | "s" | "sec" => 1,
+        "m" | "min" => 60,
+        "h" | "hr" => 3600,
+        "d" | "day" => 86_400,
+        _ => return Err(format!("Invalid timeframe unit in '{}' (s, m, h, d)", s)),
+    };
+    if n <= 0 {
+        return Err("timeframe must be positive".to_string());
+    }
+    Ok(n * unit_secs)
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: TickCandleConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid ticks_to_candles config: {}", e))?;
+    let secs = parse_timeframe(&config.timeframe)?;
+    let anchor = NaiveTime::parse_from_str(&config.anchor, "%H:%M")
+        .map_err(|_| format!("Invalid anchor '{}', expected HH:MM", config.anchor))?;
+    let anchor_secs = anchor.num_seconds_from_midnight() as i64;
+
+    // Keep the offset of localized timestamps so bar labels match the input.
+    let offset = config.ticks.first().and_then(|t| DateTime::parse_from_rfc3339(t.timestamp.trim()).ok()).map(|d| *d.offset());
+    let mut ticks: Vec<(NaiveDateTime, &TradeTick)> = config.ticks.iter()
+        .filter(|t| t.price.is_finite() && t.price > 0.0 && t.size.is_finite() && t.size >= 0.0)
+        .filter_map(|t| parse_timestamp(&t.timestamp).map(|ts| (ts, t)))
+        .collect();
+    let skipped = config.ticks.len() - ticks.len();
+    ticks.sort_by_key(|(ts, _)| *ts);
+
+    // Bucket start: whole days for daily bars, else anchored steps of `secs`.
+    let bucket = |ts: NaiveDateTime| -> NaiveDateTime {
+        let midnight = ts.date().and_time(NaiveTime::MIN);
+        if secs >= 86_400 {
+            return midnight;
+        }
+        let since_anchor = ts.time().num_seconds_from_midnight() as i64 - anchor_secs;
+        midnight + Duration::seconds(anchor_secs + since_anchor.div_euclid(secs) * secs)
+    };
+    let label = |start: NaiveDateTime| match offset {
+        Some(off) => start.and_local_timezone(off).single()
+            .map(|d| d.to_rfc3339_opts(chrono::SecondsFormat::Secs, false))
+            .unwrap_or_else(|| start.format("%Y-%m-%dT%H:%M:%S").to_string()),
+        None => start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    };
+
+    let mut candles: Vec<TickCandle> = Vec::new();
+    let mut current: Option<NaiveDateTime> = None;
+    let mut classifier = TickClassifier::new();
+    let mut cum_delta = 0.0;
+    let mut pv = 0.0;
+    for (ts, tick) in &ticks {
+        let start = bucket(*ts);
+        let is_buy = classifier.is_buy(tick);
+        if current != Some(start) {
+            if config.fill_gaps {
+                if let (Some(prev), Some(last)) = (current, candles.last()) {
+                    let close = last.close;
+                    let mut gap = prev + Duration::seconds(secs);
+                    while gap < start && gap.date() == start.date() {
+                        candles.push(TickCandle {
+                            timestamp: label(gap), open: close, high: close, low: close, close,
+                            volume: 0.0, buy_volume: 0.0, sell_volume: 0.0, delta: 0.0,
+                            cumulative_delta: round2(cum_delta), trades: 0, vwap: close,
+                        });
+                        gap += Duration::seconds(secs);
+                    }
+                }
+            }
+            current = Some(start);
+            pv = 0.0;
+            candles.push(TickCandle {
+                timestamp: label(start), open: tick.price, high: tick.price, low: tick.price, close: tick.price,
+                volume: 0.0, buy_volume: 0.0, sell_volume: 0.0, delta: 0.0,
+                cumulative_delta: 0.0, trades: 0, vwap: tick.price,
+            });
+        }
+        let Some(bar) = candles.last_mut() else { continue };
+        bar.high = bar.high.max(tick.price);
+        bar.low = bar.low.min(tick.price);
+        bar.close = tick.price;
+        bar.volume += tick.size;
+        bar.trades += 1;
+        if is_buy { bar.buy_volume += tick.size } else { bar.sell_volume += tick.size }
+        pv += tick.price * tick.size;
+        let signed = if is_buy { tick.size } else { -tick.size };
+        cum_delta += signed;
+        bar.delta += signed;
+        bar.cumulative_delta = cum_delta;
+        if bar.volume > 0.0 {
+            bar.vwap = pv / bar.volume;
+        }
+    }
+    for c in candles.iter_mut() {
+        c.vwap = round4(c.vwap);
+        for v in [&mut c.volume, &mut c.buy_volume, &mut c.sell_volume, &mut c.delta, &mut c.cumulative_delta] {
+            *v = round2(*v);
+        }
+    }
+
+    let result = TickCandleResult { timeframe_seconds: secs, ticks_used: ticks.len(), skipped_ticks: skipped, candles };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builds_ohlcv_and_delta() {
+        let ticks = json!([
+            { "timestamp": "2024-01-02T09:15:05", "price": 100.0, "size": 10.0, "side": "buy" },
+            { "timestamp": "2024-01-02T09:16:30", "price": 102.0, "size": 5.0 },
+            { "timestamp": "2024-01-02T09:19:59", "price": 99.0, "size": 20.0 },
+            { "timestamp": "2024-01-02T09:20:00", "price": 101.0, "size": 4.0, "side": "S" },
+            { "timestamp": "bad", "price": 101.0, "size": 4.0 },
+        ]);
+        let result = compute(json!({ "ticks": ticks, "timeframe": "5m" })).unwrap();
+        let candles = result["candles"].as_array().unwrap();
+        assert_eq!(candles.len(), 2);
+        assert_eq!(result["skipped_ticks"], 1);
+        let first = &candles[0];
+        assert_eq!(first["timestamp"], "2024-01-02T09:15:00");
+        assert_eq!((first["open"].as_f64(), first["high"].as_f64(), first["low"].as_f64(), first["close"].as_f64()),
+                   (Some(100.0), Some(102.0), Some(99.0), Some(99.0)));
+        assert_eq!(first["volume"], 35.0);
+        // buy 10, uptick buy 5, downtick sell 20
+        assert_eq!(first["delta"], -5.0);
+        assert_eq!(candles[1]["cumulative_delta"], -9.0);
+    }
+
+    #[test]
+    fn test_anchor_alignment_and_gap_fill() {
+        assert_eq!(parse_timeframe(&json!("1h")).unwrap(), 3600);
+        assert_eq!(parse_timeframe(&json!(90)).unwrap(), 90);
+        assert!(parse_timeframe(&json!("5x")).is_err());
+
+        let ticks = json!([
+            { "timestamp": "2024-01-02T09:20:00+05:30", "price": 100.0, "size": 1.0 },
+            { "timestamp": "2024-01-02T12:30:00+05:30", "price": 101.0, "size": 1.0 },
+        ]);
+        let result = compute(json!({ "ticks": ticks, "timeframe": "1h", "fill_gaps": true })).unwrap();
+        let stamps: Vec<&str> = result["candles"].as_array().unwrap().iter().map(|c| c["timestamp"].as_str().unwrap()).collect();
+        assert_eq!(stamps, ["2024-01-02T09:15:00+05:30", "2024-01-02T10:15:00+05:30",
+                            "2024-01-02T11:15:00+05:30", "2024-01-02T12:15:00+05:30"]);
+    }
+}
@@ -0,0 +1,144 @@
+//! Exchange-local timestamps.
+//!
+//! Every `candles` / `ticks` timestamp is rewritten as RFC3339 in the
+//! exchange zone (`market.timezone`, or the request's `timezone`) before
+//! dispatch:
+//!
+//! - RFC3339 with any offset (or `Z`) is converted to the exchange offset,
+//! - epoch seconds / milliseconds, as strings or JSON numbers, likewise,
+//! - naive strings ("2024-01-02 09:15") are already exchange-local and kept.
+//!
+//! The instant is preserved, and the date and wall-clock prefix become
+//! exchange-local, so session logic that reads dates and times (VWAP reset,
+//! initial balance, day grouping) follows the exchange across DST changes.
+//! Unless the request set `timezone`, `restore` then puts the caller's own
+//! timestamps back wherever the response echoes them.
+
+use std::collections::HashMap;
+use chrono::{DateTime, SecondsFormat, TimeZone};
+use chrono_tz::Tz;
+use serde_json::Value;
+
+/// Exchange-local timestamp → the value the caller sent for it.
+pub type Originals = HashMap<String, Value>;
+
+/// Resolve an IANA zone name such as "Asia/Kolkata" or "America/New_York".
+pub fn parse_zone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>().map_err(|_| format!("Unknown timezone '{}'", name))
+}
+
+/// Rewrite series timestamps in place, recording each original in
+/// `originals`. Returns how many were changed.
+pub fn localize(data: &mut Value, tz: Tz, originals: &mut Originals) -> usize {
+    match data {
+        Value::Object(map) => map
+            .iter_mut()
+            .map(|(key, value)| match (key.as_str(), value) {
+                ("candles" | "ticks", Value::Array(items)) => {
+                    items.iter_mut().map(|item| localize_bar(item, tz, originals)).sum()
+                }
+                (_, value) => localize(value, tz, originals),
+            })
+            .sum(),
+        Value::Array(items) => items.iter_mut().map(|v| localize(v, tz, originals)).sum(),
+        _ => 0,
+    }
+}
+
+fn localize_bar(bar: &mut Value, tz: Tz, originals: &mut Originals) -> usize {
+    let Some(ts) = bar.get_mut("timestamp") else { return 0 };
+    let local = match ts {
+        Value::String(s) => to_exchange_local(s, tz),
+        Value::Number(n) => n.as_i64().and_then(|n| from_epoch(n, tz)),
+        _ => None,
+    };
+    match local {
+        Some(local) if *ts != Value::String(local.clone()) => {
+            let original = std::mem::replace(ts, Value::String(local.clone()));
+            originals.entry(local).or_insert(original);
+            1
+        }
+        _ => 0,
+    }
+}
+
+/// Swap localized timestamps in a response back to what the caller sent.
+/// An instant sent in two spellings comes back as the first; stamps the
+/// command derived itself (e.g. resampled bucket starts) have no original
+/// and stay exchange-local.
+pub fn restore(data: &mut Value, originals: &Originals) {
+    match data {
+        Value::String(s) => {
+            if let Some(original) = originals.get(s.as_str()) {
+                *data = original.clone();
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| restore(v, originals)),
+        Value::Object(map) => map.values_mut().for_each(|v| restore(v, originals)),
+        _ => {}
+    }
+}
+
+/// Exchange-local RFC3339 for an absolute timestamp; `None` for naive or
+/// unparseable input, which is left as it is.
+pub fn to_exchange_local(ts: &str, tz: Tz) -> Option<String> {
+    let ts = ts.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(ts) {
+        return Some(dt.with_timezone(&tz).to_rfc3339_opts(SecondsFormat::AutoSi, false));
+    }
+    ts.parse::<i64>().ok().and_then(|n| from_epoch(n, tz))
+}
+
+/// Epoch seconds, or milliseconds when the magnitude says so (same cutoff as
+/// `utils::parse_timestamp`).
+fn from_epoch(n: i64, tz: Tz) -> Option<String> {
+    let millis = if n.abs() >= 100_000_000_000 { n } else { n.checked_mul(1000)? };
+    tz.timestamp_millis_opt(millis)
+        .single()
+        .map(|dt| dt.to_rfc3339_opts(SecondsFormat::AutoSi, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_localize_converts_absolute_timestamps() {
+        let ist = parse_zone("Asia/Kolkata").unwrap();
+        let mut data = json!({
+            "candles": [
+                { "timestamp": "2024-01-02T03:45:00Z" },
+                { "timestamp": 1704167400 },
+                { "timestamp": "1704167400000" },
+                { "timestamp": "2024-01-02 09:15:00" },
+            ],
+            "symbols": [{ "candles": [{ "timestamp": "2024-01-01T23:00:00-05:00" }] }],
+        });
+        let sent = data.clone();
+        let mut originals = Originals::new();
+        assert_eq!(localize(&mut data, ist, &mut originals), 4);
+        assert_eq!(data["candles"][0]["timestamp"], "2024-01-02T09:15:00+05:30");
+        assert_eq!(data["candles"][1]["timestamp"], "2024-01-02T09:20:00+05:30");
+        assert_eq!(data["candles"][2]["timestamp"], "2024-01-02T09:20:00+05:30");
+        assert_eq!(data["candles"][3]["timestamp"], "2024-01-02 09:15:00");
+        // 23:00 New York is the next morning in India.
+        assert_eq!(data["symbols"][0]["candles"][0]["timestamp"], "2024-01-02T09:30:00+05:30");
+
+        // Two stamps for one instant restore to the first one sent.
+        assert_eq!(originals.len(), 3);
+        restore(&mut data, &originals);
+        assert_eq!(data["candles"][2]["timestamp"], 1704167400);
+        data["candles"][2]["timestamp"] = sent["candles"][2]["timestamp"].clone();
+        assert_eq!(data, sent);
+    }
+
+    #[test]
+    fn test_dst_shifts_utc_open_but_not_local_open() {
+        let ny = parse_zone("America/New_York").unwrap();
+        // The 09:30 open is 14:30Z in winter and 13:30Z in summer.
+        assert_eq!(to_exchange_local("2024-01-02T14:30:00Z", ny).unwrap(), "2024-01-02T09:30:00-05:00");
+        assert_eq!(to_exchange_local("2024-07-01T13:30:00Z", ny).unwrap(), "2024-07-01T09:30:00-04:00");
+        assert!(parse_zone("Mars/Olympus").is_err());
+    }
+}
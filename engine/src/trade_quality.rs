@@ -0,0 +1,403 @@
+//! Where does a strategy actually make money? Trades are bucketed by the
+//! market context at entry and each bucket gets its own expectancy.
+//!
+//! Trades are `backtest` `trade_log` rows or anything with `entry_time` and
+//! `pnl`. Context comes from `candles` (or `candles_by_symbol[symbol]`) and is
+//! read from the last bar stamped strictly before entry, so nothing the
+//! trade could not have seen leaks in; `context_bar: "entry"` uses the bar
+//! stamped at entry instead (entries at a bar's close). Dimensions are
+//! `regime` (see `regime`), `rsi` (bucketed at `rsi_levels`),
+//! `time_of_day` (floored to `time_bucket_minutes`), `day_of_week`, `gap`
+//! (entry day's open vs the prior session's close beyond `gap_pct`) and
+//! `strategy_tag` (the trade's tag, "untagged" without one).
+//! `combine` adds two-way cross-tabs such as `["regime", "rsi"]`. Buckets
+//! with at least `min_trades` are highlighted as `works` (positive and above
+//! the overall expectancy) or `avoid` (negative expectancy).
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::regime::{self, RegimeParams, LABELS};
+use crate::utils::{calc_rsi_series, parse_timestamp, round2, sanitize_candles, Candle};
+
+const DIMENSIONS: [&str; 6] = ["regime", "rsi", "time_of_day", "day_of_week", "gap", "strategy_tag"];
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+#[derive(Deserialize)]
+struct QualityConfig {
+    trades: Vec<TradeIn>,
+    #[serde(default)]
+    candles: Vec<Candle>,
+    #[serde(default)]
+    candles_by_symbol: HashMap<String, Vec<Candle>>,
+    #[serde(default = "default_context_bar")]
+    context_bar: String,
+    #[serde(default = "default_rsi_period")]
+    rsi_period: usize,
+    #[serde(default = "default_rsi_levels")]
+    rsi_levels: Vec<f64>,
+    #[serde(default = "default_time_bucket")]
+    time_bucket_minutes: u32,
+    #[serde(default = "default_gap_pct")]
+    gap_pct: f64,
+    #[serde(default)]
+    regime: RegimeParams,
+    #[serde(default)]
+    combine: Vec<[String; 2]>,
+    #[serde(default = "default_min_trades")]
+    min_trades: usize,
+}
+
+fn default_context_bar() -> String { "prior".to_string() }
+fn default_rsi_period() -> usize { 14 }
+fn default_rsi_levels() -> Vec<f64> { vec![30.0, 50.0, 70.0] }
+fn default_time_bucket() -> u32 { 60 }
+fn default_gap_pct() -> f64 { 0.5 }
+fn default_min_trades() -> usize { 5 }
+
+#[derive(Deserialize)]
+struct TradeIn {
+    #[serde(default)]
+    symbol: Option<String>,
+    #[serde(alias = "entry_date", alias = "timestamp")]
+    entry_time: String,
+    pnl: f64,
+    #[serde(default)]
+    entry_price: Option<f64>,
+    #[serde(default)]
+    qty: Option<f64>,
+    #[serde(default, alias = "strategy", alias = "tag")]
+    strategy_tag: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+struct BucketStats {
+    bucket: String,
+    trades: usize,
+    win_rate: f64,
+    avg_win: f64,
+    avg_loss: f64,
+    expectancy: f64,
+    total_pnl: f64,
+    profit_factor: Option<f64>,
+    /// Mean P&L as a percent of entry notional, where price and qty are known.
+    avg_return_pct: Option<f64>,
+    /// Expectancy minus the overall expectancy.
+    edge: f64,
+}
+
+#[derive(Serialize)]
+struct Highlight {
+    dimension: String,
+    bucket: String,
+    trades: usize,
+    expectancy: f64,
+    win_rate: f64,
+}
+
+#[derive(Serialize)]
+struct Highlights {
+    works: Vec<Highlight>,
+    avoid: Vec<Highlight>,
+}
+
+#[derive(Serialize)]
+struct QualityResult {
+    total_trades: usize,
+    matched_trades: usize,
+    /// Trades with no context bar before entry (or no candles for their symbol).
+    unmatched_trades: usize,
+    overall: BucketStats,
+    dimensions: BTreeMap<String, Vec<BucketStats>>,
+    highlights: Highlights,
+}
+
+/// Per-bar context for one candle series.
+struct Series {
+    times: Vec<NaiveDateTime>,
+    rsi: Vec<f64>,
+    regime: Vec<Option<&'static str>>,
+    /// Open-to-prior-close gap (%) of each session.
+    gaps: HashMap<NaiveDate, f64>,
+}
+
+/// A bucket label with its sort key, so buckets list in natural order.
+type Label = (u32, String);
+
+struct Outcome {
+    pnl: f64,
+    return_pct: Option<f64>,
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let config: QualityConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid trade_quality config: {}", e))?;
+    if config.trades.is_empty() {
+        return Err("At least one trade required".to_string());
+    }
+    if config.candles.is_empty() && config.candles_by_symbol.is_empty() {
+        return Err("candles or candles_by_symbol required".to_string());
+    }
+    let strict = match config.context_bar.as_str() {
+        "prior" => true,
+        "entry" => false,
+        other => return Err(format!("Unknown context_bar '{}': use prior or entry", other)),
+    };
+    for pair in &config.combine {
+        for d in pair {
+            if !DIMENSIONS.contains(&d.as_str()) {
+                return Err(format!("Unknown dimension '{}' in combine: use {}", d, DIMENSIONS.join(", ")));
+            }
+        }
+    }
+
+    let default_series = (!config.candles.is_empty()).then(|| build_series(config.candles.clone(), &config));
+    let by_symbol: HashMap<&str, Series> = config.candles_by_symbol.iter()
+        .map(|(sym, c)| (sym.as_str(), build_series(c.clone(), &config)))
+        .collect();
+
+    let mut outcomes: Vec<Outcome> = Vec::new();
+    let mut labels: Vec<[Label; 6]> = Vec::new();
+    for trade in &config.trades {
+        let series = trade.symbol.as_deref().and_then(|s| by_symbol.get(s)).or(default_series.as_ref());
+        let Some(series) = series else { continue };
+        let Some(entry) = parse_timestamp(&trade.entry_time) else { continue };
+        let upto = if strict {
+            series.times.partition_point(|t| *t < entry)
+        } else {
+            series.times.partition_point(|t| *t <= entry)
+        };
+        if upto == 0 {
+            continue;
+        }
+        labels.push(context(series, upto - 1, entry, trade.strategy_tag.as_deref(), &config));
+        let notional = trade.entry_price.zip(trade.qty).map(|(p, q)| p * q.abs()).filter(|n| *n > 0.0);
+        outcomes.push(Outcome { pnl: trade.pnl, return_pct: notional.map(|n| trade.pnl / n * 100.0) });
+    }
+    if outcomes.is_empty() {
+        return Err("No trade could be matched to a bar before its entry".to_string());
+    }
+
+    let all: Vec<&Outcome> = outcomes.iter().collect();
+    let overall = stats("all", &all, 0.0);
+    let base = overall.expectancy;
+
+    let mut views: Vec<(String, Vec<Label>)> = DIMENSIONS.iter().enumerate()
+        .map(|(d, name)| (name.to_string(), labels.iter().map(|l| l[d].clone()).collect()))
+        .collect();
+    for [a, b] in &config.combine {
+        let (ia, ib) = (dim_index(a), dim_index(b));
+        let combined = labels.iter()
+            .map(|l| (l[ia].0 * 10_000 + l[ib].0, format!("{} | {}", l[ia].1, l[ib].1)))
+            .collect();
+        views.push((format!("{}+{}", a, b), combined));
+    }
+
+    let mut dimensions = BTreeMap::new();
+    let mut works = Vec::new();
+    let mut avoid = Vec::new();
+    for (name, view) in views {
+        let mut groups: BTreeMap<&Label, Vec<&Outcome>> = BTreeMap::new();
+        for (label, outcome) in view.iter().zip(&outcomes) {
+            groups.entry(label).or_default().push(outcome);
+        }
+        let buckets: Vec<BucketStats> = groups.iter().map(|(label, group)| stats(&label.1, group, base)).collect();
+        for b in &buckets {
+            if b.trades < config.min_trades {
+                continue;
+            }
+            let h = Highlight { dimension: name.clone(), bucket: b.bucket.clone(), trades: b.trades, expectancy: b.expectancy, win_rate: b.win_rate };
+            if b.expectancy > 0.0 && b.edge > 0.0 {
+                works.push(h);
+            } else if b.expectancy < 0.0 {
+                avoid.push(h);
+            }
+        }
+        dimensions.insert(name, buckets);
+    }
+    works.sort_by(|a, b| b.expectancy.total_cmp(&a.expectancy));
+    avoid.sort_by(|a, b| a.expectancy.total_cmp(&b.expectancy));
+
+    let result = QualityResult {
+        total_trades: config.trades.len(),
+        matched_trades: outcomes.len(),
+        unmatched_trades: config.trades.len() - outcomes.len(),
+        overall,
+        dimensions,
+        highlights: Highlights { works, avoid },
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn dim_index(name: &str) -> usize {
+    DIMENSIONS.iter().position(|d| *d == name).unwrap_or(0)
+}
+
+fn build_series(mut candles: Vec<Candle>, config: &QualityConfig) -> Series {
+    sanitize_candles(&mut candles);
+    let closes: Vec<f64> = candles.iter().map(|c| c.close).collect();
+    let rsi = calc_rsi_series(&closes, config.rsi_period.max(1));
+    // Too few bars for the regime model leaves every bar unlabelled.
+    let regime = regime::classify(&candles, &config.regime)
+        .map(|fit| fit.bars.into_iter().map(|b| b.regime).collect())
+        .unwrap_or_else(|_| vec![None; candles.len()]);
+    let times: Vec<NaiveDateTime> = candles.iter()
+        .map(|c| parse_timestamp(&c.timestamp).unwrap_or_default())
+        .collect();
+
+    let mut gaps = HashMap::new();
+    let mut prev_close: Option<f64> = None;
+    for (i, c) in candles.iter().enumerate() {
+        let day = times[i].date();
+        let first_of_day = i == 0 || times[i - 1].date() != day;
+        if first_of_day {
+            if let Some(pc) = prev_close.filter(|p| *p > 0.0) {
+                gaps.insert(day, (c.open - pc) / pc * 100.0);
+            }
+        }
+        prev_close = Some(c.close);
+    }
+    Series { times, rsi, regime, gaps }
+}
+
+fn context(series: &Series, i: usize, entry: NaiveDateTime, tag: Option<&str>, config: &QualityConfig) -> [Label; 6] {
+    let regime = match series.regime[i] {
+        Some(r) => (LABELS.iter().position(|l| *l == r).unwrap_or(0) as u32, r.to_string()),
+        None => (LABELS.len() as u32, "warmup".to_string()),
+    };
+
+    let rsi_value = series.rsi[i];
+    let levels = &config.rsi_levels;
+    let k = levels.iter().filter(|l| rsi_value >= **l).count();
+    let rsi_label = match (k, levels.len()) {
+        (_, 0) => "all".to_string(),
+        (0, _) => format!("<{}", levels[0]),
+        (k, n) if k == n => format!(">={}", levels[n - 1]),
+        (k, _) => format!("{}-{}", levels[k - 1], levels[k]),
+    };
+
+    let minutes = entry.hour() * 60 + entry.minute();
+    let time_of_day = if minutes == 0 && entry.second() == 0 {
+        (0, "daily".to_string())
+    } else {
+        let floored = minutes / config.time_bucket_minutes.max(1) * config.time_bucket_minutes.max(1);
+        (floored, format!("{:02}:{:02}", floored / 60, floored % 60))
+    };
+
+    let weekday = entry.weekday().num_days_from_monday();
+    let gap = match series.gaps.get(&entry.date()) {
+        Some(g) if *g > config.gap_pct => (2, "gap_up"),
+        Some(g) if *g < -config.gap_pct => (0, "gap_down"),
+        Some(_) => (1, "no_gap"),
+        None => (3, "unknown"),
+    };
+
+    [
+        regime,
+        (k as u32, rsi_label),
+        time_of_day,
+        (weekday, WEEKDAYS[weekday as usize].to_string()),
+        (gap.0, gap.1.to_string()),
+        // Untagged trades list after every tag.
+        tag.map_or((1, "untagged".to_string()), |t| (0, t.to_string())),
+    ]
+}
+
+fn stats(bucket: &str, group: &[&Outcome], base: f64) -> BucketStats {
+    let n = group.len();
+    let wins: Vec<f64> = group.iter().map(|o| o.pnl).filter(|p| *p > 0.0).collect();
+    let losses: Vec<f64> = group.iter().map(|o| o.pnl).filter(|p| *p < 0.0).collect();
+    let gross_win: f64 = wins.iter().sum();
+    let gross_loss: f64 = -losses.iter().sum::<f64>();
+    let total: f64 = group.iter().map(|o| o.pnl).sum();
+    let returns: Vec<f64> = group.iter().filter_map(|o| o.return_pct).collect();
+    let expectancy = total / n as f64;
+    BucketStats {
+        bucket: bucket.to_string(),
+        trades: n,
+        win_rate: round2(wins.len() as f64 / n as f64 * 100.0),
+        avg_win: if wins.is_empty() { 0.0 } else { round2(gross_win / wins.len() as f64) },
+        avg_loss: if losses.is_empty() { 0.0 } else { round2(-gross_loss / losses.len() as f64) },
+        expectancy: round2(expectancy),
+        total_pnl: round2(total),
+        profit_factor: (gross_loss > 0.0).then(|| round2(gross_win / gross_loss)),
+        avg_return_pct: (!returns.is_empty()).then(|| round2(returns.iter().sum::<f64>() / returns.len() as f64)),
+        edge: round2(expectancy - base),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Two 15-minute bars a day for 30 weekdays; every fifth session gaps up 1%.
+    fn candles() -> Vec<Value> {
+        let mut out = Vec::new();
+        let mut price = 100.0;
+        let mut day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        for d in 0..30 {
+            while day.weekday().number_from_monday() > 5 {
+                day = day.succ_opt().unwrap();
+            }
+            let open = if d % 5 == 4 { price * 1.01 } else { price };
+            for (k, hm) in ["09:15", "14:00"].iter().enumerate() {
+                let close = open + if k == 0 { 0.5 } else { -0.2 };
+                out.push(json!({ "timestamp": format!("{} {}:00", day, hm), "open": open, "high": open + 1.0, "low": open - 1.0, "close": close, "volume": 1000.0 }));
+                price = close;
+            }
+            day = day.succ_opt().unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_buckets_and_highlights() {
+        // Morning entries win 100, afternoon entries lose 40.
+        let trades: Vec<Value> = (0..20).map(|d| {
+            let day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + chrono::Duration::days(d);
+            let morning = d % 2 == 0;
+            let mut t = json!({ "entry_time": format!("{} {}", day, if morning { "09:30:00" } else { "14:20:00" }), "pnl": if morning { 100.0 } else { -40.0 }, "entry_price": 100.0, "qty": 10 });
+            if morning {
+                t["strategy_tag"] = json!("orb");
+            }
+            t
+        }).collect();
+        let result = compute(json!({ "trades": trades, "candles": candles(), "min_trades": 3 })).unwrap();
+        assert_eq!(result["matched_trades"], 20);
+        assert_eq!(result["overall"]["expectancy"], 30.0);
+        let tod = result["dimensions"]["time_of_day"].as_array().unwrap();
+        assert_eq!(tod[0]["bucket"], "09:00");
+        assert_eq!(tod[0]["expectancy"], 100.0);
+        assert_eq!(tod[0]["avg_return_pct"], 10.0);
+        assert_eq!(tod[1]["bucket"], "14:00");
+        assert_eq!(tod[1]["edge"], -70.0);
+        let dow: Vec<&str> = result["dimensions"]["day_of_week"].as_array().unwrap().iter().map(|b| b["bucket"].as_str().unwrap()).collect();
+        assert_eq!(&dow[..3], &["Mon", "Tue", "Wed"]);
+        let works = result["highlights"]["works"].as_array().unwrap();
+        assert!(works.iter().any(|h| h["dimension"] == "time_of_day" && h["bucket"] == "09:00"));
+        assert!(result["highlights"]["avoid"].as_array().unwrap().iter().any(|h| h["bucket"] == "14:00"));
+        assert!(result["dimensions"]["gap"].as_array().unwrap().iter().any(|b| b["bucket"] == "gap_up"));
+        let tags = result["dimensions"]["strategy_tag"].as_array().unwrap();
+        assert_eq!((&tags[0]["bucket"], &tags[0]["expectancy"]), (&json!("orb"), &json!(100.0)));
+        assert_eq!((&tags[1]["bucket"], &tags[1]["trades"]), (&json!("untagged"), &json!(10)));
+    }
+
+    #[test]
+    fn test_context_bar_and_combine() {
+        let trades = json!([
+            { "entry_time": "2024-01-01 09:15:00", "pnl": 10.0 },
+            { "entry_time": "2024-01-02 09:15:00", "pnl": -5.0 }
+        ]);
+        let prior = compute(json!({ "trades": trades, "candles": candles() })).unwrap();
+        // The first bar has nothing before it.
+        assert_eq!(prior["unmatched_trades"], 1);
+        let entry = compute(json!({ "trades": trades, "candles": candles(), "context_bar": "entry", "combine": [["gap", "day_of_week"]] })).unwrap();
+        assert_eq!(entry["matched_trades"], 2);
+        let combo = entry["dimensions"]["gap+day_of_week"].as_array().unwrap();
+        assert_eq!(combo[0]["bucket"], "no_gap | Tue");
+        assert_eq!(combo[1]["bucket"], "unknown | Mon");
+        assert!(compute(json!({ "trades": trades, "candles": candles(), "combine": [["gap", "moon"]] })).is_err());
+    }
+}
@@ -0,0 +1,541 @@
+//! Replay of an externally produced trade log against candle data.
+//!
+//! Fills come inline as `trades` or from `trades_file` (a broker tradebook
+//! export in CSV or JSON; common column names such as `tradingsymbol`,
+//! `transaction_type`, `quantity` and `order_execution_time` are recognised).
+//! Each fill is booked on the bar it happened in — the last bar at or before
+//! its timestamp, or the first bar for earlier fills — at the fill price and
+//! fees, and open positions are marked at every bar's close. The equity
+//! curve and FIFO round trips are scored with the backtester's own
+//! `performance`, and the fills run through `pnl_attribution`, so live
+//! results and backtests of the same strategy are directly comparable.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::backtest::{performance, EquityPoint, TradeEntry};
+use crate::calendar::{Calendar, CalendarSpec};
+use crate::utils::{parse_timestamp, round2, round4, Candle};
+
+#[derive(Deserialize)]
+struct ReplayConfig {
+    #[serde(default)]
+    trades: Vec<Fill>,
+    trades_file: Option<TradesFileSpec>,
+    /// Per-symbol candles; `symbol` + `candles` is shorthand for one entry.
+    #[serde(default)]
+    symbols: Vec<SymbolCandles>,
+    symbol: Option<String>,
+    #[serde(default)]
+    candles: Vec<Candle>,
+    initial_capital: f64,
+    bars_per_day: Option<f64>,
+    #[serde(default)]
+    calendar: Option<CalendarSpec>,
+    /// Lot matching for the attribution breakdown: "fifo" or "average".
+    #[serde(default = "default_method")]
+    method: String,
+}
+
+fn default_method() -> String { "fifo".to_string() }
+
+#[derive(Deserialize)]
+struct SymbolCandles {
+    symbol: String,
+    candles: Vec<Candle>,
+}
+
+/// One execution, in the same shape `pnl_attribution` takes.
+#[derive(Deserialize, Serialize, Clone)]
+struct Fill {
+    #[serde(alias = "tradingsymbol")]
+    symbol: String,
+    #[serde(alias = "transaction_type", alias = "trade_type")]
+    side: String,
+    #[serde(alias = "quantity")]
+    qty: f64,
+    #[serde(alias = "average_price", alias = "trade_price")]
+    price: f64,
+    #[serde(default, alias = "charges")]
+    fees: f64,
+    #[serde(default, alias = "time", alias = "date", alias = "order_execution_time", alias = "trade_date")]
+    timestamp: Option<String>,
+    #[serde(default, alias = "tag", alias = "strategy_tag")]
+    strategy: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TradesFileSpec {
+    path: String,
+    /// "csv" or "json"; inferred from the extension when omitted.
+    format: Option<String>,
+    /// Fill field → source column. Unmapped fields fall back to the
+    /// common broker headers in [`COLUMN_CANDIDATES`].
+    #[serde(default)]
+    columns: HashMap<String, String>,
+    /// CSV field delimiter, single character.
+    #[serde(default = "default_delimiter")]
+    delimiter: String,
+}
+
+fn default_delimiter() -> String { ",".to_string() }
+
+/// Header names tried, case-insensitively, for each fill field.
+const COLUMN_CANDIDATES: [(&str, &[&str]); 7] = [
+    ("symbol", &["symbol", "tradingsymbol", "trading_symbol", "instrument", "scrip"]),
+    ("side", &["side", "transaction_type", "trade_type", "buy/sell", "action"]),
+    ("qty", &["qty", "quantity", "filled_quantity", "trade_qty"]),
+    ("price", &["price", "average_price", "trade_price", "avg_price"]),
+    ("fees", &["fees", "charges", "total_charges", "brokerage"]),
+    ("timestamp", &["timestamp", "order_execution_time", "trade_time", "time", "trade_date", "date"]),
+    ("strategy", &["strategy", "tag", "strategy_tag"]),
+];
+
+/// An open lot; `fee` is the entry fee per unit, charged on close.
+struct Lot {
+    /// Becomes the `trade_id` of every exit that closes part of it.
+    id: usize,
+    qty: f64,
+    price: f64,
+    fee: f64,
+    time: String,
+    strategy: Option<String>,
+}
+
+/// Open lots for one symbol; `dir` is 1 long, -1 short, 0 flat.
+#[derive(Default)]
+struct Book {
+    dir: f64,
+    lots: VecDeque<Lot>,
+}
+
+impl Book {
+    fn net_qty(&self) -> f64 {
+        self.dir * self.lots.iter().map(|l| l.qty).sum::<f64>()
+    }
+}
+
+/// A timeline entry: the bar's original timestamp and each symbol's close.
+type Bar<'a> = (String, Vec<(&'a str, f64)>);
+
+#[derive(Serialize)]
+struct ReplayResult {
+    cagr: f64,
+    max_drawdown: f64,
+    sharpe_ratio: f64,
+    sortino_ratio: f64,
+    win_rate: f64,
+    profit_factor: f64,
+    /// Closed round trips, FIFO-matched.
+    total_trades: usize,
+    avg_win: f64,
+    avg_loss: f64,
+    total_costs: f64,
+    cost_drag_pct: f64,
+    trading_days_per_year: f64,
+    final_nav: f64,
+    fills: usize,
+    /// Fills timestamped before the first bar, booked on it.
+    fills_before_data: usize,
+    /// Signed quantity still open after the last fill, marked at the last close.
+    open_positions: BTreeMap<String, f64>,
+    equity_curve: Vec<EquityPoint>,
+    trade_log: Vec<TradeEntry>,
+    /// The `pnl_attribution` result for the same fills.
+    attribution: Value,
+}
+
+fn side_sign(side: &str) -> Option<f64> {
+    match side.trim().to_lowercase().as_str() {
+        "buy" | "b" | "long" => Some(1.0),
+        "sell" | "s" | "short" => Some(-1.0),
+        _ => None,
+    }
+}
+
+pub fn compute(data: Value) -> Result<Value, String> {
+    let mut config: ReplayConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid replay_trades config: {}", e))?;
+    if let Some(spec) = config.trades_file.take() {
+        config.trades.extend(load_trades(&spec)?);
+    }
+    if config.trades.is_empty() {
+        return Err("replay_trades requires 'trades' or 'trades_file'".to_string());
+    }
+    if !(config.initial_capital.is_finite() && config.initial_capital > 0.0) {
+        return Err("initial_capital must be positive".to_string());
+    }
+    let calendar = Calendar::from_spec(config.calendar.as_ref())?;
+    let bars_per_day = config.bars_per_day.unwrap_or(1.0).max(1.0);
+
+    let mut series: BTreeMap<String, Vec<Candle>> =
+        config.symbols.into_iter().map(|s| (s.symbol, s.candles)).collect();
+    if !config.candles.is_empty() {
+        let traded: BTreeSet<&str> = config.trades.iter().map(|t| t.symbol.as_str()).collect();
+        let symbol = match (config.symbol, traded.len()) {
+            (Some(s), _) => s,
+            (None, 1) => traded.into_iter().next().unwrap_or_default().to_string(),
+            (None, _) => return Err("'candles' without 'symbol' needs a single-symbol trade log".to_string()),
+        };
+        series.insert(symbol, config.candles);
+    }
+
+    // Validate fills up front so a bad row is reported by its position in
+    // the log, then order them by time.
+    let mut fills = Vec::with_capacity(config.trades.len());
+    let mut missing: BTreeSet<String> = BTreeSet::new();
+    for (i, t) in config.trades.iter().enumerate() {
+        let sign = side_sign(&t.side)
+            .ok_or_else(|| format!("trade {}: unknown side '{}' (buy, sell)", i, t.side))?;
+        if !(t.qty.is_finite() && t.qty > 0.0 && t.price.is_finite() && t.price > 0.0) {
+            return Err(format!("trade {}: qty and price must be positive", i));
+        }
+        let time = t.timestamp.as_deref().and_then(parse_timestamp)
+            .ok_or_else(|| format!("trade {}: missing or unparseable timestamp", i))?;
+        if !series.contains_key(&t.symbol) {
+            missing.insert(t.symbol.clone());
+        }
+        fills.push((time, sign, t));
+    }
+    if !missing.is_empty() {
+        return Err(format!("No candles for traded symbols: {}", missing.into_iter().collect::<Vec<_>>().join(", ")));
+    }
+    fills.sort_by_key(|f| f.0);
+
+    // Union of bar times across symbols, each with its closes.
+    let mut timeline: BTreeMap<chrono::NaiveDateTime, Bar> = BTreeMap::new();
+    for (symbol, candles) in &series {
+        for c in candles {
+            let t = parse_timestamp(&c.timestamp)
+                .ok_or_else(|| format!("{}: unparseable candle timestamp '{}'", symbol, c.timestamp))?;
+            timeline.entry(t).or_insert_with(|| (c.timestamp.clone(), Vec::new())).1.push((symbol.as_str(), c.close));
+        }
+    }
+    if timeline.is_empty() {
+        return Err("replay_trades requires candles for the traded symbols".to_string());
+    }
+
+    let mut cash = config.initial_capital;
+    let mut marks: HashMap<&str, f64> = HashMap::new();
+    let mut books: BTreeMap<String, Book> = BTreeMap::new();
+    let mut trade_log: Vec<TradeEntry> = Vec::new();
+    let mut equity_curve: Vec<EquityPoint> = Vec::with_capacity(timeline.len());
+    let mut total_costs = 0.0_f64;
+    let mut fills_before_data = 0usize;
+    let mut peak = config.initial_capital;
+    let mut max_dd = 0.0_f64;
+    let mut nav = cash;
+
+    let times: Vec<chrono::NaiveDateTime> = timeline.keys().copied().collect();
+    let mut next_fill = 0usize;
+    let mut next_lot_id = 0usize;
+    for (bar, (time, (label, closes))) in timeline.iter().enumerate() {
+        for (symbol, close) in closes {
+            marks.insert(symbol, *close);
+        }
+        // Fills up to (not including) the next bar belong to this one.
+        while let Some(&(ft, sign, t)) = fills.get(next_fill) {
+            if times.get(bar + 1).is_some_and(|next| ft >= *next) {
+                break;
+            }
+            if ft < *time {
+                fills_before_data += 1;
+            }
+            let exit_time = t.timestamp.clone().unwrap_or_default();
+            let fee = t.fees / t.qty;
+            let book = books.entry(t.symbol.clone()).or_default();
+            let mut remaining = t.qty;
+            while remaining > 1e-12 && book.dir == -sign {
+                let Some(lot) = book.lots.front_mut() else { break };
+                let q = remaining.min(lot.qty);
+                let gross = book.dir * q * (t.price - lot.price);
+                let costs = q * (lot.fee + fee);
+                trade_log.push(TradeEntry {
+                    symbol: t.symbol.clone(),
+                    side: if book.dir > 0.0 { "LONG" } else { "SHORT" }.into(),
+                    entry_price: round4(lot.price),
+                    exit_price: t.price,
+                    qty: q.round() as i64,
+                    pnl: round2(gross - costs),
+                    gross_pnl: round2(gross),
+                    costs: round2(costs),
+                    entry_time: lot.time.clone(),
+                    exit_time: exit_time.clone(),
+                    trade_id: lot.id,
+                    event: None,
+                    strategy_tag: lot.strategy.clone(),
+                });
+                lot.qty -= q;
+                remaining -= q;
+                if lot.qty <= 1e-12 {
+                    book.lots.pop_front();
+                }
+                if book.lots.is_empty() {
+                    book.dir = 0.0;
+                }
+            }
+            if remaining > 1e-12 {
+                book.dir = sign;
+                next_lot_id += 1;
+                book.lots.push_back(Lot { id: next_lot_id, qty: remaining, price: t.price, fee, time: exit_time, strategy: t.strategy.clone() });
+            }
+            cash -= sign * t.qty * t.price + t.fees;
+            total_costs += t.fees;
+            // A symbol traded before its first bar is marked at the fill.
+            marks.entry(t.symbol.as_str()).or_insert(t.price);
+            next_fill += 1;
+        }
+
+        nav = cash + books.iter()
+            .map(|(symbol, book)| book.net_qty() * marks.get(symbol.as_str()).copied().unwrap_or(0.0))
+            .sum::<f64>();
+        equity_curve.push(EquityPoint { date: label.clone(), nav: round2(nav) });
+        if nav > peak { peak = nav; }
+        let dd = if peak > 0.0 { (peak - nav) / peak } else { 0.0 };
+        if dd > max_dd { max_dd = dd; }
+    }
+
+    let span = times.first().zip(times.last()).map(|(a, b)| (a.date(), b.date()));
+    let perf = performance(&equity_curve, &trade_log, config.initial_capital, nav, span, &calendar, bars_per_day);
+
+    let prices: BTreeMap<&str, f64> = marks.iter().map(|(s, p)| (*s, *p)).collect();
+    let attribution = crate::pnl_attribution::compute(serde_json::json!({
+        "trades": fills.iter().map(|f| f.2).collect::<Vec<_>>(),
+        "prices": prices,
+        "method": config.method,
+    }))?;
+
+    let result = ReplayResult {
+        cagr: round2(perf.cagr),
+        max_drawdown: round2(max_dd * 100.0),
+        sharpe_ratio: round2(perf.sharpe),
+        sortino_ratio: round2(perf.sortino),
+        win_rate: round2(perf.win_rate),
+        profit_factor: round2(perf.profit_factor),
+        total_trades: perf.trades,
+        avg_win: round2(perf.avg_win),
+        avg_loss: round2(perf.avg_loss),
+        total_costs: round2(total_costs),
+        cost_drag_pct: round2(total_costs / config.initial_capital * 100.0),
+        trading_days_per_year: round2(perf.trading_days),
+        final_nav: round2(nav),
+        fills: fills.len(),
+        fills_before_data,
+        open_positions: books.iter()
+            .filter(|(_, b)| !b.lots.is_empty())
+            .map(|(s, b)| (s.clone(), b.net_qty()))
+            .collect(),
+        equity_curve,
+        trade_log,
+        attribution,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn load_trades(spec: &TradesFileSpec) -> Result<Vec<Fill>, String> {
+    let format = match &spec.format {
+        Some(f) => f.to_lowercase(),
+        None => std::path::Path::new(&spec.path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_else(|| "csv".to_string()),
+    };
+    let rows = match format.as_str() {
+        "csv" | "txt" => read_csv(spec)?,
+        "json" => read_json(spec)?,
+        other => return Err(format!("Unsupported trades_file format: {}", other)),
+    };
+    rows.into_iter().enumerate()
+        .map(|(i, row)| serde_json::from_value(Value::Object(row))
+            .map_err(|e| format!("{} row {}: {}", spec.path, i + 1, e)))
+        .collect()
+}
+
+/// Source column for each fill field present in `header`.
+fn resolve_columns(header: &[String], spec: &TradesFileSpec) -> Result<Vec<(&'static str, usize)>, String> {
+    let find = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let mut out = Vec::new();
+    for (field, candidates) in COLUMN_CANDIDATES {
+        let pos = match spec.columns.get(field) {
+            Some(name) => Some(find(name).ok_or_else(|| format!("trades_file column '{}' not found", name))?),
+            None => candidates.iter().find_map(|c| find(c)),
+        };
+        match pos {
+            Some(p) => out.push((field, p)),
+            None if matches!(field, "symbol" | "side" | "qty" | "price") => {
+                return Err(format!("trades_file has no '{}' column", field));
+            }
+            None => {}
+        }
+    }
+    Ok(out)
+}
+
+fn read_csv(spec: &TradesFileSpec) -> Result<Vec<Map<String, Value>>, String> {
+    let delimiter = match spec.delimiter.as_bytes() {
+        [b] => *b,
+        _ => return Err("delimiter must be a single character".to_string()),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(&spec.path)
+        .map_err(|e| format!("Failed to open {}: {}", spec.path, e))?;
+    let header: Vec<String> = reader.headers()
+        .map_err(|e| format!("CSV error in {}: {}", spec.path, e))?
+        .iter().map(|h| h.to_string()).collect();
+    let columns = resolve_columns(&header, spec)?;
+
+    let mut rows = Vec::new();
+    for (row, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("CSV error in {}: {}", spec.path, e))?;
+        let mut out = Map::new();
+        for &(field, pos) in &columns {
+            let raw = record.get(pos).unwrap_or("");
+            let value = match field {
+                "qty" | "price" | "fees" => {
+                    if raw.is_empty() && field == "fees" { continue; }
+                    let n = raw.replace(',', "").parse::<f64>().map_err(|_| {
+                        format!("{} row {}: invalid {} value '{}'", spec.path, row + 1, field, raw)
+                    })?;
+                    Value::from(n)
+                }
+                _ if raw.is_empty() => continue,
+                _ => Value::from(raw),
+            };
+            out.insert(field.to_string(), value);
+        }
+        rows.push(out);
+    }
+    Ok(rows)
+}
+
+/// A JSON array of fills, or an object wrapping one under `data` or
+/// `trades` as broker APIs return it. Column overrides rename keys.
+fn read_json(spec: &TradesFileSpec) -> Result<Vec<Map<String, Value>>, String> {
+    let text = std::fs::read_to_string(&spec.path)
+        .map_err(|e| format!("Failed to open {}: {}", spec.path, e))?;
+    let value: Value = serde_json::from_str(&text)
+        .map_err(|e| format!("JSON error in {}: {}", spec.path, e))?;
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(mut map) => match map.remove("data").or_else(|| map.remove("trades")) {
+            Some(Value::Array(items)) => items,
+            _ => return Err(format!("{}: expected an array of trades", spec.path)),
+        },
+        _ => return Err(format!("{}: expected an array of trades", spec.path)),
+    };
+    items.into_iter().enumerate()
+        .map(|(i, item)| match item {
+            Value::Object(mut row) => {
+                for (field, source) in &spec.columns {
+                    if let Some(v) = row.remove(source) {
+                        row.insert(field.clone(), v);
+                    }
+                }
+                Ok(row)
+            }
+            _ => Err(format!("{} row {}: expected an object", spec.path, i + 1)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candles() -> Value {
+        json!([
+            { "timestamp": "2024-01-01", "open": 100.0, "high": 101.0, "low": 99.0, "close": 100.0, "volume": 1000.0 },
+            { "timestamp": "2024-01-02", "open": 100.0, "high": 106.0, "low": 99.0, "close": 105.0, "volume": 1000.0 },
+            { "timestamp": "2024-01-03", "open": 105.0, "high": 111.0, "low": 104.0, "close": 110.0, "volume": 1000.0 },
+            { "timestamp": "2024-01-04", "open": 110.0, "high": 111.0, "low": 106.0, "close": 108.0, "volume": 1000.0 }
+        ])
+    }
+
+    fn write_tmp(name: &str, body: &str) -> String {
+        let path = std::env::temp_dir().join(format!("cg_trade_replay_{}_{}", std::process::id(), name));
+        std::fs::write(&path, body).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_replay_books_fills_on_their_bar() {
+        let result = compute(json!({
+            "initial_capital": 10_000.0,
+            "symbol": "INFY",
+            "candles": candles(),
+            "trades": [
+                { "symbol": "INFY", "side": "BUY", "qty": 10, "price": 101.0, "fees": 2.0, "timestamp": "2024-01-01T10:15:00" },
+                { "symbol": "INFY", "side": "SELL", "qty": 10, "price": 109.0, "fees": 2.0, "timestamp": "2024-01-03T14:00:00", "strategy": "breakout" }
+            ]
+        })).unwrap();
+        let curve = result["equity_curve"].as_array().unwrap();
+        assert_eq!(curve.len(), 4);
+        // Day 1: bought at 101 (+2 fees), marked at 100.
+        assert_eq!(curve[0]["nav"], 9988.0);
+        assert_eq!(curve[1]["nav"], 10038.0);
+        // Flat from day 3: 80 gross − 4 fees.
+        assert_eq!(curve[2]["nav"], 10076.0);
+        assert_eq!(curve[3]["nav"], 10076.0);
+        assert_eq!(result["total_trades"], 1);
+        assert_eq!(result["trade_log"][0]["pnl"], 76.0);
+        assert_eq!(result["trade_log"][0]["costs"], 4.0);
+        assert_eq!(result["win_rate"], 100.0);
+        assert_eq!(result["final_nav"], 10076.0);
+        assert_eq!(result["attribution"]["total"]["net"], 76.0);
+        assert!(result["open_positions"].as_object().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_broker_csv_and_open_short() {
+        let path = write_tmp("tradebook.csv",
+            "tradingsymbol,trade_type,quantity,price,order_execution_time\n\
+             TCS,sell,5,200,2024-01-02 09:20:00\n\
+             TCS,buy,2,190,2024-01-03 11:00:00\n\
+             INFY,buy,1,99,2023-12-29 15:00:00\n");
+        let result = compute(json!({
+            "initial_capital": 5_000.0,
+            "trades_file": { "path": path },
+            "symbols": [
+                { "symbol": "TCS", "candles": [
+                    { "timestamp": "2024-01-02", "high": 201.0, "low": 195.0, "close": 198.0, "volume": 1.0 },
+                    { "timestamp": "2024-01-03", "high": 199.0, "low": 189.0, "close": 192.0, "volume": 1.0 }
+                ] },
+                { "symbol": "INFY", "candles": candles() }
+            ]
+        })).unwrap();
+        assert_eq!(result["fills"], 3);
+        assert_eq!(result["fills_before_data"], 1);
+        assert_eq!(result["trade_log"][0]["side"], "SHORT");
+        assert_eq!(result["trade_log"][0]["pnl"], 20.0);
+        assert_eq!(result["open_positions"]["TCS"], -3.0);
+        assert_eq!(result["open_positions"]["INFY"], 1.0);
+        // 5000 + 1000 − 380 − 99 − 3 × 192 + 108.
+        assert_eq!(result["final_nav"], 5053.0);
+        assert_eq!(result["attribution"]["by_symbol"]["TCS"]["realized"], 20.0);
+    }
+
+    #[test]
+    fn test_json_file_and_errors() {
+        let path = write_tmp("fills.json", &json!({ "data": [
+            { "tradingsymbol": "INFY", "transaction_type": "BUY", "quantity": 3, "average_price": 100.0, "fill_time": "2024-01-02" }
+        ] }).to_string());
+        let result = compute(json!({
+            "initial_capital": 1_000.0,
+            "trades_file": { "path": path, "columns": { "timestamp": "fill_time" } },
+            "candles": candles()
+        })).unwrap();
+        assert_eq!(result["final_nav"], 1024.0);
+
+        let trade = json!({ "symbol": "TCS", "side": "buy", "qty": 1, "price": 10.0, "timestamp": "2024-01-02" });
+        let err = compute(json!({ "initial_capital": 1_000.0, "trades": [trade], "symbols": [{ "symbol": "INFY", "candles": candles() }] })).unwrap_err();
+        assert!(err.contains("TCS"));
+        let undated = json!({ "symbol": "INFY", "side": "buy", "qty": 1, "price": 10.0 });
+        assert!(compute(json!({ "initial_capital": 1_000.0, "trades": [undated], "candles": candles() })).is_err());
+        assert!(compute(json!({ "initial_capital": 1_000.0, "candles": candles() })).is_err());
+    }
+}
@@ -53,6 +53,47 @@ pub fn sanitize_candles(candles: &mut [Candle]) -> usize {
     repaired
 }
 
+/// Parse a candle timestamp into a wall-clock datetime. Accepts RFC3339 (offset
+/// kept as local time), "YYYY-MM-DD[T ]HH:MM[:SS]", a bare date (midnight), and
+/// epoch seconds or milliseconds (UTC).
+pub fn parse_timestamp(ts: &str) -> Option<chrono::NaiveDateTime> {
+    let ts = ts.trim();
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(ts) {
+        return Some(dt.naive_local());
+    }
+    for fmt in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(ts, fmt) {
+            return Some(dt);
+        }
+    }
+    if let Ok(d) = chrono::NaiveDate::parse_from_str(ts, "%Y-%m-%d") {
+        return d.and_hms_opt(0, 0, 0);
+    }
+    if let Ok(n) = ts.parse::<i64>() {
+        let secs = if n.abs() >= 100_000_000_000 { n / 1000 } else { n };
+        return chrono::DateTime::from_timestamp(secs, 0).map(|d| d.naive_utc());
+    }
+    None
+}
+
+/// Marks the first bar of each trading day in an intraday series, so
+/// session-anchored indicators (VWAP) can reset. Dates come from the
+/// timestamp's own wall clock, which is exchange-local once the request has
+/// been localized. Daily or undated series have no intraday sessions and
+/// yield all `false`.
+pub fn session_starts(candles: &[Candle]) -> Vec<bool> {
+    let dates: Vec<Option<chrono::NaiveDate>> =
+        candles.iter().map(|c| parse_timestamp(&c.timestamp).map(|t| t.date())).collect();
+    let intraday = dates.windows(2).any(|w| w[0].is_some() && w[0] == w[1]);
+    let mut starts = vec![false; candles.len()];
+    if intraday {
+        for i in 1..dates.len() {
+            starts[i] = dates[i].is_some() && dates[i] != dates[i - 1];
+        }
+    }
+    starts
+}
+
 pub fn round2(v: f64) -> f64 {
     (v * 100.0).round() / 100.0
 }
@@ -65,6 +106,28 @@ pub fn round4(v: f64) -> f64 {
     (v * 10000.0).round() / 10000.0
 }
 
+/// Round every fractional number in `value` to `decimals` places, in place.
+/// Integers are left alone and -0.0 becomes 0.0 so diffs stay stable.
+/// Beyond 15 places f64 has no digits left to round, so that is a no-op.
+pub fn round_json(value: &mut serde_json::Value, decimals: u32) {
+    use serde_json::Value;
+    match value {
+        Value::Number(n) if n.is_f64() && decimals <= 15 => {
+            if let Some(x) = n.as_f64() {
+                let scale = 10f64.powi(decimals as i32);
+                let rounded = (x * scale).round() / scale;
+                let rounded = if rounded == 0.0 { 0.0 } else { rounded };
+                if let Some(r) = serde_json::Number::from_f64(rounded) {
+                    *n = r;
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| round_json(v, decimals)),
+        Value::Object(map) => map.values_mut().for_each(|v| round_json(v, decimals)),
+        _ => {}
+    }
+}
+
 pub fn norm_cdf(x: f64) -> f64 {
     0.5 * (1.0 + erf(x / SQRT_2))
 }
@@ -192,16 +255,7 @@ pub fn rolling_std(data: &[f64]) -> f64 {
 }
 
 pub fn calc_ema_series(data: &[f64], period: usize) -> Vec<f64> {
-    if data.len() < period {
-        return vec![f64::NAN; data.len()];
-    }
-    let mut result = vec![f64::NAN; data.len()];
-    let mult = 2.0 / (period as f64 + 1.0);
-    result[period - 1] = data[..period].iter().sum::<f64>() / period as f64;
-    for i in period..data.len() {
-        result[i] = (data[i] - result[i - 1]) * mult + result[i - 1];
-    }
-    result
+    crate::kernels::ema(data, period)
 }
 
 pub fn calc_ema_last(data: &[f64], period: usize) -> f64 {
@@ -218,56 +272,11 @@ pub fn calc_ema_last(data: &[f64], period: usize) -> f64 {
 }
 
 pub fn calc_rsi_series(data: &[f64], period: usize) -> Vec<f64> {
-    if data.len() < period + 1 {
-        return vec![50.0; data.len()];
-    }
-    let mut result = vec![50.0; data.len()];
-    let mut avg_gain = 0.0;
-    let mut avg_loss = 0.0;
-
-    for i in 1..=period {
-        let diff = data[i] - data[i - 1];
-        if diff > 0.0 {
-            avg_gain += diff;
-        } else {
-            avg_loss -= diff;
-        }
-    }
-    avg_gain /= period as f64;
-    avg_loss /= period as f64;
-
-    result[period] = if avg_loss == 0.0 {
-        100.0
-    } else {
-        100.0 - 100.0 / (1.0 + avg_gain / avg_loss)
-    };
-
-    for i in period + 1..data.len() {
-        let diff = data[i] - data[i - 1];
-        let (gain, loss) = if diff > 0.0 {
-            (diff, 0.0)
-        } else {
-            (0.0, -diff)
-        };
-        avg_gain = (avg_gain * (period as f64 - 1.0) + gain) / period as f64;
-        avg_loss = (avg_loss * (period as f64 - 1.0) + loss) / period as f64;
-        result[i] = if avg_loss == 0.0 {
-            100.0
-        } else {
-            100.0 - 100.0 / (1.0 + avg_gain / avg_loss)
-        };
-    }
-    result
+    crate::kernels::rsi(data, period)
 }
 
 pub fn calc_sma(data: &[f64], period: usize) -> Vec<f64> {
-    if period == 0 || data.is_empty() { return vec![0.0; data.len()]; }
-    let mut result = vec![0.0; data.len()];
-    for i in (period - 1)..data.len() {
-        let sum: f64 = data[i + 1 - period..=i].iter().sum();
-        result[i] = sum / period as f64;
-    }
-    result
+    crate::kernels::sma(data, period)
 }
 
 pub fn calc_atr_series(
@@ -276,24 +285,7 @@ pub fn calc_atr_series(
     closes: &[f64],
     period: usize,
 ) -> Vec<f64> {
-    let n = closes.len();
-    let mut tr = vec![0.0; n];
-    let mut atr = vec![0.0; n];
-
-    tr[0] = highs[0] - lows[0];
-    for i in 1..n {
-        tr[i] = (highs[i] - lows[i])
-            .max((highs[i] - closes[i - 1]).abs())
-            .max((lows[i] - closes[i - 1]).abs());
-    }
-
-    if n >= period {
-        atr[period - 1] = tr[..period].iter().sum::<f64>() / period as f64;
-        for i in period..n {
-            atr[i] = (atr[i - 1] * (period as f64 - 1.0) + tr[i]) / period as f64;
-        }
-    }
-    atr
+    crate::kernels::atr(highs, lows, closes, period)
 }
 
 pub fn pearson_correlation(a: &[f64], b: &[f64]) -> f64 {
@@ -598,10 +590,55 @@ impl RiskLimits {
     }
 }
 
+/// Synthetic series shared by the command tests.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use serde_json::{json, Value};
+
+    /// Day `i` of a calendar of 28-day months from 2025-01-01 (valid for i < 336).
+    pub(crate) fn grid_date(i: usize) -> String {
+        format!("2025-{:02}-{:02}", 1 + i / 28, 1 + i % 28)
+    }
+
+    /// `n` daily bars closing at `1000 + 60·sin(0.15·i + phase) + drift·i`,
+    /// with high and low `band` (a fraction) either side and 50k volume.
+    pub(crate) fn sine_candles(n: usize, phase: f64, drift: f64, band: f64) -> Vec<Value> {
+        (0..n).map(|i| {
+            let close = 1_000.0 + 60.0 * (i as f64 * 0.15 + phase).sin() + drift * i as f64;
+            json!({ "timestamp": grid_date(i), "open": close, "high": close * (1.0 + band), "low": close * (1.0 - band),
+                "close": close, "volume": 50_000 })
+        }).collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_round_json() {
+        let mut v = serde_json::json!({ "a": 1.23456, "b": [2.5, -0.00001, 7], "c": { "d": 10.0 } });
+        round_json(&mut v, 2);
+        assert_eq!(v["a"].as_f64().unwrap(), 1.23);
+        assert_eq!(v["b"][0].as_f64().unwrap(), 2.5);
+        assert_eq!(serde_json::to_string(&v["b"][1]).unwrap(), "0.0");
+        assert!(v["b"][2].is_u64());
+        assert_eq!(v["c"]["d"].as_f64().unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_parse_timestamp_formats() {
+        let expect = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(9, 15, 0).unwrap();
+        assert_eq!(parse_timestamp("2024-01-02T09:15:00"), Some(expect));
+        assert_eq!(parse_timestamp("2024-01-02 09:15:00"), Some(expect));
+        assert_eq!(parse_timestamp("2024-01-02T09:15"), Some(expect));
+        assert_eq!(parse_timestamp("2024-01-02T09:15:00+05:30"), Some(expect));
+        assert_eq!(parse_timestamp("1704186900"), Some(expect));
+        assert_eq!(parse_timestamp("1704186900000"), Some(expect));
+        assert!(parse_timestamp("2024-01-02").is_some());
+        assert!(parse_timestamp("not a time").is_none());
+    }
+
     #[test]
     fn test_norm_cdf_known_values() {
         assert!((norm_cdf(0.0) - 0.5).abs() < 0.001);
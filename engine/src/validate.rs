@@ -0,0 +1,337 @@
+//! Dry-run validation: checks a command payload without executing it and
+//! reports every problem found, not just the first.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::candle_file;
+use crate::config::LimitsConfig;
+use crate::datasets;
+use crate::guardrails;
+
+#[derive(Deserialize)]
+struct ValidateInput {
+    command: String,
+    #[serde(default)]
+    data: Value,
+}
+
+#[derive(Serialize)]
+struct ValidateResult {
+    command: String,
+    valid: bool,
+    errors: usize,
+    warnings: usize,
+    problems: Vec<Problem>,
+}
+
+#[derive(Serialize)]
+struct Problem {
+    severity: &'static str,
+    /// JSON path into the payload, e.g. "candles[12].high".
+    path: String,
+    message: String,
+}
+
+/// Bars needed before RSI/ATR style indicators settle.
+const MIN_WARMUP_BARS: usize = 30;
+
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.0.push(Problem { severity: "error", path: path.into(), message: message.into() });
+    }
+    fn warn(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.0.push(Problem { severity: "warning", path: path.into(), message: message.into() });
+    }
+}
+
+pub fn compute(data: Value, limits: &LimitsConfig) -> Result<Value, String> {
+    let input: ValidateInput =
+        serde_json::from_value(data).map_err(|e| format!("Invalid validate input: {}", e))?;
+    let mut problems = Problems::default();
+    let mut payload = input.data;
+
+    if !payload.is_object() {
+        problems.error("data", "payload must be a JSON object");
+    } else {
+        if let Err(e) = candle_file::resolve(&mut payload) {
+            problems.error("candles_file", e);
+        }
+        if let Err(e) = datasets::resolve(&mut payload) {
+            problems.error("dataset_id", e);
+        }
+        if let Err(e) = guardrails::apply(&mut payload.clone(), limits, false) {
+            problems.error(e.path.clone(), e.message());
+        }
+        check_required(&input.command, &payload, &mut problems);
+        check_all_candles(&input.command, &payload, &mut problems);
+        check_param_grid(&payload, &mut problems);
+    }
+
+    let errors = problems.0.iter().filter(|p| p.severity == "error").count();
+    let result = ValidateResult {
+        command: input.command,
+        valid: errors == 0,
+        errors,
+        warnings: problems.0.len() - errors,
+        problems: problems.0,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn check_required(command: &str, data: &Value, problems: &mut Problems) {
+    let required: &[&str] = match command {
+        "backtest" => &["strategy", "symbol", "initial_capital", "candles"],
+        "portfolio_backtest" => &["strategy", "initial_capital", "symbols"],
+        "compare" => &["symbol", "initial_capital", "candles", "strategies"],
+        "optimize" | "walk_forward" => &["strategy", "symbol", "initial_capital", "candles", "param_grid"],
+        "signals" | "advanced_signals" | "multi_timeframe" | "data_check" | "regime" | "features" | "realized_vol" => &["candles"],
+        "greeks" => &["spot", "strike", "time_to_expiry", "risk_free_rate", "option_type"],
+        "scan" | "align" | "estimate_slippage" => &["symbols"],
+        "ticks_to_candles" => &["ticks", "timeframe"],
+        "bar_transform" => &["candles", "type"],
+        "strategy_payoff" | "pop" => &["spot", "legs"],
+        "chain_analysis" | "gex" => &["spot", "strikes"],
+        "expiry_day" => &["snapshots"],
+        "skew_history" => &["history"],
+        "allocate" | "exposure_check" => &["capital"],
+        "ensemble" => &["sources"],
+        "scenario" => &["positions"],
+        "strategy_allocation" => &["strategies"],
+        "trade_quality" => &["trades"],
+        "replay_trades" => &["initial_capital"],
+        "performance_report" => &["equity_curve", "benchmark"],
+        "subscribe" => &["subscription_id", "symbols"],
+        "push_candle" => &["symbol"],
+        "rebalance" => &["target_weights"],
+        "pnl_attribution" => &["trades"],
+        "suggest_strategies" => &["spot", "outlook", "strikes"],
+        "iv_crush" => &["spot", "front_iv", "post_iv", "front_expiry_days"],
+        "suggest_rolls" => &["spot", "position", "strikes"],
+        "basket_index" => &["members"],
+        "range_stats" => &["symbols"],
+        _ => &[],
+    };
+    for field in required {
+        if data.get(*field).is_none_or(|v| v.is_null()) {
+            problems.error(*field, format!("{} requires '{}'", command, field));
+        }
+    }
+    let positive = |field: &str, problems: &mut Problems| {
+        if let Some(v) = data.get(field).and_then(|v| v.as_f64()) {
+            if v <= 0.0 {
+                problems.error(field, format!("{} must be positive, got {}", field, v));
+            }
+        }
+    };
+    for field in ["initial_capital", "spot", "strike", "time_to_expiry"] {
+        positive(field, problems);
+    }
+    if let Some(kind) = data.get("option_type").and_then(|v| v.as_str()) {
+        if !matches!(kind.to_lowercase().as_str(), "call" | "put" | "ce" | "pe") {
+            problems.error("option_type", format!("option_type must be call/put/ce/pe, got '{}'", kind));
+        }
+    }
+}
+
+/// Largest lookback implied by `params` / `param_grid` keys such as
+/// `longPeriod`, `period` or `lookback`.
+fn required_bars(data: &Value) -> usize {
+    let is_lookback = |k: &str| {
+        let k = k.to_lowercase();
+        k.contains("period") || k.contains("lookback")
+    };
+    let mut longest = 0.0_f64;
+    if let Some(params) = data.get("params").and_then(|p| p.as_object()) {
+        for (_, v) in params.iter().filter(|(k, _)| is_lookback(k)) {
+            longest = longest.max(v.as_f64().unwrap_or(0.0));
+        }
+    }
+    if let Some(grid) = data.get("param_grid").and_then(|g| g.as_object()) {
+        for (_, v) in grid.iter().filter(|(k, _)| is_lookback(k)) {
+            let max = v.as_array().into_iter().flatten().filter_map(|x| x.as_f64()).fold(0.0, f64::max);
+            longest = longest.max(max);
+        }
+    }
+    (longest as usize + 1).max(MIN_WARMUP_BARS)
+}
+
+fn check_all_candles(command: &str, data: &Value, problems: &mut Problems) {
+    let needed = required_bars(data);
+    if let Some(candles) = data.get("candles") {
+        check_candles("candles", candles, needed, problems);
+    }
+    if let Some(symbols) = data.get("symbols").and_then(|s| s.as_array()) {
+        if symbols.is_empty() && command == "scan" {
+            problems.error("symbols", "symbols is empty");
+        }
+        for (i, sym) in symbols.iter().enumerate() {
+            if let Some(candles) = sym.get("candles") {
+                check_candles(&format!("symbols[{}].candles", i), candles, needed, problems);
+            }
+        }
+    }
+}
+
+fn check_candles(path: &str, candles: &Value, needed: usize, problems: &mut Problems) {
+    let Some(candles) = candles.as_array() else {
+        problems.error(path, "candles must be an array");
+        return;
+    };
+    if candles.is_empty() {
+        problems.error(path, "no candles supplied");
+        return;
+    }
+    if candles.len() < needed {
+        problems.warn(path, format!(
+            "{} candles supplied but the configured indicators need at least {}", candles.len(), needed));
+    }
+
+    // Cap per-series reports so a broken file does not produce 1M problems.
+    const MAX_REPORTS: usize = 20;
+    let mut reported = 0;
+    let mut prev_ts: Option<&str> = None;
+    for (i, c) in candles.iter().enumerate() {
+        if reported >= MAX_REPORTS {
+            problems.warn(path, format!("further candle problems suppressed after {}", MAX_REPORTS));
+            break;
+        }
+        let before = problems.0.len();
+        let at = |field: &str| format!("{}[{}].{}", path, i, field);
+        let num = |field: &str| c.get(field).and_then(|v| v.as_f64());
+
+        for field in ["high", "low", "close"] {
+            match num(field) {
+                None => problems.error(at(field), format!("missing or non-numeric {}", field)),
+                Some(v) if !v.is_finite() || v <= 0.0 => problems.error(at(field), format!("{} must be positive, got {}", field, v)),
+                _ => {}
+            }
+        }
+        if let (Some(h), Some(l)) = (num("high"), num("low")) {
+            if h < l {
+                problems.error(at("high"), format!("high {} below low {}", h, l));
+            } else {
+                for field in ["open", "close"] {
+                    if let Some(v) = num(field).filter(|v| *v > 0.0) {
+                        if v > h || v < l {
+                            problems.warn(at(field), format!("{} {} outside high/low range [{}, {}]", field, v, l, h));
+                        }
+                    }
+                }
+            }
+        }
+        if num("volume").is_some_and(|v| v < 0.0) {
+            problems.error(at("volume"), "volume must be >= 0");
+        }
+        match c.get("timestamp").and_then(|t| t.as_str()) {
+            None => problems.error(at("timestamp"), "missing timestamp"),
+            Some(ts) => {
+                if let Some(prev) = prev_ts {
+                    if ts < prev {
+                        problems.error(at("timestamp"), format!("timestamp {} is earlier than previous {}", ts, prev));
+                    } else if ts == prev {
+                        problems.warn(at("timestamp"), format!("duplicate timestamp {}", ts));
+                    }
+                }
+                prev_ts = Some(ts);
+            }
+        }
+        if problems.0.len() > before {
+            reported += 1;
+        }
+    }
+}
+
+fn check_param_grid(data: &Value, problems: &mut Problems) {
+    let Some(grid) = data.get("param_grid") else { return };
+    let Some(grid) = grid.as_object() else {
+        problems.error("param_grid", "param_grid must be an object of value arrays");
+        return;
+    };
+    if grid.is_empty() {
+        problems.error("param_grid", "param_grid is empty");
+    }
+    for (key, values) in grid {
+        let path = format!("param_grid.{}", key);
+        let Some(values) = values.as_array() else {
+            problems.error(path, "must be an array of numbers");
+            continue;
+        };
+        if values.is_empty() {
+            problems.error(path, "has no values");
+            continue;
+        }
+        let nums: Vec<f64> = values.iter().filter_map(|v| v.as_f64()).collect();
+        if nums.len() != values.len() {
+            problems.error(path.clone(), "contains non-numeric values");
+        }
+        let lower = key.to_lowercase();
+        if (lower.contains("period") || lower.contains("lookback")) && nums.iter().any(|v| *v < 1.0) {
+            problems.error(path.clone(), "periods must be >= 1");
+        }
+        let mut sorted = nums.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        sorted.dedup();
+        if sorted.len() < nums.len() {
+            problems.warn(path, "contains duplicate values");
+        }
+    }
+
+    // Crossover grids where every short period >= every long period never trade.
+    let bounds = |names: &[&str]| -> Option<(f64, f64)> {
+        names.iter().find_map(|n| grid.get(*n)).and_then(|v| v.as_array()).map(|a| {
+            let nums = a.iter().filter_map(|x| x.as_f64());
+            (nums.clone().fold(f64::INFINITY, f64::min), nums.fold(f64::NEG_INFINITY, f64::max))
+        })
+    };
+    if let (Some((short_min, _)), Some((_, long_max))) =
+        (bounds(&["short_period", "shortPeriod"]), bounds(&["long_period", "longPeriod"]))
+    {
+        if short_min >= long_max {
+            problems.error("param_grid", "no combination has short period < long period");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn candles(n: usize) -> Vec<Value> {
+        (0..n).map(|i| json!({
+            "timestamp": format!("2024-01-01T10:{:02}:00", i % 60), "open": 100.0, "high": 101.0,
+            "low": 99.0, "close": 100.5, "volume": 1000.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_valid_backtest_payload() {
+        let result = compute(json!({ "command": "backtest", "data": {
+            "strategy": "ema_crossover", "symbol": "X", "initial_capital": 100000.0, "candles": candles(40)
+        }}), &LimitsConfig::default()).unwrap();
+        assert_eq!(result["valid"], true, "{}", result["problems"]);
+        assert_eq!(result["errors"], 0);
+    }
+
+    #[test]
+    fn test_reports_all_problems() {
+        let mut bad = candles(5);
+        bad[1]["high"] = json!(98.0);
+        bad[3]["timestamp"] = json!("2023-12-31");
+        bad[4]["close"] = json!(-1.0);
+        let result = compute(json!({ "command": "optimize", "data": {
+            "strategy": "ema_crossover", "symbol": "X", "initial_capital": 0.0, "candles": bad,
+            "param_grid": { "short_period": [30, 30], "long_period": [20], "x": [] }
+        }}), &LimitsConfig::default()).unwrap();
+        assert_eq!(result["valid"], false);
+        let paths: Vec<&str> = result["problems"].as_array().unwrap().iter()
+            .map(|p| p["path"].as_str().unwrap()).collect();
+        for expected in ["initial_capital", "candles[1].high", "candles[3].timestamp", "candles[4].close",
+                         "param_grid.x", "param_grid", "candles"] {
+            assert!(paths.contains(&expected), "missing {} in {:?}", expected, paths);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Volatility-targeting overlay for backtest equity curves.
+//!
+//! The strategy's equity curve is collapsed to daily closes and each day's
+//! return is scaled by `target / forecast`, where the forecast is an EWMA
+//! (RiskMetrics-style) volatility of the raw daily returns known at the
+//! previous close — no look-ahead. Leverage is capped, held at 1 until
+//! `min_periods` returns have been seen, and each change in leverage can be
+//! charged a rebalance cost.
+
+use serde::{Deserialize, Serialize};
+use crate::backtest::EquityPoint;
+use crate::utils::{parse_timestamp, round2, round4};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct VolTargetSpec {
+    /// Annualized volatility to aim for, in percent (e.g. 10).
+    pub target_vol_pct: f64,
+    /// EWMA decay per day; 0.94 is the RiskMetrics daily value.
+    #[serde(default = "default_lambda")]
+    pub lambda: f64,
+    #[serde(default = "default_max_leverage")]
+    pub max_leverage: f64,
+    /// Daily returns observed before the overlay starts scaling.
+    #[serde(default = "default_min_periods")]
+    pub min_periods: usize,
+    /// Charged on |Δ leverage| × NAV at each daily rebalance.
+    #[serde(default)]
+    pub rebalance_cost_bps: f64,
+}
+
+fn default_lambda() -> f64 { 0.94 }
+fn default_max_leverage() -> f64 { 2.0 }
+fn default_min_periods() -> usize { 20 }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CurveStats {
+    pub total_return_pct: f64,
+    pub cagr: f64,
+    pub ann_vol_pct: f64,
+    pub sharpe_ratio: f64,
+    pub max_drawdown: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VolTargetPoint {
+    pub date: String,
+    pub raw_nav: f64,
+    pub targeted_nav: f64,
+    /// Exposure held over the day ending at `date`.
+    pub leverage: f64,
+    /// Forecast used to size that day, annualized %; None during warm-up.
+    pub forecast_vol_pct: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VolTargetResult {
+    pub target_vol_pct: f64,
+    pub raw: CurveStats,
+    pub targeted: CurveStats,
+    pub avg_leverage: f64,
+    pub rebalance_costs: f64,
+    pub curve: Vec<VolTargetPoint>,
+}
+
+/// Apply the overlay to a bar-level equity curve. Bars are grouped by
+/// calendar date, or into `bars_per_day` chunks when timestamps don't parse.
+pub(crate) fn overlay(
+    curve: &[EquityPoint],
+    spec: &VolTargetSpec,
+    trading_days: f64,
+    bars_per_day: f64,
+) -> Result<VolTargetResult, String> {
+    if !(spec.target_vol_pct.is_finite() && spec.target_vol_pct > 0.0) {
+        return Err("vol_target.target_vol_pct must be positive".to_string());
+    }
+    if !(spec.lambda > 0.0 && spec.lambda < 1.0) {
+        return Err("vol_target.lambda must be between 0 and 1".to_string());
+    }
+    if !(spec.max_leverage.is_finite() && spec.max_leverage > 0.0) {
+        return Err("vol_target.max_leverage must be positive".to_string());
+    }
+
+    let days = daily_closes(curve, bars_per_day);
+    let target = spec.target_vol_pct / 100.0;
+    let Some(first) = days.first() else {
+        return Ok(VolTargetResult {
+            target_vol_pct: spec.target_vol_pct,
+            raw: stats(&[], 0.0, trading_days),
+            targeted: stats(&[], 0.0, trading_days),
+            avg_leverage: 0.0,
+            rebalance_costs: 0.0,
+            curve: vec![],
+        });
+    };
+
+    let mut out = vec![VolTargetPoint {
+        date: first.0.clone(),
+        raw_nav: round2(first.1),
+        targeted_nav: round2(first.1),
+        leverage: 1.0,
+        forecast_vol_pct: None,
+    }];
+    let mut nav = first.1;
+    let mut variance: Option<f64> = None;
+    let mut leverage = 1.0;
+    let mut costs = 0.0;
+    let mut raw_returns = Vec::with_capacity(days.len());
+    let mut scaled_returns = Vec::with_capacity(days.len());
+    for (seen, w) in days.windows(2).enumerate() {
+        let ret = if w[0].1 > 0.0 { w[1].1 / w[0].1 - 1.0 } else { 0.0 };
+        // Size today from the forecast as of yesterday's close.
+        let forecast = variance.filter(|_| seen >= spec.min_periods).map(|v| (v * trading_days).sqrt());
+        let next = match forecast {
+            Some(vol) if vol > 0.0 => (target / vol).min(spec.max_leverage),
+            Some(_) => spec.max_leverage,
+            None => 1.0,
+        };
+        let cost = (next - leverage).abs() * nav * spec.rebalance_cost_bps / 10_000.0;
+        leverage = next;
+        costs += cost;
+        let prev = nav;
+        nav = (nav * (1.0 + leverage * ret) - cost).max(0.0);
+        raw_returns.push(ret);
+        scaled_returns.push(if prev > 0.0 { nav / prev - 1.0 } else { 0.0 });
+
+        variance = Some(match variance {
+            Some(v) => spec.lambda * v + (1.0 - spec.lambda) * ret * ret,
+            None => ret * ret,
+        });
+        out.push(VolTargetPoint {
+            date: w[1].0.clone(),
+            raw_nav: round2(w[1].1),
+            targeted_nav: round2(nav),
+            leverage: round4(leverage),
+            forecast_vol_pct: forecast.map(|v| round2(v * 100.0)),
+        });
+    }
+
+    let years = years(&days, trading_days);
+    let avg_leverage = if out.len() > 1 {
+        out[1..].iter().map(|p| p.leverage).sum::<f64>() / (out.len() - 1) as f64
+    } else {
+        1.0
+    };
+    Ok(VolTargetResult {
+        target_vol_pct: spec.target_vol_pct,
+        raw: stats(&raw_returns, years, trading_days),
+        targeted: stats(&scaled_returns, years, trading_days),
+        avg_leverage: round4(avg_leverage),
+        rebalance_costs: round2(costs),
+        curve: out,
+    })
+}
+
+/// Last NAV of each day, keyed by the day's last timestamp.
+pub(crate) fn daily_closes(curve: &[EquityPoint], bars_per_day: f64) -> Vec<(String, f64)> {
+    let dates: Option<Vec<chrono::NaiveDate>> = curve.iter()
+        .map(|p| parse_timestamp(&p.date).map(|t| t.date()))
+        .collect();
+    let mut out: Vec<(String, f64)> = Vec::new();
+    match dates {
+        Some(dates) => {
+            for (i, p) in curve.iter().enumerate() {
+                if i > 0 && dates[i] == dates[i - 1] {
+                    if let Some(last) = out.last_mut() {
+                        *last = (p.date.clone(), p.nav);
+                    }
+                } else {
+                    out.push((p.date.clone(), p.nav));
+                }
+            }
+        }
+        None => {
+            let chunk = (bars_per_day.round() as usize).max(1);
+            out = curve.chunks(chunk)
+                .filter_map(|c| c.last().map(|p| (p.date.clone(), p.nav)))
+                .collect();
+        }
+    }
+    out
+}
+
+pub(crate) fn years(days: &[(String, f64)], trading_days: f64) -> f64 {
+    let span = days.first()
+        .zip(days.last())
+        .and_then(|(a, b)| Some((parse_timestamp(&a.0)?.date(), parse_timestamp(&b.0)?.date())));
+    match span {
+        Some((a, b)) if b > a => (b - a).num_days() as f64 / 365.25,
+        _ => days.len().saturating_sub(1) as f64 / trading_days,
+    }
+}
+
+pub(crate) fn stats(returns: &[f64], years: f64, trading_days: f64) -> CurveStats {
+    let growth: f64 = returns.iter().map(|r| 1.0 + r).product();
+    let n = returns.len() as f64;
+    let mean = if returns.is_empty() { 0.0 } else { returns.iter().sum::<f64>() / n };
+    let std = if returns.len() < 2 { 0.0 } else {
+        (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+    };
+    let mut peak = 1.0f64;
+    let mut level = 1.0;
+    let mut max_dd = 0.0f64;
+    for r in returns {
+        level *= 1.0 + r;
+        peak = peak.max(level);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - level) / peak);
+        }
+    }
+    CurveStats {
+        total_return_pct: round2((growth - 1.0) * 100.0),
+        cagr: if years > 0.0 && growth > 0.0 { round2((growth.powf(1.0 / years) - 1.0) * 100.0) } else { 0.0 },
+        ann_vol_pct: round2(std * trading_days.sqrt() * 100.0),
+        sharpe_ratio: if std > 0.0 { round2(mean / std * trading_days.sqrt()) } else { 0.0 },
+        max_drawdown: round2(max_dd * 100.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(target: f64) -> VolTargetSpec {
+        VolTargetSpec { target_vol_pct: target, lambda: 0.94, max_leverage: 5.0, min_periods: 20, rebalance_cost_bps: 0.0 }
+    }
+
+    /// Daily NAVs alternating ±`daily` so realized vol is known.
+    fn curve(days: usize, daily: f64) -> Vec<EquityPoint> {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut nav = 100_000.0;
+        (0..days).map(|d| {
+            if d > 0 {
+                nav *= if d % 2 == 0 { 1.0 + daily } else { 1.0 - daily };
+            }
+            EquityPoint { date: (start + chrono::Duration::days(d as i64)).format("%Y-%m-%d").to_string(), nav }
+        }).collect()
+    }
+
+    #[test]
+    fn test_scales_to_target_vol() {
+        // 2% daily moves ≈ 31.7% annualized; a 10% target wants ~0.32x.
+        let r = overlay(&curve(200, 0.02), &spec(10.0), 252.0, 1.0).unwrap();
+        assert_eq!(r.curve.len(), 200);
+        assert!(r.curve[..21].iter().all(|p| p.leverage == 1.0));
+        let last = r.curve.last().unwrap();
+        assert!((last.leverage - 10.0 / 31.75).abs() < 0.01, "leverage {}", last.leverage);
+        assert!(r.raw.ann_vol_pct > 31.0);
+        assert!(r.targeted.ann_vol_pct < r.raw.ann_vol_pct / 2.0);
+        assert!(r.targeted.max_drawdown < r.raw.max_drawdown);
+
+        // Calm strategy is levered up, but never beyond the cap.
+        let calm = overlay(&curve(200, 0.001), &spec(10.0), 252.0, 1.0).unwrap();
+        assert_eq!(calm.curve.last().unwrap().leverage, 5.0);
+        assert!(overlay(&curve(10, 0.01), &VolTargetSpec { lambda: 1.0, ..spec(10.0) }, 252.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_intraday_bars_collapse_to_days() {
+        let bars: Vec<EquityPoint> = (0..30).map(|i| EquityPoint {
+            date: format!("2024-01-{:02}T{:02}:00:00", 1 + i / 3, 10 + i % 3),
+            nav: 1000.0 + i as f64,
+        }).collect();
+        let r = overlay(&bars, &spec(10.0), 252.0, 3.0).unwrap();
+        assert_eq!(r.curve.len(), 10);
+        assert_eq!(r.curve[1].date, "2024-01-02T12:00:00");
+        assert_eq!(r.curve[1].raw_nav, 1005.0);
+        // Undated bars fall back to bars_per_day chunks.
+        let undated: Vec<EquityPoint> = bars.iter().enumerate()
+            .map(|(i, p)| EquityPoint { date: format!("bar{}", i), nav: p.nav }).collect();
+        assert_eq!(overlay(&undated, &spec(10.0), 252.0, 3.0).unwrap().curve.len(), 10);
+    }
+}
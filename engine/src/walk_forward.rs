@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use crate::backtest;
+use crate::cancel::CancelToken;
+use crate::progress::Progress;
 use crate::utils::{round2, norm_cdf, generate_combinations_map};
 
 #[derive(Deserialize)]
@@ -50,6 +52,9 @@ struct WalkForwardResult {
     whites_rc_significant: Option<bool>,
     return_t_stat: Option<f64>,
     return_p_value: Option<f64>,
+    cancelled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cancel_reason: Option<String>,
 }
 
 #[derive(Serialize, Clone)]
@@ -76,6 +81,13 @@ struct AggregateMetrics {
 }
 
 pub fn compute(data: Value) -> Result<Value, String> {
+    compute_cancellable(data, &CancelToken::none(), &Progress::none())
+}
+
+/// Like `compute`, but stops at the next fold or parameter combo once `cancel`
+/// trips, aggregates the completed folds and skips the CPCV/White's RC extras.
+/// Progress counts in-sample combos across all folds.
+pub fn compute_cancellable(data: Value, cancel: &CancelToken, progress: &Progress) -> Result<Value, String> {
     let config: WalkForwardConfig =
         serde_json::from_value(data).map_err(|e| format!("Invalid walk-forward config: {}", e))?;
 
@@ -110,7 +122,14 @@ pub fn compute(data: Value) -> Result<Value, String> {
     let mut folds: Vec<FoldResult> = Vec::new();
     let mut param_scores: std::collections::HashMap<String, Vec<f64>> = std::collections::HashMap::new();
 
+    let mut cancel_reason: Option<String> = None;
+    let total_units = num_folds * param_combos.len();
     for fold in 0..num_folds {
+        let fold_stage = format!("fold {}/{}", fold + 1, num_folds);
+        if let Some(reason) = cancel.reason() {
+            cancel_reason = Some(reason.to_string());
+            break;
+        }
         // Expanding window: in-sample always starts from index 0
         // Rolling window: in-sample starts from fold_start
         let fold_start = if is_expanding { 0 } else { fold * fold_size };
@@ -130,7 +149,9 @@ pub fn compute(data: Value) -> Result<Value, String> {
         let mut best_is_sharpe = f64::NEG_INFINITY;
         let mut best_params = serde_json::json!({});
 
-        for combo in &param_combos {
+        for (done, combo) in param_combos.iter().enumerate() {
+            progress.update(&fold_stage, fold * param_combos.len() + done, total_units);
+            if cancel.is_cancelled() { break; }
             let bt_input = serde_json::json!({
                 "strategy": config.strategy,
                 "symbol": config.symbol,
@@ -148,6 +169,12 @@ pub fn compute(data: Value) -> Result<Value, String> {
             }
         }
 
+        // A fold whose in-sample search was interrupted is incomplete; drop it.
+        if let Some(reason) = cancel.reason() {
+            cancel_reason = Some(reason.to_string());
+            break;
+        }
+
         let oos_input = serde_json::json!({
             "strategy": config.strategy,
             "symbol": config.symbol,
@@ -205,8 +232,15 @@ pub fn compute(data: Value) -> Result<Value, String> {
     }
 
     if folds.is_empty() {
+        if let Some(reason) = cancel_reason {
+            return Err(format!("Walk-forward {} before the first fold completed", reason));
+        }
         return Err("No valid folds produced".to_string());
     }
+    let cancelled = cancel_reason.is_some();
+    if !cancelled {
+        progress.update("complete", total_units, total_units);
+    }
 
     let n_folds = folds.len() as f64;
     let avg_is = folds.iter().map(|f| f.in_sample_sharpe).sum::<f64>() / n_folds;
@@ -236,7 +270,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
         None
     };
 
-    let (cpcv_pbo, cpcv_avg_oos_sharpe) = if config.run_cpcv.unwrap_or(false) {
+    let (cpcv_pbo, cpcv_avg_oos_sharpe) = if config.run_cpcv.unwrap_or(false) && !cancelled {
         let (avg, _std, pbo) = cpcv_test(
             &candles_json, &config.strategy, &config.symbol,
             &param_combos, config.initial_capital, purge_bars, num_folds,
@@ -246,7 +280,7 @@ pub fn compute(data: Value) -> Result<Value, String> {
         (None, None)
     };
 
-    let (whites_rc_p_value, whites_rc_significant) = if config.run_whites_rc.unwrap_or(false) {
+    let (whites_rc_p_value, whites_rc_significant) = if config.run_whites_rc.unwrap_or(false) && !cancelled {
         let strategy_rets: Vec<Vec<f64>> = param_scores.values().cloned().collect();
         let (p, sig, _t) = whites_reality_check(&strategy_rets, 0.0);
         (Some(p), Some(sig))
@@ -282,6 +316,8 @@ pub fn compute(data: Value) -> Result<Value, String> {
         whites_rc_significant,
         return_t_stat,
         return_p_value,
+        cancelled,
+        cancel_reason,
     };
 
     serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
@@ -0,0 +1,384 @@
+//! Covered-call and wheel backtests.
+//!
+//! Selected by `backtest` with `strategy` "covered_call" or "wheel". The
+//! covered call buys round lots on the first bar and keeps selling calls
+//! against them; the wheel sells cash-secured puts until assigned, then
+//! calls until the shares are called away, and repeats. Options are struck
+//! at `call_delta`/`put_delta` with `dte` calendar days to expiry, priced
+//! from `option_prices` when a quote exists for that day and strike and
+//! with Black-Scholes at `iv` otherwise. Shorts are bought back at
+//! `take_profit_pct` of the premium, rolled when in the money with
+//! `roll_dte` days left, and otherwise settle at expiry with assignment.
+
+use std::collections::HashMap;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backtest::EquityPoint;
+use crate::utils::{bs_greeks, bs_price, parse_timestamp, round2, Candle};
+
+#[derive(Deserialize)]
+struct WheelConfig {
+    strategy: String,
+    #[serde(default)]
+    symbol: String,
+    initial_capital: f64,
+    candles: Vec<Candle>,
+    #[serde(default)]
+    params: WheelParams,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct WheelParams {
+    iv: f64,
+    risk_free_rate: f64,
+    dte: i64,
+    call_delta: f64,
+    put_delta: f64,
+    lot_size: f64,
+    /// Strikes are rounded away from the money to this grid; 0 = unrounded.
+    strike_step: f64,
+    take_profit_pct: Option<f64>,
+    roll_dte: Option<i64>,
+    /// Never write calls below the assigned cost basis.
+    calls_above_basis: bool,
+    commission_per_contract: f64,
+    option_prices: Vec<OptionPrice>,
+}
+
+impl Default for WheelParams {
+    fn default() -> Self {
+        Self {
+            iv: 0.25,
+            risk_free_rate: 0.065,
+            dte: 30,
+            call_delta: 0.30,
+            put_delta: 0.30,
+            lot_size: 100.0,
+            strike_step: 0.0,
+            take_profit_pct: None,
+            roll_dte: None,
+            calls_above_basis: true,
+            commission_per_contract: 0.0,
+            option_prices: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OptionPrice {
+    timestamp: String,
+    option_type: String,
+    strike: f64,
+    price: f64,
+}
+
+#[derive(Clone)]
+struct Short {
+    is_call: bool,
+    strike: f64,
+    expiry: NaiveDate,
+    premium: f64,
+    contracts: f64,
+}
+
+#[derive(Serialize)]
+struct WheelEvent {
+    date: String,
+    action: &'static str,
+    option_type: &'static str,
+    strike: f64,
+    expiry: String,
+    price: f64,
+    contracts: f64,
+    cash_flow: f64,
+}
+
+#[derive(Serialize)]
+struct WheelResult {
+    strategy: String,
+    symbol: String,
+    total_return_pct: f64,
+    cagr: f64,
+    max_drawdown: f64,
+    buy_and_hold_return_pct: f64,
+    premium_collected: f64,
+    buyback_paid: f64,
+    options_sold: usize,
+    expired_worthless: usize,
+    puts_assigned: usize,
+    calls_assigned: usize,
+    early_closes: usize,
+    rolls: usize,
+    total_costs: f64,
+    final_cash: f64,
+    final_shares: f64,
+    cost_basis: Option<f64>,
+    equity_curve: Vec<EquityPoint>,
+    trade_log: Vec<WheelEvent>,
+}
+
+pub fn run(data: Value) -> Result<Value, String> {
+    let config: WheelConfig =
+        serde_json::from_value(data).map_err(|e| format!("Invalid backtest config: {}", e))?;
+    let wheel = match config.strategy.as_str() {
+        "wheel" => true,
+        "covered_call" => false,
+        other => return Err(format!("Unknown option backtest strategy '{}' (wheel, covered_call)", other)),
+    };
+    let p = &config.params;
+    if !(p.iv > 0.0 && p.dte > 0 && p.lot_size > 0.0) {
+        return Err("iv, dte and lot_size must be positive".to_string());
+    }
+    let bars: Vec<(NaiveDate, &Candle)> = config.candles.iter()
+        .map(|c| parse_timestamp(&c.timestamp).map(|t| (t.date(), c)))
+        .collect::<Option<_>>()
+        .ok_or("option backtests need candles with parseable timestamps")?;
+    let Some(&(first_date, first)) = bars.first() else {
+        return Err("option backtests need candles".to_string());
+    };
+
+    let quotes: HashMap<(NaiveDate, bool, i64), f64> = p.option_prices.iter()
+        .filter_map(|q| {
+            let date = parse_timestamp(&q.timestamp)?.date();
+            let is_call = matches!(q.option_type.to_lowercase().as_str(), "call" | "ce" | "c");
+            Some(((date, is_call, strike_key(q.strike)), q.price))
+        })
+        .collect();
+    let price_of = |s: &Short, spot: f64, date: NaiveDate| -> f64 {
+        if let Some(&q) = quotes.get(&(date, s.is_call, strike_key(s.strike))) {
+            return q;
+        }
+        let t = (s.expiry - date).num_days().max(0) as f64 / 365.0;
+        bs_price(spot, s.strike, p.risk_free_rate, t, p.iv, s.is_call)
+    };
+
+    let mut cash = config.initial_capital;
+    let mut shares = 0.0;
+    let mut basis: Option<f64> = None;
+    let mut short: Option<Short> = None;
+    let mut log: Vec<WheelEvent> = Vec::new();
+    let mut equity_curve = Vec::with_capacity(bars.len());
+    let (mut premium, mut buyback, mut costs) = (0.0, 0.0, 0.0);
+    let (mut sold, mut worthless, mut put_assigned, mut call_assigned, mut early, mut rolls) = (0, 0, 0, 0, 0, 0);
+    let (mut peak, mut max_dd) = (config.initial_capital, 0.0_f64);
+
+    if !wheel {
+        let lots = (cash / (first.close * p.lot_size)).floor();
+        if lots < 1.0 {
+            return Err("initial_capital does not cover one lot of the underlying".to_string());
+        }
+        shares = lots * p.lot_size;
+        cash -= shares * first.close;
+        basis = Some(first.close);
+    }
+
+    let event = |date: NaiveDate, action: &'static str, s: &Short, price: f64, cash_flow: f64| WheelEvent {
+        date: date.to_string(),
+        action,
+        option_type: if s.is_call { "call" } else { "put" },
+        strike: round2(s.strike),
+        expiry: s.expiry.to_string(),
+        price: round2(price),
+        contracts: s.contracts,
+        cash_flow: round2(cash_flow),
+    };
+
+    for &(date, candle) in &bars {
+        let spot = candle.close;
+        let mut reopen = short.is_none();
+        if let Some(s) = short.clone() {
+            let units = s.contracts * p.lot_size;
+            let itm = if s.is_call { spot > s.strike } else { spot < s.strike };
+            if date >= s.expiry {
+                short = None;
+                reopen = true;
+                if !itm {
+                    worthless += 1;
+                    log.push(event(date, "expired", &s, 0.0, 0.0));
+                } else if s.is_call {
+                    call_assigned += 1;
+                    cash += s.strike * units;
+                    shares -= units;
+                    if shares <= 0.0 {
+                        basis = None;
+                    }
+                    log.push(event(date, "called_away", &s, s.strike, s.strike * units));
+                } else {
+                    put_assigned += 1;
+                    cash -= s.strike * units;
+                    let prev = basis.unwrap_or(0.0) * shares;
+                    shares += units;
+                    // Premium already banked lowers the effective basis.
+                    basis = Some((prev + (s.strike - s.premium) * units) / shares);
+                    log.push(event(date, "assigned", &s, s.strike, -s.strike * units));
+                }
+            } else {
+                let value = price_of(&s, spot, date);
+                let take = p.take_profit_pct.is_some_and(|tp| value <= s.premium * (1.0 - tp / 100.0));
+                let roll = p.roll_dte.is_some_and(|d| itm && (s.expiry - date).num_days() <= d);
+                if take || roll {
+                    let fee = p.commission_per_contract * s.contracts;
+                    cash -= value * units + fee;
+                    buyback += value * units;
+                    costs += fee;
+                    if take { early += 1 } else { rolls += 1 }
+                    log.push(event(date, if take { "bought_to_close" } else { "rolled" }, &s, value, -value * units - fee));
+                    short = None;
+                    // A roll writes the next cycle straight away; a profit
+                    // take waits for the next bar.
+                    reopen = roll;
+                }
+            }
+        }
+
+        if reopen && short.is_none() {
+            let expiry = date + Duration::days(p.dte);
+            let t = p.dte as f64 / 365.0;
+            let is_call = !wheel || shares >= p.lot_size;
+            let target = if is_call { p.call_delta } else { p.put_delta };
+            let mut strike = strike_for_delta(spot, t, p.risk_free_rate, p.iv, target, is_call);
+            if p.strike_step > 0.0 {
+                let steps = strike / p.strike_step;
+                strike = if is_call { steps.ceil() } else { steps.floor() } * p.strike_step;
+            }
+            if is_call && p.calls_above_basis {
+                if let Some(b) = basis {
+                    strike = strike.max(b);
+                }
+            }
+            let contracts = if is_call {
+                (shares / p.lot_size).floor()
+            } else {
+                (cash / (strike * p.lot_size)).floor()
+            };
+            if contracts >= 1.0 {
+                let mut s = Short { is_call, strike, expiry, premium: 0.0, contracts };
+                s.premium = price_of(&s, spot, date);
+                let units = contracts * p.lot_size;
+                let fee = p.commission_per_contract * contracts;
+                cash += s.premium * units - fee;
+                premium += s.premium * units;
+                costs += fee;
+                sold += 1;
+                log.push(event(date, if is_call { "sell_call" } else { "sell_put" }, &s, s.premium, s.premium * units - fee));
+                short = Some(s);
+            }
+        }
+
+        let liability = short.as_ref().map_or(0.0, |s| price_of(s, spot, date) * s.contracts * p.lot_size);
+        let nav = cash + shares * spot - liability;
+        peak = peak.max(nav);
+        if peak > 0.0 {
+            max_dd = max_dd.max((peak - nav) / peak);
+        }
+        equity_curve.push(EquityPoint { date: candle.timestamp.clone(), nav: round2(nav) });
+    }
+
+    let (last_date, last) = bars[bars.len() - 1];
+    let final_nav = equity_curve.last().map_or(config.initial_capital, |e| e.nav);
+    let total_return = final_nav / config.initial_capital - 1.0;
+    let years = ((last_date - first_date).num_days() + 1) as f64 / 365.25;
+    let cagr = if years > 0.0 && total_return > -1.0 { (1.0 + total_return).powf(1.0 / years) - 1.0 } else { 0.0 };
+
+    let result = WheelResult {
+        strategy: config.strategy,
+        symbol: config.symbol,
+        total_return_pct: round2(total_return * 100.0),
+        cagr: round2(cagr * 100.0),
+        max_drawdown: round2(max_dd * 100.0),
+        buy_and_hold_return_pct: round2((last.close / first.close - 1.0) * 100.0),
+        premium_collected: round2(premium),
+        buyback_paid: round2(buyback),
+        options_sold: sold,
+        expired_worthless: worthless,
+        puts_assigned: put_assigned,
+        calls_assigned: call_assigned,
+        early_closes: early,
+        rolls,
+        total_costs: round2(costs),
+        final_cash: round2(cash),
+        final_shares: shares,
+        cost_basis: basis.map(round2),
+        equity_curve,
+        trade_log: log,
+    };
+    serde_json::to_value(result).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn strike_key(strike: f64) -> i64 {
+    (strike * 100.0).round() as i64
+}
+
+/// OTM strike whose |delta| is `target`, by bisection (delta is monotone in strike).
+fn strike_for_delta(spot: f64, t: f64, r: f64, iv: f64, target: f64, is_call: bool) -> f64 {
+    let (mut lo, mut hi) = (spot * 0.2, spot * 5.0);
+    for _ in 0..60 {
+        let mid = (lo + hi) / 2.0;
+        let delta = bs_greeks(spot, mid, t, r, iv, is_call).0.abs();
+        // Call delta falls as the strike rises; put |delta| rises.
+        if (delta > target) == is_call { lo = mid } else { hi = mid }
+    }
+    (lo + hi) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn daily(closes: &[f64]) -> Vec<Value> {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        closes.iter().enumerate().map(|(i, c)| json!({
+            "timestamp": (start + Duration::days(i as i64)).to_string(),
+            "open": c, "high": c, "low": c, "close": c, "volume": 1000.0
+        })).collect()
+    }
+
+    #[test]
+    fn test_covered_call_called_away_in_rally() {
+        let closes: Vec<f64> = (0..40).map(|i| 100.0 + i as f64).collect();
+        let result = run(json!({
+            "strategy": "covered_call", "initial_capital": 20_000.0, "candles": daily(&closes),
+            "params": { "dte": 30, "strike_step": 5.0 }
+        })).unwrap();
+        assert_eq!(result["trade_log"][0]["action"], "sell_call");
+        assert!(result["trade_log"][0]["strike"].as_f64().unwrap() > 100.0);
+        assert_eq!(result["calls_assigned"], 1);
+        assert_eq!(result["final_shares"], 0.0);
+        // Capped upside: worse than buy and hold in a straight rally.
+        assert!(result["total_return_pct"].as_f64().unwrap() < result["buy_and_hold_return_pct"].as_f64().unwrap());
+        assert!(result["premium_collected"].as_f64().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_wheel_assignment_then_calls_above_basis() {
+        // Falls through the put strike, then drifts sideways.
+        let mut closes: Vec<f64> = (0..31).map(|i| 100.0 - i as f64 * 0.5).collect();
+        closes.extend((0..40).map(|_| 85.0));
+        let result = run(json!({
+            "strategy": "wheel", "initial_capital": 10_000.0, "candles": daily(&closes),
+            "params": { "dte": 30 }
+        })).unwrap();
+        assert_eq!(result["trade_log"][0]["action"], "sell_put");
+        assert_eq!(result["puts_assigned"], 1);
+        let log = result["trade_log"].as_array().unwrap();
+        let call = log.iter().find(|e| e["action"] == "sell_call").unwrap();
+        let basis = result["cost_basis"].as_f64().unwrap();
+        assert!(call["strike"].as_f64().unwrap() >= basis);
+        assert_eq!(result["equity_curve"].as_array().unwrap().len(), closes.len());
+    }
+
+    #[test]
+    fn test_take_profit_closes_early() {
+        let closes: Vec<f64> = (0..60).map(|_| 100.0).collect();
+        let result = run(json!({
+            "strategy": "wheel", "initial_capital": 10_000.0, "candles": daily(&closes),
+            "params": { "dte": 30, "take_profit_pct": 50.0 }
+        })).unwrap();
+        assert!(result["early_closes"].as_u64().unwrap() >= 1);
+        assert_eq!(result["puts_assigned"], 0);
+        assert!(result["total_return_pct"].as_f64().unwrap() > 0.0);
+    }
+}
@@ -0,0 +1,111 @@
+//! WebAssembly bindings for the engine's pure computations.
+//!
+//! The modules below are the engine's own sources compiled a second time;
+//! they depend only on serde, chrono and once_cell, so they run unchanged in the
+//! browser. Each export takes and returns the same JSON as the matching
+//! engine command, letting the frontend compute light indicators locally
+//! instead of spawning the native process.
+
+#[allow(dead_code)]
+#[path = "../../src/utils.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../../src/calendar.rs"]
+mod calendar;
+#[allow(dead_code)]
+#[path = "../../src/kernels.rs"]
+mod kernels;
+#[allow(dead_code, unused_imports)]
+#[path = "../../src/indicators.rs"]
+mod indicators;
+#[allow(dead_code)]
+#[path = "../../src/signals.rs"]
+mod signals;
+#[allow(dead_code)]
+#[path = "../../src/greeks.rs"]
+mod greeks;
+#[allow(dead_code)]
+#[path = "../../src/risk.rs"]
+mod risk;
+
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+/// Commands available in the WASM build.
+pub const COMMANDS: &[&str] = &["signals", "greeks", "risk"];
+
+fn run(command: &str, data: Value) -> Result<Value, String> {
+    match command {
+        "signals" => signals::compute(data),
+        "greeks" => greeks::compute(data),
+        "risk" => risk::compute(data),
+        _ => Err(format!("Unknown command: {} (WASM build supports {})", command, COMMANDS.join(", "))),
+    }
+}
+
+fn call(command: &str, input: &str) -> Result<String, JsError> {
+    let data: Value = serde_json::from_str(input).map_err(|e| JsError::new(&format!("Invalid JSON: {}", e)))?;
+    let out = run(command, data).map_err(|e| JsError::new(&e))?;
+    Ok(out.to_string())
+}
+
+/// `signals` command; JSON in, JSON out. Throws on invalid input.
+#[wasm_bindgen]
+pub fn signals(input: &str) -> Result<String, JsError> {
+    call("signals", input)
+}
+
+/// `greeks` command; JSON in, JSON out. Throws on invalid input.
+#[wasm_bindgen]
+pub fn greeks(input: &str) -> Result<String, JsError> {
+    call("greeks", input)
+}
+
+/// `risk` command; JSON in, JSON out. Throws on invalid input.
+#[wasm_bindgen]
+pub fn risk(input: &str) -> Result<String, JsError> {
+    call("risk", input)
+}
+
+/// Same envelope as the native engine: `{"id","command","data"}` in,
+/// `{"id","success","data","error"}` out. Never throws, so hosts can share
+/// response handling with the subprocess path.
+#[wasm_bindgen]
+pub fn execute(request: &str) -> String {
+    let reply = match serde_json::from_str::<Value>(request) {
+        Ok(req) => {
+            let id = req.get("id").cloned().unwrap_or(Value::Null);
+            let command = req.get("command").and_then(|c| c.as_str()).unwrap_or("");
+            let data = req.get("data").cloned().unwrap_or(Value::Null);
+            match run(command, data) {
+                Ok(data) => json!({ "id": id, "success": true, "data": data, "error": null }),
+                Err(e) => json!({ "id": id, "success": false, "data": null, "error": e }),
+            }
+        }
+        Err(e) => json!({ "id": null, "success": false, "data": null, "error": format!("Invalid JSON: {}", e) }),
+    };
+    reply.to_string()
+}
+
+#[wasm_bindgen]
+pub fn version() -> String {
+    json!({ "engine_version": env!("CARGO_PKG_VERSION"), "commands": COMMANDS }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute_envelope() {
+        let ok: Value = serde_json::from_str(&execute(r#"{"id":"g1","command":"greeks","data":{
+            "spot":100,"strike":100,"time_to_expiry":0.5,"risk_free_rate":0.05,"volatility":0.2,"option_type":"call"}}"#)).unwrap();
+        assert_eq!(ok["id"], "g1");
+        assert_eq!(ok["success"], true);
+        assert!(ok["data"]["delta"].as_f64().unwrap() > 0.5);
+
+        let err: Value = serde_json::from_str(&execute(r#"{"id":"x","command":"backtest","data":{}}"#)).unwrap();
+        assert_eq!(err["success"], false);
+        assert!(err["error"].as_str().unwrap().contains("WASM build supports"));
+    }
+}
@@ -0,0 +1,52 @@
+// Local (in-browser) engine for light computations: signals, greeks, risk.
+//
+// Built from engine/wasm with:
+//   wasm-pack build engine/wasm --target web --release --out-dir ../../frontend/public/engine-wasm
+// When the bundle is missing, every call resolves to null and callers should
+// fall back to the server API.
+
+interface EngineWasmModule {
+  default: (input?: unknown) => Promise<unknown>;
+  execute: (request: string) => string;
+  version: () => string;
+}
+
+export interface EngineWasmResponse<T = unknown> {
+  id: string | null;
+  success: boolean;
+  data: T | null;
+  error: string | null;
+}
+
+export type EngineWasmCommand = 'signals' | 'greeks' | 'risk';
+
+const MODULE_URL = '/engine-wasm/capital_guard_engine_wasm.js';
+
+let loading: Promise<EngineWasmModule | null> | null = null;
+
+function load(): Promise<EngineWasmModule | null> {
+  if (!loading) {
+    loading = import(/* @vite-ignore */ MODULE_URL)
+      .then(async (mod: EngineWasmModule) => {
+        await mod.default();
+        return mod;
+      })
+      .catch(() => null);
+  }
+  return loading;
+}
+
+export async function isEngineWasmAvailable(): Promise<boolean> {
+  return (await load()) !== null;
+}
+
+/** Run a command in the WASM engine. Resolves to null when WASM is unavailable. */
+export async function runEngineWasm<T = unknown>(
+  command: EngineWasmCommand,
+  data: unknown,
+): Promise<EngineWasmResponse<T> | null> {
+  const mod = await load();
+  if (!mod) return null;
+  const id = `wasm-${Date.now()}`;
+  return JSON.parse(mod.execute(JSON.stringify({ id, command, data }))) as EngineWasmResponse<T>;
+}
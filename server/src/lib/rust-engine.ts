@@ -104,11 +104,22 @@ export async function ensureEngineAvailable(): Promise<boolean> {
   }
 }
 
+interface EngineResponseMeta {
+  load_ms: number;
+  compute_ms: number;
+  total_ms: number;
+  candles: number;
+  est_input_mb: number;
+  peak_rss_mb?: number;
+  warnings?: string[];
+}
+
 interface EngineResponse {
   id?: string;
   success: boolean;
   data: unknown;
   error?: string;
+  meta?: EngineResponseMeta;
 }
 
 const ENGINE_TIMEOUT_MS = 30_000;
@@ -174,7 +185,10 @@ function spawnDaemon(): boolean {
     const rl = createInterface({ input: proc.stdout!, crlfDelay: Infinity });
     rl.on('line', (line) => {
       try {
-        const resp = JSON.parse(line) as EngineResponse;
+        const msg = JSON.parse(line);
+        // Progress notifications share the request id but are not the reply.
+        if (msg?.event === 'progress') return;
+        const resp = msg as EngineResponse;
         const reqId = resp.id;
         if (reqId && pendingRequests.has(reqId)) {
           const pending = pendingRequests.get(reqId)!;
@@ -311,6 +325,90 @@ export async function engineRisk(data: unknown): Promise<unknown> {
   return res.data;
 }
 
+export interface ReturnsResult {
+  kind: 'simple' | 'log';
+  horizon: string;
+  returns: number[];
+  timestamps: string[];
+  periods_per_year: number | null;
+  mean: number;
+  std_dev: number;
+  annualized_volatility: number | null;
+  cumulative_return: number;
+  dividends_applied: number;
+  skipped_prices: number;
+}
+
+/** Returns computed by the engine; `returns` can go straight into `engineRisk`. */
+export async function engineReturns(data: {
+  candles?: Array<{ timestamp: string; open: number; high: number; low: number; close: number; volume: number }>;
+  prices?: number[];
+  kind?: 'simple' | 'log';
+  horizon?: 'bar' | 'daily' | 'weekly' | 'monthly' | number;
+  dividends?: Array<{ ex_date: string; amount: number }>;
+}): Promise<ReturnsResult> {
+  const res = await runEngine('returns', data);
+  if (!res.success) throw new Error(res.error ?? 'Returns computation failed');
+  return res.data as ReturnsResult;
+}
+
+export interface PortfolioPosition {
+  symbol: string;
+  side: 'long' | 'short';
+  qty: number;
+  avg_price: number;
+  last_price: number;
+  market_value: number;
+  unrealized_pnl: number;
+  unrealized_pnl_pct: number;
+  realized_pnl: number;
+  margin: number;
+  stop_loss: number | null;
+  take_profit: number | null;
+  opened_at: string;
+}
+
+export interface PortfolioState {
+  initial_capital: number;
+  cash: number;
+  equity: number;
+  realized_pnl: number;
+  unrealized_pnl: number;
+  total_pnl: number;
+  fees_paid: number;
+  margin_used: number;
+  margin_available: number;
+  gross_exposure: number;
+  net_exposure: number;
+  positions: PortfolioPosition[];
+  fills_count: number;
+  updated_at: string | null;
+  fill?: { id: number; symbol: string; side: 'buy' | 'sell'; qty: number; price: number; fees: number; timestamp: string; realized_pnl: number };
+}
+
+/** Paper-trading ledger command; pass `state_file` to persist across engine processes. */
+export async function enginePortfolio(data: {
+  command: 'status' | 'init' | 'reset' | 'open' | 'close' | 'modify' | 'record_fill' | 'mark';
+  state_file?: string;
+  [key: string]: unknown;
+}): Promise<PortfolioState> {
+  const res = await runEngine('portfolio', data);
+  if (!res.success) throw new Error(res.error ?? 'Portfolio command failed');
+  return res.data as PortfolioState;
+}
+
+/** Paper order book: place/cancel/modify/list orders and `process` candles or ticks into fills. */
+export async function engineOrders(data: {
+  command: 'place' | 'cancel' | 'modify' | 'list' | 'process' | 'reset';
+  state_file?: string;
+  portfolio_state_file?: string;
+  [key: string]: unknown;
+}): Promise<unknown> {
+  const res = await runEngine('orders', data);
+  if (!res.success) throw new Error(res.error ?? 'Orders command failed');
+  return res.data;
+}
+
 export async function engineGreeks(data: unknown): Promise<unknown> {
   const res = await runEngine('greeks', data);
   if (!res.success) throw new Error(res.error ?? 'Greeks computation failed');
@@ -732,6 +830,35 @@ export async function engineMultiTimeframeScan(data: unknown): Promise<unknown>
   return res.data;
 }
 
+// ── Cached Datasets (daemon only) ──
+// Candles loaded here stay in the daemon; later requests pass `dataset_id`
+// instead of `candles`. Single-shot runs have no cache, so these always use
+// the daemon.
+
+export interface DatasetInfo {
+  dataset_id: string;
+  candles: number;
+  first_timestamp: string | null;
+  last_timestamp: string | null;
+  est_mb: number;
+  loaded_at: string;
+}
+
+export async function engineLoadDataset(data: {
+  dataset_id: string;
+  candles: Array<{ timestamp: string; open: number; high: number; low: number; close: number; volume: number }>;
+}): Promise<DatasetInfo & { replaced: boolean; repaired: number }> {
+  const res = await sendToDaemon('load_dataset', data);
+  if (!res.success) throw new Error(res.error ?? 'Load dataset failed');
+  return res.data as DatasetInfo & { replaced: boolean; repaired: number };
+}
+
+export async function engineDropDataset(datasetId: string): Promise<{ dataset_id: string; dropped: boolean }> {
+  const res = await sendToDaemon('drop_dataset', { dataset_id: datasetId });
+  if (!res.success) throw new Error(res.error ?? 'Drop dataset failed');
+  return res.data as { dataset_id: string; dropped: boolean };
+}
+
 // ── ML Scorer ──
 
 export interface MLScoreResult {